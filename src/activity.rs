@@ -0,0 +1,188 @@
+//! Grouping of records by their `Correlation` activity IDs.
+//!
+//! Records sharing an `ActivityID` belong to the same activity, and a `RelatedActivityID` links
+//! an activity to the one which caused it. Following both yields clusters of causally related
+//! records (for example, all the records of an RDP session).
+use crate::SerializedEvtxRecord;
+
+use std::collections::HashMap;
+
+/// A cluster of records, linked by their `ActivityID` and `RelatedActivityID`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivityGroup<T> {
+    /// The activity IDs of the records (normalized, see `normalize_activity_id`), in order of appearance.
+    pub activity_ids: Vec<String>,
+    /// The records of the group, in the order they were parsed.
+    pub records: Vec<SerializedEvtxRecord<T>>,
+}
+
+/// Groups records by activity, see `EvtxParser::activity_groups`.
+#[derive(Debug)]
+pub(crate) struct ActivityGrouper<T> {
+    /// Index of every activity ID seen so far (in `parents`).
+    ids: HashMap<String, usize>,
+    /// Activity IDs, in order of appearance.
+    names: Vec<String>,
+    /// Union-find parent of every activity ID.
+    parents: Vec<usize>,
+    /// Records, with the index of their activity ID.
+    records: Vec<(usize, SerializedEvtxRecord<T>)>,
+}
+
+impl<T> ActivityGrouper<T> {
+    pub(crate) fn new() -> Self {
+        ActivityGrouper {
+            ids: HashMap::new(),
+            names: vec![],
+            parents: vec![],
+            records: vec![],
+        }
+    }
+
+    /// Adds a record with the given (normalized) activity IDs.
+    pub(crate) fn add(
+        &mut self,
+        activity_id: String,
+        related_activity_id: Option<String>,
+        record: SerializedEvtxRecord<T>,
+    ) {
+        let index = self.index(activity_id);
+
+        if let Some(related) = related_activity_id {
+            let related = self.index(related);
+            self.union(index, related);
+        }
+
+        self.records.push((index, record));
+    }
+
+    /// Returns the groups, ordered by their first record.
+    pub(crate) fn into_groups(mut self) -> Vec<ActivityGroup<T>> {
+        let mut groups: Vec<ActivityGroup<T>> = vec![];
+        // Index of the group (in `groups`) of every root.
+        let mut group_of_root = HashMap::new();
+
+        for index in 0..self.names.len() {
+            let root = self.find(index);
+
+            let group = *group_of_root.entry(root).or_insert_with(|| {
+                groups.push(ActivityGroup {
+                    activity_ids: vec![],
+                    records: vec![],
+                });
+                groups.len() - 1
+            });
+
+            groups[group].activity_ids.push(self.names[index].clone());
+        }
+
+        for (index, record) in std::mem::take(&mut self.records) {
+            let root = self.find(index);
+            groups[group_of_root[&root]].records.push(record);
+        }
+
+        // A related activity might only have been seen in a `RelatedActivityID`.
+        groups.retain(|group| !group.records.is_empty());
+
+        groups
+    }
+
+    fn index(&mut self, activity_id: String) -> usize {
+        if let Some(index) = self.ids.get(&activity_id) {
+            return *index;
+        }
+
+        let index = self.parents.len();
+        self.parents.push(index);
+        self.names.push(activity_id.clone());
+        self.ids.insert(activity_id, index);
+
+        index
+    }
+
+    fn find(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+
+        index
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+
+        // Keep the activity seen first as the root, so groups are ordered by appearance.
+        if a < b {
+            self.parents[b] = a;
+        } else {
+            self.parents[a] = b;
+        }
+    }
+}
+
+/// Activity IDs are GUIDs, which appear both with and without braces.
+pub(crate) fn normalize_activity_id(activity_id: &str) -> String {
+    activity_id
+        .trim()
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .to_uppercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn record(event_record_id: u64) -> SerializedEvtxRecord<()> {
+        SerializedEvtxRecord {
+            event_record_id,
+            timestamp: Utc.ymd(2020, 1, 1).and_hms(0, 0, 0),
+            data: (),
+            recovered: false,
+            errors: vec![],
+        }
+    }
+
+    #[test]
+    fn test_groups_related_activities() {
+        let mut grouper = ActivityGrouper::new();
+
+        grouper.add("A".to_string(), None, record(1));
+        grouper.add("B".to_string(), None, record(2));
+        grouper.add("C".to_string(), Some("A".to_string()), record(3));
+        grouper.add("D".to_string(), Some("E".to_string()), record(4));
+        grouper.add("B".to_string(), None, record(5));
+        grouper.add("A".to_string(), Some("D".to_string()), record(6));
+
+        let groups: Vec<(Vec<String>, Vec<u64>)> = grouper
+            .into_groups()
+            .into_iter()
+            .map(|group| {
+                (
+                    group.activity_ids,
+                    group.records.iter().map(|r| r.event_record_id).collect(),
+                )
+            })
+            .collect();
+
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            groups,
+            vec![
+                (ids(&["A", "C", "D", "E"]), vec![1, 3, 4, 6]),
+                (ids(&["B"]), vec![2, 5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_activity_id() {
+        assert_eq!(
+            normalize_activity_id("{f42004da-7659-4b1e-b263-be994ec80000}"),
+            "F42004DA-7659-4B1E-B263-BE994EC80000"
+        );
+    }
+}
@@ -0,0 +1,95 @@
+//! Counting records by their `EventID`, provider or hour, without serializing them.
+use crate::EvtxRecord;
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// What records are grouped by when counting them, see `EvtxParser::aggregate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    EventId,
+    /// The `Provider` name.
+    Provider,
+    /// The hour of the `TimeCreated` of the record.
+    Hour,
+}
+
+/// The key of a group of records. Records missing the grouped value are counted under `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum GroupKey {
+    EventId(Option<u16>),
+    Provider(Option<String>),
+    /// The start of the hour.
+    Hour(DateTime<Utc>),
+}
+
+impl GroupKey {
+    pub(crate) fn of(record: &EvtxRecord<'_>, group_by: GroupBy) -> Self {
+        match group_by {
+            GroupBy::EventId => GroupKey::EventId(record.event_id()),
+            GroupBy::Provider => {
+                GroupKey::Provider(record.provider_name().map(|name| name.into_owned()))
+            }
+            GroupBy::Hour => {
+                let seconds = record.timestamp.timestamp();
+                GroupKey::Hour(Utc.timestamp(seconds - seconds.rem_euclid(3600), 0))
+            }
+        }
+    }
+}
+
+impl fmt::Display for GroupKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GroupKey::EventId(Some(event_id)) => write!(f, "{}", event_id),
+            GroupKey::Provider(Some(provider)) => write!(f, "{}", provider),
+            GroupKey::EventId(None) | GroupKey::Provider(None) => write!(f, "-"),
+            GroupKey::Hour(hour) => write!(f, "{}", hour.to_rfc3339()),
+        }
+    }
+}
+
+/// The number of records in every group.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub group_by: GroupBy,
+    /// Number of records of every group, ordered by key.
+    pub counts: BTreeMap<GroupKey, u64>,
+    /// Number of records counted.
+    pub total: u64,
+    /// Number of records which failed to parse (and were not counted).
+    pub errors: u64,
+}
+
+impl Histogram {
+    pub(crate) fn new(group_by: GroupBy) -> Self {
+        Histogram {
+            group_by,
+            counts: BTreeMap::new(),
+            total: 0,
+            errors: 0,
+        }
+    }
+
+    pub(crate) fn add(&mut self, key: GroupKey) {
+        *self.counts.entry(key).or_insert(0) += 1;
+        self.total += 1;
+    }
+
+    /// Returns the groups ordered by their number of records (largest first), then by key.
+    pub fn most_common(&self) -> Vec<(&GroupKey, u64)> {
+        let mut groups: Vec<(&GroupKey, u64)> = self
+            .counts
+            .iter()
+            .map(|(key, count)| (key, *count))
+            .collect();
+
+        groups.sort_by(|(a_key, a_count), (b_key, b_count)| {
+            b_count.cmp(a_count).then(a_key.cmp(b_key))
+        });
+
+        groups
+    }
+}
@@ -0,0 +1,276 @@
+//! Stacking analysis of the records of a file, see `EvtxParser::analyze`.
+//!
+//! Records are counted by provider, `EventID` and hour, and the values of selected `EventData` fields
+//! are stacked, so that the rarest values (such as the least common `CommandLine`s) stand out.
+//! Every group keeps the timestamps of it's first and last record.
+use crate::aggregate::{GroupBy, GroupKey};
+use crate::chunk_table::escape_csv;
+use crate::err::Result;
+use crate::EvtxRecord;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+/// The number and time range of the records of a group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupStats {
+    pub count: u64,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl GroupStats {
+    fn new(timestamp: DateTime<Utc>) -> Self {
+        GroupStats {
+            count: 1,
+            first_seen: timestamp,
+            last_seen: timestamp,
+        }
+    }
+
+    fn add(&mut self, timestamp: DateTime<Utc>) {
+        self.count += 1;
+        self.first_seen = self.first_seen.min(timestamp);
+        self.last_seen = self.last_seen.max(timestamp);
+    }
+
+    fn to_json_value(self) -> Value {
+        json!({
+            "count": self.count,
+            "first_seen": self.first_seen.to_rfc3339(),
+            "last_seen": self.last_seen.to_rfc3339(),
+        })
+    }
+}
+
+/// The values of an `EventData` field, with the records they appear in.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldStack {
+    pub values: HashMap<String, GroupStats>,
+}
+
+impl FieldStack {
+    /// Returns the `n` most common values (ties are ordered by value).
+    pub fn most_common(&self, n: usize) -> Vec<(&str, &GroupStats)> {
+        let mut values = self.entries();
+        values
+            .sort_by(|(a, a_stats), (b, b_stats)| b_stats.count.cmp(&a_stats.count).then(a.cmp(b)));
+        values.truncate(n);
+        values
+    }
+
+    /// Returns the `n` least common values (ties are ordered by value).
+    pub fn rarest(&self, n: usize) -> Vec<(&str, &GroupStats)> {
+        let mut values = self.entries();
+        values
+            .sort_by(|(a, a_stats), (b, b_stats)| a_stats.count.cmp(&b_stats.count).then(a.cmp(b)));
+        values.truncate(n);
+        values
+    }
+
+    fn entries(&self) -> Vec<(&str, &GroupStats)> {
+        self.values
+            .iter()
+            .map(|(value, stats)| (value.as_str(), stats))
+            .collect()
+    }
+}
+
+/// What is read from every record, see `EvtxParser::analyze`.
+#[derive(Debug, Clone)]
+pub(crate) struct RecordSummary {
+    timestamp: DateTime<Utc>,
+    provider: GroupKey,
+    event_id: GroupKey,
+    hour: GroupKey,
+    /// The values of the stacked fields present in the record.
+    fields: Vec<(String, String)>,
+}
+
+impl RecordSummary {
+    pub(crate) fn of(record: &EvtxRecord<'_>, stacked_fields: &[String]) -> Self {
+        let fields = if stacked_fields.is_empty() {
+            vec![]
+        } else {
+            let data = record.event_data().unwrap_or_default();
+            stacked_fields
+                .iter()
+                .filter_map(|name| {
+                    data.get(name)
+                        .map(|value| (name.clone(), value.as_cow_str().into_owned()))
+                })
+                .collect()
+        };
+
+        RecordSummary {
+            timestamp: record.timestamp,
+            provider: GroupKey::of(record, GroupBy::Provider),
+            event_id: GroupKey::of(record, GroupBy::EventId),
+            hour: GroupKey::of(record, GroupBy::Hour),
+            fields,
+        }
+    }
+}
+
+/// Counts of the records of a file, with the most common and rarest values of the stacked fields.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisReport {
+    /// Number of records analyzed.
+    pub records: u64,
+    /// Number of records which failed to parse (and were not analyzed).
+    pub errors: u64,
+    pub first_seen: Option<DateTime<Utc>>,
+    pub last_seen: Option<DateTime<Utc>>,
+    pub by_provider: BTreeMap<GroupKey, GroupStats>,
+    pub by_event_id: BTreeMap<GroupKey, GroupStats>,
+    pub by_hour: BTreeMap<GroupKey, GroupStats>,
+    /// The stacked `EventData` fields, by name.
+    pub fields: BTreeMap<String, FieldStack>,
+    /// The number of most common and rarest values of the fields included in the reports.
+    pub top: usize,
+}
+
+impl AnalysisReport {
+    pub(crate) fn new(stacked_fields: &[String], top: usize) -> Self {
+        AnalysisReport {
+            records: 0,
+            errors: 0,
+            first_seen: None,
+            last_seen: None,
+            by_provider: BTreeMap::new(),
+            by_event_id: BTreeMap::new(),
+            by_hour: BTreeMap::new(),
+            fields: stacked_fields
+                .iter()
+                .map(|name| (name.clone(), FieldStack::default()))
+                .collect(),
+            top,
+        }
+    }
+
+    pub(crate) fn add(&mut self, record: RecordSummary) {
+        let timestamp = record.timestamp;
+
+        self.records += 1;
+        self.first_seen = Some(
+            self.first_seen
+                .map_or(timestamp, |first| first.min(timestamp)),
+        );
+        self.last_seen = Some(self.last_seen.map_or(timestamp, |last| last.max(timestamp)));
+
+        for (groups, key) in [
+            (&mut self.by_provider, record.provider),
+            (&mut self.by_event_id, record.event_id),
+            (&mut self.by_hour, record.hour),
+        ] {
+            groups
+                .entry(key)
+                .and_modify(|stats| stats.add(timestamp))
+                .or_insert_with(|| GroupStats::new(timestamp));
+        }
+
+        for (name, value) in record.fields {
+            if let Some(stack) = self.fields.get_mut(&name) {
+                stack
+                    .values
+                    .entry(value)
+                    .and_modify(|stats| stats.add(timestamp))
+                    .or_insert_with(|| GroupStats::new(timestamp));
+            }
+        }
+    }
+
+    pub fn to_json_value(&self) -> Value {
+        let groups = |groups: &BTreeMap<GroupKey, GroupStats>| -> Value {
+            groups
+                .iter()
+                .map(|(key, stats)| {
+                    let mut group = stats.to_json_value();
+                    group["key"] = key.to_string().into();
+                    group
+                })
+                .collect()
+        };
+
+        let values = |values: Vec<(&str, &GroupStats)>| -> Value {
+            values
+                .into_iter()
+                .map(|(value, stats)| {
+                    let mut group = stats.to_json_value();
+                    group["value"] = value.into();
+                    group
+                })
+                .collect()
+        };
+
+        let fields: Map<String, Value> = self
+            .fields
+            .iter()
+            .map(|(name, stack)| {
+                let field = json!({
+                    "distinct_values": stack.values.len(),
+                    "most_common": values(stack.most_common(self.top)),
+                    "rarest": values(stack.rarest(self.top)),
+                });
+                (name.clone(), field)
+            })
+            .collect();
+
+        json!({
+            "records": self.records,
+            "errors": self.errors,
+            "first_seen": self.first_seen.map(|time| time.to_rfc3339()),
+            "last_seen": self.last_seen.map(|time| time.to_rfc3339()),
+            "by_provider": groups(&self.by_provider),
+            "by_event_id": groups(&self.by_event_id),
+            "by_hour": groups(&self.by_hour),
+            "fields": fields,
+        })
+    }
+
+    /// Writes the report as CSV, with a line for every group (and for the most common and rarest values
+    /// of every field): `category,key,count,first_seen,last_seen`.
+    ///
+    /// Categories are `provider`, `event_id`, `hour`, `most_common:<field>` and `rarest:<field>`.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> Result<()> {
+        writeln!(writer, "category,key,count,first_seen,last_seen")?;
+
+        let mut rows: Vec<(String, String, &GroupStats)> = vec![];
+
+        for (category, groups) in &[
+            ("provider", &self.by_provider),
+            ("event_id", &self.by_event_id),
+            ("hour", &self.by_hour),
+        ] {
+            for (key, stats) in groups.iter() {
+                rows.push((category.to_string(), key.to_string(), stats));
+            }
+        }
+
+        for (name, stack) in &self.fields {
+            for (value, stats) in stack.most_common(self.top) {
+                rows.push((format!("most_common:{}", name), value.to_string(), stats));
+            }
+            for (value, stats) in stack.rarest(self.top) {
+                rows.push((format!("rarest:{}", name), value.to_string(), stats));
+            }
+        }
+
+        for (category, key, stats) in rows {
+            writeln!(
+                writer,
+                "{},{},{},{},{}",
+                escape_csv(&category),
+                escape_csv(&key),
+                stats.count,
+                stats.first_seen.to_rfc3339(),
+                stats.last_seen.to_rfc3339()
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use crate::err::{self, Result};
+use crate::evtx_parser::{
+    chunk_from_data, serialize_chunk, Sampler, EVTX_CHUNK_SIZE, EVTX_FILE_HEADER_SIZE,
+};
+use crate::{EvtxChunkData, EvtxFileHeader, EvtxRecord, ParserSettings, SerializedEvtxRecord};
+
+use futures::stream::{self, Stream};
+use log::debug;
+use std::collections::VecDeque;
+use std::io::{Cursor, SeekFrom};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+/// An evtx parser reading from an asynchronous source (for example a `tokio::fs::File`,
+/// or an object stored on the network).
+///
+/// Only reading is asynchronous: chunks are read one at a time, and their records are parsed
+/// on the task polling the stream. Wrap the stream in a `spawn_blocking` task if the records are
+/// expensive to render and the runtime should not be held up by them.
+///
+/// ```rust,no_run
+/// # async fn run() -> evtx::err::Result<()> {
+/// use evtx::AsyncEvtxParser;
+/// use futures::StreamExt;
+///
+/// let file = tokio::fs::File::open("security.evtx").await?;
+/// let mut parser = AsyncEvtxParser::from_async_read(file).await?;
+///
+/// let mut records = Box::pin(parser.records());
+/// while let Some(record) = records.next().await {
+///     println!("{}", record?.data);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncEvtxParser<R: AsyncRead + AsyncSeek + Unpin> {
+    data: R,
+    header: EvtxFileHeader,
+    config: Arc<ParserSettings>,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncEvtxParser<R> {
+    /// Reads the evtx file header from `data`, will fail if the header is invalid.
+    pub async fn from_async_read(mut data: R) -> Result<Self> {
+        let mut header_data = vec![0; EVTX_FILE_HEADER_SIZE];
+
+        data.seek(SeekFrom::Start(0)).await?;
+        data.read_exact(&mut header_data).await?;
+
+        let header = EvtxFileHeader::from_stream(&mut Cursor::new(header_data))?;
+
+        debug!("EVTX Header: {:#?}", header);
+        Ok(AsyncEvtxParser {
+            data,
+            header,
+            config: Arc::new(ParserSettings::default()),
+        })
+    }
+
+    pub fn with_configuration(mut self, configuration: ParserSettings) -> Self {
+        self.config = Arc::new(configuration);
+        self
+    }
+
+    pub fn header(&self) -> &EvtxFileHeader {
+        &self.header
+    }
+
+    /// Reads the chunk at `chunk_number`, see `EvtxParser::allocate_chunk`.
+    async fn allocate_chunk(&mut self, chunk_number: u16) -> Result<Option<EvtxChunkData>> {
+        let mut chunk_data = Vec::with_capacity(EVTX_CHUNK_SIZE);
+        let chunk_offset =
+            EVTX_FILE_HEADER_SIZE as u64 + chunk_number as u64 * EVTX_CHUNK_SIZE as u64;
+
+        self.data.seek(SeekFrom::Start(chunk_offset)).await?;
+
+        (&mut self.data)
+            .take(EVTX_CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk_data)
+            .await?;
+
+        chunk_from_data(
+            chunk_data,
+            chunk_number,
+            self.config.should_validate_checksums(),
+            self.config.should_repair_chunk_headers(),
+        )
+    }
+
+    /// Find the next chunk, staring at `chunk_number` (inclusive).
+    /// Same as `EvtxParser::find_next_chunk`.
+    pub async fn find_next_chunk(
+        &mut self,
+        mut chunk_number: u16,
+    ) -> Option<(Result<EvtxChunkData>, u16)> {
+        loop {
+            match self.allocate_chunk(chunk_number).await {
+                Err(err) => {
+                    // We try to read past the `chunk_count` to allow for dirty files.
+                    // But if we failed, it means we really are at the end of the file.
+                    // A truncated file ends before `chunk_count`.
+                    if chunk_number >= self.header.chunk_count
+                        || matches!(err, err::Error::IncompleteChunk { .. })
+                    {
+                        return None;
+                    } else {
+                        return Some((Err(err), chunk_number));
+                    }
+                }
+                Ok(None) => {
+                    chunk_number = chunk_number.checked_add(1)?;
+                }
+                Ok(Some(chunk)) => {
+                    if let Some((earliest, latest)) = chunk.timestamp_bounds() {
+                        if !self.config.matches_time_bounds(&earliest, &latest) {
+                            debug!("Chunk {} is outside of the time range", chunk_number);
+                            chunk_number = chunk_number.checked_add(1)?;
+                            continue;
+                        }
+                    }
+
+                    return Some((Ok(chunk), chunk_number));
+                }
+            }
+        }
+    }
+
+    /// Return a stream over all the records.
+    /// Records will be mapped `f`, which must produce owned data from the records.
+    pub fn serialized_records<'a, U: 'a>(
+        &'a mut self,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + 'a,
+    ) -> impl Stream<Item = Result<U>> + 'a {
+        let settings = Arc::clone(&self.config);
+        let sampler = Sampler::new(&settings);
+        let records = VecDeque::new();
+
+        stream::unfold(
+            (self, f, sampler, records, Some(0)),
+            move |(parser, mut f, mut sampler, mut records, mut next_chunk_number)| {
+                let settings = Arc::clone(&settings);
+
+                async move {
+                    loop {
+                        if let Some(record) = records.pop_front() {
+                            return Some((
+                                record,
+                                (parser, f, sampler, records, next_chunk_number),
+                            ));
+                        }
+
+                        let (chunk, chunk_number) =
+                            parser.find_next_chunk(next_chunk_number?).await?;
+                        next_chunk_number = chunk_number.checked_add(1);
+
+                        serialize_chunk(chunk, Arc::clone(&settings), &mut f, |record| {
+                            if sampler.keep(&record) {
+                                records.push_back(record);
+                            }
+                            true
+                        });
+                    }
+                }
+            },
+        )
+    }
+
+    /// Return a stream over all the records.
+    /// Records will be XML-formatted.
+    pub fn records(&mut self) -> impl Stream<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_records(|record| record.and_then(|record| record.into_xml()))
+    }
+
+    /// Return a stream over all the records.
+    /// Records will be JSON-formatted.
+    pub fn records_json(
+        &mut self,
+    ) -> impl Stream<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_records(|record| record.and_then(|record| record.into_json()))
+    }
+
+    /// Return a stream over all the records.
+    /// Records will have a `serde_json::Value` data attribute.
+    pub fn records_json_value(
+        &mut self,
+    ) -> impl Stream<Item = Result<SerializedEvtxRecord<serde_json::Value>>> + '_ {
+        self.serialized_records(|record| record.and_then(|record| record.into_json_value()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ensure_env_logger_initialized;
+    use crate::EvtxParser;
+    use futures::StreamExt;
+
+    fn sync_records(path: &str) -> Vec<String> {
+        EvtxParser::from_path(path)
+            .unwrap()
+            .records()
+            .map(|record| match record {
+                Ok(record) => record.data,
+                Err(e) => format!("error: {}", e),
+            })
+            .collect()
+    }
+
+    async fn async_records(path: &str) -> Vec<String> {
+        let file = tokio::fs::File::open(path).await.unwrap();
+        let mut parser = AsyncEvtxParser::from_async_read(file).await.unwrap();
+
+        parser
+            .records()
+            .map(|record| match record {
+                Ok(record) => record.data,
+                Err(e) => format!("error: {}", e),
+            })
+            .collect()
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_async_parser_matches_sync_parser() {
+        ensure_env_logger_initialized();
+
+        for path in &[
+            "./samples/security.evtx",
+            "./samples/E_Windows_system32_winevt_logs_Microsoft-Windows-CAPI2%4Operational.evtx",
+        ] {
+            let expected = sync_records(path);
+            let actual = async_records(path).await;
+
+            assert!(!actual.is_empty());
+            assert_eq!(actual, expected);
+        }
+    }
+}
@@ -3,7 +3,7 @@ extern crate criterion;
 extern crate evtx;
 
 use criterion::Criterion;
-use evtx::EvtxParser;
+use evtx::{EvtxChunkData, EvtxParser, ParserSettings};
 
 // first chunk has 90 records
 fn process_90_records(buffer: &'static [u8]) {
@@ -19,8 +19,10 @@ fn process_90_records(buffer: &'static [u8]) {
     }
 }
 
-fn process_90_records_json(buffer: &'static [u8]) {
-    let mut parser = EvtxParser::from_buffer(buffer.to_vec()).unwrap();
+fn process_90_records_json(buffer: &'static [u8], settings: &ParserSettings) {
+    let mut parser = EvtxParser::from_buffer(buffer.to_vec())
+        .unwrap()
+        .with_configuration(settings.clone());
 
     for (i, record) in parser.records_json().take(90).enumerate() {
         match record {
@@ -32,6 +34,34 @@ fn process_90_records_json(buffer: &'static [u8]) {
     }
 }
 
+// Reads the header of every record, without deserializing them.
+// This is the lower bound for scanning a file.
+fn scan_record_headers(buffer: &'static [u8]) {
+    let mut parser = EvtxParser::from_buffer(buffer.to_vec()).unwrap();
+
+    let count = parser
+        .record_headers()
+        .filter(|header| header.is_ok())
+        .count();
+
+    assert!(count > 0);
+}
+
+// Scans all the records with a filter matching none of them.
+fn scan_with_filter_matching_nothing(buffer: &'static [u8]) {
+    let settings = ParserSettings::new().event_id_filter(&[1]);
+    let mut parser = EvtxParser::from_buffer(buffer.to_vec())
+        .unwrap()
+        .with_configuration(settings);
+
+    assert_eq!(parser.records().count(), 0);
+}
+
+// Validates the header and data checksums of every chunk.
+fn validate_checksums(chunks: &[EvtxChunkData]) {
+    assert!(chunks.iter().all(|chunk| chunk.validate_checksum()));
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let evtx_file = include_bytes!("../../samples/security.evtx");
     // ~11ms before strings cache
@@ -42,7 +72,32 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
 
     c.bench_function("read 90 records json", move |b| {
-        b.iter(|| process_90_records_json(evtx_file))
+        b.iter(|| process_90_records_json(evtx_file, &ParserSettings::new()))
+    });
+
+    // The skeletons are shared by the iterations, as they would be by the chunks of a file.
+    let settings = ParserSettings::new().skeleton_cache(true);
+    c.bench_function("read 90 records json with skeletons", move |b| {
+        b.iter(|| process_90_records_json(evtx_file, &settings))
+    });
+
+    // Records rejected by their `EventID` are not deserialized,
+    // so this should be close to only reading the record headers.
+    c.bench_function("scan record headers", move |b| {
+        b.iter(|| scan_record_headers(evtx_file))
+    });
+
+    c.bench_function("scan with a filter matching nothing", move |b| {
+        b.iter(|| scan_with_filter_matching_nothing(evtx_file))
+    });
+
+    let chunks: Vec<EvtxChunkData> = evtx_file[4096..]
+        .chunks_exact(65536)
+        .filter_map(|data| EvtxChunkData::new(data.to_vec(), false).ok())
+        .collect();
+
+    c.bench_function("validate chunk checksums", move |b| {
+        b.iter(|| validate_checksums(&chunks))
     });
 }
 
@@ -0,0 +1,54 @@
+//! The `browse` subcommand: an interactive terminal browser of the records of a file (see `tui`),
+//! which needs the `tui` feature.
+use crate::is_a_valid_filter_expression;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use indoc::indoc;
+
+#[cfg(feature = "tui")]
+mod tui;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("browse")
+        .about("Browses the records of an EVTX file in an interactive terminal UI")
+        .long_about(indoc!("Browses the records of an EVTX file in an interactive terminal UI (requires the `tui` feature).
+            The records are listed at the top, and the selected record is shown below.
+            Keys: `j`/`k` (or arrows, page up/down, `g`/`G`) move the selection, `J`/`K` scroll the record,
+            `/` searches the records as you type (`n`/`N` go to the next/previous match),
+            `f` edits the filter expression (see `--where`, an empty one showing every record), and `q` quits."))
+        .arg(
+            Arg::with_name("INPUT")
+                .required(true)
+                .help("The EVTX file to browse."),
+        )
+        .arg(
+            Arg::with_name("output-format")
+                .long("--format")
+                .short("-o")
+                .takes_value(true)
+                .possible_values(&["xml", "json"])
+                .default_value("xml")
+                .help("The format the selected record is shown in."),
+        )
+        .arg(
+            Arg::with_name("filter")
+                .long("--where")
+                .takes_value(true)
+                .value_name("EXPR")
+                .validator(is_a_valid_filter_expression)
+                .help("When set, only records matching the filter expression are listed at first (it can be edited with `f`)."),
+        )
+}
+
+/// Runs the `browse` subcommand, until the user quits.
+#[cfg(feature = "tui")]
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    tui::browse(matches)
+}
+
+#[cfg(not(feature = "tui"))]
+pub fn run(_matches: &ArgMatches) -> Result<(), Error> {
+    eprintln!("The interactive browser requires the `tui` feature");
+    std::process::exit(1)
+}
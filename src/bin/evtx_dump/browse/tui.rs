@@ -0,0 +1,507 @@
+//! The interactive terminal browser of the `browse` subcommand, with a scrollable list of records,
+//! a pane showing the selected record, incremental search and filtering.
+use crate::EvtxOutputFormat;
+
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+use evtx::err::Error;
+use evtx::symbolic_names::level_name;
+use evtx::{EvtxParser, ParserSettings};
+use is_terminal::IsTerminal;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use std::collections::HashSet;
+use std::io;
+use std::path::PathBuf;
+
+/// A record of the file, with the fields shown in the list, and it's rendering shown in the detail pane.
+struct Entry {
+    event_record_id: u64,
+    timestamp: DateTime<Utc>,
+    event_id: Option<u16>,
+    level: Option<u8>,
+    provider: String,
+    detail: String,
+}
+
+impl Entry {
+    fn summary(&self) -> String {
+        format!(
+            "{:>8}  {}  {:<11}  {:>5}  {}",
+            self.event_record_id,
+            self.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            self.level
+                .map(|level| level_name(level).map_or_else(|| level.to_string(), str::to_string))
+                .unwrap_or_default(),
+            self.event_id.map(|id| id.to_string()).unwrap_or_default(),
+            self.provider
+        )
+    }
+
+    fn contains(&self, query: &str) -> bool {
+        self.detail.to_lowercase().contains(query)
+    }
+}
+
+/// What the keys are currently editing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    /// Editing the search query, `origin` is the selection to go back to if the search is cancelled.
+    Search {
+        origin: usize,
+    },
+    Filter,
+}
+
+struct Browser {
+    path: PathBuf,
+    entries: Vec<Entry>,
+    /// The number of records (and chunks) which could not be parsed.
+    errors: usize,
+    /// Indexes into `entries` of the records matching the filter.
+    visible: Vec<usize>,
+    /// Position in `visible` of the selected record, and of the first record shown in the list.
+    selected: usize,
+    offset: usize,
+    /// The height of the list, as of the last draw.
+    page_size: usize,
+    detail_scroll: u16,
+    mode: Mode,
+    search: String,
+    filter: String,
+    /// The text being edited in the filter mode.
+    input: String,
+    message: Option<String>,
+}
+
+/// Runs the `browse` subcommand, until the user quits.
+pub fn browse(matches: &ArgMatches) -> Result<(), Error> {
+    let path = matches
+        .value_of("INPUT")
+        .expect("This is a required argument");
+    let format = match matches.value_of("output-format") {
+        Some("json") => EvtxOutputFormat::JSON,
+        _ => EvtxOutputFormat::XML,
+    };
+
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        eprintln!("The browser is interactive, and must be run in a terminal");
+        std::process::exit(1);
+    }
+
+    let mut browser = Browser::load(PathBuf::from(path), format)?;
+    if let Some(expr) = matches.value_of("filter") {
+        browser.apply_filter(expr);
+    }
+
+    let mut terminal = ratatui::try_init()?;
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+
+    Ok(result?)
+}
+
+impl Browser {
+    fn new(path: PathBuf, entries: Vec<Entry>, errors: usize) -> Self {
+        Browser {
+            path,
+            visible: (0..entries.len()).collect(),
+            entries,
+            errors,
+            selected: 0,
+            offset: 0,
+            page_size: 1,
+            detail_scroll: 0,
+            mode: Mode::Browse,
+            search: String::new(),
+            filter: String::new(),
+            input: String::new(),
+            message: None,
+        }
+    }
+
+    fn load(path: PathBuf, format: EvtxOutputFormat) -> Result<Self, Error> {
+        let mut parser = EvtxParser::from_path(&path)?;
+
+        let records = parser.serialized_records(move |record| {
+            record.and_then(|record| {
+                let event_id = record.event_id();
+                let level = record.level();
+                let provider = record
+                    .provider_name()
+                    .map(|name| name.into_owned())
+                    .unwrap_or_default();
+
+                let record = match format {
+                    EvtxOutputFormat::JSON => record.into_json()?,
+                    EvtxOutputFormat::XML => record.into_xml()?,
+                };
+
+                Ok(Entry {
+                    event_record_id: record.event_record_id,
+                    timestamp: record.timestamp,
+                    event_id,
+                    level,
+                    provider,
+                    detail: record.data,
+                })
+            })
+        });
+
+        let mut entries = vec![];
+        let mut errors = 0;
+        for record in records {
+            match record {
+                Ok(entry) => entries.push(entry),
+                Err(_) => errors += 1,
+            }
+        }
+
+        Ok(Browser::new(path, entries, errors))
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn selected_entry(&self) -> Option<&Entry> {
+        self.visible.get(self.selected).map(|&i| &self.entries[i])
+    }
+
+    fn select(&mut self, position: usize) {
+        self.selected = position.min(self.visible.len().saturating_sub(1));
+        self.detail_scroll = 0;
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        let position = if delta < 0 {
+            self.selected.saturating_sub(delta.unsigned_abs())
+        } else {
+            self.selected.saturating_add(delta as usize)
+        };
+
+        self.select(position);
+    }
+
+    /// Finds the next record (starting with `from`, wrapping around) containing the search query,
+    /// backwards if `forward` is false.
+    fn find(&self, from: usize, forward: bool) -> Option<usize> {
+        let query = self.search.to_lowercase();
+        let count = self.visible.len();
+        if query.is_empty() || count == 0 {
+            return None;
+        }
+
+        (0..count)
+            .map(|step| {
+                if forward {
+                    (from + step) % count
+                } else {
+                    (from + count - step % count) % count
+                }
+            })
+            .find(|&position| self.entries[self.visible[position]].contains(&query))
+    }
+
+    fn search_next(&mut self, forward: bool) {
+        let from = if forward {
+            self.selected + 1
+        } else {
+            self.selected + self.visible.len().saturating_sub(1)
+        };
+
+        match self.find(from % self.visible.len().max(1), forward) {
+            Some(position) => self.select(position),
+            None => self.message = Some(format!("Not found: {}", self.search)),
+        }
+    }
+
+    /// Applies a filter expression (see `RecordFilter::parse`), an empty expression showing every record.
+    /// The selection is kept on the same record if it's still shown.
+    fn apply_filter(&mut self, expr: &str) {
+        let expr = expr.trim();
+        let ids = if expr.is_empty() {
+            None
+        } else {
+            match self.matching_records(expr) {
+                Ok(ids) => Some(ids),
+                Err(e) => {
+                    self.message = Some(e.to_string());
+                    return;
+                }
+            }
+        };
+
+        let selected = self.visible.get(self.selected).copied();
+        self.visible = (0..self.entries.len())
+            .filter(|&i| {
+                ids.as_ref()
+                    .map_or(true, |ids| ids.contains(&self.entries[i].event_record_id))
+            })
+            .collect();
+        self.filter = expr.to_string();
+
+        let position =
+            selected.and_then(|selected| self.visible.iter().position(|&i| i == selected));
+        self.select(position.unwrap_or(0));
+    }
+
+    /// The IDs of the records matching the filter expression, by parsing the file again.
+    fn matching_records(&self, expr: &str) -> Result<HashSet<u64>, Error> {
+        let settings = ParserSettings::default().filter_expr(expr)?;
+        let mut parser = EvtxParser::from_path(&self.path)?.with_configuration(settings);
+
+        let ids = parser
+            .serialized_records(|record| record.map(|record| record.event_record_id))
+            .filter_map(Result::ok)
+            .collect();
+
+        Ok(ids)
+    }
+
+    /// Handles a key press, returning `false` when the browser should quit.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        self.message = None;
+
+        match self.mode {
+            Mode::Browse => return self.handle_browse_key(key),
+            Mode::Search { origin } => match key.code {
+                KeyCode::Enter => self.mode = Mode::Browse,
+                KeyCode::Esc => {
+                    self.select(origin);
+                    self.mode = Mode::Browse;
+                }
+                KeyCode::Backspace => {
+                    self.search.pop();
+                    self.search_from(origin);
+                }
+                KeyCode::Char(c) => {
+                    self.search.push(c);
+                    self.search_from(origin);
+                }
+                _ => {}
+            },
+            Mode::Filter => match key.code {
+                KeyCode::Enter => {
+                    let expr = std::mem::take(&mut self.input);
+                    self.mode = Mode::Browse;
+                    self.apply_filter(&expr);
+                }
+                KeyCode::Esc => self.mode = Mode::Browse,
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Char(c) => self.input.push(c),
+                _ => {}
+            },
+        }
+
+        true
+    }
+
+    /// Moves the selection to the first match of the search query being typed, from where the search started.
+    fn search_from(&mut self, origin: usize) {
+        match self.find(origin, true) {
+            Some(position) => self.select(position),
+            None if self.search.is_empty() => self.select(origin),
+            None => self.message = Some(format!("Not found: {}", self.search)),
+        }
+    }
+
+    fn handle_browse_key(&mut self, key: KeyEvent) -> bool {
+        let page = self.page_size.max(1) as isize;
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown => self.move_by(page),
+            KeyCode::PageUp => self.move_by(-page),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(self.visible.len()),
+            KeyCode::Char('J') => self.detail_scroll = self.detail_scroll.saturating_add(1),
+            KeyCode::Char('K') => self.detail_scroll = self.detail_scroll.saturating_sub(1),
+            KeyCode::Char('/') => {
+                self.search.clear();
+                self.mode = Mode::Search {
+                    origin: self.selected,
+                };
+            }
+            KeyCode::Char('n') => self.search_next(true),
+            KeyCode::Char('N') => self.search_next(false),
+            KeyCode::Char('f') => {
+                self.input = self.filter.clone();
+                self.mode = Mode::Filter;
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list_area, detail_area, status_area] = Layout::vertical([
+            Constraint::Percentage(40),
+            Constraint::Fill(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        // Only the rows which fit in the list are rendered, keeping the selection in view.
+        self.page_size = list_area.height.saturating_sub(2).max(1) as usize;
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + self.page_size {
+            self.offset = self.selected + 1 - self.page_size;
+        }
+
+        let items: Vec<ListItem> = self
+            .visible
+            .iter()
+            .skip(self.offset)
+            .take(self.page_size)
+            .map(|&i| ListItem::new(self.entries[i].summary()))
+            .collect();
+
+        let mut title = format!(
+            " {} - {}/{} records ",
+            self.path.display(),
+            self.visible.len(),
+            self.entries.len()
+        );
+        if !self.filter.is_empty() {
+            title.push_str(&format!("matching `{}` ", self.filter));
+        }
+        if self.errors > 0 {
+            title.push_str(&format!("({} unreadable) ", self.errors));
+        }
+
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default();
+        if !self.visible.is_empty() {
+            state.select(Some(self.selected - self.offset));
+        }
+        frame.render_stateful_widget(list, list_area, &mut state);
+
+        let (detail_title, detail) = match self.selected_entry() {
+            Some(entry) => (
+                format!(" Record {} ", entry.event_record_id),
+                entry.detail.as_str(),
+            ),
+            None => (" No record ".to_string(), ""),
+        };
+        let detail = Paragraph::new(detail)
+            .block(Block::bordered().title(detail_title))
+            .wrap(Wrap { trim: false })
+            .scroll((self.detail_scroll, 0));
+        frame.render_widget(detail, detail_area);
+
+        let status = match (self.mode, &self.message) {
+            (Mode::Search { .. }, _) => Line::from(format!("/{}", self.search)),
+            (Mode::Filter, _) => Line::from(format!("Filter: {}", self.input)),
+            (Mode::Browse, Some(message)) => Line::from(message.as_str()),
+            (Mode::Browse, None) => Line::from(
+                "q quit  j/k move  J/K scroll record  / search  n/N next/previous match  f filter",
+            )
+            .style(Style::default().add_modifier(Modifier::DIM)),
+        };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn type_keys(browser: &mut Browser, keys: &str) {
+        for c in keys.chars() {
+            assert!(browser.handle_key(key(KeyCode::Char(c))));
+        }
+    }
+
+    fn sample() -> Browser {
+        Browser::load(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("samples/security.evtx"),
+            EvtxOutputFormat::XML,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_it_searches_incrementally() {
+        let mut browser = sample();
+        assert_eq!(browser.visible.len(), 2261);
+        browser.select(10);
+
+        type_keys(&mut browser, "/<eventid>4624");
+        let found = browser.selected;
+        assert!(found >= 10);
+        assert_eq!(browser.selected_entry().unwrap().event_id, Some(4624));
+
+        // Confirming keeps the match, and `n` moves to the next one.
+        assert!(browser.handle_key(key(KeyCode::Enter)));
+        assert_eq!(browser.mode, Mode::Browse);
+        type_keys(&mut browser, "n");
+        assert_ne!(browser.selected, found);
+        assert_eq!(browser.selected_entry().unwrap().event_id, Some(4624));
+        type_keys(&mut browser, "N");
+        assert_eq!(browser.selected, found);
+
+        // Cancelling goes back to where the search started.
+        type_keys(&mut browser, "/no such text");
+        assert!(browser.message.is_some());
+        assert!(browser.handle_key(key(KeyCode::Esc)));
+        assert_eq!(browser.selected, found);
+
+        assert!(!browser.handle_key(key(KeyCode::Char('q'))));
+    }
+
+    #[test]
+    fn test_it_edits_the_filter() {
+        let mut browser = sample();
+
+        type_keys(&mut browser, "fEventID == 4624");
+        assert!(browser.handle_key(key(KeyCode::Enter)));
+        assert_eq!(browser.filter, "EventID == 4624");
+        assert!(!browser.visible.is_empty() && browser.visible.len() < 2261);
+        assert!(browser
+            .visible
+            .iter()
+            .all(|&i| browser.entries[i].event_id == Some(4624)));
+
+        // An invalid expression keeps the current filter.
+        type_keys(&mut browser, "f &&");
+        assert!(browser.handle_key(key(KeyCode::Enter)));
+        assert!(browser.message.is_some());
+        assert_eq!(browser.filter, "EventID == 4624");
+
+        // Clearing the expression shows every record again.
+        type_keys(&mut browser, "f");
+        for _ in 0.."EventID == 4624".len() {
+            browser.handle_key(key(KeyCode::Backspace));
+        }
+        assert!(browser.handle_key(key(KeyCode::Enter)));
+        assert_eq!(browser.visible.len(), 2261);
+    }
+}
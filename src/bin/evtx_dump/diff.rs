@@ -0,0 +1,57 @@
+//! The `diff` subcommand: compares the records of two files.
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use evtx::EvtxParser;
+use indoc::indoc;
+use std::process::exit;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("diff")
+        .about("Compares the records of two EVTX files, listing the records added, removed and modified")
+        .long_about(indoc!("Compares the records of two EVTX files, listing the records added, removed and modified.
+            Records are matched by their EventRecordID, and compared by a hash of their content (rendered as XML).
+            Exits with 0 if the files have the same records, and 2 if they differ (or some records could not be parsed)."))
+        .arg(
+            Arg::with_name("BEFORE")
+                .required(true)
+                .help("The first file, such as a copy of a log taken earlier."),
+        )
+        .arg(
+            Arg::with_name("AFTER")
+                .required(true)
+                .help("The second file, compared to the first."),
+        )
+        .arg(
+            Arg::with_name("report-format")
+                .long("--format")
+                .short("-o")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("The format of the report, `text` printing a line per record (`+` added, `-` removed, `~` modified) and a summary."),
+        )
+}
+
+/// Runs the `diff` subcommand: compares the records of two files, exiting with 2 if they differ.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let before = matches
+        .value_of("BEFORE")
+        .expect("This is a required argument");
+    let after = matches
+        .value_of("AFTER")
+        .expect("This is a required argument");
+
+    let report = EvtxParser::from_path(before)?.diff(&mut EvtxParser::from_path(after)?);
+
+    if matches.value_of("report-format") == Some("json") {
+        println!("{:#}", report.to_json_value());
+    } else {
+        println!("{}", report);
+    }
+
+    if !report.is_identical() {
+        exit(2);
+    }
+
+    Ok(())
+}
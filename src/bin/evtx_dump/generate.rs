@@ -0,0 +1,116 @@
+//! The `generate` subcommand: writes a synthetic EVTX file, optionally with corruption, for testing.
+use crate::{exit_on_output_error, is_a_non_negative_number, EvtxDump};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use evtx::{Corruption, EvtxGenerator};
+use indoc::indoc;
+use std::io::Write;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("generate")
+        .about("Writes a synthetic EVTX file, optionally with corruption, for testing")
+        .arg(
+            Arg::with_name("output-target")
+                .long("--output")
+                .short("-f")
+                .takes_value(true)
+                .required(true)
+                .help("The EVTX file to write, parent directories are created if needed."),
+        )
+        .arg(
+            Arg::with_name("records")
+                .long("--records")
+                .takes_value(true)
+                .value_name("N")
+                .validator(is_a_non_negative_number)
+                .help("The number of records to write (1000 by default)."),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .long("--seed")
+                .takes_value(true)
+                .validator(is_a_non_negative_number)
+                .help("The seed of the generated values, the same seed always generates the same file (0 by default)."),
+        )
+        .arg(
+            Arg::with_name("corrupt")
+                .long("--corrupt")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("KIND[=N]")
+                .validator(is_a_valid_corrupt)
+                .help(indoc!("Injects corruption in N chunks (or records), which are listed in the output.
+                              KIND is one of `header-checksum`, `data-checksum`, `truncated-record` or `truncated-file`.")),
+        )
+        .arg(
+            Arg::with_name("no-confirm-overwrite")
+                .long("--no-confirm-overwrite")
+                .takes_value(false)
+                .help("When set, will not ask for confirmation before overwriting the output file"),
+        )
+}
+
+/// Runs the `generate` subcommand: writes a synthetic EVTX file.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = matches.value_of("output-target").expect("required");
+    let mut generator = EvtxGenerator::new();
+
+    if let Some(records) = matches.value_of("records") {
+        generator = generator.records(records.parse().expect("used validator"));
+    }
+    if let Some(seed) = matches.value_of("seed") {
+        generator = generator.seed(seed.parse().expect("used validator"));
+    }
+    for corrupt in matches.values_of("corrupt").into_iter().flatten() {
+        let (kind, count) = split_corrupt(corrupt).expect("used validator");
+        generator = generator.corrupt(kind, count);
+    }
+
+    let generated = generator.generate()?;
+
+    let mut file = EvtxDump::create_output_file(path, !matches.is_present("no-confirm-overwrite"))
+        .unwrap_or_else(|e| exit_on_output_error(path, e));
+    file.write_all(&generated.data)?;
+
+    for corruption in &generated.corruptions {
+        match corruption.event_record_id {
+            Some(id) => println!(
+                "{:?}\tchunk {}\trecord {}",
+                corruption.kind, corruption.chunk_number, id
+            ),
+            None => println!("{:?}\tchunk {}", corruption.kind, corruption.chunk_number),
+        }
+    }
+
+    eprintln!(
+        "Wrote {} records in {} chunks to {}",
+        generated.records, generated.chunk_count, path
+    );
+    Ok(())
+}
+
+/// Splits a `--corrupt` value into it's kind and count (1 by default).
+fn split_corrupt(value: &str) -> Result<(Corruption, usize), String> {
+    let (kind, count) = value.split_once('=').unwrap_or((value, "1"));
+    let kind = match kind {
+        "header-checksum" => Some(Corruption::BadHeaderChecksum),
+        "data-checksum" => Some(Corruption::BadDataChecksum),
+        "truncated-record" => Some(Corruption::TruncatedRecord),
+        "truncated-file" => Some(Corruption::TruncatedFile),
+        _ => None,
+    };
+
+    match (kind, count.parse()) {
+        (Some(kind), Ok(count)) => Ok((kind, count)),
+        _ => Err(format!(
+            "Expected `header-checksum|data-checksum|truncated-record|truncated-file[=N]`, found `{}`",
+            value
+        )),
+    }
+}
+
+fn is_a_valid_corrupt(value: String) -> Result<(), String> {
+    split_corrupt(&value).map(|_| ())
+}
@@ -0,0 +1,65 @@
+//! The `import` subcommand: writes records rendered as XML or JSON to an EVTX file.
+use crate::{exit_on_output_error, EvtxDump};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use evtx::{EvtxWriter, ImportFormat};
+use std::fs;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("import")
+        .about("Writes records rendered as XML or JSON (by this tool, Event Viewer or wevtutil) to an EVTX file")
+        .arg(Arg::with_name("INPUT").required(true).multiple(true))
+        .arg(
+            Arg::with_name("output-target")
+                .long("--output")
+                .short("-f")
+                .takes_value(true)
+                .required(true)
+                .help("The EVTX file to write, parent directories are created if needed."),
+        )
+        .arg(
+            Arg::with_name("import-format")
+                .long("--format")
+                .takes_value(true)
+                .possible_values(&["xml", "json"])
+                .help("The format of the input files, detected from their content by default."),
+        )
+        .arg(
+            Arg::with_name("no-confirm-overwrite")
+                .long("--no-confirm-overwrite")
+                .takes_value(false)
+                .help("When set, will not ask for confirmation before overwriting the output file"),
+        )
+}
+
+/// Runs the `import` subcommand: writes records rendered as XML or JSON to an EVTX file.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let inputs: Vec<&str> = matches
+        .values_of("INPUT")
+        .expect("This is a required argument")
+        .collect();
+    let path = matches.value_of("output-target").expect("required");
+    let format = match matches.value_of("import-format") {
+        Some("xml") => Some(ImportFormat::Xml),
+        Some("json") => Some(ImportFormat::Json),
+        _ => None,
+    };
+
+    let file = EvtxDump::create_output_file(path, !matches.is_present("no-confirm-overwrite"))
+        .unwrap_or_else(|e| exit_on_output_error(path, e));
+
+    let mut writer = EvtxWriter::new(file)?;
+    let mut written = 0;
+
+    for input in inputs {
+        let text = fs::read_to_string(input)?;
+        let format = format.unwrap_or_else(|| ImportFormat::detect(&text));
+        written += writer.import(&text, format)?;
+    }
+
+    writer.finish()?;
+
+    eprintln!("Wrote {} records to {}", written, path);
+    Ok(())
+}
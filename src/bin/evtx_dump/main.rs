@@ -0,0 +1,2667 @@
+use clap::{App, AppSettings, Arg, ArgMatches};
+use dialoguer::Confirmation;
+use indoc::indoc;
+use is_terminal::IsTerminal;
+
+use chrono::Duration;
+use encoding::all::encodings;
+use encoding::types::Encoding;
+use evtx::err::{dump_err_with_backtrace, Error};
+use evtx::symbolic_names::levels_named;
+use evtx::{
+    parse_time, Checkpoint, CompressedWriter, Compression, DedupField, DedupKey, Deduplicator,
+    EvtFileHeader, EvtParser, EvtxCarver, EvtxParser, EvtxRecord, EvtxStreamParser, EvtxWriter,
+    Field, FilterRules, GroupBy, InputFile, LookupTable, ParserSettings, Progress, PseudonymKind,
+    RecordFilter, Redactor, RenderFormat, SerializedEvtxRecord, TimestampSorter,
+};
+use log::Level;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Cursor, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+mod browse;
+mod diff;
+mod generate;
+mod import;
+mod merge;
+mod repair;
+mod split;
+mod strings;
+mod tampering;
+mod templates;
+mod validate;
+
+#[derive(Copy, Clone, PartialOrd, PartialEq)]
+pub enum EvtxOutputFormat {
+    JSON,
+    XML,
+}
+
+/// Where the records are printed, see `OutputFile`.
+enum Output {
+    Stdout(io::Stdout),
+    File(OutputFile),
+}
+
+impl Output {
+    /// Called after every record, to rotate the output file.
+    fn end_record(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(_) => Ok(()),
+            Output::File(file) => file.end_record(),
+        }
+    }
+
+    /// Writes the end of the output file (when it is compressed).
+    fn finish(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::File(file) => file.finish(),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::File(file) => file.flush(),
+        }
+    }
+}
+
+enum PartWriter {
+    Plain(File),
+    Compressed(CompressedWriter<File>),
+}
+
+/// The `--output` file, which is split into parts with `--split-size` or `--split-records`
+/// (numbered like `out-0001.jsonl`) and compressed with `--compress` (adding the extension of the compression).
+struct OutputFile {
+    path: PathBuf,
+    compression: Option<Compression>,
+    split_size: Option<u64>,
+    split_records: Option<usize>,
+    prompt: bool,
+    /// The number of the current part (from 1), 0 if the output is not split.
+    part: usize,
+    /// The bytes (before compression) and records written to the current part.
+    bytes: u64,
+    records: usize,
+    /// `None` once a part is full, the next part being created when more data is written.
+    writer: Option<PartWriter>,
+}
+
+impl OutputFile {
+    fn create(
+        path: impl Into<PathBuf>,
+        compression: Option<Compression>,
+        split_size: Option<u64>,
+        split_records: Option<usize>,
+        prompt: bool,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        // Fails without the `compression` feature, before any file is created.
+        if let Some(compression) = compression {
+            CompressedWriter::new(io::sink(), compression)?;
+        }
+
+        let split = split_size.is_some() || split_records.is_some();
+        let mut file = OutputFile {
+            path: path.into(),
+            compression,
+            split_size,
+            split_records,
+            prompt,
+            part: usize::from(split),
+            bytes: 0,
+            records: 0,
+            writer: None,
+        };
+        file.open_part()?;
+
+        Ok(file)
+    }
+
+    fn part_path(&self) -> PathBuf {
+        let mut name = self
+            .path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let extension = self.compression.map(|c| format!(".{}", c.extension()));
+
+        if let Some(extension) = &extension {
+            if name.ends_with(extension.as_str()) {
+                name.truncate(name.len() - extension.len());
+            }
+        }
+
+        if self.part > 0 {
+            let number = format!("-{:04}", self.part);
+            match name.rfind('.') {
+                Some(i) if i > 0 => name.insert_str(i, &number),
+                _ => name.push_str(&number),
+            }
+        }
+
+        if let Some(extension) = &extension {
+            name.push_str(extension);
+        }
+
+        self.path.with_file_name(name)
+    }
+
+    fn open_part(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let file = EvtxDump::create_output_file(self.part_path(), self.prompt)?;
+
+        self.writer = Some(match self.compression {
+            Some(compression) => PartWriter::Compressed(CompressedWriter::new(file, compression)?),
+            None => PartWriter::Plain(file),
+        });
+        self.bytes = 0;
+        self.records = 0;
+
+        Ok(())
+    }
+
+    fn end_record(&mut self) -> io::Result<()> {
+        self.records += 1;
+
+        let full = self.split_size.map_or(false, |size| self.bytes >= size)
+            || self.split_records.map_or(false, |n| self.records >= n);
+        if full {
+            self.finish()?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.writer.take() {
+            Some(PartWriter::Plain(mut file)) => file.flush(),
+            Some(PartWriter::Compressed(writer)) => writer.finish().map(drop),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.writer.is_none() {
+            self.part += 1;
+            self.open_part()
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        }
+
+        let written = match self.writer.as_mut().expect("opened above") {
+            PartWriter::Plain(file) => file.write(buf)?,
+            PartWriter::Compressed(writer) => writer.write(buf)?,
+        };
+        self.bytes += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.writer.as_mut() {
+            Some(PartWriter::Plain(file)) => file.flush(),
+            Some(PartWriter::Compressed(writer)) => writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The `--progress` bar: the chunks and records read so far, their rate and the estimated time left, on stderr.
+///
+/// It's redrawn in place when stderr is a terminal, and printed every second otherwise.
+struct ProgressBar {
+    files: usize,
+    /// The index of the file being read.
+    file: usize,
+    /// The part of the file being read which was read so far.
+    fraction: f64,
+    /// The bytes of the files read before this one, and of this one so far.
+    done_bytes: u64,
+    file_bytes: u64,
+    records: u64,
+    started: Instant,
+    drawn: Option<Instant>,
+    /// Whether there was an update since the bar was drawn.
+    updated: bool,
+    terminal: bool,
+}
+
+impl ProgressBar {
+    fn new(files: usize) -> Self {
+        ProgressBar {
+            files: files.max(1),
+            file: 0,
+            fraction: 0.0,
+            done_bytes: 0,
+            file_bytes: 0,
+            records: 0,
+            started: Instant::now(),
+            drawn: None,
+            updated: false,
+            terminal: io::stderr().is_terminal(),
+        }
+    }
+
+    fn start_file(&mut self, file: usize) {
+        self.file = file;
+        self.fraction = 0.0;
+        self.done_bytes += self.file_bytes;
+        self.file_bytes = 0;
+    }
+
+    fn update(&mut self, progress: &Progress) {
+        self.fraction = progress.fraction();
+        self.file_bytes = progress.bytes_read();
+        self.records += progress.records;
+        self.updated = true;
+
+        let interval = if self.terminal {
+            std::time::Duration::from_millis(100)
+        } else {
+            std::time::Duration::from_secs(1)
+        };
+        if self.drawn.map_or(true, |drawn| drawn.elapsed() >= interval) {
+            self.draw();
+        }
+    }
+
+    fn line(&self) -> String {
+        let done = (self.file as f64 + self.fraction) / self.files as f64;
+        let elapsed = self.started.elapsed().as_secs_f64().max(f64::EPSILON);
+        let megabytes = (self.done_bytes + self.file_bytes) as f64 / (1024.0 * 1024.0);
+
+        let eta = if done > 0.0 {
+            format_duration(elapsed * (1.0 - done) / done)
+        } else {
+            "-".to_string()
+        };
+        let file = if self.files > 1 {
+            format!(" [file {}/{}]", self.file + 1, self.files)
+        } else {
+            String::new()
+        };
+
+        format!(
+            "{:>3.0}%{} {:.1} MB, {} records ({:.1} MB/s, {:.0} records/s), ETA {}",
+            done * 100.0,
+            file,
+            megabytes,
+            self.records,
+            megabytes / elapsed,
+            self.records as f64 / elapsed,
+            eta
+        )
+    }
+
+    fn draw(&mut self) {
+        if self.terminal {
+            eprint!("\r{}\x1b[K", self.line());
+        } else {
+            eprintln!("{}", self.line());
+        }
+        self.drawn = Some(Instant::now());
+        self.updated = false;
+    }
+
+    /// Draws the final state, and ends the line of the bar.
+    fn finish(&mut self) {
+        if self.updated || self.drawn.is_none() {
+            self.draw();
+        }
+        if self.terminal {
+            eprintln!();
+        }
+    }
+}
+
+/// Formats seconds as `MM:SS` (or `HH:MM:SS`).
+fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds / 3600 {
+        0 => format!("{:02}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+/// A serialized record, with it's deduplication key (when deduplicating).
+type KeyedRecord = (Option<DedupKey>, SerializedEvtxRecord<String>);
+
+/// A report printed for every input instead of it's records, see `EvtxDump::dump_report`.
+#[derive(Clone)]
+enum Report {
+    /// The findings of the file, with this timestamp tolerance.
+    Findings(Duration),
+    /// The given number of largest records.
+    LargestRecords(usize),
+    /// The number of records in every group.
+    Counts(GroupBy),
+    /// The stacking analysis of the records, stacking these fields
+    /// (with this number of most common and rarest values).
+    Analysis { fields: Vec<String>, top: usize },
+}
+
+/// Where following a file resumes, see `EvtxDump::follow`.
+enum FollowPosition {
+    /// The records appended after this checkpoint are new.
+    Checkpoint(Checkpoint),
+    /// The records with a higher ID are new (used when there is no checkpoint for this record).
+    After(u64),
+}
+
+struct EvtxDump {
+    parser_settings: ParserSettings,
+    /// The INPUT values, which are expanded again by `--follow` to find new files.
+    input_values: Vec<String>,
+    /// The files to read, directories and glob patterns being expanded.
+    inputs: Vec<PathBuf>,
+    /// The file being read.
+    input: PathBuf,
+    /// When set (there are several inputs), records are annotated with the path of their file.
+    source: Option<String>,
+    /// The number of inputs which could not be read.
+    failed_inputs: usize,
+    show_record_number: bool,
+    output_format: EvtxOutputFormat,
+    output: Output,
+    /// When set (`-o evtx`), the records are written to this EVTX file instead of being printed.
+    evtx_output: Option<File>,
+    /// When set, only this report is printed.
+    report: Option<Report>,
+    /// When set, only the records recovered from the slack space of the chunks are printed.
+    recover_slack: bool,
+    /// When set, the input is arbitrary data, from which records are carved.
+    carve: bool,
+    /// When set, records with the same key as a previous record are not printed.
+    deduplicator: Option<Deduplicator>,
+    /// When set, only the first N records are printed.
+    head: Option<usize>,
+    /// When set, only the last N records (by `EventRecordID`) are printed.
+    tail: Option<u64>,
+    /// When set, only the record with this `EventRecordID` is printed.
+    record_id: Option<u64>,
+    /// When set, only the records of this chunk are printed.
+    chunk: Option<u16>,
+    /// When set, every record is followed by a hexdump of it's bytes.
+    hexdump: bool,
+    /// When set, records are printed in timestamp order, keeping up to this many records in memory.
+    sort_by_timestamp: Option<usize>,
+    /// The records of all the inputs, when sorting them by timestamp.
+    sorter: Option<TimestampSorter>,
+    /// The number of records (and errors) printed so far, for `--head`.
+    printed: usize,
+    /// When set (`--progress`), updated by the parsers.
+    progress: Option<Arc<Mutex<ProgressBar>>>,
+    /// When set, the inputs are polled for new records at this interval, until `--head` records are printed.
+    follow: Option<std::time::Duration>,
+    verbosity_level: Option<Level>,
+    backtraces: bool,
+}
+
+/// Simple error  macro for use inside of internal errors in `EvtxDump`
+macro_rules! err {
+    ($($tt:tt)*) => { Err(Box::<dyn std::error::Error>::from(format!($($tt)*))) }
+}
+
+impl EvtxDump {
+    pub fn from_cli_matches(matches: &ArgMatches) -> Self {
+        let values: Vec<&str> = matches
+            .values_of("INPUT")
+            .expect("This is a required argument")
+            .collect();
+        let inputs = match expand_inputs(&values) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                eprintln!("{}", e);
+                exit(1)
+            }
+        };
+        // A single file is printed as before, without annotations.
+        let annotate = values.len() > 1 || inputs.len() != 1 || inputs[0] != Path::new(values[0]);
+        if annotate && inputs.is_empty() {
+            eprintln!("No EVTX files were found");
+            exit(1)
+        }
+
+        let output_format = match matches.value_of("output-format").unwrap_or_default() {
+            "xml" => EvtxOutputFormat::XML,
+            "json" | "jsonl" => EvtxOutputFormat::JSON,
+            _ => EvtxOutputFormat::XML,
+        };
+
+        let no_indent = match (
+            matches.is_present("no-indent"),
+            matches.value_of("output-format"),
+        ) {
+            // "jsonl" --> --no-indent
+            (false, Some(fmt)) => fmt == "jsonl",
+            (true, Some(fmt)) => {
+                if fmt == "jsonl" {
+                    eprintln!("no need to pass both `--no-indent` and `-o jsonl`");
+                    true
+                } else {
+                    true
+                }
+            }
+            (v, None) => v,
+        };
+
+        let separate_json_attrib_flag = matches.is_present("separate-json-attributes");
+
+        let no_show_record_number = match (
+            matches.is_present("no-show-record-number"),
+            matches.value_of("output-format"),
+        ) {
+            // "jsonl" --> --no-show-record-number
+            (false, Some(fmt)) => fmt == "jsonl",
+            (true, Some(fmt)) => {
+                if fmt == "jsonl" {
+                    eprintln!("no need to pass both `--no-show-record-number` and `-o jsonl`");
+                    true
+                } else {
+                    true
+                }
+            }
+            (v, None) => v,
+        };
+
+        let num_threads = matches
+            .value_of("num-threads")
+            .and_then(|value| Some(value.parse::<usize>().expect("used validator")));
+
+        let num_threads = match (cfg!(feature = "multithreading"), num_threads) {
+            (true, Some(number)) => number,
+            (true, None) => 0,
+            (false, _) => {
+                eprintln!("turned on threads, but library was compiled without `multithreading` feature! using fallback sync iterator");
+                1
+            }
+        };
+
+        let validate_checksums = matches.is_present("validate-checksums");
+        let verbosity_level = match matches.occurrences_of("verbose") {
+            0 => None,
+            1 => Some(Level::Info),
+            2 => Some(Level::Debug),
+            3 => Some(Level::Trace),
+            _ => {
+                eprintln!("using more than  -vvv does not affect verbosity level");
+                Some(Level::Trace)
+            }
+        };
+
+        let backtraces = matches.is_present("backtraces");
+
+        let providers: Vec<&str> = matches
+            .values_of("provider")
+            .map(Iterator::collect)
+            .unwrap_or_default();
+
+        let channels: Vec<&str> = matches
+            .values_of("channel")
+            .map(Iterator::collect)
+            .unwrap_or_default();
+
+        let event_ids: Vec<RangeInclusive<u16>> = matches
+            .values_of("event-id")
+            .into_iter()
+            .flatten()
+            .map(|value| parse_event_id_range(value).expect("used validator"))
+            .collect();
+
+        let after = matches
+            .value_of("after")
+            .map(|value| parse_time(value).expect("used validator"));
+        let before = matches
+            .value_of("before")
+            .map(|value| parse_time(value).expect("used validator"));
+
+        let level_filter = matches.values_of("level").map(|values| {
+            let levels: Vec<u8> = values
+                .flat_map(|value| parse_levels(value).expect("used validator"))
+                .collect();
+            RecordFilter::levels(&levels)
+        });
+
+        let record_filter = matches
+            .value_of("where")
+            .map(|expr| RecordFilter::parse(expr).expect("used validator"));
+
+        let xpath_filter = matches
+            .value_of("xpath")
+            .map(|query| RecordFilter::from_xpath(query).expect("used validator"));
+
+        let query_filter = matches
+            .value_of("query")
+            .map(|query| RecordFilter::from_query(query).expect("used validator"));
+
+        let allow_filters = Self::regex_list_filters(matches, "allow-regex", |field, patterns| {
+            RecordFilter::regex_allow_list(field, patterns)
+        });
+        let deny_filters = Self::regex_list_filters(matches, "deny-regex", |field, patterns| {
+            RecordFilter::regex_deny_list(field, patterns)
+        });
+
+        let sid_filter = matches
+            .values_of("sid")
+            .map(|sids| RecordFilter::sids(&sids.collect::<Vec<&str>>()));
+
+        let account_filter = matches
+            .values_of("account")
+            .map(|accounts| RecordFilter::accounts(&accounts.collect::<Vec<&str>>()));
+
+        let activity_filter = matches
+            .values_of("activity")
+            .map(|ids| RecordFilter::activity_ids(&ids.collect::<Vec<&str>>()));
+
+        let mut filter_rules = FilterRules::default();
+
+        if matches.is_present("exclude-noise") {
+            filter_rules.merge(FilterRules::default_noise());
+        }
+
+        for path in matches.values_of("rules").into_iter().flatten() {
+            filter_rules.merge(FilterRules::load_file(path).expect("used validator"));
+        }
+
+        for path in matches.values_of("include-file").into_iter().flatten() {
+            filter_rules.merge(FilterRules::load_include_file(path).expect("used validator"));
+        }
+
+        for path in matches.values_of("exclude-file").into_iter().flatten() {
+            filter_rules.merge(FilterRules::load_exclude_file(path).expect("used validator"));
+        }
+
+        // All the given filters must match.
+        let record_filter = vec![
+            level_filter,
+            record_filter,
+            xpath_filter,
+            query_filter,
+            sid_filter,
+            account_filter,
+            activity_filter,
+        ]
+        .into_iter()
+        .flatten()
+        .chain(allow_filters)
+        .chain(deny_filters)
+        .reduce(RecordFilter::and);
+
+        let largest_records = matches
+            .value_of("largest-records")
+            .map(|value| value.parse::<usize>().expect("used validator"));
+
+        let timestamp_tolerance = Duration::seconds(
+            matches
+                .value_of("timestamp-tolerance")
+                .expect("has default")
+                .parse::<i64>()
+                .expect("used validator"),
+        );
+
+        let count_by = match matches.value_of("count-by") {
+            Some("eventid") => Some(GroupBy::EventId),
+            Some("provider") => Some(GroupBy::Provider),
+            Some("hour") => Some(GroupBy::Hour),
+            _ => None,
+        };
+
+        let top = matches
+            .value_of("top")
+            .expect("has default")
+            .parse::<usize>()
+            .expect("used validator");
+        let analyze = matches.is_present("analyze").then(|| Report::Analysis {
+            fields: matches
+                .values_of("stack-field")
+                .map(|fields| fields.map(str::to_string).collect())
+                .unwrap_or_default(),
+            top,
+        });
+
+        // Only one report is printed, the first of these.
+        let report = if matches.is_present("findings") {
+            Some(Report::Findings(timestamp_tolerance))
+        } else {
+            largest_records
+                .map(Report::LargestRecords)
+                .or_else(|| count_by.map(Report::Counts))
+                .or(analyze)
+        };
+
+        let head = matches
+            .value_of("head")
+            .map(|value| value.parse::<usize>().expect("used validator"));
+
+        let tail = matches
+            .value_of("tail")
+            .map(|value| value.parse::<u64>().expect("used validator"));
+
+        let record_id = matches
+            .value_of("record-id")
+            .map(|value| value.parse::<u64>().expect("used validator"));
+
+        let chunk = matches
+            .value_of("chunk")
+            .map(|value| value.parse::<u16>().expect("used validator"));
+
+        let sort_by_timestamp = if matches.is_present("sort-by-timestamp") {
+            let buffer = matches.value_of("sort-buffer").expect("has default");
+            Some(buffer.parse::<usize>().expect("used validator"))
+        } else {
+            None
+        };
+
+        let follow = if matches.is_present("follow") {
+            let interval = matches.value_of("poll-interval").expect("has default");
+            Some(std::time::Duration::from_secs_f64(
+                interval.parse::<f64>().expect("used validator"),
+            ))
+        } else {
+            None
+        };
+
+        let sample = matches
+            .value_of("sample")
+            .map(|value| value.parse::<u64>().expect("used validator"));
+
+        let deduplicator = match matches.values_of("dedup-on") {
+            Some(fields) => Some(Deduplicator::new(
+                &fields
+                    .map(|field| match field {
+                        "timestamp" => DedupField::Timestamp,
+                        "provider" => DedupField::Provider,
+                        "eventid" => DedupField::EventId,
+                        _ => DedupField::EventData,
+                    })
+                    .collect::<Vec<_>>(),
+            )),
+            None if matches.is_present("dedup") => Some(Deduplicator::new(&DedupField::ALL)),
+            None => None,
+        };
+
+        let ansi_codec = encodings()
+            .iter()
+            .find(|c| c.name() == matches.value_of("ansi-codec").expect("has set default"))
+            .expect("possible values are derived from `encodings()`");
+
+        let compression = match matches.value_of("compress") {
+            Some("gzip") => Some(Compression::Gzip),
+            Some("zstd") => Some(Compression::Zstd),
+            _ => None,
+        };
+        let split_size = matches
+            .value_of("split-size")
+            .map(|size| parse_size(size).expect("used validator"));
+        let split_records = matches
+            .value_of("split-records")
+            .map(|n| n.parse::<usize>().expect("used validator").max(1));
+
+        let prompt = !matches.is_present("no-confirm-overwrite");
+        let (output, evtx_output) = match (
+            matches.value_of("output-format"),
+            matches.value_of("output-target"),
+        ) {
+            (Some("evtx"), _)
+                if compression.is_some() || split_size.is_some() || split_records.is_some() =>
+            {
+                eprintln!("`-o evtx` does not support `--compress`, `--split-size` or `--split-records`, use `evtx_dump split` instead");
+                exit(1)
+            }
+            (Some("evtx"), Some(path)) => {
+                let file = Self::create_output_file(path, prompt)
+                    .unwrap_or_else(|e| exit_on_output_error(path, e));
+                (Output::Stdout(io::stdout()), Some(file))
+            }
+            (Some("evtx"), None) => {
+                eprintln!("`-o evtx` requires an output file (`--output`)");
+                exit(1)
+            }
+            (_, Some(path)) => {
+                let file = OutputFile::create(path, compression, split_size, split_records, prompt)
+                    .unwrap_or_else(|e| exit_on_output_error(path, e));
+                (Output::File(file), None)
+            }
+            (_, None) => (Output::Stdout(io::stdout()), None),
+        };
+
+        let mut parser_settings = ParserSettings::new()
+            .num_threads(num_threads)
+            .validate_checksums(validate_checksums)
+            .partial_records(matches.is_present("partial-records"))
+            .repair_chunk_headers(matches.is_present("repair-chunks"))
+            .resync_records(matches.is_present("resync-records"))
+            .order_by_record_id(matches.is_present("order-by-record-id"))
+            .separate_json_attributes(separate_json_attrib_flag)
+            .resolve_sids(matches.is_present("resolve-sids"))
+            .decode_logon_events(matches.is_present("decode-logons"))
+            .indent(!no_indent)
+            .ansi_codec(*ansi_codec)
+            .provider_filter(&providers)
+            .channel_filter(&channels)
+            .event_id_ranges_filter(&event_ids)
+            .time_range_filter(after, before);
+
+        if matches.is_present("symbolic-names") {
+            let format = match output_format {
+                EvtxOutputFormat::XML => RenderFormat::Xml,
+                EvtxOutputFormat::JSON => RenderFormat::Json,
+            };
+            parser_settings = parser_settings.symbolic_names(format, true);
+        }
+
+        if let Some(record_filter) = record_filter {
+            parser_settings = parser_settings.record_filter(record_filter);
+        }
+
+        parser_settings = parser_settings.filter_rules(filter_rules);
+
+        for lookup in matches.values_of("lookup").into_iter().flatten() {
+            let (fields, path) = split_lookup(lookup).expect("used validator");
+            let table = Arc::new(LookupTable::load(path).expect("used validator"));
+            for field in fields.split(',') {
+                parser_settings = parser_settings.lookup(field.trim(), Arc::clone(&table));
+            }
+        }
+
+        if let Some(n) = sample {
+            parser_settings = parser_settings.sample_every(n);
+        }
+
+        if let Some(redactor) = Self::redactor(matches) {
+            if output_format == EvtxOutputFormat::XML && evtx_output.is_none() {
+                eprintln!(
+                    "`--redact` requires JSON or EVTX output (`-o json`, `-o jsonl` or `-o evtx`)"
+                );
+                exit(1)
+            }
+            parser_settings = parser_settings.redactor(redactor);
+        }
+
+        // Clones of the settings share the counters, so they can be read after parsing.
+        parser_settings = parser_settings.collect_stats(matches.is_present("stats"));
+
+        let progress = if matches.is_present("progress") {
+            let progress = Arc::new(Mutex::new(ProgressBar::new(inputs.len())));
+            let bar = Arc::clone(&progress);
+            parser_settings = parser_settings.on_progress(move |p| {
+                bar.lock().expect("not poisoned").update(p);
+            });
+            Some(progress)
+        } else {
+            None
+        };
+
+        EvtxDump {
+            parser_settings,
+            input_values: values.iter().map(|value| value.to_string()).collect(),
+            input: inputs[0].clone(),
+            source: if annotate { Some(String::new()) } else { None },
+            failed_inputs: 0,
+            inputs,
+            show_record_number: !no_show_record_number,
+            output_format,
+            output,
+            evtx_output,
+            report,
+            recover_slack: matches.is_present("recover-slack"),
+            carve: matches.is_present("carve"),
+            deduplicator,
+            head,
+            tail,
+            record_id,
+            chunk,
+            hexdump: matches.is_present("hexdump"),
+            sort_by_timestamp,
+            sorter: None,
+            printed: 0,
+            progress,
+            follow,
+            verbosity_level,
+            backtraces,
+        }
+    }
+
+    /// Groups the `FIELD=REGEX` values of the `arg` argument by field, and builds a filter for every field.
+    fn regex_list_filters(
+        matches: &ArgMatches,
+        arg: &str,
+        build: impl Fn(Field, &[&str]) -> Result<RecordFilter, Error>,
+    ) -> Vec<RecordFilter> {
+        let mut patterns_by_field: Vec<(&str, Vec<&str>)> = vec![];
+
+        for value in matches.values_of(arg).into_iter().flatten() {
+            let (field, pattern) = value.split_once('=').expect("used validator");
+
+            match patterns_by_field.iter_mut().find(|(f, _)| *f == field) {
+                Some((_, patterns)) => patterns.push(pattern),
+                None => patterns_by_field.push((field, vec![pattern])),
+            }
+        }
+
+        patterns_by_field
+            .into_iter()
+            .map(|(field, patterns)| {
+                let field = Field::parse(field).expect("used validator");
+                build(field, &patterns).expect("used validator")
+            })
+            .collect()
+    }
+
+    /// Builds the redactor of `--redact`/`--redact-field`, keyed by `--redact-key` (or a random key).
+    fn redactor(matches: &ArgMatches) -> Option<Redactor> {
+        if !matches.is_present("redact") && !matches.is_present("redact-field") {
+            return None;
+        }
+
+        let key = match matches.value_of("redact-key") {
+            Some(key) => key.to_string(),
+            None => {
+                // Every `RandomState` is seeded differently.
+                let random = || RandomState::new().build_hasher().finish();
+                format!("{:016x}{:016x}", random(), random())
+            }
+        };
+
+        let mut redactor = Redactor::new(key);
+        if matches.is_present("redact") {
+            redactor = redactor.with_default_fields();
+        }
+        for value in matches.values_of("redact-field").into_iter().flatten() {
+            let (name, kind) = split_redact_field(value).expect("used validator");
+            redactor = redactor.field(name, kind);
+        }
+
+        Some(redactor)
+    }
+
+    /// Main entry point for `EvtxDump`
+    pub fn run(&mut self) -> Result<(), Error> {
+        self.try_to_initialize_logging();
+
+        if self.evtx_output.is_some() && self.source.is_some() {
+            eprintln!("`-o evtx` is only supported for a single input file, use `evtx_dump merge` instead");
+            exit(1)
+        }
+
+        if let Some(interval) = self.follow {
+            if self.evtx_output.is_some() || self.inputs.iter().any(|i| i == Path::new("-")) {
+                eprintln!("`--follow` is not supported with `-o evtx` or stdin");
+                exit(1)
+            }
+            self.follow(interval)?;
+            return Ok(self.output.finish()?);
+        }
+
+        if let Some(max_records_in_memory) = self.sort_by_timestamp {
+            self.sorter = Some(TimestampSorter::new().max_records_in_memory(max_records_in_memory));
+        }
+
+        if self.source.is_none() {
+            self.run_file()?;
+        } else {
+            // Every file is processed, even if some of them fail.
+            for (i, input) in self.inputs.clone().into_iter().enumerate() {
+                if let Some(progress) = &self.progress {
+                    progress.lock().expect("not poisoned").start_file(i);
+                }
+                self.source = Some(input.display().to_string());
+                self.input = input;
+
+                if let Err(e) = self.run_file() {
+                    eprintln!("Failed to process {} - {}", self.input.display(), e);
+                    self.failed_inputs += 1;
+                }
+            }
+        }
+
+        if let Some(sorter) = self.sorter.take() {
+            for record in sorter.into_sorted()?.take(self.remaining()) {
+                self.dump_record(record)?
+            }
+        }
+
+        if let Some(deduplicator) = &self.deduplicator {
+            log::info!("Suppressed {} duplicate records", deduplicator.duplicates());
+        }
+
+        self.output.finish()?;
+
+        if let Some(progress) = &self.progress {
+            progress.lock().expect("not poisoned").finish();
+        }
+
+        if let (Some(record_id), 0) = (self.record_id, self.printed) {
+            eprintln!("Record {} was not found", record_id);
+            exit(1)
+        }
+
+        Ok(())
+    }
+
+    /// Prints the records appended to the inputs (and to new files matching them), polling them every `interval`.
+    ///
+    /// Records already in a file when it's first read are skipped, except for the last `--tail` records.
+    fn follow(&mut self, interval: std::time::Duration) -> Result<(), Error> {
+        let mut positions = HashMap::new();
+        for input in self.inputs.clone() {
+            let position = self.start_position(&input);
+            positions.insert(input, position);
+        }
+
+        let input_values = self.input_values.clone();
+        let values: Vec<&str> = input_values.iter().map(String::as_str).collect();
+
+        loop {
+            for input in expand_inputs(&values).unwrap_or_else(|_| self.inputs.clone()) {
+                if self.remaining() == 0 {
+                    return Ok(());
+                }
+
+                // Files created after the start are new, so all of their records are printed.
+                let position = positions
+                    .entry(input.clone())
+                    .or_insert(FollowPosition::After(0));
+                self.input = input;
+                if self.source.is_some() {
+                    self.source = Some(self.input.display().to_string());
+                }
+
+                // Files can be missing for a while when they are rotated.
+                if let Err(e) = self.dump_new_records(position) {
+                    log::warn!("Failed to follow {} - {}", self.input.display(), e);
+                }
+            }
+
+            self.output.flush()?;
+            if self.remaining() == 0 {
+                return Ok(());
+            }
+
+            thread::sleep(interval);
+        }
+    }
+
+    /// Returns the position following `input` starts at, keeping the last `--tail` records.
+    fn start_position(&self, input: &Path) -> FollowPosition {
+        // The records are counted without sampling, which applies to the printed records.
+        let settings = self.parser_settings.clone().sample_every(0);
+        let mut parser = match EvtxParser::from_path(input) {
+            Ok(parser) => parser.with_configuration(settings),
+            Err(_) => return FollowPosition::After(0),
+        };
+
+        // Following starts after the record preceding the last `--tail` records, if there is one.
+        let tail = self.tail.unwrap_or(0);
+        let ids: Vec<u64> = parser
+            .serialized_tail(tail + 1, |record| {
+                record.map(|record| record.event_record_id)
+            })
+            .filter_map(Result::ok)
+            .collect();
+
+        match ids.first() {
+            Some(&record_id) if ids.len() as u64 > tail => match parser.checkpoint(record_id) {
+                Some(checkpoint) => FollowPosition::Checkpoint(checkpoint),
+                None => FollowPosition::After(record_id),
+            },
+            _ => FollowPosition::After(0),
+        }
+    }
+
+    /// Prints the records of the current input after `position`, and moves it past them.
+    fn dump_new_records(&mut self, position: &mut FollowPosition) -> Result<(), Error> {
+        let mut parser =
+            EvtxParser::from_path(&self.input)?.with_configuration(self.parser_settings.clone());
+
+        let serializer = self.serializer();
+        let records: Box<dyn Iterator<Item = Result<KeyedRecord, Error>>> = match position {
+            FollowPosition::Checkpoint(checkpoint) => {
+                Box::new(parser.serialized_records_since(checkpoint, serializer))
+            }
+            FollowPosition::After(record_id) => Box::new(parser.serialized_records_in_id_range(
+                record_id.saturating_add(1),
+                u64::MAX,
+                serializer,
+            )),
+        };
+
+        let mut last_record_id = None;
+        let records = records.inspect(|record| {
+            if let Ok((_, record)) = record {
+                last_record_id = Some(record.event_record_id);
+            }
+        });
+        self.dump_serialized_records(records)?;
+
+        if let Some(record_id) = last_record_id {
+            *position = match parser.checkpoint(record_id) {
+                Some(checkpoint) => FollowPosition::Checkpoint(checkpoint),
+                None => FollowPosition::After(record_id),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// The number of records which can still be printed, according to `--head`.
+    fn remaining(&self) -> usize {
+        self.head
+            .map_or(usize::MAX, |head| head.saturating_sub(self.printed))
+    }
+
+    /// Prints the records (or the report) of the current input.
+    fn run_file(&mut self) -> Result<(), Error> {
+        if self.evtx_output.is_some() && (self.carve || is_evt_file(&self.input)) {
+            eprintln!("`-o evtx` is only supported for EVTX input files");
+            exit(1)
+        }
+
+        if self.evtx_output.is_some() && self.sort_by_timestamp.is_some() {
+            eprintln!("`--sort-by-timestamp` is not supported with `-o evtx`, use `evtx_dump merge` instead");
+            exit(1)
+        }
+
+        let stdin = self.input == Path::new("-");
+        if stdin && self.prints_records_only() {
+            return self.dump_streamed_records();
+        }
+
+        if self.carve {
+            return self.dump_carved_records();
+        }
+
+        if is_evt_file(&self.input) {
+            return self.dump_evt_records();
+        }
+
+        // Reports need to seek, so stdin is read to memory for them.
+        let parser = if stdin {
+            self.read_input().map_err(Error::from).and_then(|data| {
+                EvtxParser::from_read_seek(InputFile::Decompressed(Cursor::new(data)))
+            })
+        } else {
+            EvtxParser::from_path(&self.input)
+        };
+
+        let mut parser = match parser {
+            Ok(parser) => parser.with_configuration(self.parser_settings.clone()),
+            Err(e) if self.source.is_some() => return Err(e),
+            Err(e) => {
+                eprintln!(
+                    "Failed to open file {}.\n\tcaused by: {}",
+                    self.input.display(),
+                    &e
+                );
+                exit(1)
+            }
+        };
+
+        if let Some(report) = self.report.clone() {
+            return self.dump_report(&mut parser, report);
+        }
+
+        if let Some(file) = self.evtx_output.take() {
+            return self.rewrite_records(&mut parser, file);
+        }
+
+        let records = self.select_records(&mut parser, self.serializer());
+        self.dump_serialized_records(records)
+    }
+
+    /// Returns `true` if only records are printed (rather than a report), so they can be read from a stream.
+    fn prints_records_only(&self) -> bool {
+        self.report.is_none()
+            && self.evtx_output.is_none()
+            && self.tail.is_none()
+            && self.record_id.is_none()
+            && self.chunk.is_none()
+            && !self.recover_slack
+            && !self.carve
+    }
+
+    /// Reads the whole input, `-` being stdin.
+    fn read_input(&self) -> io::Result<Vec<u8>> {
+        if self.input == Path::new("-") {
+            let mut data = vec![];
+            io::stdin().lock().read_to_end(&mut data)?;
+            Ok(data)
+        } else {
+            fs::read(&self.input)
+        }
+    }
+
+    /// Prints the records of stdin, which is read one chunk at a time.
+    fn dump_streamed_records(&mut self) -> Result<(), Error> {
+        let stdin = io::stdin();
+        let mut parser = EvtxStreamParser::from_read(stdin.lock())?
+            .with_configuration(self.parser_settings.clone());
+
+        let records = parser.serialized_records(self.serializer());
+        self.dump_serialized_records(records)
+    }
+
+    /// Returns the function serializing records in the output format, along with their deduplication key.
+    fn serializer(
+        &self,
+    ) -> impl FnMut(Result<EvtxRecord<'_>, Error>) -> Result<KeyedRecord, Error>
+           + Send
+           + Sync
+           + Clone
+           + 'static {
+        let output_format = self.output_format;
+        let dedup_fields = self.deduplicator.as_ref().map(|d| d.fields().to_vec());
+        let source = self.source.clone();
+        let indent = self.parser_settings.should_indent();
+        let with_hexdump = self.hexdump;
+
+        // The key (and the bytes) of the record are read before it's serialized.
+        move |record: Result<EvtxRecord, Error>| {
+            record.and_then(|record| {
+                let key = dedup_fields
+                    .as_ref()
+                    .map(|fields| DedupKey::of(&record, fields))
+                    .transpose()?;
+                let dump = if with_hexdump {
+                    Some(hexdump(record.raw_bytes()))
+                } else {
+                    None
+                };
+
+                let mut record = match (output_format, source.as_deref()) {
+                    (EvtxOutputFormat::XML, source) => {
+                        let mut record = record.into_xml()?;
+                        annotate_xml(&mut record.data, source);
+                        record
+                    }
+                    (EvtxOutputFormat::JSON, None) => record.into_json()?,
+                    (EvtxOutputFormat::JSON, source) => {
+                        serialize_json(record.into_json_value()?, source, indent)
+                    }
+                };
+                if let Some(dump) = dump {
+                    record.data.push('\n');
+                    record.data.push_str(&dump);
+                }
+
+                Ok((key, record))
+            })
+        }
+    }
+
+    /// Prints the serialized records, after deduplicating them, and sorting them when `--sort-by-timestamp` is set.
+    fn dump_serialized_records(
+        &mut self,
+        records: impl Iterator<Item = Result<KeyedRecord, Error>>,
+    ) -> Result<(), Error> {
+        let mut deduplicator = self.deduplicator.take();
+        let records = deduplicate(records, &mut deduplicator);
+
+        // The sorted records are printed once all the inputs are read.
+        if let Some(mut sorter) = self.sorter.take() {
+            for record in records {
+                match record {
+                    Ok(record) => sorter.push(record)?,
+                    Err(e) => self.print_error(&e),
+                }
+            }
+            self.sorter = Some(sorter);
+        } else {
+            for record in records.take(self.remaining()) {
+                self.dump_record(record)?
+            }
+        }
+
+        self.deduplicator = deduplicator;
+        Ok(())
+    }
+
+    /// Returns the records selected by `--recover-slack`, `--record-id`, `--chunk` and `--tail`
+    /// (all the records otherwise), mapped with `f`.
+    fn select_records<'p, U: Send + 'static>(
+        &self,
+        parser: &'p mut EvtxParser<InputFile>,
+        f: impl FnMut(Result<EvtxRecord, Error>) -> Result<U, Error> + Send + Sync + Clone + 'static,
+    ) -> Box<dyn Iterator<Item = Result<U, Error>> + 'p> {
+        if let Some(record_id) = self.record_id {
+            return Box::new(parser.serialized_records_in_id_range(record_id, record_id, f));
+        }
+
+        if let Some(chunk_number) = self.chunk {
+            return Box::new(parser.serialized_records_in_chunk(chunk_number, f));
+        }
+
+        match self.tail {
+            _ if self.recover_slack => Box::new(parser.serialized_slack_records(f)),
+            Some(n) => Box::new(parser.serialized_tail(n, f)),
+            None => Box::new(parser.serialized_records(f)),
+        }
+    }
+
+    /// Writes the records (selected like the printed records) to a new EVTX file.
+    fn rewrite_records(
+        &mut self,
+        parser: &mut EvtxParser<InputFile>,
+        file: File,
+    ) -> Result<(), Error> {
+        let dedup_fields = self.deduplicator.as_ref().map(|d| d.fields().to_vec());
+
+        let own = move |record: Result<EvtxRecord, Error>| {
+            record.and_then(|record| {
+                let key = dedup_fields
+                    .as_ref()
+                    .map(|fields| DedupKey::of(&record, fields))
+                    .transpose()?;
+
+                Ok((key, record.into_owned()))
+            })
+        };
+
+        let records = self.select_records(parser, own);
+        let mut deduplicator = self.deduplicator.take();
+        let records = deduplicate(records, &mut deduplicator);
+
+        let mut writer = EvtxWriter::new(file)?;
+        let mut copied = 0;
+
+        for record in records.take(self.head.unwrap_or(usize::MAX)) {
+            match record {
+                Ok(record) => {
+                    writer.copy_record(&record)?;
+                    copied += 1;
+                }
+                Err(e) => self.print_error(&e),
+            }
+        }
+
+        writer.finish()?;
+        log::info!("Wrote {} records", copied);
+        self.printed += copied;
+
+        if let Some(deduplicator) = deduplicator {
+            log::info!("Suppressed {} duplicate records", deduplicator.duplicates());
+        }
+
+        Ok(())
+    }
+
+    /// Prints the stats of the parser to stderr, if `--stats` was passed.
+    fn print_stats(&self) {
+        if let Some(stats) = self.parser_settings.get_stats() {
+            eprintln!("{}", stats);
+        }
+    }
+
+    /// If `prompt` is passed, will display a confirmation prompt before overwriting files.
+    fn create_output_file(
+        path: impl AsRef<Path>,
+        prompt: bool,
+    ) -> Result<File, Box<dyn std::error::Error>> {
+        let p = path.as_ref();
+
+        if p.is_dir() {
+            return err!(
+                "There is a directory at {}, refusing to overwrite",
+                p.display()
+            );
+        }
+
+        if p.exists() {
+            if prompt {
+                match Confirmation::new()
+                    .with_text(&format!(
+                        "Are you sure you want to override output file at {}",
+                        p.display()
+                    ))
+                    .default(false)
+                    .interact()
+                {
+                    Ok(true) => Ok(File::create(p)?),
+                    Ok(false) => err!("Cancelled"),
+                    Err(e) => err!(
+                        "Failed to write confirmation prompt to term caused by\n{}",
+                        e
+                    ),
+                }
+            } else {
+                Ok(File::create(p)?)
+            }
+        } else {
+            // Ok to assume p is not an existing directory
+            match p.parent() {
+                Some(parent) =>
+                // Parent exist
+                {
+                    if parent.exists() {
+                        Ok(File::create(p)?)
+                    } else {
+                        fs::create_dir_all(parent)?;
+                        Ok(File::create(p)?)
+                    }
+                }
+                None => err!("Output file cannot be root."),
+            }
+        }
+    }
+
+    /// Prints `report` for the file, instead of it's records.
+    fn dump_report(
+        &mut self,
+        parser: &mut EvtxParser<InputFile>,
+        report: Report,
+    ) -> Result<(), Error> {
+        match report {
+            Report::Findings(tolerance) => self.dump_findings(parser, tolerance),
+            Report::LargestRecords(n) => self.dump_largest_records(parser, n),
+            Report::Counts(group_by) => self.dump_counts(parser, group_by),
+            Report::Analysis { fields, top } => self.dump_analysis(parser, &fields, top),
+        }
+    }
+
+    /// Prints the `n` largest records (by rendered size in the output format) as a tab separated table.
+    fn dump_largest_records(
+        &mut self,
+        parser: &mut EvtxParser<InputFile>,
+        n: usize,
+    ) -> Result<(), Error> {
+        let format = match self.output_format {
+            EvtxOutputFormat::XML => RenderFormat::Xml,
+            EvtxOutputFormat::JSON => RenderFormat::Json,
+        };
+
+        writeln!(
+            self.output,
+            "EventRecordID\tTimestamp\tEventID\tProvider\tRawSize\tRenderedSize"
+        )?;
+
+        for size in parser.largest_records(n, format) {
+            writeln!(
+                self.output,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                size.event_record_id,
+                size.timestamp.to_rfc3339(),
+                size.event_id.map(|id| id.to_string()).unwrap_or_default(),
+                size.provider_name.unwrap_or_default(),
+                size.raw_size,
+                size.rendered_size
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Prints the number of records in every group as a tab separated table,
+    /// most common groups first (or in chronological order, for hours).
+    fn dump_counts(
+        &mut self,
+        parser: &mut EvtxParser<InputFile>,
+        group_by: GroupBy,
+    ) -> Result<(), Error> {
+        let histogram = parser.aggregate(group_by);
+
+        let header = match group_by {
+            GroupBy::EventId => "EventID",
+            GroupBy::Provider => "Provider",
+            GroupBy::Hour => "Hour",
+        };
+
+        writeln!(self.output, "{}\tCount", header)?;
+
+        let groups = match group_by {
+            GroupBy::Hour => histogram
+                .counts
+                .iter()
+                .map(|(key, count)| (key, *count))
+                .collect(),
+            _ => histogram.most_common(),
+        };
+
+        for (key, count) in groups {
+            writeln!(self.output, "{}\t{}", key, count)?;
+        }
+
+        if histogram.errors > 0 {
+            eprintln!("{} records failed to parse", histogram.errors);
+        }
+
+        Ok(())
+    }
+
+    /// Prints the stacking analysis of the records, as JSON or CSV.
+    fn dump_analysis(
+        &mut self,
+        parser: &mut EvtxParser<InputFile>,
+        fields: &[String],
+        top: usize,
+    ) -> Result<(), Error> {
+        let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+        let report = parser.analyze(&fields, top);
+
+        match self.output_format {
+            EvtxOutputFormat::JSON => writeln!(self.output, "{:#}", report.to_json_value())?,
+            EvtxOutputFormat::XML => report.write_csv(&mut self.output)?,
+        }
+
+        if report.errors > 0 {
+            eprintln!("{} records failed to parse", report.errors);
+        }
+
+        Ok(())
+    }
+
+    /// Prints the records carved from the input, which is read as arbitrary data.
+    fn dump_carved_records(&mut self) -> Result<(), Error> {
+        let data = match self.read_input() {
+            Ok(data) => data,
+            Err(e) if self.source.is_some() => return Err(e.into()),
+            Err(e) => {
+                eprintln!(
+                    "Failed to read file {}.\n\tcaused by: {}",
+                    self.input.display(),
+                    &e
+                );
+                exit(1)
+            }
+        };
+
+        let carver = EvtxCarver::new().with_configuration(self.parser_settings.clone());
+        let source = self.source.as_deref();
+        let indent = self.parser_settings.should_indent();
+        let records: Vec<_> = match self.output_format {
+            EvtxOutputFormat::XML => carver
+                .records(&data)
+                .into_iter()
+                .map(|carved| {
+                    carved.record.map(|mut record| {
+                        annotate_xml(&mut record.data, source);
+                        record
+                    })
+                })
+                .collect(),
+            EvtxOutputFormat::JSON => carver
+                .serialized_records(&data, |record| {
+                    record.and_then(|record| record.into_json_value())
+                })
+                .into_iter()
+                .map(|carved| {
+                    carved
+                        .record
+                        .map(|record| serialize_json(record, source, indent))
+                })
+                .collect(),
+        };
+
+        for record in records.into_iter().take(self.remaining()) {
+            self.dump_record(record)?
+        }
+
+        Ok(())
+    }
+
+    /// Prints the records of a legacy `.evt` file.
+    fn dump_evt_records(&mut self) -> Result<(), Error> {
+        let parser = match EvtParser::from_path(&self.input) {
+            Ok(parser) => parser.with_configuration(self.parser_settings.clone()),
+            Err(e) if self.source.is_some() => return Err(e),
+            Err(e) => {
+                eprintln!(
+                    "Failed to open file {}.\n\tcaused by: {}",
+                    self.input.display(),
+                    &e
+                );
+                exit(1)
+            }
+        };
+
+        let source = self.source.clone();
+        let indent = self.parser_settings.should_indent();
+        let records: Box<dyn Iterator<Item = _>> = match self.output_format {
+            EvtxOutputFormat::XML => Box::new(parser.records().map(move |record| {
+                record.map(|mut record| {
+                    annotate_xml(&mut record.data, source.as_deref());
+                    record
+                })
+            })),
+            EvtxOutputFormat::JSON => Box::new(parser.records_json_value().map(move |record| {
+                record.map(|record| serialize_json(record, source.as_deref(), indent))
+            })),
+        };
+
+        for record in records.take(self.remaining()) {
+            self.dump_record(record)?
+        }
+
+        Ok(())
+    }
+
+    /// Prints the log clearing and audit tampering findings of the file,
+    /// as JSON or as one line per finding.
+    fn dump_findings(
+        &mut self,
+        parser: &mut EvtxParser<InputFile>,
+        tolerance: Duration,
+    ) -> Result<(), Error> {
+        let report = parser.findings_report(tolerance)?;
+
+        match self.output_format {
+            EvtxOutputFormat::JSON => writeln!(self.output, "{:#}", report.to_json_value())?,
+            EvtxOutputFormat::XML => writeln!(self.output, "{}", report)?,
+        }
+
+        Ok(())
+    }
+
+    fn dump_record(
+        &mut self,
+        record: Result<SerializedEvtxRecord<String>, Error>,
+    ) -> Result<(), Error> {
+        self.printed += 1;
+
+        match record {
+            Ok(r) => {
+                if self.show_record_number {
+                    if r.recovered {
+                        writeln!(self.output, "Record {} (recovered)", r.event_record_id)?;
+                    } else {
+                        writeln!(self.output, "Record {}", r.event_record_id)?;
+                    }
+                }
+                writeln!(self.output, "{}", r.data)?;
+                self.output.end_record()?;
+
+                for error in r.errors.iter() {
+                    eprintln!(
+                        "Record {}: substitution failed to decode: {}",
+                        r.event_record_id, error
+                    );
+                }
+            }
+            Err(e) => self.print_error(&e),
+        };
+
+        Ok(())
+    }
+
+    fn print_error(&self, e: &Error) {
+        if self.backtraces {
+            dump_err_with_backtrace(e)
+        } else {
+            eprintln!("{:?}", e);
+        }
+    }
+
+    fn try_to_initialize_logging(&self) {
+        if let Some(level) = self.verbosity_level {
+            match simplelog::WriteLogger::init(
+                level.to_level_filter(),
+                simplelog::Config::default(),
+                io::stderr(),
+            ) {
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to initialize logging: {:?}", e),
+            };
+        }
+    }
+}
+
+/// The outcome of `evtx_dump validate` (or `evtx_dump tampering`) for a file, from the best to the worst.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Validity {
+    Valid,
+    Damaged,
+    Unreadable,
+}
+
+impl Validity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Validity::Valid => "VALID",
+            Validity::Damaged => "DAMAGED",
+            Validity::Unreadable => "UNREADABLE",
+        }
+    }
+
+    /// The exit code of the `validate` and `tampering` subcommands when this is the worst outcome.
+    fn exit_code(self) -> i32 {
+        match self {
+            Validity::Valid => 0,
+            Validity::Damaged => 2,
+            Validity::Unreadable => 3,
+        }
+    }
+}
+
+/// Opens a file for a report, reading stdin to memory for `-` (as reports need to seek).
+fn open_report_input(path: &Path) -> Result<EvtxParser<InputFile>, Error> {
+    if path == Path::new("-") {
+        let mut data = vec![];
+        io::stdin().lock().read_to_end(&mut data)?;
+        EvtxParser::from_read_seek(InputFile::Decompressed(Cursor::new(data)))
+    } else {
+        EvtxParser::from_path(path)
+    }
+}
+
+/// Drops the records with the same key as a previous record (when there is a deduplicator).
+fn deduplicate<'d, T>(
+    records: impl Iterator<Item = Result<(Option<DedupKey>, T), Error>> + 'd,
+    deduplicator: &'d mut Option<Deduplicator>,
+) -> impl Iterator<Item = Result<T, Error>> + 'd {
+    records.filter_map(move |record| match record {
+        Ok((Some(key), record)) => match deduplicator.as_mut().map(|d| d.insert(key)) {
+            Some(false) => None,
+            _ => Some(Ok(record)),
+        },
+        Ok((None, record)) => Some(Ok(record)),
+        Err(e) => Some(Err(e)),
+    })
+}
+
+/// Expands the INPUT values: directories to the `*.evtx` files under them (recursively),
+/// and glob patterns (which are not existing paths) to the files they match.
+fn expand_inputs(values: &[&str]) -> Result<Vec<PathBuf>, String> {
+    let mut inputs = vec![];
+
+    for value in values {
+        let path = Path::new(value);
+
+        if *value == "-" {
+            inputs.push(path.to_path_buf());
+        } else if path.is_dir() {
+            find_evtx_files(path, &mut inputs);
+        } else if !path.exists() && value.contains(&['*', '?', '['][..]) {
+            let paths = glob::glob(value)
+                .map_err(|e| format!("Invalid glob pattern `{}` - {}", value, e))?;
+
+            for path in paths {
+                match path {
+                    Ok(path) if path.is_dir() => find_evtx_files(&path, &mut inputs),
+                    Ok(path) => inputs.push(path),
+                    Err(e) => eprintln!("Skipped {} - {}", e.path().display(), e.error()),
+                }
+            }
+        } else {
+            inputs.push(path.to_path_buf());
+        }
+    }
+
+    Ok(inputs)
+}
+
+/// Adds the `*.evtx` files under `dir` to `files`, sorted by path.
+fn find_evtx_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            eprintln!("Skipped {} - {}", dir.display(), e);
+            return;
+        }
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|e| e.path())
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let is_evtx = path
+            .extension()
+            .map_or(false, |extension| extension.eq_ignore_ascii_case("evtx"));
+
+        if path.is_dir() {
+            find_evtx_files(&path, files);
+        } else if is_evtx {
+            files.push(path);
+        }
+    }
+}
+
+/// Formats `data` like `hexdump -C`: the offset, 16 bytes in hex and the same bytes as ASCII on every line.
+fn hexdump(data: &[u8]) -> String {
+    let hex = |bytes: &[u8]| {
+        bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let lines: Vec<String> = data
+        .chunks(16)
+        .enumerate()
+        .map(|(i, line)| {
+            let (first, second) = line.split_at(line.len().min(8));
+            let ascii: String = line
+                .iter()
+                .map(|&b| {
+                    if b == b' ' || b.is_ascii_graphic() {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+
+            format!(
+                "{:08x}  {:<23}  {:<23}  |{}|",
+                i * 16,
+                hex(first),
+                hex(second),
+                ascii
+            )
+        })
+        .collect();
+
+    lines.join("\n")
+}
+
+/// Serializes a JSON record, adding the path of it's file (if any) as a `SourceFile` field.
+fn serialize_json(
+    record: SerializedEvtxRecord<serde_json::Value>,
+    source: Option<&str>,
+    indent: bool,
+) -> SerializedEvtxRecord<String> {
+    let mut data = record.data;
+    if let (Some(source), Some(object)) = (source, data.as_object_mut()) {
+        object.insert("SourceFile".to_string(), source.into());
+    }
+
+    SerializedEvtxRecord {
+        event_record_id: record.event_record_id,
+        timestamp: record.timestamp,
+        data: if indent {
+            format!("{:#}", data)
+        } else {
+            data.to_string()
+        },
+        recovered: record.recovered,
+        errors: record.errors,
+    }
+}
+
+/// Adds the path of the file (if any) of an XML record as a comment, following it's XML declaration.
+fn annotate_xml(data: &mut String, source: Option<&str>) {
+    let source = match source {
+        Some(source) => source,
+        None => return,
+    };
+
+    // Comments can't contain `--`.
+    let comment = format!("<!-- SourceFile: {} -->", source.replace("--", "- -"));
+    let declaration_end = if data.starts_with("<?xml") {
+        data.find("?>").map(|end| end + 2)
+    } else {
+        None
+    };
+
+    match declaration_end {
+        Some(end) => data.insert_str(end, &format!("\n{}", comment)),
+        None => data.insert_str(0, &format!("{}\n", comment)),
+    }
+}
+
+/// Returns `true` if the file at `path` starts with the header of a legacy `.evt` file.
+fn is_evt_file(path: &Path) -> bool {
+    let mut header = Vec::with_capacity(8);
+
+    File::open(path)
+        .and_then(|file| file.take(8).read_to_end(&mut header))
+        .map(|_| EvtFileHeader::has_signature(&header))
+        .unwrap_or(false)
+}
+
+fn exit_on_output_error(path: &str, e: Box<dyn std::error::Error>) -> ! {
+    eprintln!(
+        "An error occurred while creating output file at `{}` - `{}`",
+        path, e
+    );
+    exit(1)
+}
+
+fn is_a_non_negative_number(value: String) -> Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err("Expected value to be a positive number.".to_owned()),
+    }
+}
+
+fn is_a_valid_chunk_number(value: String) -> Result<(), String> {
+    match value.parse::<u16>() {
+        Ok(_) => Ok(()),
+        Err(_) => Err(format!("Expected a chunk number (up to {}).", u16::MAX)),
+    }
+}
+
+fn is_a_valid_interval(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => Ok(()),
+        _ => Err("Expected value to be a non-negative number of seconds.".to_owned()),
+    }
+}
+
+/// Parses a size in bytes, with an optional `K`, `M` or `G` suffix (powers of 1024).
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (digits, unit) = match value.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((i, _)) => value.split_at(i),
+        None => (value, ""),
+    };
+
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(format!("Unknown size unit `{}`", unit)),
+    };
+
+    digits
+        .parse::<u64>()
+        .map(|n| n * multiplier)
+        .map_err(|_| "Expected a size, such as `512M`".to_owned())
+}
+
+fn is_a_valid_size(value: String) -> Result<(), String> {
+    parse_size(&value).map(|_| ())
+}
+
+fn is_a_valid_filter_expression(value: String) -> Result<(), String> {
+    match RecordFilter::parse(&value) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn is_a_valid_field_regex(value: String) -> Result<(), String> {
+    let (field, pattern) = match value.split_once('=') {
+        Some(parts) => parts,
+        None => return Err("Expected `FIELD=REGEX`.".to_owned()),
+    };
+
+    let field = match Field::parse(field) {
+        Some(field) => field,
+        None => return Err(format!("Invalid field `{}`.", field)),
+    };
+
+    match RecordFilter::regex(field, pattern) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn is_a_valid_rules_file(value: String) -> Result<(), String> {
+    match FilterRules::load_file(&value) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn is_a_valid_include_file(value: String) -> Result<(), String> {
+    match FilterRules::load_include_file(&value) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn is_a_valid_exclude_file(value: String) -> Result<(), String> {
+    match FilterRules::load_exclude_file(&value) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Parses an event ID (`4624`) or an inclusive range of event IDs (`4720-4738`).
+fn parse_event_id_range(value: &str) -> Result<RangeInclusive<u16>, String> {
+    let event_id = |s: &str| {
+        s.trim()
+            .parse::<u16>()
+            .map_err(|_| format!("Invalid event ID `{}`", s))
+    };
+
+    match value.split_once('-') {
+        Some((start, end)) => Ok(event_id(start)?..=event_id(end)?),
+        None => event_id(value).map(|id| id..=id),
+    }
+}
+
+fn is_a_valid_event_id_range(value: String) -> Result<(), String> {
+    parse_event_id_range(&value).map(|_| ())
+}
+
+/// Parses a level, given by it's number or it's (standard) name.
+fn parse_levels(value: &str) -> Result<Vec<u8>, String> {
+    if let Ok(level) = value.parse::<u8>() {
+        return Ok(vec![level]);
+    }
+
+    match levels_named(value) {
+        levels if levels.is_empty() => Err(format!("Unknown level `{}`", value)),
+        levels => Ok(levels),
+    }
+}
+
+fn is_a_valid_level(value: String) -> Result<(), String> {
+    parse_levels(&value).map(|_| ())
+}
+
+fn is_a_valid_time(value: String) -> Result<(), String> {
+    match parse_time(&value) {
+        Some(_) => Ok(()),
+        None => Err(format!("Invalid time `{}`", value)),
+    }
+}
+
+/// Splits a `--redact-field` value into it's field name and pseudonym kind.
+fn split_redact_field(value: &str) -> Result<(&str, PseudonymKind), String> {
+    let (name, kind) = value.split_once('=').unwrap_or((value, "other"));
+    match PseudonymKind::parse(kind) {
+        Some(kind) if !name.trim().is_empty() => Ok((name.trim(), kind)),
+        _ => Err(format!(
+            "Expected `FIELD[=user|domain|sid|ip|host|other]`, found `{}`",
+            value
+        )),
+    }
+}
+
+fn is_a_valid_redact_field(value: String) -> Result<(), String> {
+    split_redact_field(&value).map(|_| ())
+}
+
+/// Splits a `--lookup` value into it's fields and path.
+fn split_lookup(value: &str) -> Result<(&str, &str), String> {
+    match value.split_once('=') {
+        Some((fields, path)) if !fields.trim().is_empty() && !path.is_empty() => Ok((fields, path)),
+        _ => Err(format!("Expected `FIELD=FILE`, found `{}`", value)),
+    }
+}
+
+fn is_a_valid_lookup(value: String) -> Result<(), String> {
+    let (_, path) = split_lookup(&value)?;
+    match LookupTable::load(path) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn is_a_valid_query(value: String) -> Result<(), String> {
+    match RecordFilter::from_query(&value) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn is_a_valid_xpath_query(value: String) -> Result<(), String> {
+    match RecordFilter::from_xpath(&value) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn main() {
+    let matches = App::new("EVTX Parser")
+        .version(env!("CARGO_PKG_VERSION"))
+        .setting(AppSettings::ColoredHelp)
+        .setting(AppSettings::DeriveDisplayOrder)
+        .author("Omer B. <omerbenamram@gmail.com>")
+        .about("Utility to parse EVTX files")
+        .setting(AppSettings::SubcommandsNegateReqs)
+        .subcommand(merge::subcommand())
+        .subcommand(split::subcommand())
+        .subcommand(repair::subcommand())
+        .subcommand(import::subcommand())
+        .subcommand(generate::subcommand())
+        .subcommand(validate::subcommand())
+        .subcommand(tampering::subcommand())
+        .subcommand(diff::subcommand())
+        .subcommand(browse::subcommand())
+        .subcommand(templates::subcommand())
+        .subcommand(strings::subcommand())
+        .arg(
+            Arg::with_name("INPUT")
+                .required(true)
+                .multiple(true)
+                .help(indoc!("The files to read, `-` reading from stdin. Directories are searched for `*.evtx` files (recursively),
+                       and glob patterns (such as `logs/**/Security*.evtx`) are expanded.
+                       With several files, every record is annotated with the path of it's file
+                       (a `SourceFile` field in JSON, a comment in XML), and files which fail are skipped.")),
+        )
+        .arg(
+            Arg::with_name("num-threads")
+                .short("-t")
+                .long("--threads")
+                .default_value("0")
+                .validator(is_a_non_negative_number)
+                .help("Sets the number of worker threads, defaults to number of CPU cores."),
+        )
+        .arg(
+            Arg::with_name("output-format")
+                .short("-o")
+                .long("--format")
+                .possible_values(&["json", "xml", "jsonl", "evtx"])
+                .default_value("xml")
+                .help("Sets the output format")
+                .long_help(indoc!(
+                r#"Sets the output format:
+                     "xml"   - prints XML output.
+                     "json"  - prints JSON output.
+                     "jsonl" - (jsonlines) same as json with --no-indent --dont-show-record-number 
+                     "evtx"  - writes the selected records to a new EVTX file (requires --output).
+                "#)),
+        )
+        .arg(
+            Arg::with_name("output-target")
+                .long("--output")
+                .short("-f")
+                .takes_value(true)
+                .help(indoc!("Writes output to the file specified instead of stdout, errors will still be printed to stderr.
+                       Will ask for confirmation before overwriting files, to allow overwriting, pass `--no-confirm-overwrite`
+                       Will create parent directories if needed.")),
+        )
+        .arg(
+            Arg::with_name("split-size")
+                .long("--split-size")
+                .takes_value(true)
+                .value_name("SIZE")
+                .validator(is_a_valid_size)
+                .requires("output-target")
+                .help(indoc!("When set, the output is split into files of about SIZE bytes (such as `512M`, before compression),
+                              named after `--output` with a part number (`out-0001.jsonl`, `out-0002.jsonl`...).
+                              Files are only split between records.")),
+        )
+        .arg(
+            Arg::with_name("split-records")
+                .long("--split-records")
+                .takes_value(true)
+                .value_name("N")
+                .validator(is_a_non_negative_number)
+                .requires("output-target")
+                .help("When set, the output is split into files of at most N records, named like with `--split-size`."),
+        )
+        .arg(
+            Arg::with_name("compress")
+                .long("--compress")
+                .takes_value(true)
+                .possible_values(&["gzip", "zstd"])
+                .requires("output-target")
+                .help(indoc!("Compresses the output files, adding the extension of the compression to their names (`.gz` or `.zst`).
+                              Requires the `compression` feature.")),
+        )
+        .arg(
+            Arg::with_name("no-confirm-overwrite")
+                .long("--no-confirm-overwrite")
+                .takes_value(false)
+                .help(indoc!("When set, will not ask for confirmation before overwriting files, useful for automation")),
+        )
+        .arg(
+            Arg::with_name("validate-checksums")
+                .long("--validate-checksums")
+                .takes_value(false)
+                .help(indoc!("When set, chunks with invalid checksums will not be parsed. \
+                Usually dirty files have bad checksums, so using this flag will result in fewer records.")),
+        )
+        .arg(
+            Arg::with_name("partial-records")
+                .long("--partial-records")
+                .takes_value(false)
+                .help(indoc!("When set, records with substitutions which fail to decode are written anyway, \
+                with placeholders instead of these substitutions (the errors are written to stderr).")),
+        )
+        .arg(
+            Arg::with_name("repair-chunks")
+                .long("--repair-chunks")
+                .takes_value(false)
+                .help(indoc!("When set, chunks with a damaged header (an invalid magic, or invalid checksums with \
+                `--validate-checksums`) are parsed using a header reconstructed from their records.")),
+        )
+        .arg(
+            Arg::with_name("resync-records")
+                .long("--resync-records")
+                .takes_value(false)
+                .help(indoc!("When set, a record with a corrupted header is skipped, and parsing continues at the \
+                next plausible record of it's chunk (instead of abandoning the rest of the chunk).")),
+        )
+        .arg(
+            Arg::with_name("order-by-record-id")
+                .long("--order-by-record-id")
+                .takes_value(false)
+                .help(indoc!("When set, the records of a log which has wrapped around are written in the order of \
+                their record IDs, instead of the order of their chunks in the file.")),
+        )
+        .arg(
+            Arg::with_name("no-indent")
+                .long("--no-indent")
+                .takes_value(false)
+                .help("When set, output will not be indented."),
+        )
+        .arg(
+            Arg::with_name("separate-json-attributes")
+                .long("--separate-json-attributes")
+                .takes_value(false)
+                .help("If outputting JSON, XML Element's attributes will be stored in a separate object named '<ELEMENTNAME>_attributes', with <ELEMENTNAME> containing the value of the node."),
+        )
+        .arg(
+            Arg::with_name("resolve-sids")
+                .long("--resolve-sids")
+                .takes_value(false)
+                .help("If outputting JSON, the names of well-known SIDs of records are added in an `Event.ResolvedSids` object."),
+        )
+        .arg(
+            Arg::with_name("decode-logons")
+                .long("--decode-logons")
+                .takes_value(false)
+                .help("If outputting JSON, the logon types, failure statuses and elevation of authentication events (4624, 4625, 4634 and 4672) are decoded in an `Event.Logon` object."),
+        )
+        .arg(
+            Arg::with_name("lookup")
+                .long("--lookup")
+                .takes_value(true)
+                .value_name("FIELD[,FIELD...]=FILE")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_a_valid_lookup)
+                .help(indoc!("If outputting JSON, joins the `EventData` fields against a lookup table (such as hostname
+                       to asset owner, or SID to employee) from a CSV file (keyed by it's first column) or a JSON file
+                       (an object of rows keyed by key). The columns of the matching rows are added in an
+                       `Event.Lookup` object. Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("redact")
+                .long("--redact")
+                .takes_value(false)
+                .help(indoc!("Replaces the user names, domains, SIDs, IP addresses and host names in the output by consistent
+                       pseudonyms (derived from `--redact-key`), for sharing logs with third parties.
+                       Requires JSON or EVTX output.")),
+        )
+        .arg(
+            Arg::with_name("redact-field")
+                .long("--redact-field")
+                .takes_value(true)
+                .value_name("FIELD[=KIND]")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_a_valid_redact_field)
+                .help(indoc!("Also redacts the given field, as a `user`, `domain`, `sid`, `ip`, `host` or `other` (the default) value.
+                       Implies redaction (of only the given fields, unless `--redact` is passed). Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("redact-key")
+                .long("--redact-key")
+                .takes_value(true)
+                .value_name("KEY")
+                .help(indoc!("The secret key the pseudonyms are derived from. Pass the same key to get the same pseudonyms
+                       across runs, defaults to a random key.")),
+        )
+        .arg(
+            Arg::with_name("symbolic-names")
+                .long("--symbolic-names")
+                .takes_value(false)
+                .help(indoc!("When set, the names of the `Level`, `Opcode`, `Task` and `Keywords` values of records \
+                (such as `Warning` or `Audit Failure`) are added in a `RenderingInfo` element at the end of the events.")),
+        )
+        .arg(
+            Arg::with_name("no-show-record-number")
+                .long("--dont-show-record-number")
+                .takes_value(false)
+                .help("When set, `Record <id>` will not be printed."),
+        )
+        .arg(
+            Arg::with_name("ansi-codec")
+                .long("--ansi-codec")
+                .possible_values(&encodings().iter()
+                    .filter(|&e| e.raw_decoder().is_ascii_compatible())
+                    .map(|e| e.name())
+                    .collect::<Vec<&'static str>>())
+                .default_value(encoding::all::WINDOWS_1252.name())
+                .help("When set, controls the codec of ansi encoded strings the file."),
+        )
+        .arg(
+            Arg::with_name("provider")
+                .long("--provider")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(indoc!("When set, only records from the given provider (by name or GUID) will be printed.
+                       Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("channel")
+                .long("--channel")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(indoc!("When set, only records from the given channel (e.g. `Security`) will be printed.
+                       Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("event-id")
+                .long("--event-id")
+                .takes_value(true)
+                .value_name("IDS")
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .validator(is_a_valid_event_id_range)
+                .help(indoc!("When set, only records with one of the given (comma separated) event IDs or ranges will be printed.
+                       Other records are skipped without being rendered. Example: `4624,4625,4720-4738`")),
+        )
+        .arg(
+            Arg::with_name("level")
+                .long("--level")
+                .takes_value(true)
+                .value_name("LEVELS")
+                .multiple(true)
+                .number_of_values(1)
+                .use_delimiter(true)
+                .validator(is_a_valid_level)
+                .help(indoc!("When set, only records with one of the given (comma separated) levels will be printed.
+                       Levels are numbers or names (`critical`, `error`, `warning`, `information` or `verbose`).")),
+        )
+        .arg(
+            Arg::with_name("after")
+                .long("--after")
+                .takes_value(true)
+                .value_name("TIME")
+                .validator(is_a_valid_time)
+                .help(indoc!("When set, only records created at or after the given time will be printed.
+                       The time is a date (`2023-01-01`), a UTC date and time (`2023-01-01 13:00:00`) or an RFC 3339 timestamp.
+                       Chunks of older records are skipped.")),
+        )
+        .arg(
+            Arg::with_name("before")
+                .long("--before")
+                .takes_value(true)
+                .value_name("TIME")
+                .validator(is_a_valid_time)
+                .help("When set, only records created before the given time will be printed (same format as `--after`)."),
+        )
+        .arg(
+            Arg::with_name("where")
+                .long("--where")
+                .takes_value(true)
+                .validator(is_a_valid_filter_expression)
+                .help(indoc!(r#"When set, only records matching the filter expression will be printed.
+                       Example: `EventID == 4624 && EventData.LogonType == 10 && TimeCreated >= "2023-01-01"`"#)),
+        )
+        .arg(
+            Arg::with_name("query")
+                .long("--query")
+                .takes_value(true)
+                .validator(is_a_valid_query)
+                .help(indoc!("When set, only records matching the query will be printed.
+                       Combined with `--where` and `--xpath` if they are set.
+                       Example: `EventID=4624 AND TargetUserName~'admin*' AND TimeCreated>2023-01-01`")),
+        )
+        .arg(
+            Arg::with_name("xpath")
+                .long("--xpath")
+                .takes_value(true)
+                .validator(is_a_valid_xpath_query)
+                .help(indoc!("When set, only records matching the XPath query (as used by EventViewer and `wevtutil`) will be printed.
+                       A `<QueryList>` saved by EventViewer is also accepted. Combined with `--where` and `--query` if they are set.
+                       Example: `*[System[(EventID=4624) and Level<=4]]`")),
+        )
+        .arg(
+            Arg::with_name("allow-regex")
+                .long("--allow-regex")
+                .takes_value(true)
+                .value_name("FIELD=REGEX")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_a_valid_field_regex)
+                .help(indoc!("When set, only records where the field matches one of the regexes given for it will be printed.
+                       Can be passed multiple times. Example: `EventData.CommandLine=(?i)mimikatz`")),
+        )
+        .arg(
+            Arg::with_name("deny-regex")
+                .long("--deny-regex")
+                .takes_value(true)
+                .value_name("FIELD=REGEX")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_a_valid_field_regex)
+                .help(indoc!("When set, records where the field matches one of the regexes given for it will not be printed.
+                       Can be passed multiple times. Example: `EventData.TargetUserName=\\$$`")),
+        )
+        .arg(
+            Arg::with_name("sid")
+                .long("--sid")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(indoc!("When set, only records referring to the given SID will be printed.
+                       A SID ending with `-*` matches all the SIDs starting with it (e.g. the principals of a domain).
+                       Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("account")
+                .long("--account")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(indoc!(r"When set, only records where `TargetUserName` or `SubjectUserName` is the given account will be printed.
+                       The account can be qualified by it's domain (`DOMAIN\user`).
+                       Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("activity")
+                .long("--activity")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help(indoc!("When set, only records with the given `ActivityID` (or `RelatedActivityID`) will be printed.
+                       Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("rules")
+                .long("--rules")
+                .takes_value(true)
+                .value_name("FILE")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_a_valid_rules_file)
+                .help(indoc!("Loads include and exclude rules (provider, event IDs and a filter expression)
+                       from a JSON or TOML file, see `rules/noise.toml` for an example.
+                       Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("include-file")
+                .long("--include-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_a_valid_include_file)
+                .help(indoc!("Loads filter expressions (as used by `--where`), one per line, from a file.
+                       Only records matching one of them (or an include rule of `--rules`) will be printed.
+                       Empty lines and lines starting with `#` are skipped. Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("exclude-file")
+                .long("--exclude-file")
+                .takes_value(true)
+                .value_name("FILE")
+                .multiple(true)
+                .number_of_values(1)
+                .validator(is_a_valid_exclude_file)
+                .help(indoc!("Same as `--include-file`, but records matching one of the expressions will not be printed.
+                       Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("exclude-noise")
+                .long("--exclude-noise")
+                .help(indoc!("When set, high volume events which rarely matter for triage
+                       (such as closed handles and permitted WFP connections) are not printed.")),
+        )
+        .arg(
+            Arg::with_name("largest-records")
+                .long("--largest-records")
+                .takes_value(true)
+                .value_name("N")
+                .validator(is_a_non_negative_number)
+                .help(indoc!("When set, instead of the records, prints a report of the N largest records
+                       (by their size when rendered in the output format), with their raw and rendered sizes.")),
+        )
+        .arg(
+            Arg::with_name("analyze")
+                .long("--analyze")
+                .help(indoc!("When set, instead of the records, prints a stacking analysis of the records (matching the filters):
+                       counts by provider, event ID and hour, and the most common and rarest values of the `--stack-field`s,
+                       with the first and last time they were seen. As JSON with `-o json`, as CSV otherwise.")),
+        )
+        .arg(
+            Arg::with_name("stack-field")
+                .long("--stack-field")
+                .takes_value(true)
+                .value_name("FIELD")
+                .multiple(true)
+                .number_of_values(1)
+                .help(indoc!("With `--analyze`, an `EventData` field (such as `CommandLine`) whose values are stacked.
+                       Can be passed multiple times.")),
+        )
+        .arg(
+            Arg::with_name("top")
+                .long("--top")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("10")
+                .validator(is_a_non_negative_number)
+                .help("With `--analyze`, the number of most common and rarest values of every stacked field."),
+        )
+        .arg(
+            Arg::with_name("count-by")
+                .long("--count-by")
+                .takes_value(true)
+                .possible_values(&["eventid", "provider", "hour"])
+                .help(indoc!("When set, instead of the records, prints the number of records (matching the filters)
+                       for every event ID, provider or hour. Records are not serialized.")),
+        )
+        .arg(
+            Arg::with_name("findings")
+                .long("--findings")
+                .help(indoc!("When set, instead of the records, prints the security-relevant meta-events (logs cleared,
+                       audit policy changed, event logging shut down) and the suspicious conditions (record ID gaps
+                       and duplicates, time going backwards, unreadable chunks) of the file, with their severity,
+                       as JSON with `-o json`.")),
+        )
+        .arg(
+            Arg::with_name("timestamp-tolerance")
+                .long("--timestamp-tolerance")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .default_value("1")
+                .validator(is_a_non_negative_number)
+                .help(indoc!("With `--findings`, records earlier than the previous record ID by up to
+                       this many seconds are not reported (records are routinely written slightly out of order).")),
+        )
+        .arg(
+            Arg::with_name("carve")
+                .long("--carve")
+                .help(indoc!("When set, the input is read as arbitrary data (such as a memory dump or unallocated space),
+                       and the records of the chunks found in it (or rebuilt around orphaned records) are printed.")),
+        )
+        .arg(
+            Arg::with_name("recover-slack")
+                .long("--recover-slack")
+                .help(indoc!("When set, only the records recovered from the slack space of the chunks
+                       (deleted, or partially overwritten records) are printed.")),
+        )
+        .arg(
+            Arg::with_name("dedup")
+                .long("--dedup")
+                .help(indoc!("When set, records identical to a previously printed record
+                       (same timestamp, provider, event ID and `EventData`) are not printed.")),
+        )
+        .arg(
+            Arg::with_name("dedup-on")
+                .long("--dedup-on")
+                .takes_value(true)
+                .value_name("FIELDS")
+                .use_delimiter(true)
+                .possible_values(&["timestamp", "provider", "eventid", "eventdata"])
+                .help(indoc!("Same as `--dedup`, but records are compared only on the given (comma separated) fields.")),
+        )
+        .arg(
+            Arg::with_name("head")
+                .long("--head")
+                .takes_value(true)
+                .value_name("N")
+                .validator(is_a_non_negative_number)
+                .help("When set, only the first N records will be printed."),
+        )
+        .arg(
+            Arg::with_name("tail")
+                .long("--tail")
+                .takes_value(true)
+                .value_name("N")
+                .validator(is_a_non_negative_number)
+                .help(indoc!("When set, only the last N records (the ones with the highest record IDs, of the records selected by the other options)
+                       will be printed. The chunks are read backwards, until N records are found.")),
+        )
+        .arg(
+            Arg::with_name("record-id")
+                .long("--record-id")
+                .takes_value(true)
+                .value_name("N")
+                .validator(is_a_non_negative_number)
+                .conflicts_with_all(&["chunk", "tail", "recover-slack"])
+                .help(indoc!("When set, only the record with the given `EventRecordID` will be printed (exits with 1 if it is not found).
+                       Only the chunk holding the record is read.")),
+        )
+        .arg(
+            Arg::with_name("chunk")
+                .long("--chunk")
+                .takes_value(true)
+                .value_name("N")
+                .validator(is_a_valid_chunk_number)
+                .conflicts_with_all(&["tail", "recover-slack"])
+                .help("When set, only the records of the given chunk (the first chunk being 0) will be printed."),
+        )
+        .arg(
+            Arg::with_name("hexdump")
+                .long("--hexdump")
+                .takes_value(false)
+                .help(indoc!("When set, every record is followed by a hexdump of it's bytes in the chunk
+                       (it's header and BinXML, up to the trailing copy of it's size).")),
+        )
+        .arg(
+            Arg::with_name("sort-by-timestamp")
+                .long("--sort-by-timestamp")
+                .takes_value(false)
+                .help(indoc!("When set, records are printed in timestamp order (then by record ID) instead of the order of the file.
+                       Records are kept in memory, or written to temporary files when there are more than `--sort-buffer`.
+                       `--head` applies to the sorted records.")),
+        )
+        .arg(
+            Arg::with_name("sort-buffer")
+                .long("--sort-buffer")
+                .takes_value(true)
+                .value_name("N")
+                .default_value("100000")
+                .validator(is_a_non_negative_number)
+                .help("The number of records kept in memory by `--sort-by-timestamp`."),
+        )
+        .arg(
+            Arg::with_name("follow")
+                .long("--follow")
+                .takes_value(false)
+                .conflicts_with_all(&["findings", "largest-records", "count-by", "analyze", "carve", "recover-slack", "sort-by-timestamp", "record-id", "chunk"])
+                .help(indoc!("When set, the inputs are watched for new records, which are printed as they are flushed
+                       (records already in the files are skipped, except for the last `--tail` records).
+                       Directories and glob patterns are expanded again at every poll, so new files are followed too.
+                       Runs until interrupted, or until `--head` records are printed.")),
+        )
+        .arg(
+            Arg::with_name("poll-interval")
+                .long("--poll-interval")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .default_value("1")
+                .validator(is_a_valid_interval)
+                .help("The interval at which `--follow` reads the inputs again (fractions of seconds are allowed)."),
+        )
+        .arg(
+            Arg::with_name("sample")
+                .long("--sample")
+                .takes_value(true)
+                .value_name("N")
+                .validator(is_a_non_negative_number)
+                .help("When set, only every Nth record (of the records selected by the other options) will be printed."),
+        )
+        .arg(
+            Arg::with_name("progress")
+                .long("--progress")
+                .takes_value(false)
+                .conflicts_with("follow")
+                .help(indoc!("When set, the progress (the data and records read, their rate and the estimated time left)
+                       is shown on stderr.")),
+        )
+        .arg(
+            Arg::with_name("stats")
+                .long("--stats")
+                .takes_value(false)
+                .help(indoc!("When set, a summary of the work done by the parser (chunks and records parsed,
+                       throughput, cache hit rates and time per stage) is printed to stderr.")),
+        )
+        .arg(Arg::with_name("verbose")
+            .short("-v")
+            .multiple(true)
+            .takes_value(false)
+            .help(indoc!(r#"
+            Sets debug prints level for the application:
+                -v   - info
+                -vv  - debug
+                -vvv - trace
+            NOTE: trace output is only available in debug builds, as it is extremely verbose."#))
+        )
+        .arg(
+            Arg::with_name("backtraces")
+                .long("--backtraces")
+                .takes_value(false)
+                .help("If set, a backtrace will be printed with some errors if available"))
+        .get_matches();
+
+    let subcommand = match matches.subcommand() {
+        ("merge", Some(matches)) => Some(merge::run(matches)),
+        ("split", Some(matches)) => Some(split::run(matches)),
+        ("repair", Some(matches)) => Some(repair::run(matches)),
+        ("import", Some(matches)) => Some(import::run(matches)),
+        ("generate", Some(matches)) => Some(generate::run(matches)),
+        ("validate", Some(matches)) => Some(validate::run(matches)),
+        ("tampering", Some(matches)) => Some(tampering::run(matches)),
+        ("diff", Some(matches)) => Some(diff::run(matches)),
+        ("browse", Some(matches)) => Some(browse::run(matches)),
+        ("templates", Some(matches)) => Some(templates::run(matches)),
+        ("strings", Some(matches)) => Some(strings::run(matches)),
+        _ => None,
+    };
+
+    if let Some(result) = subcommand {
+        if let Err(e) = result {
+            eprintln!("{}", &e);
+            exit(1);
+        }
+        return;
+    }
+
+    let mut app = EvtxDump::from_cli_matches(&matches);
+
+    match app.run() {
+        Ok(()) => {
+            app.print_stats();
+            if app.failed_inputs > 0 {
+                eprintln!(
+                    "Failed to process {} of {} files",
+                    app.failed_inputs,
+                    app.inputs.len()
+                );
+                exit(1);
+            }
+        }
+        Err(e) => {
+            eprintln!("{}", &e);
+            exit(1);
+        }
+    };
+}
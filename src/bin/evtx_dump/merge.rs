@@ -0,0 +1,47 @@
+//! The `merge` subcommand: writes the records of several files to a single EVTX file,
+//! in timestamp order and renumbered.
+use crate::{exit_on_output_error, EvtxDump};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use evtx::{EvtxMultiParser, EvtxWriter};
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("merge")
+        .about("Merges EVTX files into a single file, in timestamp order, renumbering the records")
+        .arg(Arg::with_name("INPUT").required(true).multiple(true))
+        .arg(
+            Arg::with_name("output-target")
+                .long("--output")
+                .short("-f")
+                .takes_value(true)
+                .required(true)
+                .help("The EVTX file to write, parent directories are created if needed."),
+        )
+        .arg(
+            Arg::with_name("no-confirm-overwrite")
+                .long("--no-confirm-overwrite")
+                .takes_value(false)
+                .help("When set, will not ask for confirmation before overwriting the output file"),
+        )
+}
+
+/// Runs the `merge` subcommand: writes the records of the input files to a single EVTX file,
+/// in timestamp order and renumbered.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let inputs: Vec<&str> = matches
+        .values_of("INPUT")
+        .expect("This is a required argument")
+        .collect();
+    let path = matches.value_of("output-target").expect("required");
+
+    let file = EvtxDump::create_output_file(path, !matches.is_present("no-confirm-overwrite"))
+        .unwrap_or_else(|e| exit_on_output_error(path, e));
+
+    let mut writer = EvtxWriter::new(file)?;
+    let written = EvtxMultiParser::from_paths(inputs).merge_into(&mut writer)?;
+    writer.finish()?;
+
+    eprintln!("Wrote {} records to {}", written, path);
+    Ok(())
+}
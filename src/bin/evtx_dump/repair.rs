@@ -0,0 +1,43 @@
+//! The `repair` subcommand: writes a repaired copy of a file which was not closed properly.
+use crate::{exit_on_output_error, EvtxDump};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use evtx::EvtxParser;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("repair")
+        .about("Writes a repaired copy of an EVTX file which was not closed properly (such as after an abrupt shutdown), \
+                fixing the record bounds and checksums of the chunks, the dirty flags and the chunk count")
+        .arg(Arg::with_name("INPUT").required(true))
+        .arg(
+            Arg::with_name("output-target")
+                .long("--output")
+                .short("-f")
+                .takes_value(true)
+                .required(true)
+                .help("The EVTX file to write, parent directories are created if needed."),
+        )
+        .arg(
+            Arg::with_name("no-confirm-overwrite")
+                .long("--no-confirm-overwrite")
+                .takes_value(false)
+                .help("When set, will not ask for confirmation before overwriting the output file"),
+        )
+}
+
+/// Runs the `repair` subcommand: writes a repaired copy of a file which was not closed properly.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let input = matches
+        .value_of("INPUT")
+        .expect("This is a required argument");
+    let path = matches.value_of("output-target").expect("required");
+
+    let file = EvtxDump::create_output_file(path, !matches.is_present("no-confirm-overwrite"))
+        .unwrap_or_else(|e| exit_on_output_error(path, e));
+
+    let report = EvtxParser::from_path(input)?.repair(file)?;
+    println!("{}", report);
+
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! The `split` subcommand: writes the records of a file to smaller EVTX files.
+use crate::{is_a_non_negative_number, is_a_valid_size, parse_size};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use evtx::{EvtxParser, SplitBy};
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("split")
+        .about("Splits an EVTX file into smaller EVTX files, by day (the default), size or record count")
+        .arg(Arg::with_name("INPUT").required(true))
+        .arg(
+            Arg::with_name("output-dir")
+                .long("--output-dir")
+                .short("-d")
+                .takes_value(true)
+                .required(true)
+                .help("The directory the files are written to, created if needed."),
+        )
+        .arg(
+            Arg::with_name("prefix")
+                .long("--prefix")
+                .takes_value(true)
+                .help("The prefix of the names of the files, defaults to the name of the input file."),
+        )
+        .arg(
+            Arg::with_name("max-size")
+                .long("--max-size")
+                .takes_value(true)
+                .value_name("SIZE")
+                .validator(is_a_valid_size)
+                .conflicts_with("max-records")
+                .help("When set, files are at most SIZE bytes (such as `512M`), in whole chunks."),
+        )
+        .arg(
+            Arg::with_name("max-records")
+                .long("--max-records")
+                .takes_value(true)
+                .value_name("N")
+                .validator(is_a_non_negative_number)
+                .help("When set, files have at most N records."),
+        )
+}
+
+/// Runs the `split` subcommand: writes the records of the input file to smaller EVTX files.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let input = Path::new(
+        matches
+            .value_of("INPUT")
+            .expect("This is a required argument"),
+    );
+    let dir = Path::new(matches.value_of("output-dir").expect("required"));
+    let prefix = matches.value_of("prefix").map_or_else(
+        || input.file_stem().unwrap_or_default().to_string_lossy(),
+        Cow::Borrowed,
+    );
+
+    let split_by = if let Some(size) = matches.value_of("max-size") {
+        SplitBy::Size(parse_size(size).expect("used validator"))
+    } else if let Some(count) = matches.value_of("max-records") {
+        SplitBy::RecordCount(count.parse().expect("used validator"))
+    } else {
+        SplitBy::Day
+    };
+
+    fs::create_dir_all(dir)?;
+    let parts = EvtxParser::from_path(input)?.split(split_by, dir, &prefix)?;
+
+    for part in parts {
+        println!(
+            "{}\t{}\t{}..={}\t{}\t{}",
+            part.path.display(),
+            part.records,
+            part.first_record_id,
+            part.last_record_id,
+            part.first_timestamp,
+            part.last_timestamp
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! The `strings` subcommand: lists the names of the string table of every chunk of a file.
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use evtx::EvtxParser;
+use serde_json::json;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("strings")
+        .about("Lists the cached strings (element and attribute names) of every chunk of an EVTX file")
+        .arg(
+            Arg::with_name("INPUT")
+                .required(true)
+                .help("The EVTX file to inspect."),
+        )
+        .arg(
+            Arg::with_name("report-format")
+                .long("--format")
+                .short("-o")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("The format of the listing, `text` printing tab separated columns and `json` a JSON object per string (on a single line)."),
+        )
+}
+
+/// Runs the `strings` subcommand: lists the names of the string table of every chunk.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = matches
+        .value_of("INPUT")
+        .expect("This is a required argument");
+    let json = matches.value_of("report-format") == Some("json");
+
+    let mut parser = EvtxParser::from_path(path)?;
+
+    if !json {
+        println!("Chunk\tOffset\tHash\tString");
+    }
+
+    for chunk in parser.chunks() {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        let chunk_number = chunk.chunk_number().unwrap_or_default();
+
+        for string in chunk.strings() {
+            if json {
+                let mut value = string.to_json_value();
+                value["chunk"] = json!(chunk_number);
+                println!("{}", value);
+            } else {
+                println!(
+                    "{}\t{}\t{:#06x}\t{}",
+                    chunk_number, string.offset, string.hash, string.value
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
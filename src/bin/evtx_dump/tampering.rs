@@ -0,0 +1,100 @@
+//! The `tampering` subcommand: reports the missing and duplicated record IDs and the timestamp regressions of files.
+use crate::{expand_inputs, is_a_non_negative_number, open_report_input, Validity};
+
+use chrono::Duration;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use indoc::indoc;
+use serde_json::json;
+use std::process::exit;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("tampering")
+        .about("Reports the missing record IDs, duplicated record IDs and timestamp regressions of EVTX files, without printing their records")
+        .long_about(indoc!("Reports the ranges of missing record IDs, the duplicated record IDs and the timestamp regressions
+            (in record ID order) of EVTX files, from the record headers only.
+            Exits with 0 if no inconsistency is found, 2 if a file has inconsistencies and 3 if a file could not be read (the worst outcome wins)."))
+        .arg(
+            Arg::with_name("INPUT")
+                .required(true)
+                .multiple(true)
+                .help("The files to check (`-` for stdin). Directories are searched for `*.evtx` files (recursively), and glob patterns are expanded."),
+        )
+        .arg(
+            Arg::with_name("report-format")
+                .long("--format")
+                .short("-o")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("The format of the reports, `json` printing a JSON object per file (on a single line)."),
+        )
+        .arg(
+            Arg::with_name("timestamp-tolerance")
+                .long("--timestamp-tolerance")
+                .takes_value(true)
+                .value_name("SECONDS")
+                .default_value("1")
+                .validator(is_a_non_negative_number)
+                .help(indoc!("Records earlier than the previous record ID by up to this many seconds are not reported
+                       (records are routinely written slightly out of order).")),
+        )
+}
+
+/// Runs the `tampering` subcommand: reports the inconsistencies of every file, and exits with a code for the worst of them.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let values: Vec<&str> = matches
+        .values_of("INPUT")
+        .expect("This is a required argument")
+        .collect();
+    let inputs = match expand_inputs(&values) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1)
+        }
+    };
+    let json = matches.value_of("report-format") == Some("json");
+    let tolerance = Duration::seconds(
+        matches
+            .value_of("timestamp-tolerance")
+            .expect("has default")
+            .parse::<i64>()
+            .expect("used validator"),
+    );
+
+    let mut worst = Validity::Valid;
+
+    for input in &inputs {
+        let outcome =
+            open_report_input(input).and_then(|mut parser| parser.tampering_report(tolerance));
+
+        let validity = match &outcome {
+            Ok(report) if report.is_clean() => Validity::Valid,
+            Ok(_) => Validity::Damaged,
+            Err(_) => Validity::Unreadable,
+        };
+        worst = worst.max(validity);
+
+        match outcome {
+            Ok(report) if json => println!(
+                "{}",
+                json!({"path": input, "clean": report.is_clean(), "report": report.to_json_value()})
+            ),
+            Ok(report) => {
+                println!("{}:", input.display());
+                for line in report.to_string().lines() {
+                    println!("  {}", line);
+                }
+            }
+            Err(e) if json => println!("{}", json!({"path": input, "error": e.to_string()})),
+            Err(e) => println!("{}: {}\n  {}", input.display(), validity.as_str(), e),
+        }
+    }
+
+    if worst != Validity::Valid {
+        exit(worst.exit_code());
+    }
+
+    Ok(())
+}
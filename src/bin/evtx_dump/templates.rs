@@ -0,0 +1,110 @@
+//! The `templates` subcommand: lists the template definitions of every chunk of a file.
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use evtx::{ChunkTemplate, EvtxParser, ParserSettings};
+use serde_json::json;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("templates")
+        .about("Lists the template definitions (GUID, size and skeleton XML) of every chunk of an EVTX file")
+        .arg(
+            Arg::with_name("INPUT")
+                .required(true)
+                .help("The EVTX file to inspect."),
+        )
+        .arg(
+            Arg::with_name("report-format")
+                .long("--format")
+                .short("-o")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("The format of the listing, `text` printing tab separated columns and `json` a JSON object per template (on a single line)."),
+        )
+        .arg(
+            Arg::with_name("unique")
+                .long("--unique")
+                .takes_value(false)
+                .help("When set, every template GUID is listed once, with the number of chunks defining it."),
+        )
+}
+
+/// Runs the `templates` subcommand: lists the template definitions of every chunk.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = matches
+        .value_of("INPUT")
+        .expect("This is a required argument");
+    let json = matches.value_of("report-format") == Some("json");
+    let unique = matches.is_present("unique");
+
+    let mut parser = EvtxParser::from_path(path)?;
+    let ansi_codec = ParserSettings::default().get_ansi_codec();
+
+    // With `--unique`, the first definition of every GUID, and the number of chunks defining it.
+    let mut by_guid: Vec<(ChunkTemplate, u16, usize)> = vec![];
+
+    if !json && !unique {
+        println!("Chunk\tOffset\tGUID\tSize\tSubstitutions\tSkeleton");
+    }
+
+    for chunk in parser.chunks() {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                eprintln!("{}", e);
+                continue;
+            }
+        };
+        let chunk_number = chunk.chunk_number().unwrap_or_default();
+
+        for template in chunk.templates(ansi_codec) {
+            if unique {
+                match by_guid.iter_mut().find(|(t, _, _)| t.guid == template.guid) {
+                    Some((_, _, chunks)) => *chunks += 1,
+                    None => by_guid.push((template, chunk_number, 1)),
+                }
+            } else if json {
+                let mut value = template.to_json_value();
+                value["chunk"] = json!(chunk_number);
+                println!("{}", value);
+            } else {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    chunk_number,
+                    template.offset,
+                    template.guid,
+                    template.data_size,
+                    template.substitutions,
+                    template.skeleton
+                );
+            }
+        }
+    }
+
+    if unique {
+        if !json {
+            println!("GUID\tChunks\tFirst chunk\tSize\tSubstitutions\tSkeleton");
+        }
+
+        for (template, first_chunk, chunks) in by_guid {
+            if json {
+                let mut value = template.to_json_value();
+                value["first_chunk"] = json!(first_chunk);
+                value["chunks"] = json!(chunks);
+                println!("{}", value);
+            } else {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}\t{}",
+                    template.guid,
+                    chunks,
+                    first_chunk,
+                    template.data_size,
+                    template.substitutions,
+                    template.skeleton
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
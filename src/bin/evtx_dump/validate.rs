@@ -0,0 +1,147 @@
+//! The `validate` subcommand: checks the integrity of files, without printing their records.
+use crate::{expand_inputs, open_report_input, Validity};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use evtx::err::Error;
+use evtx::{FileReport, IntegrityReport};
+use indoc::indoc;
+use serde_json::json;
+use std::process::exit;
+
+pub fn subcommand() -> App<'static, 'static> {
+    SubCommand::with_name("validate")
+        .about("Checks the integrity of EVTX files (checksums, truncation, dirty flag and record headers), without printing their records")
+        .long_about(indoc!("Checks the integrity of EVTX files (checksums, truncation, dirty flag and record headers), without printing their records.
+            Exits with 0 if every file is valid, 2 if a file is damaged and 3 if a file could not be read (the worst outcome wins).
+            A dirty file (such as a copy of a log in use) is valid, unless `--strict` is set."))
+        .arg(
+            Arg::with_name("INPUT")
+                .required(true)
+                .multiple(true)
+                .help("The files to check (`-` for stdin). Directories are searched for `*.evtx` files (recursively), and glob patterns are expanded."),
+        )
+        .arg(
+            Arg::with_name("report-format")
+                .long("--format")
+                .short("-o")
+                .takes_value(true)
+                .possible_values(&["text", "json"])
+                .default_value("text")
+                .help("The format of the reports, `json` printing a JSON object per file (on a single line)."),
+        )
+        .arg(
+            Arg::with_name("parse-records")
+                .long("--parse-records")
+                .takes_value(false)
+                .help("When set, every record is also parsed, and the records which cannot be parsed are reported."),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("--strict")
+                .takes_value(false)
+                .help("When set, dirty files are reported as damaged."),
+        )
+}
+
+/// The report of a file, which is the file report when records are parsed, the integrity report otherwise.
+struct FileValidation {
+    clean: bool,
+    dirty: bool,
+    report: serde_json::Value,
+    text: String,
+}
+
+impl From<IntegrityReport> for FileValidation {
+    fn from(report: IntegrityReport) -> Self {
+        FileValidation {
+            clean: report.is_valid(),
+            dirty: report.file_header.as_ref().map_or(false, |h| h.dirty),
+            report: report.to_json_value(),
+            text: report.to_string(),
+        }
+    }
+}
+
+impl From<FileReport> for FileValidation {
+    fn from(report: FileReport) -> Self {
+        FileValidation {
+            report: report.to_json_value(),
+            text: report.to_string(),
+            clean: report.is_clean(),
+            ..FileValidation::from(report.integrity)
+        }
+    }
+}
+
+/// Runs the `validate` subcommand: checks the integrity of every file, and exits with a code for the worst of them.
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let values: Vec<&str> = matches
+        .values_of("INPUT")
+        .expect("This is a required argument")
+        .collect();
+    let inputs = match expand_inputs(&values) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1)
+        }
+    };
+    let json = matches.value_of("report-format") == Some("json");
+    let parse_records = matches.is_present("parse-records");
+    let strict = matches.is_present("strict");
+
+    let mut counts = [0; 3];
+    let mut worst = Validity::Valid;
+
+    for input in &inputs {
+        let outcome = open_report_input(input).and_then(|mut parser| {
+            if parse_records {
+                parser.file_report().map(FileValidation::from)
+            } else {
+                parser.validate().map(FileValidation::from)
+            }
+        });
+
+        let validity = match &outcome {
+            Ok(file) if file.clean && !(strict && file.dirty) => Validity::Valid,
+            Ok(_) => Validity::Damaged,
+            Err(_) => Validity::Unreadable,
+        };
+        counts[validity as usize] += 1;
+        worst = worst.max(validity);
+
+        match outcome {
+            Ok(file) if json => println!(
+                "{}",
+                json!({"path": input, "status": validity.as_str(), "dirty": file.dirty, "report": file.report})
+            ),
+            Ok(file) => {
+                println!("{}: {}", input.display(), validity.as_str());
+                for line in file.text.lines() {
+                    println!("  {}", line);
+                }
+            }
+            Err(e) if json => println!(
+                "{}",
+                json!({"path": input, "status": validity.as_str(), "error": e.to_string()})
+            ),
+            Err(e) => println!("{}: {}\n  {}", input.display(), validity.as_str(), e),
+        }
+    }
+
+    if !json {
+        println!(
+            "{} files: {} valid, {} damaged, {} unreadable",
+            inputs.len(),
+            counts[Validity::Valid as usize],
+            counts[Validity::Damaged as usize],
+            counts[Validity::Unreadable as usize]
+        );
+    }
+
+    if worst != Validity::Valid {
+        exit(worst.exit_code());
+    }
+
+    Ok(())
+}
@@ -4,80 +4,157 @@ use snafu::OptionExt;
 
 use crate::binxml::value_variant::BinXmlValue;
 use crate::model::deserialized::{BinXMLDeserializedTokens, BinXmlTemplate};
-use crate::model::xml::{XmlElementBuilder, XmlModel};
+use crate::model::xml::{XmlElement, XmlElementBuilder, XmlModel};
+use crate::unimplemented_fn;
 use crate::xml_output::BinXmlOutput;
 use log::trace;
 use std::borrow::{Borrow, BorrowMut, Cow};
 
 use std::mem;
 
-pub fn parse_tokens<T: BinXmlOutput>(
-    tokens: Vec<BinXMLDeserializedTokens>,
+/// Receives the tokens of a record, as they are deserialized or as it's templates are expanded.
+pub(crate) trait TokenSink<'a> {
+    fn push_token(&mut self, token: Cow<'a, BinXMLDeserializedTokens<'a>>) -> Result<()>;
+}
+
+impl<'a> TokenSink<'a> for Vec<BinXMLDeserializedTokens<'a>> {
+    fn push_token(&mut self, token: Cow<'a, BinXMLDeserializedTokens<'a>>) -> Result<()> {
+        self.push(token.into_owned());
+        Ok(())
+    }
+}
+
+/// Receives the model of a record as it's built.
+trait ModelSink<'a> {
+    fn push_model(&mut self, model: XmlModel<'a>) -> Result<()>;
+}
+
+/// Feeds the tokens of a record to `visitor`, see `RecordRenderer`.
+pub fn parse_tokens<'a, T: BinXmlOutput>(
+    tokens: impl IntoIterator<Item = BinXMLDeserializedTokens<'a>>,
     visitor: &mut T,
 ) -> Result<()> {
-    let expanded_tokens = expand_templates(tokens);
-    let record_model = create_record_model(expanded_tokens);
+    let mut renderer = RecordRenderer::new(visitor)?;
 
-    visitor.visit_start_of_stream()?;
+    for token in tokens {
+        renderer.push_token(Cow::Owned(token))?;
+    }
 
-    let mut stack = vec![];
+    renderer.finish()
+}
 
-    for owned_token in record_model {
-        match owned_token {
+/// Renders the tokens of a record to a visitor as they are pushed.
+///
+/// Templates are expanded and the record model is built while the tokens are consumed,
+/// so neither the expanded tokens nor the model are collected.
+pub(crate) struct RecordRenderer<'a, 'v, T: BinXmlOutput> {
+    builder: RecordModelBuilder<'a, VisitorSink<'a, 'v, T>>,
+}
+
+impl<'a, 'v, T: BinXmlOutput> RecordRenderer<'a, 'v, T> {
+    pub(crate) fn new(visitor: &'v mut T) -> Result<Self> {
+        visitor.visit_start_of_stream()?;
+
+        Ok(RecordRenderer {
+            builder: RecordModelBuilder::new(VisitorSink {
+                visitor,
+                stack: vec![],
+            }),
+        })
+    }
+
+    /// Ends the record, once all of it's tokens were pushed.
+    pub(crate) fn finish(self) -> Result<()> {
+        self.builder.sink.visitor.visit_end_of_stream()
+    }
+}
+
+impl<'a, 'v, T: BinXmlOutput> TokenSink<'a> for RecordRenderer<'a, 'v, T> {
+    fn push_token(&mut self, token: Cow<'a, BinXMLDeserializedTokens<'a>>) -> Result<()> {
+        _expand_templates(token, &mut self.builder)
+    }
+}
+
+/// Passes the record model to a visitor.
+struct VisitorSink<'a, 'v, T: BinXmlOutput> {
+    visitor: &'v mut T,
+    /// The open elements.
+    stack: Vec<XmlElement<'a>>,
+}
+
+impl<'a, 'v, T: BinXmlOutput> ModelSink<'a> for VisitorSink<'a, 'v, T> {
+    fn push_model(&mut self, model: XmlModel<'a>) -> Result<()> {
+        match model {
             XmlModel::OpenElement(open_element) => {
-                stack.push(open_element);
-                visitor.visit_open_start_element(stack.last().context(
-                    err::FailedToCreateRecordModel {
-                        message: "Invalid parser state - expected stack to be non-empty",
-                    },
-                )?)?
+                self.stack.push(open_element);
+                self.visitor
+                    .visit_open_start_element(self.stack.last().context(
+                        err::FailedToCreateRecordModel {
+                            message: "Invalid parser state - expected stack to be non-empty",
+                        },
+                    )?)?
             }
             XmlModel::CloseElement => {
-                let close_element = stack.pop().context(err::FailedToCreateRecordModel {
+                let close_element = self.stack.pop().context(err::FailedToCreateRecordModel {
                     message: "Invalid parser state - expected stack to be non-empty",
                 })?;
-                visitor.visit_close_element(&close_element)?
+                self.visitor.visit_close_element(&close_element)?
             }
-            XmlModel::Value(s) => visitor.visit_characters(&s)?,
+            XmlModel::Value(s) => self.visitor.visit_characters(&s)?,
             XmlModel::EndOfStream => {}
             XmlModel::StartOfStream => {}
         };
-    }
 
-    visitor.visit_end_of_stream()?;
+        Ok(())
+    }
+}
 
-    Ok(())
+/// Builds the model of a record from expanded tokens, passing it on to a `ModelSink`.
+struct RecordModelBuilder<'a, S: ModelSink<'a>> {
+    current_element: Option<XmlElementBuilder<'a>>,
+    sink: S,
 }
 
-pub fn create_record_model<'a>(
-    tokens: Vec<Cow<'a, BinXMLDeserializedTokens<'a>>>,
-) -> Vec<XmlModel<'a>> {
-    let mut current_element: Option<XmlElementBuilder> = None;
-    let mut model: Vec<XmlModel> = Vec::with_capacity(tokens.len());
+impl<'a, S: ModelSink<'a>> RecordModelBuilder<'a, S> {
+    fn new(sink: S) -> Self {
+        RecordModelBuilder {
+            current_element: None,
+            sink,
+        }
+    }
+}
 
-    for token in tokens {
+impl<'a, S: ModelSink<'a>> TokenSink<'a> for RecordModelBuilder<'a, S> {
+    fn push_token(&mut self, token: Cow<'a, BinXMLDeserializedTokens<'a>>) -> Result<()> {
         // Handle all places where we don't care if it's an Owned or a Borrowed value.
         match token {
             Cow::Owned(BinXMLDeserializedTokens::FragmentHeader(_))
             | Cow::Borrowed(BinXMLDeserializedTokens::FragmentHeader(_)) => {}
             Cow::Owned(BinXMLDeserializedTokens::TemplateInstance(_))
             | Cow::Borrowed(BinXMLDeserializedTokens::TemplateInstance(_)) => {
-                panic!("Call `expand_templates` before calling this function")
+                panic!("Templates must be expanded before building the record model")
             }
             Cow::Owned(BinXMLDeserializedTokens::AttributeList)
             | Cow::Borrowed(BinXMLDeserializedTokens::AttributeList) => {}
 
             Cow::Owned(BinXMLDeserializedTokens::CloseElement)
             | Cow::Borrowed(BinXMLDeserializedTokens::CloseElement) => {
-                model.push(XmlModel::CloseElement);
+                self.sink.push_model(XmlModel::CloseElement)?;
             }
 
             Cow::Owned(BinXMLDeserializedTokens::CloseStartElement)
             | Cow::Borrowed(BinXMLDeserializedTokens::CloseStartElement) => {
                 trace!("BinXMLDeserializedTokens::CloseStartElement");
-                match current_element.take() {
-                    None => panic!("close start - Bad parser state"),
-                    Some(builder) => model.push(XmlModel::OpenElement(builder.finish())),
+                match self.current_element.take() {
+                    None => {
+                        return err::FailedToCreateRecordModel {
+                            message: "Invalid parser state - close start without an open element",
+                        }
+                        .fail()
+                    }
+                    Some(builder) => self
+                        .sink
+                        .push_model(XmlModel::OpenElement(builder.finish()))?,
                 };
             }
             Cow::Owned(BinXMLDeserializedTokens::CDATASection)
@@ -86,7 +163,7 @@ pub fn create_record_model<'a>(
             | Cow::Borrowed(BinXMLDeserializedTokens::CharRef) => {}
             Cow::Owned(BinXMLDeserializedTokens::EntityRef(_))
             | Cow::Borrowed(BinXMLDeserializedTokens::EntityRef(_)) => {
-                unimplemented!("EntityRef not implemented")
+                return unimplemented_fn!("EntityRef");
             }
             Cow::Owned(BinXMLDeserializedTokens::PITarget)
             | Cow::Borrowed(BinXMLDeserializedTokens::PITarget) => {}
@@ -94,45 +171,62 @@ pub fn create_record_model<'a>(
             | Cow::Borrowed(BinXMLDeserializedTokens::PIData) => {}
             Cow::Owned(BinXMLDeserializedTokens::Substitution(_))
             | Cow::Borrowed(BinXMLDeserializedTokens::Substitution(_)) => {
-                panic!("Call `expand_templates` before calling this function")
+                panic!("Templates must be expanded before building the record model")
             }
             Cow::Owned(BinXMLDeserializedTokens::EndOfStream)
             | Cow::Borrowed(BinXMLDeserializedTokens::EndOfStream) => {
-                model.push(XmlModel::EndOfStream)
+                self.sink.push_model(XmlModel::EndOfStream)?
             }
             Cow::Owned(BinXMLDeserializedTokens::StartOfStream)
             | Cow::Borrowed(BinXMLDeserializedTokens::StartOfStream) => {
-                model.push(XmlModel::StartOfStream)
+                self.sink.push_model(XmlModel::StartOfStream)?
             }
 
             Cow::Owned(BinXMLDeserializedTokens::CloseEmptyElement)
             | Cow::Borrowed(BinXMLDeserializedTokens::CloseEmptyElement) => {
                 trace!("BinXMLDeserializedTokens::CloseEmptyElement");
-                match current_element.take() {
-                    None => panic!("close empty - Bad parser state"),
+                match self.current_element.take() {
+                    None => {
+                        return err::FailedToCreateRecordModel {
+                            message: "Invalid parser state - close empty without an open element",
+                        }
+                        .fail()
+                    }
                     Some(builder) => {
-                        model.push(XmlModel::OpenElement(builder.finish()));
-                        model.push(XmlModel::CloseElement);
+                        self.sink
+                            .push_model(XmlModel::OpenElement(builder.finish()))?;
+                        self.sink.push_model(XmlModel::CloseElement)?;
                     }
                 };
             }
 
             Cow::Owned(BinXMLDeserializedTokens::Attribute(attr)) => {
                 trace!("BinXMLDeserializedTokens::Attribute(attr) - {:?}", attr);
-                match current_element.take() {
-                    None => panic!("attribute - Bad parser state"),
+                match self.current_element.take() {
+                    None => {
+                        return err::FailedToCreateRecordModel {
+                            message: "Invalid parser state - attribute without an open element",
+                        }
+                        .fail()
+                    }
                     Some(builder) => {
-                        current_element = Some(builder.attribute_name(Cow::Owned(attr.name)));
+                        self.current_element = Some(builder.attribute_name(Cow::Owned(attr.name)));
                     }
                 };
             }
 
             Cow::Borrowed(BinXMLDeserializedTokens::Attribute(attr)) => {
                 trace!("BinXMLDeserializedTokens::Attribute(attr) - {:?}", attr);
-                match current_element.take() {
-                    None => panic!("attribute - Bad parser state"),
+                match self.current_element.take() {
+                    None => {
+                        return err::FailedToCreateRecordModel {
+                            message: "Invalid parser state - attribute without an open element",
+                        }
+                        .fail()
+                    }
                     Some(builder) => {
-                        current_element = Some(builder.attribute_name(Cow::Borrowed(&attr.name)));
+                        self.current_element =
+                            Some(builder.attribute_name(Cow::Borrowed(&attr.name)));
                     }
                 };
             }
@@ -143,7 +237,7 @@ pub fn create_record_model<'a>(
                     elem.name
                 );
                 let builder = XmlElementBuilder::new();
-                current_element = Some(builder.name(Cow::Owned(elem.name)));
+                self.current_element = Some(builder.name(Cow::Owned(elem.name)));
             }
             Cow::Borrowed(BinXMLDeserializedTokens::OpenStartElement(elem)) => {
                 trace!(
@@ -151,43 +245,44 @@ pub fn create_record_model<'a>(
                     elem.name
                 );
                 let builder = XmlElementBuilder::new();
-                current_element = Some(builder.name(Cow::Borrowed(&elem.name)));
+                self.current_element = Some(builder.name(Cow::Borrowed(&elem.name)));
             }
 
             Cow::Owned(BinXMLDeserializedTokens::Value(Cow::Owned(value))) => {
                 trace!("BinXMLDeserializedTokens::Value(value) - {:?}", value);
-                match current_element.take() {
+                match self.current_element.take() {
                     // A string that is not inside any element, yield it
                     None => match value {
                         BinXmlValue::EvtXml => {
-                            panic!("Call `expand_templates` before calling this function")
+                            panic!("Templates must be expanded before building the record model")
                         }
                         _ => {
-                            model.push(XmlModel::Value(Cow::Owned(value)));
+                            self.sink.push_model(XmlModel::Value(Cow::Owned(value)))?;
                         }
                     },
                     // A string that is bound to an attribute
                     Some(builder) => {
-                        current_element = Some(builder.attribute_value(Cow::Owned(value)));
+                        self.current_element = Some(builder.attribute_value(Cow::Owned(value)));
                     }
                 };
             }
             Cow::Borrowed(BinXMLDeserializedTokens::Value(Cow::Owned(value)))
             | Cow::Owned(BinXMLDeserializedTokens::Value(Cow::Borrowed(value))) => {
                 trace!("BinXMLDeserializedTokens::Value(value) - {:?}", value);
-                match current_element.take() {
+                match self.current_element.take() {
                     // A string that is not inside any element, yield it
                     None => match value {
                         BinXmlValue::EvtXml => {
-                            panic!("Call `expand_templates` before calling this function")
+                            panic!("Templates must be expanded before building the record model")
                         }
                         _ => {
-                            model.push(XmlModel::Value(Cow::Borrowed(value)));
+                            self.sink
+                                .push_model(XmlModel::Value(Cow::Borrowed(value)))?;
                         }
                     },
                     // A string that is bound to an attribute
                     Some(builder) => {
-                        current_element = Some(builder.attribute_value(Cow::Borrowed(value)));
+                        self.current_element = Some(builder.attribute_value(Cow::Borrowed(value)));
                     }
                 };
             }
@@ -196,31 +291,33 @@ pub fn create_record_model<'a>(
             Cow::Borrowed(BinXMLDeserializedTokens::Value(Cow::Borrowed(value))) => {
                 trace!("BinXMLDeserializedTokens::Value(value) - {:?}", value);
 
-                match current_element.take() {
+                match self.current_element.take() {
                     // A string that is not inside any element, yield it
                     None => match value {
                         BinXmlValue::EvtXml => {
-                            panic!("Call `expand_templates` before calling this function")
+                            panic!("Templates must be expanded before building the record model")
                         }
                         _ => {
-                            model.push(XmlModel::Value(Cow::Borrowed(value)));
+                            self.sink
+                                .push_model(XmlModel::Value(Cow::Borrowed(value)))?;
                         }
                     },
                     // A string that is bound to an attribute
                     Some(builder) => {
-                        current_element = Some(builder.attribute_value(Cow::Borrowed(value)));
+                        self.current_element = Some(builder.attribute_value(Cow::Borrowed(value)));
                     }
                 };
             }
         }
+
+        Ok(())
     }
-    model
 }
 
 fn expand_owned_template<'a>(
     mut template: BinXmlTemplate<'a>,
-    stack: &mut Vec<Cow<'a, BinXMLDeserializedTokens<'a>>>,
-) {
+    sink: &mut impl TokenSink<'a>,
+) -> Result<()> {
     // If the template owns the definition, we can consume the tokens.
     let tokens: Vec<Cow<'a, BinXMLDeserializedTokens<'a>>> = match template.definition {
         Cow::Owned(owned_def) => owned_def.tokens.into_iter().map(Cow::Owned).collect(),
@@ -245,19 +342,20 @@ fn expand_owned_template<'a>(
 
                 _expand_templates(
                     Cow::Owned(BinXMLDeserializedTokens::Value(Cow::Owned(value))),
-                    stack,
-                );
+                    sink,
+                )?;
             }
         } else {
-            _expand_templates(token, stack);
+            _expand_templates(token, sink)?;
         }
     }
+    Ok(())
 }
 
 fn expand_borrowed_template<'a>(
     template: &'a BinXmlTemplate<'a>,
-    stack: &mut Vec<Cow<'a, BinXMLDeserializedTokens<'a>>>,
-) {
+    sink: &mut impl TokenSink<'a>,
+) -> Result<()> {
     // Here we can always use refs, since even if the definition is owned by the template,
     // we do not own it.
     for token in template.definition.as_ref().tokens.iter() {
@@ -272,26 +370,27 @@ fn expand_borrowed_template<'a>(
 
                 _expand_templates(
                     Cow::Owned(BinXMLDeserializedTokens::Value(Cow::Borrowed(value))),
-                    stack,
-                );
+                    sink,
+                )?;
             }
         } else {
-            _expand_templates(Cow::Borrowed(token), stack);
+            _expand_templates(Cow::Borrowed(token), sink)?;
         }
     }
+    Ok(())
 }
 
 fn _expand_templates<'a>(
     token: Cow<'a, BinXMLDeserializedTokens<'a>>,
-    stack: &mut Vec<Cow<'a, BinXMLDeserializedTokens<'a>>>,
-) {
+    sink: &mut impl TokenSink<'a>,
+) -> Result<()> {
     match token {
         // Owned values can be consumed when flatting, and passed on as owned.
         Cow::Owned(BinXMLDeserializedTokens::Value(Cow::Owned(BinXmlValue::BinXmlType(
             tokens,
         )))) => {
             for token in tokens.into_iter() {
-                _expand_templates(Cow::Owned(token), stack);
+                _expand_templates(Cow::Owned(token), sink)?;
             }
         }
 
@@ -306,31 +405,20 @@ fn _expand_templates<'a>(
             tokens,
         )))) => {
             for token in tokens.iter() {
-                _expand_templates(Cow::Borrowed(token), stack);
+                _expand_templates(Cow::Borrowed(token), sink)?;
             }
         }
 
         // Actual template handling.
         Cow::Owned(BinXMLDeserializedTokens::TemplateInstance(template)) => {
-            expand_owned_template(template, stack);
+            expand_owned_template(template, sink)?;
         }
         Cow::Borrowed(BinXMLDeserializedTokens::TemplateInstance(template)) => {
-            expand_borrowed_template(template, stack);
+            expand_borrowed_template(template, sink)?;
         }
 
-        _ => stack.push(token),
-    }
-}
-
-pub fn expand_templates(
-    token_tree: Vec<BinXMLDeserializedTokens>,
-) -> Vec<Cow<BinXMLDeserializedTokens>> {
-    // We can assume the new tree will be at least as big as the old one.
-    let mut stack = Vec::with_capacity(token_tree.len());
-
-    for token in token_tree {
-        _expand_templates(Cow::Owned(token), &mut stack)
+        _ => sink.push_token(token)?,
     }
 
-    stack
+    Ok(())
 }
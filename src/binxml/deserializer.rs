@@ -7,6 +7,7 @@ use byteorder::ReadBytesExt;
 use log::trace;
 use std::io::{Seek, SeekFrom};
 
+use crate::binxml::assemble::TokenSink;
 use crate::binxml::tokens::read_open_start_element;
 use crate::binxml::value_variant::BinXmlValue;
 
@@ -19,6 +20,7 @@ use crate::{
 };
 
 use crate::evtx_chunk::EvtxChunk;
+use crate::format_err;
 use encoding::EncodingRef;
 use std::borrow::Cow;
 use std::io::Cursor;
@@ -79,18 +81,7 @@ impl<'a> BinXmlDeserializer<'a> {
         );
 
         let mut tokens = vec![];
-        let mut iterator = de.iter_tokens(data_size)?;
-
-        loop {
-            let token = iterator.next();
-            if let Some(t) = token {
-                tokens.push(t?);
-            } else {
-                break;
-            }
-        }
-
-        let seek_ahead = iterator.cursor.position() - offset;
+        let seek_ahead = de.read_tokens(data_size, &mut tokens)?;
 
         trace!(
             "Position is {}, seeking {} bytes ahead",
@@ -102,6 +93,23 @@ impl<'a> BinXmlDeserializer<'a> {
         Ok(tokens)
     }
 
+    /// Reads `data_size` bytes of binary xml (or until EOF marker), pushing the tokens to `sink` as they are read.
+    /// Returns the number of bytes read.
+    pub(crate) fn read_tokens(
+        self,
+        data_size: Option<u32>,
+        sink: &mut impl TokenSink<'a>,
+    ) -> Result<u64> {
+        let offset = self.offset;
+        let mut iterator = self.iter_tokens(data_size)?;
+
+        for token in &mut iterator {
+            sink.push_token(Cow::Owned(token?))?;
+        }
+
+        Ok(iterator.cursor.position() - offset)
+    }
+
     /// Reads `data_size` bytes of binary xml, or until EOF marker.
     pub fn iter_tokens(self, data_size: Option<u32>) -> Result<IterTokens<'a>> {
         let mut cursor = Cursor::new(self.data);
@@ -233,12 +241,16 @@ impl<'a> IterTokens<'a> {
                     cursor.position()
                 );
 
-                debug_assert!(
-                    cursor.position() >= offset_from_chunk_start,
-                    "Invalid state, cursor position at entering loop {}, now at {}",
-                    offset_from_chunk_start,
-                    cursor.position()
-                );
+                // Corrupted data (such as carved records) might move the cursor backwards.
+                if cursor.position() < offset_from_chunk_start {
+                    self.eof = true;
+
+                    return Some(format_err!(
+                        "Invalid state, cursor position at entering loop {}, now at {}",
+                        offset_from_chunk_start,
+                        cursor.position()
+                    ));
+                }
 
                 Some(deserialized_token_result)
             }
@@ -0,0 +1,165 @@
+//! Decoding of BinXML fragments found outside of EVTX files,
+//! such as in registry values, ETW buffers or `WEVT_TEMPLATE` resources.
+use crate::binxml::assemble::parse_tokens;
+use crate::binxml::deserializer::BinXmlDeserializer;
+use crate::err::{self, Result};
+use crate::evtx_chunk::{EvtxChunk, EvtxChunkHeader};
+use crate::json_output::JsonOutput;
+use crate::model::deserialized::BinXMLDeserializedTokens;
+use crate::xml_output::{BinXmlOutput, XmlOutput};
+use crate::{Offset, ParserSettings};
+
+use snafu::ResultExt;
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// Decodes standalone BinXML fragments.
+///
+/// Names and template definitions are usually stored inline, or referenced by their offset in the data.
+/// When the data has tables of names or templates (like the header of a chunk), their offsets can be supplied
+/// with `string_table` and `template_table` so that they are only decoded once.
+/// Templates are also shared through the file template cache of the settings (see `ParserSettings::file_template_cache`).
+#[derive(Debug, Clone, Default)]
+pub struct BinXmlFragmentDecoder {
+    settings: Arc<ParserSettings>,
+    strings_offsets: Vec<Offset>,
+    template_offsets: Vec<Offset>,
+}
+
+/// The tokens of a decoded BinXML fragment, which do not borrow the decoded data.
+#[derive(Debug, Clone)]
+pub struct BinXmlFragment {
+    pub tokens: Vec<BinXMLDeserializedTokens<'static>>,
+    settings: Arc<ParserSettings>,
+}
+
+impl BinXmlFragmentDecoder {
+    pub fn new() -> Self {
+        BinXmlFragmentDecoder::default()
+    }
+
+    /// Decodes ANSI strings with the codec of `settings`, and renders the fragments with it's output settings.
+    pub fn with_configuration(mut self, settings: ParserSettings) -> Self {
+        self.settings = Arc::new(settings);
+        self
+    }
+
+    /// The offsets of the names cached before decoding, see `EvtxChunkHeader::strings_offsets`.
+    pub fn string_table(mut self, offsets: &[Offset]) -> Self {
+        self.strings_offsets = offsets.to_vec();
+        self
+    }
+
+    /// The offsets of the template definitions cached before decoding.
+    /// Definitions are chained by their `next_template_offset`, see `EvtxChunkHeader::template_offsets`.
+    pub fn template_table(mut self, offsets: &[Offset]) -> Self {
+        self.template_offsets = offsets.to_vec();
+        self
+    }
+
+    /// Decodes the fragment at `offset` in `data`, reading `size` bytes (or up to the end of stream token).
+    ///
+    /// Offsets of names and templates in the fragment are relative to the start of `data`.
+    pub fn decode(&self, data: &[u8], offset: u64, size: Option<u32>) -> Result<BinXmlFragment> {
+        let header = EvtxChunkHeader::with_tables(
+            self.strings_offsets.clone(),
+            self.template_offsets.clone(),
+        );
+        let context = EvtxChunk::new(data, &header, Arc::clone(&self.settings))?;
+
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(offset);
+
+        let tokens = BinXmlDeserializer::read_binxml_fragment(
+            &mut cursor,
+            Some(&context),
+            size,
+            false,
+            self.settings.get_ansi_codec(),
+        )?;
+
+        Ok(BinXmlFragment {
+            tokens: tokens
+                .iter()
+                .map(BinXMLDeserializedTokens::to_owned_token)
+                .collect(),
+            settings: Arc::clone(&self.settings),
+        })
+    }
+}
+
+impl BinXmlFragment {
+    /// Consumes the fragment, streaming it's tokens to `output_builder`.
+    pub fn into_output<T: BinXmlOutput>(self, output_builder: &mut T) -> Result<()> {
+        parse_tokens(self.tokens, output_builder)
+    }
+
+    /// Consumes the fragment, rendering it as XML.
+    pub fn into_xml(self) -> Result<String> {
+        let settings = Arc::clone(&self.settings);
+        let mut output_builder = XmlOutput::with_writer(Vec::new(), &settings);
+
+        self.into_output(&mut output_builder)?;
+
+        String::from_utf8(output_builder.into_writer()?).context(err::RecordContainsInvalidUTF8)
+    }
+
+    /// Consumes the fragment, rendering it as a `serde_json::Value`.
+    pub fn into_json_value(self) -> Result<serde_json::Value> {
+        let settings = Arc::clone(&self.settings);
+        let mut output_builder = JsonOutput::new(&settings);
+
+        self.into_output(&mut output_builder)?;
+
+        output_builder.into_value()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evtx_chunk::EvtxChunkData;
+
+    #[test]
+    fn test_decodes_records_with_supplied_tables() {
+        let evtx_file = include_bytes!("../../samples/security.evtx");
+        let mut chunk = EvtxChunkData::new(evtx_file[4096..4096 + 65536].to_vec(), true).unwrap();
+
+        let decoder = BinXmlFragmentDecoder::new()
+            .string_table(chunk.header.strings_offsets())
+            .template_table(chunk.header.template_offsets());
+        let data = chunk.data.clone();
+
+        let mut parsed = chunk.parse(Arc::new(ParserSettings::default())).unwrap();
+        let record = parsed.iter().next().unwrap().unwrap();
+        let expected = record.into_xml().unwrap().data;
+
+        // The BinXML of the first record starts after the chunk header and the record header.
+        let fragment = decoder.decode(&data, 512 + 24, None).unwrap();
+
+        assert_eq!(fragment.into_xml().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_decodes_fragments_referencing_earlier_data() {
+        let evtx_file = include_bytes!("../../samples/security.evtx");
+        let mut chunk = EvtxChunkData::new(evtx_file[4096..4096 + 65536].to_vec(), true).unwrap();
+        let data = chunk.data.clone();
+
+        let mut parsed = chunk.parse(Arc::new(ParserSettings::default())).unwrap();
+        let mut records = parsed.iter();
+        let first = records.next().unwrap().unwrap();
+        let second = records.next().unwrap().unwrap();
+
+        // The second record references the names and the template definition of the first one by offset.
+        let offset = 512 + first.data_size as u64 + 24;
+        let expected = second.into_json_value().unwrap().data;
+
+        let fragment = BinXmlFragmentDecoder::new()
+            .decode(&data, offset, None)
+            .unwrap();
+
+        assert!(!fragment.tokens.is_empty());
+        assert_eq!(fragment.into_json_value().unwrap(), expected);
+    }
+}
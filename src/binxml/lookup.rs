@@ -0,0 +1,312 @@
+use crate::binxml::value_variant::BinXmlValue;
+use crate::model::deserialized::BinXMLDeserializedTokens;
+
+use std::hash::{Hash, Hasher};
+
+/// Finds the first value of `element` in a token tree (or the value of `attribute` on `element`
+/// if an attribute is given).
+///
+/// This does not expand the templates of the record into new tokens,
+/// template substitutions are resolved in place.
+/// This makes it cheap enough to be used for filtering records before they are rendered.
+pub fn find_value<'t, 'a>(
+    tokens: &'t [BinXMLDeserializedTokens<'a>],
+    element: &str,
+    attribute: Option<&str>,
+) -> Option<&'t BinXmlValue<'a>> {
+    let mut walker = ValueWalker {
+        element,
+        attribute,
+        name: None,
+        inside_element: false,
+        inside_content: false,
+        inside_attribute: None,
+        inside_name_attribute: false,
+        name_matches: false,
+        predicate: None,
+    };
+
+    walker.walk(tokens, None)
+}
+
+/// Finds the value of the `Data` element with the given `Name` attribute
+/// (`<Data Name="LogonType">10</Data>`), as found in `EventData`.
+pub fn find_data_value<'t, 'a>(
+    tokens: &'t [BinXMLDeserializedTokens<'a>],
+    name: &str,
+) -> Option<&'t BinXmlValue<'a>> {
+    let mut walker = ValueWalker {
+        element: "Data",
+        attribute: None,
+        name: Some(name),
+        inside_element: false,
+        inside_content: false,
+        inside_attribute: None,
+        inside_name_attribute: false,
+        name_matches: false,
+        predicate: None,
+    };
+
+    walker.walk(tokens, None)
+}
+
+/// Returns true if `predicate` holds for the content of any `Data` element, whatever it's `Name` attribute.
+/// Like `find_value`, template substitutions are resolved in place.
+pub fn any_data_value(
+    tokens: &[BinXMLDeserializedTokens<'_>],
+    predicate: &mut dyn FnMut(&BinXmlValue<'_>) -> bool,
+) -> bool {
+    let mut walker = ValueWalker {
+        element: "Data",
+        attribute: None,
+        name: None,
+        inside_element: false,
+        inside_content: false,
+        inside_attribute: None,
+        inside_name_attribute: false,
+        name_matches: false,
+        predicate: Some(predicate),
+    };
+
+    walker.walk(tokens, None).is_some()
+}
+
+/// Returns true if `predicate` holds for any value of a token tree (element content or attribute value).
+/// Like `find_value`, template substitutions are resolved in place.
+pub fn any_value<'a>(
+    tokens: &[BinXMLDeserializedTokens<'a>],
+    predicate: &mut dyn FnMut(&BinXmlValue<'a>) -> bool,
+) -> bool {
+    any_value_with_substitutions(tokens, None, predicate)
+}
+
+fn any_value_with_substitutions<'a>(
+    tokens: &[BinXMLDeserializedTokens<'a>],
+    substitutions: Option<&[BinXmlValue<'a>]>,
+    predicate: &mut dyn FnMut(&BinXmlValue<'a>) -> bool,
+) -> bool {
+    tokens.iter().any(|token| {
+        let value = match token {
+            BinXMLDeserializedTokens::Value(value) => value.as_ref(),
+            BinXMLDeserializedTokens::Substitution(descriptor) if !descriptor.ignore => {
+                match substitutions.and_then(|s| s.get(descriptor.substitution_index as usize)) {
+                    Some(value) => value,
+                    None => return false,
+                }
+            }
+            BinXMLDeserializedTokens::TemplateInstance(template) => {
+                return any_value_with_substitutions(
+                    &template.definition.as_ref().tokens,
+                    Some(&template.substitution_array),
+                    predicate,
+                )
+            }
+            _ => return false,
+        };
+
+        match value {
+            BinXmlValue::BinXmlType(tokens) => {
+                any_value_with_substitutions(tokens, None, predicate)
+            }
+            _ => predicate(value),
+        }
+    })
+}
+
+/// Feeds the content of the `EventData` (or `UserData`) element of a token tree into `state`:
+/// the names of it's elements and attributes, and their values (as strings).
+/// Like `find_value`, template substitutions are resolved in place.
+pub fn hash_event_data(tokens: &[BinXMLDeserializedTokens<'_>], state: &mut dyn Hasher) {
+    let mut hasher = EventDataHasher {
+        state,
+        depth: 0,
+        event_data_depth: None,
+    };
+
+    hasher.walk(tokens, None);
+}
+
+struct EventDataHasher<'h> {
+    state: &'h mut dyn Hasher,
+    // Number of currently opened elements.
+    depth: usize,
+    // Set to the depth of the `EventData` element while inside of it.
+    event_data_depth: Option<usize>,
+}
+
+impl<'h> EventDataHasher<'h> {
+    fn walk<'a>(
+        &mut self,
+        tokens: &[BinXMLDeserializedTokens<'a>],
+        substitutions: Option<&[BinXmlValue<'a>]>,
+    ) {
+        for token in tokens {
+            match token {
+                BinXMLDeserializedTokens::OpenStartElement(elem) => {
+                    self.depth += 1;
+
+                    if self.event_data_depth.is_some() {
+                        self.write('<', &elem.name.0);
+                    } else if elem.name.0 == "EventData" || elem.name.0 == "UserData" {
+                        self.event_data_depth = Some(self.depth);
+                    }
+                }
+                BinXMLDeserializedTokens::CloseEmptyElement
+                | BinXMLDeserializedTokens::CloseElement => {
+                    if self.event_data_depth == Some(self.depth) {
+                        self.event_data_depth = None;
+                    } else if self.event_data_depth.is_some() {
+                        self.write('>', "");
+                    }
+
+                    self.depth = self.depth.saturating_sub(1);
+                }
+                BinXMLDeserializedTokens::Attribute(attr) if self.event_data_depth.is_some() => {
+                    self.write('@', &attr.name.0)
+                }
+                BinXMLDeserializedTokens::Value(value) => self.visit_value(value.as_ref()),
+                BinXMLDeserializedTokens::Substitution(descriptor) if !descriptor.ignore => {
+                    if let Some(value) =
+                        substitutions.and_then(|s| s.get(descriptor.substitution_index as usize))
+                    {
+                        self.visit_value(value)
+                    }
+                }
+                BinXMLDeserializedTokens::TemplateInstance(template) => self.walk(
+                    &template.definition.as_ref().tokens,
+                    Some(&template.substitution_array),
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    fn visit_value(&mut self, value: &BinXmlValue<'_>) {
+        match value {
+            BinXmlValue::BinXmlType(tokens) => self.walk(tokens, None),
+            value if self.event_data_depth.is_some() => self.write('=', &value.as_cow_str()),
+            _ => {}
+        }
+    }
+
+    /// Writes a tagged string, so that different structures don't hash the same.
+    fn write(&mut self, tag: char, s: &str) {
+        tag.hash(&mut self.state);
+        s.hash(&mut self.state);
+    }
+}
+
+struct ValueWalker<'q> {
+    element: &'q str,
+    attribute: Option<&'q str>,
+    // If set, only an `element` with this `Name` attribute is matched.
+    name: Option<&'q str>,
+    // Whether the last opened element is `element`.
+    inside_element: bool,
+    // Whether we are past the start tag of the last opened element.
+    inside_content: bool,
+    // Set after an attribute name, holds whether it is `attribute`.
+    inside_attribute: Option<bool>,
+    // Whether the last attribute is `Name`.
+    inside_name_attribute: bool,
+    // Whether the `Name` attribute of the last opened element is `name`.
+    name_matches: bool,
+    // If set, only values for which this holds are matched (rather than the first one).
+    predicate: Option<&'q mut dyn FnMut(&BinXmlValue<'_>) -> bool>,
+}
+
+impl<'q> ValueWalker<'q> {
+    fn walk<'t, 'a>(
+        &mut self,
+        tokens: &'t [BinXMLDeserializedTokens<'a>],
+        substitutions: Option<&'t [BinXmlValue<'a>]>,
+    ) -> Option<&'t BinXmlValue<'a>> {
+        for token in tokens {
+            let found = match token {
+                BinXMLDeserializedTokens::OpenStartElement(elem) => {
+                    self.inside_element = elem.name.0 == self.element;
+                    self.inside_content = false;
+                    self.inside_attribute = None;
+                    self.inside_name_attribute = false;
+                    self.name_matches = false;
+                    None
+                }
+                BinXMLDeserializedTokens::Attribute(attr) => {
+                    self.inside_attribute = Some(Some(attr.name.0.as_ref()) == self.attribute);
+                    self.inside_name_attribute = attr.name.0 == "Name";
+                    None
+                }
+                BinXMLDeserializedTokens::CloseStartElement => {
+                    self.inside_content = true;
+                    self.inside_attribute = None;
+                    self.inside_name_attribute = false;
+                    None
+                }
+                BinXMLDeserializedTokens::CloseEmptyElement
+                | BinXMLDeserializedTokens::CloseElement => {
+                    self.inside_element = false;
+                    self.inside_content = false;
+                    self.inside_attribute = None;
+                    self.inside_name_attribute = false;
+                    self.name_matches = false;
+                    None
+                }
+                BinXMLDeserializedTokens::Value(value) => self.visit_value(value.as_ref()),
+                BinXMLDeserializedTokens::Substitution(descriptor) => {
+                    if descriptor.ignore {
+                        None
+                    } else {
+                        substitutions
+                            .and_then(|s| s.get(descriptor.substitution_index as usize))
+                            .and_then(|value| self.visit_value(value))
+                    }
+                }
+                BinXMLDeserializedTokens::TemplateInstance(template) => self.walk(
+                    &template.definition.as_ref().tokens,
+                    Some(&template.substitution_array),
+                ),
+                _ => None,
+            };
+
+            if found.is_some() {
+                return found;
+            }
+        }
+
+        None
+    }
+
+    fn visit_value<'t, 'a>(&mut self, value: &'t BinXmlValue<'a>) -> Option<&'t BinXmlValue<'a>> {
+        if let BinXmlValue::BinXmlType(tokens) = value {
+            return self.walk(tokens, None);
+        }
+
+        if let (true, true, Some(name)) =
+            (self.inside_element, self.inside_name_attribute, self.name)
+        {
+            self.name_matches = value.as_cow_str() == name;
+        }
+
+        let is_match = self.inside_element
+            && (self.name.is_none() || self.name_matches)
+            && match (self.attribute, self.inside_attribute) {
+                (Some(_), Some(attribute_matches)) => attribute_matches,
+                (None, None) => self.inside_content,
+                _ => false,
+            }
+            && self
+                .predicate
+                .as_mut()
+                .map_or(true, |predicate| predicate(value));
+
+        // An attribute only holds a single value.
+        self.inside_attribute = None;
+        self.inside_name_attribute = false;
+
+        if is_match {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
@@ -1,6 +1,8 @@
 pub mod deserializer;
+pub mod fragment;
 pub mod name;
 pub mod value_variant;
 
 pub(crate) mod assemble;
+pub(crate) mod lookup;
 pub(crate) mod tokens;
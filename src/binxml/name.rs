@@ -38,7 +38,7 @@ impl<'a> BinXmlName<'a> {
         {
             // Seek if needed
             if name_offset == cursor.position() as u32 {
-                cursor.seek(SeekFrom::Current(i64::from(*n_bytes_read)))?;
+                cursor.seek(SeekFrom::Current(i64::from(n_bytes_read)))?;
             }
             return Ok(BinXmlName(Cow::Borrowed(name)));
         }
@@ -100,6 +100,11 @@ impl<'a> BinXmlName<'a> {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Returns a copy of the name which does not borrow the chunk it was read from.
+    pub fn to_owned_name<'b>(&self) -> BinXmlName<'b> {
+        BinXmlName(Cow::Owned(self.0.to_string()))
+    }
 }
 
 impl<'a> Into<quick_xml::events::BytesStart<'a>> for &'a BinXmlName<'a> {
@@ -2,8 +2,8 @@ use crate::err::{self, Result};
 use crate::evtx_parser::ReadSeek;
 use snafu::{OptionExt, ResultExt};
 
+use crate::guid::Guid;
 pub use byteorder::{LittleEndian, ReadBytesExt};
-use winstructs::guid::Guid;
 
 use crate::model::deserialized::*;
 use std::io::Cursor;
@@ -18,6 +18,8 @@ use std::io::Seek;
 use std::io::SeekFrom;
 
 use crate::evtx_chunk::EvtxChunk;
+use crate::stats::Counter;
+use crate::template_cache::template_guid_at;
 use encoding::EncodingRef;
 use std::borrow::Cow;
 
@@ -29,9 +31,11 @@ pub fn read_template<'a>(
     trace!("TemplateInstance at {}", cursor.position());
 
     let _ = try_read!(cursor, u8);
-    let _template_id = try_read!(cursor, u32);
+    let template_id = try_read!(cursor, u32);
     let template_definition_data_offset = try_read!(cursor, u32);
 
+    let mut resolved_from_cache = false;
+
     // If name is cached, read it and seek ahead if needed.
     let template_def = if let Some(definition) = chunk.and_then(|chunk| {
         chunk
@@ -58,20 +62,99 @@ pub fn read_template<'a>(
         );
         let position_before_seek = cursor.position();
 
-        cursor.seek(SeekFrom::Start(u64::from(template_definition_data_offset)))?;
+        let is_resident = template_guid_at(cursor.get_ref(), template_definition_data_offset)
+            .map_or(false, |guid| guid[..4] == template_id.to_le_bytes());
+
+        // The definition might be missing from this chunk, but resident in an earlier one.
+        let cached_def = match chunk {
+            Some(chunk) if !is_resident => chunk
+                .settings()
+                .get_file_template_cache()
+                .and_then(|cache| cache.get_by_id(template_id)),
+            _ => None,
+        };
 
+        let resolved_def = chunk.and_then(|chunk| {
+            chunk
+                .template_table
+                .get_resolved(template_definition_data_offset)
+        });
+
+        match (cached_def, resolved_def) {
+            (Some(template_def), _) => {
+                warn!(
+                    "Template {} is not resident at {}, using cached template",
+                    template_def.template_guid, template_definition_data_offset
+                );
+                Cow::Owned(template_def)
+            }
+            (None, Some(template_def)) => {
+                resolved_from_cache = true;
+                Cow::Owned(template_def)
+            }
+            (None, None) => {
+                cursor.seek(SeekFrom::Start(u64::from(template_definition_data_offset)))?;
+
+                let template_def = read_template_definition(cursor, chunk, ansi_codec);
+
+                cursor.seek(SeekFrom::Start(position_before_seek))?;
+
+                match (template_def, chunk) {
+                    // The definition is not in the chunk's table, cache it for the next records using it.
+                    (Ok(template_def), Some(chunk)) => {
+                        chunk
+                            .template_table
+                            .insert_resolved(template_definition_data_offset, &template_def);
+                        Cow::Owned(template_def)
+                    }
+                    (Ok(template_def), None) => Cow::Owned(template_def),
+                    // A definition which looked resident might be garbage,
+                    // the template of an earlier chunk is the last resort.
+                    (Err(err), Some(chunk)) => match chunk
+                        .settings()
+                        .get_file_template_cache()
+                        .and_then(|cache| cache.get_by_id(template_id))
+                    {
+                        Some(template_def) => {
+                            warn!(
+                                "Failed to read template at {} ({}), using cached template {}",
+                                template_definition_data_offset, err, template_def.template_guid
+                            );
+                            Cow::Owned(template_def)
+                        }
+                        None => return Err(err),
+                    },
+                    (Err(err), None) => return Err(err),
+                }
+            }
+        }
+    } else {
         let template_def = read_template_definition(cursor, chunk, ansi_codec)?;
 
-        cursor.seek(SeekFrom::Start(position_before_seek))?;
+        if let Some(chunk) = chunk {
+            chunk
+                .template_table
+                .insert_resolved(template_definition_data_offset, &template_def);
+        }
 
         Cow::Owned(template_def)
-    } else {
-        Cow::Owned(read_template_definition(cursor, chunk, ansi_codec)?)
     };
 
+    if let Some(chunk) = chunk {
+        let counter = match template_def {
+            Cow::Borrowed(_) => Counter::TemplateCacheHits,
+            Cow::Owned(_) if resolved_from_cache => Counter::TemplateCacheHits,
+            Cow::Owned(_) => Counter::TemplateCacheMisses,
+        };
+        chunk.settings().count(counter, 1);
+    }
+
     let number_of_substitutions = try_read!(cursor, u32);
 
-    let mut value_descriptors = Vec::with_capacity(number_of_substitutions as usize);
+    // Every descriptor is 4 bytes, a corrupted count should not be trusted for allocation.
+    let remaining_bytes = (cursor.get_ref().len() as u64).saturating_sub(cursor.position());
+    let mut value_descriptors =
+        Vec::with_capacity(u64::from(number_of_substitutions).min(remaining_bytes / 4) as usize);
 
     for _ in 0..number_of_substitutions {
         let size = try_read!(cursor, u16);
@@ -100,13 +183,31 @@ pub fn read_template<'a>(
             descriptor.value_type,
             position_before_reading_value
         );
-        let value = BinXmlValue::deserialize_value_type(
+        let value = match BinXmlValue::deserialize_value_type(
             &descriptor.value_type,
             cursor,
             chunk,
             Some(descriptor.size),
             ansi_codec,
-        )?;
+        ) {
+            Ok(value) => value,
+            Err(err)
+                if chunk.map_or(false, |chunk| {
+                    chunk.settings().should_render_partial_records()
+                }) =>
+            {
+                warn!(
+                    "Failed to decode substitution {:?} at {}, replacing it with a placeholder: {}",
+                    descriptor.value_type, position_before_reading_value, err
+                );
+                cursor.seek(SeekFrom::Start(
+                    position_before_reading_value + u64::from(descriptor.size),
+                ))?;
+
+                BinXmlValue::ParseError(err.to_string())
+            }
+            Err(err) => return Err(err),
+        };
 
         trace!("\t {:?}", value);
         // NullType can mean deleted substitution (and data need to be skipped)
@@ -251,10 +352,10 @@ mod test {
     use crate::binxml::tokens::read_template_definition;
     use crate::binxml::value_variant::BinXmlValue;
     use crate::ensure_env_logger_initialized;
+    use crate::guid::Guid;
     use encoding::all::WINDOWS_1252;
     use std::borrow::Cow;
     use std::io::{Cursor, Seek, SeekFrom};
-    use winstructs::guid::Guid;
 
     macro_rules! n {
         ($s: expr) => {
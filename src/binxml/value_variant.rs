@@ -8,20 +8,21 @@ pub use byteorder::{LittleEndian, ReadBytesExt};
 
 use crate::binxml::deserializer::BinXmlDeserializer;
 
-use winstructs::guid::Guid;
+use crate::guid::{Guid, GuidFormat};
 
 use crate::model::deserialized::BinXMLDeserializedTokens;
+use crate::sid::Sid;
 use crate::utils::{
     read_ansi_encoded_string, read_len_prefixed_utf16_string, read_null_terminated_utf16_string,
-    read_systemtime, read_utf16_by_size,
+    read_systemtime, read_utf16_by_size, read_utf16_by_size_in,
 };
 use chrono::{DateTime, Utc};
 use log::trace;
 use serde_json::{json, Value};
 use std::borrow::Cow;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::convert::TryFrom;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
 use std::string::ToString;
-use winstructs::security::Sid;
 
 use crate::evtx_chunk::EvtxChunk;
 use std::fmt::Write;
@@ -43,7 +44,7 @@ pub enum BinXmlValue<'a> {
     Real32Type(f32),
     Real64Type(f64),
     BoolType(bool),
-    BinaryType(&'a [u8]),
+    BinaryType(Cow<'a, [u8]>),
     GuidType(Guid),
     SizeTType(usize),
     FileTimeType(DateTime<Utc>),
@@ -79,8 +80,14 @@ pub enum BinXmlValue<'a> {
     EvtArrayHandle,
     BinXmlArrayType,
     EvtXmlArrayType,
+    /// A placeholder for a substitution which failed to decode, holding the error message.
+    /// Only produced when `ParserSettings::partial_records` is enabled.
+    ParseError(String),
 }
 
+/// Rendered instead of a value which failed to decode (except in XML text, where it is a comment).
+pub const PARSE_ERROR_PLACEHOLDER: &str = "__error__";
+
 #[derive(Debug, PartialOrd, PartialEq, Clone)]
 pub enum BinXmlValueType {
     NullType,
@@ -219,13 +226,20 @@ impl<'a> BinXmlValue<'a> {
 
         let value = match (value_type, size) {
             (BinXmlValueType::NullType, _) => BinXmlValue::NullType,
-            (BinXmlValueType::StringType, Some(sz)) => BinXmlValue::StringType(Cow::Owned(
-                read_utf16_by_size(cursor, u64::from(sz))
-                    .context(err::FailedToDecodeUTF16String {
-                        offset: cursor.position(),
-                    })?
-                    .unwrap_or_else(|| "".to_owned()),
-            )),
+            (BinXmlValueType::StringType, Some(sz)) => {
+                let s = match chunk.and_then(|chunk| chunk.arena) {
+                    Some(arena) => {
+                        read_utf16_by_size_in(cursor, u64::from(sz), arena.get_or_default())
+                            .map(|s| Cow::Borrowed(s.unwrap_or("")))
+                    }
+                    None => read_utf16_by_size(cursor, u64::from(sz))
+                        .map(|s| Cow::Owned(s.unwrap_or_default())),
+                };
+
+                BinXmlValue::StringType(s.context(err::FailedToDecodeUTF16String {
+                    offset: cursor.position(),
+                })?)
+            }
             (BinXmlValueType::StringType, None) => {
                 BinXmlValue::StringType(try_read!(cursor, utf_16_str))
             }
@@ -252,13 +266,13 @@ impl<'a> BinXmlValue<'a> {
             (BinXmlValueType::Real64Type, _) => BinXmlValue::Real64Type(try_read!(cursor, f64)),
             (BinXmlValueType::BoolType, _) => BinXmlValue::BoolType(try_read!(cursor, bool)),
             (BinXmlValueType::GuidType, _) => BinXmlValue::GuidType(try_read!(cursor, guid)),
-            // TODO: find a sample with this token.
-            (BinXmlValueType::SizeTType, _) => err::UnimplementedValueVariant {
-                name: "SizeT",
-                size,
-                offset: cursor.position(),
+            // A `SizeT` is 32 bits when the substitution says so, and 64 bits otherwise.
+            (BinXmlValueType::SizeTType, Some(4)) => {
+                BinXmlValue::SizeTType(try_read!(cursor, u32) as usize)
+            }
+            (BinXmlValueType::SizeTType, _) => {
+                BinXmlValue::SizeTType(try_read!(cursor, u64) as usize)
             }
-            .fail()?,
             (BinXmlValueType::FileTimeType, _) => {
                 BinXmlValue::FileTimeType(try_read!(cursor, filetime))
             }
@@ -293,12 +307,18 @@ impl<'a> BinXmlValue<'a> {
             (BinXmlValueType::BinaryType, Some(sz)) => {
                 // Borrow the underlying data from the cursor, and return a ref to it.
                 let data = *cursor.get_ref();
-                let bytes =
-                    &data[cursor.position() as usize..(cursor.position() + u64::from(sz)) as usize];
+                let start = cursor.position() as usize;
+                let bytes = data
+                    .get(start..start + sz as usize)
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))
+                    .context(err::FailedToRead {
+                        offset: cursor.position(),
+                        t: "binary",
+                    })?;
 
                 cursor.seek(SeekFrom::Current(i64::from(sz)))?;
 
-                BinXmlValue::BinaryType(bytes)
+                BinXmlValue::BinaryType(Cow::Borrowed(bytes))
             }
             // The array types are always sized.
             (BinXmlValueType::StringArrayType, Some(sz)) => BinXmlValue::StringArrayType(
@@ -371,6 +391,44 @@ impl<'a> BinXmlValue<'a> {
     }
 }
 
+/// Controls how `Real32`/`Real64` values are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum FloatFormat {
+    /// The shortest representation which parses back to the same value (e.g. `0.1`).
+    #[default]
+    Shortest,
+    /// A fixed number of digits after the decimal point (e.g. `0.100` with `Fixed(3)`).
+    Fixed(usize),
+    /// Scientific notation, with the shortest round-trip mantissa (e.g. `1E-1`).
+    Scientific,
+    /// Same as `Shortest`, but `Real32` JSON numbers are widened to the `f64` with the same
+    /// shortest representation (e.g. `0.1`, instead of `0.10000000149011612` with `Shortest`).
+    ShortestWidened,
+}
+
+impl FloatFormat {
+    pub fn format_f32(self, num: f32) -> String {
+        match self {
+            FloatFormat::Shortest | FloatFormat::ShortestWidened => num.to_string(),
+            FloatFormat::Fixed(precision) => format!("{:.*}", precision, num),
+            FloatFormat::Scientific => format!("{:E}", num),
+        }
+    }
+
+    pub fn format_f64(self, num: f64) -> String {
+        match self {
+            FloatFormat::Shortest | FloatFormat::ShortestWidened => num.to_string(),
+            FloatFormat::Fixed(precision) => format!("{:.*}", precision, num),
+            FloatFormat::Scientific => format!("{:E}", num),
+        }
+    }
+}
+
+/// Widens an `f32` to the `f64` with the same shortest representation, see `FloatFormat::ShortestWidened`.
+fn widen_f32(num: f32) -> f64 {
+    num.to_string().parse().unwrap_or_else(|_| f64::from(num))
+}
+
 fn to_delimited_list<N: ToString>(ns: impl AsRef<Vec<N>>) -> String {
     ns.as_ref()
         .iter()
@@ -402,7 +460,7 @@ impl<'c> Into<serde_json::Value> for BinXmlValue<'c> {
                 json!(repr)
             }
             BinXmlValue::GuidType(guid) => json!(guid.to_string()),
-            //            BinXmlValue::SizeTType(sz) => json!(sz.to_string()),
+            BinXmlValue::SizeTType(sz) => json!(sz),
             BinXmlValue::FileTimeType(tm) => json!(tm),
             BinXmlValue::SysTimeType(tm) => json!(tm),
             BinXmlValue::SidType(sid) => json!(sid.to_string()),
@@ -431,12 +489,15 @@ impl<'c> Into<serde_json::Value> for BinXmlValue<'c> {
             BinXmlValue::HexInt32ArrayType(hex_strings) => json!(hex_strings),
             BinXmlValue::HexInt64ArrayType(hex_strings) => json!(hex_strings),
             BinXmlValue::EvtHandle => {
-                panic!("Unsupported conversion, call `expand_templates` first")
+                panic!("Unsupported conversion, templates must be expanded first")
             }
             BinXmlValue::BinXmlType(_) => {
-                panic!("Unsupported conversion, call `expand_templates` first")
+                panic!("Unsupported conversion, templates must be expanded first")
+            }
+            BinXmlValue::EvtXml => {
+                panic!("Unsupported conversion, templates must be expanded first")
             }
-            BinXmlValue::EvtXml => panic!("Unsupported conversion, call `expand_templates` first"),
+            BinXmlValue::ParseError(_) => json!(PARSE_ERROR_PLACEHOLDER),
             _ => unimplemented!("{:?}", self),
         }
     }
@@ -465,7 +526,7 @@ impl<'c> Into<serde_json::Value> for &'c BinXmlValue<'c> {
                 json!(repr)
             }
             BinXmlValue::GuidType(guid) => json!(guid.to_string()),
-            //            BinXmlValue::SizeTType(sz) => json!(sz.to_string()),
+            BinXmlValue::SizeTType(sz) => json!(sz),
             BinXmlValue::FileTimeType(tm) => json!(tm),
             BinXmlValue::SysTimeType(tm) => json!(tm),
             BinXmlValue::SidType(sid) => json!(sid.to_string()),
@@ -494,18 +555,188 @@ impl<'c> Into<serde_json::Value> for &'c BinXmlValue<'c> {
             BinXmlValue::HexInt32ArrayType(hex_strings) => json!(hex_strings),
             BinXmlValue::HexInt64ArrayType(hex_strings) => json!(hex_strings),
             BinXmlValue::EvtHandle => {
-                panic!("Unsupported conversion, call `expand_templates` first")
+                panic!("Unsupported conversion, templates must be expanded first")
             }
             BinXmlValue::BinXmlType(_) => {
-                panic!("Unsupported conversion, call `expand_templates` first")
+                panic!("Unsupported conversion, templates must be expanded first")
             }
-            BinXmlValue::EvtXml => panic!("Unsupported conversion, call `expand_templates` first"),
+            BinXmlValue::EvtXml => {
+                panic!("Unsupported conversion, templates must be expanded first")
+            }
+            BinXmlValue::ParseError(_) => json!(PARSE_ERROR_PLACEHOLDER),
             _ => unimplemented!("{:?}", self),
         }
     }
 }
 
 impl<'a> BinXmlValue<'a> {
+    /// Returns a copy of the value which does not borrow the chunk it was read from.
+    pub fn to_owned_value<'b>(&self) -> BinXmlValue<'b> {
+        match self {
+            BinXmlValue::NullType => BinXmlValue::NullType,
+            BinXmlValue::EvtHandle => BinXmlValue::EvtHandle,
+            BinXmlValue::EvtXml => BinXmlValue::EvtXml,
+            BinXmlValue::AnsiStringArrayType => BinXmlValue::AnsiStringArrayType,
+            BinXmlValue::BinaryArrayType => BinXmlValue::BinaryArrayType,
+            BinXmlValue::SizeTArrayType => BinXmlValue::SizeTArrayType,
+            BinXmlValue::EvtArrayHandle => BinXmlValue::EvtArrayHandle,
+            BinXmlValue::BinXmlArrayType => BinXmlValue::BinXmlArrayType,
+            BinXmlValue::EvtXmlArrayType => BinXmlValue::EvtXmlArrayType,
+            BinXmlValue::ParseError(message) => BinXmlValue::ParseError(message.clone()),
+            BinXmlValue::StringType(s) => BinXmlValue::StringType(Cow::Owned(s.to_string())),
+            BinXmlValue::AnsiStringType(s) => {
+                BinXmlValue::AnsiStringType(Cow::Owned(s.to_string()))
+            }
+            BinXmlValue::HexInt32Type(s) => BinXmlValue::HexInt32Type(Cow::Owned(s.to_string())),
+            BinXmlValue::HexInt64Type(s) => BinXmlValue::HexInt64Type(Cow::Owned(s.to_string())),
+            BinXmlValue::Int8Type(v) => BinXmlValue::Int8Type(*v),
+            BinXmlValue::UInt8Type(v) => BinXmlValue::UInt8Type(*v),
+            BinXmlValue::Int16Type(v) => BinXmlValue::Int16Type(*v),
+            BinXmlValue::UInt16Type(v) => BinXmlValue::UInt16Type(*v),
+            BinXmlValue::Int32Type(v) => BinXmlValue::Int32Type(*v),
+            BinXmlValue::UInt32Type(v) => BinXmlValue::UInt32Type(*v),
+            BinXmlValue::Int64Type(v) => BinXmlValue::Int64Type(*v),
+            BinXmlValue::UInt64Type(v) => BinXmlValue::UInt64Type(*v),
+            BinXmlValue::Real32Type(v) => BinXmlValue::Real32Type(*v),
+            BinXmlValue::Real64Type(v) => BinXmlValue::Real64Type(*v),
+            BinXmlValue::BoolType(v) => BinXmlValue::BoolType(*v),
+            BinXmlValue::SizeTType(v) => BinXmlValue::SizeTType(*v),
+            BinXmlValue::FileTimeType(v) => BinXmlValue::FileTimeType(*v),
+            BinXmlValue::SysTimeType(v) => BinXmlValue::SysTimeType(*v),
+            BinXmlValue::GuidType(v) => BinXmlValue::GuidType(*v),
+            BinXmlValue::SidType(v) => BinXmlValue::SidType(v.clone()),
+            BinXmlValue::Int8ArrayType(v) => BinXmlValue::Int8ArrayType(v.clone()),
+            BinXmlValue::UInt8ArrayType(v) => BinXmlValue::UInt8ArrayType(v.clone()),
+            BinXmlValue::Int16ArrayType(v) => BinXmlValue::Int16ArrayType(v.clone()),
+            BinXmlValue::UInt16ArrayType(v) => BinXmlValue::UInt16ArrayType(v.clone()),
+            BinXmlValue::Int32ArrayType(v) => BinXmlValue::Int32ArrayType(v.clone()),
+            BinXmlValue::UInt32ArrayType(v) => BinXmlValue::UInt32ArrayType(v.clone()),
+            BinXmlValue::Int64ArrayType(v) => BinXmlValue::Int64ArrayType(v.clone()),
+            BinXmlValue::UInt64ArrayType(v) => BinXmlValue::UInt64ArrayType(v.clone()),
+            BinXmlValue::Real32ArrayType(v) => BinXmlValue::Real32ArrayType(v.clone()),
+            BinXmlValue::Real64ArrayType(v) => BinXmlValue::Real64ArrayType(v.clone()),
+            BinXmlValue::BoolArrayType(v) => BinXmlValue::BoolArrayType(v.clone()),
+            BinXmlValue::GuidArrayType(v) => BinXmlValue::GuidArrayType(v.clone()),
+            BinXmlValue::FileTimeArrayType(v) => BinXmlValue::FileTimeArrayType(v.clone()),
+            BinXmlValue::SysTimeArrayType(v) => BinXmlValue::SysTimeArrayType(v.clone()),
+            BinXmlValue::SidArrayType(v) => BinXmlValue::SidArrayType(v.clone()),
+            BinXmlValue::StringArrayType(v) => {
+                BinXmlValue::StringArrayType(v.iter().map(|s| Cow::Owned(s.to_string())).collect())
+            }
+            BinXmlValue::HexInt32ArrayType(v) => BinXmlValue::HexInt32ArrayType(
+                v.iter().map(|s| Cow::Owned(s.to_string())).collect(),
+            ),
+            BinXmlValue::HexInt64ArrayType(v) => BinXmlValue::HexInt64ArrayType(
+                v.iter().map(|s| Cow::Owned(s.to_string())).collect(),
+            ),
+            BinXmlValue::BinXmlType(tokens) => BinXmlValue::BinXmlType(
+                tokens
+                    .iter()
+                    .map(BinXMLDeserializedTokens::to_owned_token)
+                    .collect(),
+            ),
+            BinXmlValue::BinaryType(bytes) => BinXmlValue::BinaryType(Cow::Owned(bytes.to_vec())),
+        }
+    }
+
+    /// Like `as_cow_str_with_float_format`, but GUIDs (and arrays of them) are formatted using `guid_format`.
+    pub fn as_cow_str_with_formats(
+        &self,
+        float_format: FloatFormat,
+        guid_format: GuidFormat,
+    ) -> Cow<'_, str> {
+        match self {
+            BinXmlValue::GuidType(guid) if guid_format != GuidFormat::default() => {
+                Cow::Owned(guid_format.format(guid))
+            }
+            BinXmlValue::GuidArrayType(guids) if guid_format != GuidFormat::default() => {
+                Cow::Owned(
+                    guids
+                        .iter()
+                        .map(|guid| guid_format.format(guid))
+                        .collect::<Vec<String>>()
+                        .join(","),
+                )
+            }
+            _ => self.as_cow_str_with_float_format(float_format),
+        }
+    }
+
+    /// Like `as_cow_str`, but `Real32`/`Real64` values (and arrays of them) are formatted using `float_format`.
+    pub fn as_cow_str_with_float_format(&self, float_format: FloatFormat) -> Cow<'_, str> {
+        match self {
+            BinXmlValue::Real32Type(num) => Cow::Owned(float_format.format_f32(*num)),
+            BinXmlValue::Real64Type(num) => Cow::Owned(float_format.format_f64(*num)),
+            BinXmlValue::Real32ArrayType(numbers) => Cow::Owned(
+                numbers
+                    .iter()
+                    .map(|num| float_format.format_f32(*num))
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            BinXmlValue::Real64ArrayType(numbers) => Cow::Owned(
+                numbers
+                    .iter()
+                    .map(|num| float_format.format_f64(*num))
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            _ => self.as_cow_str(),
+        }
+    }
+
+    /// Like `to_json_with_float_format`, but GUIDs (and arrays of them) are formatted using `guid_format`.
+    pub fn to_json_with_formats(
+        &self,
+        float_format: FloatFormat,
+        guid_format: GuidFormat,
+    ) -> Value {
+        match self {
+            BinXmlValue::GuidType(guid) if guid_format != GuidFormat::default() => {
+                json!(guid_format.format(guid))
+            }
+            BinXmlValue::GuidArrayType(guids) if guid_format != GuidFormat::default() => {
+                json!(guids
+                    .iter()
+                    .map(|guid| guid_format.format(guid))
+                    .collect::<Vec<String>>())
+            }
+            _ => self.to_json_with_float_format(float_format),
+        }
+    }
+
+    /// Converts the value to JSON.
+    /// Unless `float_format` is `FloatFormat::Shortest` (or `FloatFormat::ShortestWidened`), `Real32`/`Real64` values
+    /// are converted to strings formatted using `float_format`, since JSON numbers do not carry a representation.
+    pub fn to_json_with_float_format(&self, float_format: FloatFormat) -> Value {
+        match (float_format, self) {
+            (FloatFormat::Shortest, _) => return self.clone().into(),
+            (FloatFormat::ShortestWidened, BinXmlValue::Real32Type(num)) => {
+                return json!(widen_f32(*num))
+            }
+            (FloatFormat::ShortestWidened, BinXmlValue::Real32ArrayType(numbers)) => {
+                return json!(numbers.iter().cloned().map(widen_f32).collect::<Vec<f64>>())
+            }
+            (FloatFormat::ShortestWidened, _) => return self.clone().into(),
+            _ => {}
+        }
+
+        match self {
+            BinXmlValue::Real32Type(_) | BinXmlValue::Real64Type(_) => {
+                json!(self.as_cow_str_with_float_format(float_format))
+            }
+            BinXmlValue::Real32ArrayType(numbers) => json!(numbers
+                .iter()
+                .map(|num| float_format.format_f32(*num))
+                .collect::<Vec<String>>()),
+            BinXmlValue::Real64ArrayType(numbers) => json!(numbers
+                .iter()
+                .map(|num| float_format.format_f64(*num))
+                .collect::<Vec<String>>()),
+            _ => self.clone().into(),
+        }
+    }
+
     pub fn as_cow_str(&self) -> Cow<str> {
         match self {
             BinXmlValue::NullType => Cow::Borrowed(""),
@@ -558,13 +789,78 @@ impl<'a> BinXmlValue<'a> {
             BinXmlValue::HexInt32ArrayType(hex_strings) => Cow::Owned(hex_strings.join(",")),
             BinXmlValue::HexInt64ArrayType(hex_strings) => Cow::Owned(hex_strings.join(",")),
             BinXmlValue::EvtHandle => {
-                panic!("Unsupported conversion, call `expand_templates` first")
+                panic!("Unsupported conversion, templates must be expanded first")
             }
             BinXmlValue::BinXmlType(_) => {
-                panic!("Unsupported conversion, call `expand_templates` first")
+                panic!("Unsupported conversion, templates must be expanded first")
+            }
+            BinXmlValue::EvtXml => {
+                panic!("Unsupported conversion, templates must be expanded first")
             }
-            BinXmlValue::EvtXml => panic!("Unsupported conversion, call `expand_templates` first"),
+            BinXmlValue::ParseError(_) => Cow::Borrowed(PARSE_ERROR_PLACEHOLDER),
             _ => unimplemented!("{:?}", self),
         }
     }
+
+    /// Returns the value as an unsigned integer, if it holds a non-negative integer of any width.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            BinXmlValue::Int8Type(num) => u64::try_from(num).ok(),
+            BinXmlValue::UInt8Type(num) => Some(u64::from(num)),
+            BinXmlValue::Int16Type(num) => u64::try_from(num).ok(),
+            BinXmlValue::UInt16Type(num) => Some(u64::from(num)),
+            BinXmlValue::Int32Type(num) => u64::try_from(num).ok(),
+            BinXmlValue::UInt32Type(num) => Some(u64::from(num)),
+            BinXmlValue::Int64Type(num) => u64::try_from(num).ok(),
+            BinXmlValue::UInt64Type(num) => Some(num),
+            BinXmlValue::SizeTType(num) => Some(num as u64),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_float_formats() {
+        let value = BinXmlValue::Real32Type(0.1);
+
+        assert_eq!(value.as_cow_str(), "0.1");
+        assert_eq!(
+            value.to_json_with_float_format(FloatFormat::Shortest),
+            json!(0.1_f32)
+        );
+        assert_eq!(
+            value.to_json_with_float_format(FloatFormat::ShortestWidened),
+            json!(0.1)
+        );
+        assert_eq!(
+            value.as_cow_str_with_float_format(FloatFormat::ShortestWidened),
+            "0.1"
+        );
+        assert_eq!(
+            value.as_cow_str_with_float_format(FloatFormat::Fixed(3)),
+            "0.100"
+        );
+        assert_eq!(
+            value.to_json_with_float_format(FloatFormat::Fixed(3)),
+            json!("0.100")
+        );
+        assert_eq!(
+            value.as_cow_str_with_float_format(FloatFormat::Scientific),
+            "1E-1"
+        );
+
+        let value = BinXmlValue::Real64ArrayType(vec![1.5, 1e20]);
+        assert_eq!(
+            value.as_cow_str_with_float_format(FloatFormat::Scientific),
+            "1.5E0,1E20"
+        );
+        assert_eq!(
+            value.to_json_with_float_format(FloatFormat::Fixed(1)),
+            json!(["1.5", "100000000000000000000.0"])
+        );
+    }
 }
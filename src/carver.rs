@@ -0,0 +1,335 @@
+//! Carving of EVTX chunks and records from arbitrary data, such as memory dumps or unallocated disk space.
+//!
+//! Chunks are found by their `ElfChnk` magic (at any offset, not only at multiples of the chunk size).
+//! Records outside of these chunks (whose chunk header was overwritten) are found by their magic,
+//! and placed back at their original offset in a synthetic chunk, rebuilt from the data surrounding them.
+//!
+//! ```rust
+//! use evtx::EvtxCarver;
+//!
+//! let data = std::fs::read("samples/security.evtx").unwrap();
+//! // Drop the file header, and part of the first chunk.
+//! let data = &data[4096 + 1000..];
+//!
+//! let records = EvtxCarver::new().records(data);
+//! assert!(records.iter().any(|record| record.synthetic_chunk));
+//! ```
+use crate::err::Result;
+use crate::evtx_chunk::{read_plausible_record, EvtxChunkData, EVTX_CHUNK_HEADER_SIZE};
+use crate::evtx_parser::EVTX_CHUNK_SIZE;
+use crate::evtx_record::{EvtxRecord, SerializedEvtxRecord};
+use crate::ParserSettings;
+
+use log::debug;
+
+use std::cmp::{max, min};
+use std::convert::TryInto;
+use std::ops::Range;
+use std::sync::Arc;
+
+const CHUNK_MAGIC: &[u8] = b"ElfChnk\x00";
+const RECORD_MAGIC: &[u8] = b"\x2a\x2a\x00\x00";
+
+/// The offset of the template definition from the start of a record, when the record defines it's template.
+/// It follows the record header (24 bytes), the fragment header (4 bytes) and the template instance token
+/// (a token byte, an unknown byte, the template ID and the offset of the definition).
+const RESIDENT_TEMPLATE_OFFSET: usize = 24 + 4 + 10;
+
+/// A chunk found in the carved data.
+pub struct CarvedChunk {
+    /// Offset of the chunk (or where it's header would be, for a synthetic chunk) in the carved data.
+    /// This is `0` for a synthetic chunk starting before the data.
+    pub offset: u64,
+    /// `true` if the header of the chunk was rebuilt, around records whose chunk header was overwritten.
+    /// The records of a synthetic chunk are found like the records of slack space (see `EvtxChunk::iter_slack`).
+    pub synthetic: bool,
+    pub chunk: EvtxChunkData,
+}
+
+/// A record carved from the data.
+#[derive(Debug)]
+pub struct CarvedRecord<T> {
+    /// Offset of the chunk of the record in the carved data, see `CarvedChunk::offset`.
+    pub chunk_offset: u64,
+    /// `true` if the record was found in a synthetic chunk.
+    pub synthetic_chunk: bool,
+    pub record: Result<SerializedEvtxRecord<T>>,
+}
+
+/// Carves chunks and records from arbitrary data, see the module documentation.
+#[derive(Debug, Clone, Default)]
+pub struct EvtxCarver {
+    config: Arc<ParserSettings>,
+}
+
+impl EvtxCarver {
+    pub fn new() -> Self {
+        EvtxCarver::default()
+    }
+
+    pub fn with_configuration(mut self, configuration: ParserSettings) -> Self {
+        self.config = Arc::new(configuration);
+        self
+    }
+
+    /// Returns the chunks found in `data`: intact chunks (with their own header), and synthetic chunks
+    /// rebuilt around records which are not part of any intact chunk.
+    /// Chunks with bad checksums are skipped when `ParserSettings::validate_checksums` is set.
+    pub fn carve_chunks(&self, data: &[u8]) -> Vec<CarvedChunk> {
+        let mut chunks = vec![];
+        let mut offset = 0;
+
+        while let Some(position) = find(data, offset, CHUNK_MAGIC) {
+            offset = position + 1;
+
+            let chunk_data = match data.get(position..position + EVTX_CHUNK_SIZE) {
+                Some(chunk_data) => chunk_data.to_vec(),
+                None => break,
+            };
+
+            match EvtxChunkData::new(chunk_data, self.config.should_validate_checksums()) {
+                Ok(chunk) => {
+                    debug!("Found a chunk at offset {}", position);
+
+                    chunks.push(CarvedChunk {
+                        offset: position as u64,
+                        synthetic: false,
+                        chunk,
+                    });
+                    offset = position + EVTX_CHUNK_SIZE;
+                }
+                Err(e) => debug!("Invalid chunk at offset {}: {}", position, e),
+            }
+        }
+
+        let orphans = self.synthetic_chunks(data, &chunks);
+        chunks.extend(orphans);
+        chunks.sort_by_key(|chunk| chunk.offset);
+
+        chunks
+    }
+
+    /// Rebuilds the chunks of records which are outside of `chunks`.
+    ///
+    /// The offset of a record in it's chunk is only known if the record defines it's template,
+    /// which is the case for the first record using a template in every chunk.
+    /// The other records of the chunk are then found by scanning the synthetic chunk.
+    fn synthetic_chunks(&self, data: &[u8], chunks: &[CarvedChunk]) -> Vec<CarvedChunk> {
+        // The ranges of the data covered by chunks, as chunks of the data might start before it.
+        let mut carved: Vec<Range<i64>> = chunks
+            .iter()
+            .map(|chunk| chunk.offset as i64..chunk.offset as i64 + EVTX_CHUNK_SIZE as i64)
+            .collect();
+
+        let mut synthetic = vec![];
+        let mut offset = 0;
+
+        while let Some(position) = find(data, offset, RECORD_MAGIC) {
+            offset = position + 1;
+
+            if carved
+                .iter()
+                .any(|range| range.contains(&(position as i64)))
+            {
+                continue;
+            }
+
+            let offset_in_chunk = match resident_record_offset(&data[position..]) {
+                Some(offset_in_chunk) => offset_in_chunk,
+                None => continue,
+            };
+
+            let chunk_offset = position as i64 - offset_in_chunk as i64;
+            debug!(
+                "Found a record at offset {}, rebuilding it's chunk at offset {}",
+                position, chunk_offset
+            );
+
+            carved.push(chunk_offset..chunk_offset + EVTX_CHUNK_SIZE as i64);
+
+            if let Ok(chunk) = EvtxChunkData::new(synthetic_chunk_data(data, chunk_offset), false) {
+                synthetic.push(CarvedChunk {
+                    offset: max(chunk_offset, 0) as u64,
+                    synthetic: true,
+                    chunk,
+                });
+            }
+        }
+
+        synthetic
+    }
+
+    /// Returns the records of the chunks carved from `data`, mapped using `f`.
+    pub fn serialized_records<U>(
+        &self,
+        data: &[u8],
+        mut f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<SerializedEvtxRecord<U>>,
+    ) -> Vec<CarvedRecord<U>> {
+        let mut records = vec![];
+
+        for mut carved in self.carve_chunks(data) {
+            let (chunk_offset, synthetic_chunk) = (carved.offset, carved.synthetic);
+            let carved_record = |record: Result<SerializedEvtxRecord<U>>| CarvedRecord {
+                chunk_offset,
+                synthetic_chunk,
+                record,
+            };
+
+            let mut chunk = match carved.chunk.parse(Arc::clone(&self.config)) {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    records.push(carved_record(Err(err)));
+                    continue;
+                }
+            };
+
+            if synthetic_chunk {
+                // Most of the rebuilt chunk is not records, only the plausible ones are returned.
+                records.extend(chunk.iter_slack().map(&mut f).map(carved_record));
+            } else {
+                records.extend(chunk.iter().map(&mut f).map(carved_record));
+            }
+        }
+
+        records
+    }
+
+    /// Returns the records carved from `data`, XML-formatted.
+    pub fn records(&self, data: &[u8]) -> Vec<CarvedRecord<String>> {
+        self.serialized_records(data, |record| record.and_then(|record| record.into_xml()))
+    }
+
+    /// Returns the records carved from `data`, JSON-formatted.
+    pub fn records_json(&self, data: &[u8]) -> Vec<CarvedRecord<String>> {
+        self.serialized_records(data, |record| record.and_then(|record| record.into_json()))
+    }
+}
+
+/// Returns the position of the first occurrence of `pattern` in `data`, starting at `offset`.
+fn find(data: &[u8], offset: usize, pattern: &[u8]) -> Option<usize> {
+    data.get(offset..)?
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|position| offset + position)
+}
+
+/// Returns the offset of the record starting `data` in it's chunk, if it is a plausible record
+/// which defines it's template (since the template definition is then right after the record header).
+fn resident_record_offset(data: &[u8]) -> Option<usize> {
+    let header = read_plausible_record(data, 0).ok()?;
+    let size = header.data_size as usize;
+
+    if size > EVTX_CHUNK_SIZE - EVTX_CHUNK_HEADER_SIZE {
+        return None;
+    }
+
+    // A fragment header, followed by a template instance.
+    if data.get(24..29)? != [0x0f, 0x01, 0x01, 0x00, 0x0c] {
+        return None;
+    }
+
+    let definition_offset = u32::from_le_bytes(data.get(34..38)?.try_into().ok()?) as usize;
+    let offset_in_chunk = definition_offset.checked_sub(RESIDENT_TEMPLATE_OFFSET)?;
+
+    if offset_in_chunk < EVTX_CHUNK_HEADER_SIZE || offset_in_chunk + size > EVTX_CHUNK_SIZE {
+        return None;
+    }
+
+    Some(offset_in_chunk)
+}
+
+/// Copies the chunk sized data at `chunk_offset` (padded with zeros where it is outside of `data`),
+/// replacing it's header with one without string and template tables
+/// (names and templates are read from their offsets in the chunk).
+/// The free space offset points to the end of the header, so all of the chunk is scanned for records.
+fn synthetic_chunk_data(data: &[u8], chunk_offset: i64) -> Vec<u8> {
+    let padding = (-chunk_offset).clamp(0, EVTX_CHUNK_SIZE as i64) as usize;
+    let start = max(chunk_offset, 0) as usize;
+    let end = min(start + EVTX_CHUNK_SIZE - padding, data.len());
+
+    let mut chunk_data = vec![0; padding];
+    chunk_data.extend_from_slice(&data[start..end]);
+    chunk_data.resize(EVTX_CHUNK_SIZE, 0);
+
+    let header = &mut chunk_data[..EVTX_CHUNK_HEADER_SIZE];
+    header.iter_mut().for_each(|b| *b = 0);
+    header[..8].copy_from_slice(CHUNK_MAGIC);
+    // Header size.
+    header[40..44].copy_from_slice(&128_u32.to_le_bytes());
+    // Free space offset.
+    header[48..52].copy_from_slice(&(EVTX_CHUNK_HEADER_SIZE as u32).to_le_bytes());
+
+    chunk_data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evtx_parser::EVTX_FILE_HEADER_SIZE;
+    use crate::EvtxParser;
+
+    #[test]
+    fn test_carves_chunks_at_any_offset() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        // Chunks surrounded by garbage, not aligned to the chunk size.
+        let mut data = vec![0xaa; 1234];
+        data.extend_from_slice(&evtx_file[EVTX_FILE_HEADER_SIZE..]);
+        data.extend(vec![0xbb; 4321]);
+
+        let records = EvtxCarver::new().records(&data);
+
+        let expected: Vec<String> = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .records()
+            .map(|record| record.unwrap().data)
+            .collect();
+        let carved: Vec<String> = records
+            .into_iter()
+            .map(|carved| {
+                assert!(!carved.synthetic_chunk);
+                carved.record.unwrap().data
+            })
+            .collect();
+
+        assert_eq!(carved, expected);
+    }
+
+    #[test]
+    fn test_rebuilds_chunks_of_orphaned_records() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let chunk = &evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE];
+
+        let expected: Vec<(u64, String)> = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .records()
+            .map(|record| record.unwrap())
+            .filter(|record| record.event_record_id <= 91)
+            .map(|record| (record.event_record_id, record.data))
+            .collect();
+
+        // Overwrite the chunk header.
+        let mut data = vec![0xcc; 100];
+        data.extend_from_slice(chunk);
+        data[100..100 + EVTX_CHUNK_HEADER_SIZE]
+            .iter_mut()
+            .for_each(|b| *b = 0);
+
+        let chunks = EvtxCarver::new().carve_chunks(&data);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].offset, 100);
+        assert!(chunks[0].synthetic);
+
+        let carved: Vec<(u64, String)> = EvtxCarver::new()
+            .records(&data)
+            .into_iter()
+            .filter_map(|carved| carved.record.ok())
+            .map(|record| (record.event_record_id, record.data))
+            .collect();
+
+        // Records using a template of the (lost) template table of the chunk can still be rendered,
+        // since the template is read from it's offset.
+        assert!(carved.len() >= expected.len(), "{}", carved.len());
+        assert_eq!(carved[..expected.len()], expected[..]);
+    }
+}
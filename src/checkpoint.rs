@@ -0,0 +1,70 @@
+//! Checkpoints, for parsing only the records appended to a live evtx file since an earlier run.
+use serde_json::{json, Value};
+use std::convert::TryInto;
+
+/// The position of the last record read from a file, see `EvtxParser::checkpoint`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// CRC32 of the last record (it's header and data), which identifies the file it was read from.
+    /// It does not match if the file was replaced, or cleared and written again.
+    pub file_signature: u32,
+    /// The number of the chunk holding the last record.
+    pub last_chunk_number: u16,
+    pub last_record_id: u64,
+}
+
+impl Checkpoint {
+    pub fn to_json_value(&self) -> Value {
+        json!({
+            "file_signature": self.file_signature,
+            "last_chunk_number": self.last_chunk_number,
+            "last_record_id": self.last_record_id,
+        })
+    }
+
+    /// Reads a checkpoint written by `to_json_value`, `None` if any of the fields is missing or invalid.
+    pub fn from_json_value(value: &Value) -> Option<Self> {
+        let field = |name: &str| value.get(name).and_then(Value::as_u64);
+
+        Some(Checkpoint {
+            file_signature: field("file_signature")?.try_into().ok()?,
+            last_chunk_number: field("last_chunk_number")?.try_into().ok()?,
+            last_record_id: field("last_record_id")?,
+        })
+    }
+}
+
+/// How a checkpoint relates to the current content of a file, see `EvtxParser::checkpoint_status`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointStatus {
+    /// The last record is still in the file, parsing resumes right after it.
+    Valid,
+    /// The last record was overwritten after the log wrapped around.
+    /// Parsing resumes after it, but the records overwritten since the checkpoint are lost.
+    Overwritten,
+    /// The file does not hold the last record (it was replaced, or cleared), all of it's records are parsed.
+    Replaced,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_json_roundtrip() {
+        let checkpoint = Checkpoint {
+            file_signature: 0xdead_beef,
+            last_chunk_number: 3,
+            last_record_id: 1234,
+        };
+
+        assert_eq!(
+            Checkpoint::from_json_value(&checkpoint.to_json_value()),
+            Some(checkpoint)
+        );
+        assert_eq!(
+            Checkpoint::from_json_value(&json!({"last_record_id": 1})),
+            None
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! The template definitions and the names of the string table of a chunk,
+//! see `EvtxChunkData::templates` and `EvtxChunkData::strings`.
+use crate::binxml::name::BinXmlName;
+use crate::binxml::tokens::read_template_definition;
+use crate::evtx_chunk::EvtxChunkData;
+use crate::model::deserialized::BinXMLDeserializedTokens;
+use crate::{Guid, Offset};
+
+use encoding::EncodingRef;
+use log::warn;
+use serde_json::{json, Value};
+
+use std::collections::HashSet;
+use std::convert::TryInto;
+use std::io::Cursor;
+
+/// A template definition of the template table of a chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkTemplate {
+    /// Offset of the definition from the start of the chunk.
+    pub offset: Offset,
+    pub guid: Guid,
+    /// The size of the BinXML of the definition.
+    pub data_size: u32,
+    /// The number of substitutions (the highest substitution index, plus one).
+    pub substitutions: usize,
+    /// The definition as XML, with `{index:type}` placeholders for the substitutions.
+    pub skeleton: String,
+}
+
+/// A name of the string table of a chunk (the names of the elements and attributes of it's templates).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkString {
+    /// Offset of the name from the start of the chunk.
+    pub offset: Offset,
+    pub hash: u16,
+    pub value: String,
+}
+
+impl ChunkTemplate {
+    pub fn to_json_value(&self) -> Value {
+        json!({
+            "offset": self.offset,
+            "guid": self.guid.to_string(),
+            "data_size": self.data_size,
+            "substitutions": self.substitutions,
+            "skeleton": self.skeleton,
+        })
+    }
+}
+
+impl ChunkString {
+    pub fn to_json_value(&self) -> Value {
+        json!({
+            "offset": self.offset,
+            "hash": self.hash,
+            "value": self.value,
+        })
+    }
+}
+
+impl EvtxChunkData {
+    /// Reads the template definitions of the template table of the chunk, sorted by offset.
+    ///
+    /// Definitions chained to the ones of the table (by their `next_template_offset`) are included,
+    /// and definitions which cannot be read are skipped.
+    pub fn templates(&self, ansi_codec: EncodingRef) -> Vec<ChunkTemplate> {
+        let mut templates = vec![];
+
+        for offset in chained_offsets(&self.data, self.header.template_offsets()) {
+            let mut cursor = Cursor::new(self.data.as_slice());
+            cursor.set_position(u64::from(offset));
+
+            match read_template_definition(&mut cursor, None, ansi_codec) {
+                Ok(definition) => templates.push(ChunkTemplate {
+                    offset,
+                    guid: definition.template_guid,
+                    data_size: definition.data_size,
+                    substitutions: substitution_count(&definition.tokens),
+                    skeleton: skeleton(&definition.tokens),
+                }),
+                Err(e) => warn!("Failed to read template at {}: {}", offset, e),
+            }
+        }
+
+        templates
+    }
+
+    /// Reads the names of the string table of the chunk (following their chains), sorted by offset.
+    pub fn strings(&self) -> Vec<ChunkString> {
+        let mut strings = vec![];
+
+        for offset in chained_offsets(&self.data, self.header.strings_offsets()) {
+            let mut cursor = Cursor::new(self.data.as_slice());
+            cursor.set_position(u64::from(offset));
+
+            match BinXmlName::from_stream(&mut cursor) {
+                Ok((value, hash, _)) => strings.push(ChunkString {
+                    offset,
+                    hash,
+                    value,
+                }),
+                Err(e) => warn!("Failed to read string at {}: {}", offset, e),
+            }
+        }
+
+        strings
+    }
+}
+
+/// Returns the (sorted) offsets of the entries of a table of the chunk header,
+/// and of the entries chained to them (every entry starts with the offset of the next one).
+fn chained_offsets(data: &[u8], table: &[Offset]) -> Vec<Offset> {
+    let mut offsets = HashSet::new();
+
+    for &head in table {
+        let mut offset = head;
+
+        // Chains of corrupted chunks can loop, or lead outside of the chunk.
+        while offset > 0 && (offset as usize) < data.len() && offsets.insert(offset) {
+            offset = data
+                .get(offset as usize..offset as usize + 4)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map_or(0, u32::from_le_bytes);
+        }
+    }
+
+    let mut offsets: Vec<Offset> = offsets.into_iter().collect();
+    offsets.sort_unstable();
+    offsets
+}
+
+fn substitution_count(tokens: &[BinXMLDeserializedTokens]) -> usize {
+    tokens
+        .iter()
+        .filter_map(|token| match token {
+            BinXMLDeserializedTokens::Substitution(descriptor) => {
+                Some(usize::from(descriptor.substitution_index) + 1)
+            }
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Renders the tokens of a template definition as (unindented) XML.
+fn skeleton(tokens: &[BinXMLDeserializedTokens]) -> String {
+    let mut xml = String::new();
+    let mut open_elements = vec![];
+    let mut in_attribute = false;
+
+    for token in tokens {
+        // An attribute value ends with the next attribute, or with the start tag.
+        if in_attribute
+            && matches!(
+                token,
+                BinXMLDeserializedTokens::Attribute(_)
+                    | BinXMLDeserializedTokens::CloseStartElement
+                    | BinXMLDeserializedTokens::CloseEmptyElement
+            )
+        {
+            xml.push('"');
+            in_attribute = false;
+        }
+
+        match token {
+            BinXMLDeserializedTokens::OpenStartElement(element) => {
+                xml.push('<');
+                xml.push_str(element.name.as_str());
+                open_elements.push(element.name.as_str());
+            }
+            BinXMLDeserializedTokens::Attribute(attribute) => {
+                xml.push(' ');
+                xml.push_str(attribute.name.as_str());
+                xml.push_str("=\"");
+                in_attribute = true;
+            }
+            BinXMLDeserializedTokens::CloseStartElement => xml.push('>'),
+            BinXMLDeserializedTokens::CloseEmptyElement => {
+                xml.push_str("/>");
+                open_elements.pop();
+            }
+            BinXMLDeserializedTokens::CloseElement => {
+                xml.push_str("</");
+                xml.push_str(open_elements.pop().unwrap_or_default());
+                xml.push('>');
+            }
+            BinXMLDeserializedTokens::Value(value) => xml.push_str(&escape(&value.as_cow_str())),
+            BinXMLDeserializedTokens::Substitution(descriptor) => xml.push_str(&format!(
+                "{{{}:{:?}}}",
+                descriptor.substitution_index, descriptor.value_type
+            )),
+            BinXMLDeserializedTokens::EntityRef(entity) => {
+                xml.push('&');
+                xml.push_str(entity.name.as_str());
+                xml.push(';');
+            }
+            _ => {}
+        }
+    }
+
+    xml
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::EvtxParser;
+
+    #[test]
+    fn test_lists_templates_and_strings() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let chunk = parser.chunks().next().unwrap().unwrap();
+
+        let templates = chunk.templates(encoding::all::WINDOWS_1252);
+        assert!(!templates.is_empty());
+        assert!(templates.windows(2).all(|w| w[0].offset < w[1].offset));
+
+        // Besides the events, templates can define fragments substituted into them (such as `EventData`).
+        assert!(templates
+            .iter()
+            .any(|t| t.skeleton.starts_with("<Event xmlns=") && t.skeleton.ends_with("</Event>")));
+
+        for template in &templates {
+            assert!(
+                template.skeleton.starts_with('<') && template.skeleton.ends_with('>'),
+                "{}",
+                template.skeleton
+            );
+            if template.substitutions > 0 {
+                assert!(template
+                    .skeleton
+                    .contains(&format!("{{{}:", template.substitutions - 1)));
+            }
+        }
+
+        let strings = chunk.strings();
+        let names: Vec<&str> = strings.iter().map(|s| s.value.as_str()).collect();
+        for name in &["Event", "System", "Provider", "EventID", "Data"] {
+            assert!(names.contains(name), "{:?}", names);
+        }
+    }
+}
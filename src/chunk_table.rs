@@ -0,0 +1,268 @@
+//! A per-chunk summary of an EVTX file, meant for inclusion in forensic reports.
+use crate::err::{self, Result};
+use crate::evtx_chunk::{EvtxChunkData, EVTX_CHUNK_HEADER_SIZE};
+use crate::evtx_parser::EVTX_CHUNK_SIZE;
+
+use chrono::Duration;
+use serde_json::json;
+use snafu::ResultExt;
+
+use std::io::Write;
+
+/// The format used by `EvtxParser::export_chunk_table`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChunkTableFormat {
+    /// A header line, followed by a line for every chunk.
+    Csv,
+    /// An array with an object for every chunk.
+    Json,
+}
+
+/// The state of a chunk, as found in the file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChunkStatus {
+    /// The chunk header was parsed successfully (checksums may still be invalid).
+    Valid,
+    /// The chunk is all zeroes.
+    Empty,
+    /// The chunk header could not be parsed.
+    Invalid(String),
+}
+
+impl ChunkStatus {
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            ChunkStatus::Valid => "valid",
+            ChunkStatus::Empty => "empty",
+            ChunkStatus::Invalid(_) => "invalid",
+        }
+    }
+}
+
+/// A single row of the chunk table.
+/// All the fields read from the chunk header are `None` unless the status is `ChunkStatus::Valid`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkTableEntry {
+    pub chunk_number: u16,
+    /// Offset of the chunk from the start of the file.
+    pub offset: u64,
+    pub status: ChunkStatus,
+    pub first_event_record_number: Option<u64>,
+    pub last_event_record_number: Option<u64>,
+    pub first_event_record_id: Option<u64>,
+    pub last_event_record_id: Option<u64>,
+    pub header_checksum_valid: Option<bool>,
+    pub data_checksum_valid: Option<bool>,
+    pub flags: Option<u32>,
+    pub free_space_offset: Option<u32>,
+    /// Number of bytes between the free space offset and the end of the chunk.
+    pub slack_bytes: Option<u32>,
+    /// Number of non-zero bytes in the slack space, these might be remnants of older records.
+    pub non_zero_slack_bytes: Option<u32>,
+    /// IDs of the records going backwards in time (see `ParserSettings::validate_timestamp_order`),
+    /// only set when the validation is enabled.
+    pub timestamp_regressions: Option<Vec<u64>>,
+}
+
+const CSV_COLUMNS: &[&str] = &[
+    "chunk_number",
+    "offset",
+    "status",
+    "error",
+    "first_event_record_number",
+    "last_event_record_number",
+    "first_event_record_id",
+    "last_event_record_id",
+    "header_checksum_valid",
+    "data_checksum_valid",
+    "flags",
+    "free_space_offset",
+    "slack_bytes",
+    "non_zero_slack_bytes",
+    "timestamp_regressions",
+];
+
+impl ChunkTableEntry {
+    /// Summarizes the (full, `EVTX_CHUNK_SIZE` sized) `data` of the chunk at `offset`.
+    /// Record timestamps are validated if `timestamp_order_tolerance` is set.
+    pub(crate) fn from_chunk_data(
+        chunk_number: u16,
+        offset: u64,
+        data: Vec<u8>,
+        timestamp_order_tolerance: Option<Duration>,
+    ) -> Self {
+        let mut entry = ChunkTableEntry::without_header(
+            chunk_number,
+            offset,
+            if data.iter().all(|b| *b == 0) {
+                ChunkStatus::Empty
+            } else {
+                ChunkStatus::Valid
+            },
+        );
+
+        if entry.status == ChunkStatus::Empty {
+            return entry;
+        }
+
+        let chunk = match EvtxChunkData::new(data, false) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                entry.status = ChunkStatus::Invalid(e.to_string());
+                return entry;
+            }
+        };
+
+        let header = &chunk.header;
+        let free_space_offset = header.free_space_offset as usize;
+        let slack = chunk.data.get(free_space_offset..).unwrap_or(&[]);
+
+        entry.first_event_record_number = Some(header.first_event_record_number);
+        entry.last_event_record_number = Some(header.last_event_record_number);
+        entry.first_event_record_id = Some(header.first_event_record_id);
+        entry.last_event_record_id = Some(header.last_event_record_id);
+        entry.header_checksum_valid = Some(chunk.validate_header_checksum());
+        // A corrupted free space offset would make the data checksum out of bounds.
+        entry.data_checksum_valid = Some(
+            (EVTX_CHUNK_HEADER_SIZE..=EVTX_CHUNK_SIZE).contains(&free_space_offset)
+                && chunk.validate_data_checksum(),
+        );
+        entry.flags = Some(header.flags);
+        entry.free_space_offset = Some(header.free_space_offset);
+        entry.slack_bytes = Some(slack.len() as u32);
+        entry.non_zero_slack_bytes = Some(slack.iter().filter(|b| **b != 0).count() as u32);
+        entry.timestamp_regressions = timestamp_order_tolerance.map(|tolerance| {
+            chunk
+                .timestamp_regressions(tolerance)
+                .iter()
+                .map(|regression| regression.event_record_id)
+                .collect()
+        });
+
+        entry
+    }
+
+    pub(crate) fn without_header(chunk_number: u16, offset: u64, status: ChunkStatus) -> Self {
+        ChunkTableEntry {
+            chunk_number,
+            offset,
+            status,
+            first_event_record_number: None,
+            last_event_record_number: None,
+            first_event_record_id: None,
+            last_event_record_id: None,
+            header_checksum_valid: None,
+            data_checksum_valid: None,
+            flags: None,
+            free_space_offset: None,
+            slack_bytes: None,
+            non_zero_slack_bytes: None,
+            timestamp_regressions: None,
+        }
+    }
+
+    fn error(&self) -> Option<&str> {
+        match &self.status {
+            ChunkStatus::Invalid(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    fn to_json_value(&self) -> serde_json::Value {
+        json!({
+            "chunk_number": self.chunk_number,
+            "offset": self.offset,
+            "status": self.status.as_str(),
+            "error": self.error(),
+            "first_event_record_number": self.first_event_record_number,
+            "last_event_record_number": self.last_event_record_number,
+            "first_event_record_id": self.first_event_record_id,
+            "last_event_record_id": self.last_event_record_id,
+            "header_checksum_valid": self.header_checksum_valid,
+            "data_checksum_valid": self.data_checksum_valid,
+            "flags": self.flags,
+            "free_space_offset": self.free_space_offset,
+            "slack_bytes": self.slack_bytes,
+            "non_zero_slack_bytes": self.non_zero_slack_bytes,
+            "timestamp_regressions": self.timestamp_regressions,
+        })
+    }
+
+    fn to_csv_record(&self) -> String {
+        fn cell<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+
+        [
+            self.chunk_number.to_string(),
+            self.offset.to_string(),
+            self.status.as_str().to_string(),
+            self.error().map(escape_csv).unwrap_or_default(),
+            cell(self.first_event_record_number),
+            cell(self.last_event_record_number),
+            cell(self.first_event_record_id),
+            cell(self.last_event_record_id),
+            cell(self.header_checksum_valid),
+            cell(self.data_checksum_valid),
+            cell(self.flags),
+            cell(self.free_space_offset),
+            cell(self.slack_bytes),
+            cell(self.non_zero_slack_bytes),
+            self.timestamp_regressions
+                .as_ref()
+                .map(|ids| {
+                    ids.iter()
+                        .map(|id| id.to_string())
+                        .collect::<Vec<_>>()
+                        .join(";")
+                })
+                .unwrap_or_default(),
+        ]
+        .join(",")
+    }
+}
+
+pub(crate) fn escape_csv(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Writes `entries` to `writer` using `format`.
+pub(crate) fn write_chunk_table<W: Write>(
+    entries: &[ChunkTableEntry],
+    mut writer: W,
+    format: ChunkTableFormat,
+) -> Result<()> {
+    match format {
+        ChunkTableFormat::Csv => {
+            writeln!(writer, "{}", CSV_COLUMNS.join(","))?;
+
+            for entry in entries {
+                writeln!(writer, "{}", entry.to_csv_record())?;
+            }
+        }
+        ChunkTableFormat::Json => {
+            let entries: Vec<serde_json::Value> =
+                entries.iter().map(ChunkTableEntry::to_json_value).collect();
+
+            serde_json::to_writer_pretty(&mut writer, &entries).context(err::JsonError)?;
+            writeln!(writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_escaping() {
+        assert_eq!(escape_csv("bad magic"), "bad magic");
+        assert_eq!(escape_csv("a, \"b\""), "\"a, \"\"b\"\"\"");
+    }
+}
@@ -0,0 +1,407 @@
+use crate::err::{self, Result};
+
+use snafu::ResultExt;
+
+use log::debug;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// A compression format of input files, detected by it's magic bytes (gzip and zstd are also supported for output,
+/// see `CompressedWriter`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zstd,
+    /// A ZIP archive, holding a single evtx file.
+    Zip,
+}
+
+impl Compression {
+    /// Detects the compression of data starting with `magic` (at least 4 bytes are needed).
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        match magic {
+            [0x1f, 0x8b, ..] => Some(Compression::Gzip),
+            [0x28, 0xb5, 0x2f, 0xfd, ..] => Some(Compression::Zstd),
+            [b'P', b'K', 0x03, 0x04, ..] => Some(Compression::Zip),
+            _ => None,
+        }
+    }
+}
+
+impl Compression {
+    /// The extension of files compressed with this format (without a dot).
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => "gz",
+            Compression::Zstd => "zst",
+            Compression::Zip => "zip",
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zstd => write!(f, "zstd"),
+            Compression::Zip => write!(f, "ZIP"),
+        }
+    }
+}
+
+/// A file opened by `EvtxParser::from_path`.
+/// Compressed files are decompressed in memory, since chunks are read at arbitrary offsets.
+#[derive(Debug)]
+pub enum InputFile {
+    Plain(File),
+    Decompressed(Cursor<Vec<u8>>),
+}
+
+impl Read for InputFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            InputFile::Plain(file) => file.read(buf),
+            InputFile::Decompressed(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl Seek for InputFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            InputFile::Plain(file) => file.seek(pos),
+            InputFile::Decompressed(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// Opens the file at `path`, decompressing it if it is compressed.
+pub(crate) fn open_input(path: &Path) -> Result<InputFile> {
+    let mut file = File::open(path).context(err::FailedToOpenFile { path })?;
+
+    let mut magic = Vec::with_capacity(4);
+    (&mut file).take(4).read_to_end(&mut magic)?;
+    file.seek(SeekFrom::Start(0))?;
+
+    match Compression::detect(&magic) {
+        None => Ok(InputFile::Plain(file)),
+        Some(compression) => {
+            debug!("{} is {} compressed", path.display(), compression);
+
+            let data = decompress(file, compression, path)?;
+            Ok(InputFile::Decompressed(Cursor::new(data)))
+        }
+    }
+}
+
+#[cfg(feature = "compression")]
+fn decompress(mut file: File, compression: Compression, path: &Path) -> Result<Vec<u8>> {
+    let fail = |message: String| err::Error::FailedToDecompressFile {
+        path: path.to_path_buf(),
+        compression,
+        message,
+    };
+
+    let mut data = vec![];
+
+    match compression {
+        Compression::Gzip => {
+            flate2::read::MultiGzDecoder::new(file)
+                .read_to_end(&mut data)
+                .map_err(|e| fail(e.to_string()))?;
+        }
+        Compression::Zstd => {
+            // A file can hold several frames (such as the output of `CompressedWriter`), which are concatenated.
+            let size = file.metadata()?.len();
+            while file.stream_position()? < size {
+                ruzstd::decoding::StreamingDecoder::new(&mut file)
+                    .map_err(|e| fail(e.to_string()))?
+                    .read_to_end(&mut data)
+                    .map_err(|e| fail(e.to_string()))?;
+            }
+        }
+        Compression::Zip => {
+            let mut archive = zip::ZipArchive::new(file).map_err(|e| fail(e.to_string()))?;
+
+            let names: Vec<String> = archive
+                .file_names()
+                .filter(|name| name.to_lowercase().ends_with(".evtx"))
+                .map(str::to_string)
+                .collect();
+
+            let name = match names.as_slice() {
+                [name] => name,
+                [] => return Err(fail("the archive has no evtx file".to_string())),
+                _ => {
+                    return Err(fail(format!(
+                        "the archive has {} evtx files, expected a single one",
+                        names.len()
+                    )))
+                }
+            };
+
+            debug!("Reading {} from the archive", name);
+            archive
+                .by_name(name)
+                .map_err(|e| fail(e.to_string()))?
+                .read_to_end(&mut data)
+                .map_err(|e| fail(e.to_string()))?;
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_file: File, compression: Compression, path: &Path) -> Result<Vec<u8>> {
+    err::CompressedInputNotSupported { path, compression }.fail()
+}
+
+/// The size of the data compressed in every zstd frame, which is also the data buffered by `CompressedWriter`.
+#[cfg(feature = "compression")]
+const ZSTD_FRAME_SIZE: usize = 1 << 20;
+
+/// A writer compressing the data written to it with gzip or zstd, requiring the `compression` feature.
+///
+/// `finish` must be called once all the data is written, to write the end of the compressed stream.
+///
+/// ```rust,no_run
+/// use evtx::{CompressedWriter, Compression};
+/// use std::io::Write;
+///
+/// let mut writer = CompressedWriter::new(vec![], Compression::Gzip).unwrap();
+/// writer.write_all(b"{}\n").unwrap();
+/// let compressed = writer.finish().unwrap();
+/// assert_eq!(Compression::detect(&compressed), Some(Compression::Gzip));
+/// ```
+pub struct CompressedWriter<W: Write> {
+    encoder: Encoder<W>,
+}
+
+enum Encoder<W: Write> {
+    #[cfg(feature = "compression")]
+    Gzip(flate2::write::GzEncoder<W>),
+    /// Data is compressed in frames of (up to) `ZSTD_FRAME_SIZE` bytes, since the encoder reads it's input.
+    #[cfg(feature = "compression")]
+    Zstd {
+        writer: W,
+        buffer: Vec<u8>,
+        frames: usize,
+    },
+    /// Never constructed, since `CompressedWriter::new` fails without the feature.
+    #[cfg(not(feature = "compression"))]
+    #[allow(dead_code)]
+    Unsupported(std::convert::Infallible, std::marker::PhantomData<W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Fails for `Compression::Zip`, and without the `compression` feature.
+    #[cfg(feature = "compression")]
+    pub fn new(writer: W, compression: Compression) -> Result<Self> {
+        let encoder = match compression {
+            Compression::Gzip => Encoder::Gzip(flate2::write::GzEncoder::new(
+                writer,
+                flate2::Compression::default(),
+            )),
+            Compression::Zstd => Encoder::Zstd {
+                writer,
+                buffer: Vec::with_capacity(ZSTD_FRAME_SIZE),
+                frames: 0,
+            },
+            Compression::Zip => return err::UnsupportedOutputCompression { compression }.fail(),
+        };
+
+        Ok(CompressedWriter { encoder })
+    }
+
+    /// Fails for `Compression::Zip`, and without the `compression` feature.
+    #[cfg(not(feature = "compression"))]
+    pub fn new(_writer: W, compression: Compression) -> Result<Self> {
+        match compression {
+            Compression::Zip => err::UnsupportedOutputCompression { compression }.fail(),
+            _ => err::CompressedOutputNotSupported { compression }.fail(),
+        }
+    }
+
+    /// Writes the end of the compressed stream, and returns the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.encoder {
+            #[cfg(feature = "compression")]
+            Encoder::Gzip(encoder) => encoder.finish(),
+            #[cfg(feature = "compression")]
+            Encoder::Zstd {
+                mut writer,
+                buffer,
+                frames,
+            } => {
+                // An empty stream is still a (single, empty) frame.
+                if !buffer.is_empty() || frames == 0 {
+                    write_zstd_frame(&mut writer, &buffer)?;
+                }
+                writer.flush()?;
+                Ok(writer)
+            }
+            #[cfg(not(feature = "compression"))]
+            Encoder::Unsupported(never, _) => match never {},
+        }
+    }
+}
+
+#[cfg(feature = "compression")]
+fn write_zstd_frame(writer: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    let frame =
+        ruzstd::encoding::compress_to_vec(data, ruzstd::encoding::CompressionLevel::Fastest);
+    writer.write_all(&frame)
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    #[cfg_attr(not(feature = "compression"), allow(unused_variables))]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.encoder {
+            #[cfg(feature = "compression")]
+            Encoder::Gzip(encoder) => encoder.write(buf),
+            #[cfg(feature = "compression")]
+            Encoder::Zstd {
+                writer,
+                buffer,
+                frames,
+            } => {
+                let len = buf.len().min(ZSTD_FRAME_SIZE - buffer.len());
+                buffer.extend_from_slice(&buf[..len]);
+
+                if buffer.len() == ZSTD_FRAME_SIZE {
+                    write_zstd_frame(writer, buffer)?;
+                    buffer.clear();
+                    *frames += 1;
+                }
+
+                Ok(len)
+            }
+            #[cfg(not(feature = "compression"))]
+            Encoder::Unsupported(never, _) => match *never {},
+        }
+    }
+
+    /// Compresses the buffered data (in a zstd frame of it's own), so it can be read back.
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.encoder {
+            #[cfg(feature = "compression")]
+            Encoder::Gzip(encoder) => encoder.flush(),
+            #[cfg(feature = "compression")]
+            Encoder::Zstd {
+                writer,
+                buffer,
+                frames,
+            } => {
+                if !buffer.is_empty() {
+                    write_zstd_frame(writer, buffer)?;
+                    buffer.clear();
+                    *frames += 1;
+                }
+                writer.flush()
+            }
+            #[cfg(not(feature = "compression"))]
+            Encoder::Unsupported(never, _) => match *never {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvtxParser;
+
+    #[test]
+    fn test_detect_compression() {
+        assert_eq!(Compression::detect(b"ElfFile\x00"), None);
+        assert_eq!(
+            Compression::detect(&[0x1f, 0x8b, 0x08, 0x00]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::detect(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::detect(b"PK\x03\x04"), Some(Compression::Zip));
+        assert_eq!(Compression::detect(b"PK"), None);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_inputs_are_parsed_like_the_original() {
+        let records = |path: &str| -> Vec<String> {
+            EvtxParser::from_path(path)
+                .unwrap()
+                .records()
+                .map(|r| r.unwrap().data)
+                .collect()
+        };
+
+        let expected = records("samples/new-user-security.evtx");
+        assert!(!expected.is_empty());
+
+        for path in &[
+            "samples/new-user-security.evtx.gz",
+            "samples/new-user-security.evtx.zst",
+            "samples/new-user-security.zip",
+        ] {
+            assert_eq!(records(path), expected, "{}", path);
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_output_is_decompressed_like_inputs() {
+        let dir = tempfile::tempdir().unwrap();
+        // Several zstd frames.
+        let data: Vec<u8> = (0..3 * ZSTD_FRAME_SIZE / 40)
+            .flat_map(|i| format!("{{\"EventRecordID\": {:>20}}}\n", i).into_bytes())
+            .collect();
+
+        for &(compression, name) in &[
+            (Compression::Gzip, "out.gz"),
+            (Compression::Zstd, "out.zst"),
+        ] {
+            let mut writer = CompressedWriter::new(vec![], compression).unwrap();
+            writer.write_all(&data[..1000]).unwrap();
+            writer.flush().unwrap();
+            writer.write_all(&data[1000..]).unwrap();
+            let compressed = writer.finish().unwrap();
+
+            assert_eq!(Compression::detect(&compressed), Some(compression));
+            assert!(compressed.len() < data.len() / 2, "{}", compressed.len());
+
+            let path = dir.path().join(name);
+            std::fs::write(&path, compressed).unwrap();
+            let mut decompressed = vec![];
+            open_input(&path)
+                .unwrap()
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert!(decompressed == data, "{}", compression);
+        }
+
+        assert!(CompressedWriter::new(vec![], Compression::Zip).is_err());
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn test_compressed_output_needs_the_compression_feature() {
+        let err = CompressedWriter::new(vec![], Compression::Zstd)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("feature"), "{}", err);
+    }
+
+    #[cfg(not(feature = "compression"))]
+    #[test]
+    fn test_compressed_inputs_need_the_compression_feature() {
+        let err = EvtxParser::from_path("samples/new-user-security.evtx.gz")
+            .err()
+            .unwrap();
+
+        assert!(err.to_string().contains("gzip"), "{}", err);
+    }
+}
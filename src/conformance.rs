@@ -0,0 +1,593 @@
+//! Comparison of the XML rendering of records against reference outputs of `wevtutil`,
+//! to track (and fix) differences in the output of this crate.
+//!
+//! References are produced on Windows with `wevtutil qe <file>.evtx /lf:true /f:xml > <file>.xml`,
+//! and records are matched by their `EventRecordID`.
+//!
+//! Documents are compared after normalization, so the following differences are ignored:
+//! - The XML declaration, comments and processing instructions.
+//! - Indentation, and whitespace around text.
+//! - The order of attributes, quoting, escaping and empty elements (`<A/>` and `<A></A>`).
+//!
+//! ```rust
+//! use evtx::conformance::compare_file;
+//! use evtx::ParserSettings;
+//!
+//! let report = compare_file(
+//!     "samples/security.evtx",
+//!     "samples/conformance/security.xml",
+//!     ParserSettings::default(),
+//! ).unwrap();
+//!
+//! assert_eq!(report.records_compared, 2);
+//! println!("{}", report);
+//! ```
+use crate::err::{self, Result};
+use crate::{EvtxParser, ParserSettings};
+
+use log::debug;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use snafu::ResultExt;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A normalized XML element.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: BTreeMap<String, String>,
+    /// The text content of the element (trimmed).
+    pub text: String,
+    pub children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    /// Returns the first child named `name`.
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Returns the `System/EventRecordID` of an `Event` element.
+    pub fn event_record_id(&self) -> Option<u64> {
+        self.child("System")?
+            .child("EventRecordID")?
+            .text
+            .parse()
+            .ok()
+    }
+}
+
+/// A difference between the reference and the rendering of a record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub event_record_id: u64,
+    /// Path of the element (`Event/EventData/Data[@Name='LogonGuid']`)
+    /// or attribute (`Event/System/Provider/@Guid`).
+    pub path: String,
+    /// The value in the reference, `None` if it is missing.
+    pub expected: Option<String>,
+    /// The value rendered by this crate, `None` if it is missing.
+    pub actual: Option<String>,
+}
+
+/// The result of comparing an EVTX file against it's reference.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConformanceReport {
+    pub evtx_file: PathBuf,
+    pub reference_file: PathBuf,
+    /// Number of records found in both the reference and the file.
+    pub records_compared: usize,
+    /// Records of the reference which were not rendered (failed to parse, or missing).
+    pub missing_records: Vec<u64>,
+    /// Rendered records which are not in the reference.
+    pub unexpected_records: Vec<u64>,
+    /// Records which failed to parse, with the error.
+    pub errors: Vec<String>,
+    pub divergences: Vec<Divergence>,
+}
+
+impl ConformanceReport {
+    /// Returns `true` if every record of the reference was rendered identically.
+    pub fn is_conformant(&self) -> bool {
+        self.missing_records.is_empty() && self.divergences.is_empty()
+    }
+
+    /// Returns the number of divergences at every path, to find the most common differences.
+    pub fn divergences_by_path(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+
+        for divergence in &self.divergences {
+            *counts.entry(divergence.path.as_str()).or_insert(0) += 1;
+        }
+
+        counts
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ({}): {} records compared, {} divergences",
+            self.evtx_file.display(),
+            self.reference_file.display(),
+            self.records_compared,
+            self.divergences.len()
+        )?;
+
+        if !self.missing_records.is_empty() {
+            writeln!(f, "  missing records: {:?}", self.missing_records)?;
+        }
+
+        if !self.unexpected_records.is_empty() {
+            writeln!(
+                f,
+                "  records not in the reference: {}",
+                self.unexpected_records.len()
+            )?;
+        }
+
+        for error in &self.errors {
+            writeln!(f, "  error: {}", error)?;
+        }
+
+        for (path, count) in self.divergences_by_path() {
+            let first = self
+                .divergences
+                .iter()
+                .find(|divergence| divergence.path == path)
+                .expect("path of a divergence");
+
+            writeln!(
+                f,
+                "  {} ({} records, first in record {}): expected {:?}, found {:?}",
+                path, count, first.event_record_id, first.expected, first.actual
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Compares the records of `evtx_file` against the `wevtutil` output in `reference_file`.
+pub fn compare_file(
+    evtx_file: impl AsRef<Path>,
+    reference_file: impl AsRef<Path>,
+    settings: ParserSettings,
+) -> Result<ConformanceReport> {
+    let evtx_file = evtx_file.as_ref();
+    let reference_file = reference_file.as_ref();
+
+    let data = fs::read(reference_file).context(err::FailedToOpenFile {
+        path: reference_file,
+    })?;
+
+    let mut reference: HashMap<u64, XmlElement> = HashMap::new();
+    let mut reference_ids = vec![];
+
+    for event in parse_events(&decode_reference(&data))? {
+        if let Some(id) = event.event_record_id() {
+            reference_ids.push(id);
+            reference.insert(id, event);
+        }
+    }
+
+    let mut report = ConformanceReport {
+        evtx_file: evtx_file.to_path_buf(),
+        reference_file: reference_file.to_path_buf(),
+        ..ConformanceReport::default()
+    };
+
+    let mut parser = EvtxParser::from_path(evtx_file)?.with_configuration(settings);
+
+    for record in parser.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                report.errors.push(e.to_string());
+                continue;
+            }
+        };
+
+        let id = record.event_record_id;
+
+        let expected = match reference.remove(&id) {
+            Some(expected) => expected,
+            None => {
+                report.unexpected_records.push(id);
+                continue;
+            }
+        };
+
+        let actual = parse_events(&record.data)?;
+
+        match actual.first() {
+            Some(actual) => {
+                report.records_compared += 1;
+                report
+                    .divergences
+                    .extend(compare_events(id, &expected, actual));
+            }
+            None => report.errors.push(format!("Record {} has no `Event`", id)),
+        }
+    }
+
+    report.missing_records = reference_ids
+        .into_iter()
+        .filter(|id| reference.contains_key(id))
+        .collect();
+
+    Ok(report)
+}
+
+/// Compares every `<name>.evtx` file in `evtx_dir` having a reference `<name>.xml` in `reference_dir`.
+pub fn compare_dir(
+    evtx_dir: impl AsRef<Path>,
+    reference_dir: impl AsRef<Path>,
+    settings: ParserSettings,
+) -> Result<Vec<ConformanceReport>> {
+    let evtx_dir = evtx_dir.as_ref();
+    let reference_dir = reference_dir.as_ref();
+
+    let mut files = fs::read_dir(evtx_dir)
+        .context(err::FailedToOpenFile { path: evtx_dir })?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .context(err::FailedToOpenFile { path: evtx_dir })?;
+
+    files.sort();
+
+    let mut reports = vec![];
+
+    for file in files {
+        if file.extension().and_then(|e| e.to_str()) != Some("evtx") {
+            continue;
+        }
+
+        let reference = match file.file_stem() {
+            Some(stem) => reference_dir.join(stem).with_extension("xml"),
+            None => continue,
+        };
+
+        if !reference.is_file() {
+            debug!("No reference for `{}`", file.display());
+            continue;
+        }
+
+        reports.push(compare_file(&file, &reference, settings.clone())?);
+    }
+
+    Ok(reports)
+}
+
+/// Compares two `Event` elements, returning their differences.
+pub fn compare_events(
+    event_record_id: u64,
+    expected: &XmlElement,
+    actual: &XmlElement,
+) -> Vec<Divergence> {
+    let mut divergences = vec![];
+    compare_elements(
+        event_record_id,
+        &expected.name,
+        expected,
+        actual,
+        &mut divergences,
+    );
+
+    divergences
+}
+
+fn compare_elements(
+    event_record_id: u64,
+    path: &str,
+    expected: &XmlElement,
+    actual: &XmlElement,
+    divergences: &mut Vec<Divergence>,
+) {
+    let mut diverge = |path: String, expected: Option<&String>, actual: Option<&String>| {
+        if expected != actual {
+            divergences.push(Divergence {
+                event_record_id,
+                path,
+                expected: expected.cloned(),
+                actual: actual.cloned(),
+            })
+        }
+    };
+
+    for (name, value) in &expected.attributes {
+        diverge(
+            format!("{}/@{}", path, name),
+            Some(value),
+            actual.attributes.get(name),
+        );
+    }
+
+    for (name, value) in &actual.attributes {
+        if !expected.attributes.contains_key(name) {
+            diverge(format!("{}/@{}", path, name), None, Some(value));
+        }
+    }
+
+    diverge(path.to_string(), Some(&expected.text), Some(&actual.text));
+
+    let expected_children = child_paths(path, expected);
+    let mut actual_children = child_paths(path, actual);
+
+    for (child_path, expected_child) in expected_children {
+        match actual_children.iter().position(|(p, _)| *p == child_path) {
+            Some(index) => {
+                let (_, actual_child) = actual_children.remove(index);
+                compare_elements(
+                    event_record_id,
+                    &child_path,
+                    expected_child,
+                    actual_child,
+                    divergences,
+                );
+            }
+            None => divergences.push(Divergence {
+                event_record_id,
+                path: child_path,
+                expected: Some(expected_child.text.clone()),
+                actual: None,
+            }),
+        }
+    }
+
+    for (child_path, actual_child) in actual_children {
+        divergences.push(Divergence {
+            event_record_id,
+            path: child_path,
+            expected: None,
+            actual: Some(actual_child.text.clone()),
+        });
+    }
+}
+
+/// Returns the children of `element` with their paths.
+/// Children sharing their name with siblings are identified by their `Name` attribute if they have one
+/// (like the `Data` of `EventData`), or by their position amongst them.
+fn child_paths<'e>(path: &str, element: &'e XmlElement) -> Vec<(String, &'e XmlElement)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+
+    for child in &element.children {
+        *counts.entry(child.name.as_str()).or_insert(0) += 1;
+    }
+
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+
+    element
+        .children
+        .iter()
+        .map(|child| {
+            let position = seen.entry(child.name.as_str()).or_insert(0);
+            *position += 1;
+
+            let child_path = match child.attributes.get("Name") {
+                _ if counts[child.name.as_str()] == 1 => format!("{}/{}", path, child.name),
+                Some(name) => format!("{}/{}[@Name='{}']", path, child.name, name),
+                None => format!("{}/{}[{}]", path, child.name, position),
+            };
+
+            (child_path, child)
+        })
+        .collect()
+}
+
+/// Parses all the `Event` elements of a document (either concatenated, or inside an `Events` element).
+pub fn parse_events(xml: &str) -> Result<Vec<XmlElement>> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut buf = vec![];
+    let mut stack: Vec<XmlElement> = vec![];
+    let mut events = vec![];
+
+    let fail = |reader: &Reader<&[u8]>, message: String| err::Error::FailedToParseXml {
+        message,
+        position: reader.buffer_position(),
+    };
+
+    loop {
+        let event = reader
+            .read_event(&mut buf)
+            .map_err(|e| fail(&reader, e.to_string()))?;
+
+        // Elements are kept once they are closed, if they are an `Event`.
+        let closed = match event {
+            Event::Start(ref start) => {
+                stack.push(read_element(&reader, start)?);
+                None
+            }
+            Event::Empty(ref start) => Some(read_element(&reader, start)?),
+            Event::End(_) => stack.pop(),
+            Event::Text(ref text) => {
+                let text = text.unescaped().map_err(|e| fail(&reader, e.to_string()))?;
+
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(String::from_utf8_lossy(&text).trim());
+                }
+
+                None
+            }
+            Event::CData(ref text) => {
+                if let Some(element) = stack.last_mut() {
+                    element
+                        .text
+                        .push_str(String::from_utf8_lossy(text.escaped()).trim());
+                }
+
+                None
+            }
+            Event::Eof => break,
+            _ => None,
+        };
+
+        if let Some(element) = closed {
+            match stack.last_mut() {
+                Some(parent) if element.name != "Event" => parent.children.push(element),
+                _ if element.name == "Event" => events.push(element),
+                _ => {}
+            }
+        }
+
+        buf.clear();
+    }
+
+    Ok(events)
+}
+
+fn read_element(reader: &Reader<&[u8]>, start: &BytesStart) -> Result<XmlElement> {
+    let mut element = XmlElement {
+        name: String::from_utf8_lossy(start.name()).into_owned(),
+        ..XmlElement::default()
+    };
+
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| err::Error::FailedToParseXml {
+            message: e.to_string(),
+            position: reader.buffer_position(),
+        })?;
+
+        let value = attribute
+            .unescaped_value()
+            .map_err(|e| err::Error::FailedToParseXml {
+                message: e.to_string(),
+                position: reader.buffer_position(),
+            })?;
+
+        element.attributes.insert(
+            String::from_utf8_lossy(attribute.key).into_owned(),
+            String::from_utf8_lossy(&value).into_owned(),
+        );
+    }
+
+    Ok(element)
+}
+
+/// References are UTF-8, or UTF-16 when redirected by PowerShell.
+fn decode_reference(data: &[u8]) -> String {
+    if data.starts_with(&[0xff, 0xfe]) {
+        let units: Vec<u16> = data[2..]
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect();
+
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data)).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ensure_env_logger_initialized;
+
+    fn event(xml: &str) -> XmlElement {
+        parse_events(xml).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_normalizes_formatting() {
+        let expected = event(
+            "<Event xmlns='ns'><System><Security/><EventRecordID>1</EventRecordID></System>\
+             <EventData><Data Name='a'>x &amp; y</Data></EventData></Event>",
+        );
+        let actual = event(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <Event xmlns="ns">
+              <System>
+                <Security>
+                </Security>
+                <EventRecordID>1</EventRecordID>
+              </System>
+              <EventData>
+                <Data Name="a"><![CDATA[x & y]]></Data>
+              </EventData>
+            </Event>"#,
+        );
+
+        assert_eq!(expected.event_record_id(), Some(1));
+        assert_eq!(compare_events(1, &expected, &actual), vec![]);
+    }
+
+    #[test]
+    fn test_finds_divergences() {
+        let expected = event(
+            "<Event><System><Provider Name='P' Guid='{g}'/></System>\
+             <EventData><Data Name='a'>1</Data><Data Name='b'>2</Data></EventData></Event>",
+        );
+        let actual = event(
+            "<Event><System><Provider Name='P'/></System>\
+             <EventData><Data Name='b'>3</Data><Data Name='c'>4</Data></EventData></Event>",
+        );
+
+        let divergences: Vec<(String, Option<String>, Option<String>)> =
+            compare_events(7, &expected, &actual)
+                .into_iter()
+                .map(|d| (d.path, d.expected, d.actual))
+                .collect();
+
+        let s = |s: &str| Some(s.to_string());
+
+        assert_eq!(
+            divergences,
+            vec![
+                ("Event/System/Provider/@Guid".to_string(), s("{g}"), None),
+                ("Event/EventData/Data[@Name='a']".to_string(), s("1"), None),
+                (
+                    "Event/EventData/Data[@Name='b']".to_string(),
+                    s("2"),
+                    s("3")
+                ),
+                ("Event/EventData/Data[@Name='c']".to_string(), None, s("4")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compares_sample_against_reference() {
+        ensure_env_logger_initialized();
+
+        let reports = compare_dir(
+            "samples",
+            "samples/conformance",
+            ParserSettings::default().num_threads(1),
+        )
+        .unwrap();
+
+        assert_eq!(reports.len(), 1);
+
+        let report = &reports[0];
+        assert_eq!(report.records_compared, 2);
+        assert!(report.missing_records.is_empty());
+        assert!(!report.unexpected_records.is_empty());
+        assert!(!report.is_conformant());
+
+        let by_path: Vec<(&str, usize)> = report.divergences_by_path().into_iter().collect();
+
+        assert_eq!(
+            by_path,
+            vec![
+                ("Event/EventData/Data[@Name='LogonGuid']", 1),
+                ("Event/System/Provider/@Guid", 2),
+                ("Event/System/TimeCreated/@SystemTime", 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decodes_utf16_references() {
+        let mut data = vec![0xff, 0xfe];
+        data.extend("<Event/>".encode_utf16().flat_map(u16::to_le_bytes));
+
+        assert_eq!(decode_reference(&data), "<Event/>");
+    }
+}
@@ -0,0 +1,287 @@
+//! One call conversion of EVTX files to JSON lines or XML, for scripts embedding the crate.
+//!
+//! ```rust
+//! use evtx::convert::{file_to_jsonl, Options};
+//!
+//! let output = std::env::temp_dir().join("security.jsonl");
+//! let options = Options::new().filter_expr("EventID == 4624").unwrap();
+//!
+//! let summary = file_to_jsonl("samples/security.evtx", &output, options).unwrap();
+//!
+//! assert!(summary.records_written > 0);
+//! assert_eq!(summary.errors, 0);
+//! # std::fs::remove_file(output).unwrap();
+//! ```
+use crate::err::{self, Result};
+use crate::{DedupField, DedupKey, Deduplicator, EvtxParser, ParserSettings, RenderFormat};
+
+use log::warn;
+use snafu::ResultExt;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const XML_DECLARATION: &str = r#"<?xml version="1.0" encoding="utf-8"?>"#;
+
+/// Options of a conversion. By default, all the records are written,
+/// and records which fail to parse are skipped (and counted in `Summary::errors`).
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    settings: ParserSettings,
+    dedup: Option<Vec<DedupField>>,
+    fail_fast: bool,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Options::default()
+    }
+
+    /// Sets the settings of the parser (filters, number of threads, ...).
+    pub fn settings(mut self, settings: ParserSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Only writes the records matching a filter expression (see `RecordFilter::parse`).
+    pub fn filter_expr(mut self, expr: &str) -> Result<Self> {
+        self.settings = self.settings.filter_expr(expr)?;
+        Ok(self)
+    }
+
+    /// Skips records which are duplicates (by `fields`) of a previously written record, see `Deduplicator`.
+    pub fn dedup(mut self, fields: &[DedupField]) -> Self {
+        self.dedup = Some(fields.to_vec());
+        self
+    }
+
+    /// Stops at the first record which fails to parse (returning it's error) instead of skipping it.
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+}
+
+/// What a conversion did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Summary {
+    pub records_written: u64,
+    /// Records skipped as duplicates.
+    pub duplicates: u64,
+    /// Records skipped because they failed to parse.
+    pub errors: u64,
+}
+
+/// Writes the records of `path_in` to `path_out` as JSON lines (one record per line).
+pub fn file_to_jsonl(
+    path_in: impl AsRef<Path>,
+    path_out: impl AsRef<Path>,
+    options: Options,
+) -> Result<Summary> {
+    convert_file(
+        path_in.as_ref(),
+        path_out.as_ref(),
+        options,
+        RenderFormat::Json,
+    )
+}
+
+/// Writes the records of `path_in` to `path_out` as a single XML document, with an `Events` root element.
+pub fn file_to_xml(
+    path_in: impl AsRef<Path>,
+    path_out: impl AsRef<Path>,
+    options: Options,
+) -> Result<Summary> {
+    convert_file(
+        path_in.as_ref(),
+        path_out.as_ref(),
+        options,
+        RenderFormat::Xml,
+    )
+}
+
+/// Same as `file_to_jsonl`, but records are written to `output`.
+pub fn write_jsonl<W: Write>(
+    path_in: impl AsRef<Path>,
+    output: &mut W,
+    options: Options,
+) -> Result<Summary> {
+    convert(path_in.as_ref(), output, options, RenderFormat::Json)
+}
+
+/// Same as `file_to_xml`, but records are written to `output`.
+pub fn write_xml<W: Write>(
+    path_in: impl AsRef<Path>,
+    output: &mut W,
+    options: Options,
+) -> Result<Summary> {
+    convert(path_in.as_ref(), output, options, RenderFormat::Xml)
+}
+
+fn convert_file(
+    path_in: &Path,
+    path_out: &Path,
+    options: Options,
+    format: RenderFormat,
+) -> Result<Summary> {
+    let file = File::create(path_out).context(err::FailedToOpenFile { path: path_out })?;
+    let mut output = BufWriter::new(file);
+
+    convert(path_in, &mut output, options, format)
+}
+
+fn convert<W: Write>(
+    path_in: &Path,
+    output: &mut W,
+    options: Options,
+    format: RenderFormat,
+) -> Result<Summary> {
+    let settings = match format {
+        RenderFormat::Json => options.settings.indent(false),
+        RenderFormat::Xml => options.settings,
+    };
+
+    let mut parser = EvtxParser::from_path(path_in)?.with_configuration(settings);
+    let mut deduplicator = options.dedup.as_deref().map(Deduplicator::new);
+    let dedup_fields = options.dedup;
+
+    // The key of the record is read before it's serialized.
+    let records = parser.serialized_records(move |record| {
+        record.and_then(|record| {
+            let key = dedup_fields
+                .as_ref()
+                .map(|fields| DedupKey::of(&record, fields))
+                .transpose()?;
+
+            let data = match format {
+                RenderFormat::Json => record.into_json()?.data,
+                RenderFormat::Xml => record.into_xml()?.data,
+            };
+
+            Ok((key, data))
+        })
+    });
+
+    let mut summary = Summary::default();
+
+    if format == RenderFormat::Xml {
+        writeln!(output, "{}", XML_DECLARATION)?;
+        writeln!(output, "<Events>")?;
+    }
+
+    for record in records {
+        let (key, data) = match record {
+            Ok(record) => record,
+            Err(e) if options.fail_fast => return Err(e),
+            Err(e) => {
+                warn!("Failed to parse record: {}", e);
+                summary.errors += 1;
+                continue;
+            }
+        };
+
+        if let (Some(key), Some(deduplicator)) = (key, deduplicator.as_mut()) {
+            if !deduplicator.insert(key) {
+                summary.duplicates += 1;
+                continue;
+            }
+        }
+
+        match format {
+            RenderFormat::Json => writeln!(output, "{}", data)?,
+            RenderFormat::Xml => {
+                let event = data.trim_start_matches(XML_DECLARATION).trim();
+                writeln!(output, "{}", event)?
+            }
+        }
+
+        summary.records_written += 1;
+    }
+
+    if format == RenderFormat::Xml {
+        writeln!(output, "</Events>")?;
+    }
+
+    output.flush()?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ensure_env_logger_initialized;
+    use std::fs;
+
+    #[test]
+    fn test_file_to_jsonl() {
+        ensure_env_logger_initialized();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("security.jsonl");
+
+        let summary = file_to_jsonl("samples/security.evtx", &output, Options::new()).unwrap();
+
+        assert_eq!(
+            summary,
+            Summary {
+                records_written: 2261,
+                duplicates: 0,
+                errors: 0
+            }
+        );
+
+        let data = fs::read_to_string(&output).unwrap();
+        assert_eq!(data.lines().count(), 2261);
+
+        for line in data.lines() {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(record["Event"]["System"]["EventID"].is_number());
+        }
+    }
+
+    #[test]
+    fn test_file_to_xml_with_options() {
+        ensure_env_logger_initialized();
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("security.xml");
+
+        let options = Options::new()
+            .filter_expr("EventID == 4907")
+            .unwrap()
+            .dedup(&DedupField::ALL);
+
+        let summary = file_to_xml("samples/security.evtx", &output, options).unwrap();
+        assert_eq!(summary.records_written + summary.duplicates, 620);
+
+        let data = fs::read_to_string(&output).unwrap();
+        assert_eq!(data.matches(XML_DECLARATION).count(), 1);
+        assert_eq!(
+            data.matches("<Event ").count() as u64,
+            summary.records_written
+        );
+        assert!(data.trim_end().ends_with("</Events>"));
+    }
+
+    #[test]
+    fn test_write_jsonl_fails_fast() {
+        ensure_env_logger_initialized();
+        let mut output = vec![];
+
+        let summary = write_jsonl(
+            "samples/E_Windows_system32_winevt_logs_Microsoft-Windows-CAPI2%4Operational.evtx",
+            &mut output,
+            Options::new(),
+        )
+        .unwrap();
+        assert_eq!(summary.errors, 5);
+        assert_eq!(summary.records_written, 335);
+
+        let result = write_jsonl(
+            "samples/E_Windows_system32_winevt_logs_Microsoft-Windows-CAPI2%4Operational.evtx",
+            &mut vec![],
+            Options::new().fail_fast(true),
+        );
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,203 @@
+//! Suppression of duplicate records, for processing overlapping collections
+//! (such as forwarded events alongside the local logs they were forwarded from).
+use crate::binxml::lookup::hash_event_data;
+use crate::err::Result;
+use crate::EvtxRecord;
+
+use chrono::{DateTime, Utc};
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::Hasher;
+
+/// A value records are compared on, see `Deduplicator`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DedupField {
+    /// The `TimeCreated` of the record.
+    Timestamp,
+    /// The `Provider` name.
+    Provider,
+    EventId,
+    /// A hash of the content of `EventData` (or `UserData`), names and values.
+    EventData,
+}
+
+impl DedupField {
+    pub const ALL: [DedupField; 4] = [
+        DedupField::Timestamp,
+        DedupField::Provider,
+        DedupField::EventId,
+        DedupField::EventData,
+    ];
+}
+
+/// The values of a record compared by a `Deduplicator`. Values which are not compared are `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct DedupKey {
+    pub timestamp: Option<DateTime<Utc>>,
+    pub provider: Option<String>,
+    pub event_id: Option<u16>,
+    pub event_data: Option<u64>,
+}
+
+impl DedupKey {
+    /// Returns the key of `record`, made of the given `fields`.
+    ///
+    /// Fails if the record cannot be deserialized (see `EvtxRecord::tokens`), unless only it's timestamp is compared.
+    pub fn of(record: &EvtxRecord<'_>, fields: &[DedupField]) -> Result<Self> {
+        let mut key = DedupKey::default();
+
+        if fields.iter().any(|field| *field != DedupField::Timestamp) {
+            record.tokens()?;
+        }
+
+        for field in fields {
+            match field {
+                DedupField::Timestamp => key.timestamp = Some(record.timestamp),
+                DedupField::Provider => {
+                    key.provider = record.provider_name().map(|name| name.into_owned())
+                }
+                DedupField::EventId => key.event_id = record.event_id(),
+                DedupField::EventData => {
+                    let mut hasher = DefaultHasher::new();
+                    hash_event_data(record.tokens()?, &mut hasher);
+                    key.event_data = Some(hasher.finish());
+                }
+            }
+        }
+
+        Ok(key)
+    }
+}
+
+/// Remembers the keys of the records seen so far, to suppress the ones seen before.
+/// A single `Deduplicator` can be used with the records of several files.
+///
+/// ```rust
+/// use evtx::{DedupField, DedupKey, Deduplicator, EvtxParser};
+///
+/// let mut deduplicator = Deduplicator::new(&DedupField::ALL);
+/// let mut unique = 0;
+///
+/// // The same file twice, the second time all records are duplicates.
+/// for _ in 0..2 {
+///     let mut parser = EvtxParser::from_path("samples/security.evtx").unwrap();
+///     let fields = deduplicator.fields().to_vec();
+///
+///     for key in parser.serialized_records(move |r| r.and_then(|r| DedupKey::of(&r, &fields))) {
+///         if deduplicator.insert(key.unwrap()) {
+///             unique += 1;
+///         }
+///     }
+/// }
+///
+/// assert_eq!(unique + deduplicator.duplicates(), 2 * 2261);
+/// assert!(unique <= 2261);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Deduplicator {
+    fields: Vec<DedupField>,
+    seen: HashSet<DedupKey>,
+    duplicates: u64,
+}
+
+impl Deduplicator {
+    /// Creates a deduplicator comparing records on the given `fields`.
+    pub fn new(fields: &[DedupField]) -> Self {
+        Deduplicator {
+            fields: fields.to_vec(),
+            seen: HashSet::new(),
+            duplicates: 0,
+        }
+    }
+
+    pub fn fields(&self) -> &[DedupField] {
+        &self.fields
+    }
+
+    /// Returns the key of `record` (see `DedupKey::of`).
+    pub fn key(&self, record: &EvtxRecord<'_>) -> Result<DedupKey> {
+        DedupKey::of(record, &self.fields)
+    }
+
+    /// Remembers `key`, returns `false` if it was seen before (the record is a duplicate).
+    pub fn insert(&mut self, key: DedupKey) -> bool {
+        let inserted = self.seen.insert(key);
+
+        if !inserted {
+            self.duplicates += 1;
+        }
+
+        inserted
+    }
+
+    /// Returns `true` if a record with the same key was seen before, remembering it otherwise.
+    pub fn is_duplicate(&mut self, record: &EvtxRecord<'_>) -> Result<bool> {
+        let key = self.key(record)?;
+        Ok(!self.insert(key))
+    }
+
+    /// Returns the number of duplicates seen so far.
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ensure_env_logger_initialized, EvtxParser};
+
+    fn keys(evtx_file: &[u8], fields: &[DedupField]) -> Vec<DedupKey> {
+        let fields = fields.to_vec();
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        parser
+            .serialized_records(move |r| r.and_then(|r| DedupKey::of(&r, &fields)))
+            .map(|key| key.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_deduplicates_records() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        let mut deduplicator = Deduplicator::new(&DedupField::ALL);
+        let first: Vec<bool> = keys(evtx_file, &DedupField::ALL)
+            .into_iter()
+            .map(|key| deduplicator.insert(key))
+            .collect();
+
+        // All records are distinct, unless they have the same timestamp and content.
+        let unique = first.iter().filter(|&&inserted| inserted).count();
+        assert_eq!(unique as u64 + deduplicator.duplicates(), 2261);
+
+        // Records of an overlapping collection are all suppressed.
+        let duplicates = deduplicator.duplicates();
+        for key in keys(evtx_file, &DedupField::ALL) {
+            assert!(!deduplicator.insert(key));
+        }
+        assert_eq!(deduplicator.duplicates(), duplicates + 2261);
+
+        // Comparing on fewer fields finds more duplicates.
+        let mut deduplicator = Deduplicator::new(&[DedupField::Provider, DedupField::EventId]);
+        for key in keys(evtx_file, deduplicator.fields()) {
+            deduplicator.insert(key);
+        }
+        assert!(deduplicator.duplicates() > 2000);
+    }
+
+    #[test]
+    fn test_event_data_hash_differs_by_content() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        let hashes: HashSet<Option<u64>> = keys(evtx_file, &[DedupField::EventData])
+            .into_iter()
+            .map(|key| key.event_data)
+            .collect();
+
+        assert!(hashes.len() > 100);
+    }
+}
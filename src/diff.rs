@@ -0,0 +1,293 @@
+//! A comparison of the records of two files, see `EvtxParser::diff`.
+use crate::evtx_parser::ReadSeek;
+use crate::EvtxParser;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// How a record differs between two files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordChange {
+    /// The record is only in the second file.
+    Added,
+    /// The record is only in the first file.
+    Removed,
+    /// The record is in both files, with a different content.
+    Modified,
+}
+
+impl RecordChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordChange::Added => "added",
+            RecordChange::Removed => "removed",
+            RecordChange::Modified => "modified",
+        }
+    }
+}
+
+/// A record (by it's `EventRecordID`) which differs between two files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordDifference {
+    pub event_record_id: u64,
+    pub change: RecordChange,
+    /// The timestamp of the record in the first file, `None` if it was added.
+    pub timestamp_before: Option<DateTime<Utc>>,
+    /// The timestamp of the record in the second file, `None` if it was removed.
+    pub timestamp_after: Option<DateTime<Utc>>,
+}
+
+/// The records added, removed and modified from a file to another, see `EvtxParser::diff`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DiffReport {
+    /// The records which differ, by record ID.
+    pub differences: Vec<RecordDifference>,
+    /// The number of records which are the same in both files.
+    pub unchanged: u64,
+    /// Record IDs found in more than one record of the first (or second) file, only the first of them is compared.
+    pub duplicates_before: Vec<u64>,
+    pub duplicates_after: Vec<u64>,
+    /// Errors of the records (and chunks) of the first (or second) file which could not be parsed,
+    /// those records are missing from the comparison.
+    pub errors_before: Vec<String>,
+    pub errors_after: Vec<String>,
+}
+
+/// The timestamp and the hash of the rendered XML of the records of a file, by record ID.
+#[derive(Debug, Default)]
+struct Fingerprints {
+    records: BTreeMap<u64, (DateTime<Utc>, [u8; 32])>,
+    duplicates: Vec<u64>,
+    errors: Vec<String>,
+}
+
+impl<T: ReadSeek> EvtxParser<T> {
+    /// Compares the records of this file (the one before) with the records of `other` (the one after),
+    /// matching them by record ID, and comparing their content as rendered to XML.
+    ///
+    /// Records which fail to parse are listed in the report, rather than failing it.
+    ///
+    /// ```rust
+    /// use evtx::EvtxParser;
+    ///
+    /// let mut before = EvtxParser::from_path("samples/security.evtx").unwrap();
+    /// let mut after = EvtxParser::from_path("samples/security.evtx").unwrap();
+    ///
+    /// let report = before.diff(&mut after);
+    /// assert!(report.is_identical());
+    /// assert_eq!(report.unchanged, 2261);
+    /// ```
+    pub fn diff<U: ReadSeek>(&mut self, other: &mut EvtxParser<U>) -> DiffReport {
+        DiffReport::between(self.fingerprints(), other.fingerprints())
+    }
+
+    fn fingerprints(&mut self) -> Fingerprints {
+        let mut fingerprints = Fingerprints::default();
+
+        let records = self.serialized_records(|record| {
+            record.and_then(|record| record.into_xml()).map(|record| {
+                let hash: [u8; 32] = Sha256::digest(record.data.as_bytes()).into();
+                (record.event_record_id, record.timestamp, hash)
+            })
+        });
+
+        for record in records {
+            match record {
+                Ok((id, timestamp, hash)) => match fingerprints.records.entry(id) {
+                    Entry::Vacant(entry) => {
+                        entry.insert((timestamp, hash));
+                    }
+                    Entry::Occupied(_) => fingerprints.duplicates.push(id),
+                },
+                Err(e) => fingerprints.errors.push(e.to_string()),
+            }
+        }
+
+        fingerprints.duplicates.sort_unstable();
+        fingerprints.duplicates.dedup();
+        fingerprints
+    }
+}
+
+impl DiffReport {
+    fn between(before: Fingerprints, after: Fingerprints) -> Self {
+        let mut report = DiffReport {
+            duplicates_before: before.duplicates,
+            duplicates_after: after.duplicates,
+            errors_before: before.errors,
+            errors_after: after.errors,
+            ..DiffReport::default()
+        };
+
+        let mut after_records = after.records;
+
+        for (id, (timestamp, hash)) in before.records {
+            let difference = |change, timestamp_after| RecordDifference {
+                event_record_id: id,
+                change,
+                timestamp_before: Some(timestamp),
+                timestamp_after,
+            };
+
+            match after_records.remove(&id) {
+                None => report
+                    .differences
+                    .push(difference(RecordChange::Removed, None)),
+                Some((_, after_hash)) if after_hash == hash => report.unchanged += 1,
+                Some((after_timestamp, _)) => report
+                    .differences
+                    .push(difference(RecordChange::Modified, Some(after_timestamp))),
+            }
+        }
+
+        report
+            .differences
+            .extend(
+                after_records
+                    .into_iter()
+                    .map(|(id, (timestamp, _))| RecordDifference {
+                        event_record_id: id,
+                        change: RecordChange::Added,
+                        timestamp_before: None,
+                        timestamp_after: Some(timestamp),
+                    }),
+            );
+        report.differences.sort_by_key(|d| d.event_record_id);
+
+        report
+    }
+
+    /// Returns `true` if both files have the same records (and every record could be parsed).
+    pub fn is_identical(&self) -> bool {
+        self.differences.is_empty()
+            && self.duplicates_before.is_empty()
+            && self.duplicates_after.is_empty()
+            && self.errors_before.is_empty()
+            && self.errors_after.is_empty()
+    }
+
+    /// The number of records with the given change.
+    pub fn count(&self, change: RecordChange) -> usize {
+        self.differences
+            .iter()
+            .filter(|difference| difference.change == change)
+            .count()
+    }
+
+    pub fn to_json_value(&self) -> Value {
+        let differences: Vec<Value> = self
+            .differences
+            .iter()
+            .map(|difference| {
+                json!({
+                    "event_record_id": difference.event_record_id,
+                    "change": difference.change.as_str(),
+                    "timestamp_before": difference.timestamp_before.map(|t| t.to_rfc3339()),
+                    "timestamp_after": difference.timestamp_after.map(|t| t.to_rfc3339()),
+                })
+            })
+            .collect();
+
+        json!({
+            "identical": self.is_identical(),
+            "added": self.count(RecordChange::Added),
+            "removed": self.count(RecordChange::Removed),
+            "modified": self.count(RecordChange::Modified),
+            "unchanged": self.unchanged,
+            "differences": differences,
+            "duplicates_before": self.duplicates_before,
+            "duplicates_after": self.duplicates_after,
+            "errors_before": self.errors_before,
+            "errors_after": self.errors_after,
+        })
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (side, errors) in &[
+            ("first", &self.errors_before),
+            ("second", &self.errors_after),
+        ] {
+            for error in errors.iter() {
+                writeln!(f, "Unreadable record in the {} file: {}", side, error)?;
+            }
+        }
+        for (side, duplicates) in &[
+            ("first", &self.duplicates_before),
+            ("second", &self.duplicates_after),
+        ] {
+            for id in duplicates.iter() {
+                writeln!(f, "Duplicated record ID {} in the {} file", id, side)?;
+            }
+        }
+
+        for difference in &self.differences {
+            let id = difference.event_record_id;
+            let (before, after) = (difference.timestamp_before, difference.timestamp_after);
+
+            match (difference.change, before, after) {
+                (RecordChange::Modified, Some(before), Some(after)) if before != after => {
+                    writeln!(f, "~ {}\t{} -> {}", id, before, after)?
+                }
+                (RecordChange::Modified, _, Some(after)) => writeln!(f, "~ {}\t{}", id, after)?,
+                (RecordChange::Added, _, Some(after)) => writeln!(f, "+ {}\t{}", id, after)?,
+                (RecordChange::Removed, Some(before), _) => writeln!(f, "- {}\t{}", id, before)?,
+                (change, _, _) => writeln!(f, "{} {}", change.as_str(), id)?,
+            }
+        }
+
+        write!(
+            f,
+            "{} added, {} removed, {} modified, {} unchanged",
+            self.count(RecordChange::Added),
+            self.count(RecordChange::Removed),
+            self.count(RecordChange::Modified),
+            self.unchanged
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_of_modified_records() {
+        let mut parser = EvtxParser::from_path("samples/security.evtx").unwrap();
+        let before = parser.fingerprints();
+        assert_eq!(before.records.len(), 2261);
+
+        let mut after = parser.fingerprints();
+        let ids: Vec<u64> = after.records.keys().copied().take(3).collect();
+        after.records.remove(&ids[0]);
+        after.records.get_mut(&ids[1]).unwrap().1[0] ^= 1;
+        let (timestamp, hash) = after.records[&ids[2]];
+        after.records.insert(1_000_000, (timestamp, hash));
+
+        let report = DiffReport::between(before, after);
+        assert!(!report.is_identical());
+        assert_eq!(report.unchanged, 2259);
+        assert_eq!(
+            report
+                .differences
+                .iter()
+                .map(|d| (d.event_record_id, d.change))
+                .collect::<Vec<_>>(),
+            vec![
+                (ids[0], RecordChange::Removed),
+                (ids[1], RecordChange::Modified),
+                (1_000_000, RecordChange::Added),
+            ]
+        );
+        assert!(report
+            .to_string()
+            .ends_with("1 added, 1 removed, 1 modified, 2259 unchanged"));
+        assert_eq!(report.to_json_value()["modified"], 1);
+    }
+}
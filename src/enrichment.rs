@@ -0,0 +1,524 @@
+//! Enrichment data (provider names, level and keyword names, event summaries, SID names,
+//! ATT&CK technique mappings), loaded at runtime from a directory of JSON or TOML files.
+//!
+//! Every file in the directory has the same (optional) tables:
+//!
+//! ```toml
+//! [providers]
+//! "54849625-5478-4994-a5ba-3e3b0328c30d" = "Microsoft-Windows-Security-Auditing"
+//!
+//! [levels]
+//! 4 = "Information"
+//!
+//! [keywords]
+//! "0x0020000000000000" = "Audit Success"
+//!
+//! [events."Microsoft-Windows-Security-Auditing"]
+//! 4624 = "An account was successfully logged on."
+//!
+//! [sids]
+//! "S-1-5-21-3623811015-3361044348-30300820-1013" = "CONTOSO\\alice"
+//!
+//! [[techniques]]
+//! id = "T1059.001"
+//! name = "PowerShell"
+//! provider = "Microsoft-Windows-PowerShell"
+//! event_id = 4104
+//! fields = { ScriptBlockText = "*-EncodedCommand*" }
+//! ```
+//!
+//! Files are merged in the order of their names, so entries in later files override earlier ones
+//! (techniques are accumulated).
+//!
+//! A record matches a technique when it's provider (case insensitive), `EventID` and the `EventData` fields
+//! listed in `fields` (case insensitive glob patterns) match, all of them being optional.
+//! The IDs (and names) of the matching techniques are added to the JSON output of records,
+//! as the ECS `threat.technique.id` (and `threat.technique.name`) fields.
+//!
+//! The summary of the event of a record is added to it's JSON output as an `event.description` field,
+//! taking precedence over the built-in descriptions of `ParserSettings::describe_events`.
+//!
+//! The providers of instrumentation manifests (see `wevt_manifest`) can be added with `add_manifest`.
+//! Their level, task, opcode, channel and keyword names are then used (by provider GUID and event descriptor)
+//! for the symbolic names of records of these providers, see `ParserSettings::symbolic_names`.
+use crate::err::{self, Result};
+use crate::record_filter::{normalize_provider, parse_number};
+use crate::wevt_manifest::{WevtManifest, WevtProvider};
+
+use log::debug;
+use serde_json::{Map, Value};
+use snafu::ResultExt;
+
+use glob::{MatchOptions, Pattern};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Enrichment {
+    /// Provider GUID (normalized) -> provider name.
+    providers: HashMap<String, String>,
+    levels: HashMap<u8, String>,
+    /// Keyword bit -> keyword name.
+    keywords: BTreeMap<u64, String>,
+    /// (Provider name (lowercase), EventID) -> summary.
+    events: HashMap<(String, u16), String>,
+    /// SID (uppercase) -> account name.
+    sids: HashMap<String, String>,
+    /// ATT&CK technique mappings, in the order they were loaded.
+    techniques: Vec<TechniqueMapping>,
+    /// Provider GUID (normalized) -> definitions of the provider from it's manifest.
+    manifests: HashMap<String, WevtProvider>,
+}
+
+/// An ATT&CK technique, and the records it applies to.
+#[derive(Debug, Clone, PartialEq)]
+struct TechniqueMapping {
+    id: String,
+    name: Option<String>,
+    /// Lowercase.
+    provider: Option<String>,
+    event_id: Option<u16>,
+    /// `EventData` field name -> pattern of it's value.
+    fields: Vec<(String, Pattern)>,
+}
+
+impl TechniqueMapping {
+    fn from_value(value: &Value) -> std::result::Result<Self, String> {
+        let table = value
+            .as_object()
+            .ok_or("Expected `techniques` to be an array of tables")?;
+
+        let string = |key: &str| -> std::result::Result<Option<String>, String> {
+            match table.get(key) {
+                Some(Value::String(s)) => Ok(Some(s.to_string())),
+                Some(_) => Err(format!("Expected `{}` to be a string", key)),
+                None => Ok(None),
+            }
+        };
+
+        let id = string("id")?.ok_or("Missing technique `id`")?;
+
+        let event_id = match table.get("event_id") {
+            Some(Value::Number(n)) => Some(number_key::<u16>(&n.to_string())?),
+            Some(Value::String(s)) => Some(number_key::<u16>(s)?),
+            Some(_) => return Err("Expected `event_id` to be a number".to_string()),
+            None => None,
+        };
+
+        let fields = string_table(table, "fields")?
+            .into_iter()
+            .map(|(name, pattern)| {
+                Pattern::new(pattern)
+                    .map(|pattern| (name.to_string(), pattern))
+                    .map_err(|e| format!("Invalid pattern `{}`: {}", pattern, e))
+            })
+            .collect::<std::result::Result<_, _>>()?;
+
+        Ok(TechniqueMapping {
+            id,
+            name: string("name")?,
+            provider: string("provider")?.map(|provider| provider.to_lowercase()),
+            event_id,
+            fields,
+        })
+    }
+
+    fn matches(
+        &self,
+        provider: Option<&str>,
+        event_id: Option<u16>,
+        field: &dyn Fn(&str) -> Option<String>,
+    ) -> bool {
+        let options = MatchOptions {
+            case_sensitive: false,
+            require_literal_separator: false,
+            require_literal_leading_dot: false,
+        };
+
+        let provider_matches = match &self.provider {
+            Some(expected) => {
+                provider.map_or(false, |provider| provider.to_lowercase() == *expected)
+            }
+            None => true,
+        };
+        let event_id_matches = self.event_id.is_none() || self.event_id == event_id;
+
+        provider_matches
+            && event_id_matches
+            && self.fields.iter().all(|(name, pattern)| {
+                field(name).map_or(false, |value| pattern.matches_with(&value, options))
+            })
+    }
+}
+
+impl Enrichment {
+    /// Loads and merges all the `.json` and `.toml` files in `path`.
+    /// Other files are ignored.
+    pub fn load_dir(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let mut files = fs::read_dir(path)
+            .context(err::FailedToOpenFile { path })?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()
+            .context(err::FailedToOpenFile { path })?;
+
+        files.sort();
+
+        let mut enrichment = Enrichment::default();
+
+        for file in files {
+            match file.extension().and_then(|e| e.to_str()) {
+                Some("json") | Some("toml") => enrichment.merge(Enrichment::load_file(&file)?),
+                _ => debug!("Ignoring `{}`", file.display()),
+            }
+        }
+
+        Ok(enrichment)
+    }
+
+    /// Loads a single JSON or TOML file (by it's extension).
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path).context(err::FailedToOpenFile { path })?;
+
+        let fail = |message: String| err::Error::FailedToLoadEnrichment {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let value: Value = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str::<toml::Value>(&data)
+                .map_err(|e| e.to_string())
+                .and_then(|v| serde_json::to_value(v).map_err(|e| e.to_string())),
+            _ => serde_json::from_str(&data).map_err(|e| e.to_string()),
+        }
+        .map_err(fail)?;
+
+        Enrichment::from_value(&value).map_err(fail)
+    }
+
+    fn from_value(value: &Value) -> std::result::Result<Self, String> {
+        let root = value.as_object().ok_or("Expected a table")?;
+        let mut enrichment = Enrichment::default();
+
+        for (provider, name) in string_table(root, "providers")? {
+            enrichment
+                .providers
+                .insert(normalize_provider(provider), name.to_string());
+        }
+
+        for (level, name) in string_table(root, "levels")? {
+            let level = number_key::<u8>(level)?;
+            enrichment.levels.insert(level, name.to_string());
+        }
+
+        for (keyword, name) in string_table(root, "keywords")? {
+            let keyword = number_key::<u64>(keyword)?;
+            enrichment.keywords.insert(keyword, name.to_string());
+        }
+
+        for (sid, name) in string_table(root, "sids")? {
+            enrichment.sids.insert(sid.to_uppercase(), name.to_string());
+        }
+
+        if let Some(techniques) = root.get("techniques") {
+            let techniques = techniques
+                .as_array()
+                .ok_or("Expected `techniques` to be an array of tables")?;
+
+            for technique in techniques {
+                enrichment
+                    .techniques
+                    .push(TechniqueMapping::from_value(technique)?);
+            }
+        }
+
+        if let Some(events) = root.get("events") {
+            let events = events
+                .as_object()
+                .ok_or("Expected `events` to be a table")?;
+
+            for (provider, provider_events) in events {
+                let provider_events = provider_events
+                    .as_object()
+                    .ok_or_else(|| format!("Expected `events.{}` to be a table", provider))?;
+
+                for (event_id, summary) in string_table(provider_events, "")? {
+                    enrichment.events.insert(
+                        (provider.to_lowercase(), number_key::<u16>(event_id)?),
+                        summary.to_string(),
+                    );
+                }
+            }
+        }
+
+        Ok(enrichment)
+    }
+
+    /// Adds the entries of `other`, overriding existing entries.
+    pub fn merge(&mut self, other: Enrichment) {
+        self.providers.extend(other.providers);
+        self.levels.extend(other.levels);
+        self.keywords.extend(other.keywords);
+        self.events.extend(other.events);
+        self.sids.extend(other.sids);
+        self.techniques.extend(other.techniques);
+        self.manifests.extend(other.manifests);
+    }
+
+    /// Adds the providers of `manifest`, overriding previously added definitions of the same providers.
+    pub fn add_manifest(&mut self, manifest: WevtManifest) {
+        for provider in manifest.providers {
+            self.manifests
+                .insert(normalize_provider(&provider.guid.to_string()), provider);
+        }
+    }
+
+    /// Returns the manifest definitions of the provider with the given GUID (with or without braces).
+    pub fn manifest_provider(&self, guid: &str) -> Option<&WevtProvider> {
+        self.manifests.get(&normalize_provider(guid))
+    }
+
+    /// Returns the name of the provider with the given GUID (with or without braces).
+    pub fn provider_name(&self, guid: &str) -> Option<&str> {
+        self.providers
+            .get(&normalize_provider(guid))
+            .map(String::as_str)
+    }
+
+    pub fn level_name(&self, level: u8) -> Option<&str> {
+        self.levels.get(&level).map(String::as_str)
+    }
+
+    /// Returns the names of all the known keywords set in `keywords`.
+    pub fn keyword_names(&self, keywords: u64) -> Vec<&str> {
+        self.keywords
+            .iter()
+            .filter(|(mask, _)| **mask != 0 && keywords & **mask == **mask)
+            .map(|(_, name)| name.as_str())
+            .collect()
+    }
+
+    /// Returns the summary of an event, by the provider name (case insensitive) and `EventID`.
+    pub fn event_summary(&self, provider: &str, event_id: u16) -> Option<&str> {
+        self.events
+            .get(&(provider.to_lowercase(), event_id))
+            .map(String::as_str)
+    }
+
+    /// Returns the name of the account with the given SID (case insensitive).
+    pub fn sid_name(&self, sid: &str) -> Option<&str> {
+        self.sids.get(&sid.to_uppercase()).map(String::as_str)
+    }
+
+    pub fn has_techniques(&self) -> bool {
+        !self.techniques.is_empty()
+    }
+
+    /// Returns the (ID, name) of the ATT&CK techniques matching an event, without duplicate IDs.
+    /// `field` returns the value of an `EventData` field by name.
+    pub fn techniques(
+        &self,
+        provider: Option<&str>,
+        event_id: Option<u16>,
+        field: &dyn Fn(&str) -> Option<String>,
+    ) -> Vec<(&str, Option<&str>)> {
+        let mut techniques: Vec<(&str, Option<&str>)> = vec![];
+
+        for technique in &self.techniques {
+            if techniques.iter().any(|(id, _)| *id == technique.id)
+                || !technique.matches(provider, event_id, field)
+            {
+                continue;
+            }
+
+            techniques.push((&technique.id, technique.name.as_deref()));
+        }
+
+        techniques
+    }
+}
+
+/// Returns the entries of a table of strings, `name` is the key of the table in `root`
+/// (or empty if `root` is the table itself).
+fn string_table<'v>(
+    root: &'v Map<String, Value>,
+    name: &str,
+) -> std::result::Result<Vec<(&'v str, &'v str)>, String> {
+    let table = if name.is_empty() {
+        root
+    } else {
+        match root.get(name) {
+            Some(table) => table
+                .as_object()
+                .ok_or_else(|| format!("Expected `{}` to be a table", name))?,
+            None => return Ok(vec![]),
+        }
+    };
+
+    table
+        .iter()
+        .map(|(key, value)| match value.as_str() {
+            Some(s) => Ok((key.as_str(), s)),
+            None => Err(format!("Expected `{}` to be a string", key)),
+        })
+        .collect()
+}
+
+fn number_key<N: TryFrom<i128>>(key: &str) -> std::result::Result<N, String> {
+    parse_number(key)
+        .and_then(|n| N::try_from(n).ok())
+        .ok_or_else(|| format!("Invalid number `{}`", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParserSettings;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn test_loads_and_merges_dir() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut f = File::create(dir.path().join("00-base.toml")).unwrap();
+        f.write_all(
+            br#"
+[providers]
+"54849625-5478-4994-a5ba-3e3b0328c30d" = "Microsoft-Windows-Security-Auditing"
+
+[levels]
+0 = "LogAlways"
+4 = "Information"
+
+[keywords]
+"0x0020000000000000" = "Audit Success"
+"0x8000000000000000" = "Classic"
+
+[events."Microsoft-Windows-Security-Auditing"]
+4624 = "An account was successfully logged on."
+
+[sids]
+"s-1-5-21-1-2-3-1013" = "CONTOSO\\alice"
+"#,
+        )
+        .unwrap();
+
+        let mut f = File::create(dir.path().join("01-override.json")).unwrap();
+        f.write_all(br#"{"levels": {"4": "Info"}}"#).unwrap();
+
+        File::create(dir.path().join("README.md")).unwrap();
+
+        let enrichment = Enrichment::load_dir(dir.path()).unwrap();
+
+        assert_eq!(
+            enrichment.provider_name("{54849625-5478-4994-A5BA-3E3B0328C30D}"),
+            Some("Microsoft-Windows-Security-Auditing")
+        );
+        assert_eq!(enrichment.level_name(0), Some("LogAlways"));
+        assert_eq!(enrichment.level_name(4), Some("Info"));
+        assert_eq!(
+            enrichment.keyword_names(0x8020_0000_0000_0000),
+            vec!["Audit Success", "Classic"]
+        );
+        assert_eq!(
+            enrichment.event_summary("microsoft-windows-security-auditing", 4624),
+            Some("An account was successfully logged on.")
+        );
+        assert_eq!(enrichment.event_summary("EventLog", 4624), None);
+        assert_eq!(
+            enrichment.sid_name("S-1-5-21-1-2-3-1013"),
+            Some("CONTOSO\\alice")
+        );
+
+        let settings = ParserSettings::new()
+            .load_enrichment_dir(dir.path())
+            .unwrap();
+        assert_eq!(settings.get_enrichment(), Some(&enrichment));
+    }
+
+    #[test]
+    fn test_matches_techniques() {
+        let value = serde_json::json!({
+            "techniques": [
+                {
+                    "id": "T1059.001",
+                    "name": "PowerShell",
+                    "provider": "Microsoft-Windows-PowerShell",
+                    "event_id": 4104,
+                    "fields": {"ScriptBlockText": "*-encodedcommand*"}
+                },
+                {"id": "T1070.001", "event_id": "1102"},
+                {"id": "T1059.001", "event_id": 4104}
+            ]
+        });
+        let enrichment = Enrichment::from_value(&value).unwrap();
+        assert!(enrichment.has_techniques());
+
+        let encoded = |name: &str| match name {
+            "ScriptBlockText" => Some("powershell -EncodedCommand AAAA".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            enrichment.techniques(Some("microsoft-windows-powershell"), Some(4104), &encoded),
+            vec![("T1059.001", Some("PowerShell"))]
+        );
+
+        let plain = |_: &str| Some("Get-Process".to_string());
+        assert_eq!(
+            enrichment.techniques(Some("Other"), Some(4104), &plain),
+            vec![("T1059.001", None)]
+        );
+        assert_eq!(
+            enrichment.techniques(Some("Microsoft-Windows-Eventlog"), Some(1102), &plain),
+            vec![("T1070.001", None)]
+        );
+        assert!(enrichment.techniques(None, Some(4624), &plain).is_empty());
+
+        let missing_id = serde_json::json!({"techniques": [{"event_id": 1102}]});
+        assert_eq!(
+            Enrichment::from_value(&missing_id).unwrap_err(),
+            "Missing technique `id`"
+        );
+    }
+
+    #[test]
+    fn test_adds_event_summaries_to_json_output() {
+        let value = serde_json::json!({
+            "events": {"Microsoft-Windows-Security-Auditing": {"4624": "A logon."}}
+        });
+        let settings = ParserSettings::new().enrichment(Enrichment::from_value(&value).unwrap());
+        let mut parser = crate::EvtxParser::from_path("samples/security.evtx")
+            .unwrap()
+            .with_configuration(settings);
+
+        let mut described = 0;
+        for record in parser.records_json_value() {
+            let data = record.unwrap().data;
+            let description = data["event"]["description"].as_str();
+            if data["Event"]["System"]["EventID"] == 4624 {
+                assert_eq!(description, Some("A logon."));
+                described += 1;
+            } else {
+                assert_eq!(description, None);
+            }
+        }
+        assert!(described > 0);
+    }
+
+    #[test]
+    fn test_reports_invalid_files() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut f = File::create(dir.path().join("bad.json")).unwrap();
+        f.write_all(br#"{"levels": {"a lot": "Critical"}}"#)
+            .unwrap();
+
+        let err = Enrichment::load_dir(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("Invalid number `a lot`"));
+    }
+}
@@ -3,6 +3,9 @@ use snafu::{Backtrace, ErrorCompat, GenerateBacktrace, Snafu};
 
 use std::io;
 use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::compression::Compression;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -62,6 +65,33 @@ pub enum Error {
         magic
     ))]
     InvalidEvtxFileHeaderMagic { magic: [u8; 8] },
+    #[snafu(display("Invalid EVTX record size: {}", size))]
+    InvalidEvtxRecordSize { size: u32 },
+
+    #[snafu(display(
+        "Skipped corrupted record data at chunk offsets {}..{}, caused by:\n\t {}",
+        start,
+        end,
+        source
+    ))]
+    SkippedCorruptedRecordData {
+        start: u64,
+        end: u64,
+        source: Box<Error>,
+    },
+
+    #[snafu(display(
+        "Failed to deserialize the record into the requested type: {}",
+        message
+    ))]
+    RecordDeserializationError { message: String },
+
+    #[snafu(display("Invalid EVT signature, expected `LfLe`, found `{:2X?}`", magic))]
+    InvalidEvtSignature { magic: [u8; 4] },
+
+    #[snafu(display("Offset {}: invalid EVT record, {}", offset, message))]
+    InvalidEvtRecord { offset: u64, message: String },
+
     #[snafu(display("Unknown EVTX record header flags value: {}", value))]
     UnknownEvtxHeaderFlagValue { value: u32 },
 
@@ -147,18 +177,83 @@ pub enum Error {
         offset: u64,
     },
 
+    #[snafu(display("Offset {}: `{}` is not a valid SYSTEMTIME", offset, value))]
+    InvalidSystemTime { value: String, offset: u64 },
+
     #[snafu(display("Offset {}: Failed to decode GUID, caused by: {}", offset, source))]
-    FailedToReadGUID {
-        source: winstructs::err::Error,
-        offset: u64,
-    },
+    FailedToReadGUID { source: std::io::Error, offset: u64 },
 
     #[snafu(display("Offset {}: Failed to decode NTSID, caused by: {}", offset, source))]
-    FailedToReadNTSID {
-        source: winstructs::err::Error,
-        offset: u64,
+    FailedToReadNTSID { source: std::io::Error, offset: u64 },
+
+    #[snafu(display("`{}` is not a valid {}", value, kind))]
+    InvalidIdentifier { value: String, kind: &'static str },
+
+    #[snafu(display("Invalid GeoIP database: {}", message))]
+    InvalidGeoIpDatabase { message: String },
+
+    #[snafu(display("Failed to read resources from PE file: {}", message))]
+    FailedToReadPEResources { message: String },
+
+    #[snafu(display("Offset {}: invalid WEVT_TEMPLATE manifest, {}", offset, message))]
+    InvalidWevtManifest { offset: u32, message: String },
+
+    #[snafu(display("Invalid filter expression at position {}: {}", position, message))]
+    InvalidFilterExpression { message: String, position: usize },
+
+    #[snafu(display("Invalid filter rules: {}", message))]
+    InvalidFilterRules { message: String },
+
+    #[snafu(display("Failed to load enrichment data from {}: {}", path.display(), message))]
+    FailedToLoadEnrichment { path: PathBuf, message: String },
+
+    #[snafu(display("Failed to load lookup table {}: {}", path.display(), message))]
+    FailedToLoadLookupTable { path: PathBuf, message: String },
+
+    #[snafu(display("Invalid regex `{}`: {}", pattern, source))]
+    InvalidRegex {
+        pattern: String,
+        source: regex::Error,
+    },
+
+    #[snafu(display("Failed to decompress {} ({}): {}", path.display(), compression, message))]
+    FailedToDecompressFile {
+        path: PathBuf,
+        compression: Compression,
+        message: String,
+    },
+
+    #[snafu(display(
+        "{} is {} compressed, reading it requires the `compression` feature",
+        path.display(),
+        compression
+    ))]
+    CompressedInputNotSupported {
+        path: PathBuf,
+        compression: Compression,
+    },
+
+    #[snafu(display("Writing {} compressed output is not supported", compression))]
+    UnsupportedOutputCompression { compression: Compression },
+
+    #[snafu(display(
+        "Writing {} compressed output requires the `compression` feature",
+        compression
+    ))]
+    CompressedOutputNotSupported { compression: Compression },
+
+    #[snafu(display("Invalid glob pattern `{}`: {}", pattern, source))]
+    InvalidGlobPattern {
+        pattern: String,
+        source: glob::PatternError,
     },
 
+    #[snafu(display("Invalid Sigma rule: {}", message))]
+    InvalidSigmaRule { message: String },
+
+    #[snafu(display("Failed to parse XML at position {}: {}", position, message))]
+    FailedToParseXml { message: String, position: usize },
+
     #[snafu(display("Failed to create record model, reason: {}", message))]
     FailedToCreateRecordModel { message: String },
 
@@ -177,6 +272,27 @@ pub enum Error {
     #[snafu(display("Record data contains invalid UTF-8: {}", source))]
     RecordContainsInvalidUTF8 { source: std::string::FromUtf8Error },
 
+    /// Errors related to Writing
+    #[snafu(display("A record of {} bytes does not fit in a chunk", size))]
+    RecordTooLarge { size: usize },
+
+    #[snafu(display("Failed to write value: {}", message))]
+    FailedToWriteValue { message: String },
+
+    #[snafu(display("An EVTX file cannot have more than {} chunks", u16::MAX))]
+    TooManyChunks,
+
+    #[snafu(display("Failed to import record: {}", message))]
+    FailedToImportRecord { message: String },
+
+    #[snafu(display("Chunk {} cannot be repaired: {}", chunk_number, message))]
+    UnrepairableChunk { chunk_number: u16, message: String },
+
+    /// An error which is returned again every time it's cause is needed,
+    /// such as the error deserializing a record (see `EvtxRecord::tokens`).
+    #[snafu(display("{}", source))]
+    Shared { source: Arc<Error> },
+
     /// Misc Errors
     #[snafu(display("Unimplemented: {}", name))]
     Unimplemented { name: String },
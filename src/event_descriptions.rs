@@ -0,0 +1,422 @@
+//! Short descriptions of well-known Security and Sysmon events (and of the fields of the most common ones),
+//! embedded in the crate.
+//!
+//! Enabled with `ParserSettings::describe_events`, the description of a record is added to its JSON output
+//! as an `event.description` field.
+
+/// The description of an event, and of the fields of its `EventData`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventDescription {
+    pub event_id: u16,
+    pub description: &'static str,
+    /// `(field name, meaning)` of the notable fields of the event.
+    pub fields: &'static [(&'static str, &'static str)],
+}
+
+const SECURITY_PROVIDER: &str = "Microsoft-Windows-Security-Auditing";
+const EVENTLOG_PROVIDER: &str = "Microsoft-Windows-Eventlog";
+const SYSMON_PROVIDER: &str = "Microsoft-Windows-Sysmon";
+
+/// Returns the description of the event `event_id` of `provider` (matched case insensitively).
+pub fn event_description(provider: &str, event_id: u16) -> Option<&'static EventDescription> {
+    let descriptions = if provider.eq_ignore_ascii_case(SECURITY_PROVIDER) {
+        SECURITY
+    } else if provider.eq_ignore_ascii_case(EVENTLOG_PROVIDER) {
+        EVENTLOG
+    } else if provider.eq_ignore_ascii_case(SYSMON_PROVIDER) {
+        SYSMON
+    } else {
+        return None;
+    };
+
+    descriptions
+        .binary_search_by_key(&event_id, |description| description.event_id)
+        .ok()
+        .map(|index| &descriptions[index])
+}
+
+const fn event(event_id: u16, description: &'static str) -> EventDescription {
+    EventDescription {
+        event_id,
+        description,
+        fields: &[],
+    }
+}
+
+const LOGON_TYPE: (&str, &str) = (
+    "LogonType",
+    "How the logon happened: 2 interactive, 3 network, 4 batch, 5 service, 7 unlock, \
+     8 network cleartext, 9 new credentials, 10 remote interactive (RDP), 11 cached interactive",
+);
+
+/// Sorted by `event_id`.
+static SECURITY: &[EventDescription] = &[
+    event(4608, "Windows is starting up."),
+    event(4616, "The system time was changed."),
+    EventDescription {
+        event_id: 4624,
+        description: "An account was successfully logged on.",
+        fields: &[
+            ("SubjectUserName", "Account which requested the logon (usually the computer account)"),
+            ("TargetUserName", "Account which was logged on"),
+            ("TargetDomainName", "Domain of the account which was logged on"),
+            ("TargetLogonId", "Identifier of the new logon session, to correlate with later events"),
+            LOGON_TYPE,
+            ("LogonProcessName", "Trusted logon process which handled the logon"),
+            ("AuthenticationPackageName", "Authentication package used (NTLM, Kerberos, Negotiate)"),
+            ("WorkstationName", "Name of the computer the logon came from"),
+            ("IpAddress", "Network address the logon came from"),
+            ("ProcessName", "Process which requested the logon"),
+            ("ElevatedToken", "Whether the session has an elevated (administrator) token"),
+        ],
+    },
+    EventDescription {
+        event_id: 4625,
+        description: "An account failed to log on.",
+        fields: &[
+            ("TargetUserName", "Account which failed to log on"),
+            ("TargetDomainName", "Domain of the account which failed to log on"),
+            ("Status", "NTSTATUS code of the failure"),
+            ("SubStatus", "Detailed NTSTATUS code (such as 0xC000006A for a bad password)"),
+            ("FailureReason", "Message ID of the reason of the failure"),
+            LOGON_TYPE,
+            ("WorkstationName", "Name of the computer the logon came from"),
+            ("IpAddress", "Network address the logon came from"),
+            ("ProcessName", "Process which requested the logon"),
+        ],
+    },
+    event(4634, "An account was logged off."),
+    event(4647, "User initiated logoff."),
+    event(4648, "A logon was attempted using explicit credentials."),
+    event(4656, "A handle to an object was requested."),
+    event(4657, "A registry value was modified."),
+    event(4660, "An object was deleted."),
+    event(4663, "An attempt was made to access an object."),
+    EventDescription {
+        event_id: 4672,
+        description: "Special privileges assigned to new logon.",
+        fields: &[
+            ("SubjectUserName", "Account which was logged on with administrative privileges"),
+            ("SubjectLogonId", "Identifier of the logon session, matching the `TargetLogonId` of 4624"),
+            ("PrivilegeList", "Sensitive privileges assigned to the session"),
+        ],
+    },
+    EventDescription {
+        event_id: 4688,
+        description: "A new process has been created.",
+        fields: &[
+            ("SubjectUserName", "Account which created the process"),
+            ("NewProcessId", "Identifier of the new process"),
+            ("NewProcessName", "Path of the executable of the new process"),
+            ("CommandLine", "Command line of the new process (when auditing of command lines is enabled)"),
+            ("ParentProcessName", "Path of the executable of the parent process"),
+            ("ProcessId", "Identifier of the parent process"),
+            (
+                "TokenElevationType",
+                "%%1936 full token (UAC disabled or built-in administrator), %%1937 elevated, %%1938 limited",
+            ),
+            ("MandatoryLabel", "Integrity level of the new process"),
+        ],
+    },
+    event(4689, "A process has exited."),
+    event(4697, "A service was installed in the system."),
+    event(4698, "A scheduled task was created."),
+    event(4699, "A scheduled task was deleted."),
+    event(4700, "A scheduled task was enabled."),
+    event(4701, "A scheduled task was disabled."),
+    event(4702, "A scheduled task was updated."),
+    event(4719, "System audit policy was changed."),
+    event(4720, "A user account was created."),
+    event(4722, "A user account was enabled."),
+    event(4723, "An attempt was made to change an account's password."),
+    event(4724, "An attempt was made to reset an account's password."),
+    event(4725, "A user account was disabled."),
+    event(4726, "A user account was deleted."),
+    event(4728, "A member was added to a security-enabled global group."),
+    event(4732, "A member was added to a security-enabled local group."),
+    event(4738, "A user account was changed."),
+    event(4740, "A user account was locked out."),
+    event(4756, "A member was added to a security-enabled universal group."),
+    event(4767, "A user account was unlocked."),
+    event(4768, "A Kerberos authentication ticket (TGT) was requested."),
+    event(4769, "A Kerberos service ticket was requested."),
+    event(4771, "Kerberos pre-authentication failed."),
+    event(4776, "The computer attempted to validate the credentials for an account."),
+    event(4778, "A session was reconnected to a Window Station."),
+    event(4779, "A session was disconnected from a Window Station."),
+    event(4798, "A user's local group membership was enumerated."),
+    event(4799, "A security-enabled local group membership was enumerated."),
+    event(4946, "A change has been made to Windows Firewall exception list. A rule was added."),
+    event(4947, "A change has been made to Windows Firewall exception list. A rule was modified."),
+    event(5140, "A network share object was accessed."),
+    event(5145, "A network share object was checked to see whether client can be granted desired access."),
+    event(5156, "The Windows Filtering Platform has permitted a connection."),
+];
+
+/// Sorted by `event_id`.
+static EVENTLOG: &[EventDescription] = &[
+    event(1100, "The event logging service has shut down."),
+    event(1102, "The audit log was cleared."),
+    event(1104, "The security log is now full."),
+    event(1105, "Event log automatic backup."),
+];
+
+const PROCESS_GUID: (&str, &str) = (
+    "ProcessGuid",
+    "Sysmon identifier of the process, unique across process ID reuse",
+);
+const IMAGE: (&str, &str) = ("Image", "Path of the executable of the process");
+
+/// Sorted by `event_id`.
+static SYSMON: &[EventDescription] = &[
+    EventDescription {
+        event_id: 1,
+        description: "Process creation.",
+        fields: &[
+            PROCESS_GUID,
+            IMAGE,
+            ("CommandLine", "Command line of the new process"),
+            ("User", "Account running the process"),
+            ("IntegrityLevel", "Integrity level of the process"),
+            ("Hashes", "Hashes of the executable"),
+            (
+                "ParentProcessGuid",
+                "Sysmon identifier of the parent process",
+            ),
+            (
+                "ParentImage",
+                "Path of the executable of the parent process",
+            ),
+            ("ParentCommandLine", "Command line of the parent process"),
+        ],
+    },
+    EventDescription {
+        event_id: 2,
+        description: "A process changed a file creation time.",
+        fields: &[
+            IMAGE,
+            ("TargetFilename", "File whose creation time was changed"),
+            ("CreationUtcTime", "New creation time of the file"),
+            (
+                "PreviousCreationUtcTime",
+                "Previous creation time of the file",
+            ),
+        ],
+    },
+    EventDescription {
+        event_id: 3,
+        description: "Network connection.",
+        fields: &[
+            IMAGE,
+            ("Initiated", "Whether the process initiated the connection"),
+            ("SourceIp", "Local address of the connection"),
+            ("DestinationIp", "Remote address of the connection"),
+            ("DestinationPort", "Remote port of the connection"),
+            ("DestinationHostname", "Resolved name of the remote address"),
+        ],
+    },
+    EventDescription {
+        event_id: 4,
+        description: "Sysmon service state changed.",
+        fields: &[("State", "New state of the service")],
+    },
+    EventDescription {
+        event_id: 5,
+        description: "Process terminated.",
+        fields: &[PROCESS_GUID, IMAGE],
+    },
+    EventDescription {
+        event_id: 6,
+        description: "Driver loaded.",
+        fields: &[
+            ("ImageLoaded", "Path of the driver"),
+            ("Signed", "Whether the driver is signed"),
+            ("Signature", "Signer of the driver"),
+        ],
+    },
+    EventDescription {
+        event_id: 7,
+        description: "Image loaded.",
+        fields: &[
+            IMAGE,
+            ("ImageLoaded", "Path of the loaded module"),
+            ("Signed", "Whether the module is signed"),
+        ],
+    },
+    EventDescription {
+        event_id: 8,
+        description: "CreateRemoteThread detected.",
+        fields: &[
+            ("SourceImage", "Process which created the thread"),
+            ("TargetImage", "Process the thread was created in"),
+            ("StartAddress", "Start address of the new thread"),
+        ],
+    },
+    EventDescription {
+        event_id: 9,
+        description: "RawAccessRead detected.",
+        fields: &[IMAGE, ("Device", "Device read directly")],
+    },
+    EventDescription {
+        event_id: 10,
+        description: "Process accessed.",
+        fields: &[
+            ("SourceImage", "Process which opened the other process"),
+            ("TargetImage", "Process which was opened"),
+            (
+                "GrantedAccess",
+                "Access rights granted on the target process",
+            ),
+            ("CallTrace", "Call stack of the access"),
+        ],
+    },
+    EventDescription {
+        event_id: 11,
+        description: "File created.",
+        fields: &[
+            IMAGE,
+            ("TargetFilename", "File which was created or overwritten"),
+        ],
+    },
+    EventDescription {
+        event_id: 12,
+        description: "Registry object added or deleted.",
+        fields: &[
+            (
+                "EventType",
+                "Whether the key or value was created or deleted",
+            ),
+            ("TargetObject", "Path of the registry key or value"),
+        ],
+    },
+    EventDescription {
+        event_id: 13,
+        description: "Registry value set.",
+        fields: &[
+            ("TargetObject", "Path of the registry value"),
+            ("Details", "New data of the value"),
+        ],
+    },
+    EventDescription {
+        event_id: 14,
+        description: "Registry object renamed.",
+        fields: &[
+            ("TargetObject", "Previous path of the registry key or value"),
+            ("NewName", "New path of the registry key or value"),
+        ],
+    },
+    EventDescription {
+        event_id: 15,
+        description: "File stream created.",
+        fields: &[
+            (
+                "TargetFilename",
+                "File the alternate data stream was created for",
+            ),
+            ("Hash", "Hashes of the stream"),
+        ],
+    },
+    EventDescription {
+        event_id: 16,
+        description: "Sysmon configuration changed.",
+        fields: &[("Configuration", "Path of the new configuration")],
+    },
+    EventDescription {
+        event_id: 17,
+        description: "Pipe created.",
+        fields: &[IMAGE, ("PipeName", "Name of the named pipe")],
+    },
+    EventDescription {
+        event_id: 18,
+        description: "Pipe connected.",
+        fields: &[IMAGE, ("PipeName", "Name of the named pipe")],
+    },
+    EventDescription {
+        event_id: 19,
+        description: "WmiEventFilter activity detected.",
+        fields: &[("Query", "WQL query of the filter")],
+    },
+    EventDescription {
+        event_id: 20,
+        description: "WmiEventConsumer activity detected.",
+        fields: &[("Destination", "Command or script run by the consumer")],
+    },
+    EventDescription {
+        event_id: 21,
+        description: "WmiEventConsumerToFilter activity detected.",
+        fields: &[
+            ("Consumer", "Path of the consumer"),
+            ("Filter", "Path of the filter"),
+        ],
+    },
+    EventDescription {
+        event_id: 22,
+        description: "DNS query.",
+        fields: &[
+            IMAGE,
+            ("QueryName", "Queried name"),
+            ("QueryResults", "Addresses returned for the query"),
+        ],
+    },
+    EventDescription {
+        event_id: 23,
+        description: "File deleted (archived).",
+        fields: &[
+            IMAGE,
+            ("TargetFilename", "File which was deleted"),
+            (
+                "Archived",
+                "Whether the file was copied to the archive directory",
+            ),
+        ],
+    },
+    EventDescription {
+        event_id: 24,
+        description: "Clipboard changed.",
+        fields: &[
+            IMAGE,
+            ("Hashes", "Hashes of the archived clipboard contents"),
+        ],
+    },
+    EventDescription {
+        event_id: 25,
+        description: "Process tampering.",
+        fields: &[
+            IMAGE,
+            ("Type", "Kind of tampering (such as image replaced)"),
+        ],
+    },
+    EventDescription {
+        event_id: 26,
+        description: "File delete logged.",
+        fields: &[IMAGE, ("TargetFilename", "File which was deleted")],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tables_are_sorted() {
+        for table in [SECURITY, EVENTLOG, SYSMON].iter() {
+            assert!(table
+                .windows(2)
+                .all(|pair| pair[0].event_id < pair[1].event_id));
+        }
+    }
+
+    #[test]
+    fn test_event_description() {
+        let logon = event_description("microsoft-windows-security-auditing", 4624).unwrap();
+        assert_eq!(logon.description, "An account was successfully logged on.");
+        assert!(logon.fields.iter().any(|(name, _)| *name == "LogonType"));
+
+        assert_eq!(
+            event_description("Microsoft-Windows-Sysmon", 22).map(|d| d.description),
+            Some("DNS query.")
+        );
+        assert!(event_description("Microsoft-Windows-Sysmon", 4624).is_none());
+        assert!(event_description("Service Control Manager", 7045).is_none());
+    }
+}
@@ -0,0 +1,754 @@
+//! Parsing of the legacy (pre-Vista) `.evt` event log format.
+//!
+//! An `.evt` file is a 48 bytes header, followed by a circular buffer of `EVENTLOGRECORD`s
+//! (which may be split at the end of the file, and continue right after the header),
+//! terminated by an end of file record.
+//! Records are rendered with the same outputs as EVTX records, in the shape `wevtutil` gives to classic events.
+use crate::binxml::name::BinXmlName;
+use crate::binxml::value_variant::BinXmlValue;
+use crate::compression::open_input;
+use crate::err::{self, Result};
+use crate::evtx_parser::Sampler;
+use crate::evtx_record::SerializedEvtxRecord;
+use crate::model::xml::{XmlAttribute, XmlElement};
+use crate::utils::read_null_terminated_utf16_string;
+use crate::xml_output::{BinXmlOutput, XmlOutput};
+use crate::{JsonOutput, ParserSettings};
+
+use crate::sid::Sid;
+use byteorder::{LittleEndian, ReadBytesExt};
+use chrono::{DateTime, TimeZone, Utc};
+use log::{debug, warn};
+use snafu::{ensure, OptionExt, ResultExt};
+
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+pub const EVT_FILE_HEADER_SIZE: usize = 48;
+const EVT_SIGNATURE: &[u8; 4] = b"LfLe";
+/// The size of the fixed part of an `EVENTLOGRECORD`, up to the source name.
+const EVT_RECORD_FIXED_SIZE: usize = 56;
+const EVT_EOF_RECORD_SIZE: usize = 40;
+const EVT_EOF_RECORD_MAGIC: [u8; 16] = [
+    0x11, 0x11, 0x11, 0x11, 0x22, 0x22, 0x22, 0x22, 0x33, 0x33, 0x33, 0x33, 0x44, 0x44, 0x44, 0x44,
+];
+
+/// The header of an `.evt` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvtFileHeader {
+    pub major_version: u32,
+    pub minor_version: u32,
+    /// Offset of the oldest record.
+    pub start_offset: u32,
+    /// Offset of the end of file record.
+    pub end_offset: u32,
+    pub current_record_number: u32,
+    pub oldest_record_number: u32,
+    pub max_size: u32,
+    pub flags: u32,
+    pub retention: u32,
+}
+
+impl EvtFileHeader {
+    /// The header was not updated since records were last written (`ELF_LOGFILE_HEADER_DIRTY`).
+    pub const DIRTY: u32 = 0x1;
+    /// The log has wrapped around (`ELF_LOGFILE_HEADER_WRAP`).
+    pub const WRAPPED: u32 = 0x2;
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let mut magic = [0_u8; 4];
+        magic.copy_from_slice(data.get(4..8).unwrap_or(&[0; 4]));
+        ensure!(&magic == EVT_SIGNATURE, err::InvalidEvtSignature { magic });
+
+        let mut cursor = Cursor::new(data);
+        cursor.seek(SeekFrom::Start(8))?;
+
+        Ok(EvtFileHeader {
+            major_version: cursor.read_u32::<LittleEndian>()?,
+            minor_version: cursor.read_u32::<LittleEndian>()?,
+            start_offset: cursor.read_u32::<LittleEndian>()?,
+            end_offset: cursor.read_u32::<LittleEndian>()?,
+            current_record_number: cursor.read_u32::<LittleEndian>()?,
+            oldest_record_number: cursor.read_u32::<LittleEndian>()?,
+            max_size: cursor.read_u32::<LittleEndian>()?,
+            flags: cursor.read_u32::<LittleEndian>()?,
+            retention: cursor.read_u32::<LittleEndian>()?,
+        })
+    }
+
+    /// Returns `true` if `data` starts with an `.evt` file header.
+    pub fn has_signature(data: &[u8]) -> bool {
+        data.get(4..8) == Some(&EVT_SIGNATURE[..])
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.flags & Self::DIRTY != 0
+    }
+
+    pub fn is_wrapped(&self) -> bool {
+        self.flags & Self::WRAPPED != 0
+    }
+}
+
+/// A single `EVENTLOGRECORD` of an `.evt` file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvtRecord {
+    pub record_number: u32,
+    pub time_generated: DateTime<Utc>,
+    pub time_written: DateTime<Utc>,
+    /// The full event identifier, including the qualifiers in it's high word.
+    pub event_id: u32,
+    pub event_type: u16,
+    pub event_category: u16,
+    pub source_name: String,
+    pub computer_name: String,
+    pub user_sid: Option<Sid>,
+    pub strings: Vec<String>,
+    pub data: Vec<u8>,
+    settings: Arc<ParserSettings>,
+}
+
+impl EvtRecord {
+    /// Parses the (complete) record in `data`, found at `offset` of the file.
+    pub fn from_bytes(data: &[u8], offset: u64, settings: Arc<ParserSettings>) -> Result<Self> {
+        let invalid = |message: &str| err::InvalidEvtRecord {
+            offset,
+            message: message.to_string(),
+        };
+
+        ensure!(
+            data.len() >= EVT_RECORD_FIXED_SIZE + 4,
+            invalid("record is too small")
+        );
+        ensure!(&data[4..8] == EVT_SIGNATURE, invalid("invalid signature"));
+
+        let mut cursor = Cursor::new(data);
+        cursor.seek(SeekFrom::Start(8))?;
+
+        let record_number = cursor.read_u32::<LittleEndian>()?;
+        let time_generated = cursor.read_u32::<LittleEndian>()?;
+        let time_written = cursor.read_u32::<LittleEndian>()?;
+        let event_id = cursor.read_u32::<LittleEndian>()?;
+        let event_type = cursor.read_u16::<LittleEndian>()?;
+        let num_strings = cursor.read_u16::<LittleEndian>()?;
+        let event_category = cursor.read_u16::<LittleEndian>()?;
+        let _reserved_flags = cursor.read_u16::<LittleEndian>()?;
+        let _closing_record_number = cursor.read_u32::<LittleEndian>()?;
+        let string_offset = cursor.read_u32::<LittleEndian>()? as usize;
+        let user_sid_length = cursor.read_u32::<LittleEndian>()? as usize;
+        let user_sid_offset = cursor.read_u32::<LittleEndian>()? as usize;
+        let data_length = cursor.read_u32::<LittleEndian>()? as usize;
+        let data_offset = cursor.read_u32::<LittleEndian>()? as usize;
+
+        let source_name = read_null_terminated_utf16_string(&mut cursor)?;
+        let computer_name = read_null_terminated_utf16_string(&mut cursor)?;
+
+        let user_sid = if user_sid_length == 0 {
+            None
+        } else {
+            let sid = data
+                .get(user_sid_offset..user_sid_offset + user_sid_length)
+                .context(invalid("the user SID is out of bounds"))?;
+            Some(
+                Sid::from_reader(&mut Cursor::new(sid)).context(err::FailedToReadNTSID {
+                    offset: offset + user_sid_offset as u64,
+                })?,
+            )
+        };
+
+        cursor.seek(SeekFrom::Start(string_offset as u64))?;
+        let strings = (0..num_strings)
+            .map(|_| read_null_terminated_utf16_string(&mut cursor))
+            .collect::<std::io::Result<Vec<String>>>()?;
+
+        let data = data
+            .get(data_offset..data_offset + data_length)
+            .context(invalid("the event data is out of bounds"))?
+            .to_vec();
+
+        let time = |seconds: u32| {
+            Utc.timestamp_opt(i64::from(seconds), 0)
+                .single()
+                .expect("u32 seconds are in range")
+        };
+
+        Ok(EvtRecord {
+            record_number,
+            time_generated: time(time_generated),
+            time_written: time(time_written),
+            event_id,
+            event_type,
+            event_category,
+            source_name,
+            computer_name,
+            user_sid,
+            strings,
+            data,
+            settings,
+        })
+    }
+
+    /// The `Level` of the event, as rendered for classic events.
+    pub fn level(&self) -> u8 {
+        match self.event_type {
+            0x0001 => 2,
+            0x0002 => 3,
+            // Audit success and audit failure.
+            0x0008 | 0x0010 => 0,
+            _ => 4,
+        }
+    }
+
+    /// The `Keywords` of the event, as rendered for classic events.
+    pub fn keywords(&self) -> u64 {
+        match self.event_type {
+            0x0008 => 0x8020_0000_0000_0000,
+            0x0010 => 0x8010_0000_0000_0000,
+            _ => 0x0080_0000_0000_0000,
+        }
+    }
+
+    /// Passes the record to the output, in the same shape as an EVTX record.
+    pub fn into_output<T: BinXmlOutput>(&self, output: &mut T) -> Result<()> {
+        let qualifiers = BinXmlValue::UInt16Type((self.event_id >> 16) as u16);
+        let keywords = BinXmlValue::HexInt64Type(Cow::Owned(format!("0x{:x}", self.keywords())));
+
+        output.visit_start_of_stream()?;
+        let event = element(
+            "Event",
+            vec![(
+                "xmlns",
+                BinXmlValue::StringType(Cow::Borrowed(
+                    "http://schemas.microsoft.com/win/2004/08/events/event",
+                )),
+            )],
+        );
+        output.visit_open_start_element(&event)?;
+
+        let system = element("System", vec![]);
+        output.visit_open_start_element(&system)?;
+        let provider_name = BinXmlValue::StringType(Cow::Borrowed(&self.source_name));
+        visit_element(output, "Provider", vec![("Name", provider_name)], None)?;
+        let event_id = BinXmlValue::UInt16Type(self.event_id as u16);
+        visit_element(
+            output,
+            "EventID",
+            vec![("Qualifiers", qualifiers)],
+            Some(event_id),
+        )?;
+        let level = BinXmlValue::UInt8Type(self.level());
+        visit_element(output, "Level", vec![], Some(level))?;
+        let task = BinXmlValue::UInt16Type(self.event_category);
+        visit_element(output, "Task", vec![], Some(task))?;
+        visit_element(output, "Keywords", vec![], Some(keywords))?;
+        let time_created = BinXmlValue::FileTimeType(self.time_generated);
+        visit_element(
+            output,
+            "TimeCreated",
+            vec![("SystemTime", time_created)],
+            None,
+        )?;
+        let record_id = BinXmlValue::UInt64Type(u64::from(self.record_number));
+        visit_element(output, "EventRecordID", vec![], Some(record_id))?;
+        let computer = BinXmlValue::StringType(Cow::Borrowed(&self.computer_name));
+        visit_element(output, "Computer", vec![], Some(computer))?;
+        let security = match &self.user_sid {
+            Some(sid) => vec![("UserID", BinXmlValue::SidType(sid.clone()))],
+            None => vec![],
+        };
+        visit_element(output, "Security", security, None)?;
+        output.visit_close_element(&system)?;
+
+        let event_data = element("EventData", vec![]);
+        output.visit_open_start_element(&event_data)?;
+        for string in self.strings.iter() {
+            let value = BinXmlValue::StringType(Cow::Borrowed(string));
+            visit_element(output, "Data", vec![], Some(value))?;
+        }
+        if !self.data.is_empty() {
+            let value = BinXmlValue::BinaryType(Cow::Borrowed(&self.data));
+            visit_element(output, "Binary", vec![], Some(value))?;
+        }
+        output.visit_close_element(&event_data)?;
+
+        output.visit_close_element(&event)?;
+        output.visit_end_of_stream()
+    }
+
+    pub fn into_json_value(self) -> Result<SerializedEvtxRecord<serde_json::Value>> {
+        let mut output = JsonOutput::new(&self.settings);
+        self.into_output(&mut output)?;
+
+        Ok(self.serialized(output.into_value()?))
+    }
+
+    pub fn into_json(self) -> Result<SerializedEvtxRecord<String>> {
+        let indent = self.settings.should_indent();
+        let record = self.into_json_value()?;
+
+        let data = if indent {
+            serde_json::to_string_pretty(&record.data).context(err::JsonError)?
+        } else {
+            serde_json::to_string(&record.data).context(err::JsonError)?
+        };
+
+        Ok(SerializedEvtxRecord {
+            event_record_id: record.event_record_id,
+            timestamp: record.timestamp,
+            data,
+            recovered: record.recovered,
+            errors: record.errors,
+        })
+    }
+
+    pub fn into_xml(self) -> Result<SerializedEvtxRecord<String>> {
+        let mut output = XmlOutput::with_writer(Vec::new(), &self.settings);
+        self.into_output(&mut output)?;
+
+        let data =
+            String::from_utf8(output.into_writer()?).context(err::RecordContainsInvalidUTF8)?;
+
+        Ok(self.serialized(data))
+    }
+
+    fn serialized<T>(&self, data: T) -> SerializedEvtxRecord<T> {
+        SerializedEvtxRecord {
+            event_record_id: u64::from(self.record_number),
+            timestamp: self.time_generated,
+            data,
+            recovered: false,
+            errors: vec![],
+        }
+    }
+}
+
+fn element<'a>(
+    name: &'static str,
+    attributes: Vec<(&'static str, BinXmlValue<'a>)>,
+) -> XmlElement<'a> {
+    XmlElement {
+        name: Cow::Owned(BinXmlName::from_static_string(name)),
+        attributes: attributes
+            .into_iter()
+            .map(|(name, value)| XmlAttribute {
+                name: Cow::Owned(BinXmlName::from_static_string(name)),
+                value: Cow::Owned(value),
+            })
+            .collect(),
+    }
+}
+
+/// Visits a whole element, with an optional text value.
+fn visit_element<'a, T: BinXmlOutput>(
+    output: &mut T,
+    name: &'static str,
+    attributes: Vec<(&'static str, BinXmlValue<'a>)>,
+    value: Option<BinXmlValue<'a>>,
+) -> Result<()> {
+    let element = element(name, attributes);
+
+    output.visit_open_start_element(&element)?;
+    if let Some(value) = value {
+        output.visit_characters(&value)?;
+    }
+    output.visit_close_element(&element)
+}
+
+/// A parser of `.evt` files, which are small enough (at most a few dozen MB) to be read into memory.
+pub struct EvtParser {
+    data: Vec<u8>,
+    header: EvtFileHeader,
+    config: Arc<ParserSettings>,
+}
+
+impl EvtParser {
+    /// Attempts to load an `.evt` file from a given path.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path
+            .as_ref()
+            .canonicalize()
+            .context(err::InvalidInputPath {
+                path: path.as_ref().to_string_lossy().to_string(),
+            })?;
+
+        Self::from_read(open_input(&path)?)
+    }
+
+    pub fn from_read(mut read: impl Read) -> Result<Self> {
+        let mut data = vec![];
+        read.read_to_end(&mut data)?;
+
+        Self::from_buffer(data)
+    }
+
+    pub fn from_buffer(data: Vec<u8>) -> Result<Self> {
+        let header = EvtFileHeader::from_bytes(&data)?;
+
+        debug!("EVT Header: {:#?}", header);
+
+        Ok(EvtParser {
+            data,
+            header,
+            config: Arc::new(ParserSettings::default()),
+        })
+    }
+
+    /// Only the indentation, the JSON attributes and the record ID and time filters of the settings apply.
+    pub fn with_configuration(mut self, configuration: ParserSettings) -> Self {
+        self.config = Arc::new(configuration);
+        self
+    }
+
+    pub fn header(&self) -> &EvtFileHeader {
+        &self.header
+    }
+
+    /// Returns an iterator over the records of the file, from the oldest to the newest.
+    ///
+    /// The iteration stops at the end of file record, or at the first record which cannot be read.
+    pub fn evt_records(&self) -> impl Iterator<Item = Result<EvtRecord>> + '_ {
+        let (start, end) = self.record_offsets();
+        let settings = Arc::clone(&self.config);
+        let mut sampler = Sampler::new(&settings);
+
+        IterEvtRecords {
+            data: &self.data,
+            offset: start,
+            end,
+            remaining: self.data.len().saturating_sub(EVT_FILE_HEADER_SIZE),
+            settings: Arc::clone(&self.config),
+        }
+        .filter(move |record| match record {
+            Ok(record) => {
+                settings.matches_record_id(u64::from(record.record_number))
+                    && settings.matches_timestamp(&record.time_generated)
+            }
+            Err(_) => true,
+        })
+        .filter(move |record| sampler.keep(record))
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will be XML-formatted.
+    pub fn records(&self) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.evt_records()
+            .map(|record| record.and_then(EvtRecord::into_xml))
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will be JSON-formatted.
+    pub fn records_json(&self) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.evt_records()
+            .map(|record| record.and_then(EvtRecord::into_json))
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will have a `serde_json::Value` data attribute.
+    pub fn records_json_value(
+        &self,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<serde_json::Value>>> + '_ {
+        self.evt_records()
+            .map(|record| record.and_then(EvtRecord::into_json_value))
+    }
+
+    /// The offsets of the oldest record and of the end of file record.
+    ///
+    /// The offsets of the header of a dirty file might be stale, so they are taken from it's end of file record.
+    fn record_offsets(&self) -> (usize, usize) {
+        let from_header = (
+            self.header.start_offset as usize,
+            self.header.end_offset as usize,
+        );
+
+        if !self.header.is_dirty() {
+            return from_header;
+        }
+
+        let eof_record = self.data[EVT_FILE_HEADER_SIZE.min(self.data.len())..]
+            .windows(EVT_EOF_RECORD_SIZE)
+            .find(|window| window[4..20] == EVT_EOF_RECORD_MAGIC);
+
+        match eof_record {
+            Some(record) => {
+                let offset = |at: usize| {
+                    u32::from_le_bytes(record[at..at + 4].try_into().expect("4 bytes")) as usize
+                };
+                (offset(20), offset(24))
+            }
+            None => {
+                warn!("The file is dirty, but no end of file record was found");
+                from_header
+            }
+        }
+    }
+}
+
+/// Follows the records of the circular buffer of the file, from the oldest record up to the end of file record.
+struct IterEvtRecords<'a> {
+    data: &'a [u8],
+    offset: usize,
+    end: usize,
+    /// The number of bytes which can still be read, so that a corrupted file can't loop forever.
+    remaining: usize,
+    settings: Arc<ParserSettings>,
+}
+
+impl<'a> IterEvtRecords<'a> {
+    /// Reads `len` bytes at `offset`, continuing after the file header at the end of the file.
+    fn read_circular(&self, offset: usize, len: usize) -> Option<Vec<u8>> {
+        let buffer_size = self.data.len().checked_sub(EVT_FILE_HEADER_SIZE)?;
+        if offset < EVT_FILE_HEADER_SIZE || offset >= self.data.len() || len > buffer_size {
+            return None;
+        }
+
+        let first = &self.data[offset..self.data.len().min(offset + len)];
+        let mut bytes = first.to_vec();
+        let rest = len - first.len();
+        bytes.extend_from_slice(&self.data[EVT_FILE_HEADER_SIZE..EVT_FILE_HEADER_SIZE + rest]);
+
+        Some(bytes)
+    }
+
+    fn wrap(&self, offset: usize) -> usize {
+        if offset >= self.data.len() {
+            offset - self.data.len() + EVT_FILE_HEADER_SIZE
+        } else {
+            offset
+        }
+    }
+}
+
+impl<'a> Iterator for IterEvtRecords<'a> {
+    type Item = Result<EvtRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.offset == self.end || self.remaining == 0 {
+            return None;
+        }
+
+        let offset = self.offset;
+        let invalid = |message: &str| {
+            err::InvalidEvtRecord {
+                offset: offset as u64,
+                message: message.to_string(),
+            }
+            .fail()
+        };
+
+        let size = match self.read_circular(offset, 4) {
+            Some(size) => u32::from_le_bytes(size[..].try_into().expect("4 bytes")) as usize,
+            None => {
+                self.remaining = 0;
+                return Some(invalid("the record offset is out of bounds"));
+            }
+        };
+
+        if size < EVT_RECORD_FIXED_SIZE + 4 || size > self.remaining {
+            self.remaining = 0;
+            return Some(invalid(&format!("invalid record size {}", size)));
+        }
+
+        let record = self
+            .read_circular(offset, size)
+            .expect("size is within the buffer");
+
+        if record[size - 4..] != record[..4] {
+            self.remaining = 0;
+            return Some(invalid("the trailing size does not match the record size"));
+        }
+
+        self.offset = self.wrap(offset + size);
+        self.remaining -= size;
+
+        let record = EvtRecord::from_bytes(&record, offset as u64, Arc::clone(&self.settings));
+        if record.is_err() {
+            self.remaining = 0;
+        }
+
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16()
+            .chain(Some(0))
+            .flat_map(u16::to_le_bytes)
+            .collect()
+    }
+
+    fn record(record_number: u32, strings: &[&str], data: &[u8]) -> Vec<u8> {
+        // S-1-5-18
+        let sid = [1, 1, 0, 0, 0, 0, 0, 5, 18, 0, 0, 0];
+
+        let mut variable = utf16("Service Control Manager");
+        variable.extend(utf16("WIN2003"));
+        let sid_offset = EVT_RECORD_FIXED_SIZE + variable.len();
+        variable.extend_from_slice(&sid);
+        let string_offset = EVT_RECORD_FIXED_SIZE + variable.len();
+        for s in strings {
+            variable.extend(utf16(s));
+        }
+        let data_offset = EVT_RECORD_FIXED_SIZE + variable.len();
+        variable.extend_from_slice(data);
+        // Records are 4 bytes aligned (the fixed part already is).
+        variable.resize((variable.len() + 3) / 4 * 4, 0);
+
+        let size = (EVT_RECORD_FIXED_SIZE + variable.len() + 4) as u32;
+        let mut record = vec![];
+        for value in &[size, u32::from_le_bytes(*EVT_SIGNATURE), record_number] {
+            record.extend_from_slice(&value.to_le_bytes());
+        }
+        // Time generated and written.
+        record.extend_from_slice(&1_100_000_000_u32.to_le_bytes());
+        record.extend_from_slice(&1_100_000_001_u32.to_le_bytes());
+        record.extend_from_slice(&0x4000_1b7a_u32.to_le_bytes());
+        // Information, with the number of strings and category 0.
+        record.extend_from_slice(&4_u16.to_le_bytes());
+        record.extend_from_slice(&(strings.len() as u16).to_le_bytes());
+        record.extend_from_slice(&[0; 8]);
+        for value in &[
+            string_offset,
+            sid.len(),
+            sid_offset,
+            data.len(),
+            data_offset,
+        ] {
+            record.extend_from_slice(&(*value as u32).to_le_bytes());
+        }
+        record.extend(variable);
+        record.extend_from_slice(&size.to_le_bytes());
+
+        record
+    }
+
+    fn eof_record(start: usize, end: usize, current: u32, oldest: u32) -> Vec<u8> {
+        let mut record = (EVT_EOF_RECORD_SIZE as u32).to_le_bytes().to_vec();
+        record.extend_from_slice(&EVT_EOF_RECORD_MAGIC);
+        for value in &[start as u32, end as u32, current, oldest] {
+            record.extend_from_slice(&value.to_le_bytes());
+        }
+        record.extend_from_slice(&(EVT_EOF_RECORD_SIZE as u32).to_le_bytes());
+        record
+    }
+
+    fn header(
+        start: usize,
+        end: usize,
+        current: u32,
+        oldest: u32,
+        size: usize,
+        flags: u32,
+    ) -> Vec<u8> {
+        let mut header = vec![];
+        for value in &[
+            EVT_FILE_HEADER_SIZE as u32,
+            u32::from_le_bytes(*EVT_SIGNATURE),
+            1,
+            1,
+            start as u32,
+            end as u32,
+            current,
+            oldest,
+            size as u32,
+            flags,
+            0,
+            EVT_FILE_HEADER_SIZE as u32,
+        ] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        header
+    }
+
+    fn evt_file(records: &[Vec<u8>]) -> Vec<u8> {
+        let end = EVT_FILE_HEADER_SIZE + records.iter().map(Vec::len).sum::<usize>();
+        let size = end + EVT_EOF_RECORD_SIZE + 64;
+        let current = records.len() as u32 + 1;
+
+        let mut file = header(EVT_FILE_HEADER_SIZE, end, current, 1, size, 0);
+        records.iter().for_each(|record| file.extend(record));
+        file.extend(eof_record(EVT_FILE_HEADER_SIZE, end, current, 1));
+        file.resize(size, 0);
+        file
+    }
+
+    #[test]
+    fn test_parses_records() {
+        let file = evt_file(&[
+            record(1, &["Print Spooler", "running"], &[]),
+            record(2, &["Print Spooler", "stopped"], &[0xde, 0xad]),
+        ]);
+        let parser = EvtParser::from_buffer(file).unwrap();
+
+        let records: Vec<EvtRecord> = parser.evt_records().map(Result::unwrap).collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].record_number, 2);
+        assert_eq!(records[1].event_id & 0xffff, 7034);
+        assert_eq!(records[1].source_name, "Service Control Manager");
+        assert_eq!(records[1].computer_name, "WIN2003");
+        assert_eq!(records[1].strings, vec!["Print Spooler", "stopped"]);
+        assert_eq!(records[1].data, vec![0xde, 0xad]);
+        assert_eq!(
+            records[1].user_sid.as_ref().unwrap().to_string(),
+            "S-1-5-18"
+        );
+
+        let json = parser.records_json_value().next().unwrap().unwrap();
+        let system = &json.data["Event"]["System"];
+        assert_eq!(system["EventID"]["#text"], 7034);
+        assert_eq!(system["EventID"]["#attributes"]["Qualifiers"], 16384);
+        assert_eq!(
+            system["Provider"]["#attributes"]["Name"],
+            "Service Control Manager"
+        );
+        assert_eq!(system["EventRecordID"], 1);
+
+        let xml = parser.records().nth(1).unwrap().unwrap();
+        assert!(xml.data.contains("<Data>stopped</Data>"), "{}", xml.data);
+        assert!(xml.data.contains("<Binary>DEAD</Binary>"), "{}", xml.data);
+    }
+
+    #[test]
+    fn test_follows_records_wrapping_around_the_end_of_the_file() {
+        let first = record(7, &["first"], &[]);
+        let second = record(8, &["second"], &[]);
+        let eof = eof_record(0, 0, 9, 7);
+
+        // The second record is split, and continues after the header.
+        let split = 20;
+        let start = EVT_FILE_HEADER_SIZE + eof.len() + (second.len() - split) + 16;
+        let size = start + first.len() + split;
+        let end = EVT_FILE_HEADER_SIZE + second.len() - split;
+
+        let mut file = header(start, end, 9, 7, size, EvtFileHeader::WRAPPED);
+        file.extend_from_slice(&second[split..]);
+        file.extend(eof);
+        file.resize(start, 0);
+        file.extend(first);
+        file.extend_from_slice(&second[..split]);
+
+        let parser = EvtParser::from_buffer(file).unwrap();
+        let records: Vec<u32> = parser
+            .evt_records()
+            .map(|record| record.unwrap().record_number)
+            .collect();
+
+        assert!(parser.header().is_wrapped());
+        assert_eq!(records, vec![7, 8]);
+    }
+
+    #[test]
+    fn test_rejects_other_files() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        assert!(!EvtFileHeader::has_signature(evtx_file));
+        assert!(EvtParser::from_buffer(evtx_file.to_vec()).is_err());
+    }
+}
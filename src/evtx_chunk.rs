@@ -1,25 +1,34 @@
 use crate::err::{self, Result};
 use crate::evtx_parser::ReadSeek;
-use snafu::{ensure, ResultExt};
+use snafu::{ensure, OptionExt, ResultExt};
 
 use crate::evtx_record::{EvtxRecord, EvtxRecordHeader};
 
-use crc::crc32;
-use log::{debug, info, trace};
+use log::{debug, info, trace, warn};
 use std::{
+    borrow::Cow,
+    cmp::{max, min},
+    convert::TryInto,
     io::Cursor,
     io::{Read, Seek, SeekFrom},
 };
 
+use crate::binxml::assemble::TokenSink;
 use crate::binxml::deserializer::BinXmlDeserializer;
+use crate::raw_substitutions::RawSubstitutions;
+use crate::stats::Counter;
 use crate::string_cache::StringCache;
 use crate::template_cache::TemplateCache;
 use crate::ParserSettings;
 
+use bumpalo::Bump;
 use byteorder::{LittleEndian, ReadBytesExt};
+use chrono::{DateTime, Duration, Utc};
 use std::sync::Arc;
+use thread_local::ThreadLocal;
 
-const EVTX_CHUNK_HEADER_SIZE: usize = 512;
+pub(crate) const EVTX_CHUNK_HEADER_SIZE: usize = 512;
+pub(crate) const EVTX_RECORD_HEADER_SIZE: usize = 24;
 
 #[derive(Debug)]
 pub struct EvtxChunkHeader {
@@ -31,16 +40,72 @@ pub struct EvtxChunkHeader {
     pub last_event_record_data_offset: u32,
     pub free_space_offset: u32,
     pub events_checksum: u32,
+    pub flags: u32,
     pub header_chunk_checksum: u32,
     strings_offsets: Vec<u32>,
     template_offsets: Vec<u32>,
 }
 
+/// A record whose timestamp is earlier than the timestamp of the record before it in the chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimestampRegression {
+    pub event_record_id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub previous_event_record_id: u64,
+    pub previous_timestamp: DateTime<Utc>,
+}
+
+impl TimestampRegression {
+    /// Returns the regression if `current` is earlier than `previous` by more than `tolerance`.
+    pub(crate) fn check(
+        previous: &EvtxRecordHeader,
+        current: &EvtxRecordHeader,
+        tolerance: Duration,
+    ) -> Option<Self> {
+        if current.timestamp + tolerance >= previous.timestamp {
+            return None;
+        }
+
+        Some(TimestampRegression {
+            event_record_id: current.event_record_id,
+            timestamp: current.timestamp,
+            previous_event_record_id: previous.event_record_id,
+            previous_timestamp: previous.timestamp,
+        })
+    }
+}
+
+/// Metadata of a chunk, see `EvtxChunkData::info`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkInfo {
+    /// Number of the chunk in the file, `None` if the chunk was not read by an `EvtxParser`.
+    pub chunk_number: Option<u16>,
+    pub first_event_record_id: u64,
+    pub last_event_record_id: u64,
+    pub header_checksum_valid: bool,
+    pub data_checksum_valid: bool,
+    /// `true` if the header was reconstructed from the records of the chunk.
+    pub recovered: bool,
+}
+
+impl ChunkInfo {
+    pub fn checksums_valid(&self) -> bool {
+        self.header_checksum_valid && self.data_checksum_valid
+    }
+}
+
 /// A struct which owns all the data associated with a chunk.
 /// See EvtxChunk for more.
 pub struct EvtxChunkData {
     pub header: EvtxChunkHeader,
     pub data: Vec<u8>,
+    /// Backs the strings of the parsed records, reset every time the chunk is parsed.
+    /// There is one arena per thread, so records of the chunk can be rendered on any thread.
+    arena: ThreadLocal<Bump>,
+    /// `true` if the header was reconstructed from the records of the chunk.
+    recovered: bool,
+    /// Set when the chunk is read by an `EvtxParser`.
+    chunk_number: Option<u16>,
 }
 
 impl EvtxChunkData {
@@ -50,7 +115,13 @@ impl EvtxChunkData {
         let mut cursor = Cursor::new(data.as_slice());
         let header = EvtxChunkHeader::from_reader(&mut cursor)?;
 
-        let chunk = EvtxChunkData { header, data };
+        let chunk = EvtxChunkData {
+            header,
+            data,
+            arena: ThreadLocal::new(),
+            recovered: false,
+            chunk_number: None,
+        };
         if validate_checksum {
             ensure!(chunk.validate_checksum(), err::InvalidChunkChecksum)
         }
@@ -58,9 +129,199 @@ impl EvtxChunkData {
         Ok(chunk)
     }
 
+    /// Like `new`, but a header which fails validation (an invalid magic, or invalid checksums
+    /// when `validate_checksum` is set) is reconstructed from the records of the chunk.
+    /// See `reconstruct`.
+    pub fn new_with_repair(data: Vec<u8>, validate_checksum: bool) -> Result<Self> {
+        let header = match EvtxChunkHeader::from_reader(&mut Cursor::new(data.as_slice())) {
+            Ok(header) => header,
+            Err(err) => return EvtxChunkData::reconstruct(data).ok_or(err),
+        };
+
+        let chunk = EvtxChunkData {
+            header,
+            data,
+            arena: ThreadLocal::new(),
+            recovered: false,
+            chunk_number: None,
+        };
+
+        if validate_checksum && !chunk.validate_checksum() {
+            return EvtxChunkData::reconstruct(chunk.data).context(err::InvalidChunkChecksum);
+        }
+
+        Ok(chunk)
+    }
+
+    /// Replaces the header of the chunk with one rebuilt from it's records, which are found by scanning
+    /// the chunk (from the end of the header) for the first plausible record, and following the records after it.
+    ///
+    /// The free space offset, the bounds of the record ids and the checksums are recomputed.
+    /// The string and template tables are cleared (names and templates are read from their offsets in the chunk),
+    /// and the record numbers are the record ids.
+    ///
+    /// Returns `None` if no record was found.
+    pub fn reconstruct(mut data: Vec<u8>) -> Option<Self> {
+        if data.len() < EVTX_CHUNK_HEADER_SIZE {
+            return None;
+        }
+
+        let (first_offset, first) = find_plausible_record(&data, EVTX_CHUNK_HEADER_SIZE)?;
+        let (mut last_offset, mut last) = (first_offset, first.clone());
+
+        while let Some((offset, record_header)) =
+            find_plausible_record(&data, last_offset + last.data_size as usize)
+                .filter(|&(offset, _)| offset == last_offset + last.data_size as usize)
+        {
+            last_offset = offset;
+            last = record_header;
+        }
+
+        let free_space_offset = last_offset + last.data_size as usize;
+
+        warn!(
+            "Reconstructed a chunk header with records {} to {} (free space offset {})",
+            first.event_record_id, last.event_record_id, free_space_offset
+        );
+
+        let events_checksum = crc32fast::hash(&data[EVTX_CHUNK_HEADER_SIZE..free_space_offset]);
+
+        let header = &mut data[..EVTX_CHUNK_HEADER_SIZE];
+        header.iter_mut().for_each(|b| *b = 0);
+        header[..8].copy_from_slice(b"ElfChnk\x00");
+        header[8..16].copy_from_slice(&first.event_record_id.to_le_bytes());
+        header[16..24].copy_from_slice(&last.event_record_id.to_le_bytes());
+        header[24..32].copy_from_slice(&first.event_record_id.to_le_bytes());
+        header[32..40].copy_from_slice(&last.event_record_id.to_le_bytes());
+        header[40..44].copy_from_slice(&128_u32.to_le_bytes());
+        header[44..48].copy_from_slice(&(last_offset as u32).to_le_bytes());
+        header[48..52].copy_from_slice(&(free_space_offset as u32).to_le_bytes());
+        header[52..56].copy_from_slice(&events_checksum.to_le_bytes());
+
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&header[..120]);
+        hasher.update(&header[128..]);
+        header[124..128].copy_from_slice(&hasher.finalize().to_le_bytes());
+
+        let mut chunk = EvtxChunkData::new(data, false).ok()?;
+        chunk.recovered = true;
+
+        Some(chunk)
+    }
+
+    /// Returns `true` if the header of the chunk was reconstructed, see `reconstruct`.
+    pub fn is_recovered(&self) -> bool {
+        self.recovered
+    }
+
+    /// Returns the number of the chunk in the file, `None` if the chunk was not read by an `EvtxParser`.
+    pub fn chunk_number(&self) -> Option<u16> {
+        self.chunk_number
+    }
+
+    pub(crate) fn with_chunk_number(mut self, chunk_number: u16) -> Self {
+        self.chunk_number = Some(chunk_number);
+        self
+    }
+
+    /// Returns the metadata of the chunk: it's number, the bounds of it's record ids
+    /// and whether it's checksums are valid (which are computed by this call).
+    pub fn info(&self) -> ChunkInfo {
+        ChunkInfo {
+            chunk_number: self.chunk_number,
+            first_event_record_id: self.header.first_event_record_id,
+            last_event_record_id: self.header.last_event_record_id,
+            header_checksum_valid: self.validate_header_checksum(),
+            data_checksum_valid: self.validate_data_checksum(),
+            recovered: self.recovered,
+        }
+    }
+
     /// Require that the settings live at least as long as &self.
     pub fn parse(&mut self, settings: Arc<ParserSettings>) -> Result<EvtxChunk> {
-        EvtxChunk::new(&self.data, &self.header, Arc::clone(&settings))
+        // Records of an earlier parse cannot outlive the mutable borrow of `self`.
+        for arena in self.arena.iter_mut() {
+            arena.reset();
+        }
+
+        let mut chunk = EvtxChunk::new(&self.data, &self.header, Arc::clone(&settings))?;
+        chunk.arena = Some(&self.arena);
+        chunk.recovered = self.recovered;
+
+        Ok(chunk)
+    }
+
+    /// Returns the earliest and the latest timestamps of the records in the chunk,
+    /// reading only their record headers.
+    /// Records are not always written in chronological order (see `timestamp_regressions`),
+    /// so these are not necessarily the timestamps of the first and the last records.
+    /// Returns `None` if an invalid record header is found before the last record.
+    pub fn timestamp_bounds(&self) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+        let headers = self.record_headers_with_offsets();
+        let (last_record_offset, _) = headers.last()?;
+
+        if *last_record_offset != self.header.last_event_record_data_offset as usize {
+            return None;
+        }
+
+        let timestamps = headers.iter().map(|(_, header)| header.timestamp);
+
+        Some((timestamps.clone().min()?, timestamps.max()?))
+    }
+
+    /// Returns the headers of the records in the chunk, without deserializing the records.
+    /// Stops at the first invalid record header.
+    pub fn record_headers(&self) -> Vec<EvtxRecordHeader> {
+        self.record_headers_with_offsets()
+            .into_iter()
+            .map(|(_, header)| header)
+            .collect()
+    }
+
+    /// Returns the data of the record with `event_record_id` (including it's header),
+    /// or `None` if the chunk does not hold it.
+    pub fn record_data(&self, event_record_id: u64) -> Option<&[u8]> {
+        let (offset, header) = self
+            .record_headers_with_offsets()
+            .into_iter()
+            .find(|(_, header)| header.event_record_id == event_record_id)?;
+
+        self.data.get(offset..offset + header.data_size as usize)
+    }
+
+    /// Same as `record_headers`, along with the offset of every record from the start of the chunk.
+    fn record_headers_with_offsets(&self) -> Vec<(usize, EvtxRecordHeader)> {
+        let end = min(self.header.free_space_offset as usize, self.data.len());
+        let mut offset = EVTX_CHUNK_HEADER_SIZE;
+        let mut headers = vec![];
+
+        while offset < end {
+            let header = match EvtxRecordHeader::from_reader(&mut Cursor::new(&self.data[offset..]))
+            {
+                Ok(header) => header,
+                Err(_) => break,
+            };
+
+            // A record is at least as large as it's header (24 bytes) and the trailing copy of it's size.
+            if header.data_size < 28 {
+                break;
+            }
+
+            let size = header.data_size as usize;
+            headers.push((offset, header));
+            offset += size;
+        }
+
+        headers
+    }
+
+    /// Returns the records whose timestamp is earlier than the one of the record before them
+    /// by more than `tolerance`, reading only the record headers.
+    pub fn timestamp_regressions(&self, tolerance: Duration) -> Vec<TimestampRegression> {
+        self.record_headers()
+            .windows(2)
+            .filter_map(|pair| TimestampRegression::check(&pair[0], &pair[1], tolerance))
+            .collect()
     }
 
     pub fn validate_data_checksum(&self) -> bool {
@@ -68,9 +329,14 @@ impl EvtxChunkData {
 
         let expected_checksum = self.header.events_checksum;
 
-        let checksum = crc32::checksum_ieee(
-            &self.data[EVTX_CHUNK_HEADER_SIZE..self.header.free_space_offset as usize],
-        );
+        // A damaged header can point past the end of the chunk.
+        let checksum = match self
+            .data
+            .get(EVTX_CHUNK_HEADER_SIZE..self.header.free_space_offset as usize)
+        {
+            Some(events) => crc32fast::hash(events),
+            None => return false,
+        };
 
         debug!(
             "Expected checksum: {:?}, found: {:?}",
@@ -85,16 +351,11 @@ impl EvtxChunkData {
 
         let expected_checksum = self.header.header_chunk_checksum;
 
-        let header_bytes_1 = &self.data[..120];
-        let header_bytes_2 = &self.data[128..512];
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&self.data[..120]);
+        hasher.update(&self.data[128..512]);
 
-        let bytes_for_checksum: Vec<u8> = header_bytes_1
-            .iter()
-            .chain(header_bytes_2)
-            .cloned()
-            .collect();
-
-        let checksum = crc32::checksum_ieee(bytes_for_checksum.as_slice());
+        let checksum = hasher.finalize();
 
         debug!(
             "Expected checksum: {:?}, found: {:?}",
@@ -117,12 +378,16 @@ pub struct EvtxChunk<'chunk> {
     pub data: &'chunk [u8],
     pub header: &'chunk EvtxChunkHeader,
 
-    // For now, `StringCache` does not reference the `chunk lifetime,
-    // but in the future we might want to change that, so it's here.
-    pub string_cache: StringCache,
+    pub string_cache: StringCache<'chunk>,
 
     pub template_table: TemplateCache<'chunk>,
 
+    /// Where the strings of the records are allocated, when the chunk is parsed from `EvtxChunkData`.
+    pub(crate) arena: Option<&'chunk ThreadLocal<Bump>>,
+
+    /// `true` if the header of the chunk was reconstructed, see `EvtxChunkData::reconstruct`.
+    pub(crate) recovered: bool,
+
     settings: Arc<ParserSettings>,
 }
 
@@ -136,21 +401,86 @@ impl<'chunk> EvtxChunk<'chunk> {
         let _cursor = Cursor::new(data);
 
         info!("Initializing string cache");
-        let string_cache = StringCache::populate(&data, &header.strings_offsets)?;
+        let string_cache = StringCache::populate(data, &header.strings_offsets);
 
         info!("Initializing template cache");
-        let template_table =
-            TemplateCache::populate(data, &header.template_offsets, settings.get_ansi_codec())?;
+        let template_table = TemplateCache::populate_with_file_cache(
+            data,
+            &header.template_offsets,
+            settings.get_ansi_codec(),
+            settings.get_file_template_cache(),
+        )?;
 
         Ok(EvtxChunk {
             header,
             data,
             string_cache,
             template_table,
+            arena: None,
+            recovered: false,
             settings,
         })
     }
 
+    pub(crate) fn settings(&self) -> &ParserSettings {
+        &self.settings
+    }
+
+    /// Deserializes the BinXML of the record `record_id` (of `binxml_data_size` bytes, starting at `binxml_offset`),
+    /// pushing the tokens to `sink` as they are read.
+    pub(crate) fn deserialize_record(
+        &'chunk self,
+        record_id: u64,
+        binxml_offset: u64,
+        binxml_data_size: u32,
+        sink: &mut impl TokenSink<'chunk>,
+    ) -> Result<()> {
+        trace!("Need to deserialize {} bytes of binxml", binxml_data_size);
+
+        // `EvtxChunk` only owns `template_table`, which we want to loan to the Deserializer.
+        // `data` and `string_cache` are both references and are `Copy`ed when passed to init.
+        // We avoid creating new references so that `BinXmlDeserializer` can still generate 'a data.
+        let deserializer = BinXmlDeserializer::init(
+            self.data,
+            binxml_offset,
+            Some(self),
+            false,
+            self.settings.get_ansi_codec(),
+        );
+
+        let iter = deserializer
+            .iter_tokens(Some(binxml_data_size))
+            .context(err::FailedToDeserializeRecord { record_id })?;
+
+        for token in iter {
+            let token = token.context(err::FailedToDeserializeRecord { record_id })?;
+
+            trace!("successfully read {:?}", token);
+            sink.push_token(Cow::Owned(token))?;
+        }
+
+        Ok(())
+    }
+
+    /// Return an iterator of the records found in the slack space of the chunk (past it's free space offset),
+    /// such as deleted records, or older records which were only partially overwritten.
+    /// These records are never returned by `iter`.
+    ///
+    /// The slack space is scanned for record headers with a plausible size (matching the copy of the size
+    /// at the end of the record), which are deserialized. Filters of the settings do not apply to these records.
+    pub fn iter_slack<'a: 'chunk>(&'a mut self) -> IterSlackRecords<'a> {
+        let start = max(
+            self.header.free_space_offset as usize,
+            EVTX_CHUNK_HEADER_SIZE,
+        );
+
+        IterSlackRecords {
+            settings: Arc::clone(&self.settings),
+            chunk: self,
+            offset_from_chunk_start: start,
+        }
+    }
+
     /// Return an iterator of records from the chunk.
     /// See `IterChunkRecords` for a more detailed explanation regarding the lifetime scopes of the
     /// resulting records.
@@ -171,10 +501,105 @@ impl<'chunk> EvtxChunk<'chunk> {
             chunk: self,
             offset_from_chunk_start: EVTX_CHUNK_HEADER_SIZE as u64,
             exhausted: false,
+            previous_record_header: None,
         }
     }
 }
 
+/// Why the data at some offset is not a plausible record, see `read_plausible_record`.
+#[derive(Debug)]
+pub(crate) enum ImplausibleRecord {
+    /// The record header could not be read (bad magic, or truncated).
+    InvalidHeader(err::Error),
+    /// The size of the record is too small, or goes past the end of the data.
+    InvalidSize(u32),
+    /// The copy of the size at the end of the record does not match the size in it's header.
+    SizeMismatch { size: u32, trailing_size: u32 },
+}
+
+/// Reads the header of the record at `offset` of `data`, if it is a plausible record:
+/// a valid record header, followed (at the end of the record, within `data`) by a matching trailing copy of the record's size.
+pub(crate) fn read_plausible_record(
+    data: &[u8],
+    offset: usize,
+) -> std::result::Result<EvtxRecordHeader, ImplausibleRecord> {
+    let record_header =
+        EvtxRecordHeader::from_reader(&mut Cursor::new(data.get(offset..).unwrap_or_default()))
+            .map_err(ImplausibleRecord::InvalidHeader)?;
+
+    // A record is at least as large as it's header (24 bytes) and the trailing copy of it's size.
+    let size = record_header.data_size as usize;
+    if size < 28 || offset + size > data.len() {
+        return Err(ImplausibleRecord::InvalidSize(record_header.data_size));
+    }
+
+    let trailing = &data[offset + size - 4..offset + size];
+    let trailing_size = u32::from_le_bytes(trailing.try_into().expect("4 bytes"));
+    if trailing_size != record_header.data_size {
+        return Err(ImplausibleRecord::SizeMismatch {
+            size: record_header.data_size,
+            trailing_size,
+        });
+    }
+
+    Ok(record_header)
+}
+
+/// Scans `data` from `offset` for the start of a plausible record, see `read_plausible_record`.
+fn find_plausible_record(data: &[u8], mut offset: usize) -> Option<(usize, EvtxRecordHeader)> {
+    loop {
+        offset += data
+            .get(offset..)?
+            .windows(4)
+            .position(|magic| magic == b"\x2a\x2a\x00\x00")?;
+
+        if let Ok(record_header) = read_plausible_record(data, offset) {
+            return Some((offset, record_header));
+        }
+
+        offset += 1;
+    }
+}
+
+/// An iterator over the slack space of a chunk, yielding the records recovered from it.
+/// This iterator can be created using the `iter_slack` function on `EvtxChunk`.
+pub struct IterSlackRecords<'chunk> {
+    chunk: &'chunk EvtxChunk<'chunk>,
+    offset_from_chunk_start: usize,
+    settings: Arc<ParserSettings>,
+}
+
+impl<'a> Iterator for IterSlackRecords<'a> {
+    type Item = Result<EvtxRecord<'a>>;
+
+    fn next(&mut self) -> Option<<Self as Iterator>::Item> {
+        let (offset, record_header) =
+            find_plausible_record(self.chunk.data, self.offset_from_chunk_start)?;
+
+        self.offset_from_chunk_start = offset + record_header.data_size as usize;
+
+        debug!(
+            "Found record {} in the slack space at offset {}",
+            record_header.event_record_id, offset
+        );
+
+        let record = EvtxRecord::from_chunk(
+            self.chunk,
+            &record_header,
+            offset,
+            true,
+            Arc::clone(&self.settings),
+        );
+
+        // Only records which can be deserialized are recovered.
+        if let Err(err) = record.tokens() {
+            return Some(Err(err));
+        }
+
+        Some(Ok(record))
+    }
+}
+
 /// An iterator over a chunk, yielding records.
 /// This iterator can be created using the `iter` function on `EvtxChunk`.
 ///
@@ -200,89 +625,199 @@ pub struct IterChunkRecords<'chunk> {
     offset_from_chunk_start: u64,
     exhausted: bool,
     settings: Arc<ParserSettings>,
+    /// Used to validate the order of timestamps, when enabled.
+    previous_record_header: Option<EvtxRecordHeader>,
+}
+
+impl<'a> IterChunkRecords<'a> {
+    /// Skips over a corrupted record, to the next plausible record in the chunk.
+    /// The chunk is abandoned when there is no such record, or when resyncing is disabled
+    /// (see `ParserSettings::resync_records`).
+    fn resync(&mut self, source: err::Error) -> err::Error {
+        if !self.settings.should_resync_records() {
+            self.exhausted = true;
+            return source;
+        }
+
+        let start = self.offset_from_chunk_start;
+        let free_space_offset = u64::from(self.chunk.header.free_space_offset);
+
+        let end = find_plausible_record(self.chunk.data, start as usize + 1)
+            .map(|(offset, _)| offset as u64)
+            .filter(|&offset| offset < free_space_offset)
+            .unwrap_or_else(|| {
+                self.exhausted = true;
+                free_space_offset
+            });
+
+        warn!(
+            "Skipped corrupted record data at chunk offsets {}..{}: {}",
+            start, end, source
+        );
+
+        self.offset_from_chunk_start = end;
+
+        err::Error::SkippedCorruptedRecordData {
+            start,
+            end,
+            source: Box::new(source),
+        }
+    }
+}
+
+impl<'a> IterChunkRecords<'a> {
+    /// Offset of the next record from the start of the chunk.
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset_from_chunk_start
+    }
 }
 
 impl<'a> Iterator for IterChunkRecords<'a> {
     type Item = Result<EvtxRecord<'a>>;
 
     fn next(&mut self) -> Option<<Self as Iterator>::Item> {
-        if self.exhausted
-            || self.offset_from_chunk_start >= u64::from(self.chunk.header.free_space_offset)
-        {
-            return None;
-        }
+        loop {
+            if self.exhausted
+                || self.offset_from_chunk_start >= u64::from(self.chunk.header.free_space_offset)
+            {
+                return None;
+            }
 
-        let mut cursor = Cursor::new(&self.chunk.data[self.offset_from_chunk_start as usize..]);
+            let mut cursor = Cursor::new(&self.chunk.data[self.offset_from_chunk_start as usize..]);
+
+            let record_header =
+                match EvtxRecordHeader::from_reader(&mut cursor).and_then(|record_header| {
+                    let size = u64::from(record_header.data_size);
+                    ensure!(
+                        size >= 28
+                            && self.offset_from_chunk_start + size <= self.chunk.data.len() as u64,
+                        err::InvalidEvtxRecordSize {
+                            size: record_header.data_size
+                        }
+                    );
+                    Ok(record_header)
+                }) {
+                    Ok(record_header) => record_header,
+                    Err(err) => return Some(Err(self.resync(err))),
+                };
+
+            info!("Record id - {}", record_header.event_record_id);
+            debug!("Record header - {:?}", record_header);
+
+            if let Some(tolerance) = self.settings.get_timestamp_order_tolerance() {
+                if let Some(regression) =
+                    self.previous_record_header.as_ref().and_then(|previous| {
+                        TimestampRegression::check(previous, &record_header, tolerance)
+                    })
+                {
+                    warn!(
+                        "Record {} ({}) is earlier than the previous record {} ({})",
+                        regression.event_record_id,
+                        regression.timestamp,
+                        regression.previous_event_record_id,
+                        regression.previous_timestamp
+                    );
+                }
 
-        let record_header = match EvtxRecordHeader::from_reader(&mut cursor) {
-            Ok(record_header) => record_header,
-            Err(err) => {
-                // We currently do not try to recover after an invalid record.
-                self.exhausted = true;
+                self.previous_record_header = Some(record_header.clone());
+            }
 
-                return Some(Err(err));
+            // Records outside of the time (or id) range are skipped before they are deserialized.
+            if !self.settings.matches_timestamp(&record_header.timestamp)
+                || !self
+                    .settings
+                    .matches_record_id(record_header.event_record_id)
+            {
+                trace!("Record {} filtered out", record_header.event_record_id);
+                self.settings.count(Counter::RecordsFiltered, 1);
+                self.offset_from_chunk_start += u64::from(record_header.data_size);
+
+                if self.chunk.header.last_event_record_id == record_header.event_record_id {
+                    self.exhausted = true;
+                }
+                continue;
             }
-        };
 
-        info!("Record id - {}", record_header.event_record_id);
-        debug!("Record header - {:?}", record_header);
+            let record_start = self.offset_from_chunk_start as usize;
+            let binxml_offset = record_start + EVTX_RECORD_HEADER_SIZE;
 
-        let binxml_data_size = record_header.record_data_size();
+            // Most records can be filtered out using their substitutions, without deserializing them.
+            if self.settings.has_substitution_filters()
+                && RawSubstitutions::read(self.chunk, binxml_offset)
+                    .map_or(false, |raw| self.settings.rejects_substitutions(&raw))
+            {
+                trace!("Record {} filtered out", record_header.event_record_id);
+                self.settings.count(Counter::RecordsFiltered, 1);
+                self.offset_from_chunk_start += u64::from(record_header.data_size);
 
-        trace!("Need to deserialize {} bytes of binxml", binxml_data_size);
+                if self.chunk.header.last_event_record_id == record_header.event_record_id {
+                    self.exhausted = true;
+                }
+                continue;
+            }
 
-        // `EvtxChunk` only owns `template_table`, which we want to loan to the Deserializer.
-        // `data` and `string_cache` are both references and are `Copy`ed when passed to init.
-        // We avoid creating new references so that `BinXmlDeserializer` can still generate 'a data.
-        let deserializer = BinXmlDeserializer::init(
-            self.chunk.data,
-            self.offset_from_chunk_start + cursor.position(),
-            Some(self.chunk),
-            false,
-            self.settings.get_ansi_codec(),
-        );
+            self.offset_from_chunk_start += u64::from(record_header.data_size);
 
-        let mut tokens = vec![];
-        let iter = match deserializer.iter_tokens(Some(binxml_data_size)).context(
-            err::FailedToDeserializeRecord {
-                record_id: record_header.event_record_id,
-            },
-        ) {
-            Ok(iter) => iter,
-            Err(err) => return Some(Err(err)),
-        };
+            if self.chunk.header.last_event_record_id == record_header.event_record_id {
+                self.exhausted = true;
+            }
 
-        for token in iter {
-            match token.context(err::FailedToDeserializeRecord {
-                record_id: record_header.event_record_id,
-            }) {
-                Ok(token) => {
-                    trace!("successfully read {:?}", token);
-                    tokens.push(token)
-                }
-                Err(err) => {
-                    self.offset_from_chunk_start += u64::from(record_header.data_size);
-                    return Some(Err(err));
+            // The record is deserialized when it is rendered, unless the filters need it's values.
+            let record = EvtxRecord::from_chunk(
+                self.chunk,
+                &record_header,
+                record_start,
+                self.chunk.recovered,
+                Arc::clone(&self.settings),
+            );
+
+            if self.settings.has_record_filters() {
+                match self.settings.matches_record(&record) {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        trace!("Record {} filtered out", record.event_record_id);
+                        self.settings.count(Counter::RecordsFiltered, 1);
+                        continue;
+                    }
+                    Err(err) => return Some(Err(err)),
                 }
             }
-        }
-
-        self.offset_from_chunk_start += u64::from(record_header.data_size);
 
-        if self.chunk.header.last_event_record_id == record_header.event_record_id {
-            self.exhausted = true;
+            return Some(Ok(record));
         }
-
-        Some(Ok(EvtxRecord {
-            event_record_id: record_header.event_record_id,
-            timestamp: record_header.timestamp,
-            tokens,
-            settings: Arc::clone(&self.settings),
-        }))
     }
 }
 
 impl EvtxChunkHeader {
+    /// A header for BinXML data outside of a chunk, whose names and templates
+    /// (referenced by their offsets in the data) are cached from `strings_offsets` and `template_offsets`.
+    pub(crate) fn with_tables(strings_offsets: Vec<u32>, template_offsets: Vec<u32>) -> Self {
+        EvtxChunkHeader {
+            first_event_record_number: 0,
+            last_event_record_number: 0,
+            first_event_record_id: 0,
+            last_event_record_id: 0,
+            header_size: 0,
+            last_event_record_data_offset: 0,
+            free_space_offset: 0,
+            events_checksum: 0,
+            flags: 0,
+            header_chunk_checksum: 0,
+            strings_offsets,
+            template_offsets,
+        }
+    }
+
+    /// The offsets of the names in the string table of the chunk.
+    pub fn strings_offsets(&self) -> &[u32] {
+        &self.strings_offsets
+    }
+
+    /// The offsets of the template definitions in the template table of the chunk.
+    pub fn template_offsets(&self) -> &[u32] {
+        &self.template_offsets
+    }
+
     pub fn from_reader(input: &mut Cursor<&[u8]>) -> Result<EvtxChunkHeader> {
         let mut magic = [0_u8; 8];
         input.take(8).read_exact(&mut magic)?;
@@ -304,8 +839,7 @@ impl EvtxChunkHeader {
 
         // Reserved
         input.seek(SeekFrom::Current(64))?;
-        // Flags
-        input.seek(SeekFrom::Current(4))?;
+        let flags = try_read!(input, u32);
 
         let header_chunk_checksum = try_read!(input, u32);
 
@@ -324,6 +858,7 @@ impl EvtxChunkHeader {
             last_event_record_data_offset,
             free_space_offset,
             events_checksum,
+            flags,
             header_chunk_checksum,
             template_offsets,
             strings_offsets,
@@ -337,7 +872,9 @@ mod tests {
     use crate::ensure_env_logger_initialized;
     use crate::evtx_parser::EVTX_CHUNK_SIZE;
     use crate::evtx_parser::EVTX_FILE_HEADER_SIZE;
+    use crate::ParserStats;
 
+    use std::convert::TryInto;
     use std::io::Cursor;
 
     #[test]
@@ -360,6 +897,7 @@ mod tests {
             last_event_record_data_offset: 64928,
             free_space_offset: 65376,
             events_checksum: 4_252_479_141,
+            flags: 1,
             header_chunk_checksum: 978_805_790,
             strings_offsets: vec![0_u32; 64],
             template_offsets: vec![0_u32; 32],
@@ -388,6 +926,7 @@ mod tests {
         );
         assert_eq!(chunk_header.free_space_offset, expected.free_space_offset);
         assert_eq!(chunk_header.events_checksum, expected.events_checksum);
+        assert_eq!(chunk_header.flags, expected.flags);
         assert_eq!(
             chunk_header.header_chunk_checksum,
             expected.header_chunk_checksum
@@ -406,4 +945,340 @@ mod tests {
         let chunk = EvtxChunkData::new(chunk_data, false).unwrap();
         assert!(chunk.validate_checksum());
     }
+
+    #[test]
+    fn test_timestamp_regressions() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut chunk_data =
+            evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE].to_vec();
+
+        let chunk = EvtxChunkData::new(chunk_data.clone(), false).unwrap();
+        let headers = chunk.record_headers();
+        assert_eq!(headers.len(), 91);
+
+        // Records are not always written in order, the sample has a sub-second regression.
+        let ids = |regressions: Vec<TimestampRegression>| -> Vec<u64> {
+            regressions.iter().map(|r| r.event_record_id).collect()
+        };
+        assert_eq!(ids(chunk.timestamp_regressions(Duration::zero())), vec![39]);
+        assert!(chunk.timestamp_regressions(Duration::seconds(1)).is_empty());
+
+        // Move the timestamp (a FILETIME, right after the record id) of the 10th record two hours back.
+        let offset = EVTX_CHUNK_HEADER_SIZE
+            + headers[..9]
+                .iter()
+                .map(|h| h.data_size as usize)
+                .sum::<usize>()
+            + 16;
+        let filetime = u64::from_le_bytes(chunk_data[offset..offset + 8].try_into().unwrap());
+        let filetime = filetime - 2 * 3600 * 10_000_000;
+        chunk_data[offset..offset + 8].copy_from_slice(&filetime.to_le_bytes());
+
+        let chunk = EvtxChunkData::new(chunk_data, false).unwrap();
+        let regressions = chunk.timestamp_regressions(Duration::minutes(5));
+
+        assert_eq!(ids(regressions.clone()), vec![10]);
+        assert_eq!(regressions[0].previous_event_record_id, 9);
+        assert!(chunk.timestamp_regressions(Duration::hours(3)).is_empty());
+    }
+
+    #[test]
+    fn test_timestamp_bounds_are_the_earliest_and_latest_timestamps() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut chunk_data =
+            evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE].to_vec();
+
+        let chunk = EvtxChunkData::new(chunk_data.clone(), false).unwrap();
+        let headers = chunk.record_headers();
+        let (first, last) = chunk.timestamp_bounds().unwrap();
+        assert_eq!(first, headers[0].timestamp);
+        assert_eq!(last, headers.iter().map(|h| h.timestamp).max().unwrap());
+
+        // Move the timestamp of the 10th record a day before the one of the first record.
+        let offset = EVTX_CHUNK_HEADER_SIZE
+            + headers[..9]
+                .iter()
+                .map(|h| h.data_size as usize)
+                .sum::<usize>()
+            + 16;
+        let filetime = u64::from_le_bytes(chunk_data[offset..offset + 8].try_into().unwrap());
+        let filetime = filetime - 24 * 3600 * 10_000_000;
+        chunk_data[offset..offset + 8].copy_from_slice(&filetime.to_le_bytes());
+
+        let chunk = EvtxChunkData::new(chunk_data, false).unwrap();
+        let (earliest, _) = chunk.timestamp_bounds().unwrap();
+
+        assert_eq!(earliest, chunk.record_headers()[9].timestamp);
+        assert!(earliest < headers[0].timestamp);
+    }
+
+    #[test]
+    fn test_chunk_can_be_parsed_again_after_arena_reset() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let chunk_data =
+            evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE].to_vec();
+
+        let mut chunk = EvtxChunkData::new(chunk_data, true).unwrap();
+        let settings = Arc::new(ParserSettings::default());
+
+        let render = |chunk: &mut EvtxChunkData| -> Vec<String> {
+            let mut parsed = chunk.parse(Arc::clone(&settings)).unwrap();
+            parsed
+                .iter()
+                .map(|record| record.unwrap().into_xml().unwrap().data)
+                .collect()
+        };
+
+        let first = render(&mut chunk);
+        assert_eq!(first.len(), 91);
+        assert!(chunk
+            .arena
+            .iter_mut()
+            .any(|arena| arena.allocated_bytes() > 0));
+
+        assert_eq!(render(&mut chunk), first);
+    }
+
+    #[test]
+    fn test_resyncs_after_a_corrupted_record() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut chunk_data =
+            evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE].to_vec();
+
+        let headers = EvtxChunkData::new(chunk_data.clone(), false)
+            .unwrap()
+            .record_headers();
+
+        // Corrupt the magic of the 10th record.
+        let start = EVTX_CHUNK_HEADER_SIZE
+            + headers[..9]
+                .iter()
+                .map(|h| h.data_size as usize)
+                .sum::<usize>();
+        chunk_data[start] = 0xff;
+
+        let mut chunk = EvtxChunkData::new(chunk_data, false).unwrap();
+
+        // By default, the rest of the chunk is abandoned.
+        let mut parsed = chunk.parse(Arc::new(ParserSettings::default())).unwrap();
+        let results: Vec<_> = parsed.iter().collect();
+
+        assert_eq!(results.len(), 10);
+        assert!(matches!(
+            results[9],
+            Err(err::Error::InvalidEvtxRecordHeaderMagic { .. })
+        ));
+
+        let mut parsed = chunk
+            .parse(Arc::new(ParserSettings::new().resync_records(true)))
+            .unwrap();
+        let results: Vec<_> = parsed.iter().collect();
+
+        assert_eq!(results.len(), 91);
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 90);
+
+        match &results[9] {
+            Err(err::Error::SkippedCorruptedRecordData { start: s, end, .. }) => {
+                assert_eq!(*s, start as u64);
+                assert_eq!(*end, (start + headers[9].data_size as usize) as u64);
+            }
+            other => panic!(
+                "Expected skipped record data, found {:?}",
+                other.as_ref().err()
+            ),
+        }
+
+        assert_eq!(
+            results[10].as_ref().unwrap().event_record_id,
+            headers[10].event_record_id
+        );
+    }
+
+    #[test]
+    fn test_recovers_records_from_slack_space() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut chunk_data =
+            evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE].to_vec();
+        let settings = Arc::new(ParserSettings::default());
+
+        let render = |chunk: &mut EvtxChunkData, slack: bool| -> Vec<(u64, String)> {
+            let mut parsed = chunk.parse(Arc::clone(&settings)).unwrap();
+            let records: Vec<_> = if slack {
+                parsed
+                    .iter_slack()
+                    .filter_map(|record| record.ok())
+                    .collect()
+            } else {
+                parsed.iter().map(|record| record.unwrap()).collect()
+            };
+
+            records
+                .into_iter()
+                .map(|record| (record.event_record_id, record.into_xml().unwrap().data))
+                .collect()
+        };
+
+        let mut chunk = EvtxChunkData::new(chunk_data.clone(), false).unwrap();
+        let records = render(&mut chunk, false);
+
+        // "Delete" the last record, by moving the free space offset to it's start.
+        let (last_record_offset, _) = *chunk.record_headers_with_offsets().last().unwrap();
+        chunk_data[48..52].copy_from_slice(&(last_record_offset as u32).to_le_bytes());
+
+        let mut chunk = EvtxChunkData::new(chunk_data, false).unwrap();
+        assert_eq!(render(&mut chunk, false), records[..90]);
+
+        let recovered = render(&mut chunk, true);
+        assert_eq!(recovered.first(), records.last());
+    }
+
+    #[test]
+    fn test_reconstructs_damaged_chunk_headers() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let original =
+            evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE].to_vec();
+        let settings = Arc::new(ParserSettings::default());
+
+        let render = |chunk: &mut EvtxChunkData| -> Vec<(String, bool)> {
+            let mut parsed = chunk.parse(Arc::clone(&settings)).unwrap();
+            parsed
+                .iter()
+                .map(|record| {
+                    let record = record.unwrap().into_xml().unwrap();
+                    (record.data, record.recovered)
+                })
+                .collect()
+        };
+
+        let mut intact = EvtxChunkData::new_with_repair(original.clone(), true).unwrap();
+        assert!(!intact.is_recovered());
+        let expected = render(&mut intact);
+
+        let mut wiped = original.clone();
+        wiped[..EVTX_CHUNK_HEADER_SIZE].fill(0);
+        assert!(EvtxChunkData::new(wiped.clone(), false).is_err());
+
+        let mut chunk = EvtxChunkData::new_with_repair(wiped, true).unwrap();
+        assert!(chunk.is_recovered());
+        assert!(chunk.validate_checksum());
+        assert_eq!(chunk.header.first_event_record_id, 1);
+        assert_eq!(chunk.header.last_event_record_id, 91);
+        assert_eq!(
+            chunk.header.free_space_offset,
+            intact.header.free_space_offset
+        );
+        assert_eq!(
+            chunk.header.last_event_record_data_offset,
+            intact.header.last_event_record_data_offset
+        );
+
+        let records = render(&mut chunk);
+        assert_eq!(records.len(), 91);
+        assert!(records.iter().all(|(_, recovered)| *recovered));
+        assert!(records
+            .iter()
+            .zip(expected.iter())
+            .all(|((data, _), (expected, _))| data == expected));
+
+        // A bad header checksum is only repaired when checksums are validated.
+        let mut bad_checksum = original;
+        bad_checksum[124] ^= 0xff;
+        assert!(!EvtxChunkData::new_with_repair(bad_checksum.clone(), false)
+            .unwrap()
+            .is_recovered());
+        assert!(EvtxChunkData::new_with_repair(bad_checksum, true)
+            .unwrap()
+            .is_recovered());
+    }
+
+    #[test]
+    fn test_templates_missing_from_the_table_are_read_once() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let original =
+            evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE].to_vec();
+
+        let render = |data: Vec<u8>| -> (Vec<String>, ParserStats) {
+            let settings = Arc::new(ParserSettings::new().collect_stats(true));
+            let mut chunk_data = EvtxChunkData::new(data, false).unwrap();
+            let mut chunk = chunk_data.parse(Arc::clone(&settings)).unwrap();
+            let records = chunk
+                .iter()
+                .map(|record| record.unwrap().into_xml().unwrap().data)
+                .collect();
+
+            (records, settings.get_stats().unwrap())
+        };
+
+        // Wipe the template table, the definitions are still in the chunk.
+        let mut wiped = original.clone();
+        wiped[0x180..0x200].fill(0);
+
+        let (expected, expected_stats) = render(original);
+        let (records, stats) = render(wiped);
+
+        assert_eq!(records, expected);
+        assert_eq!(
+            stats.template_cache_hits + stats.template_cache_misses,
+            expected_stats.template_cache_hits + expected_stats.template_cache_misses
+        );
+        // Every template is read once, the other template instances use the cached definition.
+        assert_eq!(stats.template_cache_misses, 15);
+    }
+
+    #[test]
+    fn test_file_template_cache_renders_templates_of_earlier_chunks() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let chunk_data = |n: usize| {
+            let start = EVTX_FILE_HEADER_SIZE + n * EVTX_CHUNK_SIZE;
+            evtx_file[start..start + EVTX_CHUNK_SIZE].to_vec()
+        };
+
+        let render = |data: Vec<u8>, settings: &Arc<ParserSettings>| -> Vec<Option<String>> {
+            let mut chunk_data = EvtxChunkData::new(data, false).unwrap();
+            let mut chunk = chunk_data.parse(Arc::clone(settings)).unwrap();
+            chunk
+                .iter()
+                .map(|record| record.and_then(|r| r.into_xml()).ok().map(|r| r.data))
+                .collect()
+        };
+
+        // The first chunk fills the cache.
+        let settings = Arc::new(ParserSettings::new().file_template_cache(true));
+        render(chunk_data(0), &settings);
+        assert!(!settings.get_file_template_cache().unwrap().is_empty());
+
+        // Wipe the template definitions of the second chunk, and the table pointing to them.
+        let original = chunk_data(1);
+        let mut wiped = original.clone();
+        let header = EvtxChunkData::new(original.clone(), false).unwrap().header;
+
+        for &offset in header.template_offsets.iter().filter(|&&o| o > 0) {
+            let offset = offset as usize;
+            let data_size =
+                u32::from_le_bytes(original[offset + 20..offset + 24].try_into().unwrap());
+            wiped[offset..offset + 24 + data_size as usize].fill(0);
+        }
+        wiped[0x180..0x200].fill(0);
+
+        let expected = render(original, &Arc::new(ParserSettings::new()));
+        let without_cache = render(wiped.clone(), &Arc::new(ParserSettings::new()));
+        let with_cache = render(wiped, &settings);
+
+        let rendered = |records: &[Option<String>]| {
+            records
+                .iter()
+                .zip(&expected)
+                .filter(|(record, expected)| record == expected)
+                .count()
+        };
+        assert!(rendered(&with_cache) > rendered(&without_cache));
+    }
 }
@@ -3,7 +3,16 @@ use crate::evtx_parser::ReadSeek;
 use snafu::{ensure, ResultExt};
 
 use byteorder::ReadBytesExt;
-use std::io::{Read, Seek, SeekFrom};
+use log::warn;
+use std::io::{Read, Seek};
+use std::ops::Range;
+
+/// Offset of the unused bytes between `chunk_count` and `flags`.
+const RESERVED_OFFSET: usize = 44;
+const RESERVED_SIZE: usize = 76;
+/// Offset of the unused bytes following the checksum, up to the end of the header block.
+const TRAILING_OFFSET: usize = 128;
+const TRAILING_SIZE: usize = 4096 - TRAILING_OFFSET;
 
 #[derive(Debug, PartialEq)]
 pub struct EvtxFileHeader {
@@ -18,6 +27,56 @@ pub struct EvtxFileHeader {
     pub flags: HeaderFlags,
     // Checksum is of first 120 bytes of header
     pub checksum: u32,
+    pub unused: UnusedHeaderBytes,
+}
+
+/// The bytes of the header block which are not part of any known field.
+/// Windows leaves them zeroed, so other values may indicate a non-standard writer (or tampering).
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnusedHeaderBytes {
+    /// The 76 bytes at offset 44 (between `chunk_count` and `flags`).
+    pub reserved: Vec<u8>,
+    /// The bytes at offset 128 (after the checksum) up to the end of the 4096 bytes header block.
+    pub trailing: Vec<u8>,
+}
+
+impl UnusedHeaderBytes {
+    fn zeroed() -> Self {
+        UnusedHeaderBytes {
+            reserved: vec![0; RESERVED_SIZE],
+            trailing: vec![0; TRAILING_SIZE],
+        }
+    }
+
+    /// Returns `true` if all the unused bytes are zero.
+    pub fn is_zeroed(&self) -> bool {
+        self.reserved.iter().chain(&self.trailing).all(|&b| b == 0)
+    }
+
+    /// Returns the ranges (as offsets in the file header) of the non-zero unused bytes.
+    pub fn non_zero_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = non_zero_ranges(&self.reserved, RESERVED_OFFSET);
+        ranges.extend(non_zero_ranges(&self.trailing, TRAILING_OFFSET));
+
+        ranges
+    }
+}
+
+fn non_zero_ranges(bytes: &[u8], offset: usize) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = vec![];
+
+    for (i, &b) in bytes.iter().enumerate() {
+        if b == 0 {
+            continue;
+        }
+
+        match ranges.last_mut() {
+            Some(range) if range.end == offset + i => range.end += 1,
+            _ => ranges.push(offset + i..offset + i + 1),
+        }
+    }
+
+    ranges
 }
 
 #[derive(Debug, PartialEq)]
@@ -25,9 +84,40 @@ pub enum HeaderFlags {
     Empty,
     Dirty,
     Full,
+    /// Both flags are set, when a full log was not closed properly.
+    DirtyAndFull,
 }
 
 impl EvtxFileHeader {
+    /// Creates a header for data which consists only of chunks (without a file header),
+    /// such as chunks exported by carving tools.
+    pub fn for_chunks_only(chunk_count: u16) -> EvtxFileHeader {
+        EvtxFileHeader {
+            first_chunk_number: 0,
+            last_chunk_number: u64::from(chunk_count.saturating_sub(1)),
+            next_record_id: 0,
+            header_size: 0,
+            minor_version: 0,
+            major_version: 0,
+            header_block_size: 0,
+            chunk_count,
+            flags: HeaderFlags::Empty,
+            checksum: 0,
+            unused: UnusedHeaderBytes::zeroed(),
+        }
+    }
+
+    /// Returns `true` if the file was not closed properly, so the header (and mostly the chunk count)
+    /// might not reflect the last chunks written.
+    pub fn is_dirty(&self) -> bool {
+        matches!(self.flags, HeaderFlags::Dirty | HeaderFlags::DirtyAndFull)
+    }
+
+    /// Returns `true` if the log reached it's maximum size.
+    pub fn is_full(&self) -> bool {
+        matches!(self.flags, HeaderFlags::Full | HeaderFlags::DirtyAndFull)
+    }
+
     pub fn from_stream<T: Read + Seek>(stream: &mut T) -> Result<EvtxFileHeader> {
         let mut magic = [0_u8; 8];
         stream.take(8).read_exact(&mut magic)?;
@@ -46,19 +136,30 @@ impl EvtxFileHeader {
         let header_block_size = try_read!(stream, u16);
         let chunk_count = try_read!(stream, u16);
 
-        // unused
-        stream.seek(SeekFrom::Current(76))?;
+        let mut reserved = vec![0; RESERVED_SIZE];
+        stream.read_exact(&mut reserved)?;
 
         let flags = match try_read!(stream, u32) {
             0_u32 => HeaderFlags::Empty,
             1_u32 => HeaderFlags::Dirty,
             2_u32 => HeaderFlags::Full,
+            3_u32 => HeaderFlags::DirtyAndFull,
             other => return err::UnknownEvtxHeaderFlagValue { value: other }.fail(),
         };
 
         let checksum = try_read!(stream, u32);
-        // unused
-        stream.seek(SeekFrom::Current(4096 - 128))?;
+
+        let mut trailing = vec![0; TRAILING_SIZE];
+        stream.read_exact(&mut trailing)?;
+
+        let unused = UnusedHeaderBytes { reserved, trailing };
+
+        if !unused.is_zeroed() {
+            warn!(
+                "Unused bytes of the file header are not zero (at {:?}), the file might have been written by a non-standard writer",
+                unused.non_zero_ranges()
+            );
+        }
 
         Ok(EvtxFileHeader {
             first_chunk_number: oldest_chunk,
@@ -71,6 +172,7 @@ impl EvtxFileHeader {
             chunk_count,
             flags,
             checksum,
+            unused,
         })
     }
 }
@@ -78,7 +180,6 @@ impl EvtxFileHeader {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crc::crc32;
     use std::io::Cursor;
 
     #[test]
@@ -98,8 +199,34 @@ mod tests {
                 header_block_size: 4096,
                 chunk_count: 26,
                 flags: HeaderFlags::Dirty,
-                checksum: crc32::checksum_ieee(&evtx_file[..120]),
+                checksum: crc32fast::hash(&evtx_file[..120]),
+                unused: UnusedHeaderBytes::zeroed(),
             }
         );
     }
+
+    #[test]
+    fn test_parses_dirty_and_full_flags() {
+        let mut header = include_bytes!("../samples/security.evtx")[..4096].to_vec();
+        header[120..124].copy_from_slice(&3_u32.to_le_bytes());
+
+        let header = EvtxFileHeader::from_stream(&mut Cursor::new(&header)).unwrap();
+
+        assert_eq!(header.flags, HeaderFlags::DirtyAndFull);
+        assert!(header.is_dirty());
+        assert!(header.is_full());
+    }
+
+    #[test]
+    fn test_finds_non_zero_unused_bytes() {
+        let mut header = include_bytes!("../samples/security.evtx")[..4096].to_vec();
+        header[50] = 1;
+        header[51] = 2;
+        header[4095] = 0xff;
+
+        let parsed = EvtxFileHeader::from_stream(&mut Cursor::new(header)).unwrap();
+
+        assert!(!parsed.unused.is_zeroed());
+        assert_eq!(parsed.unused.non_zero_ranges(), vec![50..52, 4095..4096]);
+    }
 }
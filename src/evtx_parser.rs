@@ -1,28 +1,61 @@
 use crate::err::{self, Result};
 use snafu::{ensure, ResultExt};
 
-use crate::evtx_chunk::EvtxChunkData;
+use crate::activity::{normalize_activity_id, ActivityGroup, ActivityGrouper};
+use crate::aggregate::{GroupBy, GroupKey, Histogram};
+use crate::analysis::{AnalysisReport, RecordSummary};
+use crate::binxml::value_variant::FloatFormat;
+use crate::checkpoint::{Checkpoint, CheckpointStatus};
+use crate::chunk_table::{write_chunk_table, ChunkStatus, ChunkTableEntry, ChunkTableFormat};
+use crate::compression::{open_input, InputFile};
+use crate::enrichment::Enrichment;
+use crate::evtx_chunk::{EvtxChunkData, EvtxChunkHeader, EVTX_CHUNK_HEADER_SIZE};
 use crate::evtx_file_header::EvtxFileHeader;
-use crate::evtx_record::SerializedEvtxRecord;
+use crate::evtx_record::{EvtxRecordHeader, EvtxRecordOwned, SerializedEvtxRecord};
+use crate::evtx_writer::{self, EvtxWriter};
+use crate::file_report::{ChunkFailure, FileReport, RecordFailure};
+use crate::filter_rules::FilterRules;
+use crate::findings::{Finding, FindingsReport};
+#[cfg(feature = "geoip")]
+use crate::geoip::GeoIpEnricher;
+use crate::guid::GuidFormat;
+use crate::integrity::{ChunkIntegrity, FileHeaderIntegrity, IntegrityReport};
+use crate::lookup::LookupTable;
+use crate::provider_names::ProviderNameLookup;
+use crate::raw_substitutions::RawSubstitutions;
+use crate::record_filter::{normalize_provider, RecordFilter};
+use crate::record_size::{self, RecordSize, RenderFormat};
+use crate::redact::Redactor;
+use crate::repair::{self, RepairReport};
+use crate::skeleton::SkeletonCache;
+use crate::split::{self, SplitBy, SplitPart};
+use crate::stats::{Counter, ParserStats, Progress, Stage, StatsCollector};
+use crate::tampering::TamperingReport;
+use crate::template_cache::FileTemplateCache;
+#[cfg(feature = "wevt_templates")]
+use crate::wevt_templates::extract_wevt_template_resources;
 #[cfg(feature = "multithreading")]
 use rayon;
-#[cfg(feature = "multithreading")]
-use rayon::prelude::*;
+use std::sync::mpsc::{sync_channel, Receiver};
 
-use log::{debug, info};
+use log::{debug, info, warn};
 
-use std::fs::File;
-use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
 use std::iter::{IntoIterator, Iterator};
+use std::ops::{Range, RangeInclusive};
 
 use crate::EvtxRecord;
+use chrono::{DateTime, Duration, Utc};
 use encoding::all::WINDOWS_1252;
 use encoding::EncodingRef;
-use std::cmp::max;
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::fmt;
 use std::fmt::Debug;
 use std::path::Path;
 use std::sync::Arc;
+use std::thread::{self, JoinHandle};
 
 pub const EVTX_CHUNK_SIZE: usize = 65536;
 pub const EVTX_FILE_HEADER_SIZE: usize = 4096;
@@ -79,6 +112,11 @@ pub struct EvtxParser<T: ReadSeek> {
     data: T,
     header: EvtxFileHeader,
     config: Arc<ParserSettings>,
+    /// Offset of the first chunk in `data`.
+    /// This is `EVTX_FILE_HEADER_SIZE`, unless the data is made only of chunks.
+    chunks_offset: u64,
+    /// The number of chunks in the file, see `chunk_count`.
+    chunk_count: u16,
 }
 
 #[derive(Clone)]
@@ -87,6 +125,16 @@ pub struct ParserSettings {
     num_threads: usize,
     /// If enabled, chunk with bad checksums will be skipped.
     validate_checksums: bool,
+    /// If enabled, substitutions which fail to decode are replaced by a placeholder,
+    /// instead of failing the whole record.
+    partial_records: bool,
+    /// If enabled, chunks whose header fails validation are parsed using a header reconstructed from their records.
+    repair_chunk_headers: bool,
+    /// If enabled, the parser skips over records with a corrupted header to the next plausible record of their chunk,
+    /// instead of abandoning the rest of the chunk.
+    resync_records: bool,
+    /// If enabled, chunks are read in the order of their record IDs instead of their order in the file.
+    order_by_record_id: bool,
     /// If enabled, XML attributes will be separated in JSON
     /// into a separate field. Example:
     /// {
@@ -110,17 +158,171 @@ pub struct ParserSettings {
     indent: bool,
     /// Controls the ansi codec used to deserialize ansi strings inside the xml document.
     ansi_codec: EncodingRef,
+    /// Controls how `Real32`/`Real64` values are rendered, in both XML and JSON.
+    float_format: FloatFormat,
+    /// The format of GUID values.
+    guid_format: GuidFormat,
+    /// If not empty, only records with an `EventID` inside one of these ranges will be yielded.
+    /// Records are skipped before they are serialized.
+    event_ids: Vec<RangeInclusive<u16>>,
+    /// If set, only records written at or after this time will be yielded.
+    start_time: Option<DateTime<Utc>>,
+    /// If set, only records written before this time will be yielded.
+    end_time: Option<DateTime<Utc>>,
+    /// If not empty, only records from one of these providers (by name or GUID) will be yielded.
+    providers: Vec<String>,
+    /// If not empty, only records from one of these channels will be yielded.
+    channels: Vec<String>,
+    /// If set, only records with an `EventRecordID` inside this range will be yielded.
+    /// Set by `EvtxParser::records_in_id_range`.
+    record_ids: Option<RangeInclusive<u64>>,
+    /// If set, only records with an `EventRecordID` which is a multiple of this will be yielded.
+    sample_every: Option<u64>,
+    /// If set, only records matching this filter will be yielded.
+    filter: Option<RecordFilter>,
+    /// Provider, level, keyword and event names, loaded at runtime.
+    enrichment: Option<Arc<Enrichment>>,
+    /// If set, the bundled description of well-known events is added to the JSON output.
+    describe_events: bool,
+    /// If set, the typed Sysmon event of Sysmon records is added to the JSON output.
+    sysmon_events: bool,
+    /// The output formats in which the symbolic names of `Level`, `Opcode`, `Task` and `Keywords` are rendered.
+    symbolic_names: Vec<RenderFormat>,
+    /// If set, the names of the SIDs of records are added to the JSON output.
+    resolve_sids: bool,
+    /// If set, the decoded fields of authentication events are added to the JSON output.
+    decode_logon_events: bool,
+    /// If set, the names of providers are resolved from their GUID when the `Name` attribute is missing.
+    resolve_provider_names: bool,
+    provider_name_lookup: Option<Arc<dyn ProviderNameLookup>>,
+    /// Lookup tables joined against `EventData` fields (by field name), whose matching rows are added
+    /// to the JSON output.
+    lookups: Vec<(String, Arc<LookupTable>)>,
+    /// If set, the values of the fields it is configured with are pseudonymized in the JSON output
+    /// (and in the element trees of records, written by `EvtxWriter`).
+    redactor: Option<Arc<Redactor>>,
+    /// If set, the location of the IP addresses of records is added to the JSON output.
+    #[cfg(feature = "geoip")]
+    geoip: Option<Arc<GeoIpEnricher>>,
+    /// If set, records whose timestamp is earlier than the one of the record before them
+    /// (in the same chunk) by more than this tolerance are reported.
+    timestamp_order_tolerance: Option<Duration>,
+    /// Templates shared by all the chunks of the parsed files.
+    file_template_cache: Option<Arc<FileTemplateCache>>,
+    /// The rendered XML and JSON of the templates of the parsed files.
+    skeleton_cache: Option<Arc<SkeletonCache>>,
+    /// Counters of the work done by the parsers using these settings.
+    stats: Option<Arc<StatsCollector>>,
+    /// Called every time a chunk is read.
+    progress: Option<ProgressCallback>,
+    /// Number of chunks parsed ahead of the record being yielded, 0 for twice the number of threads.
+    max_chunks_in_flight: usize,
+    /// Runs the parsing of chunks, the global rayon pool if `None`.
+    #[cfg(feature = "multithreading")]
+    executor: Option<Executor>,
+}
+
+/// The callback of `ParserSettings::on_progress`.
+type ProgressCallback = Arc<dyn Fn(&Progress) + Send + Sync>;
+
+/// A job of the parser, see `Executor::Spawn`.
+#[cfg(feature = "multithreading")]
+pub type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs the parsing of chunks on threads owned by the caller, see `ParserSettings::thread_pool`
+/// and `ParserSettings::spawn_with`.
+#[cfg(feature = "multithreading")]
+#[derive(Clone)]
+pub enum Executor {
+    ThreadPool(Arc<rayon::ThreadPool>),
+    /// A function running the given job (on some other thread).
+    Spawn(Arc<dyn Fn(Job) + Send + Sync>),
+}
+
+#[cfg(feature = "multithreading")]
+impl Executor {
+    fn spawn(&self, job: impl FnOnce() + Send + 'static) {
+        match self {
+            Executor::ThreadPool(pool) => pool.spawn_fifo(job),
+            Executor::Spawn(spawn) => spawn(Box::new(job)),
+        }
+    }
+
+    /// Returns `true` if the current thread is one of the executor's (as far as we can tell).
+    fn is_current_thread(&self) -> bool {
+        match self {
+            Executor::ThreadPool(pool) => pool.current_thread_index().is_some(),
+            Executor::Spawn(_) => false,
+        }
+    }
+}
+
+#[cfg(feature = "multithreading")]
+impl Debug for Executor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> ::std::fmt::Result {
+        match self {
+            Executor::ThreadPool(pool) => f
+                .debug_tuple("ThreadPool")
+                .field(&pool.current_num_threads())
+                .finish(),
+            Executor::Spawn(_) => f.write_str("Spawn"),
+        }
+    }
 }
 
 impl Debug for ParserSettings {
     fn fmt(&self, f: &mut fmt::Formatter) -> ::std::fmt::Result {
-        f.debug_struct("ParserSettings")
+        let mut debug = f.debug_struct("ParserSettings");
+        debug
             .field("num_threads", &self.num_threads)
             .field("validate_checksums", &self.validate_checksums)
+            .field("partial_records", &self.partial_records)
+            .field("repair_chunk_headers", &self.repair_chunk_headers)
+            .field("resync_records", &self.resync_records)
+            .field("order_by_record_id", &self.order_by_record_id)
             .field("separate_json_attributes", &self.separate_json_attributes)
             .field("indent", &self.indent)
             .field("ansi_codec", &self.ansi_codec.name())
-            .finish()
+            .field("float_format", &self.float_format)
+            .field("guid_format", &self.guid_format)
+            .field("event_ids", &self.event_ids)
+            .field("start_time", &self.start_time)
+            .field("end_time", &self.end_time)
+            .field("providers", &self.providers)
+            .field("channels", &self.channels)
+            .field("record_ids", &self.record_ids)
+            .field("sample_every", &self.sample_every)
+            .field("filter", &self.filter)
+            .field("enrichment", &self.enrichment.is_some())
+            .field("describe_events", &self.describe_events)
+            .field("sysmon_events", &self.sysmon_events)
+            .field("symbolic_names", &self.symbolic_names)
+            .field("resolve_sids", &self.resolve_sids)
+            .field("decode_logon_events", &self.decode_logon_events)
+            .field("resolve_provider_names", &self.resolve_provider_names)
+            .field("provider_name_lookup", &self.provider_name_lookup.is_some())
+            .field(
+                "lookups",
+                &self
+                    .lookups
+                    .iter()
+                    .map(|(field, _)| field)
+                    .collect::<Vec<_>>(),
+            )
+            .field("redactor", &self.redactor)
+            .field("timestamp_order_tolerance", &self.timestamp_order_tolerance)
+            .field("file_template_cache", &self.file_template_cache.is_some())
+            .field("skeleton_cache", &self.skeleton_cache.is_some())
+            .field("stats", &self.stats.is_some())
+            .field("progress", &self.progress.is_some())
+            .field("max_chunks_in_flight", &self.max_chunks_in_flight);
+
+        #[cfg(feature = "geoip")]
+        debug.field("geoip", &self.geoip);
+        #[cfg(feature = "multithreading")]
+        debug.field("executor", &self.executor);
+
+        debug.finish()
     }
 }
 
@@ -129,8 +331,40 @@ impl PartialEq for ParserSettings {
         self.ansi_codec.name() == other.ansi_codec.name()
             && self.num_threads == other.num_threads
             && self.validate_checksums == other.validate_checksums
+            && self.partial_records == other.partial_records
+            && self.repair_chunk_headers == other.repair_chunk_headers
+            && self.resync_records == other.resync_records
+            && self.order_by_record_id == other.order_by_record_id
             && self.separate_json_attributes == other.separate_json_attributes
             && self.indent == other.indent
+            && self.float_format == other.float_format
+            && self.guid_format == other.guid_format
+            && self.event_ids == other.event_ids
+            && self.start_time == other.start_time
+            && self.end_time == other.end_time
+            && self.providers == other.providers
+            && self.channels == other.channels
+            && self.record_ids == other.record_ids
+            && self.sample_every == other.sample_every
+            && self.filter == other.filter
+            && self.enrichment == other.enrichment
+            && self.describe_events == other.describe_events
+            && self.sysmon_events == other.sysmon_events
+            && self.symbolic_names == other.symbolic_names
+            && self.resolve_sids == other.resolve_sids
+            && self.decode_logon_events == other.decode_logon_events
+            && self.resolve_provider_names == other.resolve_provider_names
+            && self.provider_name_lookup.is_some() == other.provider_name_lookup.is_some()
+            && self.lookups == other.lookups
+            && self.redactor == other.redactor
+            && self.timestamp_order_tolerance == other.timestamp_order_tolerance
+            && self.file_template_cache.is_some() == other.file_template_cache.is_some()
+            && self.skeleton_cache.is_some() == other.skeleton_cache.is_some()
+            && self.stats.is_some() == other.stats.is_some()
+            && self.progress.is_some() == other.progress.is_some()
+            && self.max_chunks_in_flight == other.max_chunks_in_flight
+            && self.has_executor() == other.has_executor()
+            && self.has_geoip() == other.has_geoip()
     }
 }
 
@@ -139,9 +373,43 @@ impl Default for ParserSettings {
         ParserSettings {
             num_threads: 0,
             validate_checksums: false,
+            partial_records: false,
+            repair_chunk_headers: false,
+            resync_records: false,
+            order_by_record_id: false,
             separate_json_attributes: false,
             indent: true,
             ansi_codec: WINDOWS_1252,
+            float_format: FloatFormat::default(),
+            guid_format: GuidFormat::default(),
+            event_ids: vec![],
+            start_time: None,
+            end_time: None,
+            providers: vec![],
+            channels: vec![],
+            record_ids: None,
+            sample_every: None,
+            filter: None,
+            enrichment: None,
+            describe_events: false,
+            sysmon_events: false,
+            symbolic_names: vec![],
+            resolve_sids: false,
+            decode_logon_events: false,
+            resolve_provider_names: false,
+            provider_name_lookup: None,
+            lookups: vec![],
+            redactor: None,
+            #[cfg(feature = "geoip")]
+            geoip: None,
+            timestamp_order_tolerance: None,
+            file_template_cache: None,
+            skeleton_cache: None,
+            stats: None,
+            progress: None,
+            max_chunks_in_flight: 0,
+            #[cfg(feature = "multithreading")]
+            executor: None,
         }
     }
 }
@@ -173,6 +441,33 @@ impl ParserSettings {
         self
     }
 
+    /// Parses chunks on `pool` instead of the global rayon pool,
+    /// using as many threads as the pool has (see `num_threads`).
+    #[cfg(feature = "multithreading")]
+    pub fn thread_pool(mut self, pool: Arc<rayon::ThreadPool>) -> Self {
+        self.num_threads = pool.current_num_threads();
+        self.executor = Some(Executor::ThreadPool(pool));
+        self
+    }
+
+    /// Parses chunks by passing jobs to `spawn`, which should run them on another thread
+    /// (for example, the blocking pool of an async runtime).
+    /// The number of chunks parsed concurrently is still set by `num_threads`.
+    ///
+    /// Jobs can block while the caller is not consuming records.
+    #[cfg(feature = "multithreading")]
+    pub fn spawn_with(mut self, spawn: impl Fn(Job) + Send + Sync + 'static) -> Self {
+        self.executor = Some(Executor::Spawn(Arc::new(spawn)));
+        self
+    }
+
+    /// Sets the maximum number of chunks parsed (by the thread pool) ahead of the record being yielded,
+    /// bounding memory usage when the caller is slower than the parser. Defaults to twice the number of threads.
+    pub fn max_chunks_in_flight(mut self, max_chunks_in_flight: usize) -> Self {
+        self.max_chunks_in_flight = max_chunks_in_flight;
+        self
+    }
+
     /// Sets the ansi codec used by the parser.
     pub fn ansi_codec(mut self, ansi_codec: EncodingRef) -> Self {
         self.ansi_codec = ansi_codec;
@@ -186,464 +481,4187 @@ impl ParserSettings {
         self
     }
 
-    pub fn separate_json_attributes(mut self, separate: bool) -> Self {
-        self.separate_json_attributes = separate;
+    /// When a substitution of a record fails to decode, render the record anyway, with a placeholder
+    /// instead of the substitution (`<!-- parse error -->` in XML, `"__error__"` in JSON).
+    /// The errors are listed in the `errors` of the serialized record.
+    ///
+    /// Records whose structure (rather than a value) is corrupted are still returned as errors.
+    pub fn partial_records(mut self, partial_records: bool) -> Self {
+        self.partial_records = partial_records;
 
         self
     }
 
-    pub fn indent(mut self, pretty: bool) -> Self {
-        self.indent = pretty;
+    /// When the header of a chunk has an invalid magic (or invalid checksums, if `validate_checksums` is set),
+    /// reconstruct it from the records found in the chunk (see `EvtxChunkData::reconstruct`),
+    /// instead of failing the chunk. The records of such chunks are marked as `recovered`.
+    pub fn repair_chunk_headers(mut self, repair_chunk_headers: bool) -> Self {
+        self.repair_chunk_headers = repair_chunk_headers;
 
         self
     }
 
-    /// Gets the current ansi codec
-    pub fn get_ansi_codec(&self) -> EncodingRef {
-        self.ansi_codec
-    }
+    /// When the header of a record is corrupted (an invalid magic, or a size which does not fit in the chunk),
+    /// skip to the next plausible record of the chunk (a valid record header whose size is repeated at it's end),
+    /// yielding `Error::SkippedCorruptedRecordData` for the skipped bytes.
+    ///
+    /// By default, the rest of the chunk is abandoned, since a plausible record is not guaranteed to be a real one.
+    pub fn resync_records(mut self, resync_records: bool) -> Self {
+        self.resync_records = resync_records;
 
-    pub fn should_separate_json_attributes(&self) -> bool {
-        self.separate_json_attributes
+        self
     }
 
-    pub fn should_indent(&self) -> bool {
-        self.indent
+    /// Yield the records of a log which has wrapped around (see `EvtxParser::wrap_point`)
+    /// in the order of their record IDs, by reading the chunks in the order of their first record ID
+    /// instead of their physical order. Chunks whose header cannot be read are read last.
+    ///
+    /// Applies to `EvtxParser::records` (and the other iterators over all the records of the file).
+    pub fn order_by_record_id(mut self, order_by_record_id: bool) -> Self {
+        self.order_by_record_id = order_by_record_id;
+
+        self
     }
 
-    pub fn should_validate_checksums(&self) -> bool {
-        self.validate_checksums
+    pub fn separate_json_attributes(mut self, separate: bool) -> Self {
+        self.separate_json_attributes = separate;
+
+        self
     }
 
-    pub fn get_num_threads(&self) -> &usize {
-        &self.num_threads
+    pub fn indent(mut self, pretty: bool) -> Self {
+        self.indent = pretty;
+
+        self
     }
-}
 
-impl EvtxParser<File> {
-    /// Attempts to load an evtx file from a given path, will fail if the path does not exist,
-    /// or if evtx header is invalid.
-    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
-        let path = path
-            .as_ref()
-            .canonicalize()
-            .context(err::InvalidInputPath {
-                path: path.as_ref().to_string_lossy().to_string(),
-            })?;
+    /// Only records with one of the given event IDs will be yielded.
+    /// The `EventID` is read from the record's template substitutions,
+    /// so non-matching records are skipped without being rendered.
+    pub fn event_id_filter(self, event_ids: &[u16]) -> Self {
+        let ranges: Vec<RangeInclusive<u16>> = event_ids.iter().map(|&id| id..=id).collect();
+
+        self.event_id_ranges_filter(&ranges)
+    }
 
-        let f = File::open(&path).context(err::FailedToOpenFile { path })?;
+    /// Same as `event_id_filter`, but accepts inclusive ranges of event IDs.
+    pub fn event_id_ranges_filter(mut self, ranges: &[RangeInclusive<u16>]) -> Self {
+        self.event_ids = ranges.to_vec();
 
-        let cursor = f;
-        Self::from_read_seek(cursor)
+        self
     }
-}
 
-impl EvtxParser<Cursor<Vec<u8>>> {
-    /// Attempts to load an evtx file from a given path, will fail the evtx header is invalid.
-    pub fn from_buffer(buffer: Vec<u8>) -> Result<Self> {
-        let cursor = Cursor::new(buffer);
-        Self::from_read_seek(cursor)
+    /// Only records with a timestamp inside `[start, end)` will be yielded,
+    /// `None` leaves that side of the range open.
+    ///
+    /// Chunks whose records are all outside of the range are skipped entirely,
+    /// reading only their record headers (see `EvtxChunkData::timestamp_bounds`).
+    pub fn time_range_filter(
+        mut self,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Self {
+        self.start_time = start;
+        self.end_time = end;
+
+        self
     }
-}
 
-impl<T: ReadSeek> EvtxParser<T> {
-    pub fn from_read_seek(mut read_seek: T) -> Result<Self> {
-        let evtx_header = EvtxFileHeader::from_stream(&mut read_seek)?;
+    /// Only every `n`th record (the `n`th, `2n`th.. of the records kept by the other filters) will be yielded,
+    /// for a quick preview of a large file.
+    /// `0` and `1` yield all the records.
+    pub fn sample_every(mut self, n: u64) -> Self {
+        self.sample_every = Some(n).filter(|&n| n > 1);
 
-        debug!("EVTX Header: {:#?}", evtx_header);
-        Ok(EvtxParser {
-            data: read_seek,
-            header: evtx_header,
-            config: Arc::new(ParserSettings::default()),
-        })
+        self
     }
 
-    pub fn with_configuration(mut self, configuration: ParserSettings) -> Self {
-        self.config = Arc::new(configuration);
+    /// Sets the format of `Real32`/`Real64` values.
+    /// In JSON, values are emitted as strings unless the format is `FloatFormat::Shortest` (the default)
+    /// or `FloatFormat::ShortestWidened`.
+    pub fn float_format(mut self, float_format: FloatFormat) -> Self {
+        self.float_format = float_format;
         self
     }
 
-    /// Allocate a new chunk from the given data, at the offset expected by `chunk_number`.
-    /// If the read chunk contains valid data, an `Ok(Some(EvtxChunkData))` will be returned.
-    /// If the read chunk contains invalid data (bad magic, bad checksum when `validate_checksum` is set to true),
-    /// of if not enough data can be read (e.g. because we reached EOF), an `Err` is returned.
-    /// If the read chunk is empty, `Ok(None)` will be returned.
-    fn allocate_chunk(
-        data: &mut T,
-        chunk_number: u16,
-        validate_checksum: bool,
-    ) -> Result<Option<EvtxChunkData>> {
-        let mut chunk_data = Vec::with_capacity(EVTX_CHUNK_SIZE);
-        let chunk_offset = EVTX_FILE_HEADER_SIZE + chunk_number as usize * EVTX_CHUNK_SIZE;
+    /// Sets the format of GUID values (in braces, hyphenated, uppercase), in both XML and JSON.
+    pub fn guid_format(mut self, guid_format: GuidFormat) -> Self {
+        self.guid_format = guid_format;
+        self
+    }
 
-        data.seek(SeekFrom::Start(chunk_offset as u64))?;
+    /// Sets the enrichment data (provider, level, keyword and event names).
+    pub fn enrichment(mut self, enrichment: Enrichment) -> Self {
+        self.enrichment = Some(Arc::new(enrichment));
+        self
+    }
 
-        let amount_read = data
-            .take(EVTX_CHUNK_SIZE as u64)
-            .read_to_end(&mut chunk_data)?;
+    /// Loads the enrichment data from a directory of JSON/TOML files, see `Enrichment` for the format.
+    /// The files are read when this is called, so they can be updated without recompiling.
+    pub fn load_enrichment_dir(self, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(self.enrichment(Enrichment::load_dir(path)?))
+    }
+
+    /// Extracts the `WEVT_TEMPLATE` resources of the provider binary (DLL/EXE) at `path`, and adds the providers
+    /// of their manifests (event definitions, templates, level, task, opcode, channel and keyword names)
+    /// to the enrichment data, see `Enrichment::add_manifest`.
+    ///
+    /// Fails if the binary has no `WEVT_TEMPLATE` resource.
+    #[cfg(feature = "wevt_templates")]
+    pub fn load_provider_binary(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let resources = extract_wevt_template_resources(path)?;
 
         ensure!(
-            amount_read == EVTX_CHUNK_SIZE,
-            err::IncompleteChunk { chunk_number }
+            !resources.is_empty(),
+            err::FailedToReadPEResources {
+                message: format!("No `WEVT_TEMPLATE` resource in {}", path.display()),
+            }
         );
 
-        // There might be empty chunks in the middle of a dirty file.
-        if chunk_data.iter().all(|x| *x == 0) {
-            return Ok(None);
+        let enrichment = Arc::make_mut(self.enrichment.get_or_insert_with(Default::default));
+        for resource in resources {
+            enrichment.add_manifest(resource.manifest()?);
         }
 
-        EvtxChunkData::new(chunk_data, validate_checksum).map(Some)
+        Ok(self)
     }
 
-    /// Find the next chunk, staring at `chunk_number` (inclusive).
-    /// If a chunk is found, returns the data of the chunk or the relevant error,
-    /// and the number of that chunk.
-    pub fn find_next_chunk(
-        &mut self,
-        mut chunk_number: u16,
-    ) -> Option<(Result<EvtxChunkData>, u16)> {
-        loop {
-            match EvtxParser::allocate_chunk(
-                &mut self.data,
-                chunk_number,
-                self.config.validate_checksums,
-            ) {
-                Err(err) => {
-                    // We try to read past the `chunk_count` to allow for dirty files.
-                    // But if we failed, it means we really are at the end of the file.
-                    if chunk_number >= self.header.chunk_count {
-                        return None;
-                    } else {
-                        return Some((Err(err), chunk_number));
-                    }
-                }
-                Ok(None) => {
-                    // We try to read past the `chunk_count` to allow for dirty files.
-                    // But if we get an empty chunk, we need to keep looking.
-                    // Increment and try again.
-                    chunk_number += 1;
-                }
-                Ok(Some(chunk)) => {
-                    return Some((Ok(chunk), chunk_number));
-                }
-            };
+    /// Renders the symbolic names of the `Level`, `Opcode`, `Task` and `Keywords` values of records
+    /// (such as `Warning` or `Audit Failure`) when outputting `format`, see `symbolic_names`.
+    pub fn symbolic_names(mut self, format: RenderFormat, symbolic_names: bool) -> Self {
+        self.symbolic_names.retain(|f| *f != format);
+        if symbolic_names {
+            self.symbolic_names.push(format);
         }
+        self
     }
 
-    /// Return an iterator over all the chunks.
-    /// Each chunk supports iterating over it's records in their un-serialized state
-    /// (before they are converted to XML or JSON).
-    pub fn chunks(&mut self) -> IterChunks<T> {
-        IterChunks {
-            parser: self,
-            current_chunk_number: 0,
-        }
+    /// Adds the names of the SIDs of records (well-known SIDs, and those of the `sids` table of the enrichment data)
+    /// to their JSON output, in an `Event.ResolvedSids` object, see `sid_names`.
+    /// The XML output is unchanged.
+    pub fn resolve_sids(mut self, resolve_sids: bool) -> Self {
+        self.resolve_sids = resolve_sids;
+        self
     }
 
-    /// Consumes the parser, returning an iterator over all the chunks.
-    /// Each chunk supports iterating over it's records in their un-serialized state
-    /// (before they are converted to XML or JSON).
-    pub fn into_chunks(self) -> IntoIterChunks<T> {
-        IntoIterChunks {
-            parser: self,
-            current_chunk_number: 0,
-        }
+    /// Adds the decoded fields of logon (4624), failed logon (4625), logoff (4634) and special privileges (4672)
+    /// events to their JSON output, in an `Event.Logon` object (logon type names, status meanings, elevation),
+    /// see `logon`. The XML output is unchanged.
+    pub fn decode_logon_events(mut self, decode_logon_events: bool) -> Self {
+        self.decode_logon_events = decode_logon_events;
+        self
     }
-    /// Return an iterator over all the records.
-    /// Records will be mapped `f`, which must produce owned data from the records.
-    pub fn serialized_records<'a, U: Send>(
-        &'a mut self,
-        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'a,
-    ) -> impl Iterator<Item = Result<U>> + '_ {
-        // Retrieve parser settings here, while `self` is immutably borrowed.
-        let num_threads = max(self.config.num_threads, 1);
-        let chunk_settings = Arc::clone(&self.config);
 
-        // `self` is mutably borrowed from here on.
-        let mut chunks = self.chunks();
+    /// Resolves the name of the provider of records whose `Provider` element has a `Guid` but no `Name`,
+    /// adding it to the JSON output and returning it from `EvtxRecord::provider_name`, see `provider_names`.
+    pub fn resolve_provider_names(mut self, resolve_provider_names: bool) -> Self {
+        self.resolve_provider_names = resolve_provider_names;
+        self
+    }
 
-        let records_per_chunk = std::iter::from_fn(move || {
-            // Allocate some chunks in advance, so they can be parsed in parallel.
-            let mut chunk_of_chunks = Vec::with_capacity(num_threads);
+    /// Sets a lookup of provider names, consulted before the enrichment data and the bundled table.
+    /// Implies `resolve_provider_names`.
+    pub fn provider_name_lookup(mut self, lookup: impl ProviderNameLookup + 'static) -> Self {
+        self.provider_name_lookup = Some(Arc::new(lookup));
+        self.resolve_provider_names = true;
+        self
+    }
 
-            for _ in 0..num_threads {
-                if let Some(chunk) = chunks.next() {
-                    chunk_of_chunks.push(chunk);
-                };
-            }
+    /// Joins `table` against the `EventData` field `field` of records, adding the columns of the matching row
+    /// to their JSON output, in an `Event.Lookup` object keyed by field name, see `LookupTable`.
+    /// Can be called several times, joining several fields (or several tables on the same field).
+    /// The XML output is unchanged.
+    pub fn lookup(mut self, field: impl Into<String>, table: Arc<LookupTable>) -> Self {
+        self.lookups.push((field.into(), table));
+        self
+    }
 
-            // We only stop once no chunks can be allocated.
-            if chunk_of_chunks.is_empty() {
-                None
-            } else {
-                #[cfg(feature = "multithreading")]
-                let chunk_iter = chunk_of_chunks.into_par_iter();
-
-                #[cfg(not(feature = "multithreading"))]
-                let chunk_iter = chunk_of_chunks.into_iter();
-
-                // Serialize the records in each chunk.
-                let iterators: Vec<Vec<Result<U>>> = chunk_iter
-                    .map(|chunk_res| match chunk_res {
-                        Err(err) => vec![Err(err)],
-                        Ok(mut chunk) => {
-                            let chunk_records_res = chunk.parse(chunk_settings.clone());
-
-                            match chunk_records_res {
-                                Err(err) => vec![Err(err)],
-                                Ok(mut chunk_records) => {
-                                    chunk_records.iter().map(f.clone()).collect()
-                                }
-                            }
-                        }
-                    })
-                    .collect();
+    /// Pseudonymizes the values of the fields `redactor` is configured with (such as user names, SIDs and
+    /// IP addresses), in the JSON output of records and in their element trees (`EventElement::from_record`),
+    /// so the EVTX files written from them are redacted too. The XML output is unchanged.
+    ///
+    /// Annotations derived from the original values (`ResolvedSids`, `Lookup`, `GeoIp`, ...) should not be
+    /// enabled along with redaction.
+    pub fn redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
 
-                Some(iterators.into_iter().flatten())
-            }
-        });
+    /// Adds the country and autonomous system of the IP addresses of records (`IpAddress`, `WorkstationIp`, ...)
+    /// to their JSON output, in an `Event.GeoIp` object, see `geoip`.
+    /// The XML output is unchanged.
+    #[cfg(feature = "geoip")]
+    pub fn geoip(mut self, enricher: GeoIpEnricher) -> Self {
+        self.geoip = Some(Arc::new(enricher));
+        self
+    }
 
-        records_per_chunk.flatten()
+    /// Adds the bundled description of well-known Security, EventLog and Sysmon events
+    /// (see `event_descriptions`) to the JSON output of records, as an `event.description` field.
+    /// The XML output is unchanged.
+    #[cfg(feature = "event_descriptions")]
+    pub fn describe_events(mut self, describe_events: bool) -> Self {
+        self.describe_events = describe_events;
+        self
     }
 
-    /// Return an iterator over all the records.
-    /// Records will be XML-formatted.
-    pub fn records(&mut self) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
-        // '_ is required in the signature because the iterator is bound to &self.
-        self.serialized_records(|record| record.and_then(|record| record.into_xml()))
+    /// Adds the typed event of Sysmon records (with split hashes and normalized timestamps, see `sysmon`)
+    /// to their JSON output, in an `Event.Sysmon` object.
+    /// The XML output is unchanged.
+    #[cfg(feature = "sysmon")]
+    pub fn sysmon_events(mut self, sysmon_events: bool) -> Self {
+        self.sysmon_events = sysmon_events;
+        self
     }
 
-    /// Return an iterator over all the records.
-    /// Records will be JSON-formatted.
-    pub fn records_json(
-        &mut self,
-    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
-        self.serialized_records(|record| record.and_then(|record| record.into_json()))
+    /// Enables validation of the order of record timestamps within chunks.
+    /// Records going backwards in time by more than `tolerance` (which is associated with injected records)
+    /// are logged as warnings when parsed, and listed in `EvtxParser::chunk_table`.
+    pub fn validate_timestamp_order(mut self, tolerance: Duration) -> Self {
+        self.timestamp_order_tolerance = Some(tolerance);
+        self
     }
 
-    /// Return an iterator over all the records.
-    /// Records will have a `serde_json::Value` data attribute.
-    pub fn records_json_value(
-        &mut self,
-    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<serde_json::Value>>> + '_ {
-        self.serialized_records(|record| record.and_then(|record| record.into_json_value()))
+    /// Enables a template cache shared by all the chunks (keyed by template GUID),
+    /// so templates are parsed once per file instead of once per chunk.
+    /// Records referencing a template missing from their chunk are rendered using the cached template, if any.
+    ///
+    /// The cache is shared by the parsers using (clones of) these settings.
+    pub fn file_template_cache(mut self, enabled: bool) -> Self {
+        self.file_template_cache = if enabled {
+            Some(Arc::new(FileTemplateCache::new()))
+        } else {
+            None
+        };
+        self
     }
-}
 
-pub struct IterChunks<'c, T: ReadSeek> {
-    parser: &'c mut EvtxParser<T>,
-    current_chunk_number: u16,
-}
+    /// Enables a cache of the XML and JSON of templates (keyed by template GUID), rendered once with placeholders.
+    /// Records using a cached template are rendered by splicing in their substitution values
+    /// (into a clone of the JSON skeleton, for JSON), instead of walking all their tokens.
+    ///
+    /// The cache is shared by the parsers using (clones of) these settings.
+    pub fn skeleton_cache(mut self, enabled: bool) -> Self {
+        self.skeleton_cache = if enabled {
+            Some(Arc::new(SkeletonCache::new()))
+        } else {
+            None
+        };
+        self
+    }
 
-impl<'c, T: ReadSeek> Iterator for IterChunks<'c, T> {
-    type Item = Result<EvtxChunkData>;
-    fn next(&mut self) -> Option<<Self as Iterator>::Item> {
-        match self.parser.find_next_chunk(self.current_chunk_number) {
-            None => None,
-            Some((chunk, chunk_number)) => {
-                self.current_chunk_number = chunk_number + 1;
+    /// Enables counting the chunks and records parsed, and the time spent in every stage of parsing,
+    /// see `get_stats`.
+    ///
+    /// The counters are shared by the parsers using (clones of) these settings.
+    pub fn collect_stats(mut self, enabled: bool) -> Self {
+        self.stats = if enabled {
+            Some(Arc::new(StatsCollector::default()))
+        } else {
+            None
+        };
+        self
+    }
 
-                Some(chunk)
-            }
-        }
+    /// Calls `callback` every time a parser using these settings reads a chunk (on the thread reading the chunks),
+    /// for example to show a progress bar.
+    ///
+    /// ```rust
+    /// use evtx::{EvtxParser, ParserSettings};
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let records = Arc::new(AtomicU64::new(0));
+    /// let counted = Arc::clone(&records);
+    /// let settings = ParserSettings::new().on_progress(move |progress| {
+    ///     counted.fetch_add(progress.records, Ordering::Relaxed);
+    /// });
+    ///
+    /// let mut parser = EvtxParser::from_path("samples/security.evtx")
+    ///     .unwrap()
+    ///     .with_configuration(settings);
+    /// assert_eq!(parser.records().count() as u64, records.load(Ordering::Relaxed));
+    /// ```
+    pub fn on_progress(mut self, callback: impl Fn(&Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
     }
-}
 
-pub struct IntoIterChunks<T: ReadSeek> {
-    parser: EvtxParser<T>,
-    current_chunk_number: u16,
+    /// Gets the current ansi codec
+    pub fn get_ansi_codec(&self) -> EncodingRef {
+        self.ansi_codec
+    }
+
+    pub fn get_float_format(&self) -> FloatFormat {
+        self.float_format
+    }
+
+    pub fn get_guid_format(&self) -> GuidFormat {
+        self.guid_format
+    }
+
+    pub fn should_separate_json_attributes(&self) -> bool {
+        self.separate_json_attributes
+    }
+
+    pub fn should_indent(&self) -> bool {
+        self.indent
+    }
+
+    pub fn should_validate_checksums(&self) -> bool {
+        self.validate_checksums
+    }
+
+    pub fn should_render_partial_records(&self) -> bool {
+        self.partial_records
+    }
+
+    pub fn should_repair_chunk_headers(&self) -> bool {
+        self.repair_chunk_headers
+    }
+
+    pub fn should_resync_records(&self) -> bool {
+        self.resync_records
+    }
+
+    pub fn should_order_by_record_id(&self) -> bool {
+        self.order_by_record_id
+    }
+
+    pub fn get_num_threads(&self) -> &usize {
+        &self.num_threads
+    }
+
+    #[cfg(feature = "multithreading")]
+    pub fn get_executor(&self) -> Option<&Executor> {
+        self.executor.as_ref()
+    }
+
+    fn has_executor(&self) -> bool {
+        #[cfg(feature = "multithreading")]
+        return self.executor.is_some();
+
+        #[cfg(not(feature = "multithreading"))]
+        return false;
+    }
+
+    pub fn get_max_chunks_in_flight(&self) -> usize {
+        match self.max_chunks_in_flight {
+            0 => max(self.num_threads, 1) * 2,
+            n => n,
+        }
+    }
+
+    /// Only records from one of the given providers will be yielded.
+    /// Providers can be given by name or by GUID (with or without braces), matching is case insensitive.
+    pub fn provider_filter<S: AsRef<str>>(mut self, providers: &[S]) -> Self {
+        self.providers = providers
+            .iter()
+            .map(|provider| normalize_provider(provider.as_ref()))
+            .collect();
+
+        self
+    }
+
+    /// Only records from one of the given channels will be yielded (e.g. `Security`).
+    /// Matching is case insensitive.
+    pub fn channel_filter<S: AsRef<str>>(mut self, channels: &[S]) -> Self {
+        self.channels = channels
+            .iter()
+            .map(|channel| channel.as_ref().to_lowercase())
+            .collect();
+
+        self
+    }
+
+    /// Only records matching `filter` will be yielded.
+    /// The filter is combined (with AND) with the other filters of the settings,
+    /// use `RecordFilter::or` to express alternatives.
+    pub fn record_filter(mut self, filter: RecordFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    /// Only records kept by the include and exclude `rules` will be yielded.
+    /// The rules are combined (with AND) with the filter set by `record_filter` (or `filter_expr`..),
+    /// so they should be set after it.
+    pub fn filter_rules(mut self, rules: FilterRules) -> Self {
+        if let Some(rules) = rules.into_filter() {
+            self.filter = match self.filter.take() {
+                Some(filter) => Some(filter.and(rules)),
+                None => Some(rules),
+            };
+        }
+
+        self
+    }
+
+    /// Loads include and exclude rules from a JSON or TOML file, see `filter_rules`.
+    pub fn load_filter_rules(self, path: impl AsRef<Path>) -> Result<Self> {
+        Ok(self.filter_rules(FilterRules::load_file(path)?))
+    }
+
+    /// Only records matching the filter expression will be yielded,
+    /// see `RecordFilter::parse` for the syntax.
+    /// Replaces the filter set by `record_filter`.
+    pub fn filter_expr(self, expr: &str) -> Result<Self> {
+        Ok(self.record_filter(RecordFilter::parse(expr)?))
+    }
+
+    /// Only records matching the query will be yielded,
+    /// see `RecordFilter::from_query` for the syntax.
+    /// Replaces the filter set by `record_filter`.
+    pub fn filter_query(self, query: &str) -> Result<Self> {
+        Ok(self.record_filter(RecordFilter::from_query(query)?))
+    }
+
+    /// Only records matching the XPath query will be yielded,
+    /// see `RecordFilter::from_xpath` for the supported subset.
+    /// Replaces the filter set by `record_filter`.
+    pub fn xpath_filter(self, query: &str) -> Result<Self> {
+        Ok(self.record_filter(RecordFilter::from_xpath(query)?))
+    }
+
+    pub fn get_event_id_filter(&self) -> &[RangeInclusive<u16>] {
+        &self.event_ids
+    }
+
+    pub fn get_time_range_filter(&self) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        (self.start_time, self.end_time)
+    }
+
+    pub fn get_provider_filter(&self) -> &[String] {
+        &self.providers
+    }
+
+    pub fn get_channel_filter(&self) -> &[String] {
+        &self.channels
+    }
+
+    pub fn get_sample_every(&self) -> Option<u64> {
+        self.sample_every
+    }
+
+    pub fn get_record_filter(&self) -> Option<&RecordFilter> {
+        self.filter.as_ref()
+    }
+
+    pub fn get_timestamp_order_tolerance(&self) -> Option<Duration> {
+        self.timestamp_order_tolerance
+    }
+
+    pub fn get_enrichment(&self) -> Option<&Enrichment> {
+        self.enrichment.as_deref()
+    }
+
+    pub fn should_describe_events(&self) -> bool {
+        self.describe_events
+    }
+
+    pub fn should_add_sysmon_events(&self) -> bool {
+        self.sysmon_events
+    }
+
+    pub fn should_render_symbolic_names(&self, format: RenderFormat) -> bool {
+        self.symbolic_names.contains(&format)
+    }
+
+    pub fn should_resolve_sids(&self) -> bool {
+        self.resolve_sids
+    }
+
+    pub fn should_decode_logon_events(&self) -> bool {
+        self.decode_logon_events
+    }
+
+    pub fn should_resolve_provider_names(&self) -> bool {
+        self.resolve_provider_names
+    }
+
+    pub fn get_provider_name_lookup(&self) -> Option<&dyn ProviderNameLookup> {
+        self.provider_name_lookup.as_deref()
+    }
+
+    pub fn get_lookups(&self) -> &[(String, Arc<LookupTable>)] {
+        &self.lookups
+    }
+
+    pub fn get_redactor(&self) -> Option<&Redactor> {
+        self.redactor.as_deref()
+    }
+
+    #[cfg(feature = "geoip")]
+    pub fn get_geoip(&self) -> Option<&GeoIpEnricher> {
+        self.geoip.as_deref()
+    }
+
+    fn has_geoip(&self) -> bool {
+        #[cfg(feature = "geoip")]
+        return self.geoip.is_some();
+        #[cfg(not(feature = "geoip"))]
+        false
+    }
+
+    pub fn get_file_template_cache(&self) -> Option<&FileTemplateCache> {
+        self.file_template_cache.as_deref()
+    }
+
+    pub fn get_skeleton_cache(&self) -> Option<&SkeletonCache> {
+        self.skeleton_cache.as_deref()
+    }
+
+    /// Returns the counters of the parsers using these settings (so far), `None` unless `collect_stats` is enabled.
+    pub fn get_stats(&self) -> Option<ParserStats> {
+        self.stats.as_ref().map(|stats| stats.snapshot())
+    }
+
+    /// Adds `n` to `counter`, when stats are collected.
+    pub(crate) fn count(&self, counter: Counter, n: u64) {
+        if let Some(stats) = &self.stats {
+            stats.add(counter, n);
+        }
+    }
+
+    /// Calls the callback of `on_progress` (if any) for a chunk which was just read.
+    /// Chunks which fail past `chunk_count` are not reported, since they are expected at the end of dirty files.
+    pub(crate) fn report_progress(
+        &self,
+        chunk_number: u16,
+        chunk_count: u16,
+        chunk: &Result<Option<EvtxChunkData>>,
+    ) {
+        let callback = match &self.progress {
+            Some(callback) => callback,
+            None => return,
+        };
+
+        let records = match chunk {
+            Ok(Some(chunk)) => (chunk.header.last_event_record_id + 1)
+                .saturating_sub(chunk.header.first_event_record_id),
+            Ok(None) | Err(_) if chunk_number < chunk_count => 0,
+            _ => return,
+        };
+
+        callback(&Progress {
+            chunk_number,
+            chunk_count,
+            records,
+        });
+    }
+
+    /// Runs `f`, adding the time it took to `stage` when stats are collected.
+    pub(crate) fn time<T>(&self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        match &self.stats {
+            Some(stats) => stats.time(stage, f),
+            None => f(),
+        }
+    }
+
+    /// Returns true if the (not yet rendered) record should be yielded.
+    /// Fails if the record cannot be deserialized, so it is not filtered out silently.
+    pub(crate) fn matches_record(&self, record: &EvtxRecord) -> Result<bool> {
+        record.tokens()?;
+
+        Ok(self.matches_event_id(record.event_id())
+            && self.matches_provider(record)
+            && self.matches_channel(record)
+            && match &self.filter {
+                Some(filter) => filter.matches(record)?,
+                None => true,
+            })
+    }
+
+    /// Returns true if `matches_record` needs the values of records (which are then deserialized up front).
+    pub(crate) fn has_record_filters(&self) -> bool {
+        !self.event_ids.is_empty()
+            || !self.providers.is_empty()
+            || !self.channels.is_empty()
+            || self.filter.is_some()
+    }
+
+    /// Returns true if some of the filters might be decided by `rejects_substitutions`.
+    pub(crate) fn has_substitution_filters(&self) -> bool {
+        !self.event_ids.is_empty() || self.filter.is_some()
+    }
+
+    /// Returns true if a record can be rejected using only it's raw substitutions
+    /// (before it is deserialized), see `RawSubstitutions`.
+    pub(crate) fn rejects_substitutions(&self, raw: &RawSubstitutions) -> bool {
+        let rejected_event_id = !self.event_ids.is_empty()
+            && raw
+                .event_id()
+                .map_or(false, |id| !self.matches_event_id(Some(id)));
+
+        rejected_event_id
+            || self
+                .filter
+                .as_ref()
+                .and_then(|filter| filter.matches_substitutions(raw))
+                == Some(false)
+    }
+
+    fn matches_provider(&self, record: &EvtxRecord) -> bool {
+        if self.providers.is_empty() {
+            return true;
+        }
+
+        let name = record.provider_name().map(|name| normalize_provider(&name));
+        let guid = record.provider_guid().map(|guid| normalize_provider(&guid));
+
+        self.providers
+            .iter()
+            .any(|provider| Some(provider) == name.as_ref() || Some(provider) == guid.as_ref())
+    }
+
+    fn matches_channel(&self, record: &EvtxRecord) -> bool {
+        if self.channels.is_empty() {
+            return true;
+        }
+
+        match record.channel() {
+            Some(channel) => self.channels.contains(&channel.to_lowercase()),
+            None => false,
+        }
+    }
+
+    /// Returns true if a record with the given timestamp should be yielded.
+    pub(crate) fn matches_timestamp(&self, timestamp: &DateTime<Utc>) -> bool {
+        self.start_time.map_or(true, |start| *timestamp >= start)
+            && self.end_time.map_or(true, |end| *timestamp < end)
+    }
+
+    /// Returns true if a record with the given `EventRecordID` should be yielded.
+    pub(crate) fn matches_record_id(&self, record_id: u64) -> bool {
+        self.record_ids
+            .as_ref()
+            .map_or(true, |range| range.contains(&record_id))
+    }
+
+    /// Returns true if a chunk whose records have timestamps in `[earliest, latest]`
+    /// might contain records which should be yielded.
+    pub(crate) fn matches_time_bounds(
+        &self,
+        earliest: &DateTime<Utc>,
+        latest: &DateTime<Utc>,
+    ) -> bool {
+        self.start_time.map_or(true, |start| *latest >= start)
+            && self.end_time.map_or(true, |end| *earliest < end)
+    }
+
+    /// Returns true if a record with the given `EventID` should be yielded.
+    /// A record without an `EventID` only passes when no filter is set.
+    fn matches_event_id(&self, event_id: Option<u16>) -> bool {
+        if self.event_ids.is_empty() {
+            return true;
+        }
+
+        match event_id {
+            Some(id) => self.event_ids.iter().any(|range| range.contains(&id)),
+            None => false,
+        }
+    }
 }
 
-impl<T: ReadSeek> Iterator for IntoIterChunks<T> {
-    type Item = Result<EvtxChunkData>;
-    fn next(&mut self) -> Option<<Self as Iterator>::Item> {
-        info!("Chunk {}", self.current_chunk_number);
-        match self.parser.find_next_chunk(self.current_chunk_number) {
-            None => None,
-            Some((chunk, chunk_number)) => {
-                self.current_chunk_number = chunk_number + 1;
+impl EvtxParser<InputFile> {
+    /// Attempts to load an evtx file from a given path, will fail if the path does not exist,
+    /// or if evtx header is invalid.
+    ///
+    /// Compressed files (gzip, zstd, or a ZIP archive holding a single evtx file) are detected
+    /// by their magic bytes and decompressed in memory, see the `compression` feature.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path
+            .as_ref()
+            .canonicalize()
+            .context(err::InvalidInputPath {
+                path: path.as_ref().to_string_lossy().to_string(),
+            })?;
 
-                Some(chunk)
+        Self::from_read_seek(open_input(&path)?)
+    }
+
+    /// Attempts to load a file which is a concatenation of chunks, without an evtx file header
+    /// (for example chunks exported by carving tools).
+    /// Will fail if the path does not exist, or if the file does not start with a chunk header.
+    pub fn from_chunks_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path
+            .as_ref()
+            .canonicalize()
+            .context(err::InvalidInputPath {
+                path: path.as_ref().to_string_lossy().to_string(),
+            })?;
+
+        Self::from_chunks_read_seek(open_input(&path)?)
+    }
+}
+
+impl EvtxParser<Cursor<Vec<u8>>> {
+    /// Attempts to load an evtx file from a given path, will fail the evtx header is invalid.
+    pub fn from_buffer(buffer: Vec<u8>) -> Result<Self> {
+        let cursor = Cursor::new(buffer);
+        Self::from_read_seek(cursor)
+    }
+}
+
+impl<T: ReadSeek> EvtxParser<T> {
+    pub fn from_read_seek(mut read_seek: T) -> Result<Self> {
+        let evtx_header = EvtxFileHeader::from_stream(&mut read_seek)?;
+
+        debug!("EVTX Header: {:#?}", evtx_header);
+
+        // The header of a dirty file was not updated since chunks were last written,
+        // so it's chunk count might be stale.
+        // Chunks it does count are kept even without a valid magic, to report them as corrupted.
+        let chunk_count = if evtx_header.is_dirty() {
+            let chunk_count = max(
+                count_chunks(&mut read_seek, EVTX_FILE_HEADER_SIZE as u64)?,
+                evtx_header.chunk_count,
+            );
+
+            if chunk_count != evtx_header.chunk_count {
+                info!(
+                    "The file is dirty, found {} chunks (the header says {})",
+                    chunk_count, evtx_header.chunk_count
+                );
             }
-        }
+            chunk_count
+        } else {
+            evtx_header.chunk_count
+        };
+
+        Ok(EvtxParser {
+            data: read_seek,
+            header: evtx_header,
+            config: Arc::new(ParserSettings::default()),
+            chunks_offset: EVTX_FILE_HEADER_SIZE as u64,
+            chunk_count,
+        })
+    }
+
+    /// Same as `from_read_seek`, but for data which is only a concatenation of chunks
+    /// (no evtx file header). The data is expected to start with `ElfChnk` magic.
+    pub fn from_chunks_read_seek(mut read_seek: T) -> Result<Self> {
+        let mut magic = [0_u8; 8];
+        read_seek.seek(SeekFrom::Start(0))?;
+        read_seek.read_exact(&mut magic)?;
+
+        ensure!(
+            &magic == b"ElfChnk\x00",
+            err::InvalidEvtxChunkMagic { magic }
+        );
+
+        let data_size = read_seek.seek(SeekFrom::End(0))?;
+        // A trailing partial chunk is counted, so it will be reported as incomplete.
+        let chunk_count = (data_size + EVTX_CHUNK_SIZE as u64 - 1) / EVTX_CHUNK_SIZE as u64;
+        let chunk_count = min(chunk_count, u64::from(u16::MAX)) as u16;
+
+        let evtx_header = EvtxFileHeader::for_chunks_only(chunk_count);
+
+        debug!("Chunk only data, with {} chunks", chunk_count);
+        Ok(EvtxParser {
+            data: read_seek,
+            header: evtx_header,
+            config: Arc::new(ParserSettings::default()),
+            chunks_offset: 0,
+            chunk_count,
+        })
+    }
+
+    pub fn with_configuration(mut self, configuration: ParserSettings) -> Self {
+        self.config = Arc::new(configuration);
+        self
+    }
+
+    /// Returns the file header (an empty one when parsing chunks only, see `from_chunks_read_seek`).
+    pub fn header(&self) -> &EvtxFileHeader {
+        &self.header
+    }
+
+    /// Returns the number of chunks in the file.
+    /// This is the chunk count of the file header, unless the file is dirty (see `EvtxFileHeader::is_dirty`),
+    /// in which case chunks past it are counted by their `ElfChnk` magic, since the header might be stale.
+    pub fn chunk_count(&self) -> u16 {
+        self.chunk_count
+    }
+
+    /// Allocate a new chunk from the given data, at the offset expected by `chunk_number`.
+    /// If the read chunk contains valid data, an `Ok(Some(EvtxChunkData))` will be returned.
+    /// If the read chunk contains invalid data (bad magic, bad checksum when `validate_checksum` is set to true),
+    /// of if not enough data can be read (e.g. because we reached EOF), an `Err` is returned.
+    /// If the read chunk is empty, `Ok(None)` will be returned.
+    fn allocate_chunk(
+        data: &mut T,
+        chunks_offset: u64,
+        chunk_number: u16,
+        validate_checksum: bool,
+        repair_header: bool,
+    ) -> Result<Option<EvtxChunkData>> {
+        let mut chunk_data = Vec::with_capacity(EVTX_CHUNK_SIZE);
+        let chunk_offset = chunks_offset + chunk_number as u64 * EVTX_CHUNK_SIZE as u64;
+
+        data.seek(SeekFrom::Start(chunk_offset))?;
+
+        data.take(EVTX_CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk_data)?;
+
+        chunk_from_data(chunk_data, chunk_number, validate_checksum, repair_header)
+    }
+
+    /// Same as `allocate_chunk`, counting the chunk in the stats of the settings and reporting it's progress.
+    fn read_chunk(&mut self, chunk_number: u16) -> Result<Option<EvtxChunkData>> {
+        let config = Arc::clone(&self.config);
+
+        let chunk = config.time(Stage::Read, || {
+            EvtxParser::allocate_chunk(
+                &mut self.data,
+                self.chunks_offset,
+                chunk_number,
+                config.validate_checksums,
+                config.repair_chunk_headers,
+            )
+        });
+
+        // Errors past the end of the file are expected, they are counted only if they reach the caller.
+        if let Ok(Some(_)) = &chunk {
+            config.count(Counter::ChunksRead, 1);
+            config.count(Counter::BytesRead, EVTX_CHUNK_SIZE as u64);
+        }
+
+        config.report_progress(chunk_number, self.chunk_count, &chunk);
+
+        chunk
+    }
+
+    /// Find the next chunk, staring at `chunk_number` (inclusive).
+    /// If a chunk is found, returns the data of the chunk or the relevant error,
+    /// and the number of that chunk.
+    pub fn find_next_chunk(
+        &mut self,
+        mut chunk_number: u16,
+    ) -> Option<(Result<EvtxChunkData>, u16)> {
+        loop {
+            match self.read_chunk(chunk_number) {
+                Err(err) => {
+                    // We try to read past the `chunk_count` to allow for dirty files.
+                    // But if we failed, it means we really are at the end of the file.
+                    // A truncated file ends before `chunk_count`.
+                    if chunk_number >= self.chunk_count
+                        || matches!(err, err::Error::IncompleteChunk { .. })
+                    {
+                        return None;
+                    } else {
+                        return Some((Err(err), chunk_number));
+                    }
+                }
+                Ok(None) => {
+                    // We try to read past the `chunk_count` to allow for dirty files.
+                    // But if we get an empty chunk, we need to keep looking.
+                    // Increment and try again.
+                    chunk_number += 1;
+                }
+                Ok(Some(chunk)) => {
+                    if self.is_outside_time_range(&chunk, chunk_number) {
+                        chunk_number += 1;
+                        continue;
+                    }
+
+                    return Some((Ok(chunk), chunk_number));
+                }
+            };
+        }
+    }
+
+    /// Returns `true` if none of the records of `chunk` can match the time filter of the settings.
+    fn is_outside_time_range(&self, chunk: &EvtxChunkData, chunk_number: u16) -> bool {
+        match chunk.timestamp_bounds() {
+            Some((earliest, latest)) if !self.config.matches_time_bounds(&earliest, &latest) => {
+                debug!("Chunk {} is outside of the time range", chunk_number);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the number of the chunk holding the oldest records of a log which has wrapped around
+    /// (a circular log, overwriting it's oldest chunks once it is full).
+    /// This is the first chunk whose records have lower IDs than the records of the chunk before it.
+    ///
+    /// Returns `None` if the chunks are ordered by their record IDs.
+    pub fn wrap_point(&mut self) -> Option<u16> {
+        let mut previous_last_id = None;
+
+        for chunk_number in 0..self.chunk_count {
+            if let Ok(Some(header)) = self.read_chunk_header(chunk_number) {
+                if previous_last_id.map_or(false, |last_id| header.first_event_record_id < last_id)
+                {
+                    return Some(chunk_number);
+                }
+
+                previous_last_id = Some(header.last_event_record_id);
+            }
+        }
+
+        None
+    }
+
+    /// Returns the numbers of the chunks ordered by their first record ID, see `ParserSettings::order_by_record_id`.
+    /// Empty chunks are left out, and chunks whose header cannot be read are last.
+    fn chunk_numbers_by_record_id(&mut self) -> Vec<u16> {
+        let mut chunks = vec![];
+
+        for chunk_number in 0..self.chunk_count {
+            match self.read_chunk_header(chunk_number) {
+                Ok(Some(header)) => chunks.push((Some(header.first_event_record_id), chunk_number)),
+                Ok(None) => {}
+                Err(_) => chunks.push((None, chunk_number)),
+            }
+        }
+
+        chunks
+            .sort_by_key(|&(first_id, chunk_number)| (first_id.is_none(), first_id, chunk_number));
+        chunks
+            .into_iter()
+            .map(|(_, chunk_number)| chunk_number)
+            .collect()
+    }
+
+    /// Reads the chunks in `order` (skipping empty chunks and chunks outside of the time range).
+    fn next_chunk_in_order(
+        &mut self,
+        order: &mut std::vec::IntoIter<u16>,
+    ) -> Option<Result<EvtxChunkData>> {
+        for chunk_number in order {
+            match self.read_chunk(chunk_number) {
+                Ok(None) => {}
+                Ok(Some(chunk)) if self.is_outside_time_range(&chunk, chunk_number) => {}
+                chunk => return chunk.transpose(),
+            }
+        }
+
+        None
+    }
+
+    /// Reads only the header of the chunk at `chunk_number`.
+    /// If the chunk is empty, `Ok(None)` will be returned.
+    fn read_chunk_header(&mut self, chunk_number: u16) -> Result<Option<EvtxChunkHeader>> {
+        let mut header_data = Vec::with_capacity(EVTX_CHUNK_HEADER_SIZE);
+        let chunk_offset = self.chunks_offset + chunk_number as u64 * EVTX_CHUNK_SIZE as u64;
+
+        self.data.seek(SeekFrom::Start(chunk_offset))?;
+
+        let amount_read = (&mut self.data)
+            .take(EVTX_CHUNK_HEADER_SIZE as u64)
+            .read_to_end(&mut header_data)?;
+
+        ensure!(
+            amount_read == EVTX_CHUNK_HEADER_SIZE,
+            err::IncompleteChunk { chunk_number }
+        );
+
+        if header_data.iter().all(|x| *x == 0) {
+            return Ok(None);
+        }
+
+        EvtxChunkHeader::from_reader(&mut Cursor::new(header_data.as_slice())).map(Some)
+    }
+
+    /// Returns the numbers of the chunks which might contain records with an `EventRecordID` in `start..=end`.
+    ///
+    /// Chunks are usually ordered by their record IDs, in which case only `O(log n)` chunk headers are read.
+    /// If the file has wrapped around (or some chunk headers cannot be read),
+    /// this falls back to reading the headers of all the chunks.
+    fn find_chunks_in_id_range(&mut self, start: u64, end: u64) -> Vec<u16> {
+        if self.chunk_count == 0 || start > end {
+            return vec![];
+        }
+
+        if let Some(chunk_numbers) = self.binary_search_chunks(start, end) {
+            return chunk_numbers.collect();
+        }
+
+        debug!("Chunks are not ordered by record ID, reading all chunk headers");
+
+        (0..self.chunk_count)
+            .filter(|&chunk_number| match self.read_chunk_header(chunk_number) {
+                Ok(Some(header)) => {
+                    header.first_event_record_id <= end && header.last_event_record_id >= start
+                }
+                Ok(None) => false,
+                // The error will be yielded when the chunk itself is read.
+                Err(_) => true,
+            })
+            .collect()
+    }
+
+    /// Binary searches the chunk headers for the chunks which might contain records in `start..=end`.
+    /// Returns `None` if the chunks are not ordered by their record IDs.
+    fn binary_search_chunks(&mut self, start: u64, end: u64) -> Option<Range<u16>> {
+        let chunk_count = self.chunk_count;
+
+        let mut id_bounds = |chunk_number| match self.read_chunk_header(chunk_number) {
+            Ok(Some(header)) => Some((header.first_event_record_id, header.last_event_record_id)),
+            _ => None,
+        };
+
+        let (first_id, _) = id_bounds(0)?;
+        let (_, last_id) = id_bounds(chunk_count - 1)?;
+
+        // The log has wrapped around.
+        if first_id > last_id {
+            return None;
+        }
+
+        // The first chunk ending at or after `start`.
+        let first_chunk = partition_point(0..chunk_count, |n| Some(id_bounds(n)?.1 < start))?;
+        // The first chunk starting after `end`.
+        let end_chunk =
+            partition_point(first_chunk..chunk_count, |n| Some(id_bounds(n)?.0 <= end))?;
+
+        Some(first_chunk..end_chunk)
+    }
+
+    /// Returns a summary of every chunk in the file (including empty and invalid chunks).
+    /// Like `chunks`, this reads past `chunk_count` to allow for dirty files.
+    pub fn chunk_table(&mut self) -> Result<Vec<ChunkTableEntry>> {
+        let mut entries = vec![];
+        let timestamp_order_tolerance = self.config.timestamp_order_tolerance;
+
+        self.for_each_raw_chunk(|chunk_number, offset, chunk_data| {
+            entries.push(match chunk_data {
+                Ok(data) => ChunkTableEntry::from_chunk_data(
+                    chunk_number,
+                    offset,
+                    data,
+                    timestamp_order_tolerance,
+                ),
+                Err(message) => ChunkTableEntry::without_header(
+                    chunk_number,
+                    offset,
+                    ChunkStatus::Invalid(message),
+                ),
+            })
+        })?;
+
+        // Trailing empty chunks past `chunk_count` are just preallocated space.
+        while entries.len() > self.chunk_count as usize
+            && entries.last().map(|e| &e.status) == Some(&ChunkStatus::Empty)
+        {
+            entries.pop();
+        }
+
+        Ok(entries)
+    }
+
+    /// Validates the file header, the checksums of every chunk and the chain of record headers in them,
+    /// without deserializing any record.
+    /// This is much faster than parsing the records, for triaging many files for corruption.
+    pub fn validate(&mut self) -> Result<IntegrityReport> {
+        let mut chunks = vec![];
+
+        self.for_each_raw_chunk(|chunk_number, offset, chunk_data| {
+            chunks.push(match chunk_data {
+                Ok(data) => ChunkIntegrity::from_chunk_data(chunk_number, offset, data),
+                Err(message) => ChunkIntegrity::without_header(
+                    chunk_number,
+                    offset,
+                    ChunkStatus::Invalid(message),
+                ),
+            })
+        })?;
+
+        // Trailing empty chunks past `chunk_count` are just preallocated space.
+        while chunks.len() > self.chunk_count as usize
+            && chunks.last().map(|c| &c.status) == Some(&ChunkStatus::Empty)
+        {
+            chunks.pop();
+        }
+
+        // Parsers created by `from_chunks` have no file header.
+        let file_header = if self.chunks_offset == 0 {
+            None
+        } else {
+            let mut header_data = Vec::with_capacity(EVTX_FILE_HEADER_SIZE);
+            self.data.seek(SeekFrom::Start(0))?;
+            (&mut self.data)
+                .take(EVTX_FILE_HEADER_SIZE as u64)
+                .read_to_end(&mut header_data)?;
+
+            let chunks_found = chunks
+                .iter()
+                .filter(|chunk| chunk.status != ChunkStatus::Empty)
+                .count();
+
+            Some(FileHeaderIntegrity::new(
+                &header_data,
+                self.header.chunk_count,
+                u16::try_from(chunks_found).unwrap_or(u16::MAX),
+            ))
+        };
+
+        let file_size = self.data.seek(SeekFrom::End(0))?;
+        let expected_size =
+            self.chunks_offset + u64::from(self.header.chunk_count) * EVTX_CHUNK_SIZE as u64;
+        let truncated = file_size < expected_size
+            || file_size.saturating_sub(self.chunks_offset) % EVTX_CHUNK_SIZE as u64 != 0;
+
+        Ok(IntegrityReport {
+            file_header,
+            chunks,
+            truncated,
+        })
+    }
+
+    /// Writes a repaired copy of the file to `output`, for files which were not closed properly
+    /// (such as after an abrupt shutdown) and whose headers do not match their records.
+    ///
+    /// The record bounds (up to the last record found in every chunk), free space offsets and checksums of the chunks
+    /// are recomputed, the dirty flags are cleared, and the file header is updated with the chunks written.
+    /// Empty chunks are left out, and so are chunks which cannot be repaired (see `RepairReport::dropped_chunks`).
+    /// Files without a file header (see `from_chunks`) get a new one.
+    pub fn repair<W: Write + Seek>(&mut self, mut output: W) -> Result<RepairReport> {
+        let (mut header, fixed_header_checksum) = if self.chunks_offset == 0 {
+            (evtx_writer::file_header(0, 1), true)
+        } else {
+            let mut header = vec![0; EVTX_FILE_HEADER_SIZE];
+            self.data.seek(SeekFrom::Start(0))?;
+            self.data.read_exact(&mut header)?;
+
+            let integrity = FileHeaderIntegrity::new(&header, self.header.chunk_count, 0);
+            (header, !integrity.checksum_valid)
+        };
+
+        // The file header is written once all the chunks are known.
+        let start = output.stream_position()?;
+        output.write_all(&header)?;
+
+        let mut chunks = vec![];
+        let mut dropped_chunks = vec![];
+        let mut record_ids = vec![];
+        let mut result = Ok(());
+
+        self.for_each_raw_chunk(|chunk_number, _, chunk_data| {
+            let mut data = match chunk_data {
+                _ if result.is_err() => return,
+                Ok(data) if data.iter().all(|b| *b == 0) => return,
+                Ok(data) => data,
+                Err(message) => {
+                    warn!("Dropped chunk {}: {}", chunk_number, message);
+                    let error = err::Error::IncompleteChunk { chunk_number };
+                    dropped_chunks.push(ChunkFailure::new(chunk_number, &error));
+                    return;
+                }
+            };
+
+            let chunk = match repair::repair_chunk(chunk_number, &mut data) {
+                Ok(chunk) => chunk,
+                Err(error) => {
+                    warn!("Dropped chunk {}: {}", chunk_number, error);
+                    dropped_chunks.push(ChunkFailure::new(chunk_number, &error));
+                    return;
+                }
+            };
+
+            if record_ids.len() == usize::from(u16::MAX) {
+                result = err::TooManyChunks.fail();
+                return;
+            }
+
+            result = EvtxChunkHeader::from_reader(&mut Cursor::new(data.as_slice())).and_then(
+                |header| {
+                    output.write_all(&data)?;
+                    record_ids.push((header.first_event_record_id, header.last_event_record_id));
+                    chunks.push(chunk);
+                    Ok(())
+                },
+            );
+        })?;
+        result?;
+
+        let cleared_dirty_flag = repair::repair_file_header(&mut header, &record_ids);
+        let end = output.stream_position()?;
+        output.seek(SeekFrom::Start(start))?;
+        output.write_all(&header)?;
+        output.seek(SeekFrom::Start(end))?;
+        output.flush()?;
+
+        Ok(RepairReport {
+            header_chunk_count: self.header.chunk_count,
+            chunk_count: record_ids.len() as u16,
+            fixed_header_checksum,
+            cleared_dirty_flag,
+            chunks,
+            dropped_chunks,
+        })
+    }
+
+    /// Parses every record of the file (without serializing them), reporting all the chunks and records
+    /// which failed to parse (with their error and offset), along with the integrity report of the file
+    /// and the number of recovered records.
+    ///
+    /// The settings of the parser apply, so recovered records are only counted with `repair_chunk_headers`.
+    pub fn file_report(&mut self) -> Result<FileReport> {
+        let mut report = FileReport::new(self.validate()?);
+        let settings = Arc::clone(&self.config);
+        let mut next_chunk_number = Some(0);
+
+        while let Some((chunk, chunk_number)) =
+            next_chunk_number.and_then(|n| self.find_next_chunk(n))
+        {
+            next_chunk_number = chunk_number.checked_add(1);
+
+            let mut chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    report
+                        .chunk_failures
+                        .push(ChunkFailure::new(chunk_number, &err));
+                    continue;
+                }
+            };
+
+            match chunk.parse(Arc::clone(&settings)) {
+                Ok(mut chunk) => {
+                    let mut records = chunk.iter();
+                    loop {
+                        let offset = records.offset();
+                        match records.next() {
+                            Some(Ok(record)) => {
+                                report.records_ok += 1;
+                                report.records_recovered += u64::from(record.recovered);
+                            }
+                            Some(Err(err)) => report.record_failures.push(RecordFailure::new(
+                                chunk_number,
+                                offset,
+                                &err,
+                            )),
+                            None => break,
+                        }
+                    }
+                }
+                Err(err) => {
+                    report
+                        .chunk_failures
+                        .push(ChunkFailure::new(chunk_number, &err));
+                    continue;
+                }
+            }
+
+            // Records can't outlive the chunk, so it's parsed again for it's slack space.
+            if let Ok(mut chunk) = chunk.parse(Arc::clone(&settings)) {
+                report.slack_records += chunk.iter_slack().filter(Result::is_ok).count() as u64;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Reports missing record ID ranges, duplicated record IDs and timestamp regressions across the whole file,
+    /// from the record headers only (see `record_headers`).
+    ///
+    /// Records whose timestamp is earlier than the timestamp of the previous record ID by more than `tolerance`
+    /// are reported as regressions. Chunks which cannot be read are listed in the report rather than failing it.
+    pub fn tampering_report(&mut self, tolerance: Duration) -> Result<TamperingReport> {
+        let mut headers = vec![];
+        let mut chunk_errors = vec![];
+
+        for header in self.record_headers() {
+            match header {
+                Ok(header) => headers.push(header),
+                Err(e) => chunk_errors.push(e.to_string()),
+            }
+        }
+
+        Ok(TamperingReport::from_record_headers(
+            headers,
+            chunk_errors,
+            tolerance,
+        ))
+    }
+
+    /// Reports the records of security-relevant meta-events (logs cleared, audit policy changed,
+    /// event logging shut down), along with the inconsistencies of the `tampering_report`
+    /// (with the same `tolerance`), ordered by record ID.
+    ///
+    /// Records which fail to parse are skipped.
+    pub fn findings_report(&mut self, tolerance: Duration) -> Result<FindingsReport> {
+        let tampering = self.tampering_report(tolerance)?;
+
+        let mut findings = vec![];
+        for finding in self.serialized_records(|record| record.map(|r| Finding::of_record(&r))) {
+            match finding {
+                Ok(Some(finding)) => findings.push(finding),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to parse record: {}", e),
+            }
+        }
+
+        Ok(FindingsReport::new(findings, tampering))
+    }
+
+    /// Reads every chunk of the file (past `chunk_count`, to allow for dirty files) until the end of the file,
+    /// passing it's number, offset and data to `visit`.
+    /// An incomplete chunk is passed as an error, if the file header says it should be there.
+    fn for_each_raw_chunk(
+        &mut self,
+        mut visit: impl FnMut(u16, u64, std::result::Result<Vec<u8>, String>),
+    ) -> Result<()> {
+        for chunk_number in 0..=u16::MAX {
+            let offset = self.chunks_offset + chunk_number as u64 * EVTX_CHUNK_SIZE as u64;
+            let mut chunk_data = Vec::with_capacity(EVTX_CHUNK_SIZE);
+
+            self.data.seek(SeekFrom::Start(offset))?;
+            let amount_read = (&mut self.data)
+                .take(EVTX_CHUNK_SIZE as u64)
+                .read_to_end(&mut chunk_data)?;
+
+            if amount_read == EVTX_CHUNK_SIZE {
+                visit(chunk_number, offset, Ok(chunk_data));
+                continue;
+            }
+
+            // A missing chunk is only an error when the file header says it should be there.
+            if chunk_number < self.chunk_count {
+                visit(
+                    chunk_number,
+                    offset,
+                    Err(format!(
+                        "Incomplete chunk, only {} bytes could be read",
+                        amount_read
+                    )),
+                );
+            }
+
+            break;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the table returned by `chunk_table` to `writer`, as CSV or JSON.
+    pub fn export_chunk_table<W: Write>(
+        &mut self,
+        writer: W,
+        format: ChunkTableFormat,
+    ) -> Result<()> {
+        let entries = self.chunk_table()?;
+
+        write_chunk_table(&entries, writer, format)
+    }
+
+    /// Return an iterator over all the chunks.
+    /// Each chunk supports iterating over it's records in their un-serialized state
+    /// (before they are converted to XML or JSON).
+    ///
+    /// Chunks are yielded in their order in the file (or by their record ids, see `ParserSettings::order_by_record_id`),
+    /// reading past `chunk_count` to allow for dirty files. Empty chunks, and chunks outside of the time range
+    /// of the settings, are skipped. Chunks which fail to be read are yielded as errors, and iteration continues
+    /// with the next chunk.
+    ///
+    /// The number of each chunk, the bounds of it's record ids and the validity of it's checksums
+    /// are available from `EvtxChunkData::info`, for consumers scheduling, caching or carving chunks themselves.
+    pub fn chunks(&mut self) -> IterChunks<T> {
+        let order = self.chunk_order();
+
+        IterChunks {
+            parser: self,
+            current_chunk_number: 0,
+            order,
+        }
+    }
+
+    /// The order of the chunks read by `chunks`, `None` for their physical order.
+    fn chunk_order(&mut self) -> Option<std::vec::IntoIter<u16>> {
+        if self.config.order_by_record_id {
+            Some(self.chunk_numbers_by_record_id().into_iter())
+        } else {
+            None
+        }
+    }
+
+    /// Consumes the parser, returning an iterator over all the chunks.
+    /// Each chunk supports iterating over it's records in their un-serialized state
+    /// (before they are converted to XML or JSON).
+    pub fn into_chunks(mut self) -> IntoIterChunks<T> {
+        let order = self.chunk_order();
+
+        IntoIterChunks {
+            parser: self,
+            current_chunk_number: 0,
+            order,
+        }
+    }
+    /// Return an iterator over all the records.
+    /// Records will be mapped `f`, which must produce owned data from the records.
+    pub fn serialized_records<U: Send + 'static>(
+        &mut self,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+    ) -> impl Iterator<Item = Result<U>> + '_ {
+        // Retrieve parser settings here, while `self` is immutably borrowed.
+        let chunk_settings = Arc::clone(&self.config);
+
+        // `self` is mutably borrowed from here on.
+        Self::serialize_chunks(self.chunks(), chunk_settings, f)
+    }
+
+    /// Serializes the records of `chunks` using `f`, see `OrderedRecords`.
+    fn serialize_chunks<'a, U: Send + 'static>(
+        chunks: impl Iterator<Item = Result<EvtxChunkData>> + 'a,
+        chunk_settings: Arc<ParserSettings>,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+    ) -> impl Iterator<Item = Result<U>> + 'a {
+        let mut sampler = Sampler::new(&chunk_settings);
+
+        OrderedRecords::new(chunks, chunk_settings, f).filter(move |record| sampler.keep(record))
+    }
+
+    /// Return an iterator over the headers of all the records (their ID, timestamp and size),
+    /// without deserializing the records.
+    ///
+    /// This is much faster than `records`, and useful for inventories or building indexes of record IDs.
+    /// Only the record ID and time filters of the settings apply to the headers.
+    pub fn record_headers(&mut self) -> impl Iterator<Item = Result<EvtxRecordHeader>> + '_ {
+        let settings = Arc::clone(&self.config);
+        let mut sampler = Sampler::new(&settings);
+
+        self.chunks()
+            .flat_map(|chunk| match chunk {
+                Ok(chunk) => chunk.record_headers().into_iter().map(Ok).collect(),
+                Err(err) => vec![Err(err)],
+            })
+            .filter(move |header| match header {
+                Ok(header) => {
+                    settings.matches_record_id(header.event_record_id)
+                        && settings.matches_timestamp(&header.timestamp)
+                }
+                Err(_) => true,
+            })
+            .filter(move |header| sampler.keep(header))
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will be XML-formatted.
+    pub fn records(&mut self) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        // '_ is required in the signature because the iterator is bound to &self.
+        self.serialized_records(|record| record.and_then(|record| record.into_xml()))
+    }
+
+    /// Return an iterator over the records with an `EventRecordID` in `start..=end`.
+    /// Records will be XML-formatted.
+    ///
+    /// Unlike filtering the output of `records`, only the chunks which might contain these records are read.
+    /// They are found by searching the `first_event_record_id`/`last_event_record_id` of the chunk headers.
+    pub fn records_in_id_range(
+        &mut self,
+        start: u64,
+        end: u64,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_records_in_id_range(start, end, |record| {
+            record.and_then(|record| record.into_xml())
+        })
+    }
+
+    /// Same as `records_in_id_range`, but records will be mapped using `f` (see `serialized_records`).
+    pub fn serialized_records_in_id_range<'a, U: Send + 'static>(
+        &'a mut self,
+        start: u64,
+        end: u64,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+    ) -> impl Iterator<Item = Result<U>> + 'a {
+        let chunk_numbers = self.find_chunks_in_id_range(start, end);
+        debug!(
+            "Records {}..={} might be in chunks {:?}",
+            start, end, chunk_numbers
+        );
+
+        let chunk_settings = Arc::new(ParserSettings {
+            record_ids: Some(start..=end),
+            ..(*self.config).clone()
+        });
+        let chunks = chunk_numbers
+            .into_iter()
+            .filter_map(move |chunk_number| self.read_chunk(chunk_number).transpose());
+
+        Self::serialize_chunks(chunks, chunk_settings, f)
+    }
+
+    /// Return an iterator over the records of the chunk `chunk_number` (the first chunk being `0`).
+    /// Records will be XML-formatted.
+    ///
+    /// An empty chunk has no records, and a chunk which cannot be read yields a single error.
+    pub fn records_in_chunk(
+        &mut self,
+        chunk_number: u16,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_records_in_chunk(chunk_number, |record| {
+            record.and_then(|record| record.into_xml())
+        })
+    }
+
+    /// Same as `records_in_chunk`, but records will be mapped using `f` (see `serialized_records`).
+    pub fn serialized_records_in_chunk<'a, U: Send + 'static>(
+        &'a mut self,
+        chunk_number: u16,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+    ) -> impl Iterator<Item = Result<U>> + 'a {
+        let chunk = self.read_chunk(chunk_number).transpose();
+
+        Self::serialize_chunks(chunk.into_iter(), Arc::clone(&self.config), f)
+    }
+
+    /// Returns the record with `record_id`, XML-formatted, or `None` if no chunk holds it.
+    ///
+    /// Only the chunk holding the record is parsed, see `records_in_id_range`.
+    pub fn record_by_id(&mut self, record_id: u64) -> Result<Option<SerializedEvtxRecord<String>>> {
+        self.serialized_record_by_id(record_id, |record| {
+            record.and_then(|record| record.into_xml())
+        })
+    }
+
+    /// Same as `record_by_id`, but the record will be mapped using `f` (see `serialized_records`).
+    ///
+    /// An error is only returned if the record was not found in any other chunk.
+    pub fn serialized_record_by_id<U: Send + 'static>(
+        &mut self,
+        record_id: u64,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+    ) -> Result<Option<U>> {
+        let mut first_error = None;
+
+        for record in self.serialized_records_in_id_range(record_id, record_id, f) {
+            match record {
+                Ok(record) => return Ok(Some(record)),
+                Err(err) => {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(None),
+        }
+    }
+
+    /// Return an iterator over the last `n` records (the ones with the highest `EventRecordID`s)
+    /// kept by the filters of the settings. Records will be XML-formatted.
+    ///
+    /// The chunks are read from the one holding the highest record IDs backwards, until `n` records are found.
+    pub fn tail(&mut self, n: u64) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> {
+        self.serialized_tail(n, |record| record.and_then(|record| record.into_xml()))
+    }
+
+    /// Same as `tail`, but records will be mapped using `f` (see `serialized_records`).
+    ///
+    /// Errors of the chunks which were read are yielded along with the records, in their position.
+    pub fn serialized_tail<U: Send + 'static>(
+        &mut self,
+        n: u64,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+    ) -> impl Iterator<Item = Result<U>> {
+        // Chunks are ordered by their record IDs rather than by their position, which differ once the log wraps around.
+        let mut chunk_numbers: Vec<(u64, u16)> = (0..self.chunk_count)
+            .filter_map(|chunk_number| match self.read_chunk_header(chunk_number) {
+                Ok(Some(header)) => Some((header.last_event_record_id, chunk_number)),
+                _ => None,
+            })
+            .collect();
+        chunk_numbers.sort_unstable();
+
+        // The records of the chunks read so far, the last chunk first.
+        let mut chunks: Vec<Vec<Result<U>>> = vec![];
+        let mut found = 0;
+
+        for (_, chunk_number) in chunk_numbers.into_iter().rev() {
+            if found >= n {
+                break;
+            }
+
+            // Records are sampled once the tail is found.
+            let chunk = self.read_chunk(chunk_number).transpose();
+            let records: Vec<Result<U>> =
+                OrderedRecords::new(chunk.into_iter(), Arc::clone(&self.config), f.clone())
+                    .collect();
+            found += records.iter().filter(|record| record.is_ok()).count() as u64;
+            chunks.push(records);
+        }
+
+        // Only the last `n` records (and the errors following the first of them) are kept.
+        let mut tail: Vec<Result<U>> = chunks.into_iter().rev().flatten().collect();
+        let mut excess = found.saturating_sub(n);
+        let first = tail
+            .iter()
+            .position(|record| {
+                if record.is_ok() {
+                    if excess == 0 {
+                        return true;
+                    }
+                    excess -= 1;
+                }
+                false
+            })
+            .unwrap_or(tail.len());
+
+        tail.drain(..first);
+
+        let mut sampler = Sampler::new(&self.config);
+        tail.into_iter().filter(move |record| sampler.keep(record))
+    }
+
+    /// Returns the highest `EventRecordID` of the file, read from the chunk headers.
+    ///
+    /// Unless the log has wrapped around, this is the last record of the last (valid) chunk,
+    /// which is found by walking the chunks backwards.
+    pub fn last_record_id(&mut self) -> Option<u64> {
+        let chunk_count = self.chunk_count;
+        let first_id = match self.read_chunk_header(0) {
+            Ok(Some(header)) => header.first_event_record_id,
+            _ => 0,
+        };
+
+        let mut last_id = None;
+
+        for chunk_number in (0..chunk_count).rev() {
+            if let Ok(Some(header)) = self.read_chunk_header(chunk_number) {
+                last_id = max(last_id, Some(header.last_event_record_id));
+
+                // Records before this chunk have lower IDs, unless the log has wrapped around.
+                if header.last_event_record_id >= first_id {
+                    break;
+                }
+            }
+        }
+
+        last_id
+    }
+
+    /// Returns a checkpoint after the record with `record_id`, for resuming parsing with `records_since`.
+    /// Returns `None` if the file does not hold the record.
+    ///
+    /// This should be the last record handled by the caller (rather than `last_record_id`),
+    /// since records might be appended to a live file while it is being parsed.
+    pub fn checkpoint(&mut self, record_id: u64) -> Option<Checkpoint> {
+        let (last_chunk_number, file_signature) = self.find_record_checksum(record_id, None)?;
+
+        Some(Checkpoint {
+            file_signature,
+            last_chunk_number,
+            last_record_id: record_id,
+        })
+    }
+
+    /// Checks if the file still holds the last record of `checkpoint`.
+    pub fn checkpoint_status(&mut self, checkpoint: &Checkpoint) -> CheckpointStatus {
+        match self.find_record_checksum(
+            checkpoint.last_record_id,
+            Some(checkpoint.last_chunk_number),
+        ) {
+            Some((_, checksum)) if checksum == checkpoint.file_signature => CheckpointStatus::Valid,
+            Some(_) => CheckpointStatus::Replaced,
+            None => match self.oldest_chunk() {
+                Some((_, first_record_id)) if first_record_id > checkpoint.last_record_id => {
+                    CheckpointStatus::Overwritten
+                }
+                _ => CheckpointStatus::Replaced,
+            },
+        }
+    }
+
+    /// Returns the number of the chunk holding the oldest record, and the ID of that record.
+    fn oldest_chunk(&mut self) -> Option<(u16, u64)> {
+        (0..self.chunk_count)
+            .filter_map(|chunk_number| {
+                let header = self.read_chunk_header(chunk_number).ok()??;
+                Some((chunk_number, header.first_event_record_id))
+            })
+            .min_by_key(|&(_, first_record_id)| first_record_id)
+    }
+
+    /// Returns the exact bytes of the record with `record_id` in the file (it's header and BinXML),
+    /// or `None` if no chunk holds it. The record is not deserialized.
+    ///
+    /// Useful for exporting samples of problematic records, or building fuzzing corpora.
+    pub fn extract_record_bytes(&mut self, record_id: u64) -> Option<Vec<u8>> {
+        self.find_record_data(record_id, None)
+            .map(|(_, record)| record)
+    }
+
+    /// Finds the record with `record_id`, returning the number of it's chunk and the CRC32 of the record.
+    /// The chunk at `hint` is searched first.
+    fn find_record_checksum(&mut self, record_id: u64, hint: Option<u16>) -> Option<(u16, u32)> {
+        self.find_record_data(record_id, hint)
+            .map(|(chunk_number, record)| (chunk_number, crc32fast::hash(&record)))
+    }
+
+    /// Finds the record with `record_id`, returning the number of it's chunk and the data of the record.
+    /// The chunk at `hint` is searched first.
+    fn find_record_data(&mut self, record_id: u64, hint: Option<u16>) -> Option<(u16, Vec<u8>)> {
+        let chunk_count = self.chunk_count;
+        let candidates = hint
+            .into_iter()
+            .chain(self.find_chunks_in_id_range(record_id, record_id))
+            // The header of a live file might not count the chunks which were added last.
+            .chain(chunk_count..u16::MAX);
+
+        for chunk_number in candidates {
+            match EvtxParser::allocate_chunk(
+                &mut self.data,
+                self.chunks_offset,
+                chunk_number,
+                false,
+                false,
+            ) {
+                Ok(Some(chunk)) => {
+                    if let Some(record) = chunk.record_data(record_id) {
+                        return Some((chunk_number, record.to_vec()));
+                    }
+                }
+                Ok(None) => {}
+                // We are past the end of the file.
+                Err(_) if chunk_number >= chunk_count => break,
+                Err(_) => {}
+            }
+        }
+
+        None
+    }
+
+    /// Return an iterator over the records added to the file after `checkpoint`
+    /// (or all the records, if the file was replaced, see `checkpoint_status`).
+    /// Records will be XML-formatted.
+    ///
+    /// Chunks are read from the one of the checkpoint (or the one of the oldest record, if the checkpoint
+    /// was overwritten), wrapping around to the first chunk. Records up to the one of the checkpoint
+    /// are skipped before they are deserialized.
+    pub fn records_since(
+        &mut self,
+        checkpoint: &Checkpoint,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_records_since(checkpoint, |record| {
+            record.and_then(|record| record.into_xml())
+        })
+    }
+
+    /// Same as `records_since`, but records will be mapped using `f` (see `serialized_records`).
+    pub fn serialized_records_since<'a, U: Send + 'static>(
+        &'a mut self,
+        checkpoint: &Checkpoint,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+    ) -> impl Iterator<Item = Result<U>> + 'a {
+        let status = self.checkpoint_status(checkpoint);
+        debug!("Resuming from {:?} ({:?})", checkpoint, status);
+
+        let after_checkpoint = Some(checkpoint.last_record_id.saturating_add(1)..=u64::MAX);
+
+        let (first_chunk_number, record_ids) = match status {
+            CheckpointStatus::Valid => (checkpoint.last_chunk_number, after_checkpoint),
+            // The chunk of the checkpoint now holds newer records.
+            CheckpointStatus::Overwritten => (
+                self.oldest_chunk()
+                    .map_or(0, |(chunk_number, _)| chunk_number),
+                after_checkpoint,
+            ),
+            CheckpointStatus::Replaced => (0, self.config.record_ids.clone()),
+        };
+
+        let chunk_settings = Arc::new(ParserSettings {
+            record_ids,
+            ..(*self.config).clone()
+        });
+
+        // Chunks from the one of the checkpoint to the end of the file, then from the start of the file.
+        let mut next_chunk_number = first_chunk_number;
+        let mut wrapped = false;
+
+        let chunks = std::iter::from_fn(move || loop {
+            match self.find_next_chunk(next_chunk_number) {
+                Some((chunk, chunk_number)) if !wrapped || chunk_number < first_chunk_number => {
+                    next_chunk_number = chunk_number.checked_add(1)?;
+                    return Some(chunk);
+                }
+                _ if !wrapped && first_chunk_number > 0 => {
+                    wrapped = true;
+                    next_chunk_number = 0;
+                }
+                _ => return None,
+            }
+        });
+
+        Self::serialize_chunks(chunks, chunk_settings, f)
+    }
+
+    /// Return an iterator over the records written at or after `timestamp`.
+    /// Records will be XML-formatted.
+    ///
+    /// Iteration starts at the first chunk holding such records, found by binary searching the timestamps
+    /// of the first and last records of the chunks, instead of scanning from the start of the file.
+    /// If the log has wrapped around, all the chunks are scanned.
+    pub fn records_from(
+        &mut self,
+        timestamp: DateTime<Utc>,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_records_from(timestamp, |record| {
+            record.and_then(|record| record.into_xml())
+        })
+    }
+
+    /// Same as `records_from`, but records will be mapped using `f` (see `serialized_records`).
+    pub fn serialized_records_from<'a, U: Send + 'static>(
+        &'a mut self,
+        timestamp: DateTime<Utc>,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+    ) -> impl Iterator<Item = Result<U>> + 'a {
+        let first_chunk_number = self.find_first_chunk_from(timestamp).unwrap_or(0);
+        debug!(
+            "Records from {} start in chunk {}",
+            timestamp, first_chunk_number
+        );
+
+        let chunk_settings = Arc::new(ParserSettings {
+            start_time: Some(
+                self.config
+                    .start_time
+                    .map_or(timestamp, |start| start.max(timestamp)),
+            ),
+            ..(*self.config).clone()
+        });
+
+        let mut next_chunk_number = first_chunk_number;
+        let chunks = std::iter::from_fn(move || {
+            let (chunk, chunk_number) = self.find_next_chunk(next_chunk_number)?;
+            next_chunk_number = chunk_number.checked_add(1)?;
+            Some(chunk)
+        });
+
+        Self::serialize_chunks(chunks, chunk_settings, f)
+    }
+
+    /// Binary searches the chunks for the first one whose last record was written at or after `timestamp`.
+    /// Returns `None` if the chunks are not ordered by time (or the timestamps of some chunks cannot be read).
+    fn find_first_chunk_from(&mut self, timestamp: DateTime<Utc>) -> Option<u16> {
+        let chunk_count = self.chunk_count;
+
+        if chunk_count == 0 {
+            return None;
+        }
+
+        let mut timestamp_bounds = |chunk_number| match EvtxParser::allocate_chunk(
+            &mut self.data,
+            self.chunks_offset,
+            chunk_number,
+            false,
+            false,
+        ) {
+            Ok(Some(chunk)) => chunk.timestamp_bounds(),
+            _ => None,
+        };
+
+        let (first_timestamp, _) = timestamp_bounds(0)?;
+        let (_, last_timestamp) = timestamp_bounds(chunk_count - 1)?;
+
+        // The log has wrapped around.
+        if first_timestamp > last_timestamp {
+            return None;
+        }
+
+        partition_point(0..chunk_count, |n| Some(timestamp_bounds(n)?.1 < timestamp))
+    }
+
+    /// Return an iterator over the records recovered from the slack space of the chunks (see `EvtxChunk::iter_slack`),
+    /// such as deleted or partially overwritten records, which are never returned by `records`.
+    /// Records will be XML-formatted.
+    pub fn slack_records(
+        &mut self,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_slack_records(|record| record.and_then(|record| record.into_xml()))
+    }
+
+    /// Same as `slack_records`, but records will be mapped using `f` (see `serialized_records`).
+    /// Chunks are parsed serially, since their slack space is usually small.
+    pub fn serialized_slack_records<'a, U: 'a>(
+        &'a mut self,
+        mut f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + 'a,
+    ) -> impl Iterator<Item = Result<U>> + 'a {
+        let settings = Arc::clone(&self.config);
+
+        self.chunks().flat_map(move |chunk| {
+            let records = chunk.and_then(|mut chunk| {
+                let mut chunk = chunk.parse(Arc::clone(&settings))?;
+                Ok(chunk.iter_slack().map(&mut f).collect::<Vec<_>>())
+            });
+
+            match records {
+                Ok(records) => records,
+                Err(err) => vec![Err(err)],
+            }
+        })
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will be JSON-formatted.
+    pub fn records_json(
+        &mut self,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_records(|record| record.and_then(|record| record.into_json()))
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will have a `serde_json::Value` data attribute.
+    pub fn records_json_value(
+        &mut self,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<serde_json::Value>>> + '_ {
+        self.serialized_records(|record| record.and_then(|record| record.into_json_value()))
+    }
+
+    /// Calls `f` with every record, on the calling thread.
+    ///
+    /// Records borrow the data of their chunk only for the duration of the call, so nothing is copied
+    /// (unlike `serialized_records`, which requires mapping the records to owned data).
+    /// Chunks which fail to be read or parsed are passed to `f` as errors.
+    pub fn for_each_record(&mut self, mut f: impl FnMut(Result<EvtxRecord<'_>>)) {
+        let settings = Arc::clone(&self.config);
+
+        for chunk in self.chunks() {
+            let mut chunk_error = None;
+
+            serialize_chunk(
+                chunk,
+                Arc::clone(&settings),
+                |record| {
+                    f(record);
+                    Ok(())
+                },
+                |result| {
+                    // Records are always mapped to `Ok`, only the error of the chunk itself is left.
+                    if let Err(err) = result {
+                        chunk_error = Some(err);
+                    }
+                    true
+                },
+            );
+
+            if let Some(err) = chunk_error {
+                f(Err(err));
+            }
+        }
+    }
+
+    /// Return an iterator over all the records, which do not borrow their chunks (see `EvtxRecord::into_owned`).
+    ///
+    /// Unlike the records of `EvtxChunk::iter`, these can be sent to other threads before being rendered.
+    pub fn owned_records(&mut self) -> impl Iterator<Item = Result<EvtxRecordOwned>> + '_ {
+        self.serialized_records(|record| record.map(EvtxRecord::into_owned))
+    }
+
+    /// Copies the records to `writer`, producing an EVTX file with only the records matching
+    /// the filters of the parser settings (records which failed to parse are skipped).
+    /// Returns the number of records copied, the file is complete once `EvtxWriter::finish` is called.
+    ///
+    /// ```rust
+    /// # use evtx::{EvtxParser, EvtxWriter, ParserSettings};
+    /// # use std::io::Cursor;
+    /// # let path = concat!(env!("CARGO_MANIFEST_DIR"), "/samples/security.evtx");
+    /// let mut parser = EvtxParser::from_path(path)
+    ///     .unwrap()
+    ///     .with_configuration(ParserSettings::new().event_id_filter(&[4624]));
+    ///
+    /// let mut writer = EvtxWriter::new(Cursor::new(vec![])).unwrap();
+    /// let copied = parser.rewrite(&mut writer).unwrap();
+    /// let logons = writer.finish().unwrap().into_inner();
+    ///
+    /// let mut parser = EvtxParser::from_buffer(logons).unwrap();
+    /// assert_eq!(parser.records().count() as u64, copied);
+    /// ```
+    pub fn rewrite<W: Write + Seek>(&mut self, writer: &mut EvtxWriter<W>) -> Result<u64> {
+        writer.copy_records(self.owned_records())
+    }
+
+    /// Splits the file into smaller EVTX files in `dir` (which must exist), by day, size or record count.
+    /// Only the records matching the filters of the parser settings are written (keeping their record IDs),
+    /// records which failed to parse are skipped. Returns the files written, see `SplitBy` for their names.
+    pub fn split(
+        &mut self,
+        split_by: SplitBy,
+        dir: impl AsRef<Path>,
+        prefix: &str,
+    ) -> Result<Vec<SplitPart>> {
+        split::split_records(self.owned_records(), split_by, dir.as_ref(), prefix)
+    }
+
+    /// Return an iterator over the sizes of all the records.
+    /// Records are rendered using `format` (and the parser settings) to measure their rendered size.
+    pub fn record_sizes(
+        &mut self,
+        format: RenderFormat,
+    ) -> impl Iterator<Item = Result<RecordSize>> + '_ {
+        self.serialized_records(move |record| {
+            record.and_then(|record| RecordSize::measure(record, format))
+        })
+    }
+
+    /// Returns the `n` largest records (by rendered size), largest first.
+    /// Records which fail to render are skipped.
+    pub fn largest_records(&mut self, n: usize, format: RenderFormat) -> Vec<RecordSize> {
+        let sizes = self.record_sizes(format).filter_map(|size| match size {
+            Ok(size) => Some(size),
+            Err(e) => {
+                warn!("Failed to measure record: {}", e);
+                None
+            }
+        });
+
+        record_size::largest(sizes, n)
+    }
+
+    /// Counts the records (matching the filters of the parser settings) in every group of `group_by`.
+    ///
+    /// Records are not serialized, only the grouped value is read from them.
+    /// Records which fail to parse are counted in `Histogram::errors`.
+    pub fn aggregate(&mut self, group_by: GroupBy) -> Histogram {
+        let keys =
+            self.serialized_records(move |record| record.map(|r| GroupKey::of(&r, group_by)));
+
+        let mut histogram = Histogram::new(group_by);
+
+        for key in keys {
+            match key {
+                Ok(key) => histogram.add(key),
+                Err(e) => {
+                    warn!("Failed to parse record: {}", e);
+                    histogram.errors += 1;
+                }
+            }
+        }
+
+        histogram
+    }
+
+    /// Performs a stacking analysis of the records: counts by provider, `EventID` and hour,
+    /// and the `top` most common and rarest values of the `EventData` fields `stacked_fields`,
+    /// with the first and last time every group was seen.
+    ///
+    /// Records are not serialized, only the analyzed values are read from them.
+    /// Records which fail to parse are counted in `AnalysisReport::errors`.
+    pub fn analyze(&mut self, stacked_fields: &[&str], top: usize) -> AnalysisReport {
+        let stacked_fields: Vec<String> = stacked_fields.iter().map(|f| f.to_string()).collect();
+        let mut report = AnalysisReport::new(&stacked_fields, top);
+
+        let summaries = self.serialized_records(move |record| {
+            record.map(|r| RecordSummary::of(&r, &stacked_fields))
+        });
+
+        for summary in summaries {
+            match summary {
+                Ok(summary) => report.add(summary),
+                Err(e) => {
+                    warn!("Failed to parse record: {}", e);
+                    report.errors += 1;
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Groups the records by activity: records sharing an `ActivityID`, or linked by a `RelatedActivityID`,
+    /// end up in the same group (for example, all the records of an RDP session).
+    ///
+    /// Groups are ordered by their first record, and records without an `ActivityID` are not grouped.
+    /// Records will have a `serde_json::Value` data attribute, records which fail to render are skipped.
+    pub fn activity_groups(&mut self) -> Vec<ActivityGroup<serde_json::Value>> {
+        let records = self.serialized_records(|record| {
+            record.and_then(|record| {
+                let activity_id = record.activity_id().map(|id| normalize_activity_id(&id));
+                let related_activity_id = record
+                    .related_activity_id()
+                    .map(|id| normalize_activity_id(&id));
+
+                Ok((activity_id, related_activity_id, record.into_json_value()?))
+            })
+        });
+
+        let mut grouper = ActivityGrouper::new();
+
+        for record in records {
+            match record {
+                Ok((Some(activity_id), related_activity_id, record)) => {
+                    grouper.add(activity_id, related_activity_id, record)
+                }
+                Ok((None, _, _)) => {}
+                Err(e) => warn!("Failed to render record: {}", e),
+            }
+        }
+
+        grouper.into_groups()
+    }
+}
+
+/// Returns the first number in `range` for which `pred` is false,
+/// assuming `pred` is true for some prefix of the range and false for the rest of it.
+/// Returns `None` as soon as `pred` does.
+fn partition_point(range: Range<u16>, mut pred: impl FnMut(u16) -> Option<bool>) -> Option<u16> {
+    let (mut low, mut high) = (range.start, range.end);
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+
+        if pred(mid)? {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    Some(low)
+}
+
+impl<T: ReadSeek + Send + 'static> EvtxParser<T> {
+    /// Consumes the parser, returning an iterator over all the chunks,
+    /// which are read (and have their checksums validated) on a background thread.
+    ///
+    /// The next chunk is read while the current one is being deserialized, which overlaps I/O with CPU work
+    /// when iterating serially over slow storage (spinning disks, network mounts).
+    pub fn prefetch_chunks(self) -> PrefetchChunks {
+        PrefetchChunks::new(self.into_chunks())
+    }
+
+    /// Consumes the parser, returning an iterator over all the records, with chunks read by `prefetch_chunks`.
+    /// Records will be mapped `f`, see `serialized_records`.
+    pub fn into_prefetched_serialized_records<U: Send + 'static>(
+        self,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+    ) -> impl Iterator<Item = Result<U>> {
+        let chunk_settings = Arc::clone(&self.config);
+
+        Self::serialize_chunks(self.prefetch_chunks(), chunk_settings, f)
+    }
+
+    /// Consumes the parser, returning an iterator over all the records, with chunks read by `prefetch_chunks`.
+    /// Records will be XML-formatted.
+    pub fn into_prefetched_records(
+        self,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> {
+        self.into_prefetched_serialized_records(|record| {
+            record.and_then(|record| record.into_xml())
+        })
+    }
+
+    /// Consumes the parser, returning an iterator over all the records, with chunks read by `prefetch_chunks`.
+    /// Records will be JSON-formatted.
+    pub fn into_prefetched_records_json(
+        self,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> {
+        self.into_prefetched_serialized_records(|record| {
+            record.and_then(|record| record.into_json())
+        })
+    }
+}
+
+pub struct IterChunks<'c, T: ReadSeek> {
+    parser: &'c mut EvtxParser<T>,
+    current_chunk_number: u16,
+    /// The chunks left to read, when they are not read in their physical order.
+    order: Option<std::vec::IntoIter<u16>>,
+}
+
+impl<'c, T: ReadSeek> Iterator for IterChunks<'c, T> {
+    type Item = Result<EvtxChunkData>;
+    fn next(&mut self) -> Option<<Self as Iterator>::Item> {
+        if let Some(order) = self.order.as_mut() {
+            return self.parser.next_chunk_in_order(order);
+        }
+
+        match self.parser.find_next_chunk(self.current_chunk_number) {
+            None => None,
+            Some((chunk, chunk_number)) => {
+                self.current_chunk_number = chunk_number + 1;
+
+                Some(chunk)
+            }
+        }
+    }
+}
+
+/// Creates a chunk from the data read at the offset of `chunk_number`,
+/// `None` if the chunk is empty (which happens in the middle of dirty files).
+/// Counts the chunks of `data` (starting at `chunks_offset`), up to the last one starting with `ElfChnk` magic.
+fn count_chunks<T: ReadSeek>(data: &mut T, chunks_offset: u64) -> Result<u16> {
+    let mut chunk_count = 0;
+
+    for chunk_number in 0..u16::MAX {
+        let mut magic = Vec::with_capacity(8);
+        data.seek(SeekFrom::Start(
+            chunks_offset + u64::from(chunk_number) * EVTX_CHUNK_SIZE as u64,
+        ))?;
+
+        if (&mut *data).take(8).read_to_end(&mut magic)? < 8 {
+            break;
+        }
+
+        if magic == b"ElfChnk\x00" {
+            chunk_count = chunk_number + 1;
+        }
+    }
+
+    Ok(chunk_count)
+}
+
+/// Creates the chunk `chunk_number` from `chunk_data`, `None` if the chunk is empty.
+///
+/// A chunk cut off by the end of the file (common with live acquisitions) is padded with zeros,
+/// so that it's complete records can still be parsed. It's checksums are not validated, since they can't match.
+pub(crate) fn chunk_from_data(
+    mut chunk_data: Vec<u8>,
+    chunk_number: u16,
+    mut validate_checksum: bool,
+    repair_header: bool,
+) -> Result<Option<EvtxChunkData>> {
+    ensure!(
+        !chunk_data.is_empty(),
+        err::IncompleteChunk { chunk_number }
+    );
+
+    if chunk_data.len() < EVTX_CHUNK_SIZE {
+        warn!(
+            "Chunk {} is truncated ({} bytes), parsing it's complete records",
+            chunk_number,
+            chunk_data.len()
+        );
+        chunk_data.resize(EVTX_CHUNK_SIZE, 0);
+        validate_checksum = false;
+    }
+
+    if chunk_data.iter().all(|x| *x == 0) {
+        return Ok(None);
+    }
+
+    let chunk = if repair_header {
+        EvtxChunkData::new_with_repair(chunk_data, validate_checksum)?
+    } else {
+        EvtxChunkData::new(chunk_data, validate_checksum)?
+    };
+
+    Ok(Some(chunk.with_chunk_number(chunk_number)))
+}
+
+/// Parses `chunk`, passing it's records mapped by `f` to `emit` (until it returns `false`).
+pub(crate) fn serialize_chunk<U>(
+    chunk: Result<EvtxChunkData>,
+    settings: Arc<ParserSettings>,
+    mut f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U>,
+    mut emit: impl FnMut(Result<U>) -> bool,
+) {
+    let stats = Arc::clone(&settings);
+
+    match chunk.and_then(|mut chunk| {
+        let mut chunk_records = stats.time(Stage::Parse, || chunk.parse(settings))?;
+        stats.count(Counter::ChunksParsed, 1);
+
+        let mut records = chunk_records.iter();
+
+        while let Some(record) = stats.time(Stage::Deserialize, || records.next()) {
+            let record = stats.time(Stage::Serialize, || f(record));
+
+            match record {
+                Ok(_) => stats.count(Counter::RecordsOk, 1),
+                Err(_) => stats.count(Counter::RecordsFailed, 1),
+            }
+
+            // Stop serializing if nobody is waiting for the records.
+            if !emit(record) {
+                break;
+            }
+        }
+
+        Ok(())
+    }) {
+        Ok(()) => {}
+        Err(err) => {
+            stats.count(Counter::ChunksFailed, 1);
+            emit(Err(err));
+        }
+    }
+}
+
+/// Number of serialized records a worker can send ahead of the caller, for every chunk in flight.
+#[cfg(feature = "multithreading")]
+const RECORDS_BUFFERED_PER_CHUNK: usize = 64;
+
+/// The records of a chunk, in order.
+enum ChunkRecords<U> {
+    /// Serialized on the calling thread.
+    Serialized(std::vec::IntoIter<Result<U>>),
+    /// Being serialized by a worker.
+    #[cfg(feature = "multithreading")]
+    Pending(Receiver<Result<U>>),
+}
+
+impl<U> Iterator for ChunkRecords<U> {
+    type Item = Result<U>;
+
+    fn next(&mut self) -> Option<Result<U>> {
+        match self {
+            ChunkRecords::Serialized(records) => records.next(),
+            // The worker is done once it drops it's sender.
+            #[cfg(feature = "multithreading")]
+            ChunkRecords::Pending(receiver) => receiver.recv().ok(),
+        }
+    }
+}
+
+/// Keeps every `n`th of the records it sees, see `ParserSettings::sample_every`.
+/// Errors are always kept, and are not counted.
+pub(crate) struct Sampler {
+    every: Option<u64>,
+    seen: u64,
+}
+
+impl Sampler {
+    pub(crate) fn new(settings: &ParserSettings) -> Self {
+        Sampler {
+            every: settings.get_sample_every(),
+            seen: 0,
+        }
+    }
+
+    pub(crate) fn keep<T>(&mut self, record: &Result<T>) -> bool {
+        match (self.every, record) {
+            (Some(n), Ok(_)) => {
+                self.seen += 1;
+                self.seen % n == 0
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Serializes the records of chunks on the thread pool, yielding them in file order.
+///
+/// Chunks are read by the calling thread, and up to `ParserSettings::get_max_chunks_in_flight` chunks
+/// are parsed and serialized ahead of the record being yielded. Every worker sends at most
+/// `RECORDS_BUFFERED_PER_CHUNK` records ahead, so memory usage does not depend on the size of the chunks.
+///
+/// With a single thread (or when called from a thread of the pool, which would otherwise wait for itself),
+/// chunks are serialized on the calling thread, one at a time.
+struct OrderedRecords<I, U, F> {
+    chunks: I,
+    settings: Arc<ParserSettings>,
+    f: F,
+    in_flight: VecDeque<ChunkRecords<U>>,
+    max_in_flight: usize,
+}
+
+impl<I, U, F> OrderedRecords<I, U, F>
+where
+    I: Iterator<Item = Result<EvtxChunkData>>,
+    U: Send + 'static,
+    F: FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+{
+    fn new(chunks: I, settings: Arc<ParserSettings>, f: F) -> Self {
+        #[cfg(feature = "multithreading")]
+        let is_worker_thread = match &settings.executor {
+            Some(executor) => executor.is_current_thread(),
+            None => rayon::current_thread_index().is_some(),
+        };
+
+        #[cfg(not(feature = "multithreading"))]
+        let is_worker_thread = false;
+
+        let max_in_flight = if settings.num_threads > 1 && !is_worker_thread {
+            settings.get_max_chunks_in_flight()
+        } else {
+            1
+        };
+
+        OrderedRecords {
+            chunks,
+            settings,
+            f,
+            in_flight: VecDeque::with_capacity(max_in_flight),
+            max_in_flight,
+        }
+    }
+
+    fn dispatch(&self, chunk: Result<EvtxChunkData>) -> ChunkRecords<U> {
+        let settings = Arc::clone(&self.settings);
+        let f = self.f.clone();
+
+        #[cfg(feature = "multithreading")]
+        {
+            if self.max_in_flight > 1 {
+                let (sender, receiver) = sync_channel(RECORDS_BUFFERED_PER_CHUNK);
+
+                let executor = settings.executor.clone();
+                let job = move || {
+                    serialize_chunk(chunk, settings, f, |record| sender.send(record).is_ok())
+                };
+
+                // FIFO, so the chunk the caller is waiting on is always the first to be serialized.
+                match executor {
+                    Some(executor) => executor.spawn(job),
+                    None => rayon::spawn_fifo(job),
+                }
+
+                return ChunkRecords::Pending(receiver);
+            }
+        }
+
+        let mut records = vec![];
+        serialize_chunk(chunk, settings, f, |record| {
+            records.push(record);
+            true
+        });
+
+        ChunkRecords::Serialized(records.into_iter())
+    }
+}
+
+impl<I, U, F> Iterator for OrderedRecords<I, U, F>
+where
+    I: Iterator<Item = Result<EvtxChunkData>>,
+    U: Send + 'static,
+    F: FnMut(Result<EvtxRecord<'_>>) -> Result<U> + Send + Sync + Clone + 'static,
+{
+    type Item = Result<U>;
+
+    fn next(&mut self) -> Option<Result<U>> {
+        loop {
+            while self.in_flight.len() < self.max_in_flight {
+                match self.chunks.next() {
+                    Some(chunk) => {
+                        let records = self.dispatch(chunk);
+                        self.in_flight.push_back(records);
+                    }
+                    None => break,
+                }
+            }
+
+            match self.in_flight.front_mut()?.next() {
+                Some(record) => return Some(record),
+                None => {
+                    self.in_flight.pop_front();
+                }
+            }
+        }
+    }
+}
+
+pub struct IntoIterChunks<T: ReadSeek> {
+    parser: EvtxParser<T>,
+    current_chunk_number: u16,
+    /// The chunks left to read, when they are not read in their physical order.
+    order: Option<std::vec::IntoIter<u16>>,
+}
+
+impl<T: ReadSeek> Iterator for IntoIterChunks<T> {
+    type Item = Result<EvtxChunkData>;
+    fn next(&mut self) -> Option<<Self as Iterator>::Item> {
+        if let Some(order) = self.order.as_mut() {
+            return self.parser.next_chunk_in_order(order);
+        }
+
+        info!("Chunk {}", self.current_chunk_number);
+        match self.parser.find_next_chunk(self.current_chunk_number) {
+            None => None,
+            Some((chunk, chunk_number)) => {
+                self.current_chunk_number = chunk_number + 1;
+
+                Some(chunk)
+            }
+        }
+    }
+}
+
+/// Chunks read on a background thread, one chunk ahead of the caller, see `EvtxParser::prefetch_chunks`.
+pub struct PrefetchChunks {
+    receiver: Receiver<Result<EvtxChunkData>>,
+    reader: Option<JoinHandle<()>>,
+}
+
+impl PrefetchChunks {
+    fn new(chunks: impl Iterator<Item = Result<EvtxChunkData>> + Send + 'static) -> Self {
+        // Sends block until the caller asks for the chunk,
+        // so the reader is never more than one chunk ahead.
+        let (sender, receiver) = sync_channel(0);
+
+        let reader = thread::Builder::new()
+            .name("evtx-prefetch".to_string())
+            .spawn(move || {
+                for chunk in chunks {
+                    // The caller is gone.
+                    if sender.send(chunk).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("failed to spawn the chunk prefetching thread");
+
+        PrefetchChunks {
+            receiver,
+            reader: Some(reader),
+        }
+    }
+}
+
+impl Iterator for PrefetchChunks {
+    type Item = Result<EvtxChunkData>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.receiver.recv() {
+            Ok(chunk) => Some(chunk),
+            Err(_) => {
+                // The reader is done, pass on it's panic (if any) to the caller.
+                if let Some(Err(panic)) = self.reader.take().map(JoinHandle::join) {
+                    std::panic::resume_unwind(panic);
+                }
+
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(unused_variables)]
+
+    use super::*;
+    use crate::ensure_env_logger_initialized;
+    use crate::findings::{FindingKind, Severity};
+    use crate::record_filter::*;
+    use crate::tampering::RecordIdRange;
+    use chrono::TimeZone;
+    use std::convert::TryInto;
+
+    fn process_90_records(buffer: &'static [u8]) {
+        let mut parser = EvtxParser::from_buffer(buffer.to_vec()).unwrap();
+
+        for (i, record) in parser.records().take(90).enumerate() {
+            match record {
+                Ok(r) => {
+                    assert_eq!(r.event_record_id, i as u64 + 1);
+                }
+                Err(e) => println!("Error while reading record {}, {:?}", i, e),
+            }
+        }
+    }
+
+    // For clion profiler
+    #[test]
+    fn test_process_single_chunk() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        process_90_records(evtx_file);
+    }
+
+    #[test]
+    fn test_sample_2() {
+        let evtx_file = include_bytes!("../samples/system.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let records: Vec<_> = parser.records().take(10).collect();
+
+        for (i, record) in records.iter().enumerate() {
+            match record {
+                Ok(r) => {
+                    assert_eq!(
+                        r.event_record_id,
+                        i as u64 + 1,
+                        "Parser is skipping records!"
+                    );
+                }
+                Err(e) => panic!("Error while reading record {}, {:?}", i, e),
+            }
+        }
+
+        // It should be empty, and not a [].
+        assert!(records[0]
+            .as_ref()
+            .unwrap()
+            .data
+            .contains("<Binary></Binary>"));
+        assert!(records[1]
+            .as_ref()
+            .unwrap()
+            .data
+            .contains("<Binary>E107070003000C00110010001C00D6000000000000000000</Binary>"));
+    }
+
+    #[test]
+    fn test_parses_first_10_records() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        for (i, record) in parser.records().take(10).enumerate() {
+            match record {
+                Ok(r) => {
+                    assert_eq!(
+                        r.event_record_id,
+                        i as u64 + 1,
+                        "Parser is skipping records!"
+                    );
+                }
+                Err(e) => panic!("Error while reading record {}, {:?}", i, e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parses_records_from_different_chunks() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        for (i, record) in parser.records().take(1000).enumerate() {
+            match record {
+                Ok(r) => {
+                    assert_eq!(r.event_record_id, i as u64 + 1);
+                }
+                Err(e) => println!("Error while reading record {}, {:?}", i, e),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "multithreading")]
+    fn test_multithreading() {
+        use std::collections::HashSet;
+
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let mut record_ids = HashSet::new();
+        for record in parser.records().take(1000) {
+            match record {
+                Ok(r) => {
+                    record_ids.insert(r.event_record_id);
+                }
+                Err(e) => panic!("Error while reading record {:?}", e),
+            }
+        }
+
+        assert_eq!(record_ids.len(), 1000);
+    }
+
+    #[test]
+    fn test_record_headers_match_records() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let records: Vec<(u64, DateTime<Utc>)> = parser
+            .records()
+            .map(|r| r.unwrap())
+            .map(|r| (r.event_record_id, r.timestamp))
+            .collect();
+
+        let headers: Vec<(u64, DateTime<Utc>)> = parser
+            .record_headers()
+            .map(|h| h.unwrap())
+            .map(|h| (h.event_record_id, h.timestamp))
+            .collect();
+
+        assert_eq!(headers.len(), 2261);
+        assert_eq!(headers, records);
+
+        // Record ID filters apply to the headers as well.
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(ParserSettings::new().sample_every(100));
+        let ids: Vec<u64> = parser
+            .record_headers()
+            .map(|h| h.unwrap().event_record_id)
+            .collect();
+        assert_eq!(ids.len(), 22);
+        assert!(ids.iter().all(|id| id % 100 == 0));
+    }
+
+    #[test]
+    #[cfg(feature = "multithreading")]
+    fn test_parallel_records_are_ordered() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        let records = |settings: ParserSettings| -> Vec<String> {
+            let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+                .unwrap()
+                .with_configuration(settings);
+            parser.records().map(|r| r.unwrap().data).collect()
+        };
+
+        let expected = records(ParserSettings::new().num_threads(1));
+        assert_eq!(expected.len(), 2261);
+
+        for max_chunks_in_flight in [1, 3, 0] {
+            let settings = ParserSettings::new()
+                .num_threads(4)
+                .max_chunks_in_flight(max_chunks_in_flight);
+            assert_eq!(records(settings), expected);
+        }
+
+        // Workers stop once the records are not needed anymore.
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(ParserSettings::new().num_threads(4));
+        assert_eq!(parser.records().take(100).count(), 100);
+    }
+
+    #[test]
+    #[cfg(feature = "multithreading")]
+    fn test_bring_your_own_thread_pool() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        let record_ids = |settings: ParserSettings| -> Vec<u64> {
+            let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+                .unwrap()
+                .with_configuration(settings);
+            parser
+                .records()
+                .map(|r| r.unwrap().event_record_id)
+                .collect()
+        };
+
+        let expected = record_ids(ParserSettings::new());
+
+        let pool = Arc::new(
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(3)
+                .build()
+                .unwrap(),
+        );
+        let settings = ParserSettings::new().thread_pool(Arc::clone(&pool));
+        assert_eq!(*settings.get_num_threads(), 3);
+        assert_eq!(record_ids(settings.clone()), expected);
+
+        // Records can also be consumed from within the pool.
+        assert_eq!(pool.install(|| record_ids(settings)), expected);
+
+        let jobs = Arc::new(AtomicUsize::new(0));
+        let spawned = Arc::clone(&jobs);
+        let settings = ParserSettings::new().num_threads(2).spawn_with(move |job| {
+            spawned.fetch_add(1, Ordering::SeqCst);
+            std::thread::spawn(job);
+        });
+
+        assert_eq!(record_ids(settings), expected);
+        assert!(jobs.load(Ordering::SeqCst) > 0);
+    }
+
+    #[test]
+    fn test_file_with_only_a_single_chunk() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/new-user-security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let records: Vec<_> = parser.records().collect();
+        assert_eq!(records.len(), 4);
+    }
+
+    #[test]
+    fn test_parses_chunk2() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        let mut chunk = EvtxChunkData::new(
+            evtx_file[EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE
+                ..EVTX_FILE_HEADER_SIZE + 2 * EVTX_CHUNK_SIZE]
+                .to_vec(),
+            false,
+        )
+        .unwrap();
+
+        assert!(chunk.validate_checksum());
+
+        for record in chunk
+            .parse(Arc::new(ParserSettings::default()))
+            .unwrap()
+            .iter()
+        {
+            record.unwrap();
+        }
+    }
+
+    #[test]
+    fn test_extracts_record_bytes() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let mut chunk = parser.chunks().nth(1).unwrap().unwrap();
+        let raw: Vec<(u64, Vec<u8>)> = chunk
+            .parse(Arc::new(ParserSettings::default()))
+            .unwrap()
+            .iter()
+            .map(|record| record.unwrap())
+            .map(|record| (record.event_record_id, record.raw_bytes().to_vec()))
+            .collect();
+
+        for (record_id, bytes) in raw.iter() {
+            assert_eq!(&bytes[..4], b"\x2a\x2a\x00\x00");
+            assert_eq!(bytes[bytes.len() - 4..], bytes[4..8]);
+            assert_eq!(
+                parser.extract_record_bytes(*record_id).as_ref(),
+                Some(bytes)
+            );
+        }
+
+        assert_eq!(parser.extract_record_bytes(1_000_000), None);
+    }
+
+    #[test]
+    fn test_chunk_info() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let table = parser.chunk_table().unwrap();
+        let infos: Vec<_> = parser.chunks().map(|chunk| chunk.unwrap().info()).collect();
+
+        assert_eq!(infos.len(), table.len());
+
+        for (info, entry) in infos.iter().zip(table.iter()) {
+            assert_eq!(info.chunk_number, Some(entry.chunk_number));
+            assert_eq!(
+                Some(info.first_event_record_id),
+                entry.first_event_record_id
+            );
+            assert_eq!(Some(info.last_event_record_id), entry.last_event_record_id);
+            assert_eq!(
+                Some(info.header_checksum_valid),
+                entry.header_checksum_valid
+            );
+            assert_eq!(Some(info.data_checksum_valid), entry.data_checksum_valid);
+            assert!(info.checksums_valid());
+            assert!(!info.recovered);
+        }
+
+        let chunk =
+            EvtxChunkData::new(evtx_file[4096..4096 + EVTX_CHUNK_SIZE].to_vec(), true).unwrap();
+        assert_eq!(chunk.chunk_number(), None);
+    }
+
+    #[test]
+    fn test_for_each_record() {
+        let evtx_file = include_bytes!("../samples/sample_with_a_bad_chunk_magic.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let expected: Vec<_> = parser
+            .records()
+            .map(|record| record.map(|record| record.data).ok())
+            .collect();
+
+        let mut records = vec![];
+        parser.for_each_record(|record| {
+            records.push(
+                record
+                    .and_then(|record| record.into_xml())
+                    .map(|record| record.data)
+                    .ok(),
+            )
+        });
+
+        assert!(records.iter().any(Option::is_none));
+        assert_eq!(records, expected);
+    }
+
+    #[test]
+    fn test_into_chunks() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/new-user-security.evtx");
+        let parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let records: Vec<_> = parser.into_chunks().collect();
+        assert_eq!(records.len(), 1);
+    }
+
+    #[test]
+    fn test_into_json_value_records() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/new-user-security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let records: Vec<_> = parser.records_json_value().collect();
+
+        for record in records {
+            let record = record.unwrap();
+
+            assert!(record.data.is_object());
+            assert!(record.data.as_object().unwrap().contains_key("Event"));
+        }
+    }
+
+    #[test]
+    fn test_event_id_filter() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let settings = ParserSettings::default().event_id_filter(&[4624]);
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+
+        let records: Vec<_> = parser.records().map(|r| r.unwrap()).collect();
+
+        assert_eq!(records.len(), 583);
+        assert!(records
+            .iter()
+            .all(|r| r.data.contains("<EventID>4624</EventID>")));
+    }
+
+    #[test]
+    fn test_event_id_ranges_filter() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let settings = ParserSettings::default().event_id_ranges_filter(&[4720..=4724]);
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+
+        assert_eq!(parser.records().count(), 6);
+    }
+
+    #[test]
+    fn test_parses_chunks_only_data() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let expected_count = parser.records().count();
+
+        let chunks_only = Cursor::new(evtx_file[EVTX_FILE_HEADER_SIZE..].to_vec());
+        let mut parser = EvtxParser::from_chunks_read_seek(chunks_only).unwrap();
+
+        let records: Vec<_> = parser.records().collect();
+        assert_eq!(records.len(), expected_count);
+        assert_eq!(records[0].as_ref().unwrap().event_record_id, 1);
+    }
+
+    #[test]
+    fn test_chunks_only_data_requires_chunk_magic() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        assert!(EvtxParser::from_chunks_read_seek(Cursor::new(evtx_file.to_vec())).is_err());
+    }
+
+    #[test]
+    fn test_time_range_filter() {
+        use chrono::TimeZone;
+
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let start = Utc.ymd(2016, 7, 17).and_hms(0, 0, 0);
+        let end = Utc.ymd(2016, 7, 24).and_hms(0, 0, 0);
+
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let expected: Vec<u64> = parser
+            .records()
+            .map(|r| r.unwrap())
+            .filter(|r| r.timestamp >= start && r.timestamp < end)
+            .map(|r| r.event_record_id)
+            .collect();
+
+        let settings = ParserSettings::default().time_range_filter(Some(start), Some(end));
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+        let actual: Vec<u64> = parser
+            .records()
+            .map(|r| r.unwrap().event_record_id)
+            .collect();
+
+        assert!(!expected.is_empty());
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn test_time_range_filter_keeps_chunks_with_out_of_order_records() {
+        use chrono::TimeZone;
+
+        ensure_env_logger_initialized();
+        let mut evtx_file = include_bytes!("../samples/security.evtx").to_vec();
+
+        // Move the timestamp of the 10th record of the first chunk years back,
+        // so it's the only one in the range while the first and last records of it's chunk are not.
+        let chunk = EvtxChunkData::new(
+            evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE].to_vec(),
+            false,
+        )
+        .unwrap();
+        let offset = EVTX_FILE_HEADER_SIZE
+            + EVTX_CHUNK_HEADER_SIZE
+            + chunk.record_headers()[..9]
+                .iter()
+                .map(|h| h.data_size as usize)
+                .sum::<usize>()
+            + 16;
+        let timestamp = Utc.ymd(2010, 1, 1).and_hms(12, 0, 0);
+        let filetime = (timestamp.timestamp() as u64 + 11_644_473_600) * 10_000_000;
+        evtx_file[offset..offset + 8].copy_from_slice(&filetime.to_le_bytes());
+
+        let settings = ParserSettings::default().time_range_filter(
+            Some(Utc.ymd(2010, 1, 1).and_hms(0, 0, 0)),
+            Some(Utc.ymd(2010, 1, 2).and_hms(0, 0, 0)),
+        );
+        let mut parser = EvtxParser::from_buffer(evtx_file)
+            .unwrap()
+            .with_configuration(settings);
+        let records: Vec<_> = parser.records().map(|r| r.unwrap()).collect();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].event_record_id, 10);
+        assert_eq!(records[0].timestamp, timestamp);
+    }
+
+    #[test]
+    fn test_provider_and_channel_filters() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let total = parser.records().count();
+
+        let settings = ParserSettings::default()
+            .provider_filter(&["{54849625-5478-4994-a5ba-3e3b0328c30d}", "EventLog"])
+            .channel_filter(&["security"]);
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+
+        let records: Vec<_> = parser.records().map(|r| r.unwrap()).collect();
+        assert!(!records.is_empty() && records.len() < total);
+        assert!(records.iter().all(|r| r
+            .data
+            .contains("Name=\"Microsoft-Windows-Security-Auditing\"")
+            || r.data.contains("Name=\"EventLog\"")));
+
+        let settings = ParserSettings::default().channel_filter(&["System"]);
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+
+        assert_eq!(parser.records().count(), 0);
+    }
+
+    fn assert_records_in_id_range(evtx_file: &[u8], start: u64, end: u64) {
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let expected: Vec<u64> = parser
+            .records()
+            .map(|r| r.unwrap().event_record_id)
+            .filter(|id| (start..=end).contains(id))
+            .collect();
+
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let ids: Vec<u64> = parser
+            .records_in_id_range(start, end)
+            .map(|r| r.unwrap().event_record_id)
+            .collect();
+
+        assert!(!ids.is_empty());
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn test_records_in_id_range() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        // Spans chunks 1 and 2.
+        assert_records_in_id_range(evtx_file, 170, 185);
+        assert_records_in_id_range(evtx_file, 1, 1);
+        assert_records_in_id_range(evtx_file, 2250, u64::MAX);
+
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        assert_eq!(parser.find_chunks_in_id_range(170, 185), vec![1, 2]);
+        assert_eq!(parser.records_in_id_range(3000, 4000).count(), 0);
+        assert_eq!(parser.records_in_id_range(10, 5).count(), 0);
+    }
+
+    #[test]
+    fn test_records_in_chunk() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let chunk = parser.chunks().nth(1).unwrap().unwrap();
+        let (first, last) = (
+            chunk.header.first_event_record_id,
+            chunk.header.last_event_record_id,
+        );
+
+        let ids: Vec<u64> = parser
+            .records_in_chunk(1)
+            .map(|record| record.unwrap().event_record_id)
+            .collect();
+        assert_eq!(ids, (first..=last).collect::<Vec<_>>());
+
+        // Past the end of the file.
+        let records: Vec<_> = parser.records_in_chunk(100).collect();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].is_err());
+    }
+
+    #[test]
+    fn test_records_in_id_range_wrapped_file() {
+        ensure_env_logger_initialized();
+        let evtx_file =
+            include_bytes!("../samples/2-system-Microsoft-Windows-LiveId%4Operational.evtx");
+
+        // The last chunk holds the oldest records.
+        assert_records_in_id_range(evtx_file, 2040, 2090);
+    }
+
+    #[test]
+    fn test_records_from() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let timestamps: Vec<_> = parser
+            .record_headers()
+            .map(|header| header.unwrap().timestamp)
+            .collect();
+
+        for &timestamp in [
+            timestamps[0],
+            timestamps[1500],
+            timestamps[timestamps.len() - 1],
+        ]
+        .iter()
+        {
+            let settings = ParserSettings::default().time_range_filter(Some(timestamp), None);
+            let mut filtered = EvtxParser::from_buffer(evtx_file.to_vec())
+                .unwrap()
+                .with_configuration(settings);
+            let expected: Vec<_> = filtered
+                .records()
+                .map(|r| r.unwrap().event_record_id)
+                .collect();
+
+            let records: Vec<_> = parser
+                .records_from(timestamp)
+                .map(|r| r.unwrap().event_record_id)
+                .collect();
+
+            assert!(!records.is_empty());
+            assert_eq!(records, expected);
+        }
+
+        let after_last = *timestamps.iter().max().unwrap() + Duration::seconds(1);
+        assert_eq!(parser.records_from(after_last).count(), 0);
+    }
+
+    #[test]
+    fn test_record_by_id() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let expected: Vec<_> = parser.records().map(Result::unwrap).collect();
+        let last = expected.last().unwrap().event_record_id;
+
+        for record_id in [1, 2, 1000, last].iter() {
+            let record = parser.record_by_id(*record_id).unwrap().unwrap();
+            let expected = expected
+                .iter()
+                .find(|r| r.event_record_id == *record_id)
+                .unwrap();
+
+            assert_eq!(record.data, expected.data);
+            assert_eq!(record.timestamp, expected.timestamp);
+        }
+
+        assert!(parser.record_by_id(last + 1).unwrap().is_none());
+        assert!(parser.record_by_id(0).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_tail_and_sample() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        assert_eq!(parser.last_record_id(), Some(2261));
+        assert_eq!(parser.tail(0).count(), 0);
+
+        let ids: Vec<u64> = parser.tail(3).map(|r| r.unwrap().event_record_id).collect();
+        assert_eq!(ids, vec![2259, 2260, 2261]);
+
+        // The last records matching the filters are yielded, even if they are spread over several chunks.
+        let settings = ParserSettings::default()
+            .filter_expr("EventID == 4624")
+            .unwrap();
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings.clone());
+        let all: Vec<u64> = parser
+            .records()
+            .map(|r| r.unwrap().event_record_id)
+            .collect();
+        let ids: Vec<u64> = parser
+            .tail(150)
+            .map(|r| r.unwrap().event_record_id)
+            .collect();
+        assert!(all.len() > 150);
+        assert_eq!(ids, all[all.len() - 150..]);
+
+        // Every 3rd of the matching records is sampled, whatever their IDs are.
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings.sample_every(3));
+        let ids: Vec<u64> = parser
+            .records()
+            .map(|r| r.unwrap().event_record_id)
+            .collect();
+        let expected: Vec<u64> = all.iter().skip(2).step_by(3).copied().collect();
+        assert_eq!(ids, expected);
+
+        let settings = ParserSettings::default().sample_every(500);
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+        let ids: Vec<u64> = parser
+            .records()
+            .map(|r| r.unwrap().event_record_id)
+            .collect();
+        assert_eq!(ids, vec![500, 1000, 1500, 2000]);
+
+        // Walking the chunks backwards must find the same record as reading all the chunk headers.
+        for evtx_file in [
+            &include_bytes!("../samples/system.evtx")[..],
+            &include_bytes!("../samples/2-system-Security-dirty.evtx")[..],
+            &include_bytes!("../samples/sample_with_a_bad_chunk_magic.evtx")[..],
+        ] {
+            let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+            let expected = (0..parser.chunk_count())
+                .filter_map(|n| parser.read_chunk_header(n).ok().flatten())
+                .map(|header| header.last_event_record_id)
+                .max();
+
+            assert_eq!(parser.last_record_id(), expected);
+        }
+    }
+
+    #[test]
+    fn test_aggregate() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let histogram = parser.aggregate(GroupBy::EventId);
+        assert_eq!(histogram.total, 2261);
+        assert_eq!(histogram.errors, 0);
+        assert_eq!(histogram.counts.values().sum::<u64>(), 2261);
+
+        let most_common = histogram.most_common();
+        assert_eq!(most_common[0], (&GroupKey::EventId(Some(4907)), 620));
+
+        let histogram = parser.aggregate(GroupBy::Provider);
+        let providers: Vec<String> = histogram.counts.keys().map(|k| k.to_string()).collect();
+        assert_eq!(
+            providers,
+            vec![
+                "Microsoft-Windows-Eventlog",
+                "Microsoft-Windows-Security-Auditing"
+            ]
+        );
+
+        let histogram = parser.aggregate(GroupBy::Hour);
+        let first_hour = Utc.ymd(2016, 7, 8).and_hms(18, 0, 0);
+        assert_eq!(
+            histogram.counts.keys().next(),
+            Some(&GroupKey::Hour(first_hour))
+        );
+
+        // Filters apply to the counted records.
+        let settings = ParserSettings::default().event_id_filter(&[4624]);
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+        let histogram = parser.aggregate(GroupBy::EventId);
+        assert_eq!(histogram.counts.len(), 1);
+    }
+
+    #[test]
+    fn test_analyze() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/sysmon.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let report = parser.analyze(&["Image", "NotAField"], 3);
+        assert_eq!(report.records, 358);
+        assert_eq!(report.errors, 0);
+        assert_eq!(report.by_event_id[&GroupKey::EventId(Some(1))].count, 227);
+        assert_eq!(
+            report
+                .by_hour
+                .values()
+                .map(|stats| stats.count)
+                .sum::<u64>(),
+            358
+        );
+
+        let provider =
+            &report.by_provider[&GroupKey::Provider(Some("Microsoft-Windows-Sysmon".to_string()))];
+        assert_eq!(Some(provider.first_seen), report.first_seen);
+        assert_eq!(Some(provider.last_seen), report.last_seen);
+
+        let images = &report.fields["Image"];
+        let most_common = images.most_common(3);
+        let rarest = images.rarest(3);
+        assert_eq!(most_common.len(), 3);
+        assert!(most_common[0].1.count >= most_common[1].1.count);
+        assert!(rarest[0].1.count <= rarest[1].1.count);
+        assert!(rarest[0].1.count <= most_common[2].1.count);
+        assert!(report.fields["NotAField"].values.is_empty());
+
+        let json = report.to_json_value();
+        assert_eq!(
+            json["fields"]["Image"]["rarest"].as_array().unwrap().len(),
+            3
+        );
+
+        let mut csv = vec![];
+        report.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("category,key,count,first_seen,last_seen\n"));
+        assert!(csv.contains("\nevent_id,1,227,"));
+        assert_eq!(csv.matches("\nrarest:Image,").count(), 3);
+    }
+
+    #[test]
+    fn test_chunk_table() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let table = parser.chunk_table().unwrap();
+        assert_eq!(table.len(), 26);
+
+        let first = &table[0];
+        assert_eq!(first.status, ChunkStatus::Valid);
+        assert_eq!(first.offset, EVTX_FILE_HEADER_SIZE as u64);
+        assert_eq!(first.first_event_record_id, Some(1));
+        assert_eq!(first.last_event_record_id, Some(91));
+        assert_eq!(first.header_checksum_valid, Some(true));
+        assert_eq!(first.data_checksum_valid, Some(true));
+        assert_eq!(first.free_space_offset, Some(65376));
+        assert_eq!(first.slack_bytes, Some(EVTX_CHUNK_SIZE as u32 - 65376));
+        assert_eq!(first.timestamp_regressions, None);
+
+        let mut csv = vec![];
+        parser
+            .export_chunk_table(&mut csv, ChunkTableFormat::Csv)
+            .unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 27);
+        assert!(csv.starts_with("chunk_number,offset,status,"));
+        assert!(csv
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("0,4096,valid,,1,91,1,91,true,true,"));
+
+        let mut json = vec![];
+        parser
+            .export_chunk_table(&mut json, ChunkTableFormat::Json)
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 26);
+        assert_eq!(json[25]["last_event_record_id"], 2261);
+
+        let settings = ParserSettings::default().validate_timestamp_order(Duration::zero());
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+        let table = parser.chunk_table().unwrap();
+        assert!(table
+            .iter()
+            .filter(|entry| entry.status == ChunkStatus::Valid)
+            .all(|entry| entry.timestamp_regressions.is_some()));
+    }
+
+    #[test]
+    fn test_chunk_table_with_bad_chunk() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/sample_with_a_bad_chunk_magic.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let table = parser.chunk_table().unwrap();
+        assert_eq!(table[0].status, ChunkStatus::Valid);
+        assert!(table
+            .iter()
+            .any(|entry| matches!(entry.status, ChunkStatus::Invalid(_))));
+    }
+
+    #[test]
+    fn test_activity_groups() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!(
+            "../samples/2-vss_0-Microsoft-Windows-RemoteDesktopServices-RdpCoreTS%4Operational.evtx"
+        );
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let groups = parser.activity_groups();
+        assert_eq!(groups.len(), 151);
+        assert_eq!(groups.iter().map(|g| g.records.len()).sum::<usize>(), 1910);
+
+        let session = groups
+            .iter()
+            .find(|g| g.activity_ids == ["F42040AE-5EA7-459B-BE52-71B882BA0000"])
+            .unwrap();
+        assert_eq!(session.records.len(), 36);
+        assert!(session
+            .records
+            .iter()
+            .all(
+                |r| r.data["Event"]["System"]["Correlation"]["#attributes"]["ActivityID"]
+                    == "F42040AE-5EA7-459B-BE52-71B882BA0000"
+            ));
+
+        let settings = ParserSettings::default().record_filter(RecordFilter::activity_ids(&[
+            "{f42040ae-5ea7-459b-be52-71b882ba0000}",
+        ]));
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+        assert_eq!(parser.records().filter(|r| r.is_ok()).count(), 36);
+    }
+
+    #[test]
+    fn test_sid_and_account_filters() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        let count = |filter: RecordFilter| {
+            let settings = ParserSettings::default().record_filter(filter);
+            let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+                .unwrap()
+                .with_configuration(settings);
+
+            parser.records().filter(|r| r.is_ok()).count()
+        };
+
+        let user = "S-1-5-21-2603537626-3982775912-406486804-1000";
+        assert_eq!(count(RecordFilter::sids(&[user])), 191);
+        assert_eq!(count(RecordFilter::sids(&[user.to_lowercase()])), 191);
+        assert_eq!(
+            count(RecordFilter::sids(&[
+                "S-1-5-21-2603537626-3982775912-406486804-*"
+            ])),
+            208
+        );
+        // Also matches the domain SID itself.
+        assert_eq!(count(RecordFilter::sids(&["S-1-5-21-2603537626-*"])), 209);
+        assert_eq!(count(RecordFilter::sids(&["S-1-5-21-2603537626"])), 0);
+
+        assert_eq!(count(RecordFilter::accounts(&["FSIR"])), 229);
+        assert_eq!(
+            count(RecordFilter::accounts(&["WIN-03DLIIOFRRA\\fsir"])),
+            229
+        );
+        assert_eq!(count(RecordFilter::accounts(&["WORKGROUP\\fsir"])), 0);
+    }
+
+    #[test]
+    fn test_level_and_keywords_filters() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        let count = |filter: RecordFilter| {
+            let settings = ParserSettings::default().record_filter(filter);
+            let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+                .unwrap()
+                .with_configuration(settings);
+
+            parser.records().filter(|r| r.is_ok()).count()
+        };
+
+        let total = count(RecordFilter::And(vec![]));
+
+        assert_eq!(count(RecordFilter::levels(&[LEVEL_INFORMATION])), 36);
+        assert_eq!(count(RecordFilter::levels(&[LEVEL_ERROR])), 1);
+        assert_eq!(
+            count(RecordFilter::keywords_all(0x8020_0000_0000_0000)),
+            2224
+        );
+        assert_eq!(
+            count(RecordFilter::keywords_any(KEYWORD_AUDIT_SUCCESS)),
+            total
+        );
+        assert_eq!(count(RecordFilter::keywords_any(KEYWORD_AUDIT_FAILURE)), 0);
+        assert_eq!(
+            count(RecordFilter::keywords_any(KEYWORD_AUDIT_FAILURE).negate()),
+            total
+        );
+
+        assert_eq!(
+            count(
+                RecordFilter::levels(&[LEVEL_ERROR]).or(RecordFilter::levels(&[LEVEL_INFORMATION]))
+            ),
+            37
+        );
+        assert_eq!(
+            count(
+                RecordFilter::levels(&[LEVEL_LOG_ALWAYS])
+                    .and(RecordFilter::keywords_all(0x4020_0000_0000_0000))
+            ),
+            0
+        );
+
+        // Composes with the other filters of the settings.
+        let settings = ParserSettings::default()
+            .event_id_filter(&[4624])
+            .record_filter(RecordFilter::levels(&[LEVEL_LOG_ALWAYS]));
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+        assert_eq!(parser.records().count(), 583);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #![allow(unused_variables)]
+    #[test]
+    fn test_largest_records() {
+        ensure_env_logger_initialized();
+        let evtx_file = include_bytes!("../samples/security.evtx");
 
-    use super::*;
-    use crate::ensure_env_logger_initialized;
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let mut expected: Vec<usize> = parser
+            .records_json()
+            .map(|r| r.unwrap().data.len())
+            .collect();
+        expected.sort_unstable_by(|a, b| b.cmp(a));
 
-    fn process_90_records(buffer: &'static [u8]) {
-        let mut parser = EvtxParser::from_buffer(buffer.to_vec()).unwrap();
+        let largest = parser.largest_records(5, RenderFormat::Json);
 
-        for (i, record) in parser.records().take(90).enumerate() {
-            match record {
-                Ok(r) => {
-                    assert_eq!(r.event_record_id, i as u64 + 1);
-                }
-                Err(e) => println!("Error while reading record {}, {:?}", i, e),
-            }
+        assert_eq!(
+            largest
+                .iter()
+                .map(|size| size.rendered_size)
+                .collect::<Vec<_>>(),
+            expected[..5].to_vec()
+        );
+
+        for size in largest {
+            assert!(size.raw_size > 24);
+            assert_eq!(
+                size.provider_name.as_deref(),
+                Some("Microsoft-Windows-Security-Auditing")
+            );
         }
     }
 
-    // For clion profiler
     #[test]
-    fn test_process_single_chunk() {
+    fn test_filter_expr() {
+        ensure_env_logger_initialized();
         let evtx_file = include_bytes!("../samples/security.evtx");
-        process_90_records(evtx_file);
-    }
+        use chrono::TimeZone;
+        let start = Utc.ymd(2016, 7, 10).and_hms(0, 0, 0);
 
-    #[test]
-    fn test_sample_2() {
-        let evtx_file = include_bytes!("../samples/system.evtx");
         let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let expected: Vec<u64> = parser
+            .records_json_value()
+            .map(|r| r.unwrap())
+            .filter(|r| {
+                r.data["Event"]["System"]["EventID"] == 4624
+                    && r.data["Event"]["EventData"]["LogonType"] == 5
+                    && r.timestamp >= start
+            })
+            .map(|r| r.event_record_id)
+            .collect();
 
-        let records: Vec<_> = parser.records().take(10).collect();
-
-        for (i, record) in records.iter().enumerate() {
-            match record {
-                Ok(r) => {
-                    assert_eq!(
-                        r.event_record_id,
-                        i as u64 + 1,
-                        "Parser is skipping records!"
-                    );
-                }
-                Err(e) => panic!("Error while reading record {}, {:?}", i, e),
-            }
-        }
+        assert!(!expected.is_empty());
 
-        // It should be empty, and not a [].
-        assert!(records[0]
-            .as_ref()
+        let settings = ParserSettings::default()
+            .filter_expr(
+                r#"EventID == 4624 && EventData.LogonType == 5 && TimeCreated >= "2016-07-10""#,
+            )
+            .unwrap();
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
             .unwrap()
-            .data
-            .contains("<Binary></Binary>"));
-        assert!(records[1]
-            .as_ref()
+            .with_configuration(settings);
+
+        let ids: Vec<u64> = parser
+            .records()
+            .map(|r| r.unwrap().event_record_id)
+            .collect();
+        assert_eq!(ids, expected);
+
+        let settings = ParserSettings::default()
+            .filter_expr(r#"Provider.Name == "EVENTLOG" || !(Channel == "security")"#)
+            .unwrap();
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
             .unwrap()
-            .data
-            .contains("<Binary>E107070003000C00110010001C00D6000000000000000000</Binary>"));
+            .with_configuration(settings);
+        assert!(parser
+            .records()
+            .all(|r| r.unwrap().data.contains("EventLog")));
+
+        assert!(ParserSettings::default().filter_expr("EventID ==").is_err());
     }
 
     #[test]
-    fn test_parses_first_10_records() {
+    fn test_filter_query() {
         ensure_env_logger_initialized();
         let evtx_file = include_bytes!("../samples/security.evtx");
+        use chrono::TimeZone;
+        let start = Utc.ymd(2016, 7, 10).and_hms(0, 0, 0);
+
         let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let expected: Vec<u64> = parser
+            .records_json_value()
+            .map(|r| r.unwrap())
+            .filter(|r| {
+                r.data["Event"]["System"]["EventID"] == 4624
+                    && r.data["Event"]["EventData"]["TargetUserName"]
+                        .as_str()
+                        .map_or(false, |name| name.ends_with("SERVICE"))
+                    && r.timestamp >= start
+            })
+            .map(|r| r.event_record_id)
+            .collect();
 
-        for (i, record) in parser.records().take(10).enumerate() {
-            match record {
-                Ok(r) => {
-                    assert_eq!(
-                        r.event_record_id,
-                        i as u64 + 1,
-                        "Parser is skipping records!"
-                    );
-                }
-                Err(e) => panic!("Error while reading record {}, {:?}", i, e),
-            }
-        }
+        assert!(!expected.is_empty());
+
+        let settings = ParserSettings::default()
+            .filter_query("EventID=4624 AND TargetUserName~'*service' AND TimeCreated>=2016-07-10")
+            .unwrap();
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+
+        let ids: Vec<u64> = parser
+            .records()
+            .map(|r| r.unwrap().event_record_id)
+            .collect();
+        assert_eq!(ids, expected);
     }
 
     #[test]
-    fn test_parses_records_from_different_chunks() {
+    fn test_any_data_filters_match_every_data_element() {
         ensure_env_logger_initialized();
         let evtx_file = include_bytes!("../samples/security.evtx");
+
         let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let records: Vec<(u64, String)> = parser
+            .records()
+            .map(|r| r.unwrap())
+            .map(|r| (r.event_record_id, r.data))
+            .collect();
+        let expected: Vec<u64> = records
+            .iter()
+            .filter(|(_, xml)| xml.contains(">NT AUTHORITY</Data>"))
+            .map(|(id, _)| *id)
+            .collect();
 
-        for (i, record) in parser.records().take(1000).enumerate() {
-            match record {
-                Ok(r) => {
-                    assert_eq!(r.event_record_id, i as u64 + 1);
-                }
-                Err(e) => println!("Error while reading record {}, {:?}", i, e),
-            }
+        // The value is not always in the first `Data` element.
+        assert!(records.iter().any(|(_, xml)| {
+            xml.find(">NT AUTHORITY</Data>")
+                .map_or(false, |position| position > xml.find("</Data>").unwrap())
+        }));
+
+        for filter in [
+            RecordFilter::from_xpath("*[EventData[Data='NT AUTHORITY']]").unwrap(),
+            RecordFilter::Like {
+                field: Field::AnyEventData,
+                pattern: "nt author*".to_string(),
+            },
+        ] {
+            let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+                .unwrap()
+                .with_configuration(ParserSettings::default().record_filter(filter));
+            let ids: Vec<u64> = parser
+                .records()
+                .map(|r| r.unwrap().event_record_id)
+                .collect();
+
+            assert!(!ids.is_empty());
+            assert_eq!(ids, expected);
         }
     }
 
     #[test]
-    #[cfg(feature = "multithreading")]
-    fn test_multithreading() {
-        use std::collections::HashSet;
+    fn test_file_template_cache() {
+        ensure_env_logger_initialized();
 
+        for path in [
+            "samples/security.evtx",
+            "samples/E_Windows_system32_winevt_logs_Microsoft-Windows-CAPI2%4Operational.evtx",
+        ] {
+            let records = |settings: ParserSettings| -> Vec<std::result::Result<String, String>> {
+                let mut parser = EvtxParser::from_path(path)
+                    .unwrap()
+                    .with_configuration(settings);
+                parser
+                    .records()
+                    .map(|r| r.map(|r| r.data).map_err(|e| e.to_string()))
+                    .collect()
+            };
+
+            let settings = ParserSettings::new().file_template_cache(true);
+            let cached = records(settings.clone());
+
+            assert!(!settings.get_file_template_cache().unwrap().is_empty());
+            assert_eq!(cached, records(ParserSettings::new()), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_skeleton_cache() {
         ensure_env_logger_initialized();
-        let evtx_file = include_bytes!("../samples/security.evtx");
-        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
 
-        let mut record_ids = HashSet::new();
-        for record in parser.records().take(1000) {
-            match record {
-                Ok(r) => {
-                    record_ids.insert(r.event_record_id);
+        for path in [
+            "samples/security.evtx",
+            "samples/system.evtx",
+            "samples/sysmon.evtx",
+            "samples/2-system-Security-dirty.evtx",
+            "samples/sample-with-irregular-bool-values.evtx",
+            "samples/E_Windows_system32_winevt_logs_Microsoft-Windows-CAPI2%4Operational.evtx",
+        ] {
+            let records = |settings: ParserSettings| -> Vec<std::result::Result<String, String>> {
+                let mut parser = EvtxParser::from_path(path)
+                    .unwrap()
+                    .with_configuration(settings);
+                parser
+                    .records()
+                    .map(|r| r.map(|r| r.data).map_err(|e| e.to_string()))
+                    .collect()
+            };
+            let json_records =
+                |settings: ParserSettings| -> Vec<std::result::Result<String, String>> {
+                    let mut parser = EvtxParser::from_path(path)
+                        .unwrap()
+                        .with_configuration(settings);
+                    parser
+                        .records_json()
+                        .map(|r| r.map(|r| r.data).map_err(|e| e.to_string()))
+                        .collect()
+                };
+
+            for indent in [true, false] {
+                let settings = ParserSettings::new().indent(indent);
+                let with_skeletons = settings.clone().skeleton_cache(true);
+
+                assert_eq!(
+                    records(with_skeletons.clone()),
+                    records(settings),
+                    "{}",
+                    path
+                );
+                if path == "samples/security.evtx" {
+                    assert!(!with_skeletons.get_skeleton_cache().unwrap().is_empty());
+                }
+            }
+
+            for separate_json_attributes in [true, false] {
+                let settings =
+                    ParserSettings::new().separate_json_attributes(separate_json_attributes);
+                let with_skeletons = settings.clone().skeleton_cache(true).collect_stats(true);
+
+                assert_eq!(
+                    json_records(with_skeletons.clone()),
+                    json_records(settings),
+                    "{}",
+                    path
+                );
+
+                let stats = with_skeletons.get_stats().unwrap();
+                if path == "samples/security.evtx" {
+                    assert!(stats.json_skeleton_hit_rate().unwrap() > 0.9, "{:?}", stats);
                 }
-                Err(e) => panic!("Error while reading record {:?}", e),
             }
         }
+    }
 
-        assert_eq!(record_ids.len(), 1000);
+    #[test]
+    fn test_chunk_count_of_dirty_files_is_scanned() {
+        let mut evtx_file = include_bytes!("../samples/security.evtx").to_vec();
+        // A stale chunk count.
+        evtx_file[42..44].copy_from_slice(&20_u16.to_le_bytes());
+
+        let parser = EvtxParser::from_buffer(evtx_file.clone()).unwrap();
+        assert!(parser.header().is_dirty());
+        assert_eq!(parser.header().chunk_count, 20);
+        assert_eq!(parser.chunk_count(), 26);
+
+        // The header of a clean file is trusted.
+        evtx_file[120..124].copy_from_slice(&0_u32.to_le_bytes());
+
+        let parser = EvtxParser::from_buffer(evtx_file).unwrap();
+        assert!(!parser.header().is_dirty());
+        assert_eq!(parser.chunk_count(), 20);
     }
 
     #[test]
-    fn test_file_with_only_a_single_chunk() {
+    fn test_orders_wrapped_logs_by_record_id() {
         ensure_env_logger_initialized();
-        let evtx_file = include_bytes!("../samples/new-user-security.evtx");
-        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let chunk_count = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .chunk_count() as usize;
 
-        let records: Vec<_> = parser.records().collect();
-        assert_eq!(records.len(), 4);
+        // Wrap the log around, so that the oldest records are in the 7th chunk.
+        let chunks = &evtx_file[EVTX_FILE_HEADER_SIZE..][..chunk_count * EVTX_CHUNK_SIZE];
+        let mut wrapped = evtx_file[..EVTX_FILE_HEADER_SIZE].to_vec();
+        wrapped.extend_from_slice(&chunks[(chunk_count - 6) * EVTX_CHUNK_SIZE..]);
+        wrapped.extend_from_slice(&chunks[..(chunk_count - 6) * EVTX_CHUNK_SIZE]);
+
+        let record_ids = |parser: &mut EvtxParser<Cursor<Vec<u8>>>| -> Vec<u64> {
+            parser
+                .records()
+                .map(|record| record.unwrap().event_record_id)
+                .collect()
+        };
+
+        let mut original = evtx_file[..EVTX_FILE_HEADER_SIZE].to_vec();
+        original.extend_from_slice(chunks);
+
+        let mut parser = EvtxParser::from_buffer(original).unwrap();
+        assert_eq!(parser.wrap_point(), None);
+        let expected = record_ids(&mut parser);
+
+        let mut parser = EvtxParser::from_buffer(wrapped).unwrap();
+        assert_eq!(parser.wrap_point(), Some(6));
+        assert_ne!(record_ids(&mut parser), expected);
+
+        let mut parser = parser.with_configuration(ParserSettings::new().order_by_record_id(true));
+        assert_eq!(record_ids(&mut parser), expected);
     }
 
     #[test]
-    fn test_parses_chunk2() {
+    fn test_parses_truncated_files() {
         ensure_env_logger_initialized();
         let evtx_file = include_bytes!("../samples/security.evtx");
 
-        let mut chunk = EvtxChunkData::new(
-            evtx_file[EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE
-                ..EVTX_FILE_HEADER_SIZE + 2 * EVTX_CHUNK_SIZE]
-                .to_vec(),
-            false,
-        )
-        .unwrap();
+        let count = |len: usize| -> (usize, usize) {
+            let mut parser = EvtxParser::from_buffer(evtx_file[..len].to_vec()).unwrap();
+            let results: Vec<_> = parser.records().collect();
+            let ok = results.iter().filter(|r| r.is_ok()).count();
 
-        assert!(chunk.validate_checksum());
+            (ok, results.len() - ok)
+        };
 
-        for record in chunk
-            .parse(Arc::new(ParserSettings::default()))
+        // Cut off after the first chunk.
+        assert_eq!(count(EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE), (91, 0));
+
+        // Cut off in the middle of the second chunk, the rest of it is reported as skipped.
+        let (ok, errors) = count(EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE + 30000);
+        assert!(ok > 91 && ok < 91 * 2, "{}", ok);
+        assert_eq!(errors, 1);
+    }
+
+    #[test]
+    fn test_slack_records() {
+        use std::collections::HashSet;
+
+        let evtx_file = include_bytes!(
+            "../samples/2-vss_0-Microsoft-Windows-TerminalServices-RemoteConnectionManager%4Operational.evtx"
+        );
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let live: HashSet<u64> = parser
+            .records()
+            .filter_map(|record| record.ok())
+            .map(|record| record.event_record_id)
+            .collect();
+        let recovered: Vec<u64> = parser
+            .slack_records()
+            .map(|record| record.unwrap().event_record_id)
+            .collect();
+
+        assert!(!recovered.is_empty());
+        // Records in the slack space were overwritten by newer ones.
+        assert!(recovered.iter().all(|id| !live.contains(id)));
+    }
+
+    #[test]
+    fn test_validate() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let report = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .validate()
+            .unwrap();
+
+        assert!(report.is_valid(), "{}", report);
+        assert!(report.file_header.as_ref().unwrap().checksum_valid);
+        assert!(!report.truncated);
+        // The sample was copied while it's log was open.
+        assert!(report.file_header.as_ref().unwrap().dirty);
+        assert_eq!(report.record_count(), 2261);
+
+        let report = EvtxParser::from_buffer(evtx_file[..100_000].to_vec())
+            .unwrap()
+            .validate()
+            .unwrap();
+        assert!(report.truncated);
+        assert!(!report.is_valid());
+
+        // Break the data checksum of the second chunk.
+        let mut data = evtx_file.to_vec();
+        data[EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE + 600] ^= 0xff;
+
+        let report = EvtxParser::from_buffer(data).unwrap().validate().unwrap();
+        let invalid: Vec<_> = report.invalid_chunks().collect();
+
+        assert!(!report.is_valid());
+        assert_eq!(invalid.len(), 1);
+        assert_eq!(invalid[0].chunk_number, 1);
+        assert_eq!(invalid[0].data_checksum_valid, Some(false));
+    }
+
+    #[test]
+    fn test_file_report() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let report = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .file_report()
+            .unwrap();
+
+        assert!(report.is_clean(), "{}", report);
+        assert_eq!(report.records_ok, 2261);
+        assert_eq!(report.records_recovered, 0);
+
+        // The same failures as the ones yielded by `records`.
+        let evtx_file = include_bytes!("../samples/sample_with_a_bad_chunk_magic.evtx");
+        let report = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .file_report()
+            .unwrap();
+
+        assert!(!report.is_clean());
+        assert_eq!(report.records_ok, 270);
+        assert_eq!(
+            report.chunk_failures.len() + report.record_failures.len(),
+            5,
+            "{}",
+            report
+        );
+        assert_eq!(report.to_json_value()["records_ok"], 270);
+    }
+
+    #[test]
+    fn test_tampering_report() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let report = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .tampering_report(Duration::minutes(1))
+            .unwrap();
+
+        // Records are routinely written slightly out of order.
+        assert!(report.is_clean(), "{}", report);
+        assert_eq!(report.records, 2261);
+
+        // Break the magic of the second chunk, so that it's records are missing.
+        let mut data = evtx_file.to_vec();
+        let second_chunk = EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE;
+        data[second_chunk] ^= 0xff;
+        let first_id = u64::from_le_bytes(
+            data[second_chunk + 8..second_chunk + 16]
+                .try_into()
+                .unwrap(),
+        );
+        let last_id = u64::from_le_bytes(
+            data[second_chunk + 16..second_chunk + 24]
+                .try_into()
+                .unwrap(),
+        );
+
+        let report = EvtxParser::from_buffer(data)
+            .unwrap()
+            .tampering_report(Duration::minutes(1))
+            .unwrap();
+
+        assert!(!report.is_clean());
+        assert_eq!(report.chunk_errors.len(), 1);
+        assert_eq!(
+            report.missing_record_ids,
+            vec![RecordIdRange {
+                first: first_id,
+                last: last_id
+            }]
+        );
+        assert!(report.duplicate_record_ids.is_empty());
+    }
+
+    #[test]
+    fn test_findings_report() {
+        let evtx_file = include_bytes!("../samples/system.evtx");
+        let report = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .findings_report(Duration::minutes(1))
+            .unwrap();
+
+        let cleared: Vec<&Finding> = report.of_kind(FindingKind::LogCleared).collect();
+        assert_eq!(cleared.len(), 2, "{}", report);
+        assert_eq!(cleared[0].event_record_id, Some(1847));
+        assert_eq!(
+            cleared[0].description,
+            "The Microsoft-Windows-Sysmon/Operational log was cleared by DESKTOP-2KGM189\\tony"
+        );
+        assert_eq!(report.max_severity(), Some(Severity::High));
+    }
+
+    #[test]
+    fn test_reports_progress() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let reported = Arc::new(std::sync::Mutex::new(vec![]));
+        let progress = Arc::clone(&reported);
+        let settings = ParserSettings::new()
+            .num_threads(2)
+            .on_progress(move |p| progress.lock().unwrap().push(*p));
+
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
             .unwrap()
+            .with_configuration(settings);
+        let count = parser.records().count();
+
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), usize::from(parser.chunk_count()));
+        assert!(reported
             .iter()
-        {
-            record.unwrap();
-        }
+            .enumerate()
+            .all(|(i, p)| usize::from(p.chunk_number) == i));
+        assert_eq!(
+            reported.iter().map(|p| p.records).sum::<u64>(),
+            count as u64
+        );
+
+        let last = reported.last().unwrap();
+        assert_eq!(last.fraction(), 1.0);
+        assert_eq!(last.bytes_read(), last.total_bytes());
     }
 
     #[test]
-    fn test_into_chunks() {
+    fn test_collects_stats() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let settings = ParserSettings::new().num_threads(2).collect_stats(true);
+
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings.clone());
+
+        assert_eq!(settings.get_stats(), Some(ParserStats::default()));
+
+        let count = parser.records().filter(Result::is_ok).count();
+        let stats = settings.get_stats().unwrap();
+
+        assert_eq!(stats.records_ok, count as u64);
+        assert_eq!(stats.records_failed, 0);
+        assert!(stats.chunks_parsed > 0);
+        assert_eq!(stats.chunks_read, stats.chunks_parsed);
+        assert_eq!(stats.bytes_read, stats.chunks_read * EVTX_CHUNK_SIZE as u64);
+        assert!(stats.template_cache_hit_rate().is_some());
+
+        assert_eq!(ParserSettings::new().get_stats(), None);
+    }
+
+    #[test]
+    fn test_prefetched_records_match_records() {
         ensure_env_logger_initialized();
-        let evtx_file = include_bytes!("../samples/new-user-security.evtx");
+
+        for path in [
+            "samples/security.evtx",
+            "samples/2-system-Security-dirty.evtx",
+            "samples/E_Windows_system32_winevt_logs_Microsoft-Windows-CAPI2%4Operational.evtx",
+        ] {
+            for num_threads in [1, 4] {
+                let settings = ParserSettings::new().num_threads(num_threads);
+                let parser = || {
+                    EvtxParser::from_path(path)
+                        .unwrap()
+                        .with_configuration(settings.clone())
+                };
+
+                let expected: Vec<_> = parser()
+                    .records()
+                    .map(|r| r.map(|r| r.data).map_err(|e| e.to_string()))
+                    .collect();
+                let prefetched: Vec<_> = parser()
+                    .into_prefetched_records()
+                    .map(|r| r.map(|r| r.data).map_err(|e| e.to_string()))
+                    .collect();
+
+                assert!(!prefetched.is_empty());
+                assert_eq!(prefetched, expected, "{}", path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_prefetched_chunks_can_be_dropped_early() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
         let parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
 
-        let records: Vec<_> = parser.into_chunks().collect();
-        assert_eq!(records.len(), 1);
+        let mut chunks = parser.prefetch_chunks();
+        let chunk = chunks.next().unwrap().unwrap();
+        assert_eq!(chunk.header.first_event_record_id, 1);
+
+        // The reader thread must stop once nobody is waiting for its chunks.
+        drop(chunks);
     }
 
     #[test]
-    fn test_into_json_value_records() {
+    fn test_records_since_checkpoint() {
         ensure_env_logger_initialized();
-        let evtx_file = include_bytes!("../samples/new-user-security.evtx");
-        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
-
-        let records: Vec<_> = parser.records_json_value().collect();
 
-        for record in records {
-            let record = record.unwrap();
+        let evtx_file = include_bytes!("../samples/security.evtx").to_vec();
+        let record_ids = |records: Vec<Result<SerializedEvtxRecord<String>>>| -> Vec<u64> {
+            records
+                .into_iter()
+                .map(|r| r.unwrap().event_record_id)
+                .collect()
+        };
 
-            assert!(record.data.is_object());
-            assert!(record.data.as_object().unwrap().contains_key("Event"));
+        // The same log, before it's last chunks were written.
+        let mut earlier_file = evtx_file.clone();
+        for byte in &mut earlier_file[EVTX_FILE_HEADER_SIZE + 10 * EVTX_CHUNK_SIZE..] {
+            *byte = 0;
         }
+
+        let mut earlier = EvtxParser::from_buffer(earlier_file).unwrap();
+        let last_id = *record_ids(earlier.records().collect()).last().unwrap();
+        let checkpoint = earlier.checkpoint(last_id).unwrap();
+        assert_eq!(checkpoint.last_chunk_number, 9);
+
+        let mut parser = EvtxParser::from_buffer(evtx_file).unwrap();
+        let all_ids = record_ids(parser.records().collect());
+        let expected: Vec<u64> = all_ids.iter().copied().filter(|&id| id > last_id).collect();
+        assert!(!expected.is_empty());
+
+        assert_eq!(
+            parser.checkpoint_status(&checkpoint),
+            CheckpointStatus::Valid
+        );
+        assert_eq!(
+            record_ids(parser.records_since(&checkpoint).collect()),
+            expected
+        );
+
+        // Nothing was added since the last record.
+        let latest = parser.checkpoint(*all_ids.last().unwrap()).unwrap();
+        assert_eq!(parser.records_since(&latest).count(), 0);
+
+        // Another file, all it's records are parsed again.
+        let replaced = Checkpoint {
+            file_signature: checkpoint.file_signature ^ 1,
+            ..checkpoint
+        };
+        assert_eq!(
+            parser.checkpoint_status(&replaced),
+            CheckpointStatus::Replaced
+        );
+        assert_eq!(
+            record_ids(parser.records_since(&replaced).collect()),
+            all_ids
+        );
+
+        // The record was overwritten, records from the oldest one which is left are parsed.
+        let overwritten = Checkpoint {
+            last_record_id: 0,
+            ..checkpoint
+        };
+        assert_eq!(
+            parser.checkpoint_status(&overwritten),
+            CheckpointStatus::Overwritten
+        );
+        assert_eq!(
+            record_ids(parser.records_since(&overwritten).collect()),
+            all_ids
+        );
     }
 }
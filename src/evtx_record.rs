@@ -1,26 +1,94 @@
-use crate::binxml::assemble::parse_tokens;
+use crate::binxml::assemble::{parse_tokens, RecordRenderer};
+use crate::binxml::lookup::find_value;
+use crate::binxml::value_variant::BinXmlValue;
 use crate::err::{self, Result};
+use crate::evtx_chunk::{EvtxChunk, EVTX_RECORD_HEADER_SIZE};
 use crate::evtx_parser::ReadSeek;
-use crate::json_output::JsonOutput;
+use crate::json_output::{insert_at_path, JsonOutput};
+use crate::logon::LogonEvent;
 use crate::model::deserialized::BinXMLDeserializedTokens;
+use crate::provider_names::resolve_provider_name;
+use crate::record_de::Element;
+use crate::record_size::RenderFormat;
+use crate::sid_names::well_known_sid_name;
+use crate::stats::Counter;
+use crate::symbolic_names::RenderingInfo;
 use crate::xml_output::{BinXmlOutput, XmlOutput};
 use crate::ParserSettings;
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
 use std::io::{Cursor, Read};
+use std::ptr;
+use std::sync::Arc;
 
 use byteorder::ReadBytesExt;
 use chrono::prelude::*;
+use once_cell::sync::OnceCell;
+use serde::de::DeserializeOwned;
 use snafu::{ensure, ResultExt};
-use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct EvtxRecord<'a> {
     pub event_record_id: u64,
     pub timestamp: DateTime<Utc>,
-    pub tokens: Vec<BinXMLDeserializedTokens<'a>>,
+    /// The size of the record in the chunk, including it's header.
+    pub data_size: u32,
+    /// `true` if the record was recovered from slack space, or from a chunk whose header was reconstructed.
+    pub recovered: bool,
     pub settings: Arc<ParserSettings>,
+    /// The bytes of the record in the chunk.
+    pub(crate) raw: Cow<'a, [u8]>,
+    /// The tokens of the record (or the error deserializing them), deserialized the first time they are needed (see `tokens`).
+    tokens: OnceCell<std::result::Result<Vec<BinXMLDeserializedTokens<'a>>, SharedError>>,
+    /// The chunk the record is deserialized from, `None` if the tokens were set when the record was created.
+    chunk: Option<RecordChunk<'a>>,
+}
+
+/// The error deserializing a record, returned by every use of it's tokens.
+#[derive(Debug, Clone)]
+struct SharedError(Arc<err::Error>);
+
+impl PartialEq for SharedError {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl From<&SharedError> for err::Error {
+    fn from(error: &SharedError) -> Self {
+        err::Error::Shared {
+            source: Arc::clone(&error.0),
+        }
+    }
+}
+
+/// The chunk of a record, and the offset of it's BinXML in it.
+#[derive(Clone, Copy)]
+struct RecordChunk<'a> {
+    chunk: &'a EvtxChunk<'a>,
+    binxml_offset: u64,
+}
+
+impl fmt::Debug for RecordChunk<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordChunk")
+            .field("binxml_offset", &self.binxml_offset)
+            .finish()
+    }
+}
+
+impl PartialEq for RecordChunk<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        ptr::eq(self.chunk, other.chunk) && self.binxml_offset == other.binxml_offset
+    }
 }
 
+/// A record which does not borrow it's chunk, see `EvtxRecord::into_owned`.
+pub type EvtxRecordOwned = EvtxRecord<'static>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct EvtxRecordHeader {
     pub data_size: u32,
@@ -33,6 +101,12 @@ pub struct SerializedEvtxRecord<T> {
     pub event_record_id: u64,
     pub timestamp: DateTime<Utc>,
     pub data: T,
+    /// `true` if the record was recovered from slack space, or from a chunk whose header was reconstructed
+    /// (see `ParserSettings::repair_chunk_headers`).
+    pub recovered: bool,
+    /// The substitutions which failed to decode, and were rendered as placeholders.
+    /// Always empty unless `ParserSettings::partial_records` is enabled.
+    pub errors: Vec<String>,
 }
 
 impl EvtxRecordHeader {
@@ -64,28 +138,521 @@ impl EvtxRecordHeader {
 }
 
 impl<'a> EvtxRecord<'a> {
+    /// A record of `chunk`, starting at `offset` from the start of the chunk,
+    /// which is deserialized when it's tokens are first needed.
+    pub(crate) fn from_chunk(
+        chunk: &'a EvtxChunk<'a>,
+        header: &EvtxRecordHeader,
+        offset: usize,
+        recovered: bool,
+        settings: Arc<ParserSettings>,
+    ) -> Self {
+        EvtxRecord {
+            event_record_id: header.event_record_id,
+            timestamp: header.timestamp,
+            data_size: header.data_size,
+            recovered,
+            settings,
+            raw: Cow::Borrowed(&chunk.data[offset..offset + header.data_size as usize]),
+            tokens: OnceCell::new(),
+            chunk: Some(RecordChunk {
+                chunk,
+                binxml_offset: (offset + EVTX_RECORD_HEADER_SIZE) as u64,
+            }),
+        }
+    }
+
+    /// Returns the tokens of the record, deserializing them the first time this is called.
+    /// A record which cannot be deserialized returns the same error every time.
+    ///
+    /// This replaces the `tokens` field of earlier versions.
+    /// Rendering the record (see `into_output`) does not need them, it's BinXML is rendered as it is read.
+    pub fn tokens(&self) -> Result<&[BinXMLDeserializedTokens<'a>]> {
+        match self.deserialized() {
+            Ok(tokens) => Ok(tokens),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn deserialized(&self) -> &std::result::Result<Vec<BinXMLDeserializedTokens<'a>>, SharedError> {
+        self.tokens.get_or_init(|| {
+            let mut tokens = vec![];
+
+            if let Some(record_chunk) = self.chunk {
+                record_chunk
+                    .chunk
+                    .deserialize_record(
+                        self.event_record_id,
+                        record_chunk.binxml_offset,
+                        self.binxml_data_size(),
+                        &mut tokens,
+                    )
+                    .map_err(|e| SharedError(Arc::new(e)))?;
+            }
+
+            Ok(tokens)
+        })
+    }
+
+    /// Returns the tokens of the record, or no tokens if it cannot be deserialized.
+    ///
+    /// Only used for values which are `None` when missing, the error itself is returned by `tokens`
+    /// (and by filtering or rendering the record).
+    pub(crate) fn tokens_or_empty(&self) -> &[BinXMLDeserializedTokens<'a>] {
+        self.tokens().unwrap_or_default()
+    }
+
+    fn binxml_data_size(&self) -> u32 {
+        // 24 - record header size
+        // 4 - copy of size record size
+        self.data_size - 24 - 4
+    }
+
+    /// Returns the exact bytes of the record in the chunk (it's header and BinXML, up to the trailing copy of it's size).
+    ///
+    /// Note that the record alone is usually not enough to deserialize it again,
+    /// since it refers to the templates and strings of it's chunk by offset.
+    pub fn raw_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+
+    /// Returns a copy of the record which does not borrow it's chunk.
+    ///
+    /// Records borrow the data of the chunk they were read from, so they cannot outlive the iteration over it's records.
+    /// An owned record can be moved to another thread (for example through a channel), and rendered there.
+    ///
+    /// The tokens of the record are deserialized (a record which cannot be deserialized keeps the error, see `tokens`).
+    pub fn into_owned(self) -> EvtxRecordOwned {
+        let tokens = match self.deserialized() {
+            Ok(tokens) => Ok(tokens
+                .iter()
+                .map(BinXMLDeserializedTokens::to_owned_token)
+                .collect()),
+            Err(e) => Err(e.clone()),
+        };
+
+        EvtxRecord {
+            event_record_id: self.event_record_id,
+            timestamp: self.timestamp,
+            data_size: self.data_size,
+            recovered: self.recovered,
+            settings: self.settings,
+            raw: Cow::Owned(self.raw.into_owned()),
+            tokens: OnceCell::from(tokens),
+            chunk: None,
+        }
+    }
+
+    /// Returns the `EventID` of the record, read directly from the deserialized tokens
+    /// (without rendering the record).
+    pub fn event_id(&self) -> Option<u16> {
+        find_value(self.tokens_or_empty(), "EventID", None)
+            .and_then(|value| value.as_u64())
+            .and_then(|id| u16::try_from(id).ok())
+    }
+
+    /// Returns the `Level` of the record.
+    pub fn level(&self) -> Option<u8> {
+        find_value(self.tokens_or_empty(), "Level", None)
+            .and_then(|value| value.as_u64())
+            .and_then(|level| u8::try_from(level).ok())
+    }
+
+    /// Returns the `Version` of the record.
+    pub fn version(&self) -> Option<u8> {
+        find_value(self.tokens_or_empty(), "Version", None)
+            .and_then(|value| value.as_u64())
+            .and_then(|version| u8::try_from(version).ok())
+    }
+
+    /// Returns the `Task` of the record.
+    pub fn task(&self) -> Option<u16> {
+        find_value(self.tokens_or_empty(), "Task", None)
+            .and_then(|value| value.as_u64())
+            .and_then(|task| u16::try_from(task).ok())
+    }
+
+    /// Returns the `Opcode` of the record.
+    pub fn opcode(&self) -> Option<u8> {
+        find_value(self.tokens_or_empty(), "Opcode", None)
+            .and_then(|value| value.as_u64())
+            .and_then(|opcode| u8::try_from(opcode).ok())
+    }
+
+    /// Returns the `Keywords` bitmask of the record.
+    pub fn keywords(&self) -> Option<u64> {
+        match find_value(self.tokens_or_empty(), "Keywords", None)? {
+            BinXmlValue::HexInt64Type(hex) => {
+                u64::from_str_radix(hex.trim_start_matches("0x"), 16).ok()
+            }
+            value => value.as_u64(),
+        }
+    }
+
+    /// Returns the `Name` attribute of the `Provider` element of the record.
+    ///
+    /// With `ParserSettings::resolve_provider_names`, the name is resolved from the `Guid` attribute
+    /// when there is no `Name`.
+    pub fn provider_name(&self) -> Option<Cow<'_, str>> {
+        match find_value(self.tokens_or_empty(), "Provider", Some("Name")) {
+            Some(name) => Some(name.as_cow_str()),
+            None => self.resolved_provider_name().map(Cow::Owned),
+        }
+    }
+
+    /// Returns the name of the provider resolved from the `Guid` attribute, see `provider_name`.
+    fn resolved_provider_name(&self) -> Option<String> {
+        if !self.settings.should_resolve_provider_names() {
+            return None;
+        }
+
+        resolve_provider_name(&self.settings, &self.provider_guid()?)
+    }
+
+    /// Returns the `Guid` attribute of the `Provider` element of the record.
+    pub fn provider_guid(&self) -> Option<Cow<'_, str>> {
+        find_value(self.tokens_or_empty(), "Provider", Some("Guid")).map(BinXmlValue::as_cow_str)
+    }
+
+    /// Returns the `Channel` of the record.
+    pub fn channel(&self) -> Option<Cow<'_, str>> {
+        find_value(self.tokens_or_empty(), "Channel", None).map(BinXmlValue::as_cow_str)
+    }
+
+    /// Returns the `Computer` of the record.
+    pub fn computer(&self) -> Option<Cow<'_, str>> {
+        find_value(self.tokens_or_empty(), "Computer", None).map(BinXmlValue::as_cow_str)
+    }
+
+    /// Returns the `SystemTime` attribute of the `TimeCreated` element of the record.
+    ///
+    /// This is usually the same as `timestamp` (the time the record was written, from it's header),
+    /// but can differ for forwarded events.
+    pub fn time_created(&self) -> Option<DateTime<Utc>> {
+        match find_value(self.tokens_or_empty(), "TimeCreated", Some("SystemTime"))? {
+            BinXmlValue::FileTimeType(time) | BinXmlValue::SysTimeType(time) => Some(*time),
+            value => DateTime::parse_from_rfc3339(&value.as_cow_str())
+                .ok()
+                .map(|time| time.with_timezone(&Utc)),
+        }
+    }
+
+    /// Returns the `ProcessID` attribute of the `Execution` element of the record.
+    pub fn process_id(&self) -> Option<u32> {
+        find_value(self.tokens_or_empty(), "Execution", Some("ProcessID"))
+            .and_then(|value| value.as_u64())
+            .and_then(|pid| u32::try_from(pid).ok())
+    }
+
+    /// Returns the `ThreadID` attribute of the `Execution` element of the record.
+    pub fn thread_id(&self) -> Option<u32> {
+        find_value(self.tokens_or_empty(), "Execution", Some("ThreadID"))
+            .and_then(|value| value.as_u64())
+            .and_then(|tid| u32::try_from(tid).ok())
+    }
+
+    /// Returns the `UserID` attribute of the `Security` element of the record (a SID, such as `S-1-5-18`).
+    pub fn user_id(&self) -> Option<Cow<'_, str>> {
+        match find_value(self.tokens_or_empty(), "Security", Some("UserID"))? {
+            BinXmlValue::NullType => None,
+            value => Some(value.as_cow_str()),
+        }
+    }
+
+    /// Returns the `ActivityID` attribute of the `Correlation` element of the record.
+    pub fn activity_id(&self) -> Option<Cow<'_, str>> {
+        self.correlation("ActivityID")
+    }
+
+    /// Returns the `RelatedActivityID` attribute of the `Correlation` element of the record.
+    pub fn related_activity_id(&self) -> Option<Cow<'_, str>> {
+        self.correlation("RelatedActivityID")
+    }
+
+    /// Most templates have both `Correlation` attributes, substituted with nulls when missing.
+    fn correlation(&self, attribute: &str) -> Option<Cow<'_, str>> {
+        match find_value(self.tokens_or_empty(), "Correlation", Some(attribute))? {
+            BinXmlValue::NullType => None,
+            value => Some(value.as_cow_str()),
+        }
+    }
+
+    /// Returns the fields of the `EventData` of the record (or of the element in it's `UserData`), with their types.
+    ///
+    /// `<Data Name="X">` elements are keyed by `X`, anonymous `Data` elements (as found in classic events)
+    /// by their position (`"0"`, `"1"`, ..., zero-padded so that keys sort in the order of the elements),
+    /// and other elements by their name. Empty fields are `BinXmlValue::NullType`.
+    pub fn event_data(&self) -> Result<BTreeMap<String, BinXmlValue<'static>>> {
+        let event = Element::from_tokens(self.tokens()?.to_vec())?;
+
+        Ok(event.event_data().map(Element::fields).unwrap_or_default())
+    }
+
+    /// Deserializes the `EventData` of the record (or the element holding the fields of it's `UserData`) into `T`.
+    ///
+    /// `<Data Name="X">` elements map to the field `X` (use `#[serde(rename = "X")]` for other field names),
+    /// keeping the type of their value, and missing or null fields deserialize as `None`.
+    ///
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// #[serde(rename_all = "PascalCase")]
+    /// struct Logon {
+    ///     target_user_name: String,
+    ///     logon_type: u32,
+    ///     ip_address: Option<String>,
+    /// }
+    ///
+    /// let logon: Logon = record.deserialize()?;
+    /// ```
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        let event = Element::from_tokens(self.tokens()?.to_vec())?;
+
+        match event.event_data() {
+            Some(data) => T::deserialize(data),
+            None => T::deserialize(&Element::default()),
+        }
+    }
+
+    /// Deserializes the whole `Event` element of the record into `T` (with `System` and `EventData` fields),
+    /// see `deserialize`.
+    ///
+    /// Elements with attributes deserialize as maps, with their text under `#text`.
+    pub fn deserialize_event<T: DeserializeOwned>(&self) -> Result<T> {
+        T::deserialize(&Element::from_tokens(self.tokens()?.to_vec())?)
+    }
+
+    /// Returns the messages of the substitutions which failed to decode, see `ParserSettings::partial_records`.
+    pub fn parse_errors(&self) -> Vec<String> {
+        let mut errors = vec![];
+
+        if self.settings.should_render_partial_records() {
+            collect_parse_errors(self.tokens_or_empty(), &mut errors);
+        }
+
+        errors
+    }
+
     /// Consumes the record, processing it using the given `output_builder`.
+    ///
+    /// Unless they were already deserialized (see `tokens`), the tokens of the record are rendered as they are read.
     pub fn into_output<T: BinXmlOutput>(self, output_builder: &mut T) -> Result<()> {
-        parse_tokens(self.tokens, output_builder)?;
+        let binxml_data_size = self.binxml_data_size();
 
-        Ok(())
+        match self.tokens.into_inner() {
+            Some(Ok(tokens)) => return parse_tokens(tokens, output_builder),
+            Some(Err(e)) => return Err((&e).into()),
+            None => {}
+        }
+
+        let mut renderer = RecordRenderer::new(output_builder)?;
+        if let Some(record_chunk) = self.chunk {
+            record_chunk.chunk.deserialize_record(
+                self.event_record_id,
+                record_chunk.binxml_offset,
+                binxml_data_size,
+                &mut renderer,
+            )?;
+        }
+
+        renderer.finish()
     }
 
     /// Consumes the record, returning a `EvtxRecordWithJsonValue` with the `serde_json::Value` data.
     pub fn into_json_value(self) -> Result<SerializedEvtxRecord<serde_json::Value>> {
-        let mut output_builder = JsonOutput::new(&self.settings);
+        let settings = Arc::clone(&self.settings);
 
         let event_record_id = self.event_record_id;
         let timestamp = self.timestamp;
-        self.into_output(&mut output_builder)?;
+        let recovered = self.recovered;
+        let errors = self.parse_errors();
+        let annotations = self.json_annotations();
+
+        let skeleton = settings
+            .get_skeleton_cache()
+            .map(|cache| cache.render_json(self.tokens_or_empty(), &settings));
+        let mut data = match skeleton {
+            Some(Some(data)) => {
+                settings.count(Counter::JsonSkeletonHits, 1);
+                data
+            }
+            skeleton => {
+                if skeleton.is_some() {
+                    settings.count(Counter::JsonSkeletonMisses, 1);
+                }
+
+                let mut output_builder = JsonOutput::new(&settings);
+                self.into_output(&mut output_builder)?;
+                output_builder.into_value()?
+            }
+        };
+
+        for (path, value) in annotations {
+            insert_at_path(&mut data, path, value);
+        }
+
+        if let Some(redactor) = settings.get_redactor() {
+            redactor.redact_json(&mut data);
+        }
 
         Ok(SerializedEvtxRecord {
             event_record_id,
             timestamp,
-            data: output_builder.into_value()?,
+            data,
+            recovered,
+            errors,
         })
     }
 
+    /// Fields added to the root of the JSON output of the record, keyed by their path.
+    fn json_annotations(&self) -> Vec<(&'static [&'static str], serde_json::Value)> {
+        let mut annotations = vec![];
+
+        // The name is only looked up when it may be resolved, so the record is not deserialized needlessly.
+        let has_provider_name = !self.settings.should_resolve_provider_names()
+            || find_value(self.tokens_or_empty(), "Provider", Some("Name")).is_some();
+        if !has_provider_name {
+            if let Some(name) = self.resolved_provider_name() {
+                let path: &[&str] = if self.settings.should_separate_json_attributes() {
+                    &["Event", "System", "Provider_attributes", "Name"]
+                } else {
+                    &["Event", "System", "Provider", "#attributes", "Name"]
+                };
+                annotations.push((path, name.into()));
+            }
+        }
+
+        if self
+            .settings
+            .should_render_symbolic_names(RenderFormat::Json)
+        {
+            if let Some(info) = RenderingInfo::of_record(self).to_json() {
+                annotations.push((&["Event", "RenderingInfo"][..], info));
+            }
+        }
+
+        if self.settings.should_resolve_sids() {
+            let resolved = self.resolved_sids();
+            if !resolved.is_empty() {
+                annotations.push((&["Event", "ResolvedSids"][..], resolved.into()));
+            }
+        }
+
+        let enrichment = self.settings.get_enrichment();
+        if let Some(enrichment) = enrichment.filter(|enrichment| enrichment.has_techniques()) {
+            let fields = self.event_data().unwrap_or_default();
+            let field = |name: &str| {
+                fields
+                    .get(name)
+                    .map(|value| value.as_cow_str().into_owned())
+            };
+            let techniques =
+                enrichment.techniques(self.provider_name().as_deref(), self.event_id(), &field);
+
+            if !techniques.is_empty() {
+                let ids: Vec<&str> = techniques.iter().map(|(id, _)| *id).collect();
+                annotations.push((&["threat", "technique", "id"][..], ids.into()));
+
+                let names: Vec<&str> = techniques.iter().filter_map(|(_, name)| *name).collect();
+                if names.len() == techniques.len() {
+                    annotations.push((&["threat", "technique", "name"][..], names.into()));
+                }
+            }
+        }
+
+        if self.settings.should_decode_logon_events() {
+            if let Some(logon) = LogonEvent::from_record(self) {
+                annotations.push((&["Event", "Logon"][..], logon.to_json()));
+            }
+        }
+
+        let lookups = self.settings.get_lookups();
+        if !lookups.is_empty() {
+            let fields = self.event_data().unwrap_or_default();
+            let lookup = crate::lookup::annotate(lookups, |name| {
+                fields
+                    .get(name)
+                    .map(|value| value.as_cow_str().into_owned())
+            });
+            if !lookup.is_empty() {
+                annotations.push((&["Event", "Lookup"][..], lookup.into()));
+            }
+        }
+
+        #[cfg(feature = "geoip")]
+        {
+            if let Some(enricher) = self.settings.get_geoip() {
+                let fields: Vec<(String, String)> = self
+                    .event_data()
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(name, value)| (name, value.as_cow_str().into_owned()))
+                    .collect();
+                let geoip = enricher.annotate(
+                    fields
+                        .iter()
+                        .map(|(name, value)| (name.as_str(), value.as_str())),
+                );
+                if !geoip.is_empty() {
+                    annotations.push((&["Event", "GeoIp"][..], geoip.into()));
+                }
+            }
+        }
+
+        #[cfg(feature = "sysmon")]
+        {
+            if self.settings.should_add_sysmon_events() {
+                if let Ok(Some(event)) = crate::sysmon::SysmonEvent::from_record(self) {
+                    annotations.push((&["Event", "Sysmon"][..], event.to_json()));
+                }
+            }
+        }
+
+        // Event summaries of the enrichment data take precedence over the built-in descriptions.
+        let description = enrichment.and_then(|enrichment| {
+            let provider = self.provider_name()?;
+            enrichment
+                .event_summary(&provider, self.event_id()?)
+                .map(str::to_string)
+        });
+
+        #[cfg(feature = "event_descriptions")]
+        let description = description.or_else(|| {
+            if !self.settings.should_describe_events() {
+                return None;
+            }
+            let provider = self.provider_name()?;
+            crate::event_descriptions::event_description(&provider, self.event_id()?)
+                .map(|description| description.description.to_string())
+        });
+
+        if let Some(description) = description {
+            annotations.push((&["event", "description"][..], description.into()));
+        }
+
+        annotations
+    }
+
+    /// Returns the names of the SIDs (`SidType` values) of the record, by SID.
+    fn resolved_sids(&self) -> serde_json::Map<String, serde_json::Value> {
+        let enrichment = self.settings.get_enrichment();
+        let mut resolved = serde_json::Map::new();
+
+        visit_values(self.tokens_or_empty(), &mut |value| {
+            if let BinXmlValue::SidType(sid) = value {
+                let sid = sid.to_string();
+                let name = enrichment
+                    .and_then(|enrichment| enrichment.sid_name(&sid))
+                    .or_else(|| well_known_sid_name(&sid));
+
+                if let Some(name) = name {
+                    resolved.insert(sid, name.into());
+                }
+            }
+        });
+
+        resolved
+    }
+
     /// Consumes the record and parse it, producing a JSON serialized record.
     pub fn into_json(self) -> Result<SerializedEvtxRecord<String>> {
         let indent = self.settings.should_indent();
@@ -101,15 +668,53 @@ impl<'a> EvtxRecord<'a> {
             event_record_id: record_with_json_value.event_record_id,
             timestamp: record_with_json_value.timestamp,
             data,
+            recovered: record_with_json_value.recovered,
+            errors: record_with_json_value.errors,
         })
     }
 
     /// Consumes the record and parse it, producing an XML serialized record.
     pub fn into_xml(self) -> Result<SerializedEvtxRecord<String>> {
+        let rendering_info = if self
+            .settings
+            .should_render_symbolic_names(RenderFormat::Xml)
+        {
+            Some(RenderingInfo::of_record(&self))
+        } else {
+            None
+        };
+        let indent = self.settings.should_indent();
+
+        let mut record = self.render_xml()?;
+        if let Some(info) = rendering_info {
+            info.insert_into_xml(&mut record.data, indent);
+        }
+
+        Ok(record)
+    }
+
+    fn render_xml(self) -> Result<SerializedEvtxRecord<String>> {
+        if let Some(cache) = self.settings.get_skeleton_cache() {
+            if let Some(data) = cache.render_xml(self.tokens_or_empty(), &self.settings) {
+                self.settings.count(Counter::XmlSkeletonHits, 1);
+
+                return Ok(SerializedEvtxRecord {
+                    event_record_id: self.event_record_id,
+                    timestamp: self.timestamp,
+                    data,
+                    recovered: self.recovered,
+                    errors: self.parse_errors(),
+                });
+            }
+            self.settings.count(Counter::XmlSkeletonMisses, 1);
+        }
+
         let mut output_builder = XmlOutput::with_writer(Vec::new(), &self.settings);
 
         let event_record_id = self.event_record_id;
         let timestamp = self.timestamp;
+        let recovered = self.recovered;
+        let errors = self.parse_errors();
         self.into_output(&mut output_builder)?;
 
         let data = String::from_utf8(output_builder.into_writer()?)
@@ -119,6 +724,35 @@ impl<'a> EvtxRecord<'a> {
             event_record_id,
             timestamp,
             data,
+            recovered,
+            errors,
         })
     }
 }
+
+fn collect_parse_errors(tokens: &[BinXMLDeserializedTokens], errors: &mut Vec<String>) {
+    visit_values(tokens, &mut |value| {
+        if let BinXmlValue::ParseError(message) = value {
+            errors.push(message.clone())
+        }
+    });
+}
+
+/// Calls `f` with every value (and substitution) of `tokens`, including those of nested BinXml values.
+fn visit_values(tokens: &[BinXMLDeserializedTokens], f: &mut impl FnMut(&BinXmlValue)) {
+    let mut visit_value = |value: &BinXmlValue| match value {
+        BinXmlValue::BinXmlType(tokens) => visit_values(tokens, f),
+        value => f(value),
+    };
+
+    for token in tokens {
+        match token {
+            BinXMLDeserializedTokens::Value(value) => visit_value(value),
+            BinXMLDeserializedTokens::TemplateInstance(template) => template
+                .substitution_array
+                .iter()
+                .for_each(&mut visit_value),
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,1073 @@
+//! Writing of EVTX files, see `EvtxWriter`.
+//!
+//! Records are trees of elements (`EventElement`), built in memory or copied from the records of a parser.
+//! Every record is written as an instance of a template derived from it's structure (the names of it's
+//! elements and attributes, and the types of it's values), all the values being substitutions.
+//! Like Windows does, the names and the template definitions are stored once per chunk (the first
+//! record using them holds them) and are listed in the string and template tables of the chunk header.
+//!
+//! ```rust
+//! use evtx::binxml::value_variant::BinXmlValue;
+//! use evtx::{EventElement, EvtxParser, EvtxWriter};
+//! use std::io::Cursor;
+//!
+//! let event = EventElement::new("Event")
+//!     .child(
+//!         EventElement::new("System")
+//!             .child(EventElement::new("Provider").attribute("Name", BinXmlValue::StringType("Example".into())))
+//!             .child(EventElement::new("EventID").value(BinXmlValue::UInt16Type(4624))),
+//!     )
+//!     .child(EventElement::new("EventData").child(EventElement::new("Data").text("Hello")));
+//!
+//! let mut writer = EvtxWriter::new(Cursor::new(vec![])).unwrap();
+//! writer.write_record(chrono::Utc::now(), &event).unwrap();
+//! let data = writer.finish().unwrap().into_inner();
+//!
+//! let mut parser = EvtxParser::from_buffer(data).unwrap();
+//! let record = parser.records_json_value().next().unwrap().unwrap();
+//! assert_eq!(record.data["Event"]["System"]["EventID"], 4624);
+//! ```
+use crate::binxml::value_variant::BinXmlValue;
+use crate::err::{self, Result};
+use crate::evtx_chunk::EVTX_CHUNK_HEADER_SIZE;
+use crate::evtx_parser::{EVTX_CHUNK_SIZE, EVTX_FILE_HEADER_SIZE};
+use crate::evtx_record::{EvtxRecord, EvtxRecordOwned};
+use crate::import::ImportFormat;
+use crate::model::xml::XmlElement;
+use crate::utils::{to_filetime, to_systemtime};
+use crate::xml_output::BinXmlOutput;
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use snafu::ResultExt;
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+const STRING_TABLE_SIZE: usize = 64;
+const TEMPLATE_TABLE_SIZE: usize = 32;
+
+/// An element of a record to write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventElement {
+    pub name: String,
+    /// Attributes with a `NullType` value are not written.
+    pub attributes: Vec<(String, BinXmlValue<'static>)>,
+    pub children: Vec<EventContent>,
+}
+
+/// The content of an element, in document order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EventContent {
+    Element(EventElement),
+    /// A text value, not written if it is `NullType`.
+    Value(BinXmlValue<'static>),
+}
+
+impl EventElement {
+    pub fn new(name: impl Into<String>) -> Self {
+        EventElement {
+            name: name.into(),
+            attributes: vec![],
+            children: vec![],
+        }
+    }
+
+    pub fn attribute(mut self, name: impl Into<String>, value: BinXmlValue<'static>) -> Self {
+        self.attributes.push((name.into(), value));
+        self
+    }
+
+    pub fn child(mut self, element: EventElement) -> Self {
+        self.children.push(EventContent::Element(element));
+        self
+    }
+
+    pub fn value(mut self, value: BinXmlValue<'static>) -> Self {
+        self.children.push(EventContent::Value(value));
+        self
+    }
+
+    /// Same as `value`, with a string.
+    pub fn text(self, text: impl Into<String>) -> Self {
+        self.value(BinXmlValue::StringType(Cow::Owned(text.into())))
+    }
+
+    /// Returns the first child element named `name`.
+    pub fn find(&self, name: &str) -> Option<&EventElement> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    /// Same as `find`, for a mutable element.
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut EventElement> {
+        self.children.iter_mut().find_map(|content| match content {
+            EventContent::Element(element) if element.name == name => Some(element),
+            _ => None,
+        })
+    }
+
+    /// Returns the child elements.
+    pub fn elements(&self) -> impl Iterator<Item = &EventElement> {
+        self.children.iter().filter_map(|content| match content {
+            EventContent::Element(element) => Some(element),
+            EventContent::Value(_) => None,
+        })
+    }
+
+    /// Builds the element tree of a parsed record (with it's templates expanded),
+    /// redacted if the parser settings have a `redactor`.
+    pub fn from_record(record: &EvtxRecord) -> Result<Self> {
+        let mut builder = TreeBuilder::default();
+        crate::binxml::assemble::parse_tokens(record.tokens()?.to_vec(), &mut builder)?;
+
+        let mut element = builder
+            .root
+            .ok_or_else(|| err::Error::FailedToCreateRecordModel {
+                message: "The record has no root element".to_string(),
+            })?;
+
+        if let Some(redactor) = record.settings.get_redactor() {
+            redactor.redact_element(&mut element);
+        }
+
+        Ok(element)
+    }
+}
+
+/// Builds an `EventElement` from the model of a record.
+#[derive(Default)]
+struct TreeBuilder {
+    stack: Vec<EventElement>,
+    root: Option<EventElement>,
+}
+
+impl BinXmlOutput for TreeBuilder {
+    fn visit_end_of_stream(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_open_start_element(&mut self, element: &XmlElement) -> Result<()> {
+        let mut event_element = EventElement::new(element.name.as_str());
+        for attribute in &element.attributes {
+            event_element.attributes.push((
+                attribute.name.as_str().to_string(),
+                attribute.value.to_owned_value(),
+            ));
+        }
+
+        self.stack.push(event_element);
+        Ok(())
+    }
+
+    fn visit_close_element(&mut self, _element: &XmlElement) -> Result<()> {
+        let element = match self.stack.pop() {
+            Some(element) => element,
+            None => return Ok(()),
+        };
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(EventContent::Element(element)),
+            None => self.root = Some(element),
+        }
+        Ok(())
+    }
+
+    fn visit_characters(&mut self, value: &BinXmlValue) -> Result<()> {
+        if let Some(element) = self.stack.last_mut() {
+            element
+                .children
+                .push(EventContent::Value(value.to_owned_value()));
+        }
+        Ok(())
+    }
+
+    fn visit_cdata_section(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_entity_reference(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_processing_instruction_target(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_processing_instruction_data(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_start_of_stream(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes records to a new EVTX file, see the module documentation.
+///
+/// Chunks are written as they are filled, the file header is written by `finish`.
+pub struct EvtxWriter<W: Write + Seek> {
+    writer: W,
+    chunk: ChunkBuilder,
+    chunk_count: u16,
+    next_record_id: u64,
+}
+
+impl EvtxWriter<File> {
+    /// Creates (or truncates) the file at `path`.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::create(path).context(err::FailedToOpenFile { path })?;
+
+        EvtxWriter::new(file)
+    }
+}
+
+impl<W: Write + Seek> EvtxWriter<W> {
+    /// Starts a file at the current position of `writer`.
+    pub fn new(mut writer: W) -> Result<Self> {
+        writer.write_all(&[0; EVTX_FILE_HEADER_SIZE])?;
+
+        Ok(EvtxWriter {
+            writer,
+            chunk: ChunkBuilder::new(),
+            chunk_count: 0,
+            next_record_id: 1,
+        })
+    }
+
+    /// Writes a record with the record ID following the last one written (starting at 1).
+    /// Returns the record ID.
+    pub fn write_record(&mut self, timestamp: DateTime<Utc>, event: &EventElement) -> Result<u64> {
+        let event_record_id = self.next_record_id;
+        self.write_record_with_id(event_record_id, timestamp, event)?;
+
+        Ok(event_record_id)
+    }
+
+    /// Writes a record with the given record ID. The following records are numbered after it.
+    ///
+    /// The `System/EventRecordID` element of the event (if any) is set to the record ID.
+    pub fn write_record_with_id(
+        &mut self,
+        event_record_id: u64,
+        timestamp: DateTime<Utc>,
+        event: &EventElement,
+    ) -> Result<()> {
+        self.write_event(event_record_id, timestamp, event, true)
+            .map(|_| ())
+    }
+
+    /// Writes a record, starting a new chunk if it does not fit in the current one (if `new_chunk` is set).
+    /// Returns `false` if the record was not written, because it required a new chunk.
+    pub(crate) fn write_event(
+        &mut self,
+        event_record_id: u64,
+        timestamp: DateTime<Utc>,
+        event: &EventElement,
+        new_chunk: bool,
+    ) -> Result<bool> {
+        let renumbered = renumber(event, event_record_id);
+        let event = renumbered.as_ref().unwrap_or(event);
+
+        let mut values = vec![];
+        collect_values(event, &mut values);
+        let template = TemplateShape::of(event, &values);
+
+        if !self
+            .chunk
+            .try_add_record(event_record_id, timestamp, event, &values, &template)?
+        {
+            if self.chunk.is_empty() {
+                return Err(err::Error::RecordTooLarge {
+                    size: self.chunk.record_size(event, &values, &template)?,
+                });
+            }
+
+            if !new_chunk {
+                return Ok(false);
+            }
+
+            self.flush_chunk()?;
+
+            if !self
+                .chunk
+                .try_add_record(event_record_id, timestamp, event, &values, &template)?
+            {
+                return Err(err::Error::RecordTooLarge {
+                    size: self.chunk.record_size(event, &values, &template)?,
+                });
+            }
+        }
+
+        self.next_record_id = event_record_id + 1;
+        Ok(true)
+    }
+
+    /// Writes a copy of a parsed record, keeping it's record ID and timestamp.
+    pub fn copy_record(&mut self, record: &EvtxRecord) -> Result<()> {
+        let event = EventElement::from_record(record)?;
+        self.write_record_with_id(record.event_record_id, record.timestamp, &event)
+    }
+
+    /// Copies records (see `copy_record`), such as the records of `EvtxParser::owned_records`.
+    /// Records which failed to parse are skipped (with a warning). Returns the number of records copied.
+    pub fn copy_records(
+        &mut self,
+        records: impl IntoIterator<Item = Result<EvtxRecordOwned>>,
+    ) -> Result<u64> {
+        let mut copied = 0;
+
+        for record in records {
+            match record {
+                Ok(record) => {
+                    self.copy_record(&record)?;
+                    copied += 1;
+                }
+                Err(e) => warn!("Skipped a record which failed to parse: {}", e),
+            }
+        }
+
+        Ok(copied)
+    }
+
+    /// Writes the records rendered as XML or JSON in `input` (see `EventElement::parse_xml` and `EventElement::parse_json`),
+    /// with their `System/EventRecordID` and `System/TimeCreated/@SystemTime` (records without them are numbered
+    /// after the previous record, and timestamped with the current time). Returns the number of records written.
+    pub fn import(&mut self, input: &str, format: ImportFormat) -> Result<u64> {
+        let events = match format {
+            ImportFormat::Xml => EventElement::parse_xml(input)?,
+            ImportFormat::Json => EventElement::parse_json(input)?,
+        };
+
+        for event in &events {
+            let timestamp = event.time_created().unwrap_or_else(Utc::now);
+            let event_record_id = event.event_record_id().unwrap_or(self.next_record_id);
+            self.write_record_with_id(event_record_id, timestamp, event)?;
+        }
+
+        Ok(events.len() as u64)
+    }
+
+    /// Returns the number of chunks written so far (not counting the chunk being filled).
+    pub fn chunk_count(&self) -> u16 {
+        self.chunk_count
+    }
+
+    /// Returns the size the file would have if it was finished now.
+    pub fn file_size(&self) -> u64 {
+        let chunks = u64::from(self.chunk_count) + u64::from(!self.chunk.is_empty());
+        EVTX_FILE_HEADER_SIZE as u64 + chunks * EVTX_CHUNK_SIZE as u64
+    }
+
+    /// Writes the last chunk and the file header, and returns the writer (positioned at the end of the file).
+    pub fn finish(mut self) -> Result<W> {
+        if !self.chunk.is_empty() {
+            self.flush_chunk()?;
+        }
+
+        let end = self.writer.stream_position()?;
+        let start = end
+            - EVTX_FILE_HEADER_SIZE as u64
+            - u64::from(self.chunk_count) * EVTX_CHUNK_SIZE as u64;
+
+        self.writer.seek(SeekFrom::Start(start))?;
+        self.writer
+            .write_all(&file_header(self.chunk_count, self.next_record_id))?;
+        self.writer.seek(SeekFrom::Start(end))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn flush_chunk(&mut self) -> Result<()> {
+        if self.chunk_count == u16::MAX {
+            return Err(err::Error::TooManyChunks);
+        }
+
+        let chunk = std::mem::replace(&mut self.chunk, ChunkBuilder::new());
+        self.writer.write_all(&chunk.finish())?;
+        self.chunk_count += 1;
+
+        Ok(())
+    }
+}
+
+/// Returns a copy of `event` with `event_record_id` as it's `System/EventRecordID`,
+/// if it has a different one.
+fn renumber(event: &EventElement, event_record_id: u64) -> Option<EventElement> {
+    let id = BinXmlValue::UInt64Type(event_record_id);
+    let current = event.find("System")?.find("EventRecordID")?;
+    if current.children == [EventContent::Value(id.clone())] {
+        return None;
+    }
+
+    let mut event = event.clone();
+    let element = event.find_mut("System")?.find_mut("EventRecordID")?;
+    element.children = vec![EventContent::Value(id)];
+
+    Some(event)
+}
+
+/// Returns the file header (the whole header block) of a file with `chunk_count` chunks.
+pub(crate) fn file_header(chunk_count: u16, next_record_id: u64) -> Vec<u8> {
+    let mut header = vec![0; EVTX_FILE_HEADER_SIZE];
+
+    header[..8].copy_from_slice(b"ElfFile\x00");
+    // First chunk number (0), last chunk number.
+    header[16..24].copy_from_slice(&u64::from(chunk_count.saturating_sub(1)).to_le_bytes());
+    header[24..32].copy_from_slice(&next_record_id.to_le_bytes());
+    header[32..36].copy_from_slice(&128_u32.to_le_bytes());
+    // Minor and major versions (3.1).
+    header[36..38].copy_from_slice(&1_u16.to_le_bytes());
+    header[38..40].copy_from_slice(&3_u16.to_le_bytes());
+    header[40..42].copy_from_slice(&(EVTX_FILE_HEADER_SIZE as u16).to_le_bytes());
+    header[42..44].copy_from_slice(&chunk_count.to_le_bytes());
+    let checksum = crc32fast::hash(&header[..120]);
+    header[124..128].copy_from_slice(&checksum.to_le_bytes());
+
+    header
+}
+
+/// The structure of a record: it's elements and attributes, and the types of it's values.
+struct TemplateShape {
+    guid: [u8; 16],
+}
+
+impl TemplateShape {
+    fn of(event: &EventElement, values: &[&BinXmlValue]) -> Self {
+        let mut shape = vec![];
+        describe_shape(event, &mut shape);
+        for value in values {
+            shape.push(value_type(value));
+        }
+
+        // Two FNV-1a hashes (with different offset bases) of the shape.
+        let hash = |basis: u64| {
+            shape.iter().fold(basis, |hash, byte| {
+                (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+            })
+        };
+
+        let mut guid = [0; 16];
+        guid[..8].copy_from_slice(&hash(0xcbf2_9ce4_8422_2325).to_le_bytes());
+        guid[8..].copy_from_slice(&hash(0x8422_2325_cbf2_9ce4).to_le_bytes());
+
+        TemplateShape { guid }
+    }
+
+    fn id(&self) -> u32 {
+        u32::from_le_bytes([self.guid[0], self.guid[1], self.guid[2], self.guid[3]])
+    }
+}
+
+fn describe_shape(element: &EventElement, shape: &mut Vec<u8>) {
+    shape.push(b'<');
+    shape.extend_from_slice(element.name.as_bytes());
+    for (name, _) in &element.attributes {
+        shape.push(b' ');
+        shape.extend_from_slice(name.as_bytes());
+    }
+    shape.push(b'>');
+
+    for content in &element.children {
+        match content {
+            EventContent::Element(child) => describe_shape(child, shape),
+            EventContent::Value(_) => shape.push(b'$'),
+        }
+    }
+
+    shape.push(b'/');
+}
+
+/// Collects the values of the element, in the order of their substitutions:
+/// the attributes of an element, then it's content.
+fn collect_values<'e>(element: &'e EventElement, values: &mut Vec<&'e BinXmlValue<'static>>) {
+    for (_, value) in &element.attributes {
+        values.push(value);
+    }
+
+    for content in &element.children {
+        match content {
+            EventContent::Element(child) => collect_values(child, values),
+            EventContent::Value(value) => values.push(value),
+        }
+    }
+}
+
+/// A chunk being filled with records.
+struct ChunkBuilder {
+    data: Vec<u8>,
+    /// Name -> offset.
+    names: HashMap<String, u32>,
+    string_table: [u32; STRING_TABLE_SIZE],
+    /// Template GUID -> offset of the definition.
+    templates: HashMap<[u8; 16], u32>,
+    template_table: [u32; TEMPLATE_TABLE_SIZE],
+    first_record_id: Option<u64>,
+    last_record_id: u64,
+    last_record_offset: u32,
+}
+
+impl ChunkBuilder {
+    fn new() -> Self {
+        ChunkBuilder {
+            data: vec![0; EVTX_CHUNK_HEADER_SIZE],
+            names: HashMap::new(),
+            string_table: [0; STRING_TABLE_SIZE],
+            templates: HashMap::new(),
+            template_table: [0; TEMPLATE_TABLE_SIZE],
+            first_record_id: None,
+            last_record_id: 0,
+            last_record_offset: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.first_record_id.is_none()
+    }
+
+    /// Appends a record, returns `false` (leaving the chunk unchanged) if it does not fit.
+    fn try_add_record(
+        &mut self,
+        event_record_id: u64,
+        timestamp: DateTime<Utc>,
+        event: &EventElement,
+        values: &[&BinXmlValue],
+        template: &TemplateShape,
+    ) -> Result<bool> {
+        let offset = self.data.len();
+        let mut encoder = RecordEncoder::new(self, offset);
+        encoder.record(event_record_id, timestamp, event, values, template)?;
+
+        if offset + encoder.data.len() > EVTX_CHUNK_SIZE {
+            return Ok(false);
+        }
+
+        let RecordEncoder {
+            data,
+            new_names,
+            string_table,
+            new_template,
+            template_table,
+            ..
+        } = encoder;
+
+        self.data.extend_from_slice(&data);
+        self.names.extend(new_names);
+        self.string_table = string_table;
+        self.templates.extend(new_template);
+        self.template_table = template_table;
+        self.first_record_id.get_or_insert(event_record_id);
+        self.last_record_id = event_record_id;
+        self.last_record_offset = offset as u32;
+
+        Ok(true)
+    }
+
+    /// The size of a record in this chunk (names and templates it shares with earlier records are not counted).
+    fn record_size(
+        &self,
+        event: &EventElement,
+        values: &[&BinXmlValue],
+        template: &TemplateShape,
+    ) -> Result<usize> {
+        let mut encoder = RecordEncoder::new(self, self.data.len());
+        encoder.record(0, Utc::now(), event, values, template)?;
+        Ok(encoder.data.len())
+    }
+
+    /// Returns the data of the chunk, with it's header and checksums.
+    fn finish(self) -> Vec<u8> {
+        let free_space_offset = self.data.len();
+        let mut data = self.data;
+        data.resize(EVTX_CHUNK_SIZE, 0);
+
+        let first_record_id = self.first_record_id.unwrap_or_default();
+        let events_checksum = crc32fast::hash(&data[EVTX_CHUNK_HEADER_SIZE..free_space_offset]);
+
+        let header = &mut data[..EVTX_CHUNK_HEADER_SIZE];
+        header[..8].copy_from_slice(b"ElfChnk\x00");
+        // Record numbers, then record IDs.
+        header[8..16].copy_from_slice(&first_record_id.to_le_bytes());
+        header[16..24].copy_from_slice(&self.last_record_id.to_le_bytes());
+        header[24..32].copy_from_slice(&first_record_id.to_le_bytes());
+        header[32..40].copy_from_slice(&self.last_record_id.to_le_bytes());
+        header[40..44].copy_from_slice(&128_u32.to_le_bytes());
+        header[44..48].copy_from_slice(&self.last_record_offset.to_le_bytes());
+        header[48..52].copy_from_slice(&(free_space_offset as u32).to_le_bytes());
+        header[52..56].copy_from_slice(&events_checksum.to_le_bytes());
+
+        for (i, offset) in self.string_table.iter().enumerate() {
+            header[128 + i * 4..132 + i * 4].copy_from_slice(&offset.to_le_bytes());
+        }
+        for (i, offset) in self.template_table.iter().enumerate() {
+            header[384 + i * 4..388 + i * 4].copy_from_slice(&offset.to_le_bytes());
+        }
+
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&header[..120]);
+        hasher.update(&header[128..]);
+        header[124..128].copy_from_slice(&hasher.finalize().to_le_bytes());
+
+        data
+    }
+}
+
+/// Encodes a record at `offset` in a chunk, keeping track of the names and the template it adds to the chunk.
+struct RecordEncoder<'c> {
+    chunk: &'c ChunkBuilder,
+    offset: usize,
+    data: Vec<u8>,
+    new_names: HashMap<String, u32>,
+    string_table: [u32; STRING_TABLE_SIZE],
+    new_template: Option<([u8; 16], u32)>,
+    template_table: [u32; TEMPLATE_TABLE_SIZE],
+}
+
+impl<'c> RecordEncoder<'c> {
+    fn new(chunk: &'c ChunkBuilder, offset: usize) -> Self {
+        RecordEncoder {
+            chunk,
+            offset,
+            data: vec![],
+            new_names: HashMap::new(),
+            string_table: chunk.string_table,
+            new_template: None,
+            template_table: chunk.template_table,
+        }
+    }
+
+    /// The offset of the next byte in the chunk.
+    fn position(&self) -> u32 {
+        (self.offset + self.data.len()) as u32
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn patch_u32(&mut self, at: usize, value: u32) {
+        self.data[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    fn record(
+        &mut self,
+        event_record_id: u64,
+        timestamp: DateTime<Utc>,
+        event: &EventElement,
+        values: &[&BinXmlValue],
+        template: &TemplateShape,
+    ) -> Result<()> {
+        self.data.extend_from_slice(b"\x2a\x2a\x00\x00");
+        self.u32(0);
+        self.data.extend_from_slice(&event_record_id.to_le_bytes());
+        self.data
+            .extend_from_slice(&to_filetime(&timestamp).to_le_bytes());
+
+        // Fragment header (version 1.1), and the template instance.
+        self.data.extend_from_slice(&[0x0f, 0x01, 0x01, 0x00]);
+        self.data.extend_from_slice(&[0x0c, 0x01]);
+        self.u32(template.id());
+
+        match self.chunk.templates.get(&template.guid) {
+            Some(&definition_offset) => self.u32(definition_offset),
+            None => {
+                let definition_offset = self.position() + 4;
+                self.u32(definition_offset);
+                self.template_definition(event, values, template, definition_offset);
+            }
+        }
+
+        self.u32(values.len() as u32);
+        let mut encoded_values = Vec::with_capacity(values.len());
+        for value in values {
+            let mut bytes = vec![];
+            let value_type = encode_value(value, &mut bytes)?;
+            let size = u16::try_from(bytes.len()).map_err(|_| err::Error::FailedToWriteValue {
+                message: format!("a value of {} bytes is too large", bytes.len()),
+            })?;
+
+            self.u16(size);
+            self.data.extend_from_slice(&[value_type, 0]);
+            encoded_values.push(bytes);
+        }
+        for bytes in encoded_values {
+            self.data.extend_from_slice(&bytes);
+        }
+
+        // End of stream, and padding to 8 bytes (with the trailing copy of the size).
+        self.data.push(0x00);
+        let padding = (8 - (self.data.len() + 4) % 8) % 8;
+        self.data.resize(self.data.len() + padding, 0);
+
+        let size = (self.data.len() + 4) as u32;
+        self.patch_u32(4, size);
+        self.u32(size);
+
+        Ok(())
+    }
+
+    fn template_definition(
+        &mut self,
+        event: &EventElement,
+        values: &[&BinXmlValue],
+        template: &TemplateShape,
+        definition_offset: u32,
+    ) {
+        let bucket = template.id() as usize % TEMPLATE_TABLE_SIZE;
+        self.u32(self.template_table[bucket]);
+        self.template_table[bucket] = definition_offset;
+        self.new_template = Some((template.guid, definition_offset));
+
+        self.data.extend_from_slice(&template.guid);
+        let data_size_at = self.data.len();
+        self.u32(0);
+
+        let start = self.data.len();
+        self.data.extend_from_slice(&[0x0f, 0x01, 0x01, 0x00]);
+        let mut next_value = 0;
+        self.element(event, values, &mut next_value);
+        self.data.push(0x00);
+
+        let data_size = (self.data.len() - start) as u32;
+        self.patch_u32(data_size_at, data_size);
+    }
+
+    fn element(&mut self, element: &EventElement, values: &[&BinXmlValue], next_value: &mut u16) {
+        let has_attributes = !element.attributes.is_empty();
+
+        self.data.push(if has_attributes { 0x41 } else { 0x01 });
+        // Dependency identifier (none).
+        self.u16(0xffff);
+        let size_at = self.data.len();
+        self.u32(0);
+        self.name(&element.name);
+
+        if has_attributes {
+            let attributes_size_at = self.data.len();
+            self.u32(0);
+            let attributes_start = self.data.len();
+
+            for (i, (name, _)) in element.attributes.iter().enumerate() {
+                let more_attributes = i + 1 < element.attributes.len();
+                self.data.push(if more_attributes { 0x46 } else { 0x06 });
+                self.name(name);
+                self.substitution(values, next_value);
+            }
+
+            let attributes_size = (self.data.len() - attributes_start) as u32;
+            self.patch_u32(attributes_size_at, attributes_size);
+        }
+
+        if element.children.is_empty() {
+            self.data.push(0x03);
+        } else {
+            self.data.push(0x02);
+            for content in &element.children {
+                match content {
+                    EventContent::Element(child) => self.element(child, values, next_value),
+                    EventContent::Value(_) => self.substitution(values, next_value),
+                }
+            }
+            self.data.push(0x04);
+        }
+
+        let size = (self.data.len() - size_at - 4) as u32;
+        self.patch_u32(size_at, size);
+    }
+
+    /// An optional substitution, so that `NullType` values are not rendered.
+    fn substitution(&mut self, values: &[&BinXmlValue], next_value: &mut u16) {
+        let value_type = value_type(values[*next_value as usize]);
+
+        self.data.push(0x0e);
+        self.u16(*next_value);
+        self.data.push(value_type);
+        *next_value += 1;
+    }
+
+    /// A reference to a name of the chunk, written inline the first time it is used.
+    fn name(&mut self, name: &str) {
+        if let Some(&offset) = self
+            .chunk
+            .names
+            .get(name)
+            .or_else(|| self.new_names.get(name))
+        {
+            self.u32(offset);
+            return;
+        }
+
+        let offset = self.position() + 4;
+        self.u32(offset);
+
+        let hash = name_hash(name);
+        let bucket = hash as usize % STRING_TABLE_SIZE;
+        let units: Vec<u16> = name.encode_utf16().collect();
+
+        self.u32(self.string_table[bucket]);
+        self.u16(hash);
+        self.u16(units.len() as u16);
+        for unit in units {
+            self.u16(unit);
+        }
+        self.u16(0);
+
+        self.string_table[bucket] = offset;
+        self.new_names.insert(name.to_string(), offset);
+    }
+}
+
+/// The hash of the names of a chunk's string table.
+fn name_hash(name: &str) -> u16 {
+    let hash = name.encode_utf16().fold(0_u32, |hash, unit| {
+        hash.wrapping_mul(65599).wrapping_add(u32::from(unit))
+    });
+
+    hash as u16
+}
+
+/// The type of a substitution, see `BinXmlValueType::from_u8`.
+fn value_type(value: &BinXmlValue) -> u8 {
+    match value {
+        BinXmlValue::NullType => 0x00,
+        // ANSI strings are written as UTF-16 strings.
+        BinXmlValue::StringType(_) | BinXmlValue::AnsiStringType(_) => 0x01,
+        BinXmlValue::Int8Type(_) => 0x03,
+        BinXmlValue::UInt8Type(_) => 0x04,
+        BinXmlValue::Int16Type(_) => 0x05,
+        BinXmlValue::UInt16Type(_) => 0x06,
+        BinXmlValue::Int32Type(_) => 0x07,
+        BinXmlValue::UInt32Type(_) => 0x08,
+        BinXmlValue::Int64Type(_) => 0x09,
+        // `SizeT` values are written as 64 bit integers.
+        BinXmlValue::UInt64Type(_) | BinXmlValue::SizeTType(_) => 0x0a,
+        BinXmlValue::Real32Type(_) => 0x0b,
+        BinXmlValue::Real64Type(_) => 0x0c,
+        BinXmlValue::BoolType(_) => 0x0d,
+        BinXmlValue::BinaryType(_) => 0x0e,
+        BinXmlValue::GuidType(_) => 0x0f,
+        BinXmlValue::FileTimeType(_) => 0x11,
+        BinXmlValue::SysTimeType(_) => 0x12,
+        BinXmlValue::SidType(_) => 0x13,
+        BinXmlValue::HexInt32Type(_) => 0x14,
+        BinXmlValue::HexInt64Type(_) => 0x15,
+        BinXmlValue::StringArrayType(_) => 0x81,
+        BinXmlValue::Int8ArrayType(_) => 0x83,
+        BinXmlValue::UInt8ArrayType(_) => 0x84,
+        BinXmlValue::Int16ArrayType(_) => 0x85,
+        BinXmlValue::UInt16ArrayType(_) => 0x86,
+        BinXmlValue::Int32ArrayType(_) => 0x87,
+        BinXmlValue::UInt32ArrayType(_) => 0x88,
+        BinXmlValue::Int64ArrayType(_) => 0x89,
+        BinXmlValue::UInt64ArrayType(_) => 0x8a,
+        BinXmlValue::Real32ArrayType(_) => 0x8b,
+        BinXmlValue::Real64ArrayType(_) => 0x8c,
+        BinXmlValue::BoolArrayType(_) => 0x8d,
+        BinXmlValue::GuidArrayType(_) => 0x8f,
+        BinXmlValue::FileTimeArrayType(_) => 0x91,
+        BinXmlValue::SysTimeArrayType(_) => 0x92,
+        BinXmlValue::SidArrayType(_) => 0x93,
+        BinXmlValue::HexInt32ArrayType(_) => 0x94,
+        BinXmlValue::HexInt64ArrayType(_) => 0x95,
+        // Not writable, `encode_value` fails on these.
+        _ => 0x00,
+    }
+}
+
+/// Appends the data of a substitution value to `out`, returning it's type.
+fn encode_value(value: &BinXmlValue, out: &mut Vec<u8>) -> Result<u8> {
+    fn utf16(s: &str, out: &mut Vec<u8>) {
+        for unit in s.encode_utf16() {
+            out.extend_from_slice(&unit.to_le_bytes());
+        }
+    }
+
+    fn hex(s: &str) -> Result<u64> {
+        let digits = s.trim_start_matches("0x").trim_start_matches("0X");
+        u64::from_str_radix(digits, 16).map_err(|_| err::Error::FailedToWriteValue {
+            message: format!("`{}` is not an hexadecimal number", s),
+        })
+    }
+
+    match value {
+        BinXmlValue::NullType => {}
+        BinXmlValue::StringType(s) | BinXmlValue::AnsiStringType(s) => utf16(s, out),
+        BinXmlValue::Int8Type(v) => out.extend_from_slice(&v.to_le_bytes()),
+        BinXmlValue::UInt8Type(v) => out.push(*v),
+        BinXmlValue::Int16Type(v) => out.extend_from_slice(&v.to_le_bytes()),
+        BinXmlValue::UInt16Type(v) => out.extend_from_slice(&v.to_le_bytes()),
+        BinXmlValue::Int32Type(v) => out.extend_from_slice(&v.to_le_bytes()),
+        BinXmlValue::UInt32Type(v) => out.extend_from_slice(&v.to_le_bytes()),
+        BinXmlValue::Int64Type(v) => out.extend_from_slice(&v.to_le_bytes()),
+        BinXmlValue::UInt64Type(v) => out.extend_from_slice(&v.to_le_bytes()),
+        BinXmlValue::SizeTType(v) => out.extend_from_slice(&(*v as u64).to_le_bytes()),
+        BinXmlValue::Real32Type(v) => out.extend_from_slice(&v.to_le_bytes()),
+        BinXmlValue::Real64Type(v) => out.extend_from_slice(&v.to_le_bytes()),
+        BinXmlValue::BoolType(v) => out.extend_from_slice(&i32::from(*v).to_le_bytes()),
+        BinXmlValue::BinaryType(bytes) => out.extend_from_slice(bytes),
+        BinXmlValue::GuidType(guid) => out.extend_from_slice(&guid.to_bytes()),
+        BinXmlValue::FileTimeType(time) => out.extend_from_slice(&to_filetime(time).to_le_bytes()),
+        BinXmlValue::SysTimeType(time) => out.extend_from_slice(&to_systemtime(time)),
+        BinXmlValue::SidType(sid) => out.extend_from_slice(&sid.to_bytes()),
+        BinXmlValue::HexInt32Type(s) => out.extend_from_slice(&(hex(s)? as u32).to_le_bytes()),
+        BinXmlValue::HexInt64Type(s) => out.extend_from_slice(&hex(s)?.to_le_bytes()),
+        BinXmlValue::StringArrayType(strings) => {
+            for s in strings {
+                utf16(s, out);
+                out.extend_from_slice(&[0, 0]);
+            }
+        }
+        BinXmlValue::Int8ArrayType(v) => v.iter().for_each(|n| out.extend(&n.to_le_bytes())),
+        BinXmlValue::UInt8ArrayType(v) => out.extend_from_slice(v),
+        BinXmlValue::Int16ArrayType(v) => v.iter().for_each(|n| out.extend(&n.to_le_bytes())),
+        BinXmlValue::UInt16ArrayType(v) => v.iter().for_each(|n| out.extend(&n.to_le_bytes())),
+        BinXmlValue::Int32ArrayType(v) => v.iter().for_each(|n| out.extend(&n.to_le_bytes())),
+        BinXmlValue::UInt32ArrayType(v) => v.iter().for_each(|n| out.extend(&n.to_le_bytes())),
+        BinXmlValue::Int64ArrayType(v) => v.iter().for_each(|n| out.extend(&n.to_le_bytes())),
+        BinXmlValue::UInt64ArrayType(v) => v.iter().for_each(|n| out.extend(&n.to_le_bytes())),
+        BinXmlValue::Real32ArrayType(v) => v.iter().for_each(|n| out.extend(&n.to_le_bytes())),
+        BinXmlValue::Real64ArrayType(v) => v.iter().for_each(|n| out.extend(&n.to_le_bytes())),
+        BinXmlValue::BoolArrayType(v) => v
+            .iter()
+            .for_each(|b| out.extend(&i32::from(*b).to_le_bytes())),
+        BinXmlValue::GuidArrayType(v) => v.iter().for_each(|g| out.extend(&g.to_bytes())),
+        BinXmlValue::FileTimeArrayType(v) => v
+            .iter()
+            .for_each(|t| out.extend(&to_filetime(t).to_le_bytes())),
+        BinXmlValue::SysTimeArrayType(v) => v.iter().for_each(|t| out.extend(&to_systemtime(t))),
+        BinXmlValue::SidArrayType(v) => v.iter().for_each(|sid| out.extend(sid.to_bytes())),
+        BinXmlValue::HexInt32ArrayType(v) => {
+            for s in v {
+                out.extend_from_slice(&(hex(s)? as u32).to_le_bytes());
+            }
+        }
+        BinXmlValue::HexInt64ArrayType(v) => {
+            for s in v {
+                out.extend_from_slice(&hex(s)?.to_le_bytes());
+            }
+        }
+        other => {
+            return Err(err::Error::FailedToWriteValue {
+                message: format!("cannot write {:?}", other),
+            })
+        }
+    }
+
+    Ok(value_type(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EvtxParser, ParserSettings};
+    use chrono::TimeZone;
+    use std::io::Cursor;
+
+    fn event(event_id: u16, data: &str) -> EventElement {
+        EventElement::new("Event")
+            .attribute(
+                "xmlns",
+                BinXmlValue::StringType(
+                    "http://schemas.microsoft.com/win/2004/08/events/event".into(),
+                ),
+            )
+            .child(
+                EventElement::new("System")
+                    .child(
+                        EventElement::new("Provider")
+                            .attribute("Name", BinXmlValue::StringType("Test".into())),
+                    )
+                    .child(EventElement::new("EventID").value(BinXmlValue::UInt16Type(event_id)))
+                    .child(EventElement::new("Computer").text("WORKSTATION")),
+            )
+            .child(
+                EventElement::new("EventData").child(
+                    EventElement::new("Data")
+                        .attribute("Name", BinXmlValue::StringType("Message".into()))
+                        .text(data),
+                ),
+            )
+    }
+
+    #[test]
+    fn test_written_records_are_parsed_back() {
+        let timestamp = Utc.timestamp(1_600_000_000, 123_456_000);
+        let mut writer = EvtxWriter::new(Cursor::new(vec![])).unwrap();
+
+        // Enough records for several chunks.
+        for i in 0..2000 {
+            let id = writer
+                .write_record(timestamp, &event(i % 7, &"x".repeat(i as usize % 50)))
+                .unwrap();
+            assert_eq!(id, u64::from(i) + 1);
+        }
+        let data = writer.finish().unwrap().into_inner();
+        assert_eq!((data.len() - EVTX_FILE_HEADER_SIZE) % EVTX_CHUNK_SIZE, 0);
+
+        let mut parser = EvtxParser::from_buffer(data)
+            .unwrap()
+            .with_configuration(ParserSettings::new().validate_checksums(true));
+        assert!(parser.header().chunk_count > 1);
+
+        let records: Vec<_> = parser.records_json_value().map(Result::unwrap).collect();
+        assert_eq!(records.len(), 2000);
+
+        let record = &records[12];
+        assert_eq!(record.event_record_id, 13);
+        assert_eq!(record.timestamp, timestamp);
+        assert_eq!(record.data["Event"]["System"]["EventID"], 5);
+        assert_eq!(
+            record.data["Event"]["System"]["Provider"]["#attributes"]["Name"],
+            "Test"
+        );
+        assert_eq!(record.data["Event"]["EventData"]["Message"], "x".repeat(12));
+    }
+
+    #[test]
+    fn test_copied_records_are_identical() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let mut writer = EvtxWriter::new(Cursor::new(vec![])).unwrap();
+        for record in parser.owned_records() {
+            writer.copy_record(&record.unwrap()).unwrap();
+        }
+        let data = writer.finish().unwrap().into_inner();
+
+        let expected: Vec<_> = parser.records().map(Result::unwrap).collect();
+        let mut copy = EvtxParser::from_buffer(data)
+            .unwrap()
+            .with_configuration(ParserSettings::new().validate_checksums(true));
+        let copied: Vec<_> = copy.records().map(Result::unwrap).collect();
+        assert_eq!(copied.len(), expected.len());
+        assert_eq!(copied, expected);
+    }
+
+    #[test]
+    fn test_oversized_records_are_rejected() {
+        let mut writer = EvtxWriter::new(Cursor::new(vec![])).unwrap();
+        let huge = (0..40).fold(EventElement::new("Event"), |event, _| {
+            event.child(EventElement::new("Data").text("x".repeat(1000)))
+        });
+
+        assert!(matches!(
+            writer.write_record(Utc::now(), &huge),
+            Err(err::Error::RecordTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_name_hash() {
+        assert_eq!(name_hash("Event"), 0x0cba);
+        assert_eq!(name_hash("xmlns"), 0x0fbc);
+    }
+}
@@ -0,0 +1,166 @@
+//! A report of every failure met while parsing a file, see `EvtxParser::file_report`.
+use crate::err::Error;
+use crate::integrity::IntegrityReport;
+
+use serde_json::{json, Value};
+
+use std::fmt;
+
+/// The integrity of the file, along with the outcome of parsing every record of it.
+///
+/// Unlike the iterators of the parser, which yield every failure as an individual `Err`,
+/// the report keeps all of them, with where they happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileReport {
+    /// The checksums of the file header and of every chunk, and the chains of record headers.
+    pub integrity: IntegrityReport,
+    /// The number of records which were parsed successfully.
+    pub records_ok: u64,
+    /// The number of records (included in `records_ok`) parsed from chunks whose header was reconstructed.
+    pub records_recovered: u64,
+    /// The number of records recovered from the slack space of the chunks.
+    pub slack_records: u64,
+    /// The chunks which could not be parsed at all.
+    pub chunk_failures: Vec<ChunkFailure>,
+    /// The records which could not be parsed.
+    pub record_failures: Vec<RecordFailure>,
+}
+
+/// A chunk which could not be read or parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkFailure {
+    pub chunk_number: u16,
+    /// The name of the `Error` variant.
+    pub kind: String,
+    pub message: String,
+}
+
+/// A record which could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordFailure {
+    pub chunk_number: u16,
+    /// Offset of the record from the start of the chunk.
+    pub offset: u64,
+    /// The name of the `Error` variant.
+    pub kind: String,
+    pub message: String,
+}
+
+impl ChunkFailure {
+    pub(crate) fn new(chunk_number: u16, error: &Error) -> Self {
+        ChunkFailure {
+            chunk_number,
+            kind: error_kind(error),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl RecordFailure {
+    pub(crate) fn new(chunk_number: u16, offset: u64, error: &Error) -> Self {
+        RecordFailure {
+            chunk_number,
+            offset,
+            kind: error_kind(error),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// The name of the variant of `error`, such as `InvalidEvtxRecordSize`.
+fn error_kind(error: &Error) -> String {
+    format!("{:?}", error)
+        .split(|c: char| !c.is_alphanumeric())
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+impl FileReport {
+    pub(crate) fn new(integrity: IntegrityReport) -> Self {
+        FileReport {
+            integrity,
+            records_ok: 0,
+            records_recovered: 0,
+            slack_records: 0,
+            chunk_failures: vec![],
+            record_failures: vec![],
+        }
+    }
+
+    /// Returns `true` if the file is valid and every record was parsed.
+    pub fn is_clean(&self) -> bool {
+        self.integrity.is_valid()
+            && self.chunk_failures.is_empty()
+            && self.record_failures.is_empty()
+    }
+
+    pub fn to_json_value(&self) -> Value {
+        let chunk_failures: Vec<Value> = self
+            .chunk_failures
+            .iter()
+            .map(|failure| {
+                json!({
+                    "chunk_number": failure.chunk_number,
+                    "kind": failure.kind,
+                    "error": failure.message,
+                })
+            })
+            .collect();
+
+        let record_failures: Vec<Value> = self
+            .record_failures
+            .iter()
+            .map(|failure| {
+                json!({
+                    "chunk_number": failure.chunk_number,
+                    "offset": failure.offset,
+                    "kind": failure.kind,
+                    "error": failure.message,
+                })
+            })
+            .collect();
+
+        json!({
+            "clean": self.is_clean(),
+            "records_ok": self.records_ok,
+            "records_recovered": self.records_recovered,
+            "slack_records": self.slack_records,
+            "chunk_failures": chunk_failures,
+            "record_failures": record_failures,
+            "integrity": self.integrity.to_json_value(),
+        })
+    }
+}
+
+impl fmt::Display for FileReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.integrity)?;
+
+        for failure in &self.chunk_failures {
+            writeln!(
+                f,
+                "Chunk {}: {} ({})",
+                failure.chunk_number, failure.message, failure.kind
+            )?;
+        }
+        for failure in &self.record_failures {
+            writeln!(
+                f,
+                "Chunk {}, record at offset {}: {} ({})",
+                failure.chunk_number, failure.offset, failure.message, failure.kind
+            )?;
+        }
+
+        write!(
+            f,
+            "{}: {} records ({} recovered), {} slack records, {} failed chunks, {} failed records",
+            if self.is_clean() { "CLEAN" } else { "DAMAGED" },
+            self.records_ok,
+            self.records_recovered,
+            self.slack_records,
+            self.chunk_failures.len(),
+            self.record_failures.len()
+        )
+    }
+}
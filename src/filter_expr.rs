@@ -0,0 +1,502 @@
+//! Parser for filter expressions, see `RecordFilter::parse`.
+//!
+//! Grammar:
+//!
+//! ```text
+//! expr       := and ( "||" and )*
+//! and        := unary ( "&&" unary )*
+//! unary      := "!" unary | "(" expr ")" | comparison
+//! comparison := field ( "==" | "!=" | "<" | "<=" | ">" | ">=" ) value | field "~" regex
+//! field      := identifier ( "." identifier )*
+//! value      := number | string
+//! regex      := "/" pattern "/" flags | string
+//! ```
+use crate::err::{self, Result};
+use crate::record_filter::{parse_number, CompareOp, Field, Literal, RecordFilter};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Identifier(String),
+    Number(i128),
+    String(String),
+    Op(CompareOp),
+    /// `~`, matching a regex.
+    Match,
+    /// A `/pattern/flags` literal, with the flags converted to a `(?flags)` prefix.
+    Regex(String),
+    And,
+    Or,
+    Not,
+    OpenParen,
+    CloseParen,
+}
+
+pub(crate) fn parse(expr: &str) -> Result<RecordFilter> {
+    let tokens = tokenize(expr)?;
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        end: expr.chars().count(),
+        depth: 0,
+    };
+
+    let filter = parser.parse_or()?;
+
+    match parser.tokens.get(parser.position) {
+        None => Ok(filter),
+        Some((token, position)) => err::InvalidFilterExpression {
+            message: format!("Unexpected `{:?}`", token),
+            position: *position,
+        }
+        .fail(),
+    }
+}
+
+/// Parses a time given as a date (`2023-01-01`), a UTC date and time
+/// (`2023-01-01 13:00:00` or `2023-01-01T13:00:00`) or an RFC 3339 timestamp.
+pub fn parse_time(s: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Some(time.with_timezone(&Utc));
+    }
+
+    for format in &["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(s, format) {
+            return Some(DateTime::from_utc(time, Utc));
+        }
+    }
+
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .map(|date| DateTime::from_utc(date.and_hms(0, 0, 0), Utc))
+}
+
+/// Reads a `/pattern/flags` literal starting at `start`, where `/` is escaped as `\/`.
+/// Returns the pattern (with the flags as a `(?flags)` prefix) and the position after the literal.
+pub(crate) fn read_regex_literal(chars: &[char], start: usize) -> Result<(String, usize)> {
+    let mut pattern = String::new();
+    let mut i = start + 1;
+
+    loop {
+        match chars.get(i) {
+            None => {
+                return err::InvalidFilterExpression {
+                    message: "Unterminated regex",
+                    position: start,
+                }
+                .fail()
+            }
+            Some('\\') if chars.get(i + 1) == Some(&'/') => {
+                pattern.push('/');
+                i += 2;
+            }
+            Some('\\') if i + 1 < chars.len() => {
+                pattern.push('\\');
+                pattern.push(chars[i + 1]);
+                i += 2;
+            }
+            Some('/') => break,
+            Some(&c) => {
+                pattern.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    i += 1;
+    let flags_start = i;
+
+    while i < chars.len() && chars[i].is_ascii_alphabetic() {
+        if !"imsx".contains(chars[i]) {
+            return err::InvalidFilterExpression {
+                message: format!("Invalid regex flag `{}`", chars[i]),
+                position: i,
+            }
+            .fail();
+        }
+        i += 1;
+    }
+
+    if i > flags_start {
+        let flags: String = chars[flags_start..i].iter().collect();
+        pattern = format!("(?{}){}", flags, pattern);
+    }
+
+    Ok((pattern, i))
+}
+
+fn tokenize(expr: &str) -> Result<Vec<(Token, usize)>> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    let fail = |message: &str, position: usize| {
+        err::InvalidFilterExpression {
+            message: message.to_string(),
+            position,
+        }
+        .fail()
+    };
+
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+
+        let token = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => Token::OpenParen,
+            (')', _) => Token::CloseParen,
+            ('~', _) => Token::Match,
+            ('/', _) => {
+                let (pattern, end) = read_regex_literal(&chars, start)?;
+                tokens.push((Token::Regex(pattern), start));
+                i = end;
+                continue;
+            }
+            ('&', Some('&')) => Token::And,
+            ('|', Some('|')) => Token::Or,
+            ('=', Some('=')) => Token::Op(CompareOp::Eq),
+            ('!', Some('=')) => Token::Op(CompareOp::Ne),
+            ('<', Some('=')) => Token::Op(CompareOp::Le),
+            ('>', Some('=')) => Token::Op(CompareOp::Ge),
+            ('!', _) => Token::Not,
+            ('<', _) => Token::Op(CompareOp::Lt),
+            ('>', _) => Token::Op(CompareOp::Gt),
+            ('"', _) | ('\'', _) => {
+                let mut value = String::new();
+                i += 1;
+
+                loop {
+                    match chars.get(i) {
+                        None => return fail("Unterminated string", start),
+                        Some('\\') if i + 1 < chars.len() => {
+                            value.push(chars[i + 1]);
+                            i += 2;
+                        }
+                        Some(&q) if q == c => break,
+                        Some(&other) => {
+                            value.push(other);
+                            i += 1;
+                        }
+                    }
+                }
+
+                Token::String(value)
+            }
+            (c, _) if c.is_ascii_digit() => {
+                while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                    i += 1;
+                }
+
+                let literal: String = chars[start..i].iter().collect();
+
+                match parse_number(&literal) {
+                    Some(n) => tokens.push((Token::Number(n), start)),
+                    None => return fail(&format!("Invalid number `{}`", literal), start),
+                }
+                continue;
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.')
+                {
+                    i += 1;
+                }
+
+                tokens.push((Token::Identifier(chars[start..i].iter().collect()), start));
+                continue;
+            }
+            (c, _) => return fail(&format!("Unexpected character `{}`", c), start),
+        };
+
+        i += match token {
+            Token::And | Token::Or | Token::Op(CompareOp::Eq) | Token::Op(CompareOp::Ne) => 2,
+            Token::Op(CompareOp::Le) | Token::Op(CompareOp::Ge) => 2,
+            _ => 1,
+        };
+
+        tokens.push((token, start));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Length of the expression, reported when it ends unexpectedly.
+    end: usize,
+    /// The number of enclosing `!` and `(`, limited to `MAX_DEPTH`.
+    depth: usize,
+}
+
+/// The deepest nesting of `!` and `(` accepted, so that hostile expressions cannot overflow the stack.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<(Token, usize)> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => err::InvalidFilterExpression {
+                message: "Unexpected end of expression",
+                position: self.end,
+            }
+            .fail(),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<RecordFilter> {
+        let mut filter = self.parse_and()?;
+
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            filter = filter.or(self.parse_and()?);
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<RecordFilter> {
+        let mut filter = self.parse_unary()?;
+
+        while self.peek() == Some(&Token::And) {
+            self.position += 1;
+            filter = filter.and(self.parse_unary()?);
+        }
+
+        Ok(filter)
+    }
+
+    /// Runs `parse` one nesting level deeper, failing at `position` past `MAX_DEPTH`.
+    fn nested<T>(
+        &mut self,
+        position: usize,
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        if self.depth >= MAX_DEPTH {
+            return err::InvalidFilterExpression {
+                message: format!("Expression is nested deeper than {} levels", MAX_DEPTH),
+                position,
+            }
+            .fail();
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_unary(&mut self) -> Result<RecordFilter> {
+        match self.next()? {
+            (Token::Not, position) => Ok(self.nested(position, Self::parse_unary)?.negate()),
+            (Token::OpenParen, position) => {
+                let filter = self.nested(position, Self::parse_or)?;
+
+                match self.next()? {
+                    (Token::CloseParen, _) => Ok(filter),
+                    (token, position) => err::InvalidFilterExpression {
+                        message: format!("Expected `)`, found `{:?}`", token),
+                        position,
+                    }
+                    .fail(),
+                }
+            }
+            (Token::Identifier(name), position) => self.parse_comparison(&name, position),
+            (token, position) => err::InvalidFilterExpression {
+                message: format!("Expected a field, found `{:?}`", token),
+                position,
+            }
+            .fail(),
+        }
+    }
+
+    fn parse_comparison(&mut self, name: &str, position: usize) -> Result<RecordFilter> {
+        let field = Field::parse(name).ok_or_else(|| err::Error::InvalidFilterExpression {
+            message: format!("Invalid field `{}`", name),
+            position,
+        })?;
+
+        let op = match self.next()? {
+            (Token::Op(op), _) => op,
+            (Token::Match, _) => return self.parse_regex(field),
+            (token, position) => {
+                return err::InvalidFilterExpression {
+                    message: format!("Expected a comparison operator, found `{:?}`", token),
+                    position,
+                }
+                .fail()
+            }
+        };
+
+        let value = match self.next()? {
+            (Token::Number(n), _) => Literal::Number(n),
+            (Token::String(s), position) if field.is_time() => match parse_time(&s) {
+                Some(time) => Literal::Time(time),
+                None => {
+                    return err::InvalidFilterExpression {
+                        message: format!("Invalid time `{}`", s),
+                        position,
+                    }
+                    .fail()
+                }
+            },
+            (Token::String(s), _) => Literal::String(s.to_lowercase()),
+            (token, position) => {
+                return err::InvalidFilterExpression {
+                    message: format!("Expected a value, found `{:?}`", token),
+                    position,
+                }
+                .fail()
+            }
+        };
+
+        Ok(RecordFilter::Compare { field, op, value })
+    }
+
+    fn parse_regex(&mut self, field: Field) -> Result<RecordFilter> {
+        match self.next()? {
+            (Token::Regex(pattern), position) | (Token::String(pattern), position) => {
+                RecordFilter::regex(field, &pattern).map_err(|e| {
+                    err::Error::InvalidFilterExpression {
+                        message: e.to_string(),
+                        position,
+                    }
+                })
+            }
+            (token, position) => err::InvalidFilterExpression {
+                message: format!("Expected a regex, found `{:?}`", token),
+                position,
+            }
+            .fail(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compare(field: Field, op: CompareOp, value: Literal) -> RecordFilter {
+        RecordFilter::Compare { field, op, value }
+    }
+
+    #[test]
+    fn test_parses_expression() {
+        let filter =
+            parse(r#"EventID == 4624 && EventData.LogonType == 10 && TimeCreated >= "2023-01-01""#)
+                .unwrap();
+
+        assert_eq!(
+            filter,
+            RecordFilter::And(vec![
+                compare(
+                    Field::Element("EventID".to_string()),
+                    CompareOp::Eq,
+                    Literal::Number(4624)
+                ),
+                compare(
+                    Field::EventData("LogonType".to_string()),
+                    CompareOp::Eq,
+                    Literal::Number(10)
+                ),
+                compare(
+                    Field::TimeCreated,
+                    CompareOp::Ge,
+                    Literal::Time(parse_time("2023-01-01T00:00:00Z").unwrap())
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let filter =
+            parse("!(Level < 3) || Execution.ProcessID != 0x4 && Computer == 'DC'").unwrap();
+
+        assert_eq!(
+            filter,
+            RecordFilter::Or(vec![
+                compare(
+                    Field::Element("Level".to_string()),
+                    CompareOp::Lt,
+                    Literal::Number(3)
+                )
+                .negate(),
+                compare(
+                    Field::Attribute {
+                        element: "Execution".to_string(),
+                        attribute: "ProcessID".to_string()
+                    },
+                    CompareOp::Ne,
+                    Literal::Number(4)
+                )
+                .and(compare(
+                    Field::Element("Computer".to_string()),
+                    CompareOp::Eq,
+                    Literal::String("dc".to_string())
+                )),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let position = |expr: &str| match parse(expr) {
+            Err(err::Error::InvalidFilterExpression { position, .. }) => position,
+            other => panic!("Expected an error, got {:?}", other),
+        };
+
+        assert_eq!(position("EventID =="), 10);
+        assert_eq!(position("EventID = 4"), 8);
+        assert_eq!(position("(EventID == 4"), 13);
+        assert_eq!(position("EventID == 4 Level"), 13);
+        assert_eq!(position("TimeCreated > 'yesterday'"), 14);
+        assert_eq!(position("Computer == \"DC"), 12);
+        assert_eq!(position("a..b == 1"), 0);
+        assert_eq!(position("Image ~ /a(/"), 8);
+        assert_eq!(position("Image ~ /a/q"), 11);
+        assert_eq!(position("Image ~ /a"), 8);
+        assert_eq!(position("Image ~ 1"), 8);
+
+        let nested = format!("{}EventID == 1{}", "(".repeat(60000), ")".repeat(60000));
+        assert_eq!(position(&nested), MAX_DEPTH);
+        assert_eq!(position(&"!".repeat(60000)), MAX_DEPTH);
+        assert!(parse(&format!(
+            "{}EventID == 1{}",
+            "(".repeat(100),
+            ")".repeat(100)
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_parses_regex() {
+        let filter =
+            parse(r#"EventData.CommandLine ~ /mimikatz|c:\/temp\//i && Image ~ "cmd\\.exe$""#)
+                .unwrap();
+
+        assert_eq!(
+            filter,
+            RecordFilter::regex(
+                Field::EventData("CommandLine".to_string()),
+                r"(?i)mimikatz|c:/temp/"
+            )
+            .unwrap()
+            .and(RecordFilter::regex(Field::Element("Image".to_string()), r"cmd\.exe$").unwrap())
+        );
+    }
+}
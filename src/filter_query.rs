@@ -0,0 +1,402 @@
+//! Parser for simple queries, see `RecordFilter::from_query`.
+//!
+//! Grammar:
+//!
+//! ```text
+//! query := and ( "OR" and )*
+//! and   := unary ( "AND" unary )*
+//! unary := "NOT" unary | "(" query ")" | term
+//! term  := field ( "=" | "!=" | "<" | "<=" | ">" | ">=" | "~" ) value | field "~" regex
+//! field := name ( "." name )*
+//! value := quoted string | word
+//! regex := "/" pattern "/" flags
+//! ```
+use crate::err::{self, Result};
+use crate::filter_expr::{parse_time, read_regex_literal, MAX_DEPTH};
+use crate::record_filter::{parse_number, CompareOp, Field, Literal, RecordFilter};
+
+/// Elements of `System` which can be referred to without a prefix,
+/// other unqualified names refer to `EventData` values.
+const SYSTEM_ELEMENTS: &[&str] = &[
+    "EventID", "Version", "Level", "Task", "Opcode", "Keywords", "Channel", "Computer",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A field name, keyword or unquoted value.
+    Word(String),
+    String(String),
+    Op(CompareOp),
+    Like,
+    /// A `/pattern/flags` literal (only after `~`).
+    Regex(String),
+    OpenParen,
+    CloseParen,
+}
+
+pub(crate) fn parse(query: &str) -> Result<RecordFilter> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        position: 0,
+        end: query.chars().count(),
+        depth: 0,
+    };
+
+    let filter = parser.parse_or()?;
+
+    match parser.tokens.get(parser.position) {
+        None => Ok(filter),
+        Some((token, position)) => fail(format!("Unexpected `{:?}`", token), *position),
+    }
+}
+
+fn fail<T>(message: impl Into<String>, position: usize) -> Result<T> {
+    err::InvalidFilterExpression {
+        message: message.into(),
+        position,
+    }
+    .fail()
+}
+
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+        i += 1;
+
+        let token = match (c, next) {
+            (c, _) if c.is_whitespace() => continue,
+            ('(', _) => Token::OpenParen,
+            (')', _) => Token::CloseParen,
+            ('~', _) => Token::Like,
+            ('/', _) if matches!(tokens.last(), Some((Token::Like, _))) => {
+                let (pattern, end) = read_regex_literal(&chars, start)?;
+                i = end;
+                Token::Regex(pattern)
+            }
+            ('!', Some('=')) => {
+                i += 1;
+                Token::Op(CompareOp::Ne)
+            }
+            ('<', Some('=')) => {
+                i += 1;
+                Token::Op(CompareOp::Le)
+            }
+            ('>', Some('=')) => {
+                i += 1;
+                Token::Op(CompareOp::Ge)
+            }
+            ('=', _) => Token::Op(CompareOp::Eq),
+            ('<', _) => Token::Op(CompareOp::Lt),
+            ('>', _) => Token::Op(CompareOp::Gt),
+            ('"', _) | ('\'', _) => {
+                while i < chars.len() && chars[i] != c {
+                    i += 1;
+                }
+
+                if i == chars.len() {
+                    return fail("Unterminated string", start);
+                }
+
+                i += 1;
+                Token::String(chars[start + 1..i - 1].iter().collect())
+            }
+            _ => {
+                while i < chars.len()
+                    && !chars[i].is_whitespace()
+                    && !"()=!<>~\"'".contains(chars[i])
+                {
+                    i += 1;
+                }
+
+                Token::Word(chars[start..i].iter().collect())
+            }
+        };
+
+        tokens.push((token, start));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Length of the query, reported when it ends unexpectedly.
+    end: usize,
+    /// The number of enclosing `NOT` and `(`, limited to `MAX_DEPTH`.
+    depth: usize,
+}
+
+impl Parser {
+    /// Returns true (and advances) if the next token is the keyword (case insensitive).
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.tokens.get(self.position) {
+            Some((Token::Word(word), _)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn next(&mut self) -> Result<(Token, usize)> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => fail("Unexpected end of query", self.end),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<RecordFilter> {
+        let mut filter = self.parse_and()?;
+
+        while self.keyword("or") {
+            filter = filter.or(self.parse_and()?);
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_and(&mut self) -> Result<RecordFilter> {
+        let mut filter = self.parse_unary()?;
+
+        while self.keyword("and") {
+            filter = filter.and(self.parse_unary()?);
+        }
+
+        Ok(filter)
+    }
+
+    /// Runs `parse` one nesting level deeper, failing at `position` past `MAX_DEPTH`.
+    fn nested<T>(
+        &mut self,
+        position: usize,
+        parse: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        if self.depth >= MAX_DEPTH {
+            return fail(
+                format!("Query is nested deeper than {} levels", MAX_DEPTH),
+                position,
+            );
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_unary(&mut self) -> Result<RecordFilter> {
+        if self.keyword("not") {
+            let position = self.tokens[self.position - 1].1;
+            return Ok(self.nested(position, Self::parse_unary)?.negate());
+        }
+
+        match self.next()? {
+            (Token::OpenParen, position) => {
+                let filter = self.nested(position, Self::parse_or)?;
+
+                match self.next()? {
+                    (Token::CloseParen, _) => Ok(filter),
+                    (token, position) => {
+                        fail(format!("Expected `)`, found `{:?}`", token), position)
+                    }
+                }
+            }
+            (Token::Word(name), position) => self.parse_term(&name, position),
+            (token, position) => fail(format!("Expected a field, found `{:?}`", token), position),
+        }
+    }
+
+    fn parse_term(&mut self, name: &str, position: usize) -> Result<RecordFilter> {
+        let field = match parse_field(name) {
+            Some(field) => field,
+            None => return fail(format!("Invalid field `{}`", name), position),
+        };
+
+        let op = match self.next()? {
+            (Token::Op(op), _) => Some(op),
+            (Token::Like, _) => None,
+            (token, position) => {
+                return fail(
+                    format!("Expected an operator, found `{:?}`", token),
+                    position,
+                )
+            }
+        };
+
+        let (value, quoted, position) = match self.next()? {
+            (Token::Regex(pattern), position) => {
+                return RecordFilter::regex(field, &pattern)
+                    .or_else(|e| fail(e.to_string(), position))
+            }
+            (Token::Word(value), position) => (value, false, position),
+            (Token::String(value), position) => (value, true, position),
+            (token, position) => {
+                return fail(format!("Expected a value, found `{:?}`", token), position)
+            }
+        };
+
+        let op = match op {
+            Some(op) => op,
+            None => {
+                return Ok(RecordFilter::Like {
+                    field,
+                    pattern: value.to_lowercase(),
+                })
+            }
+        };
+
+        let value = if field.is_time() {
+            match parse_time(&value) {
+                Some(time) => Literal::Time(time),
+                None => return fail(format!("Invalid time `{}`", value), position),
+            }
+        } else {
+            match parse_number(&value) {
+                Some(n) if !quoted => Literal::Number(n),
+                _ => Literal::String(value.to_lowercase()),
+            }
+        };
+
+        Ok(RecordFilter::Compare { field, op, value })
+    }
+}
+
+fn parse_field(name: &str) -> Option<Field> {
+    let parts: Vec<&str> = name.split('.').collect();
+
+    if parts.iter().any(|part| part.is_empty()) {
+        return None;
+    }
+
+    let field = match parts.as_slice() {
+        ["TimeCreated"] | ["System", "TimeCreated"] => Field::TimeCreated,
+        ["EventRecordID"] | ["System", "EventRecordID"] => Field::EventRecordId,
+        ["Provider"] | ["System", "Provider"] => Field::Attribute {
+            element: "Provider".to_string(),
+            attribute: "Name".to_string(),
+        },
+        [element] if SYSTEM_ELEMENTS.contains(element) => Field::Element(element.to_string()),
+        ["EventData", name] | [name] => Field::EventData(name.to_string()),
+        ["System", element] | ["UserData", element] => Field::Element(element.to_string()),
+        ["System", element, attribute] | [element, attribute] => Field::Attribute {
+            element: element.to_string(),
+            attribute: attribute.to_string(),
+        },
+        _ => return None,
+    };
+
+    Some(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compare(field: Field, op: CompareOp, value: Literal) -> RecordFilter {
+        RecordFilter::Compare { field, op, value }
+    }
+
+    #[test]
+    fn test_parses_query() {
+        let filter =
+            parse("EventID=4624 AND TargetUserName~'admin*' and TimeCreated>2023-01-01").unwrap();
+
+        assert_eq!(
+            filter,
+            RecordFilter::And(vec![
+                compare(
+                    Field::Element("EventID".to_string()),
+                    CompareOp::Eq,
+                    Literal::Number(4624)
+                ),
+                RecordFilter::Like {
+                    field: Field::EventData("TargetUserName".to_string()),
+                    pattern: "admin*".to_string()
+                },
+                compare(
+                    Field::TimeCreated,
+                    CompareOp::Gt,
+                    Literal::Time(parse_time("2023-01-01").unwrap())
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parses_words_and_precedence() {
+        let filter = parse("NOT Level<=2 OR (Provider=EventLog AND Computer!='42')").unwrap();
+
+        assert_eq!(
+            filter,
+            RecordFilter::Or(vec![
+                compare(
+                    Field::Element("Level".to_string()),
+                    CompareOp::Le,
+                    Literal::Number(2)
+                )
+                .negate(),
+                compare(
+                    Field::Attribute {
+                        element: "Provider".to_string(),
+                        attribute: "Name".to_string()
+                    },
+                    CompareOp::Eq,
+                    Literal::String("eventlog".to_string())
+                )
+                .and(compare(
+                    Field::Element("Computer".to_string()),
+                    CompareOp::Ne,
+                    Literal::String("42".to_string())
+                )),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parses_regex() {
+        assert_eq!(
+            parse("CommandLine ~ /mimikatz|rundll32/i AND Channel=Microsoft-Windows-Sysmon/Operational")
+                .unwrap(),
+            RecordFilter::regex(
+                Field::EventData("CommandLine".to_string()),
+                "(?i)mimikatz|rundll32"
+            )
+            .unwrap()
+            .and(compare(
+                Field::Element("Channel".to_string()),
+                CompareOp::Eq,
+                Literal::String("microsoft-windows-sysmon/operational".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let position = |query: &str| match parse(query) {
+            Err(err::Error::InvalidFilterExpression { position, .. }) => position,
+            other => panic!("Expected an error, got {:?}", other),
+        };
+
+        assert_eq!(position("EventID="), 8);
+        assert_eq!(position("EventID 4624"), 8);
+        assert_eq!(position("(EventID=1"), 10);
+        assert_eq!(position("TimeCreated>yesterday"), 12);
+        assert_eq!(position("Computer='DC"), 9);
+        assert_eq!(position("=1"), 0);
+        assert_eq!(position("Image~/(/"), 6);
+
+        let nested = format!("{}EventID=1{}", "(".repeat(60000), ")".repeat(60000));
+        assert_eq!(position(&nested), MAX_DEPTH);
+        assert_eq!(position(&"NOT ".repeat(60000)), 4 * MAX_DEPTH);
+    }
+}
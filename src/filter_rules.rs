@@ -0,0 +1,326 @@
+//! Include and exclude rules, loaded from JSON or TOML files, for dropping noise while parsing.
+//!
+//! Every rule matches a provider, a list of event IDs (numbers, or ranges such as `"5150-5159"`)
+//! and a filter expression (see `RecordFilter::parse`), all optional (but at least one is required):
+//!
+//! ```toml
+//! [[exclude]]
+//! description = "A handle to an object was closed"
+//! provider = "Microsoft-Windows-Security-Auditing"
+//! event_ids = [4658]
+//!
+//! [[include]]
+//! where = 'Level <= 3 || EventData.TargetUserName == "admin"'
+//! ```
+//!
+//! A record is kept if it matches any `include` rule (or there are none), and no `exclude` rule.
+//! A default noise profile is shipped with the crate (see `FilterRules::default_noise`).
+use crate::err::{self, Result};
+use crate::record_filter::parse_number;
+use crate::RecordFilter;
+
+use serde_json::{Map, Value};
+use snafu::ResultExt;
+
+use std::convert::TryFrom;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+const DEFAULT_NOISE: &str = include_str!("../rules/noise.toml");
+
+/// Include and exclude rules, compiled to filters.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FilterRules {
+    include: Vec<RecordFilter>,
+    exclude: Vec<RecordFilter>,
+}
+
+impl FilterRules {
+    /// The default noise profile (`rules/noise.toml`), excluding high volume events
+    /// which rarely matter for triage (such as closed handles and permitted WFP connections).
+    pub fn default_noise() -> Self {
+        FilterRules::from_toml(DEFAULT_NOISE).expect("default noise profile is valid")
+    }
+
+    /// Loads a single JSON or TOML file (by it's extension).
+    pub fn load_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path).context(err::FailedToOpenFile { path })?;
+
+        let value = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml_to_value(&data),
+            _ => serde_json::from_str(&data).map_err(|e| e.to_string()),
+        };
+
+        value
+            .and_then(|value| FilterRules::from_value(&value))
+            .map_err(|e| err::Error::InvalidFilterRules {
+                message: format!("{}: {}", path.display(), e),
+            })
+    }
+
+    /// Loads a file of filter expressions (see `RecordFilter::parse`), one per line, as include rules.
+    /// Empty lines and lines starting with `#` are skipped.
+    pub fn load_include_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(FilterRules {
+            include: load_expressions(path.as_ref())?,
+            exclude: vec![],
+        })
+    }
+
+    /// Same as `load_include_file`, but the expressions are loaded as exclude rules.
+    pub fn load_exclude_file(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(FilterRules {
+            include: vec![],
+            exclude: load_expressions(path.as_ref())?,
+        })
+    }
+
+    pub fn from_toml(data: &str) -> Result<Self> {
+        toml_to_value(data)
+            .and_then(|value| FilterRules::from_value(&value))
+            .map_err(|message| err::Error::InvalidFilterRules { message })
+    }
+
+    pub fn from_json(data: &str) -> Result<Self> {
+        serde_json::from_str(data)
+            .map_err(|e| e.to_string())
+            .and_then(|value| FilterRules::from_value(&value))
+            .map_err(|message| err::Error::InvalidFilterRules { message })
+    }
+
+    fn from_value(value: &Value) -> std::result::Result<Self, String> {
+        let root = value.as_object().ok_or("Expected a table")?;
+
+        let rules = |name: &str| -> std::result::Result<Vec<RecordFilter>, String> {
+            match root.get(name) {
+                Some(Value::Array(rules)) => rules
+                    .iter()
+                    .enumerate()
+                    .map(|(i, rule)| {
+                        rule.as_object()
+                            .ok_or_else(|| "Expected a table".to_string())
+                            .and_then(rule_filter)
+                            .map_err(|e| format!("`{}` rule {}: {}", name, i + 1, e))
+                    })
+                    .collect(),
+                Some(_) => Err(format!("Expected `{}` to be a list of rules", name)),
+                None => Ok(vec![]),
+            }
+        };
+
+        Ok(FilterRules {
+            include: rules("include")?,
+            exclude: rules("exclude")?,
+        })
+    }
+
+    /// Adds the rules of `other`.
+    pub fn merge(&mut self, other: FilterRules) {
+        self.include.extend(other.include);
+        self.exclude.extend(other.exclude);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Returns a filter matching the records kept by the rules, `None` if there are no rules.
+    pub fn into_filter(self) -> Option<RecordFilter> {
+        let include = self.include.into_iter().reduce(RecordFilter::or);
+        let exclude = self
+            .exclude
+            .into_iter()
+            .reduce(RecordFilter::or)
+            .map(RecordFilter::negate);
+
+        match (include, exclude) {
+            (Some(include), Some(exclude)) => Some(include.and(exclude)),
+            (include, exclude) => include.or(exclude),
+        }
+    }
+}
+
+fn load_expressions(path: &Path) -> Result<Vec<RecordFilter>> {
+    let data = fs::read_to_string(path).context(err::FailedToOpenFile { path })?;
+
+    data.lines()
+        .enumerate()
+        .map(|(i, line)| (i, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| {
+            RecordFilter::parse(line).map_err(|e| err::Error::InvalidFilterRules {
+                message: format!("{}:{}: {}", path.display(), i + 1, e),
+            })
+        })
+        .collect()
+}
+
+fn toml_to_value(data: &str) -> std::result::Result<Value, String> {
+    toml::from_str::<toml::Value>(data)
+        .map_err(|e| e.to_string())
+        .and_then(|v| serde_json::to_value(v).map_err(|e| e.to_string()))
+}
+
+fn rule_filter(rule: &Map<String, Value>) -> std::result::Result<RecordFilter, String> {
+    let mut filters = vec![];
+
+    for (key, value) in rule {
+        match key.as_str() {
+            "description" => {}
+            "provider" => {
+                let provider = value.as_str().ok_or("Expected `provider` to be a string")?;
+                filters.push(RecordFilter::providers(&[provider]));
+            }
+            "event_ids" => {
+                let event_ids = value
+                    .as_array()
+                    .ok_or("Expected `event_ids` to be a list")?
+                    .iter()
+                    .map(event_id_range)
+                    .collect::<std::result::Result<Vec<_>, _>>()?;
+
+                filters.push(RecordFilter::event_id_ranges(&event_ids));
+            }
+            "where" => {
+                let expr = value.as_str().ok_or("Expected `where` to be a string")?;
+                filters.push(RecordFilter::parse(expr).map_err(|e| e.to_string())?);
+            }
+            other => return Err(format!("Unknown key `{}`", other)),
+        }
+    }
+
+    filters
+        .into_iter()
+        .reduce(RecordFilter::and)
+        .ok_or_else(|| "A rule needs a `provider`, `event_ids` or `where`".to_string())
+}
+
+/// Reads an event ID (`4624`) or a range of event IDs (`"5150-5159"`).
+fn event_id_range(value: &Value) -> std::result::Result<RangeInclusive<u16>, String> {
+    let event_id = |s: &str| {
+        parse_number(s)
+            .and_then(|n| u16::try_from(n).ok())
+            .ok_or_else(|| format!("Invalid event ID `{}`", s))
+    };
+
+    match value {
+        Value::Number(n) => {
+            let id = event_id(&n.to_string())?;
+            Ok(id..=id)
+        }
+        Value::String(s) => match s.split_once('-') {
+            Some((start, end)) => Ok(event_id(start)?..=event_id(end)?),
+            None => {
+                let id = event_id(s)?;
+                Ok(id..=id)
+            }
+        },
+        _ => Err(format!("Invalid event ID `{}`", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ensure_env_logger_initialized, EvtxParser, ParserSettings};
+
+    fn event_ids(settings: ParserSettings) -> Vec<u16> {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(settings);
+
+        parser
+            .serialized_records(|r| r.map(|r| r.event_id().unwrap()))
+            .map(|id| id.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_include_and_exclude_rules() {
+        ensure_env_logger_initialized();
+
+        let rules = FilterRules::from_toml(
+            r#"
+[[include]]
+event_ids = ["4600-4699"]
+
+[[exclude]]
+provider = "Microsoft-Windows-Security-Auditing"
+event_ids = [4624, 4672]
+
+[[exclude]]
+where = "EventID == 4634"
+"#,
+        )
+        .unwrap();
+
+        let ids = event_ids(ParserSettings::new().filter_rules(rules));
+
+        assert!(!ids.is_empty());
+        assert!(ids
+            .iter()
+            .all(|id| (4600..=4699).contains(id) && ![4624, 4672, 4634].contains(id)));
+    }
+
+    #[test]
+    fn test_default_noise_profile() {
+        ensure_env_logger_initialized();
+
+        let all = event_ids(ParserSettings::new());
+        let filtered = event_ids(ParserSettings::new().filter_rules(FilterRules::default_noise()));
+
+        // Only the special privileges assigned to `SYSTEM` are noise.
+        let count = |ids: &[u16], id| ids.iter().filter(|&&i| i == id).count();
+        assert_eq!(count(&all, 4672), 459);
+        assert!(count(&filtered, 4672) > 0);
+        assert!(count(&filtered, 4672) < 459);
+
+        assert_eq!(
+            all.len() - filtered.len(),
+            count(&all, 4672) - count(&filtered, 4672)
+        );
+    }
+
+    #[test]
+    fn test_include_and_exclude_files() {
+        ensure_env_logger_initialized();
+
+        let dir = tempfile::tempdir().unwrap();
+        let include = dir.path().join("include.txt");
+        let exclude = dir.path().join("exclude.txt");
+        fs::write(&include, "# Logons\nEventID == 4624\n\nEventID == 4672\n").unwrap();
+        fs::write(&exclude, "EventData.SubjectUserSid == 'S-1-5-18'").unwrap();
+
+        let mut rules = FilterRules::load_include_file(&include).unwrap();
+        let included = event_ids(ParserSettings::new().filter_rules(rules.clone()));
+        assert!(included.iter().all(|id| [4624, 4672].contains(id)));
+        assert_eq!(included.iter().filter(|&&id| id == 4672).count(), 459);
+
+        rules.merge(FilterRules::load_exclude_file(&exclude).unwrap());
+        let filtered = event_ids(ParserSettings::new().filter_rules(rules));
+        assert!(!filtered.is_empty());
+        assert!(filtered.len() < included.len());
+
+        fs::write(&exclude, "EventID == 4624\nEventID ==").unwrap();
+        let e = FilterRules::load_exclude_file(&exclude).unwrap_err();
+        assert!(e.to_string().contains("exclude.txt:2:"), "{}", e);
+    }
+
+    #[test]
+    fn test_invalid_rules() {
+        for rules in [
+            "[[exclude]]\ndescription = \"matches everything\"",
+            "[[exclude]]\nevent_ids = [\"abc\"]",
+            "[[exclude]]\nwhere = \"EventID ==\"",
+            "[[exclude]]\nevent = 4624",
+            "exclude = 4624",
+        ] {
+            assert!(FilterRules::from_toml(rules).is_err(), "{}", rules);
+        }
+
+        assert!(FilterRules::from_json(r#"{"exclude": [{"event_ids": [4658]}]}"#).is_ok());
+    }
+}
@@ -0,0 +1,364 @@
+//! Detection of log clearing and audit tampering, see `EvtxParser::findings_report`.
+//!
+//! Combines the security-relevant meta-events of the log (logs cleared, audit policy changed,
+//! event logging shut down) with the inconsistencies of the `TamperingReport` (record ID gaps and duplicates,
+//! time going backwards, unreadable chunks).
+use crate::tampering::TamperingReport;
+use crate::EvtxRecord;
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+
+use std::fmt;
+
+const EVENTLOG_PROVIDER: &str = "Microsoft-Windows-Eventlog";
+const SECURITY_AUDITING_PROVIDER: &str = "Microsoft-Windows-Security-Auditing";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Low,
+    Medium,
+    High,
+}
+
+impl Severity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Low => "low",
+            Severity::Medium => "medium",
+            Severity::High => "high",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FindingKind {
+    /// Security 1102, the audit log was cleared.
+    AuditLogCleared,
+    /// System 104, a log was cleared.
+    LogCleared,
+    /// 4719, the system audit policy was changed.
+    AuditPolicyChanged,
+    /// 4912, a per-user audit policy was changed.
+    PerUserAuditPolicyChanged,
+    /// 4906, the `CrashOnAuditFail` value was changed.
+    CrashOnAuditFailChanged,
+    /// 1100, the event logging service was shut down.
+    EventLoggingShutdown,
+    /// 1104, the security log is full.
+    SecurityLogFull,
+    /// Record IDs missing between the first and the last record ID.
+    RecordGap,
+    DuplicateRecordId,
+    /// A record earlier than the previous record ID.
+    TimeWentBackwards,
+    UnreadableChunk,
+}
+
+impl FindingKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FindingKind::AuditLogCleared => "audit_log_cleared",
+            FindingKind::LogCleared => "log_cleared",
+            FindingKind::AuditPolicyChanged => "audit_policy_changed",
+            FindingKind::PerUserAuditPolicyChanged => "per_user_audit_policy_changed",
+            FindingKind::CrashOnAuditFailChanged => "crash_on_audit_fail_changed",
+            FindingKind::EventLoggingShutdown => "event_logging_shutdown",
+            FindingKind::SecurityLogFull => "security_log_full",
+            FindingKind::RecordGap => "record_gap",
+            FindingKind::DuplicateRecordId => "duplicate_record_id",
+            FindingKind::TimeWentBackwards => "time_went_backwards",
+            FindingKind::UnreadableChunk => "unreadable_chunk",
+        }
+    }
+
+    pub fn severity(self) -> Severity {
+        match self {
+            FindingKind::AuditLogCleared
+            | FindingKind::LogCleared
+            | FindingKind::AuditPolicyChanged
+            | FindingKind::PerUserAuditPolicyChanged
+            | FindingKind::CrashOnAuditFailChanged
+            | FindingKind::RecordGap => Severity::High,
+            FindingKind::EventLoggingShutdown
+            | FindingKind::DuplicateRecordId
+            | FindingKind::TimeWentBackwards
+            | FindingKind::UnreadableChunk => Severity::Medium,
+            FindingKind::SecurityLogFull => Severity::Low,
+        }
+    }
+
+    /// Returns the kind of meta-event of a record, by provider and `EventID`.
+    fn of_event(provider: &str, event_id: u16) -> Option<FindingKind> {
+        let kind = if provider.eq_ignore_ascii_case(EVENTLOG_PROVIDER) {
+            match event_id {
+                1102 => FindingKind::AuditLogCleared,
+                104 => FindingKind::LogCleared,
+                1100 => FindingKind::EventLoggingShutdown,
+                1104 => FindingKind::SecurityLogFull,
+                _ => return None,
+            }
+        } else if provider.eq_ignore_ascii_case(SECURITY_AUDITING_PROVIDER) {
+            match event_id {
+                4719 => FindingKind::AuditPolicyChanged,
+                4912 => FindingKind::PerUserAuditPolicyChanged,
+                4906 => FindingKind::CrashOnAuditFailChanged,
+                _ => return None,
+            }
+        } else {
+            return None;
+        };
+
+        Some(kind)
+    }
+}
+
+/// Something suspicious found in the log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub kind: FindingKind,
+    /// The record of the meta-event (or where the inconsistency was found), if any.
+    pub event_record_id: Option<u64>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub description: String,
+}
+
+impl Finding {
+    /// Returns the finding of a meta-event record, if it is one.
+    pub(crate) fn of_record(record: &EvtxRecord<'_>) -> Option<Finding> {
+        let kind = FindingKind::of_event(&record.provider_name()?, record.event_id()?)?;
+
+        let fields = record.event_data().unwrap_or_default();
+        let field = |name: &str| {
+            fields
+                .get(name)
+                .map(|value| value.as_cow_str().into_owned())
+                .filter(|value| !value.is_empty() && value != "-")
+        };
+        let subject = match (field("SubjectDomainName"), field("SubjectUserName")) {
+            (Some(domain), Some(user)) => format!(" by {}\\{}", domain, user),
+            (None, Some(user)) => format!(" by {}", user),
+            _ => String::new(),
+        };
+
+        let description = match kind {
+            FindingKind::AuditLogCleared => format!("The Security log was cleared{}", subject),
+            FindingKind::LogCleared => format!(
+                "The {} log was cleared{}",
+                field("Channel").unwrap_or_else(|| "event".to_string()),
+                subject
+            ),
+            FindingKind::AuditPolicyChanged => {
+                format!("The system audit policy was changed{}", subject)
+            }
+            FindingKind::PerUserAuditPolicyChanged => {
+                format!("A per-user audit policy was changed{}", subject)
+            }
+            FindingKind::CrashOnAuditFailChanged => {
+                "The CrashOnAuditFail value was changed".to_string()
+            }
+            FindingKind::EventLoggingShutdown => {
+                "The event logging service was shut down".to_string()
+            }
+            _ => "The Security log is full".to_string(),
+        };
+
+        Some(Finding {
+            kind,
+            event_record_id: Some(record.event_record_id),
+            timestamp: Some(record.timestamp),
+            description,
+        })
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+
+    pub fn to_json_value(&self) -> Value {
+        json!({
+            "kind": self.kind.as_str(),
+            "severity": self.severity().as_str(),
+            "event_record_id": self.event_record_id,
+            "timestamp": self.timestamp.map(|time| time.to_rfc3339()),
+            "description": self.description,
+        })
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] ", self.severity().as_str().to_uppercase())?;
+        if let Some(event_record_id) = self.event_record_id {
+            write!(f, "Record {}", event_record_id)?;
+            if let Some(timestamp) = self.timestamp {
+                write!(f, " ({})", timestamp)?;
+            }
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.description)
+    }
+}
+
+/// The findings of a whole file, ordered by record ID (findings without a record come last).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FindingsReport {
+    pub findings: Vec<Finding>,
+    /// The analysis of the record IDs and timestamps the inconsistencies were taken from.
+    pub tampering: TamperingReport,
+}
+
+impl FindingsReport {
+    pub(crate) fn new(mut findings: Vec<Finding>, tampering: TamperingReport) -> Self {
+        for range in &tampering.missing_record_ids {
+            let description = if range.first == range.last {
+                format!("Record ID {} is missing", range.first)
+            } else {
+                format!(
+                    "Record IDs {}-{} are missing ({} records)",
+                    range.first,
+                    range.last,
+                    range.count()
+                )
+            };
+            findings.push(Finding {
+                kind: FindingKind::RecordGap,
+                event_record_id: Some(range.first),
+                timestamp: None,
+                description,
+            });
+        }
+
+        for duplicate in &tampering.duplicate_record_ids {
+            findings.push(Finding {
+                kind: FindingKind::DuplicateRecordId,
+                event_record_id: Some(duplicate.event_record_id),
+                timestamp: None,
+                description: format!(
+                    "Record ID {} is used by {} records",
+                    duplicate.event_record_id, duplicate.count
+                ),
+            });
+        }
+
+        for regression in &tampering.timestamp_regressions {
+            findings.push(Finding {
+                kind: FindingKind::TimeWentBackwards,
+                event_record_id: Some(regression.event_record_id),
+                timestamp: Some(regression.timestamp),
+                description: format!(
+                    "Time went backwards, the previous record {} is from {}",
+                    regression.previous_event_record_id, regression.previous_timestamp
+                ),
+            });
+        }
+
+        for error in &tampering.chunk_errors {
+            findings.push(Finding {
+                kind: FindingKind::UnreadableChunk,
+                event_record_id: None,
+                timestamp: None,
+                description: error.clone(),
+            });
+        }
+
+        // Stable, so that findings of the same record stay in the order above.
+        findings
+            .sort_by_key(|finding| (finding.event_record_id.is_none(), finding.event_record_id));
+
+        FindingsReport {
+            findings,
+            tampering,
+        }
+    }
+
+    /// Returns the findings of a kind.
+    pub fn of_kind(&self, kind: FindingKind) -> impl Iterator<Item = &Finding> {
+        self.findings
+            .iter()
+            .filter(move |finding| finding.kind == kind)
+    }
+
+    /// Returns the highest severity of the findings, `None` if nothing was found.
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(Finding::severity).max()
+    }
+
+    pub fn to_json_value(&self) -> Value {
+        let findings: Vec<Value> = self.findings.iter().map(Finding::to_json_value).collect();
+
+        json!({
+            "max_severity": self.max_severity().map(Severity::as_str),
+            "findings": findings,
+            "records": self.tampering.records,
+            "first_record_id": self.tampering.first_record_id,
+            "last_record_id": self.tampering.last_record_id,
+        })
+    }
+}
+
+impl fmt::Display for FindingsReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for finding in &self.findings {
+            writeln!(f, "{}", finding)?;
+        }
+
+        write!(
+            f,
+            "{} findings in {} records",
+            self.findings.len(),
+            self.tampering.records
+        )?;
+        if let Some(severity) = self.max_severity() {
+            write!(f, " (highest severity: {})", severity.as_str())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evtx_record::EvtxRecordHeader;
+    use chrono::{Duration, TimeZone};
+
+    #[test]
+    fn test_findings_of_the_tampering_report() {
+        let header = |event_record_id: u64, seconds: i64| EvtxRecordHeader {
+            data_size: 0,
+            event_record_id,
+            timestamp: Utc.timestamp_opt(1_600_000_000 + seconds, 0).unwrap(),
+        };
+        let tampering = TamperingReport::from_record_headers(
+            vec![header(1, 10), header(2, 0), header(5, 50)],
+            vec!["Chunk 3: bad magic".to_string()],
+            Duration::zero(),
+        );
+        let cleared = Finding {
+            kind: FindingKind::AuditLogCleared,
+            event_record_id: Some(5),
+            timestamp: None,
+            description: "The Security log was cleared".to_string(),
+        };
+
+        let report = FindingsReport::new(vec![cleared], tampering);
+
+        let kinds: Vec<FindingKind> = report.findings.iter().map(|finding| finding.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                FindingKind::TimeWentBackwards,
+                FindingKind::RecordGap,
+                FindingKind::AuditLogCleared,
+                FindingKind::UnreadableChunk
+            ]
+        );
+        assert_eq!(
+            report.findings[1].description,
+            "Record IDs 3-4 are missing (2 records)"
+        );
+        assert_eq!(report.max_severity(), Some(Severity::High));
+        assert_eq!(report.to_json_value()["findings"][0]["severity"], "medium");
+    }
+}
@@ -0,0 +1,661 @@
+//! Generation of synthetic EVTX files, see `EvtxGenerator`.
+//!
+//! Records are instances of `EventTemplate`s (chosen at random), with pseudo-random values
+//! (including edge cases such as empty strings, XML special characters, non-ASCII and long strings).
+//! Everything is derived from the seed, so the same settings always generate the same file.
+//!
+//! Corruption (see `Corruption`) is injected after the file is written, and is listed in the `GeneratedFile`,
+//! so tests can check what a tool reports for it.
+//!
+//! ```rust
+//! use evtx::{Corruption, EvtxGenerator, EvtxParser};
+//!
+//! let generated = EvtxGenerator::new()
+//!     .seed(7)
+//!     .records(500)
+//!     .corrupt(Corruption::TruncatedRecord, 1)
+//!     .generate()
+//!     .unwrap();
+//!
+//! let chunk_number = generated.corruptions[0].chunk_number as usize;
+//! let mut parser = EvtxParser::from_buffer(generated.data).unwrap();
+//! let report = parser.validate().unwrap();
+//! assert!(report.chunks[chunk_number].record_chain_error.is_some());
+//! ```
+use crate::binxml::value_variant::BinXmlValue;
+use crate::err::Result;
+use crate::evtx_chunk::EVTX_CHUNK_HEADER_SIZE;
+use crate::evtx_parser::{EVTX_CHUNK_SIZE, EVTX_FILE_HEADER_SIZE};
+use crate::evtx_writer::{EventElement, EvtxWriter};
+use crate::guid::Guid;
+use crate::repair::record_chain;
+use crate::sid::Sid;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::io::Cursor;
+
+/// The type of the values generated for a field of `EventData`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FieldKind {
+    String,
+    UInt32,
+    UInt64,
+    HexInt64,
+    Bool,
+    Guid,
+    Sid,
+    FileTime,
+    Binary,
+}
+
+/// A kind of event: it's provider, event ID and the fields of it's `EventData`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventTemplate {
+    provider: String,
+    provider_guid: Option<Guid>,
+    event_id: u16,
+    level: u8,
+    channel: String,
+    fields: Vec<(String, FieldKind)>,
+}
+
+impl EventTemplate {
+    /// A template without fields, in the `Application` channel, with level 4 (information).
+    pub fn new(provider: impl Into<String>, event_id: u16) -> Self {
+        EventTemplate {
+            provider: provider.into(),
+            provider_guid: None,
+            event_id,
+            level: 4,
+            channel: "Application".to_string(),
+            fields: vec![],
+        }
+    }
+
+    pub fn provider_guid(mut self, guid: Guid) -> Self {
+        self.provider_guid = Some(guid);
+        self
+    }
+
+    pub fn level(mut self, level: u8) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn channel(mut self, channel: impl Into<String>) -> Self {
+        self.channel = channel.into();
+        self
+    }
+
+    /// Adds a `Data` element with a `Name` attribute to `EventData`.
+    pub fn field(mut self, name: impl Into<String>, kind: FieldKind) -> Self {
+        self.fields.push((name.into(), kind));
+        self
+    }
+
+    /// The templates used when none is given: a logon, a service state change and a process creation.
+    fn defaults() -> Vec<EventTemplate> {
+        vec![
+            EventTemplate::new("Microsoft-Windows-Security-Auditing", 4624)
+                .provider_guid(Guid::new(
+                    0x5484_9625,
+                    0x5478,
+                    0x4994,
+                    [0xa5, 0xba, 0x3e, 0x3b, 0x03, 0x28, 0xc3, 0x0d],
+                ))
+                .level(0)
+                .channel("Security")
+                .field("SubjectUserSid", FieldKind::Sid)
+                .field("SubjectUserName", FieldKind::String)
+                .field("SubjectLogonId", FieldKind::HexInt64)
+                .field("TargetUserName", FieldKind::String)
+                .field("LogonType", FieldKind::UInt32)
+                .field("LogonGuid", FieldKind::Guid)
+                .field("IpAddress", FieldKind::String),
+            EventTemplate::new("Service Control Manager", 7036)
+                .channel("System")
+                .field("param1", FieldKind::String)
+                .field("param2", FieldKind::String)
+                .field("Binary", FieldKind::Binary),
+            EventTemplate::new("Microsoft-Windows-Sysmon", 1)
+                .provider_guid(Guid::new(
+                    0x5770_385f,
+                    0xc22a,
+                    0x43e0,
+                    [0xbf, 0x4c, 0x06, 0xf5, 0x69, 0x8f, 0xfb, 0xd9],
+                ))
+                .channel("Microsoft-Windows-Sysmon/Operational")
+                .field("UtcTime", FieldKind::FileTime)
+                .field("ProcessGuid", FieldKind::Guid)
+                .field("ProcessId", FieldKind::UInt32)
+                .field("Image", FieldKind::String)
+                .field("CommandLine", FieldKind::String)
+                .field("IsElevated", FieldKind::Bool)
+                .field("LogonId", FieldKind::UInt64),
+        ]
+    }
+}
+
+/// Corruption injected in a generated file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Corruption {
+    /// The header checksum of a chunk is wrong.
+    BadHeaderChecksum,
+    /// The checksum of the records of a chunk is wrong.
+    BadDataChecksum,
+    /// The second half of a record (including the copy of it's size) is zeroed,
+    /// the checksums of it's chunk being updated (the parser may still render it, with zeroed values).
+    TruncatedRecord,
+    /// The file ends in the middle of a record of it's last chunk (injected at most once).
+    TruncatedFile,
+}
+
+/// Where corruption was injected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InjectedCorruption {
+    pub kind: Corruption,
+    pub chunk_number: u16,
+    /// The damaged record, for `TruncatedRecord` and `TruncatedFile`.
+    pub event_record_id: Option<u64>,
+}
+
+/// A file written by `EvtxGenerator::generate`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedFile {
+    pub data: Vec<u8>,
+    /// The number of records written (including the damaged ones).
+    pub records: u64,
+    /// The number of chunks written (including a truncated last chunk).
+    pub chunk_count: u16,
+    /// The injected corruption, ordered by chunk.
+    pub corruptions: Vec<InjectedCorruption>,
+}
+
+/// Generates synthetic EVTX files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvtxGenerator {
+    seed: u64,
+    records: u64,
+    start: DateTime<Utc>,
+    interval: Duration,
+    templates: Vec<EventTemplate>,
+    corruptions: Vec<(Corruption, usize)>,
+}
+
+impl Default for EvtxGenerator {
+    fn default() -> Self {
+        EvtxGenerator {
+            seed: 0,
+            records: 1000,
+            start: Utc.ymd(2020, 1, 1).and_hms(0, 0, 0),
+            interval: Duration::seconds(1),
+            templates: vec![],
+            corruptions: vec![],
+        }
+    }
+}
+
+impl EvtxGenerator {
+    /// 1000 records of the default templates, one per second from 2020-01-01, with seed 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn records(mut self, records: u64) -> Self {
+        self.records = records;
+        self
+    }
+
+    /// The timestamp of the first record.
+    pub fn start(mut self, start: DateTime<Utc>) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// The time between consecutive records.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Adds a template, the default templates are used only if none is added.
+    pub fn template(mut self, template: EventTemplate) -> Self {
+        self.templates.push(template);
+        self
+    }
+
+    /// Injects corruption of this kind in `count` different chunks (or records, for `TruncatedRecord`),
+    /// or as many as there are.
+    pub fn corrupt(mut self, kind: Corruption, count: usize) -> Self {
+        self.corruptions.push((kind, count));
+        self
+    }
+
+    pub fn generate(&self) -> Result<GeneratedFile> {
+        let mut rng = Rng::new(self.seed);
+        let defaults;
+        let templates = if self.templates.is_empty() {
+            defaults = EventTemplate::defaults();
+            &defaults
+        } else {
+            &self.templates
+        };
+
+        let mut writer = EvtxWriter::new(Cursor::new(vec![]))?;
+        for i in 0..self.records {
+            let template = &templates[rng.below(templates.len() as u64) as usize];
+            let timestamp = self.start + self.interval * i as i32;
+            let event = event(template, timestamp, &mut rng);
+            writer.write_record(timestamp, &event)?;
+        }
+
+        let chunk_count = writer.chunk_count() + u16::from(self.records > 0);
+        let data = writer.finish()?.into_inner();
+
+        let mut generated = GeneratedFile {
+            data,
+            records: self.records,
+            chunk_count,
+            corruptions: vec![],
+        };
+
+        // The file is truncated last, so the other corruption is not cut off.
+        let mut corruptions = self.corruptions.clone();
+        corruptions.sort_by_key(|(kind, _)| *kind == Corruption::TruncatedFile);
+        for (kind, count) in corruptions {
+            inject(&mut generated, kind, count, &mut rng);
+        }
+        generated.corruptions.sort_by_key(|c| c.chunk_number);
+
+        Ok(generated)
+    }
+}
+
+/// Builds an instance of `template`.
+fn event(template: &EventTemplate, timestamp: DateTime<Utc>, rng: &mut Rng) -> EventElement {
+    let mut provider =
+        EventElement::new("Provider").attribute("Name", string(template.provider.clone()));
+    if let Some(guid) = &template.provider_guid {
+        provider = provider.attribute("Guid", BinXmlValue::GuidType(*guid));
+    }
+
+    let system = EventElement::new("System")
+        .child(provider)
+        .child(EventElement::new("EventID").value(BinXmlValue::UInt16Type(template.event_id)))
+        .child(EventElement::new("Version").value(BinXmlValue::UInt8Type(0)))
+        .child(EventElement::new("Level").value(BinXmlValue::UInt8Type(template.level)))
+        .child(EventElement::new("Task").value(BinXmlValue::UInt16Type(0)))
+        .child(EventElement::new("Opcode").value(BinXmlValue::UInt8Type(0)))
+        .child(
+            EventElement::new("Keywords").value(BinXmlValue::HexInt64Type(Cow::Borrowed(
+                "0x8000000000000000",
+            ))),
+        )
+        .child(
+            EventElement::new("TimeCreated")
+                .attribute("SystemTime", BinXmlValue::FileTimeType(timestamp)),
+        )
+        // Set by the writer.
+        .child(EventElement::new("EventRecordID").value(BinXmlValue::UInt64Type(0)))
+        .child(EventElement::new("Correlation"))
+        .child(
+            EventElement::new("Execution")
+                .attribute(
+                    "ProcessID",
+                    BinXmlValue::UInt32Type(rng.below(10_000) as u32),
+                )
+                .attribute(
+                    "ThreadID",
+                    BinXmlValue::UInt32Type(rng.below(10_000) as u32),
+                ),
+        )
+        .child(EventElement::new("Channel").value(string(template.channel.clone())))
+        .child(
+            EventElement::new("Computer")
+                .value(string(format!("WS{:03}.corp.example.com", rng.below(50)))),
+        )
+        .child(EventElement::new("Security"));
+
+    let mut event_data = EventElement::new("EventData");
+    for (name, kind) in &template.fields {
+        event_data = event_data.child(
+            EventElement::new("Data")
+                .attribute("Name", string(name.clone()))
+                .value(value(*kind, rng)),
+        );
+    }
+
+    EventElement::new("Event").child(system).child(event_data)
+}
+
+fn string(s: String) -> BinXmlValue<'static> {
+    BinXmlValue::StringType(Cow::Owned(s))
+}
+
+/// A random value, strings being edge cases one time out of four.
+fn value(kind: FieldKind, rng: &mut Rng) -> BinXmlValue<'static> {
+    match kind {
+        FieldKind::String => string(match rng.below(16) {
+            0 => String::new(),
+            1 => "<tag attr=\"value\"> & 'quotes'".to_string(),
+            2 => "Ünïcødé ✓ 日本語 🦀".to_string(),
+            3 => "x".repeat(500 + rng.below(1500) as usize),
+            _ => (0..1 + rng.below(24))
+                .map(|_| (b'a' + rng.below(26) as u8) as char)
+                .collect(),
+        }),
+        FieldKind::UInt32 => BinXmlValue::UInt32Type(rng.next() as u32),
+        FieldKind::UInt64 => BinXmlValue::UInt64Type(rng.next()),
+        FieldKind::HexInt64 => BinXmlValue::HexInt64Type(Cow::Owned(format!("0x{:x}", rng.next()))),
+        FieldKind::Bool => BinXmlValue::BoolType(rng.below(2) == 1),
+        FieldKind::Guid => BinXmlValue::GuidType(Guid::from_bytes(
+            rng.bytes(16).try_into().expect("16 bytes"),
+        )),
+        FieldKind::Sid => BinXmlValue::SidType(Sid::new(
+            1,
+            5,
+            vec![
+                21,
+                rng.next() as u32,
+                rng.next() as u32,
+                1000 + rng.below(1000) as u32,
+            ],
+        )),
+        FieldKind::FileTime => BinXmlValue::FileTimeType(
+            Utc.timestamp(1_500_000_000 + rng.below(300_000_000) as i64, 0),
+        ),
+        FieldKind::Binary => {
+            let len = rng.below(64) as usize;
+            BinXmlValue::BinaryType(Cow::Owned(rng.bytes(len)))
+        }
+    }
+}
+
+/// Injects `count` corruptions of `kind` in chunks (or records) picked at random.
+fn inject(generated: &mut GeneratedFile, kind: Corruption, count: usize, rng: &mut Rng) {
+    let chunk_count = generated.chunk_count;
+    if chunk_count == 0 {
+        return;
+    }
+
+    let chunk_range = |chunk_number: u16| {
+        let start = EVTX_FILE_HEADER_SIZE + chunk_number as usize * EVTX_CHUNK_SIZE;
+        start..start + EVTX_CHUNK_SIZE
+    };
+
+    match kind {
+        Corruption::BadHeaderChecksum | Corruption::BadDataChecksum => {
+            let offset = if kind == Corruption::BadHeaderChecksum {
+                124
+            } else {
+                52
+            };
+
+            for chunk_number in rng.pick(u64::from(chunk_count), count) {
+                let chunk_number = chunk_number as u16;
+                let chunk = &mut generated.data[chunk_range(chunk_number)];
+                chunk[offset] ^= 0xff;
+                generated.corruptions.push(InjectedCorruption {
+                    kind,
+                    chunk_number,
+                    event_record_id: None,
+                });
+            }
+        }
+        Corruption::TruncatedRecord => {
+            let records: Vec<(u16, usize, u64, usize)> = (0..chunk_count)
+                .flat_map(|chunk_number| {
+                    record_chain(&generated.data[chunk_range(chunk_number)])
+                        .into_iter()
+                        .map(move |(offset, header)| {
+                            let size = header.data_size as usize;
+                            (chunk_number, offset, header.event_record_id, size)
+                        })
+                })
+                .collect();
+
+            for i in rng.pick(records.len() as u64, count) {
+                let (chunk_number, offset, event_record_id, size) = records[i as usize];
+                let chunk = &mut generated.data[chunk_range(chunk_number)];
+                for byte in &mut chunk[offset + size / 2..offset + size] {
+                    *byte = 0;
+                }
+                update_checksums(chunk);
+
+                generated.corruptions.push(InjectedCorruption {
+                    kind,
+                    chunk_number,
+                    event_record_id: Some(event_record_id),
+                });
+            }
+        }
+        Corruption::TruncatedFile => {
+            if count == 0 || generated.data.len() < EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE {
+                return;
+            }
+
+            let chunk_number = chunk_count - 1;
+            let start = chunk_range(chunk_number).start;
+            let records = record_chain(&generated.data[chunk_range(chunk_number)]);
+            let (offset, header) = &records[rng.below(records.len() as u64) as usize];
+
+            generated
+                .data
+                .truncate(start + offset + header.data_size as usize / 2);
+            generated.corruptions.push(InjectedCorruption {
+                kind,
+                chunk_number,
+                event_record_id: Some(header.event_record_id),
+            });
+        }
+    }
+}
+
+/// Updates the checksums of a chunk after it's records were changed.
+fn update_checksums(chunk: &mut [u8]) {
+    let free_space_offset = u32::from_le_bytes(chunk[48..52].try_into().expect("4 bytes")) as usize;
+    let events_checksum = crc32fast::hash(&chunk[EVTX_CHUNK_HEADER_SIZE..free_space_offset]);
+    chunk[52..56].copy_from_slice(&events_checksum.to_le_bytes());
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&chunk[..120]);
+    hasher.update(&chunk[128..EVTX_CHUNK_HEADER_SIZE]);
+    chunk[124..128].copy_from_slice(&hasher.finalize().to_le_bytes());
+}
+
+/// SplitMix64, which is good enough for test data, and stable across versions (unlike the generators of `rand`).
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n` (`n` must not be 0).
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+
+    /// `count` different numbers in `0..n` (or all of them), in increasing order.
+    fn pick(&mut self, n: u64, count: usize) -> Vec<u64> {
+        let mut all: Vec<u64> = (0..n).collect();
+        for i in 0..all.len().min(count) {
+            let j = i + self.below((all.len() - i) as u64) as usize;
+            all.swap(i, j);
+        }
+        all.truncate(count);
+        all.sort_unstable();
+        all
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EvtxParser, RecordChainErrorKind};
+
+    fn parse(data: Vec<u8>) -> EvtxParser<Cursor<Vec<u8>>> {
+        EvtxParser::from_buffer(data).unwrap()
+    }
+
+    #[test]
+    fn test_generated_files_are_deterministic() {
+        let generated = EvtxGenerator::new().seed(42).generate().unwrap();
+
+        assert_eq!(generated, EvtxGenerator::new().seed(42).generate().unwrap());
+        assert_ne!(
+            generated.data,
+            EvtxGenerator::new().seed(43).generate().unwrap().data
+        );
+    }
+
+    #[test]
+    fn test_generated_files_are_valid() {
+        let start = Utc.ymd(2021, 6, 1).and_hms(12, 0, 0);
+        let generated = EvtxGenerator::new()
+            .records(3000)
+            .start(start)
+            .interval(Duration::minutes(1))
+            .generate()
+            .unwrap();
+        assert!(generated.chunk_count > 1);
+        assert!(generated.corruptions.is_empty());
+
+        let mut parser = parse(generated.data);
+        assert!(parser.validate().unwrap().is_valid());
+
+        let records: Vec<_> = parser.records_json_value().map(|r| r.unwrap()).collect();
+        assert_eq!(records.len(), 3000);
+        assert_eq!(records[0].timestamp, start);
+        assert_eq!(records[2999].timestamp, start + Duration::minutes(2999));
+        assert_eq!(records[2999].event_record_id, 3000);
+
+        let event_ids: std::collections::HashSet<_> = records
+            .iter()
+            .map(|r| r.data["Event"]["System"]["EventID"].as_u64().unwrap())
+            .collect();
+        assert_eq!(event_ids.len(), 3);
+    }
+
+    #[test]
+    fn test_generates_custom_templates() {
+        let generated = EvtxGenerator::new()
+            .records(10)
+            .template(
+                EventTemplate::new("Custom", 1000)
+                    .channel("Custom/Operational")
+                    .field("Enabled", FieldKind::Bool),
+            )
+            .generate()
+            .unwrap();
+
+        for record in parse(generated.data).records_json_value() {
+            let data = record.unwrap().data;
+            assert_eq!(
+                data["Event"]["System"]["Provider"]["#attributes"]["Name"],
+                "Custom"
+            );
+            assert_eq!(data["Event"]["System"]["Channel"], "Custom/Operational");
+            assert!(data["Event"]["EventData"]["Enabled"].is_boolean());
+        }
+    }
+
+    #[test]
+    fn test_injects_checksum_errors() {
+        let generated = EvtxGenerator::new()
+            .records(3000)
+            .corrupt(Corruption::BadHeaderChecksum, 1)
+            .corrupt(Corruption::BadDataChecksum, 1)
+            .generate()
+            .unwrap();
+        assert_eq!(generated.corruptions.len(), 2);
+
+        let mut corrupted: Vec<u16> = generated
+            .corruptions
+            .iter()
+            .map(|c| c.chunk_number)
+            .collect();
+        corrupted.dedup();
+
+        let report = parse(generated.data).validate().unwrap();
+        for corruption in &generated.corruptions {
+            let chunk = &report.chunks[corruption.chunk_number as usize];
+            match corruption.kind {
+                Corruption::BadHeaderChecksum => {
+                    assert_eq!(chunk.header_checksum_valid, Some(false))
+                }
+                _ => assert_eq!(chunk.data_checksum_valid, Some(false)),
+            }
+        }
+        let invalid: Vec<u16> = report.invalid_chunks().map(|c| c.chunk_number).collect();
+        assert_eq!(invalid, corrupted);
+    }
+
+    #[test]
+    fn test_injects_truncated_records() {
+        let generated = EvtxGenerator::new()
+            .records(3000)
+            .corrupt(Corruption::TruncatedRecord, 1)
+            .generate()
+            .unwrap();
+        let corruption = &generated.corruptions[0];
+
+        let mut parser = parse(generated.data);
+        let report = parser.validate().unwrap();
+        let chunk = &report.chunks[corruption.chunk_number as usize];
+        assert_eq!(chunk.data_checksum_valid, Some(true));
+        assert!(matches!(
+            chunk.record_chain_error.as_ref().unwrap().kind,
+            RecordChainErrorKind::SizeMismatch { .. }
+        ));
+
+        // The parser renders the record, with zeroed values.
+        let record = parser
+            .records()
+            .map(|r| r.unwrap())
+            .find(|r| Some(r.event_record_id) == corruption.event_record_id)
+            .unwrap();
+        assert!(record.data.contains('\0'));
+    }
+
+    #[test]
+    fn test_injects_truncated_files() {
+        let generated = EvtxGenerator::new()
+            .records(3000)
+            .corrupt(Corruption::TruncatedFile, 1)
+            .generate()
+            .unwrap();
+        let corruption = &generated.corruptions[0];
+        assert_eq!(corruption.chunk_number, generated.chunk_count - 1);
+
+        let truncated = corruption.event_record_id.unwrap();
+        let last = parse(generated.data)
+            .records()
+            .filter_map(|r| r.ok())
+            .map(|r| r.event_record_id)
+            .max()
+            .unwrap();
+        // The parser may render the truncated record, with zeroed values.
+        assert!(last <= truncated);
+    }
+}
@@ -0,0 +1,309 @@
+//! GeoIP enrichment of the IP addresses of records, using MaxMind (GeoLite2/GeoIP2) databases.
+//!
+//! With `ParserSettings::geoip`, the country (from Country or City databases) and the autonomous system
+//! (from ASN databases) of the addresses in the `IP_ADDRESS_FIELDS` of the `EventData` of records are added
+//! to their JSON output, in an `Event.GeoIp` object keyed by field name:
+//!
+//! ```json
+//! "GeoIp": {
+//!   "IpAddress": {"CountryCode": "NZ", "CountryName": "New Zealand", "Asn": 9790, "AsOrganization": "..."}
+//! }
+//! ```
+//!
+//! Addresses which are not found in any of the databases (such as private addresses) are skipped.
+use crate::err::{self, Result};
+
+use maxminddb::{geoip2, Reader};
+use serde_json::{Map, Value};
+use snafu::ResultExt;
+
+use std::fmt::{self, Debug};
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// The `EventData` fields holding IP addresses (`IpAddress` and `WorkstationIp` of logon events,
+/// `SourceIp` and `DestinationIp` of Sysmon network connections).
+pub const IP_ADDRESS_FIELDS: &[&str] = &["IpAddress", "WorkstationIp", "SourceIp", "DestinationIp"];
+
+/// The location of an IP address.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct GeoIpInfo {
+    pub country_code: Option<String>,
+    /// The English name of the country.
+    pub country_name: Option<String>,
+    pub asn: Option<u32>,
+    pub as_organization: Option<String>,
+}
+
+impl GeoIpInfo {
+    fn is_empty(&self) -> bool {
+        *self == GeoIpInfo::default()
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        let mut object = Map::new();
+
+        if let Some(code) = &self.country_code {
+            object.insert("CountryCode".to_string(), code.as_str().into());
+        }
+        if let Some(name) = &self.country_name {
+            object.insert("CountryName".to_string(), name.as_str().into());
+        }
+        if let Some(asn) = self.asn {
+            object.insert("Asn".to_string(), asn.into());
+        }
+        if let Some(organization) = &self.as_organization {
+            object.insert("AsOrganization".to_string(), organization.as_str().into());
+        }
+
+        Value::Object(object)
+    }
+}
+
+/// Looks up IP addresses in one or more MaxMind databases (typically a Country or City database,
+/// and an ASN database).
+#[derive(Default)]
+pub struct GeoIpEnricher {
+    databases: Vec<Reader<Vec<u8>>>,
+}
+
+impl Debug for GeoIpEnricher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.databases.iter().map(|db| &db.metadata.database_type))
+            .finish()
+    }
+}
+
+impl GeoIpEnricher {
+    /// Opens the database at `path` (an `.mmdb` file).
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        GeoIpEnricher::default().with_database(path)
+    }
+
+    /// Adds the database at `path`, consulted after the previous ones.
+    pub fn with_database(self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read(path).context(err::FailedToOpenFile { path })?;
+
+        self.with_database_bytes(data)
+    }
+
+    /// Adds a database from it's contents, consulted after the previous ones.
+    pub fn with_database_bytes(mut self, data: Vec<u8>) -> Result<Self> {
+        let reader = Reader::from_source(data).map_err(|e| err::Error::InvalidGeoIpDatabase {
+            message: e.to_string(),
+        })?;
+
+        self.databases.push(reader);
+        Ok(self)
+    }
+
+    /// Returns the location of `ip`, or `None` if it is not in any of the databases.
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoIpInfo> {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            IpAddr::V4(_) => ip,
+        };
+
+        let mut info = GeoIpInfo::default();
+
+        for database in &self.databases {
+            if let Ok(country) = database.lookup::<geoip2::Country>(ip) {
+                if let Some(country) = country.country {
+                    info.country_code = info
+                        .country_code
+                        .or_else(|| country.iso_code.map(str::to_string));
+                    info.country_name = info.country_name.or_else(|| {
+                        country
+                            .names
+                            .and_then(|names| names.get("en").map(|name| name.to_string()))
+                    });
+                }
+            }
+
+            if let Ok(asn) = database.lookup::<geoip2::Asn>(ip) {
+                info.asn = info.asn.or(asn.autonomous_system_number);
+                info.as_organization = info
+                    .as_organization
+                    .or_else(|| asn.autonomous_system_organization.map(str::to_string));
+            }
+        }
+
+        if info.is_empty() {
+            None
+        } else {
+            Some(info)
+        }
+    }
+
+    /// Returns the `Event.GeoIp` object of a record with the given `EventData` fields (by field name).
+    pub(crate) fn annotate<'a>(
+        &self,
+        fields: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Map<String, Value> {
+        let mut annotations = Map::new();
+
+        for (name, value) in fields {
+            if !IP_ADDRESS_FIELDS.contains(&name) {
+                continue;
+            }
+
+            if let Some(info) = value.trim().parse().ok().and_then(|ip| self.lookup(ip)) {
+                annotations.insert(name.to_string(), info.to_json());
+            }
+        }
+
+        annotations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EvtxParser, ParserSettings};
+
+    /// Encodes the control byte(s) of a value of the MaxMind data format.
+    fn control(data_type: u8, size: usize, out: &mut Vec<u8>) {
+        assert!(size < 29 + 256);
+        let size_bits = size.min(29) as u8;
+        if data_type > 7 {
+            out.push(size_bits);
+            out.push(data_type - 7);
+        } else {
+            out.push((data_type << 5) | size_bits);
+        }
+        if size >= 29 {
+            out.push((size - 29) as u8);
+        }
+    }
+
+    fn string(s: &str, out: &mut Vec<u8>) {
+        control(2, s.len(), out);
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    fn uint(data_type: u8, n: u64, width: usize, out: &mut Vec<u8>) {
+        control(data_type, width, out);
+        out.extend_from_slice(&n.to_be_bytes()[8 - width..]);
+    }
+
+    /// Builds a database mapping every IPv4 address to New Zealand and AS 9790.
+    fn database() -> Vec<u8> {
+        let mut db = vec![];
+
+        // A single node, with both records pointing to the start of the data section
+        // (`node_count + 16 + offset`).
+        let record = 1 + 16;
+        db.extend_from_slice(&[0, 0, record, 0, 0, record]);
+        db.extend_from_slice(&[0; 16]);
+
+        control(7, 3, &mut db);
+        string("country", &mut db);
+        control(7, 2, &mut db);
+        string("iso_code", &mut db);
+        string("NZ", &mut db);
+        string("names", &mut db);
+        control(7, 1, &mut db);
+        string("en", &mut db);
+        string("New Zealand", &mut db);
+        string("autonomous_system_number", &mut db);
+        uint(6, 9790, 2, &mut db);
+        string("autonomous_system_organization", &mut db);
+        string("Example Networks", &mut db);
+
+        db.extend_from_slice(b"\xAB\xCD\xEFMaxMind.com");
+        control(7, 9, &mut db);
+        string("binary_format_major_version", &mut db);
+        uint(5, 2, 1, &mut db);
+        string("binary_format_minor_version", &mut db);
+        uint(5, 0, 0, &mut db);
+        string("build_epoch", &mut db);
+        uint(9, 1_600_000_000, 4, &mut db);
+        string("database_type", &mut db);
+        string("Test", &mut db);
+        string("description", &mut db);
+        control(7, 0, &mut db);
+        string("ip_version", &mut db);
+        uint(5, 4, 1, &mut db);
+        string("languages", &mut db);
+        control(11, 1, &mut db);
+        string("en", &mut db);
+        string("node_count", &mut db);
+        uint(6, 1, 1, &mut db);
+        string("record_size", &mut db);
+        uint(5, 24, 1, &mut db);
+
+        db
+    }
+
+    #[test]
+    fn test_lookup() {
+        let enricher = GeoIpEnricher::default()
+            .with_database_bytes(database())
+            .unwrap();
+
+        let expected = GeoIpInfo {
+            country_code: Some("NZ".to_string()),
+            country_name: Some("New Zealand".to_string()),
+            asn: Some(9790),
+            as_organization: Some("Example Networks".to_string()),
+        };
+        assert_eq!(
+            enricher.lookup("118.93.18.141".parse().unwrap()),
+            Some(expected.clone())
+        );
+        assert_eq!(
+            enricher.lookup("::ffff:118.93.18.141".parse().unwrap()),
+            Some(expected)
+        );
+        assert_eq!(
+            GeoIpEnricher::default().lookup("118.93.18.141".parse().unwrap()),
+            None
+        );
+
+        let annotations = enricher.annotate(vec![
+            ("IpAddress", "118.93.18.141"),
+            ("WorkstationName", "118.93.18.141"),
+            ("SourceIp", "-"),
+        ]);
+        assert_eq!(annotations.keys().collect::<Vec<_>>(), vec!["IpAddress"]);
+        assert_eq!(annotations["IpAddress"]["Asn"], 9790);
+    }
+
+    #[test]
+    fn test_invalid_database() {
+        let err = GeoIpEnricher::default()
+            .with_database_bytes(b"not a database".to_vec())
+            .unwrap_err();
+        assert!(err.to_string().contains("Invalid GeoIP database"));
+    }
+
+    #[test]
+    fn test_records_are_enriched() {
+        let enricher = GeoIpEnricher::default()
+            .with_database_bytes(database())
+            .unwrap();
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+            .unwrap()
+            .with_configuration(ParserSettings::new().geoip(enricher));
+
+        let records: Vec<_> = parser.records_json_value().map(Result::unwrap).collect();
+
+        let local_logon = records
+            .iter()
+            .find(|record| record.data["Event"]["EventData"]["IpAddress"] == "127.0.0.1")
+            .expect("to have a local logon");
+        assert_eq!(
+            local_logon.data["Event"]["GeoIp"]["IpAddress"]["CountryCode"],
+            "NZ"
+        );
+
+        assert!(records
+            .iter()
+            .filter(|record| record.data["Event"]["EventData"]["IpAddress"] == "-")
+            .all(|record| record.data["Event"].get("GeoIp").is_none()));
+    }
+}
@@ -0,0 +1,305 @@
+//! GUIDs, as found in `GuidType` values and template definitions.
+use crate::err::{self, Error};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use serde::{de, ser};
+
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::io::{self, Cursor, Read};
+use std::str::FromStr;
+
+/// A GUID, displayed (and serialized) as `54849625-5478-4994-A5BA-3E3B0328C30D`.
+///
+/// ```
+/// # use evtx::Guid;
+/// let raw_guid: [u8; 16] = [0x25, 0x96, 0x84, 0x54, 0x78, 0x54, 0x94, 0x49,
+///                           0xa5, 0xba, 0x3e, 0x3b, 0x3, 0x28, 0xc3, 0xd];
+///
+/// let guid = Guid::from_bytes(raw_guid);
+///
+/// assert_eq!(guid.to_string(), "54849625-5478-4994-A5BA-3E3B0328C30D");
+/// assert_eq!("{54849625-5478-4994-a5ba-3e3b0328c30d}".parse::<Guid>().unwrap(), guid);
+/// assert_eq!(guid.to_bytes(), raw_guid);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Guid {
+    data1: u32,
+    data2: u16,
+    data3: u16,
+    data4: [u8; 8],
+}
+
+impl Guid {
+    /// Creates a new GUID directly from it's components.
+    pub fn new(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Guid {
+        Guid {
+            data1,
+            data2,
+            data3,
+            data4,
+        }
+    }
+
+    /// Creates a GUID from it's (little endian, as stored on disk) bytes.
+    pub fn from_bytes(bytes: [u8; 16]) -> Guid {
+        Self::from_buffer(&bytes).expect("16 bytes are enough for a GUID")
+    }
+
+    /// Reads a GUID from the start of a buffer.
+    pub fn from_buffer(buffer: &[u8]) -> io::Result<Guid> {
+        Self::from_reader(&mut Cursor::new(buffer))
+    }
+
+    /// Reads a GUID from a `Read` instance.
+    pub fn from_reader<T: Read>(stream: &mut T) -> io::Result<Guid> {
+        let data1 = stream.read_u32::<LittleEndian>()?;
+        let data2 = stream.read_u16::<LittleEndian>()?;
+        let data3 = stream.read_u16::<LittleEndian>()?;
+
+        let mut data4 = [0; 8];
+        stream.read_exact(&mut data4)?;
+
+        Ok(Guid::new(data1, data2, data3, data4))
+    }
+
+    /// Returns the (little endian, as stored on disk) bytes of the GUID.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..4].copy_from_slice(&self.data1.to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.data2.to_le_bytes());
+        bytes[6..8].copy_from_slice(&self.data3.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.data4);
+        bytes
+    }
+
+    pub fn data1(&self) -> u32 {
+        self.data1
+    }
+
+    pub fn data2(&self) -> u16 {
+        self.data2
+    }
+
+    pub fn data3(&self) -> u16 {
+        self.data3
+    }
+
+    pub fn data4(&self) -> [u8; 8] {
+        self.data4
+    }
+}
+
+impl Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+            self.data1,
+            self.data2,
+            self.data3,
+            self.data4[0],
+            self.data4[1],
+            self.data4[2],
+            self.data4[3],
+            self.data4[4],
+            self.data4[5],
+            self.data4[6],
+            self.data4[7]
+        )
+    }
+}
+
+/// Parses `XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX` (case insensitive), optionally in braces.
+impl FromStr for Guid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || err::InvalidIdentifier {
+            value: s,
+            kind: "GUID",
+        };
+
+        let unbraced = s
+            .strip_prefix('{')
+            .and_then(|s| s.strip_suffix('}'))
+            .unwrap_or(s);
+
+        let groups: Vec<&str> = unbraced.split('-').collect();
+        let lengths: Vec<usize> = groups.iter().map(|group| group.len()).collect();
+        if lengths != [8, 4, 4, 4, 12]
+            || !unbraced.chars().all(|c| c == '-' || c.is_ascii_hexdigit())
+        {
+            return invalid().fail();
+        }
+
+        let hex = |group: &str| u64::from_str_radix(group, 16).expect("checked to be hex digits");
+
+        let mut data4 = [0; 8];
+        data4[..2].copy_from_slice(&(hex(groups[3]) as u16).to_be_bytes());
+        data4[2..].copy_from_slice(&hex(groups[4]).to_be_bytes()[2..]);
+
+        Ok(Guid::new(
+            u32::try_from(hex(groups[0])).expect("8 hex digits"),
+            hex(groups[1]) as u16,
+            hex(groups[2]) as u16,
+            data4,
+        ))
+    }
+}
+
+/// Controls how GUIDs are rendered in the XML and JSON output.
+///
+/// The default is hyphenated and uppercase, without braces (`54849625-5478-4994-A5BA-3E3B0328C30D`),
+/// `GuidFormat::braced()` matches the output of `wevtutil` (`{54849625-5478-4994-A5BA-3E3B0328C30D}`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GuidFormat {
+    pub braced: bool,
+    pub hyphenated: bool,
+    pub uppercase: bool,
+}
+
+impl Default for GuidFormat {
+    fn default() -> Self {
+        GuidFormat {
+            braced: false,
+            hyphenated: true,
+            uppercase: true,
+        }
+    }
+}
+
+impl GuidFormat {
+    /// Hyphenated and uppercase, in braces.
+    pub fn braced() -> Self {
+        GuidFormat {
+            braced: true,
+            ..GuidFormat::default()
+        }
+    }
+
+    pub fn format(self, guid: &Guid) -> String {
+        let mut s = guid.to_string();
+
+        if !self.hyphenated {
+            s.retain(|c| c != '-');
+        }
+        if !self.uppercase {
+            s.make_ascii_lowercase();
+        }
+        if self.braced {
+            s = format!("{{{}}}", s);
+        }
+
+        s
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<Guid> for uuid::Uuid {
+    fn from(guid: Guid) -> Self {
+        uuid::Uuid::from_fields(guid.data1, guid.data2, guid.data3, &guid.data4)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for Guid {
+    fn from(uuid: uuid::Uuid) -> Self {
+        let (data1, data2, data3, data4) = uuid.as_fields();
+        Guid::new(data1, data2, data3, *data4)
+    }
+}
+
+/// For GUIDs, a string representation is preferable to a struct for serialization.
+impl ser::Serialize for Guid {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Guid {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_guids() {
+        let guid = Guid::new(
+            0x5484_9625,
+            0x5478,
+            0x4994,
+            [0xA5, 0xBA, 0x3E, 0x3B, 0x03, 0x28, 0xC3, 0x0D],
+        );
+
+        assert_eq!(
+            "54849625-5478-4994-A5BA-3E3B0328C30D"
+                .parse::<Guid>()
+                .unwrap(),
+            guid
+        );
+        assert_eq!(Guid::from_bytes(guid.to_bytes()), guid);
+
+        for invalid in &[
+            "",
+            "54849625-5478-4994-A5BA-3E3B0328C30",
+            "54849625-5478-4994-A5BA3E3B-0328C30D",
+            "{54849625-5478-4994-A5BA-3E3B0328C30D",
+            "5484962G-5478-4994-A5BA-3E3B0328C30D",
+            "+4849625-5478-4994-A5BA-3E3B0328C30D",
+        ] {
+            assert!(invalid.parse::<Guid>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_guid_formats() {
+        let guid: Guid = "54849625-5478-4994-A5BA-3E3B0328C30D".parse().unwrap();
+
+        assert_eq!(
+            GuidFormat::default().format(&guid),
+            "54849625-5478-4994-A5BA-3E3B0328C30D"
+        );
+        assert_eq!(
+            GuidFormat::braced().format(&guid),
+            "{54849625-5478-4994-A5BA-3E3B0328C30D}"
+        );
+        let format = GuidFormat {
+            braced: false,
+            hyphenated: false,
+            uppercase: false,
+        };
+        assert_eq!(format.format(&guid), "5484962554784994a5ba3e3b0328c30d");
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn test_uuid_conversions() {
+        let guid: Guid = "54849625-5478-4994-A5BA-3E3B0328C30D".parse().unwrap();
+        let uuid = uuid::Uuid::from(guid);
+
+        assert_eq!(uuid.to_string(), "54849625-5478-4994-a5ba-3e3b0328c30d");
+        assert_eq!(Guid::from(uuid), guid);
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let guid: Guid = "{54849625-5478-4994-A5BA-3E3B0328C30D}".parse().unwrap();
+
+        let json = serde_json::to_string(&guid).unwrap();
+        assert_eq!(json, "\"54849625-5478-4994-A5BA-3E3B0328C30D\"");
+        assert_eq!(serde_json::from_str::<Guid>(&json).unwrap(), guid);
+        assert!(serde_json::from_str::<Guid>("\"not a guid\"").is_err());
+    }
+}
@@ -0,0 +1,532 @@
+//! Importing of records rendered as XML or JSON back into EVTX files, see `EvtxWriter::import`.
+//!
+//! Supported inputs are the output of this crate (`evtx_dump` XML, JSON and JSON lines, with or without
+//! the `Record N` lines), and the XML exported by Event Viewer or `wevtutil qe /f:xml`
+//! (the `RenderingInfo` elements of these are left out, since they are not part of the records).
+//!
+//! The rendered records do not hold the types of their values, so:
+//! - The values of the `System` element are given the types Windows uses for them
+//!   (`EventID` is a `UInt16`, `TimeCreated/@SystemTime` a `FileTime`, `Security/@UserID` a `Sid`...).
+//! - Other XML values are strings.
+//! - Other JSON values keep their JSON type: numbers are 64 bit integers (or `Real64`), booleans are `Bool`.
+//!
+//! JSON objects are sorted by key, so the `System` element of JSON records is moved first
+//! (and it's children are put in the usual order), but the order of the `EventData` fields is lost.
+//! `EventData` fields are imported as `Data` elements with a `Name` attribute, as they are rendered.
+use crate::binxml::value_variant::BinXmlValue;
+use crate::err::{self, Result};
+use crate::evtx_writer::{EventContent, EventElement};
+use crate::guid::{Guid, GuidFormat};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde_json::Value;
+use snafu::ResultExt;
+
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// The format of the records to import.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImportFormat {
+    Xml,
+    /// JSON documents (such as JSON lines), or arrays of them.
+    Json,
+}
+
+impl ImportFormat {
+    /// Returns `Json` if the input (past any `Record N` line) starts like a JSON document, `Xml` otherwise.
+    pub fn detect(input: &str) -> Self {
+        match without_record_lines(input).trim_start().chars().next() {
+            Some('{') | Some('[') => ImportFormat::Json,
+            _ => ImportFormat::Xml,
+        }
+    }
+}
+
+/// The children of `System`, in the order Windows renders them.
+const SYSTEM_ELEMENTS: &[&str] = &[
+    "Provider",
+    "EventID",
+    "Version",
+    "Level",
+    "Task",
+    "Opcode",
+    "Keywords",
+    "TimeCreated",
+    "EventRecordID",
+    "Correlation",
+    "Execution",
+    "Channel",
+    "Computer",
+    "Security",
+];
+
+impl EventElement {
+    /// Parses all the `Event` elements of an XML document (either concatenated, or inside an `Events` element).
+    pub fn parse_xml(xml: &str) -> Result<Vec<EventElement>> {
+        let mut reader = Reader::from_str(xml);
+        let mut buf = vec![];
+        let mut stack: Vec<EventElement> = vec![];
+        let mut events = vec![];
+        // `<Data></Data>` holds an empty string, unlike `<Data/>` (or an element with only whitespace).
+        let mut empty_string = false;
+
+        let fail = |reader: &Reader<&[u8]>, message: String| err::Error::FailedToParseXml {
+            message,
+            position: reader.buffer_position(),
+        };
+
+        loop {
+            let event = reader
+                .read_event(&mut buf)
+                .map_err(|e| fail(&reader, e.to_string()))?;
+
+            let opened = match event {
+                Event::Start(_) => true,
+                Event::Text(ref text) if text.escaped().is_empty() => empty_string,
+                _ => false,
+            };
+            let closed = match event {
+                Event::Start(ref start) => {
+                    stack.push(read_element(&reader, start)?);
+                    None
+                }
+                Event::Empty(ref start) => Some(read_element(&reader, start)?),
+                Event::End(_) if empty_string => stack.pop().map(|element| element.text("")),
+                Event::End(_) => stack.pop(),
+                Event::Text(ref text) => {
+                    let text = text.unescaped().map_err(|e| fail(&reader, e.to_string()))?;
+                    add_text(stack.last_mut(), &String::from_utf8_lossy(&text));
+                    None
+                }
+                Event::CData(ref text) => {
+                    add_text(stack.last_mut(), &String::from_utf8_lossy(text.escaped()));
+                    None
+                }
+                Event::Eof => break,
+                _ => None,
+            };
+
+            empty_string = opened;
+
+            match closed {
+                Some(mut element) if element.name == "Event" => {
+                    type_system_values(&mut element);
+                    events.push(element);
+                }
+                Some(element) if element.name != "RenderingInfo" => {
+                    if let Some(parent) = stack.last_mut() {
+                        parent.children.push(EventContent::Element(element));
+                    }
+                }
+                _ => {}
+            }
+
+            buf.clear();
+        }
+
+        Ok(events)
+    }
+
+    /// Parses JSON records (see `from_json`), which are either concatenated (such as JSON lines)
+    /// or in arrays.
+    pub fn parse_json(json: &str) -> Result<Vec<EventElement>> {
+        let mut events = vec![];
+
+        for document in serde_json::Deserializer::from_str(&without_record_lines(json)).into_iter()
+        {
+            match document.context(err::JsonError)? {
+                Value::Array(documents) => {
+                    for document in &documents {
+                        events.push(EventElement::from_json(document)?);
+                    }
+                }
+                document => events.push(EventElement::from_json(&document)?),
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Builds the element tree of a record rendered as JSON (an object with an `Event` key),
+    /// with attributes either in `#attributes` objects or separated (see `ParserSettings::separate_json_attributes`).
+    pub fn from_json(value: &Value) -> Result<EventElement> {
+        let event = value
+            .get("Event")
+            .ok_or_else(|| err::Error::FailedToImportRecord {
+                message: "expected an object with an `Event` key".to_string(),
+            })?;
+
+        let mut element = elements_from_json("Event", event, "")
+            .pop()
+            .ok_or_else(|| err::Error::FailedToImportRecord {
+                message: "`Event` is an empty array".to_string(),
+            })?;
+
+        element.children.sort_by_key(|content| match content {
+            EventContent::Element(element) => element.name != "System",
+            EventContent::Value(_) => true,
+        });
+
+        if let Some(system) = element.find_mut("System") {
+            system.children.sort_by_key(|content| match content {
+                EventContent::Element(element) => SYSTEM_ELEMENTS
+                    .iter()
+                    .position(|name| *name == element.name)
+                    .unwrap_or(SYSTEM_ELEMENTS.len()),
+                EventContent::Value(_) => SYSTEM_ELEMENTS.len(),
+            });
+        }
+
+        type_system_values(&mut element);
+        Ok(element)
+    }
+
+    /// Returns the `System/EventRecordID` of an imported event.
+    pub(crate) fn event_record_id(&self) -> Option<u64> {
+        let element = self.find("System")?.find("EventRecordID")?;
+        match element.children.first()? {
+            EventContent::Value(BinXmlValue::UInt64Type(id)) => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Returns the `System/TimeCreated/@SystemTime` of an imported event.
+    pub(crate) fn time_created(&self) -> Option<DateTime<Utc>> {
+        let element = self.find("System")?.find("TimeCreated")?;
+        element
+            .attributes
+            .iter()
+            .find_map(|(name, value)| match value {
+                BinXmlValue::FileTimeType(time) if name == "SystemTime" => Some(*time),
+                _ => None,
+            })
+    }
+}
+
+/// Drops the `Record N` lines printed by `evtx_dump` before every record.
+fn without_record_lines(input: &str) -> Cow<'_, str> {
+    let is_record_line = |line: &str| {
+        line.strip_prefix("Record ")
+            .map_or(false, |id| id.trim().chars().all(|c| c.is_ascii_digit()))
+    };
+
+    if !input.lines().any(is_record_line) {
+        return Cow::Borrowed(input);
+    }
+
+    Cow::Owned(
+        input
+            .lines()
+            .filter(|line| !is_record_line(line))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
+fn read_element(reader: &Reader<&[u8]>, start: &BytesStart) -> Result<EventElement> {
+    let mut element = EventElement::new(String::from_utf8_lossy(start.name()));
+
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| err::Error::FailedToParseXml {
+            message: e.to_string(),
+            position: reader.buffer_position(),
+        })?;
+
+        let value = attribute
+            .unescaped_value()
+            .map_err(|e| err::Error::FailedToParseXml {
+                message: e.to_string(),
+                position: reader.buffer_position(),
+            })?;
+
+        element = element.attribute(
+            String::from_utf8_lossy(attribute.key),
+            BinXmlValue::StringType(Cow::Owned(String::from_utf8_lossy(&value).into_owned())),
+        );
+    }
+
+    Ok(element)
+}
+
+/// Adds text to an element, ignoring whitespace (the indentation of the document).
+fn add_text(element: Option<&mut EventElement>, text: &str) {
+    if let Some(element) = element.filter(|_| !text.trim().is_empty()) {
+        element
+            .children
+            .push(EventContent::Value(BinXmlValue::StringType(Cow::Owned(
+                text.to_string(),
+            ))));
+    }
+}
+
+/// Builds the elements named `name` from their JSON rendering (arrays are repeated elements).
+fn elements_from_json(name: &str, value: &Value, parent: &str) -> Vec<EventElement> {
+    if let Value::Array(values) = value {
+        return values
+            .iter()
+            .flat_map(|value| elements_from_json(name, value, parent))
+            .collect();
+    }
+
+    // `EventData` fields are rendered by their `Name`.
+    let mut element = if parent == "EventData" && name != "Data" && name != "Binary" {
+        EventElement::new("Data").attribute("Name", string(name))
+    } else {
+        EventElement::new(name)
+    };
+
+    let object = match value {
+        Value::Object(object) => object,
+        Value::Null => return vec![element],
+        scalar => return vec![element.value(json_value(scalar))],
+    };
+
+    for (key, value) in object {
+        match key.as_str() {
+            "#attributes" => {
+                for (name, value) in value.as_object().into_iter().flatten() {
+                    element.attributes.push((name.clone(), json_value(value)));
+                }
+            }
+            "#text" => element
+                .children
+                .push(EventContent::Value(json_value(value))),
+            // Separated attributes are added once all the elements are built.
+            key if key.ends_with("_attributes") => {}
+            key => {
+                for child in elements_from_json(key, value, name) {
+                    element.children.push(EventContent::Element(child));
+                }
+            }
+        }
+    }
+
+    for (key, value) in object {
+        let name = match key.strip_suffix("_attributes") {
+            Some(name) => name,
+            None => continue,
+        };
+
+        let attributes = value
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(name, value)| (name.clone(), json_value(value)));
+
+        match element.find_mut(name) {
+            Some(child) => child.attributes.extend(attributes),
+            None => element.children.push(EventContent::Element(EventElement {
+                name: name.to_string(),
+                attributes: attributes.collect(),
+                children: vec![],
+            })),
+        }
+    }
+
+    vec![element]
+}
+
+fn json_value(value: &Value) -> BinXmlValue<'static> {
+    match value {
+        Value::Null => BinXmlValue::NullType,
+        Value::Bool(b) => BinXmlValue::BoolType(*b),
+        Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+            (Some(n), _) => BinXmlValue::UInt64Type(n),
+            (None, Some(n)) => BinXmlValue::Int64Type(n),
+            _ => BinXmlValue::Real64Type(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => string(s),
+        other => string(&other.to_string()),
+    }
+}
+
+fn string(s: &str) -> BinXmlValue<'static> {
+    BinXmlValue::StringType(Cow::Owned(s.to_string()))
+}
+
+/// Gives the values of the `System` element of `event` the types Windows uses for them.
+fn type_system_values(event: &mut EventElement) {
+    let system = match event.find_mut("System") {
+        Some(system) => system,
+        None => return,
+    };
+
+    for content in &mut system.children {
+        let element = match content {
+            EventContent::Element(element) => element,
+            EventContent::Value(_) => continue,
+        };
+
+        for (attribute, value) in &mut element.attributes {
+            if let Some(typed) = system_value(&element.name, Some(attribute), value) {
+                *value = typed;
+            }
+        }
+
+        for child in &mut element.children {
+            if let EventContent::Value(value) = child {
+                if let Some(typed) = system_value(&element.name, None, value) {
+                    *value = typed;
+                }
+            }
+        }
+    }
+}
+
+/// Returns the typed value of an element (or of one of it's attributes) of `System`,
+/// `None` if it is not typed, or if it's text is not how a value of it's type is rendered
+/// (some providers write these values as strings, such as GUIDs in lowercase).
+fn system_value(
+    element: &str,
+    attribute: Option<&str>,
+    value: &BinXmlValue,
+) -> Option<BinXmlValue<'static>> {
+    let text = value.as_cow_str();
+    let text = text.as_ref();
+
+    let value = match (element, attribute) {
+        ("EventID", None) | ("EventID", Some("Qualifiers")) | ("Task", None) => {
+            BinXmlValue::UInt16Type(parse_exact(text)?)
+        }
+        ("Version", None) | ("Level", None) | ("Opcode", None) => {
+            BinXmlValue::UInt8Type(parse_exact(text)?)
+        }
+        ("Keywords", None) => {
+            let digits = text.strip_prefix("0x")?;
+            u64::from_str_radix(digits, 16).ok()?;
+            BinXmlValue::HexInt64Type(Cow::Owned(text.to_string()))
+        }
+        ("EventRecordID", None) => BinXmlValue::UInt64Type(parse_exact(text)?),
+        ("TimeCreated", Some("SystemTime")) => BinXmlValue::FileTimeType(parse_timestamp(text)?),
+        ("Provider", Some("Guid"))
+        | ("Correlation", Some("ActivityID"))
+        | ("Correlation", Some("RelatedActivityID")) => {
+            // As rendered by this crate, or by Windows (in braces).
+            let guid: Guid = text.parse().ok()?;
+            if text != guid.to_string() && text != GuidFormat::braced().format(&guid) {
+                return None;
+            }
+            BinXmlValue::GuidType(guid)
+        }
+        ("Execution", Some("ProcessID")) | ("Execution", Some("ThreadID")) => {
+            BinXmlValue::UInt32Type(parse_exact(text)?)
+        }
+        ("Security", Some("UserID")) => BinXmlValue::SidType(parse_exact(text)?),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+/// Parses `text`, if it is how the parsed value is rendered (so `007` is not a number).
+fn parse_exact<T: FromStr + ToString>(text: &str) -> Option<T> {
+    text.parse::<T>()
+        .ok()
+        .filter(|value| value.to_string() == text)
+}
+
+/// Parses timestamps rendered as JSON or by Windows (`2016-07-08T18:12:51.681640Z`),
+/// or as XML by this crate (`2016-07-08 18:12:51.681640 UTC`).
+fn parse_timestamp(text: &str) -> Option<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(text) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+
+    let timestamp = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f UTC").ok()?;
+    Some(DateTime::from_utc(timestamp, Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EvtxParser, EvtxWriter, ParserSettings};
+    use std::io::Cursor;
+
+    const SAMPLE: &str = "samples/security.evtx";
+
+    fn import(input: &str) -> Vec<u8> {
+        let mut writer = EvtxWriter::new(Cursor::new(vec![])).unwrap();
+        writer.import(input, ImportFormat::detect(input)).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn xml_records(
+        mut parser: EvtxParser<impl crate::evtx_parser::ReadSeek>,
+    ) -> Vec<(u64, String)> {
+        parser
+            .records()
+            .map(|record| record.unwrap())
+            .map(|record| (record.event_record_id, record.data))
+            .collect()
+    }
+
+    fn json_records(mut parser: EvtxParser<impl crate::evtx_parser::ReadSeek>) -> Vec<Value> {
+        parser
+            .records_json_value()
+            .map(|record| record.unwrap().data)
+            .collect()
+    }
+
+    fn no_indent<T: crate::evtx_parser::ReadSeek>(parser: EvtxParser<T>) -> EvtxParser<T> {
+        parser.with_configuration(ParserSettings::new().indent(false))
+    }
+
+    #[test]
+    fn test_xml_round_trip() {
+        let original = xml_records(EvtxParser::from_path(SAMPLE).unwrap());
+        let xml: String = original
+            .iter()
+            .map(|(id, xml)| format!("Record {}\n{}\n", id, xml))
+            .collect();
+
+        let imported = xml_records(EvtxParser::from_buffer(import(&xml)).unwrap());
+
+        assert_eq!(imported.len(), original.len());
+        assert_eq!(imported, original);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let original = json_records(no_indent(EvtxParser::from_path(SAMPLE).unwrap()));
+        let jsonl: String = original
+            .iter()
+            .map(|record| format!("{}\n", record))
+            .collect();
+
+        let imported = json_records(EvtxParser::from_buffer(import(&jsonl)).unwrap());
+
+        assert_eq!(imported, original);
+    }
+
+    #[test]
+    fn test_imports_event_viewer_exports() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Events>
+<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-A5BA-3E3B0328C30D}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2023-01-02T03:04:05.6789012Z'/><EventRecordID>1234</EventRecordID><Correlation ActivityID='{3c8e6f21-1b5a-0001-9c6f-8e3c5a1bd901}'/><Execution ProcessID='788' ThreadID='5004'/><Channel>Security</Channel><Computer>WS042.corp.example.com</Computer><Security/></System><EventData><Data Name='TargetUserName'>alice &amp; bob</Data><Data Name='LogonType'>10</Data></EventData><RenderingInfo Culture='en-US'><Message>An account was successfully logged on.</Message></RenderingInfo></Event>
+</Events>"#;
+
+        let mut parser = EvtxParser::from_buffer(import(xml)).unwrap();
+        let record = parser.records_json_value().next().unwrap().unwrap();
+
+        assert_eq!(record.event_record_id, 1234);
+        assert_eq!(
+            record.timestamp,
+            "2023-01-02T03:04:05.678901Z"
+                .parse::<DateTime<Utc>>()
+                .unwrap()
+        );
+
+        let event = &record.data["Event"];
+        assert_eq!(event["System"]["EventID"], 4624);
+        assert_eq!(
+            event["System"]["Provider"]["#attributes"]["Guid"],
+            "54849625-5478-4994-A5BA-3E3B0328C30D"
+        );
+        assert_eq!(event["EventData"]["TargetUserName"], "alice & bob");
+        assert_eq!(event["EventData"]["LogonType"], "10");
+        assert!(event.get("RenderingInfo").is_none());
+    }
+}
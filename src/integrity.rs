@@ -0,0 +1,395 @@
+//! A fast scan of the structure of an EVTX file, without deserializing any record, see `EvtxParser::validate`.
+use crate::chunk_table::ChunkStatus;
+use crate::evtx_chunk::{
+    read_plausible_record, EvtxChunkData, ImplausibleRecord, EVTX_CHUNK_HEADER_SIZE,
+};
+
+use serde_json::{json, Value};
+
+use std::cmp::min;
+use std::convert::TryInto;
+use std::fmt;
+
+const HEADER_DIRTY_FLAG: u8 = 0x1;
+
+/// The result of validating the file header, the checksums of every chunk and the chain of record headers in them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegrityReport {
+    /// `None` for files without a file header (see `EvtxParser::from_chunks`).
+    pub file_header: Option<FileHeaderIntegrity>,
+    /// Every chunk of the file, including empty chunks (up to the `chunk_count` of the header).
+    pub chunks: Vec<ChunkIntegrity>,
+    /// Whether the file ends in the middle of a chunk, or before the last chunk of the header.
+    pub truncated: bool,
+}
+
+/// The state of the file header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileHeaderIntegrity {
+    /// Whether the CRC32 of the first 120 bytes of the header matches it's checksum.
+    pub checksum_valid: bool,
+    /// The number of chunks according to the header.
+    pub chunk_count: u16,
+    /// The number of (non-empty) chunks found in the file, which can be larger for dirty files.
+    pub chunks_found: u16,
+    /// Whether the dirty flag is set, as it is while the file is in use (or if it was not closed properly).
+    pub dirty: bool,
+}
+
+/// The state of a single chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkIntegrity {
+    pub chunk_number: u16,
+    /// Offset of the chunk from the start of the file.
+    pub offset: u64,
+    pub status: ChunkStatus,
+    /// `None` unless the status is `ChunkStatus::Valid`.
+    pub header_checksum_valid: Option<bool>,
+    /// `None` unless the status is `ChunkStatus::Valid`.
+    pub data_checksum_valid: Option<bool>,
+    /// The number of records found by following the chain of record headers.
+    pub records: u64,
+    /// The first inconsistency of the chain of record headers, if any.
+    pub record_chain_error: Option<RecordChainError>,
+}
+
+/// An inconsistency in the chain of record headers of a chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordChainError {
+    /// Offset of the offending record from the start of the chunk.
+    pub offset: u64,
+    pub kind: RecordChainErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordChainErrorKind {
+    /// The record header could not be read (bad magic, or truncated).
+    InvalidHeader(String),
+    /// The size of the record is too small, or goes past the free space of the chunk.
+    InvalidSize(u32),
+    /// The copy of the size at the end of the record does not match the size in it's header.
+    SizeMismatch { size: u32, trailing_size: u32 },
+    /// Record IDs of a chunk are expected to be consecutive, starting at the first ID of the chunk header.
+    UnexpectedRecordId { expected: u64, found: u64 },
+    /// The last record of the chain is not the last record according to the chunk header.
+    LastRecordMismatch { expected: u64, found: Option<u64> },
+}
+
+impl From<ImplausibleRecord> for RecordChainErrorKind {
+    fn from(e: ImplausibleRecord) -> Self {
+        match e {
+            ImplausibleRecord::InvalidHeader(e) => {
+                RecordChainErrorKind::InvalidHeader(e.to_string())
+            }
+            ImplausibleRecord::InvalidSize(size) => RecordChainErrorKind::InvalidSize(size),
+            ImplausibleRecord::SizeMismatch {
+                size,
+                trailing_size,
+            } => RecordChainErrorKind::SizeMismatch {
+                size,
+                trailing_size,
+            },
+        }
+    }
+}
+
+impl IntegrityReport {
+    /// Returns `true` if no issue was found (a dirty file can be valid).
+    pub fn is_valid(&self) -> bool {
+        !self.truncated
+            && self
+                .file_header
+                .as_ref()
+                .map_or(true, |header| header.checksum_valid)
+            && self.chunks.iter().all(ChunkIntegrity::is_valid)
+    }
+
+    /// Returns the chunks with any issue.
+    pub fn invalid_chunks(&self) -> impl Iterator<Item = &ChunkIntegrity> {
+        self.chunks.iter().filter(|chunk| !chunk.is_valid())
+    }
+
+    /// The number of records found in all chunks.
+    pub fn record_count(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.records).sum()
+    }
+
+    pub fn to_json_value(&self) -> Value {
+        let file_header = self.file_header.as_ref().map(|header| {
+            json!({
+                "checksum_valid": header.checksum_valid,
+                "chunk_count": header.chunk_count,
+                "chunks_found": header.chunks_found,
+                "dirty": header.dirty,
+            })
+        });
+
+        let chunks: Vec<Value> = self
+            .chunks
+            .iter()
+            .map(|chunk| {
+                json!({
+                    "chunk_number": chunk.chunk_number,
+                    "offset": chunk.offset,
+                    "status": chunk.status.as_str(),
+                    "error": match &chunk.status {
+                        ChunkStatus::Invalid(e) => Some(e),
+                        _ => None,
+                    },
+                    "header_checksum_valid": chunk.header_checksum_valid,
+                    "data_checksum_valid": chunk.data_checksum_valid,
+                    "records": chunk.records,
+                    "record_chain_error": chunk.record_chain_error.as_ref().map(|e| json!({
+                        "offset": e.offset,
+                        "error": e.kind.to_string(),
+                    })),
+                })
+            })
+            .collect();
+
+        json!({
+            "valid": self.is_valid(),
+            "records": self.record_count(),
+            "truncated": self.truncated,
+            "file_header": file_header,
+            "chunks": chunks,
+        })
+    }
+}
+
+impl fmt::Display for IntegrityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(header) = &self.file_header {
+            writeln!(
+                f,
+                "File header: checksum {}, {} chunks ({} found){}",
+                if header.checksum_valid {
+                    "valid"
+                } else {
+                    "INVALID"
+                },
+                header.chunk_count,
+                header.chunks_found,
+                if header.dirty { ", dirty" } else { "" }
+            )?;
+        }
+
+        if self.truncated {
+            writeln!(f, "File is TRUNCATED")?;
+        }
+
+        for chunk in self.invalid_chunks() {
+            write!(f, "Chunk {} (offset {}):", chunk.chunk_number, chunk.offset)?;
+
+            if let ChunkStatus::Invalid(e) = &chunk.status {
+                write!(f, " {}", e)?;
+            }
+            if chunk.header_checksum_valid == Some(false) {
+                write!(f, " invalid header checksum,")?;
+            }
+            if chunk.data_checksum_valid == Some(false) {
+                write!(f, " invalid data checksum,")?;
+            }
+            if let Some(e) = &chunk.record_chain_error {
+                write!(f, " {} (at offset {})", e.kind, e.offset)?;
+            }
+            writeln!(f)?;
+        }
+
+        write!(
+            f,
+            "{}: {} chunks, {} records, {} invalid chunks",
+            if self.is_valid() { "VALID" } else { "INVALID" },
+            self.chunks.len(),
+            self.record_count(),
+            self.invalid_chunks().count()
+        )
+    }
+}
+
+impl fmt::Display for RecordChainErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordChainErrorKind::InvalidHeader(e) => write!(f, "invalid record header: {}", e),
+            RecordChainErrorKind::InvalidSize(size) => write!(f, "invalid record size {}", size),
+            RecordChainErrorKind::SizeMismatch {
+                size,
+                trailing_size,
+            } => write!(
+                f,
+                "record size {} does not match the trailing size {}",
+                size, trailing_size
+            ),
+            RecordChainErrorKind::UnexpectedRecordId { expected, found } => {
+                write!(f, "expected record ID {}, found {}", expected, found)
+            }
+            RecordChainErrorKind::LastRecordMismatch { expected, found } => write!(
+                f,
+                "expected the last record ID to be {}, found {:?}",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl FileHeaderIntegrity {
+    /// Validates the (first 120 bytes of the) file header.
+    pub(crate) fn new(header_data: &[u8], chunk_count: u16, chunks_found: u16) -> Self {
+        let checksum_valid = match (header_data.get(..120), header_data.get(124..128)) {
+            (Some(data), Some(checksum)) => {
+                crc32fast::hash(data) == u32::from_le_bytes(checksum.try_into().expect("4 bytes"))
+            }
+            _ => false,
+        };
+
+        FileHeaderIntegrity {
+            checksum_valid,
+            chunk_count,
+            chunks_found,
+            // The flags follow the checksummed part of the header.
+            dirty: header_data
+                .get(120)
+                .map_or(false, |flags| flags & HEADER_DIRTY_FLAG != 0),
+        }
+    }
+}
+
+impl ChunkIntegrity {
+    /// Validates the (full, `EVTX_CHUNK_SIZE` sized) `data` of the chunk at `offset`.
+    pub(crate) fn from_chunk_data(chunk_number: u16, offset: u64, data: Vec<u8>) -> Self {
+        let mut integrity =
+            ChunkIntegrity::without_header(chunk_number, offset, ChunkStatus::Empty);
+
+        if data.iter().all(|b| *b == 0) {
+            return integrity;
+        }
+
+        let chunk = match EvtxChunkData::new(data, false) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                integrity.status = ChunkStatus::Invalid(e.to_string());
+                return integrity;
+            }
+        };
+
+        integrity.status = ChunkStatus::Valid;
+        integrity.header_checksum_valid = Some(chunk.validate_header_checksum());
+        // A corrupted free space offset would make the checksum panic.
+        integrity.data_checksum_valid = Some(
+            (chunk.header.free_space_offset as usize) <= chunk.data.len()
+                && chunk.validate_data_checksum(),
+        );
+
+        let (records, record_chain_error) = validate_record_chain(&chunk);
+        integrity.records = records;
+        integrity.record_chain_error = record_chain_error;
+
+        integrity
+    }
+
+    pub(crate) fn without_header(chunk_number: u16, offset: u64, status: ChunkStatus) -> Self {
+        ChunkIntegrity {
+            chunk_number,
+            offset,
+            status,
+            header_checksum_valid: None,
+            data_checksum_valid: None,
+            records: 0,
+            record_chain_error: None,
+        }
+    }
+
+    /// Returns `true` if no issue was found (empty chunks are valid).
+    pub fn is_valid(&self) -> bool {
+        !matches!(self.status, ChunkStatus::Invalid(_))
+            && self.header_checksum_valid != Some(false)
+            && self.data_checksum_valid != Some(false)
+            && self.record_chain_error.is_none()
+    }
+}
+
+/// Follows the record headers from the start of the chunk up to it's free space,
+/// returning the number of records and the first inconsistency found.
+fn validate_record_chain(chunk: &EvtxChunkData) -> (u64, Option<RecordChainError>) {
+    let header = &chunk.header;
+    let end = min(header.free_space_offset as usize, chunk.data.len());
+
+    let mut offset = EVTX_CHUNK_HEADER_SIZE;
+    let mut records = 0;
+    let mut last_record_id = None;
+
+    let error = |offset: usize, kind: RecordChainErrorKind| RecordChainError {
+        offset: offset as u64,
+        kind,
+    };
+
+    while offset < end {
+        let record = match read_plausible_record(&chunk.data[..end], offset) {
+            Ok(record) => record,
+            Err(e) => return (records, Some(error(offset, e.into()))),
+        };
+
+        let expected = header.first_event_record_id + records;
+        if record.event_record_id != expected {
+            let kind = RecordChainErrorKind::UnexpectedRecordId {
+                expected,
+                found: record.event_record_id,
+            };
+            return (records, Some(error(offset, kind)));
+        }
+
+        records += 1;
+        last_record_id = Some(record.event_record_id);
+        offset += record.data_size as usize;
+    }
+
+    if last_record_id != Some(header.last_event_record_id) {
+        let kind = RecordChainErrorKind::LastRecordMismatch {
+            expected: header.last_event_record_id,
+            found: last_record_id,
+        };
+        return (records, Some(error(offset, kind)));
+    }
+
+    (records, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evtx_parser::EVTX_CHUNK_SIZE;
+
+    fn first_chunk() -> Vec<u8> {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        evtx_file[4096..4096 + EVTX_CHUNK_SIZE].to_vec()
+    }
+
+    #[test]
+    fn test_valid_chunk() {
+        let integrity = ChunkIntegrity::from_chunk_data(0, 4096, first_chunk());
+
+        assert!(integrity.is_valid(), "{:?}", integrity);
+        assert_eq!(integrity.records, 91);
+    }
+
+    #[test]
+    fn test_detects_broken_record_chain() {
+        let mut data = first_chunk();
+        // The trailing size of the first record.
+        let size = u32::from_le_bytes(data[516..520].try_into().unwrap()) as usize;
+        data[512 + size - 4] ^= 0xff;
+
+        let integrity = ChunkIntegrity::from_chunk_data(0, 4096, data);
+
+        assert_eq!(integrity.data_checksum_valid, Some(false));
+        assert_eq!(integrity.records, 0);
+        assert!(matches!(
+            integrity.record_chain_error,
+            Some(RecordChainError {
+                offset: 512,
+                kind: RecordChainErrorKind::SizeMismatch { .. }
+            })
+        ));
+        assert!(!integrity.is_valid());
+    }
+}
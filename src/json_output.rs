@@ -1,7 +1,8 @@
 use crate::err::{self, Result};
 use snafu::{ensure, OptionExt};
 
-use crate::binxml::value_variant::BinXmlValue;
+use crate::binxml::value_variant::{BinXmlValue, FloatFormat};
+use crate::guid::GuidFormat;
 use crate::model::xml::XmlElement;
 use crate::unimplemented_fn;
 use crate::xml_output::BinXmlOutput;
@@ -18,6 +19,8 @@ pub struct JsonOutput {
     map: Value,
     stack: Vec<String>,
     separate_json_attributes: bool,
+    float_format: FloatFormat,
+    guid_format: GuidFormat,
 }
 
 impl JsonOutput {
@@ -26,6 +29,8 @@ impl JsonOutput {
             map: Value::Object(Map::new()),
             stack: vec![],
             separate_json_attributes: settings.should_separate_json_attributes(),
+            float_format: settings.get_float_format(),
+            guid_format: settings.get_guid_format(),
         }
     }
 
@@ -121,8 +126,10 @@ impl JsonOutput {
         let mut attributes = Map::new();
 
         for attribute in element.attributes.iter() {
-            let value = attribute.value.clone().into_owned();
-            let value: Value = value.into();
+            let value = attribute
+                .value
+                .as_ref()
+                .to_json_with_formats(self.float_format, self.guid_format);
 
             if !value.is_null() {
                 let name: &str = attribute.name.as_str();
@@ -188,6 +195,35 @@ impl JsonOutput {
     }
 }
 
+/// Inserts `value` into the `root` object, at the nested `path` (creating the objects along it).
+/// Non-object values found along the path are replaced.
+pub(crate) fn insert_at_path(root: &mut Value, path: &[&str], value: Value) {
+    let (last, parents) = match path.split_last() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let mut current = root;
+    for key in parents {
+        let object = match current {
+            Value::Object(object) => object,
+            _ => return,
+        };
+
+        let child = object
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !child.is_object() {
+            *child = Value::Object(Map::new());
+        }
+        current = child;
+    }
+
+    if let Value::Object(object) = current {
+        object.insert(last.to_string(), value);
+    }
+}
+
 impl BinXmlOutput for JsonOutput {
     fn visit_end_of_stream(&mut self) -> Result<()> {
         trace!("visit_end_of_stream");
@@ -220,13 +256,14 @@ impl BinXmlOutput for JsonOutput {
         trace!("visit_chars {:?}", &self.stack);
         // We need to clone this bool since the next statement will borrow self as mutable.
         let separate_json_attributes = self.separate_json_attributes;
+        let value = value.to_json_with_formats(self.float_format, self.guid_format);
         let current_value = self.get_or_create_current_path();
 
         // If our parent is an element without any attributes,
         // we simply swap the null with the string value.
         // This is also true for the case when the attributes were inserted as our siblings.
         if current_value.is_null() || separate_json_attributes {
-            mem::replace(current_value, value.clone().into());
+            *current_value = value;
         } else {
             // Otherwise,
             // Should look like:
@@ -244,7 +281,7 @@ impl BinXmlOutput for JsonOutput {
                         message: "expected current value to be an object type",
                     })?;
 
-            current_object.insert("#text".to_owned(), value.clone().into());
+            current_object.insert("#text".to_owned(), value);
         }
 
         Ok(())
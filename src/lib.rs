@@ -6,27 +6,140 @@
 #[macro_use]
 mod macros;
 
-pub use evtx_chunk::{EvtxChunk, EvtxChunkData, EvtxChunkHeader, IterChunkRecords};
+pub use activity::ActivityGroup;
+pub use aggregate::{GroupBy, GroupKey, Histogram};
+pub use analysis::{AnalysisReport, FieldStack, GroupStats};
+#[cfg(feature = "async")]
+pub use async_parser::AsyncEvtxParser;
+pub use binxml::fragment::{BinXmlFragment, BinXmlFragmentDecoder};
+pub use binxml::value_variant::FloatFormat;
+pub use carver::{CarvedChunk, CarvedRecord, EvtxCarver};
+pub use checkpoint::{Checkpoint, CheckpointStatus};
+pub use chunk_inventory::{ChunkString, ChunkTemplate};
+pub use chunk_table::{ChunkStatus, ChunkTableEntry, ChunkTableFormat};
+pub use compression::{CompressedWriter, Compression, InputFile};
+pub use dedup::{DedupField, DedupKey, Deduplicator};
+pub use diff::{DiffReport, RecordChange, RecordDifference};
+pub use enrichment::Enrichment;
+pub use evt::{EvtFileHeader, EvtParser, EvtRecord};
+pub use evtx_chunk::{
+    ChunkInfo, EvtxChunk, EvtxChunkData, EvtxChunkHeader, IterChunkRecords, IterSlackRecords,
+    TimestampRegression,
+};
+pub use evtx_file_header::{EvtxFileHeader, HeaderFlags, UnusedHeaderBytes};
 pub use evtx_parser::{EvtxParser, IntoIterChunks, IterChunks, ParserSettings};
-pub use evtx_record::{EvtxRecord, EvtxRecordHeader, SerializedEvtxRecord};
+#[cfg(feature = "multithreading")]
+pub use evtx_parser::{Executor, Job};
+pub use evtx_record::{EvtxRecord, EvtxRecordHeader, EvtxRecordOwned, SerializedEvtxRecord};
+pub use evtx_writer::{EventContent, EventElement, EvtxWriter};
+pub use file_report::{ChunkFailure, FileReport, RecordFailure};
+pub use filter_expr::parse_time;
+pub use filter_rules::FilterRules;
+pub use findings::{Finding, FindingKind, FindingsReport, Severity};
+pub use generator::{
+    Corruption, EventTemplate, EvtxGenerator, FieldKind, GeneratedFile, InjectedCorruption,
+};
+pub use guid::{Guid, GuidFormat};
+pub use import::ImportFormat;
+pub use integrity::{
+    ChunkIntegrity, FileHeaderIntegrity, IntegrityReport, RecordChainError, RecordChainErrorKind,
+};
 pub use json_output::JsonOutput;
+pub use lookup::LookupTable;
+pub use multi_parser::{EvtxMultiParser, FileRecord, MultiRecords};
+pub use record_filter::*;
+pub use record_size::{RecordSize, RenderFormat};
+pub use redact::{PseudonymKind, Redactor};
+pub use repair::{ChunkRepair, RepairReport};
+pub use sid::Sid;
+pub use skeleton::SkeletonCache;
+pub use sort::{SortedRecords, TimestampSorter};
+pub use split::{SplitBy, SplitPart};
+pub use stats::{ParserStats, Progress};
+pub use stream_parser::EvtxStreamParser;
+pub use tampering::{DuplicateRecordId, RecordIdRange, TamperingReport};
+pub use template_cache::FileTemplateCache;
 pub use xml_output::{BinXmlOutput, XmlOutput};
 
 pub mod binxml;
+pub mod convert;
 pub mod err;
+pub mod guid;
 pub mod model;
 
+mod activity;
+mod aggregate;
+mod analysis;
+#[cfg(feature = "async")]
+mod async_parser;
+mod carver;
+mod checkpoint;
+mod chunk_inventory;
+mod chunk_table;
+mod compression;
+mod dedup;
+mod diff;
+mod enrichment;
+mod evt;
 mod evtx_chunk;
 mod evtx_file_header;
 mod evtx_parser;
 mod evtx_record;
+mod evtx_writer;
+mod file_report;
+mod filter_expr;
+mod filter_query;
+mod filter_rules;
+mod findings;
+mod generator;
+mod import;
+mod integrity;
+pub mod logon;
+mod lookup;
+mod multi_parser;
+pub mod provider_names;
+mod raw_substitutions;
+mod record_de;
+mod record_filter;
+mod record_size;
+mod redact;
+mod repair;
+pub mod sid;
+pub mod sid_names;
+mod sort;
+mod split;
+mod stats;
+mod stream_parser;
 mod string_cache;
+pub mod symbolic_names;
+mod tampering;
 mod template_cache;
 mod utils;
+pub mod wevt_manifest;
+mod xpath;
 
 mod json_output;
+mod skeleton;
 mod xml_output;
 
+#[cfg(feature = "wevt_templates")]
+pub mod wevt_templates;
+
+#[cfg(feature = "sigma")]
+pub mod sigma;
+
+#[cfg(feature = "conformance")]
+pub mod conformance;
+
+#[cfg(feature = "event_descriptions")]
+pub mod event_descriptions;
+
+#[cfg(feature = "geoip")]
+pub mod geoip;
+
+#[cfg(feature = "sysmon")]
+pub mod sysmon;
+
 pub type Offset = u32;
 
 // For tests, we only initialize logging once.
@@ -0,0 +1,368 @@
+//! Decoding of the Security authentication events: logons (4624), failed logons (4625), logoffs (4634)
+//! and special privileges assigned to new logons (4672).
+//!
+//! With `ParserSettings::decode_logon_events`, the decoded fields of these events are added to their
+//! JSON output, in an `Event.Logon` object:
+//!
+//! ```json
+//! "Logon": {
+//!   "Kind": "FailedLogon",
+//!   "LogonType": 3,
+//!   "LogonTypeName": "Network",
+//!   "Status": "0xC000006D",
+//!   "StatusDescription": "The user name or authentication information is incorrect",
+//!   "SubStatus": "0xC000006A",
+//!   "SubStatusDescription": "The password is incorrect"
+//! }
+//! ```
+use crate::binxml::value_variant::BinXmlValue;
+use crate::EvtxRecord;
+
+use serde_json::{Map, Value};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+const SECURITY_AUDITING_PROVIDER: &str = "Microsoft-Windows-Security-Auditing";
+
+/// The authentication events which can be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogonEventKind {
+    /// 4624, an account was successfully logged on.
+    Logon,
+    /// 4625, an account failed to log on.
+    FailedLogon,
+    /// 4634, an account was logged off.
+    Logoff,
+    /// 4672, special privileges assigned to new logon.
+    SpecialPrivileges,
+}
+
+impl LogonEventKind {
+    pub fn from_event_id(event_id: u16) -> Option<LogonEventKind> {
+        match event_id {
+            4624 => Some(LogonEventKind::Logon),
+            4625 => Some(LogonEventKind::FailedLogon),
+            4634 => Some(LogonEventKind::Logoff),
+            4672 => Some(LogonEventKind::SpecialPrivileges),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogonEventKind::Logon => "Logon",
+            LogonEventKind::FailedLogon => "FailedLogon",
+            LogonEventKind::Logoff => "Logoff",
+            LogonEventKind::SpecialPrivileges => "SpecialPrivileges",
+        }
+    }
+}
+
+/// The `LogonType` of logon and logoff events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogonType {
+    /// 0, used only by the `SYSTEM` account (at startup).
+    System,
+    Interactive,
+    Network,
+    Batch,
+    Service,
+    Unlock,
+    NetworkCleartext,
+    NewCredentials,
+    RemoteInteractive,
+    CachedInteractive,
+    CachedRemoteInteractive,
+    CachedUnlock,
+    Other(u32),
+}
+
+impl From<u32> for LogonType {
+    fn from(logon_type: u32) -> Self {
+        match logon_type {
+            0 => LogonType::System,
+            2 => LogonType::Interactive,
+            3 => LogonType::Network,
+            4 => LogonType::Batch,
+            5 => LogonType::Service,
+            7 => LogonType::Unlock,
+            8 => LogonType::NetworkCleartext,
+            9 => LogonType::NewCredentials,
+            10 => LogonType::RemoteInteractive,
+            11 => LogonType::CachedInteractive,
+            12 => LogonType::CachedRemoteInteractive,
+            13 => LogonType::CachedUnlock,
+            other => LogonType::Other(other),
+        }
+    }
+}
+
+impl LogonType {
+    pub fn value(self) -> u32 {
+        match self {
+            LogonType::System => 0,
+            LogonType::Interactive => 2,
+            LogonType::Network => 3,
+            LogonType::Batch => 4,
+            LogonType::Service => 5,
+            LogonType::Unlock => 7,
+            LogonType::NetworkCleartext => 8,
+            LogonType::NewCredentials => 9,
+            LogonType::RemoteInteractive => 10,
+            LogonType::CachedInteractive => 11,
+            LogonType::CachedRemoteInteractive => 12,
+            LogonType::CachedUnlock => 13,
+            LogonType::Other(other) => other,
+        }
+    }
+
+    /// Returns the name of the logon type, as documented for event 4624, or `None` for unknown types.
+    pub fn name(self) -> Option<&'static str> {
+        let name = match self {
+            LogonType::System => "System",
+            LogonType::Interactive => "Interactive",
+            LogonType::Network => "Network",
+            LogonType::Batch => "Batch",
+            LogonType::Service => "Service",
+            LogonType::Unlock => "Unlock",
+            LogonType::NetworkCleartext => "NetworkCleartext",
+            LogonType::NewCredentials => "NewCredentials",
+            LogonType::RemoteInteractive => "RemoteInteractive",
+            LogonType::CachedInteractive => "CachedInteractive",
+            LogonType::CachedRemoteInteractive => "CachedRemoteInteractive",
+            LogonType::CachedUnlock => "CachedUnlock",
+            LogonType::Other(_) => return None,
+        };
+
+        Some(name)
+    }
+}
+
+impl fmt::Display for LogonType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{}", self.value()),
+        }
+    }
+}
+
+/// Returns the meaning of the `Status` or `SubStatus` (an `NTSTATUS`) of a failed logon.
+pub fn logon_status_description(status: u32) -> Option<&'static str> {
+    let description = match status {
+        0x0000_0000 => "Success",
+        0xC000_005E => {
+            "There are currently no logon servers available to service the logon request"
+        }
+        0xC000_0064 => "The user name does not exist",
+        0xC000_006A => "The password is incorrect",
+        0xC000_006C => "The password does not meet the password policy requirements",
+        0xC000_006D => "The user name or authentication information is incorrect",
+        0xC000_006E => "The account has restrictions which prevent the logon",
+        0xC000_006F => "The account is not allowed to log on at this time",
+        0xC000_0070 => "The account is not allowed to log on from this workstation",
+        0xC000_0071 => "The password has expired",
+        0xC000_0072 => "The account is disabled",
+        0xC000_009A => "Insufficient system resources",
+        0xC000_00DC => "The server was in the wrong state to perform the operation",
+        0xC000_0133 => "The clocks of the client and the domain controller are out of sync",
+        0xC000_015B => "The user has not been granted the requested logon type on this computer",
+        0xC000_018C => {
+            "The trust relationship between the primary domain and the trusted domain failed"
+        }
+        0xC000_0192 => "The Netlogon service was not started",
+        0xC000_0193 => "The account has expired",
+        0xC000_0224 => "The password must be changed before logging on the first time",
+        0xC000_0225 => "An internal error of Windows (not a security risk)",
+        0xC000_0234 => "The account is locked out",
+        0xC000_02EE => "An error occurred during logon",
+        0xC000_0371 => "The local account store does not contain secret material for the account",
+        0xC000_0413 => "The machine is protected by an authentication firewall",
+        _ => return None,
+    };
+
+    Some(description)
+}
+
+/// The decoded fields of an authentication event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogonEvent {
+    pub kind: LogonEventKind,
+    /// The `LogonType` (logons, failed logons and logoffs).
+    pub logon_type: Option<LogonType>,
+    /// The `Status` of failed logons.
+    pub status: Option<u32>,
+    /// The `SubStatus` of failed logons.
+    pub sub_status: Option<u32>,
+    /// Whether the `ElevatedToken` of a logon is set (`%%1842`).
+    pub elevated_token: Option<bool>,
+    /// The `PrivilegeList` of special privileges assigned to a logon.
+    pub privileges: Vec<String>,
+}
+
+impl LogonEvent {
+    /// Decodes `record`, if it is one of the `LogonEventKind` events of the Security auditing provider.
+    pub fn from_record(record: &EvtxRecord) -> Option<LogonEvent> {
+        let kind = LogonEventKind::from_event_id(record.event_id()?)?;
+        if !record
+            .provider_name()?
+            .eq_ignore_ascii_case(SECURITY_AUDITING_PROVIDER)
+        {
+            return None;
+        }
+
+        Some(LogonEvent::from_fields(kind, &record.event_data().ok()?))
+    }
+
+    /// Decodes the `EventData` fields of an event of the given kind.
+    pub fn from_fields(kind: LogonEventKind, fields: &BTreeMap<String, BinXmlValue>) -> LogonEvent {
+        let field = |name: &str| fields.get(name);
+
+        let elevated_token =
+            field("ElevatedToken").and_then(|value| match value.as_cow_str().trim() {
+                "%%1842" => Some(true),
+                "%%1843" => Some(false),
+                _ => None,
+            });
+
+        let privileges = field("PrivilegeList")
+            .map(|value| {
+                value
+                    .as_cow_str()
+                    .split_whitespace()
+                    .filter(|privilege| *privilege != "-")
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        LogonEvent {
+            kind,
+            logon_type: field("LogonType").and_then(as_u32).map(LogonType::from),
+            status: field("Status").and_then(as_u32),
+            sub_status: field("SubStatus").and_then(as_u32),
+            elevated_token,
+            privileges,
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        object.insert("Kind".to_string(), self.kind.name().into());
+
+        if let Some(logon_type) = self.logon_type {
+            object.insert("LogonType".to_string(), logon_type.value().into());
+            if let Some(name) = logon_type.name() {
+                object.insert("LogonTypeName".to_string(), name.into());
+            }
+        }
+
+        for (key, status) in &[("Status", self.status), ("SubStatus", self.sub_status)] {
+            if let Some(status) = status {
+                object.insert(key.to_string(), format!("0x{:08X}", status).into());
+                if let Some(description) = logon_status_description(*status) {
+                    object.insert(format!("{}Description", key), description.into());
+                }
+            }
+        }
+
+        if let Some(elevated_token) = self.elevated_token {
+            object.insert("ElevatedToken".to_string(), elevated_token.into());
+        }
+
+        if !self.privileges.is_empty() {
+            object.insert("Privileges".to_string(), self.privileges.clone().into());
+        }
+
+        Value::Object(object)
+    }
+}
+
+/// Reads an integer field, rendered either as a number or as a (hex or decimal) string.
+fn as_u32(value: &BinXmlValue) -> Option<u32> {
+    match value {
+        BinXmlValue::UInt8Type(n) => Some(u32::from(*n)),
+        BinXmlValue::UInt16Type(n) => Some(u32::from(*n)),
+        BinXmlValue::UInt32Type(n) => Some(*n),
+        BinXmlValue::Int32Type(n) => Some(*n as u32),
+        _ => {
+            let s = value.as_cow_str();
+            let s = s.trim();
+            match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                None => s.parse().ok(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+
+    #[test]
+    fn test_decodes_failed_logons() {
+        let mut fields = BTreeMap::new();
+        fields.insert("LogonType".to_string(), BinXmlValue::UInt32Type(3));
+        fields.insert(
+            "Status".to_string(),
+            BinXmlValue::HexInt32Type(Cow::Borrowed("0xc000006d")),
+        );
+        fields.insert(
+            "SubStatus".to_string(),
+            BinXmlValue::StringType(Cow::Borrowed("0xC000006A")),
+        );
+
+        let logon = LogonEvent::from_fields(LogonEventKind::FailedLogon, &fields);
+        assert_eq!(logon.logon_type, Some(LogonType::Network));
+        assert_eq!(logon.status, Some(0xC000_006D));
+        assert_eq!(
+            logon.to_json(),
+            serde_json::json!({
+                "Kind": "FailedLogon",
+                "LogonType": 3,
+                "LogonTypeName": "Network",
+                "Status": "0xC000006D",
+                "StatusDescription": "The user name or authentication information is incorrect",
+                "SubStatus": "0xC000006A",
+                "SubStatusDescription": "The password is incorrect",
+            })
+        );
+    }
+
+    #[test]
+    fn test_decodes_elevation() {
+        let mut fields = BTreeMap::new();
+        fields.insert("LogonType".to_string(), BinXmlValue::UInt32Type(42));
+        fields.insert(
+            "ElevatedToken".to_string(),
+            BinXmlValue::StringType(Cow::Borrowed("%%1842")),
+        );
+
+        let logon = LogonEvent::from_fields(LogonEventKind::Logon, &fields);
+        assert_eq!(logon.logon_type, Some(LogonType::Other(42)));
+        assert_eq!(logon.elevated_token, Some(true));
+        assert_eq!(
+            logon.to_json(),
+            serde_json::json!({"Kind": "Logon", "LogonType": 42, "ElevatedToken": true})
+        );
+    }
+
+    #[test]
+    fn test_logon_type_names() {
+        for value in 0..16 {
+            let logon_type = LogonType::from(value);
+            assert_eq!(logon_type.value(), value);
+            assert_eq!(
+                logon_type.name().is_some(),
+                !matches!(value, 1 | 6 | 14 | 15)
+            );
+        }
+        assert_eq!(
+            LogonType::RemoteInteractive.to_string(),
+            "RemoteInteractive"
+        );
+    }
+}
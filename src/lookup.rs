@@ -0,0 +1,258 @@
+//! Lookup tables joined against the `EventData` fields of records, see `ParserSettings::lookup`.
+//!
+//! A table maps a key (such as a hostname or a SID) to columns (such as the owner of the asset,
+//! or the name and department of an employee), and is loaded from a CSV or a JSON file:
+//!
+//! ```csv
+//! Sid,Employee,Department
+//! S-1-5-21-3623811015-3361044348-30300820-1013,Alice,Finance
+//! ```
+//!
+//! ```json
+//! {"S-1-5-21-3623811015-3361044348-30300820-1013": {"Employee": "Alice", "Department": "Finance"}}
+//! ```
+//!
+//! The key of a CSV table is it's first column. Keys are matched case insensitively.
+//!
+//! The columns of the row matching the value of a joined field are added to the JSON output of records,
+//! in an `Event.Lookup` object keyed by field name:
+//!
+//! ```json
+//! "Lookup": {
+//!   "TargetUserSid": {"Employee": "Alice", "Department": "Finance"}
+//! }
+//! ```
+use crate::err::{self, Result};
+
+use serde_json::{Map, Value};
+use snafu::ResultExt;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LookupTable {
+    /// Key (lowercase) -> columns.
+    rows: HashMap<String, Map<String, Value>>,
+}
+
+impl LookupTable {
+    /// Loads a CSV or JSON table (by it's extension, `.json` or anything else for CSV).
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path).context(err::FailedToOpenFile { path })?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => {
+                serde_json::from_str(&data)
+                    .map_err(|e| e.to_string())
+                    .and_then(|value| LookupTable::from_json_value(&value))
+            }
+            _ => LookupTable::from_csv(&data),
+        }
+        .map_err(|message| err::Error::FailedToLoadLookupTable {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// Parses a CSV table, with a header line. The first column is the key.
+    pub fn from_csv(data: &str) -> std::result::Result<Self, String> {
+        let mut lines = parse_csv(data.trim_start_matches('\u{feff}'))?.into_iter();
+        let header = lines.next().ok_or("Expected a header line")?;
+        if header.len() < 2 {
+            return Err("Expected a key column and at least one other column".to_string());
+        }
+
+        let mut table = LookupTable::default();
+
+        for (n, line) in lines.enumerate() {
+            if line.len() > header.len() {
+                return Err(format!(
+                    "Line {}: expected at most {} columns, found {}",
+                    n + 2,
+                    header.len(),
+                    line.len()
+                ));
+            }
+
+            let mut columns = line.into_iter();
+            let key = columns.next().expect("lines are not empty");
+            let columns = header[1..]
+                .iter()
+                .zip(columns)
+                .map(|(name, value)| (name.clone(), Value::String(value)))
+                .collect();
+
+            table.insert(&key, columns);
+        }
+
+        Ok(table)
+    }
+
+    /// Reads a JSON table: an object of rows (objects of columns) keyed by key.
+    pub fn from_json_value(value: &Value) -> std::result::Result<Self, String> {
+        let rows = value
+            .as_object()
+            .ok_or("Expected an object of rows keyed by key")?;
+
+        let mut table = LookupTable::default();
+
+        for (key, columns) in rows {
+            let columns = columns
+                .as_object()
+                .ok_or_else(|| format!("Expected the row of `{}` to be an object", key))?;
+            table.insert(key, columns.clone());
+        }
+
+        Ok(table)
+    }
+
+    /// Adds a row, replacing the row with the same key.
+    pub fn insert(&mut self, key: &str, columns: Map<String, Value>) {
+        self.rows.insert(key.trim().to_lowercase(), columns);
+    }
+
+    /// Returns the columns of the row of `key`.
+    pub fn get(&self, key: &str) -> Option<&Map<String, Value>> {
+        self.rows.get(&key.trim().to_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+}
+
+/// Returns the `Event.Lookup` object of a record, joining `tables` (by field name) against
+/// it's `EventData` fields. Tables joined on the same field are merged, later tables overriding earlier ones.
+pub(crate) fn annotate(
+    tables: &[(String, Arc<LookupTable>)],
+    field: impl Fn(&str) -> Option<String>,
+) -> Map<String, Value> {
+    let mut annotations = Map::new();
+
+    for (name, table) in tables {
+        let row = match field(name).and_then(|value| table.get(&value)) {
+            Some(row) => row,
+            None => continue,
+        };
+
+        let columns = annotations
+            .entry(name.clone())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(columns) = columns {
+            columns.extend(row.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+    }
+
+    annotations
+}
+
+/// Splits CSV data into lines of fields (RFC 4180: fields are optionally quoted, quotes escaped by doubling them).
+/// Empty lines are skipped.
+fn parse_csv(data: &str) -> std::result::Result<Vec<Vec<String>>, String> {
+    let mut lines = vec![];
+    let mut line = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = data.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => line.push(std::mem::take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                if !line.is_empty() || !field.is_empty() {
+                    line.push(std::mem::take(&mut field));
+                    lines.push(std::mem::take(&mut line));
+                }
+            }
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err("Unterminated quoted field".to_string());
+    }
+    if !line.is_empty() || !field.is_empty() {
+        line.push(field);
+        lines.push(line);
+    }
+
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_csv_table() {
+        let table = LookupTable::from_csv(
+            "\u{feff}Hostname,Owner,Notes\r\n\
+             DC01,IT,\"Domain controller, \"\"primary\"\"\"\r\n\
+             \r\n\
+             ws-042,Alice\n",
+        )
+        .unwrap();
+
+        assert_eq!(table.len(), 2);
+        assert_eq!(
+            Value::Object(table.get("dc01").unwrap().clone()),
+            json!({"Owner": "IT", "Notes": "Domain controller, \"primary\""})
+        );
+        assert_eq!(
+            Value::Object(table.get(" WS-042 ").unwrap().clone()),
+            json!({"Owner": "Alice"})
+        );
+        assert_eq!(table.get("ws-043"), None);
+
+        assert!(LookupTable::from_csv("Hostname,Owner\nDC01,IT,extra\n")
+            .unwrap_err()
+            .contains("Line 2"));
+        assert!(LookupTable::from_csv("Hostname,Owner\n\"DC01,IT\n").is_err());
+    }
+
+    #[test]
+    fn test_annotate() {
+        let hosts =
+            LookupTable::from_json_value(&json!({"DC01": {"Owner": "IT", "Critical": true}}))
+                .unwrap();
+        let sids = Arc::new(LookupTable::from_csv("Sid,Employee\nS-1-5-18,SYSTEM\n").unwrap());
+        let tables = vec![
+            ("WorkstationName".to_string(), Arc::new(hosts)),
+            ("TargetUserSid".to_string(), Arc::clone(&sids)),
+            ("SubjectUserSid".to_string(), sids),
+        ];
+
+        let annotations = annotate(&tables, |name| match name {
+            "WorkstationName" => Some("dc01".to_string()),
+            "TargetUserSid" => Some("S-1-5-18".to_string()),
+            _ => None,
+        });
+
+        assert_eq!(
+            Value::Object(annotations),
+            json!({
+                "WorkstationName": {"Owner": "IT", "Critical": true},
+                "TargetUserSid": {"Employee": "SYSTEM"},
+            })
+        );
+    }
+}
@@ -3,7 +3,7 @@ use crate::binxml::value_variant::{BinXmlValue, BinXmlValueType};
 
 use std::borrow::Cow;
 
-use winstructs::guid::Guid;
+use crate::guid::Guid;
 
 #[derive(Debug, PartialOrd, PartialEq, Clone)]
 pub enum BinXMLDeserializedTokens<'a> {
@@ -77,3 +77,76 @@ pub struct BinXMLFragmentHeader {
 pub struct BinXMLAttribute<'a> {
     pub name: BinXmlName<'a>,
 }
+
+impl<'a> BinXMLTemplateDefinition<'a> {
+    /// Returns a copy of the definition which does not borrow the chunk it was read from.
+    pub fn to_owned_definition<'b>(&self) -> BinXMLTemplateDefinition<'b> {
+        BinXMLTemplateDefinition {
+            next_template_offset: self.next_template_offset,
+            template_guid: self.template_guid,
+            data_size: self.data_size,
+            tokens: self
+                .tokens
+                .iter()
+                .map(BinXMLDeserializedTokens::to_owned_token)
+                .collect(),
+        }
+    }
+}
+
+impl<'a> BinXMLDeserializedTokens<'a> {
+    /// Returns a copy of the token which does not borrow the chunk it was read from.
+    pub fn to_owned_token<'b>(&self) -> BinXMLDeserializedTokens<'b> {
+        match self {
+            BinXMLDeserializedTokens::FragmentHeader(header) => {
+                BinXMLDeserializedTokens::FragmentHeader(header.clone())
+            }
+            BinXMLDeserializedTokens::TemplateInstance(template) => {
+                BinXMLDeserializedTokens::TemplateInstance(BinXmlTemplate {
+                    definition: Cow::Owned(template.definition.to_owned_definition()),
+                    substitution_array: template
+                        .substitution_array
+                        .iter()
+                        .map(BinXmlValue::to_owned_value)
+                        .collect(),
+                })
+            }
+            BinXMLDeserializedTokens::OpenStartElement(elem) => {
+                BinXMLDeserializedTokens::OpenStartElement(BinXMLOpenStartElement {
+                    data_size: elem.data_size,
+                    name: elem.name.to_owned_name(),
+                })
+            }
+            BinXMLDeserializedTokens::AttributeList => BinXMLDeserializedTokens::AttributeList,
+            BinXMLDeserializedTokens::Attribute(attr) => {
+                BinXMLDeserializedTokens::Attribute(BinXMLAttribute {
+                    name: attr.name.to_owned_name(),
+                })
+            }
+            BinXMLDeserializedTokens::CloseStartElement => {
+                BinXMLDeserializedTokens::CloseStartElement
+            }
+            BinXMLDeserializedTokens::CloseEmptyElement => {
+                BinXMLDeserializedTokens::CloseEmptyElement
+            }
+            BinXMLDeserializedTokens::CloseElement => BinXMLDeserializedTokens::CloseElement,
+            BinXMLDeserializedTokens::Value(value) => {
+                BinXMLDeserializedTokens::Value(Cow::Owned(value.to_owned_value()))
+            }
+            BinXMLDeserializedTokens::CDATASection => BinXMLDeserializedTokens::CDATASection,
+            BinXMLDeserializedTokens::CharRef => BinXMLDeserializedTokens::CharRef,
+            BinXMLDeserializedTokens::EntityRef(entity) => {
+                BinXMLDeserializedTokens::EntityRef(BinXmlEntityReference {
+                    name: entity.name.to_owned_name(),
+                })
+            }
+            BinXMLDeserializedTokens::PITarget => BinXMLDeserializedTokens::PITarget,
+            BinXMLDeserializedTokens::PIData => BinXMLDeserializedTokens::PIData,
+            BinXMLDeserializedTokens::Substitution(descriptor) => {
+                BinXMLDeserializedTokens::Substitution(descriptor.clone())
+            }
+            BinXMLDeserializedTokens::EndOfStream => BinXMLDeserializedTokens::EndOfStream,
+            BinXMLDeserializedTokens::StartOfStream => BinXMLDeserializedTokens::StartOfStream,
+        }
+    }
+}
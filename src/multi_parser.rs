@@ -0,0 +1,601 @@
+use crate::err::{self, Result};
+use crate::evtx_parser::{serialize_chunk, EvtxParser, ParserSettings, Sampler};
+use crate::evtx_record::{EvtxRecord, SerializedEvtxRecord};
+use crate::evtx_writer::{EventElement, EvtxWriter};
+
+use snafu::ResultExt;
+
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::io::{Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// Number of serialized records a file can be ahead of the caller.
+const RECORDS_BUFFERED_PER_FILE: usize = 256;
+
+/// A record of one of the files of an `EvtxMultiParser`.
+#[derive(Debug)]
+pub struct FileRecord<T> {
+    /// The path of the file the record was read from.
+    pub path: Arc<Path>,
+    /// The record, or the error which occurred while reading it (or while opening the file).
+    pub record: Result<SerializedEvtxRecord<T>>,
+}
+
+/// Parses several evtx files, yielding their records as a single stream:
+/// as they are parsed (by several files concurrently), or merged by timestamp (see `merge_by_timestamp`).
+///
+/// Errors are isolated to the file they occurred in: a file which cannot be opened (or a corrupted record)
+/// is reported as a `FileRecord` with an error, and the other files are still parsed.
+///
+/// ```rust,no_run
+/// # fn run() -> evtx::err::Result<()> {
+/// use evtx::EvtxMultiParser;
+///
+/// let parser = EvtxMultiParser::new()
+///     .add_glob("logs/**/*.evtx")?
+///     .merge_by_timestamp(true);
+///
+/// for record in parser.records() {
+///     match record.record {
+///         Ok(r) => println!("{}: {}", record.path.display(), r.data),
+///         Err(e) => eprintln!("{}: {}", record.path.display(), e),
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct EvtxMultiParser {
+    paths: Vec<PathBuf>,
+    config: ParserSettings,
+    merge_by_timestamp: bool,
+    max_concurrent_files: usize,
+}
+
+impl EvtxMultiParser {
+    pub fn new() -> Self {
+        EvtxMultiParser::default()
+    }
+
+    pub fn from_paths<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> Self {
+        EvtxMultiParser::new().add_paths(paths)
+    }
+
+    pub fn add_paths<P: AsRef<Path>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.paths
+            .extend(paths.into_iter().map(|path| path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Adds the files matching the glob `pattern` (for example `logs/**/*.evtx`), sorted by path.
+    /// Paths which cannot be read while matching the pattern are skipped.
+    pub fn add_glob(mut self, pattern: &str) -> Result<Self> {
+        let paths = glob::glob(pattern).context(err::InvalidGlobPattern { pattern })?;
+
+        for path in paths {
+            match path {
+                Ok(path) if path.is_file() => self.paths.push(path),
+                Ok(path) => debug!("Skipping {}, which is not a file", path.display()),
+                Err(e) => warn!("Skipping a path matching `{}`: {}", pattern, e),
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Sets the settings used to parse every file.
+    ///
+    /// Files are parsed concurrently, so each of them is parsed with a single thread.
+    pub fn with_configuration(mut self, configuration: ParserSettings) -> Self {
+        self.config = configuration;
+        self
+    }
+
+    /// If enabled, records are yielded in timestamp order across the files,
+    /// otherwise they are yielded as soon as they are parsed (in order within each file).
+    ///
+    /// The records of each file are expected to be in timestamp order, as written by the event log service.
+    /// The files are merged on the calling thread, which keeps every file open,
+    /// along with the records of it's current chunk.
+    pub fn merge_by_timestamp(mut self, merge: bool) -> Self {
+        self.merge_by_timestamp = merge;
+        self
+    }
+
+    /// Sets the number of files parsed at the same time when records are not merged by timestamp.
+    /// `0` (the default) uses the number of CPU cores.
+    pub fn max_concurrent_files(mut self, max_concurrent_files: usize) -> Self {
+        self.max_concurrent_files = max_concurrent_files;
+        self
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn get_max_concurrent_files(&self) -> usize {
+        match self.max_concurrent_files {
+            0 => thread::available_parallelism().map_or(1, usize::from),
+            n => n,
+        }
+    }
+
+    pub fn should_merge_by_timestamp(&self) -> bool {
+        self.merge_by_timestamp
+    }
+
+    /// Return an iterator over the records of all the files.
+    /// Records will be mapped `f`, see `EvtxParser::serialized_records`.
+    pub fn serialized_records<U: Send + 'static>(
+        &self,
+        f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<SerializedEvtxRecord<U>>
+            + Send
+            + Sync
+            + Clone
+            + 'static,
+    ) -> MultiRecords<U> {
+        let settings = self.config.clone().num_threads(1);
+        let paths: VecDeque<Arc<Path>> = self
+            .paths
+            .iter()
+            .map(|path| path.as_path().into())
+            .collect();
+
+        if self.merge_by_timestamp {
+            let files = paths
+                .into_iter()
+                .map(|path| -> Box<dyn Iterator<Item = FileRecord<U>> + Send> {
+                    Box::new(file_records(path, settings.clone(), f.clone()))
+                })
+                .collect();
+
+            MultiRecords {
+                source: Source::Merged {
+                    files,
+                    heads: vec![],
+                    order: BinaryHeap::new(),
+                },
+                readers: vec![],
+            }
+        } else {
+            let (sender, receiver) = sync_channel(RECORDS_BUFFERED_PER_FILE);
+            let queue = Arc::new(Mutex::new(paths));
+
+            let readers = (0..self.get_max_concurrent_files().min(self.paths.len()))
+                .map(|_| {
+                    let (queue, sender) = (Arc::clone(&queue), sender.clone());
+                    let (settings, f) = (settings.clone(), f.clone());
+
+                    spawn_reader(move || loop {
+                        let path = queue
+                            .lock()
+                            .expect("file queue lock is poisoned")
+                            .pop_front();
+
+                        match path {
+                            Some(path) => {
+                                if !parse_file(path, settings.clone(), f.clone(), &sender) {
+                                    break;
+                                }
+                            }
+                            None => break,
+                        }
+                    })
+                })
+                .collect();
+
+            MultiRecords {
+                source: Source::Multiplexed(receiver),
+                readers,
+            }
+        }
+    }
+
+    /// Return an iterator over the records of all the files.
+    /// Records will be XML-formatted.
+    pub fn records(&self) -> MultiRecords<String> {
+        self.serialized_records(|record| record.and_then(|record| record.into_xml()))
+    }
+
+    /// Return an iterator over the records of all the files.
+    /// Records will be JSON-formatted.
+    pub fn records_json(&self) -> MultiRecords<String> {
+        self.serialized_records(|record| record.and_then(|record| record.into_json()))
+    }
+
+    /// Return an iterator over the records of all the files.
+    /// Records will have a `serde_json::Value` data attribute.
+    pub fn records_json_value(&self) -> MultiRecords<serde_json::Value> {
+        self.serialized_records(|record| record.and_then(|record| record.into_json_value()))
+    }
+
+    /// Writes the records of all the files to `writer`, merged by timestamp (see `merge_by_timestamp`)
+    /// and renumbered from the next record ID of the writer.
+    /// Records which failed to parse (and files which cannot be opened) are skipped, with a warning.
+    /// Returns the number of records written.
+    ///
+    /// ```rust,no_run
+    /// # fn run() -> evtx::err::Result<()> {
+    /// use evtx::{EvtxMultiParser, EvtxWriter};
+    ///
+    /// let mut writer = EvtxWriter::create("merged.evtx")?;
+    /// EvtxMultiParser::from_paths(["dc01/Security.evtx", "dc02/Security.evtx"]).merge_into(&mut writer)?;
+    /// writer.finish()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn merge_into<W: Write + Seek>(&self, writer: &mut EvtxWriter<W>) -> Result<u64> {
+        let records = self
+            .clone()
+            .merge_by_timestamp(true)
+            .serialized_records(|record| {
+                record.and_then(|record| {
+                    Ok(SerializedEvtxRecord {
+                        event_record_id: record.event_record_id,
+                        timestamp: record.timestamp,
+                        data: EventElement::from_record(&record)?,
+                        recovered: record.recovered,
+                        errors: vec![],
+                    })
+                })
+            });
+
+        let mut written = 0;
+
+        for record in records {
+            match record.record {
+                Ok(r) => {
+                    writer.write_record(r.timestamp, &r.data)?;
+                    written += 1;
+                }
+                Err(e) => warn!("Skipped a record of {}: {}", record.path.display(), e),
+            }
+        }
+
+        Ok(written)
+    }
+}
+
+fn spawn_reader(read: impl FnOnce() + Send + 'static) -> JoinHandle<()> {
+    thread::Builder::new()
+        .name("evtx-multi-parser".to_string())
+        .spawn(read)
+        .expect("failed to spawn a file reading thread")
+}
+
+/// Sends the records of the file at `path` to `sender`.
+/// Returns `false` if the caller is gone.
+fn parse_file<U: Send + 'static>(
+    path: Arc<Path>,
+    settings: ParserSettings,
+    f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<SerializedEvtxRecord<U>>
+        + Send
+        + Sync
+        + Clone
+        + 'static,
+    sender: &SyncSender<FileRecord<U>>,
+) -> bool {
+    debug!("Parsing {}", path.display());
+
+    let send = |record| {
+        sender
+            .send(FileRecord {
+                path: Arc::clone(&path),
+                record,
+            })
+            .is_ok()
+    };
+
+    match EvtxParser::from_path(&path) {
+        Ok(parser) => parser
+            .with_configuration(settings)
+            .serialized_records(f)
+            .all(send),
+        Err(e) => send(Err(e)),
+    }
+}
+
+/// Returns an iterator over the records of the file at `path`, parsed on the calling thread a chunk at a time.
+fn file_records<U: Send + 'static>(
+    path: Arc<Path>,
+    settings: ParserSettings,
+    mut f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<SerializedEvtxRecord<U>> + Send + 'static,
+) -> impl Iterator<Item = FileRecord<U>> + Send {
+    debug!("Parsing {}", path.display());
+
+    let mut records = VecDeque::new();
+    let mut sampler = Sampler::new(&settings);
+    let settings = Arc::new(settings);
+
+    let mut chunks = match EvtxParser::from_path(&path) {
+        Ok(parser) => Some(
+            parser
+                .with_configuration(settings.as_ref().clone())
+                .into_chunks(),
+        ),
+        Err(e) => {
+            records.push_back(Err(e));
+            None
+        }
+    };
+
+    std::iter::from_fn(move || loop {
+        if let Some(record) = records.pop_front() {
+            return Some(FileRecord {
+                path: Arc::clone(&path),
+                record,
+            });
+        }
+
+        let chunk = chunks.as_mut()?.next()?;
+        serialize_chunk(chunk, Arc::clone(&settings), &mut f, |record| {
+            if sampler.keep(&record) {
+                records.push_back(record);
+            }
+            true
+        });
+    })
+}
+
+/// The order of the next record of a file in a merge: errors (which have no timestamp) first,
+/// then by timestamp, and by the index of the file.
+type MergeKey = Reverse<(Option<DateTime<Utc>>, usize)>;
+
+enum Source<U> {
+    /// Records of all the files, as soon as they are parsed.
+    Multiplexed(Receiver<FileRecord<U>>),
+    /// Records of every file, the next record of each of them (`None` once it is done),
+    /// and the files with a next record, ordered by it.
+    Merged {
+        files: Vec<Box<dyn Iterator<Item = FileRecord<U>> + Send>>,
+        heads: Vec<Option<FileRecord<U>>>,
+        order: BinaryHeap<MergeKey>,
+    },
+}
+
+/// The records of the files of an `EvtxMultiParser`, see `EvtxMultiParser::serialized_records`.
+///
+/// Dropping the iterator stops parsing the files.
+pub struct MultiRecords<U> {
+    source: Source<U>,
+    readers: Vec<JoinHandle<()>>,
+}
+
+impl<U> MultiRecords<U> {
+    /// Waits for the readers to be done, passing on their panic (if any) to the caller.
+    fn join_readers(&mut self) {
+        for reader in self.readers.drain(..) {
+            if let Err(panic) = reader.join() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+    }
+}
+
+impl<U> Iterator for MultiRecords<U> {
+    type Item = FileRecord<U>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = match &mut self.source {
+            Source::Multiplexed(receiver) => receiver.recv().ok(),
+            Source::Merged {
+                files,
+                heads,
+                order,
+            } => {
+                let merge_key = |file: usize, head: &FileRecord<U>| {
+                    Reverse((head.record.as_ref().ok().map(|r| r.timestamp), file))
+                };
+
+                if heads.is_empty() {
+                    *heads = files.iter_mut().map(|file| file.next()).collect();
+                    order.extend(
+                        heads
+                            .iter()
+                            .enumerate()
+                            .filter_map(|(i, head)| head.as_ref().map(|head| merge_key(i, head))),
+                    );
+                }
+
+                let Reverse((_, next_file)) = order.pop()?;
+                let next = heads[next_file].take();
+
+                heads[next_file] = files[next_file].next();
+                if let Some(head) = &heads[next_file] {
+                    order.push(merge_key(next_file, head));
+                }
+
+                next
+            }
+        };
+
+        if next.is_none() {
+            self.join_readers();
+        }
+
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ensure_env_logger_initialized;
+    use chrono::{DateTime, Utc};
+
+    const SAMPLES: [&str; 3] = [
+        "samples/security.evtx",
+        "samples/system.evtx",
+        "samples/sysmon.evtx",
+    ];
+
+    fn records_of(path: &str) -> Vec<(u64, String)> {
+        EvtxParser::from_path(path)
+            .unwrap()
+            .records()
+            .filter_map(|r| r.ok())
+            .map(|r| (r.event_record_id, r.data))
+            .collect()
+    }
+
+    #[test]
+    fn test_multiplexed_records_keep_file_order() {
+        ensure_env_logger_initialized();
+
+        let parser = EvtxMultiParser::from_paths(SAMPLES).max_concurrent_files(2);
+        let records: Vec<_> = parser.records().collect();
+
+        for path in SAMPLES.iter() {
+            let of_file: Vec<_> = records
+                .iter()
+                .filter(|r| r.path.as_ref() == Path::new(path))
+                .map(|r| {
+                    let record = r.record.as_ref().unwrap();
+                    (record.event_record_id, record.data.clone())
+                })
+                .collect();
+
+            assert_eq!(of_file, records_of(path), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_merged_records_are_sorted_by_timestamp() {
+        ensure_env_logger_initialized();
+
+        let regressions =
+            |timestamps: &[DateTime<Utc>]| timestamps.windows(2).filter(|w| w[0] > w[1]).count();
+
+        let parser = EvtxMultiParser::from_paths(SAMPLES).merge_by_timestamp(true);
+        let records: Vec<_> = parser
+            .records()
+            .map(|r| (r.path, r.record.unwrap()))
+            .collect();
+
+        // The samples are not perfectly sorted, so the merge can only be as sorted as them.
+        let mut file_regressions = 0;
+
+        for path in SAMPLES.iter() {
+            let of_file: Vec<_> = records
+                .iter()
+                .filter(|(p, _)| p.as_ref() == Path::new(path))
+                .map(|(_, r)| (r.event_record_id, r.data.clone()))
+                .collect();
+            assert_eq!(of_file, records_of(path), "{}", path);
+
+            let timestamps: Vec<_> = EvtxParser::from_path(path)
+                .unwrap()
+                .records()
+                .map(|r| r.unwrap().timestamp)
+                .collect();
+            file_regressions += regressions(&timestamps);
+        }
+
+        let timestamps: Vec<_> = records.iter().map(|(_, r)| r.timestamp).collect();
+        assert_eq!(regressions(&timestamps), file_regressions);
+    }
+
+    #[test]
+    fn test_merges_many_files_without_threads() {
+        ensure_env_logger_initialized();
+
+        let path = "samples/Security_short_selected.evtx";
+        let expected = records_of(path);
+
+        let mut paths = vec!["samples/missing.evtx"];
+        paths.extend(std::iter::repeat(path).take(500));
+        let mut records = EvtxMultiParser::from_paths(paths)
+            .merge_by_timestamp(true)
+            .records();
+        assert!(records.readers.is_empty());
+
+        // The error of the missing file comes first.
+        assert!(records.next().unwrap().record.is_err());
+
+        let merged: Vec<_> = records.map(|r| r.record.unwrap().event_record_id).collect();
+        assert_eq!(merged.len(), 500 * expected.len());
+        let mut counts = std::collections::HashMap::new();
+        for id in merged {
+            *counts.entry(id).or_insert(0) += 1;
+        }
+        assert!(expected.iter().all(|(id, _)| counts[id] == 500));
+    }
+
+    #[test]
+    fn test_merged_files_are_renumbered() {
+        ensure_env_logger_initialized();
+
+        let mut writer = EvtxWriter::new(std::io::Cursor::new(vec![])).unwrap();
+        let written = EvtxMultiParser::from_paths(SAMPLES)
+            .merge_into(&mut writer)
+            .unwrap();
+        let data = writer.finish().unwrap().into_inner();
+
+        let expected: Vec<_> = EvtxMultiParser::from_paths(SAMPLES)
+            .merge_by_timestamp(true)
+            .records()
+            .map(|r| r.record.unwrap())
+            .collect();
+        assert_eq!(written, expected.len() as u64);
+
+        let merged: Vec<_> = EvtxParser::from_buffer(data)
+            .unwrap()
+            .records()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(merged.len(), expected.len());
+
+        for (i, (merged, expected)) in merged.iter().zip(&expected).enumerate() {
+            let event_record_id = i as u64 + 1;
+            assert_eq!(merged.event_record_id, event_record_id);
+            assert_eq!(merged.timestamp, expected.timestamp);
+            assert!(merged.data.contains(&format!(
+                "<EventRecordID>{}</EventRecordID>",
+                event_record_id
+            )));
+        }
+    }
+
+    #[test]
+    fn test_errors_are_isolated_to_their_file() {
+        ensure_env_logger_initialized();
+
+        for merge in [false, true] {
+            let parser = EvtxMultiParser::from_paths(["samples/does-not-exist.evtx", SAMPLES[2]])
+                .merge_by_timestamp(merge);
+            let records: Vec<_> = parser.records().collect();
+
+            let (failed, parsed): (Vec<_>, Vec<_>) =
+                records.iter().partition(|r| r.record.is_err());
+
+            assert_eq!(failed.len(), 1);
+            assert_eq!(
+                failed[0].path.as_ref(),
+                Path::new("samples/does-not-exist.evtx")
+            );
+            assert_eq!(parsed.len(), records_of(SAMPLES[2]).len());
+        }
+    }
+
+    #[test]
+    fn test_add_glob() {
+        let parser = EvtxMultiParser::new()
+            .add_glob("samples/sys*.evtx")
+            .unwrap();
+
+        assert_eq!(
+            parser.paths(),
+            &[
+                PathBuf::from("samples/sysmon.evtx"),
+                PathBuf::from("samples/system.evtx")
+            ]
+        );
+        assert!(EvtxMultiParser::new().add_glob("samples/[").is_err());
+    }
+}
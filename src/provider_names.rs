@@ -0,0 +1,258 @@
+//! Resolution of provider names from provider GUIDs, for records whose `Provider` element has no `Name`.
+//!
+//! With `ParserSettings::resolve_provider_names`, the GUID is looked up (in order) with the lookup set by
+//! `ParserSettings::provider_name_lookup`, in the `providers` table of the `Enrichment` of the settings,
+//! and in a bundled table of common providers.
+//! The resolved name is added to the JSON output as the `Name` attribute of the `Provider`,
+//! and returned by `EvtxRecord::provider_name`.
+use crate::guid::Guid;
+use crate::ParserSettings;
+
+/// A source of provider names, see `ParserSettings::provider_name_lookup`.
+///
+/// Implemented for closures, so a `HashMap` (or any other source) can be plugged in:
+///
+/// ```
+/// # use evtx::{Guid, ParserSettings};
+/// # use std::collections::HashMap;
+/// let mut names = HashMap::new();
+/// names.insert("9E814AAD-3204-11D2-9A82-006008A86939".parse::<Guid>().unwrap(), "MSNT_SystemTrace");
+///
+/// let settings = ParserSettings::new()
+///     .provider_name_lookup(move |guid: &Guid| names.get(guid).map(|name| name.to_string()));
+/// ```
+pub trait ProviderNameLookup: Send + Sync {
+    fn provider_name(&self, guid: &Guid) -> Option<String>;
+}
+
+impl<F> ProviderNameLookup for F
+where
+    F: Fn(&Guid) -> Option<String> + Send + Sync,
+{
+    fn provider_name(&self, guid: &Guid) -> Option<String> {
+        self(guid)
+    }
+}
+
+/// Resolves the name of the provider `guid` (with or without braces) using the lookups enabled in `settings`.
+pub(crate) fn resolve_provider_name(settings: &ParserSettings, guid: &str) -> Option<String> {
+    let guid: Guid = guid.parse().ok()?;
+
+    settings
+        .get_provider_name_lookup()
+        .and_then(|lookup| lookup.provider_name(&guid))
+        .or_else(|| {
+            settings
+                .get_enrichment()
+                .and_then(|enrichment| enrichment.provider_name(&guid.to_string()))
+                .map(str::to_string)
+        })
+        .or_else(|| bundled_provider_name(&guid).map(str::to_string))
+}
+
+/// Returns the name of a common provider from the bundled table.
+pub fn bundled_provider_name(guid: &Guid) -> Option<&'static str> {
+    let guid = guid.to_string();
+
+    PROVIDERS
+        .binary_search_by_key(&guid.as_str(), |(guid, _)| *guid)
+        .ok()
+        .map(|index| PROVIDERS[index].1)
+}
+
+/// Sorted by GUID (uppercase, as displayed).
+const PROVIDERS: &[(&str, &str)] = &[
+    (
+        "06EDCFEB-0FD0-4E53-ACCA-A6F8BBF81BCB",
+        "Microsoft-Windows-Time-Service",
+    ),
+    (
+        "0888E5EF-9B98-4695-979D-E92CE4247224",
+        "Microsoft-Windows-RestartManager",
+    ),
+    (
+        "11CD958A-C507-4EF3-B3F2-5FD9DFBD2C78",
+        "Microsoft-Windows-Windows Defender",
+    ),
+    (
+        "1418EF04-B0B4-4623-BF7E-D74AB47BBDAA",
+        "Microsoft-Windows-WMI-Activity",
+    ),
+    (
+        "15CA44FF-4D7A-4BAA-BBA5-0998955E531E",
+        "Microsoft-Windows-Kernel-Boot",
+    ),
+    (
+        "1C95126E-7EEA-49A9-A3FE-A378B03DDB4D",
+        "Microsoft-Windows-DNS-Client",
+    ),
+    (
+        "206F6DEA-D3C5-4D10-BC72-989F03C8B84B",
+        "Microsoft-Windows-Wininit",
+    ),
+    (
+        "22FB2CD6-0E7B-422B-A0C7-2FAD1FD0E716",
+        "Microsoft-Windows-Kernel-Process",
+    ),
+    (
+        "331C3B3A-2005-44C2-AC5E-77220C37D6B4",
+        "Microsoft-Windows-Kernel-Power",
+    ),
+    (
+        "4EE76BD8-3CF4-44A0-A0AC-3937643E37A3",
+        "Microsoft-Windows-CodeIntegrity",
+    ),
+    (
+        "54849625-5478-4994-A5BA-3E3B0328C30D",
+        "Microsoft-Windows-Security-Auditing",
+    ),
+    (
+        "555908D1-A6D7-4695-8E1E-26931D2012F4",
+        "Service Control Manager",
+    ),
+    (
+        "5770385F-C22A-43E0-BF4C-06F5698FFBD9",
+        "Microsoft-Windows-Sysmon",
+    ),
+    (
+        "5D896912-022D-40AA-A3A8-4FA5515C76D7",
+        "Microsoft-Windows-TerminalServices-LocalSessionManager",
+    ),
+    (
+        "70EB4F03-C1DE-4F73-A051-33D13D5413BD",
+        "Microsoft-Windows-Kernel-Registry",
+    ),
+    (
+        "7DD42A49-5329-4832-8DFD-43D979153A88",
+        "Microsoft-Windows-Kernel-Network",
+    ),
+    (
+        "89B1E9F0-5AFF-44A6-9B44-0A07A7CE5845",
+        "Microsoft-Windows-User Profiles Service",
+    ),
+    (
+        "945A8954-C147-4ACD-923F-40C45405A658",
+        "Microsoft-Windows-WindowsUpdateClient",
+    ),
+    (
+        "988C59C5-0A1C-45B6-A555-0C62276E327D",
+        "Microsoft-Windows-SMBClient",
+    ),
+    (
+        "9C205A39-1250-487D-ABD7-E831C6290539",
+        "Microsoft-Windows-Kernel-PnP",
+    ),
+    (
+        "A0C1853B-5C40-4B15-8766-3CF1C58F985A",
+        "Microsoft-Windows-PowerShell",
+    ),
+    (
+        "A68CA8B7-004F-D7B6-A698-07E2DE0F1F5D",
+        "Microsoft-Windows-Kernel-General",
+    ),
+    (
+        "A7975C8F-AC13-49F1-87DA-5A984A4AB417",
+        "Microsoft-Windows-WinRM",
+    ),
+    (
+        "AEA1B4FA-97D1-45F2-A64C-4D69FFFD92C9",
+        "Microsoft-Windows-GroupPolicy",
+    ),
+    (
+        "C76BAA63-AE81-421C-B425-340B4B24157F",
+        "Microsoft-Windows-TerminalServices-RemoteConnectionManager",
+    ),
+    (
+        "CBDA4DBF-8D5D-4F69-9578-BE14AA540D22",
+        "Microsoft-Windows-AppLocker",
+    ),
+    (
+        "D1BC9AFF-2ABF-4D71-9146-ECB2A986EB85",
+        "Microsoft-Windows-Windows Firewall With Advanced Security",
+    ),
+    (
+        "D48CE617-33A2-4BC3-A5C7-11AA4F29619E",
+        "Microsoft-Windows-SMBServer",
+    ),
+    (
+        "DBE9B383-7CF3-4331-91CC-A3CB16A3B538",
+        "Microsoft-Windows-Winlogon",
+    ),
+    (
+        "DE7B24EA-73C8-4A09-985D-5BDADCFA9017",
+        "Microsoft-Windows-TaskScheduler",
+    ),
+    (
+        "E23B33B0-C8C9-472C-A5F9-F2BDFEA0F156",
+        "Microsoft-Windows-Security-SPP",
+    ),
+    (
+        "EDD08927-9CC4-4E65-B970-C2560FB5C289",
+        "Microsoft-Windows-Kernel-File",
+    ),
+    (
+        "EEF54E71-0661-422D-9A98-82FD4940B820",
+        "Microsoft-Windows-Application-Experience",
+    ),
+    (
+        "EF1CC15B-46C1-414E-BB95-E76B077BD51E",
+        "Microsoft-Windows-Bits-Client",
+    ),
+    (
+        "FC65DDD8-D6EF-4962-83D5-6E5CFE9CE148",
+        "Microsoft-Windows-Eventlog",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_providers_are_sorted() {
+        assert!(PROVIDERS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(PROVIDERS
+            .iter()
+            .all(|(guid, _)| guid.parse::<Guid>().unwrap().to_string() == *guid));
+    }
+
+    #[test]
+    fn test_bundled_provider_name() {
+        let guid: Guid = "{5770385f-c22a-43e0-bf4c-06f5698ffbd9}".parse().unwrap();
+        assert_eq!(
+            bundled_provider_name(&guid),
+            Some("Microsoft-Windows-Sysmon")
+        );
+
+        let guid: Guid = "00000000-0000-0000-0000-000000000000".parse().unwrap();
+        assert_eq!(bundled_provider_name(&guid), None);
+    }
+
+    #[test]
+    fn test_resolve_provider_name() {
+        let settings = ParserSettings::new();
+        assert_eq!(
+            resolve_provider_name(&settings, "{54849625-5478-4994-A5BA-3E3B0328C30D}").as_deref(),
+            Some("Microsoft-Windows-Security-Auditing")
+        );
+        assert_eq!(resolve_provider_name(&settings, "not a guid"), None);
+
+        let custom: Guid = "9E814AAD-3204-11D2-9A82-006008A86939".parse().unwrap();
+        let settings = ParserSettings::new().provider_name_lookup(move |guid: &Guid| {
+            if *guid == custom {
+                Some("MSNT_SystemTrace".to_string())
+            } else {
+                None
+            }
+        });
+        assert!(settings.should_resolve_provider_names());
+        assert_eq!(
+            resolve_provider_name(&settings, "9e814aad-3204-11d2-9a82-006008a86939").as_deref(),
+            Some("MSNT_SystemTrace")
+        );
+        assert_eq!(
+            resolve_provider_name(&settings, "5770385F-C22A-43E0-BF4C-06F5698FFBD9").as_deref(),
+            Some("Microsoft-Windows-Sysmon")
+        );
+    }
+}
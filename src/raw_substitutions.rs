@@ -0,0 +1,271 @@
+//! Reading the values of a record straight from the substitutions of it's template instance.
+//!
+//! Most records are a single instance of a template, where the `System` values (`EventID`, `Level`, ...)
+//! are substitutions. Finding the substitution of an element in the (cached) template definition
+//! and decoding it from the chunk data is enough to reject most records filtered on these values,
+//! without deserializing (or allocating) any of their tokens.
+use crate::binxml::value_variant::{BinXmlValue, BinXmlValueType};
+use crate::evtx_chunk::EvtxChunk;
+use crate::model::deserialized::{BinXMLDeserializedTokens, BinXMLTemplateDefinition};
+
+use byteorder::{ByteOrder, LittleEndian};
+use std::convert::TryFrom;
+
+const FRAGMENT_HEADER_TOKEN: u8 = 0x0f;
+const TEMPLATE_INSTANCE_TOKEN: u8 = 0x0c;
+/// Size of the template definition header (next offset, GUID and data size).
+const TEMPLATE_DEFINITION_HEADER_SIZE: usize = 24;
+
+/// The substitutions of a record, read from the chunk data.
+pub(crate) struct RawSubstitutions<'c> {
+    data: &'c [u8],
+    definition: &'c BinXMLTemplateDefinition<'c>,
+    /// Number of substitutions.
+    count: usize,
+    /// Offset of the value descriptors (size and type of every substitution) in `data`.
+    descriptors_offset: usize,
+    /// Offset of the first value in `data`.
+    values_offset: usize,
+}
+
+/// The content of an element in a template definition.
+enum Content<'t, 'c> {
+    Static(&'t BinXmlValue<'c>),
+    Substitution(usize),
+}
+
+impl<'c> RawSubstitutions<'c> {
+    /// Reads the template instance of the record whose BinXML starts at `offset` (in the chunk).
+    /// Returns `None` unless the record is a single instance of a template cached by the chunk.
+    pub(crate) fn read(chunk: &'c EvtxChunk<'c>, offset: usize) -> Option<Self> {
+        let data = chunk.data;
+
+        // Fragment header (token, major and minor version, flags).
+        if *data.get(offset)? != FRAGMENT_HEADER_TOKEN {
+            return None;
+        }
+
+        // Template instance (token, unknown byte, template id and definition offset).
+        let instance = offset + 4;
+        if *data.get(instance)? != TEMPLATE_INSTANCE_TOKEN {
+            return None;
+        }
+
+        let definition_offset = read_u32(data, instance + 6)?;
+        let definition = chunk.template_table.get_template(definition_offset)?;
+
+        let mut position = instance + 10;
+
+        // The definition follows the instance the first time the template is used.
+        if definition_offset as usize == position {
+            position += TEMPLATE_DEFINITION_HEADER_SIZE + definition.data_size as usize;
+        }
+
+        let count = read_u32(data, position)? as usize;
+        let descriptors_offset = position + 4;
+        let values_offset = descriptors_offset.checked_add(count.checked_mul(4)?)?;
+
+        if values_offset > data.len() {
+            return None;
+        }
+
+        Some(RawSubstitutions {
+            data,
+            definition,
+            count,
+            descriptors_offset,
+            values_offset,
+        })
+    }
+
+    /// Returns the `EventID` of the record, if it can be read from the substitutions.
+    pub(crate) fn event_id(&self) -> Option<u16> {
+        self.element_as_u64("EventID", false)
+            .and_then(|id| u16::try_from(id).ok())
+    }
+
+    /// Returns the `Level` of the record, if it can be read from the substitutions.
+    pub(crate) fn level(&self) -> Option<u8> {
+        self.element_as_u64("Level", false)
+            .and_then(|level| u8::try_from(level).ok())
+    }
+
+    /// Returns the `Keywords` of the record, if they can be read from the substitutions.
+    pub(crate) fn keywords(&self) -> Option<u64> {
+        self.element_as_u64("Keywords", true)
+    }
+
+    /// Returns the content of the first `element` as an integer (hex integers are only read if `hex` is set),
+    /// if it is one and the same value would be found in the deserialized record
+    /// (see `EvtxRecord::event_id`). Returns `None` if it can't be decided.
+    pub(crate) fn element_as_u64(&self, element: &str, hex: bool) -> Option<u64> {
+        match find_content(&self.definition.tokens, element)? {
+            Content::Static(value) => match value {
+                BinXmlValue::HexInt32Type(s) | BinXmlValue::HexInt64Type(s) if hex => {
+                    u64::from_str_radix(s.trim_start_matches("0x"), 16).ok()
+                }
+                value => value.as_u64(),
+            },
+            Content::Substitution(index) => self.substitution_as_u64(index, hex),
+        }
+    }
+
+    fn substitution_as_u64(&self, index: usize, hex: bool) -> Option<u64> {
+        if index >= self.count {
+            return None;
+        }
+
+        let mut offset = self.values_offset;
+
+        for i in 0..index {
+            offset += read_u16(self.data, self.descriptors_offset + i * 4)? as usize;
+        }
+
+        let descriptor = self.descriptors_offset + index * 4;
+        let size = read_u16(self.data, descriptor)? as usize;
+        let value_type = BinXmlValueType::from_u8(*self.data.get(descriptor + 2)?)?;
+        let value = self.data.get(offset..offset + size)?;
+
+        // Signed values are only integers (for `as_u64`) when they are not negative.
+        match (value_type, size) {
+            (BinXmlValueType::UInt8Type, 1) => Some(u64::from(value[0])),
+            (BinXmlValueType::UInt16Type, 2) => Some(u64::from(LittleEndian::read_u16(value))),
+            (BinXmlValueType::UInt32Type, 4) => Some(u64::from(LittleEndian::read_u32(value))),
+            (BinXmlValueType::UInt64Type, 8) => Some(LittleEndian::read_u64(value)),
+            (BinXmlValueType::Int8Type, 1) => u64::try_from(value[0] as i8).ok(),
+            (BinXmlValueType::Int16Type, 2) => u64::try_from(LittleEndian::read_i16(value)).ok(),
+            (BinXmlValueType::Int32Type, 4) => u64::try_from(LittleEndian::read_i32(value)).ok(),
+            (BinXmlValueType::Int64Type, 8) => u64::try_from(LittleEndian::read_i64(value)).ok(),
+            (BinXmlValueType::HexInt32Type, 4) if hex => {
+                Some(u64::from(LittleEndian::read_u32(value)))
+            }
+            (BinXmlValueType::HexInt64Type, 8) if hex => Some(LittleEndian::read_u64(value)),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the content of the first `element` in the tokens of a template definition,
+/// the same way `find_value` would in the deserialized record.
+///
+/// Gives up (returning `None`) on anything which might hide an earlier `element`,
+/// such as nested BinXML or optional substitutions.
+fn find_content<'t, 'c>(
+    tokens: &'t [BinXMLDeserializedTokens<'c>],
+    element: &str,
+) -> Option<Content<'t, 'c>> {
+    let mut inside_element = false;
+    let mut inside_content = false;
+
+    for token in tokens {
+        match token {
+            BinXMLDeserializedTokens::OpenStartElement(elem) => {
+                inside_element = elem.name.0 == element;
+                inside_content = false;
+            }
+            BinXMLDeserializedTokens::CloseStartElement => inside_content = true,
+            BinXMLDeserializedTokens::CloseEmptyElement
+            | BinXMLDeserializedTokens::CloseElement => {
+                inside_element = false;
+                inside_content = false;
+            }
+            BinXMLDeserializedTokens::Value(value) => match value.as_ref() {
+                BinXmlValue::BinXmlType(_) => return None,
+                value if inside_element && inside_content => return Some(Content::Static(value)),
+                _ => {}
+            },
+            BinXMLDeserializedTokens::Substitution(descriptor) => {
+                if descriptor.ignore || descriptor.value_type == BinXmlValueType::BinXmlType {
+                    return None;
+                }
+
+                if inside_element && inside_content {
+                    return Some(Content::Substitution(
+                        descriptor.substitution_index as usize,
+                    ));
+                }
+            }
+            BinXMLDeserializedTokens::TemplateInstance(_) => return None,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2).map(LittleEndian::read_u16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4).map(LittleEndian::read_u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ensure_env_logger_initialized;
+    use crate::evtx_chunk::{EvtxChunkData, EVTX_CHUNK_HEADER_SIZE};
+    use crate::evtx_parser::{EVTX_CHUNK_SIZE, EVTX_FILE_HEADER_SIZE};
+    use crate::ParserSettings;
+    use std::sync::Arc;
+
+    /// Checks the values read from the substitutions of every record of the first chunk of `evtx_file`
+    /// against the deserialized record, returns the number of records whose substitutions could be read.
+    fn check_first_chunk(evtx_file: &[u8]) -> (usize, usize) {
+        let chunk_data =
+            evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE].to_vec();
+        let settings = Arc::new(ParserSettings::default());
+
+        // Iterating borrows the chunk, so the substitutions are read from a second copy of it.
+        let mut records_chunk_data = EvtxChunkData::new(chunk_data.clone(), false).unwrap();
+        let mut records_chunk = records_chunk_data.parse(Arc::clone(&settings)).unwrap();
+        let mut chunk_data = EvtxChunkData::new(chunk_data, false).unwrap();
+        let headers = chunk_data.record_headers();
+        let chunk = chunk_data.parse(settings).unwrap();
+
+        let mut offset = EVTX_CHUNK_HEADER_SIZE;
+        let mut read = 0;
+
+        for (header, record) in headers.iter().zip(records_chunk.iter()) {
+            let record = record.unwrap();
+            // The BinXML follows the 24 bytes record header.
+            let raw = RawSubstitutions::read(&chunk, offset + 24);
+            offset += header.data_size as usize;
+
+            if let Some(raw) = raw {
+                read += 1;
+
+                for (value, expected) in [
+                    (
+                        raw.event_id().map(u64::from),
+                        record.event_id().map(u64::from),
+                    ),
+                    (raw.level().map(u64::from), record.level().map(u64::from)),
+                    (raw.keywords(), record.keywords()),
+                ] {
+                    assert!(value.is_none() || value == expected);
+                }
+            }
+        }
+
+        (read, headers.len())
+    }
+
+    #[test]
+    fn test_reads_same_values_as_deserialized_records() {
+        ensure_env_logger_initialized();
+
+        let (read, total) = check_first_chunk(include_bytes!("../samples/security.evtx"));
+        assert_eq!(read, total);
+
+        for evtx_file in [
+            &include_bytes!("../samples/system.evtx")[..],
+            &include_bytes!("../samples/sysmon.evtx")[..],
+            &include_bytes!("../samples/Application.evtx")[..],
+        ] {
+            let (read, _) = check_first_chunk(evtx_file);
+            assert!(read > 0);
+        }
+    }
+}
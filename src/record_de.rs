@@ -0,0 +1,695 @@
+//! A `serde::Deserializer` over the elements of a record, see `EvtxRecord::deserialize`.
+//!
+//! The record is assembled into a tree of elements, keeping the typed values of the token tree,
+//! so that numbers are deserialized as numbers without going through a rendered string.
+//!
+//! An element is deserialized as it's value if it has neither attributes nor child elements,
+//! otherwise as a map of it's attributes, it's child elements (as a sequence when a name is repeated)
+//! and it's text (under `#text`). `<Data Name="X">` elements are keyed by their `Name`, like in the JSON output.
+use crate::binxml::assemble::parse_tokens;
+use crate::binxml::value_variant::BinXmlValue;
+use crate::err::{self, Error, Result};
+use crate::model::deserialized::BinXMLDeserializedTokens;
+use crate::model::xml::XmlElement;
+use crate::xml_output::BinXmlOutput;
+
+use chrono::SecondsFormat;
+use serde::de::value::StringDeserializer;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::forward_to_deserialize_any;
+use snafu::OptionExt;
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::RecordDeserializationError {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// An element of the record, with owned values.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Element {
+    name: String,
+    /// The `Name` attribute of a `Data` element, which is it's key instead of it's name.
+    data_name: Option<String>,
+    attributes: Vec<(String, BinXmlValue<'static>)>,
+    text: Vec<BinXmlValue<'static>>,
+    children: Vec<Element>,
+}
+
+impl Element {
+    /// Assembles the tokens of a record into it's root (`Event`) element.
+    pub(crate) fn from_tokens(tokens: Vec<BinXMLDeserializedTokens>) -> Result<Self> {
+        let mut builder = ElementBuilder {
+            stack: vec![],
+            root: None,
+        };
+        parse_tokens(tokens, &mut builder)?;
+
+        builder.root.context(err::RecordDeserializationError {
+            message: "the record has no root element",
+        })
+    }
+
+    /// Returns the first child element named `name`.
+    pub(crate) fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// The `EventData` of the record, or the element holding the fields of it's `UserData`.
+    pub(crate) fn event_data(&self) -> Option<&Element> {
+        self.child("EventData").or_else(|| {
+            self.child("UserData")
+                .and_then(|data| data.children.first())
+        })
+    }
+
+    /// The values of the child elements, keyed by their `Name` (for `Data` elements) or by their name.
+    ///
+    /// Anonymous `Data` elements are keyed by their position, zero-padded so that they sort in order.
+    /// Child elements which have child elements of their own are skipped.
+    pub(crate) fn fields(&self) -> BTreeMap<String, BinXmlValue<'static>> {
+        let anonymous = self
+            .children
+            .iter()
+            .filter(|child| child.name == "Data" && child.data_name.is_none())
+            .count();
+        let width = anonymous.saturating_sub(1).to_string().len();
+        let mut position = 0;
+
+        let mut fields = BTreeMap::new();
+        for child in self
+            .children
+            .iter()
+            .filter(|child| child.children.is_empty())
+        {
+            let key = if child.name == "Data" && child.data_name.is_none() {
+                position += 1;
+                format!("{:0width$}", position - 1, width = width)
+            } else {
+                child.key().to_owned()
+            };
+            let value = child.value().map_or(BinXmlValue::NullType, Cow::into_owned);
+
+            fields.insert(key, value);
+        }
+
+        fields
+    }
+
+    fn key(&self) -> &str {
+        self.data_name.as_deref().unwrap_or(&self.name)
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.attributes.is_empty() && self.children.is_empty()
+    }
+
+    /// The value of a leaf element, `None` if it's empty.
+    fn value(&self) -> Option<Cow<'_, BinXmlValue<'static>>> {
+        match self.text.as_slice() {
+            [] | [BinXmlValue::NullType] => None,
+            [value] => Some(Cow::Borrowed(value)),
+            values => Some(Cow::Owned(BinXmlValue::StringType(Cow::Owned(
+                values.iter().map(BinXmlValue::as_cow_str).collect(),
+            )))),
+        }
+    }
+}
+
+struct ElementBuilder {
+    stack: Vec<Element>,
+    root: Option<Element>,
+}
+
+impl BinXmlOutput for ElementBuilder {
+    fn visit_end_of_stream(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_open_start_element(&mut self, open_start_element: &XmlElement) -> Result<()> {
+        let mut element = Element {
+            name: open_start_element.name.as_str().to_owned(),
+            ..Element::default()
+        };
+
+        for attribute in open_start_element.attributes.iter() {
+            let name = attribute.name.as_str();
+            let value = attribute.value.as_ref();
+
+            if element.name == "Data" && name == "Name" {
+                element.data_name = Some(value.as_cow_str().into_owned());
+            } else if *value == BinXmlValue::NullType {
+                // Like in the JSON output, null attributes (such as missing `Qualifiers`) are omitted.
+            } else {
+                element
+                    .attributes
+                    .push((name.to_owned(), value.to_owned_value()));
+            }
+        }
+
+        self.stack.push(element);
+        Ok(())
+    }
+
+    fn visit_close_element(&mut self, _element: &XmlElement) -> Result<()> {
+        let element = self.stack.pop().context(err::RecordDeserializationError {
+            message: "unbalanced elements",
+        })?;
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => self.root = Some(element),
+        }
+        Ok(())
+    }
+
+    fn visit_characters(&mut self, value: &BinXmlValue) -> Result<()> {
+        if let Some(element) = self.stack.last_mut() {
+            element.text.push(value.to_owned_value());
+        }
+        Ok(())
+    }
+
+    fn visit_cdata_section(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_entity_reference(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_processing_instruction_target(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_processing_instruction_data(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_start_of_stream(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Deserializes a primitive from the value of a leaf element, or from the whole element otherwise.
+macro_rules! deserialize_leaf {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                match self.value() {
+                    Some(value) if self.is_leaf() => ValueDeserializer(&value).$method(visitor),
+                    _ => self.deserialize_any(visitor),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for &Element {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if !self.is_leaf() {
+            return visitor.visit_map(ElementMap::new(self));
+        }
+
+        match self.value() {
+            Some(value) => ValueDeserializer(&value).deserialize_any(visitor),
+            None => visitor.visit_unit(),
+        }
+    }
+
+    deserialize_leaf! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_f32 deserialize_f64
+        deserialize_char deserialize_str deserialize_string deserialize_bytes deserialize_byte_buf
+        deserialize_identifier
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.is_leaf() && self.value().is_none() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value() {
+            // An array value, or a string (which is probably a single element of a sequence).
+            Some(value) if self.is_leaf() => ValueDeserializer(&value).deserialize_seq(visitor),
+            _ => visitor.visit_seq(ElementSeq(vec![self].into_iter())),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_map(ElementMap::new(self))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let variant = self
+            .value()
+            .map(|value| value.as_cow_str().into_owned())
+            .unwrap_or_default();
+
+        visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(variant))
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+/// The value of a map entry.
+enum Entry<'e> {
+    Attribute(&'e BinXmlValue<'static>),
+    Elements(Vec<&'e Element>),
+    Text(&'e Element),
+}
+
+struct ElementMap<'e> {
+    entries: std::vec::IntoIter<(&'e str, Entry<'e>)>,
+    value: Option<Entry<'e>>,
+}
+
+impl<'e> ElementMap<'e> {
+    fn new(element: &'e Element) -> Self {
+        let mut entries: Vec<(&str, Entry)> = element
+            .attributes
+            .iter()
+            .map(|(name, value)| (name.as_str(), Entry::Attribute(value)))
+            .collect();
+
+        for child in element.children.iter() {
+            let existing = entries.iter_mut().find_map(|(key, entry)| match entry {
+                Entry::Elements(elements) if *key == child.key() => Some(elements),
+                _ => None,
+            });
+
+            match existing {
+                Some(elements) => elements.push(child),
+                None => entries.push((child.key(), Entry::Elements(vec![child]))),
+            }
+        }
+
+        if !element.is_leaf() && element.value().is_some() {
+            entries.push(("#text", Entry::Text(element)));
+        }
+
+        ElementMap {
+            entries: entries.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de, 'e> MapAccess<'de> for ElementMap<'e> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                let key: StringDeserializer<Error> = key.to_owned().into_deserializer();
+                seed.deserialize(key).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        match self.value.take() {
+            Some(Entry::Attribute(value)) => seed.deserialize(ValueDeserializer(value)),
+            Some(Entry::Elements(elements)) => match elements.as_slice() {
+                [element] => seed.deserialize(*element),
+                _ => seed.deserialize(ElementSeqDeserializer(elements)),
+            },
+            Some(Entry::Text(element)) => {
+                let value = element.value().expect("checked when listing the entries");
+                seed.deserialize(ValueDeserializer(&value))
+            }
+            None => Err(de::Error::custom("value requested before it's key")),
+        }
+    }
+}
+
+/// Repeated child elements.
+struct ElementSeqDeserializer<'e>(Vec<&'e Element>);
+
+impl<'de, 'e> de::Deserializer<'de> for ElementSeqDeserializer<'e> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(ElementSeq(self.0.into_iter()))
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct ElementSeq<'e>(std::vec::IntoIter<&'e Element>);
+
+impl<'de, 'e> SeqAccess<'de> for ElementSeq<'e> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.0
+            .next()
+            .map(|element| seed.deserialize(element))
+            .transpose()
+    }
+}
+
+/// Deserializes a single value, parsing strings when a number or a boolean is expected.
+struct ValueDeserializer<'v>(&'v BinXmlValue<'static>);
+
+impl<'v> ValueDeserializer<'v> {
+    /// Parses a decimal or a `0x` prefixed hexadecimal number.
+    fn parse_u64(&self) -> Option<u64> {
+        let s = self.0.as_cow_str();
+        let s = s.trim();
+
+        match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => s.parse().ok(),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(&self) -> Option<T> {
+        self.0.as_cow_str().trim().parse().ok()
+    }
+
+    fn parse_bool(&self) -> Option<bool> {
+        match self.0.as_cow_str().trim() {
+            "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        }
+    }
+
+    fn is_string(&self) -> bool {
+        matches!(
+            self.0,
+            BinXmlValue::StringType(_)
+                | BinXmlValue::AnsiStringType(_)
+                | BinXmlValue::HexInt32Type(_)
+                | BinXmlValue::HexInt64Type(_)
+        )
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident($parse:ident)),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+                if self.is_string() {
+                    match self.$parse() {
+                        Some(value) => visitor.$visit(value),
+                        None => Err(de::Error::invalid_value(
+                            de::Unexpected::Str(&self.0.as_cow_str()),
+                            &visitor,
+                        )),
+                    }
+                } else {
+                    self.deserialize_any(visitor)
+                }
+            }
+        )*
+    };
+}
+
+impl<'de, 'v> de::Deserializer<'de> for ValueDeserializer<'v> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            BinXmlValue::NullType => visitor.visit_unit(),
+            BinXmlValue::StringType(s) | BinXmlValue::AnsiStringType(s) => visitor.visit_str(s),
+            BinXmlValue::Int8Type(num) => visitor.visit_i8(*num),
+            BinXmlValue::UInt8Type(num) => visitor.visit_u8(*num),
+            BinXmlValue::Int16Type(num) => visitor.visit_i16(*num),
+            BinXmlValue::UInt16Type(num) => visitor.visit_u16(*num),
+            BinXmlValue::Int32Type(num) => visitor.visit_i32(*num),
+            BinXmlValue::UInt32Type(num) => visitor.visit_u32(*num),
+            BinXmlValue::Int64Type(num) => visitor.visit_i64(*num),
+            BinXmlValue::UInt64Type(num) => visitor.visit_u64(*num),
+            BinXmlValue::SizeTType(num) => visitor.visit_u64(*num as u64),
+            BinXmlValue::Real32Type(num) => visitor.visit_f32(*num),
+            BinXmlValue::Real64Type(num) => visitor.visit_f64(*num),
+            BinXmlValue::BoolType(b) => visitor.visit_bool(*b),
+            BinXmlValue::BinaryType(bytes) => visitor.visit_bytes(bytes),
+            BinXmlValue::FileTimeType(time) | BinXmlValue::SysTimeType(time) => {
+                visitor.visit_string(time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }
+            BinXmlValue::StringArrayType(_)
+            | BinXmlValue::Int8ArrayType(_)
+            | BinXmlValue::UInt8ArrayType(_)
+            | BinXmlValue::Int16ArrayType(_)
+            | BinXmlValue::UInt16ArrayType(_)
+            | BinXmlValue::Int32ArrayType(_)
+            | BinXmlValue::UInt32ArrayType(_)
+            | BinXmlValue::Int64ArrayType(_)
+            | BinXmlValue::UInt64ArrayType(_)
+            | BinXmlValue::Real32ArrayType(_)
+            | BinXmlValue::Real64ArrayType(_)
+            | BinXmlValue::BoolArrayType(_)
+            | BinXmlValue::GuidArrayType(_)
+            | BinXmlValue::FileTimeArrayType(_)
+            | BinXmlValue::SysTimeArrayType(_)
+            | BinXmlValue::SidArrayType(_)
+            | BinXmlValue::HexInt32ArrayType(_)
+            | BinXmlValue::HexInt64ArrayType(_) => self.deserialize_seq(visitor),
+            value => visitor.visit_string(value.as_cow_str().into_owned()),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_u8 => visit_u64(parse_u64),
+        deserialize_u16 => visit_u64(parse_u64),
+        deserialize_u32 => visit_u64(parse_u64),
+        deserialize_u64 => visit_u64(parse_u64),
+        deserialize_i8 => visit_i64(parse),
+        deserialize_i16 => visit_i64(parse),
+        deserialize_i32 => visit_i64(parse),
+        deserialize_i64 => visit_i64(parse),
+        deserialize_f32 => visit_f64(parse),
+        deserialize_f64 => visit_f64(parse),
+        deserialize_bool => visit_bool(parse_bool),
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            BinXmlValue::NullType => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let values: Vec<BinXmlValue<'static>> = match self.0 {
+            BinXmlValue::StringArrayType(values) => values
+                .iter()
+                .map(|s| BinXmlValue::StringType(Cow::Owned(s.to_string())))
+                .collect(),
+            BinXmlValue::Int8ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::Int8Type(*n)).collect()
+            }
+            BinXmlValue::UInt8ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::UInt8Type(*n)).collect()
+            }
+            BinXmlValue::Int16ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::Int16Type(*n)).collect()
+            }
+            BinXmlValue::UInt16ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::UInt16Type(*n)).collect()
+            }
+            BinXmlValue::Int32ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::Int32Type(*n)).collect()
+            }
+            BinXmlValue::UInt32ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::UInt32Type(*n)).collect()
+            }
+            BinXmlValue::Int64ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::Int64Type(*n)).collect()
+            }
+            BinXmlValue::UInt64ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::UInt64Type(*n)).collect()
+            }
+            BinXmlValue::Real32ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::Real32Type(*n)).collect()
+            }
+            BinXmlValue::Real64ArrayType(values) => {
+                values.iter().map(|n| BinXmlValue::Real64Type(*n)).collect()
+            }
+            BinXmlValue::BoolArrayType(values) => {
+                values.iter().map(|b| BinXmlValue::BoolType(*b)).collect()
+            }
+            BinXmlValue::FileTimeArrayType(values) | BinXmlValue::SysTimeArrayType(values) => {
+                values
+                    .iter()
+                    .map(|t| BinXmlValue::FileTimeType(*t))
+                    .collect()
+            }
+            BinXmlValue::GuidArrayType(values) => values
+                .iter()
+                .map(|guid| BinXmlValue::StringType(Cow::Owned(guid.to_string())))
+                .collect(),
+            BinXmlValue::SidArrayType(values) => values
+                .iter()
+                .map(|sid| BinXmlValue::StringType(Cow::Owned(sid.to_string())))
+                .collect(),
+            BinXmlValue::HexInt32ArrayType(values) | BinXmlValue::HexInt64ArrayType(values) => {
+                values
+                    .iter()
+                    .map(|s| BinXmlValue::StringType(Cow::Owned(s.to_string())))
+                    .collect()
+            }
+            // A single value is a sequence of one value.
+            value => vec![value.clone()],
+        };
+
+        visitor.visit_seq(ValueSeq(values.into_iter()))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let variant = self.0.as_cow_str().into_owned();
+        visitor.visit_enum(IntoDeserializer::<Error>::into_deserializer(variant))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct ValueSeq(std::vec::IntoIter<BinXmlValue<'static>>);
+
+impl<'de> SeqAccess<'de> for ValueSeq {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(ValueDeserializer(&value)))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(name: Option<&str>, value: &str) -> Element {
+        Element {
+            name: "Data".to_string(),
+            data_name: name.map(str::to_string),
+            text: vec![BinXmlValue::StringType(Cow::Owned(value.to_string()))],
+            ..Element::default()
+        }
+    }
+
+    #[test]
+    fn test_anonymous_data_fields_sort_in_order() {
+        let event_data = Element {
+            name: "EventData".to_string(),
+            children: (0..11).map(|i| data(None, &i.to_string())).collect(),
+            ..Element::default()
+        };
+
+        let fields = event_data.fields();
+        let keys: Vec<&str> = fields.keys().map(String::as_str).collect();
+        let values: Vec<Cow<str>> = fields.values().map(BinXmlValue::as_cow_str).collect();
+
+        assert_eq!(keys[..3], ["00", "01", "02"]);
+        assert_eq!(keys[10], "10");
+        assert_eq!(values[..3], ["0", "1", "2"]);
+    }
+
+    #[test]
+    fn test_named_data_fields() {
+        let mut empty = data(Some("Empty"), "");
+        empty.text.clear();
+        let event_data = Element {
+            name: "EventData".to_string(),
+            children: vec![data(Some("B"), "b"), data(Some("A"), "a"), empty],
+            ..Element::default()
+        };
+
+        let fields = event_data.fields();
+
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["A", "B", "Empty"]);
+        assert_eq!(fields["Empty"], BinXmlValue::NullType);
+    }
+}
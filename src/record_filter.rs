@@ -0,0 +1,689 @@
+//! Composable filters over the `System` values of a record.
+//!
+//! Filters are evaluated on the record tokens, before the record is serialized.
+//!
+//! ```rust
+//! use evtx::{ParserSettings, RecordFilter, KEYWORD_AUDIT_FAILURE};
+//!
+//! // Errors, or failed audits of a logon.
+//! let filter = RecordFilter::levels(&[1, 2])
+//!     .or(RecordFilter::keywords_any(KEYWORD_AUDIT_FAILURE).and(RecordFilter::event_ids(&[4625])));
+//!
+//! let settings = ParserSettings::new().record_filter(filter);
+//! ```
+use crate::activity::normalize_activity_id;
+use crate::binxml::lookup::{any_data_value, any_value, find_data_value, find_value};
+use crate::binxml::value_variant::BinXmlValue;
+use crate::err::{self, Result};
+use crate::filter_expr;
+use crate::filter_query;
+use crate::raw_substitutions::RawSubstitutions;
+use crate::xpath;
+use crate::EvtxRecord;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use snafu::ResultExt;
+use std::cmp::Ordering;
+use std::ops::RangeInclusive;
+
+/// `LogAlways` level, used by most of the `Security` events.
+pub const LEVEL_LOG_ALWAYS: u8 = 0;
+pub const LEVEL_CRITICAL: u8 = 1;
+pub const LEVEL_ERROR: u8 = 2;
+pub const LEVEL_WARNING: u8 = 3;
+pub const LEVEL_INFORMATION: u8 = 4;
+pub const LEVEL_VERBOSE: u8 = 5;
+
+pub const KEYWORD_RESPONSE_TIME: u64 = 0x0001_0000_0000_0000;
+pub const KEYWORD_WDI_CONTEXT: u64 = 0x0002_0000_0000_0000;
+pub const KEYWORD_WDI_DIAGNOSTIC: u64 = 0x0004_0000_0000_0000;
+pub const KEYWORD_SQM: u64 = 0x0008_0000_0000_0000;
+pub const KEYWORD_AUDIT_FAILURE: u64 = 0x0010_0000_0000_0000;
+pub const KEYWORD_AUDIT_SUCCESS: u64 = 0x0020_0000_0000_0000;
+pub const KEYWORD_CORRELATION_HINT: u64 = 0x0040_0000_0000_0000;
+pub const KEYWORD_CLASSIC: u64 = 0x0080_0000_0000_0000;
+
+/// A predicate over a record, which can be composed using `and`, `or` and `negate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordFilter {
+    /// The `EventID` is inside one of the ranges.
+    EventId(Vec<RangeInclusive<u16>>),
+    /// The `Level` is one of the given levels.
+    Level(Vec<u8>),
+    /// The `Keywords` have at least one of the bits of the mask set.
+    KeywordsAny(u64),
+    /// The `Keywords` have all of the bits of the mask set.
+    KeywordsAll(u64),
+    /// The provider name or GUID is one of the given providers (normalized, see `RecordFilter::providers`).
+    Provider(Vec<String>),
+    /// The `Channel` is one of the given channels (lowercase).
+    Channel(Vec<String>),
+    /// The `ActivityID` or `RelatedActivityID` is one of the given activity IDs (normalized, see `RecordFilter::activity_ids`).
+    ActivityId(Vec<String>),
+    /// One of the SIDs of the record is one of the given SIDs (uppercase, see `RecordFilter::sids`).
+    Sid(Vec<String>),
+    /// One of the account name fields is one of the given accounts (lowercase, see `RecordFilter::accounts`).
+    Account(Vec<String>),
+    /// The value of `field` compares to `value` using `op`.
+    Compare {
+        field: Field,
+        op: CompareOp,
+        value: Literal,
+    },
+    /// The value of `field` matches a wildcard pattern (`*` and `?`, lowercase), case insensitively.
+    Like {
+        field: Field,
+        pattern: String,
+    },
+    /// The value of `field` matches a regex.
+    Regex {
+        field: Field,
+        regex: FilterRegex,
+    },
+    And(Vec<RecordFilter>),
+    Or(Vec<RecordFilter>),
+    Not(Box<RecordFilter>),
+}
+
+impl RecordFilter {
+    pub fn event_ids(event_ids: &[u16]) -> Self {
+        RecordFilter::EventId(event_ids.iter().map(|&id| id..=id).collect())
+    }
+
+    pub fn event_id_ranges(ranges: &[RangeInclusive<u16>]) -> Self {
+        RecordFilter::EventId(ranges.to_vec())
+    }
+
+    pub fn levels(levels: &[u8]) -> Self {
+        RecordFilter::Level(levels.to_vec())
+    }
+
+    pub fn keywords_any(mask: u64) -> Self {
+        RecordFilter::KeywordsAny(mask)
+    }
+
+    pub fn keywords_all(mask: u64) -> Self {
+        RecordFilter::KeywordsAll(mask)
+    }
+
+    /// Providers are matched case insensitively, against both the name and the GUID of the provider.
+    pub fn providers<S: AsRef<str>>(providers: &[S]) -> Self {
+        RecordFilter::Provider(
+            providers
+                .iter()
+                .map(|p| normalize_provider(p.as_ref()))
+                .collect(),
+        )
+    }
+
+    /// Channels are matched case insensitively.
+    pub fn channels<S: AsRef<str>>(channels: &[S]) -> Self {
+        RecordFilter::Channel(channels.iter().map(|c| c.as_ref().to_lowercase()).collect())
+    }
+
+    /// Matches records with one of the given `ActivityID`s or `RelatedActivityID`s.
+    /// Activity IDs are matched case insensitively, with or without braces.
+    pub fn activity_ids<S: AsRef<str>>(activity_ids: &[S]) -> Self {
+        RecordFilter::ActivityId(
+            activity_ids
+                .iter()
+                .map(|id| normalize_activity_id(id.as_ref()))
+                .collect(),
+        )
+    }
+
+    /// Matches records referring to one of the given SIDs anywhere in the record
+    /// (e.g. `Security.UserID`, `EventData.SubjectUserSid` or `EventData.TargetSid`).
+    ///
+    /// A SID ending with `-*` matches all the SIDs it is a prefix of,
+    /// so `S-1-5-21-2603537626-3982775912-406486804-*` matches all the principals of a domain.
+    pub fn sids<S: AsRef<str>>(sids: &[S]) -> Self {
+        RecordFilter::Sid(
+            sids.iter()
+                .map(|s| s.as_ref().trim().to_uppercase())
+                .collect(),
+        )
+    }
+
+    /// Matches records where one of the account name fields (`EventData.TargetUserName` or
+    /// `EventData.SubjectUserName`) is one of the given accounts, case insensitively.
+    ///
+    /// An account can be qualified by it's domain (`WIN-03DLIIOFRRA\fsir`), in which case the domain
+    /// must match the corresponding domain field (`TargetDomainName` or `SubjectDomainName`).
+    pub fn accounts<S: AsRef<str>>(accounts: &[S]) -> Self {
+        RecordFilter::Account(
+            accounts
+                .iter()
+                .map(|a| a.as_ref().trim().to_lowercase())
+                .collect(),
+        )
+    }
+
+    /// The value of `field` matches the regex `pattern`
+    /// (use inline flags for case insensitive matching, e.g. `(?i)mimikatz`).
+    pub fn regex(field: Field, pattern: &str) -> Result<Self> {
+        Ok(RecordFilter::Regex {
+            field,
+            regex: FilterRegex::new(pattern)?,
+        })
+    }
+
+    /// The value of `field` matches at least one of the regex `patterns`.
+    /// Records missing the field never match.
+    pub fn regex_allow_list<S: AsRef<str>>(field: Field, patterns: &[S]) -> Result<Self> {
+        if patterns.is_empty() {
+            return Ok(RecordFilter::Or(vec![]));
+        }
+
+        // A single regex is faster than trying every pattern.
+        let pattern = patterns
+            .iter()
+            .map(|pattern| format!("(?:{})", pattern.as_ref()))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        RecordFilter::regex(field, &pattern)
+    }
+
+    /// The value of `field` matches none of the regex `patterns`.
+    /// Records missing the field always match.
+    pub fn regex_deny_list<S: AsRef<str>>(field: Field, patterns: &[S]) -> Result<Self> {
+        if patterns.is_empty() {
+            return Ok(RecordFilter::And(vec![]));
+        }
+
+        Ok(RecordFilter::regex_allow_list(field, patterns)?.negate())
+    }
+
+    /// Compiles a filter expression, such as
+    /// `EventID == 4624 && EventData.LogonType == 10 && TimeCreated >= "2023-01-01"`.
+    ///
+    /// Comparisons are written as `field op value`, where `op` is one of `==`, `!=`, `<`, `<=`, `>`, `>=`,
+    /// and can be combined using `&&`, `||`, `!` and parentheses.
+    /// Fields can also be matched against a regex with `field ~ /regex/flags`
+    /// (e.g. `EventData.CommandLine ~ /mimikatz|rundll32/i`), where flags are any of `i`, `m`, `s` and `x`.
+    ///
+    /// A field is one of:
+    /// - `TimeCreated` or `EventRecordID`, read from the record header.
+    /// - `EventData.<Name>`, the value of the `Data` element with the given `Name`.
+    /// - `<Element>` (or `System.<Element>`), the value of the first element with that name (e.g. `EventID`).
+    /// - `<Element>.<Attribute>`, an attribute of the first element with that name (e.g. `Execution.ProcessID`).
+    ///
+    /// Values are numbers (decimal or `0x` hex) or quoted strings.
+    /// String comparisons are case insensitive, and strings compared to times must be dates
+    /// (`2023-01-01`), UTC times (`2023-01-01 13:00:00`) or RFC 3339 timestamps.
+    pub fn parse(expr: &str) -> Result<RecordFilter> {
+        filter_expr::parse(expr)
+    }
+
+    /// Compiles a simple query, such as
+    /// `EventID=4624 AND TargetUserName~'admin*' AND TimeCreated>2023-01-01`.
+    ///
+    /// Terms are written as `field op value`, where `op` is one of `=`, `!=`, `<`, `<=`, `>`, `>=`
+    /// or `~` (a case insensitive match against a pattern with `*` and `?` wildcards),
+    /// and can be combined using `AND`, `OR`, `NOT` (in any case) and parentheses.
+    ///
+    /// Fields are the same as in `RecordFilter::parse`, except that unqualified names which are not
+    /// `System` elements (e.g. `TargetUserName`) refer to `EventData` values, and `Provider` refers to the provider name.
+    ///
+    /// Values are numbers, times, quoted strings, or unquoted words (which are numbers if they parse as one).
+    /// `~` also accepts a regex, written as in `RecordFilter::parse` (`CommandLine ~ /mimikatz|rundll32/i`).
+    pub fn from_query(query: &str) -> Result<RecordFilter> {
+        filter_query::parse(query)
+    }
+
+    /// Compiles a Windows Event Log XPath query (as used by EventViewer and `wevtutil qe /q:`).
+    ///
+    /// Only the subset used by these queries is supported: `*[System[...]]`, `*[EventData[...]]`
+    /// and `*[UserData[...]]` predicates with `and`, `or`, `not()` and comparisons,
+    /// `Data[@Name='...']` (or `Data` alone, matching any `Data` element), attributes (`Provider[@Name='...']`),
+    /// `band(Keywords, mask)` and `TimeCreated[timediff(@SystemTime) <= ms]` (relative to the time the query is compiled).
+    ///
+    /// `<QueryList>` documents (as saved by EventViewer) are also accepted: a record is kept if a `Query`
+    /// selects it with one of it's `Select` elements and none of it's `Suppress` elements
+    /// (restricted to the channel given by their `Path`). Queries nested deeper than 128 levels are rejected.
+    pub fn from_xpath(query: &str) -> Result<RecordFilter> {
+        xpath::parse(query)
+    }
+
+    /// Matches records matched by both `self` and `other`.
+    pub fn and(self, other: RecordFilter) -> Self {
+        match self {
+            RecordFilter::And(mut filters) => {
+                filters.push(other);
+                RecordFilter::And(filters)
+            }
+            _ => RecordFilter::And(vec![self, other]),
+        }
+    }
+
+    /// Matches records matched by either `self` or `other`.
+    pub fn or(self, other: RecordFilter) -> Self {
+        match self {
+            RecordFilter::Or(mut filters) => {
+                filters.push(other);
+                RecordFilter::Or(filters)
+            }
+            _ => RecordFilter::Or(vec![self, other]),
+        }
+    }
+
+    /// Matches records not matched by `self`.
+    pub fn negate(self) -> Self {
+        RecordFilter::Not(Box::new(self))
+    }
+
+    /// Returns true if `record` matches the filter.
+    /// A record missing the filtered value never matches (but does match the negation of the filter).
+    ///
+    /// Fails if the record cannot be deserialized (see `EvtxRecord::tokens`).
+    pub fn matches(&self, record: &EvtxRecord) -> Result<bool> {
+        record.tokens()?;
+
+        Ok(self.matches_deserialized(record))
+    }
+
+    fn matches_deserialized(&self, record: &EvtxRecord) -> bool {
+        match self {
+            RecordFilter::EventId(ranges) => record
+                .event_id()
+                .map_or(false, |id| ranges.iter().any(|range| range.contains(&id))),
+            RecordFilter::Level(levels) => record
+                .level()
+                .map_or(false, |level| levels.contains(&level)),
+            RecordFilter::KeywordsAny(mask) => record
+                .keywords()
+                .map_or(false, |keywords| keywords & mask != 0),
+            RecordFilter::KeywordsAll(mask) => record
+                .keywords()
+                .map_or(false, |keywords| keywords & mask == *mask),
+            RecordFilter::Provider(providers) => {
+                let name = record.provider_name().map(|name| normalize_provider(&name));
+                let guid = record.provider_guid().map(|guid| normalize_provider(&guid));
+
+                providers.iter().any(|provider| {
+                    Some(provider) == name.as_ref() || Some(provider) == guid.as_ref()
+                })
+            }
+            RecordFilter::Channel(channels) => record
+                .channel()
+                .map_or(false, |channel| channels.contains(&channel.to_lowercase())),
+            RecordFilter::ActivityId(activity_ids) => record
+                .activity_id()
+                .into_iter()
+                .chain(record.related_activity_id())
+                .any(|id| activity_ids.contains(&normalize_activity_id(&id))),
+            RecordFilter::Sid(sids) => any_value(record.tokens_or_empty(), &mut |value| {
+                let sid = match value {
+                    BinXmlValue::SidType(sid) => sid.to_string(),
+                    BinXmlValue::StringType(s) if s.starts_with("S-1-") => s.to_uppercase(),
+                    _ => return false,
+                };
+
+                sids.iter().any(|pattern| sid_matches(pattern, &sid))
+            }),
+            RecordFilter::Account(accounts) => ACCOUNT_FIELDS.iter().any(|(user, domain)| {
+                let user = match find_data_value(record.tokens_or_empty(), user) {
+                    Some(user) => user.as_cow_str().to_lowercase(),
+                    None => return false,
+                };
+                let domain = find_data_value(record.tokens_or_empty(), domain)
+                    .map(|domain| domain.as_cow_str().to_lowercase());
+
+                accounts
+                    .iter()
+                    .any(|account| match account.split_once('\\') {
+                        Some((account_domain, account_user)) => {
+                            account_user == user && Some(account_domain) == domain.as_deref()
+                        }
+                        None => *account == user,
+                    })
+            }),
+            RecordFilter::Compare { field, op, value } => field.compare(record, *op, value),
+            RecordFilter::Like { field, pattern } => field.matches_string(record, |value| {
+                wildcard_match(pattern, &value.to_lowercase())
+            }),
+            RecordFilter::Regex { field, regex } => {
+                field.matches_string(record, |value| regex.0.is_match(value))
+            }
+            RecordFilter::And(filters) => filters.iter().all(|f| f.matches_deserialized(record)),
+            RecordFilter::Or(filters) => filters.iter().any(|f| f.matches_deserialized(record)),
+            RecordFilter::Not(filter) => !filter.matches_deserialized(record),
+        }
+    }
+
+    /// Evaluates the filter using only the raw substitutions of a record, without deserializing it.
+    /// Returns `None` if the result depends on values which can't be read this way.
+    pub(crate) fn matches_substitutions(&self, raw: &RawSubstitutions) -> Option<bool> {
+        match self {
+            RecordFilter::EventId(ranges) => raw
+                .event_id()
+                .map(|id| ranges.iter().any(|range| range.contains(&id))),
+            RecordFilter::Level(levels) => raw.level().map(|level| levels.contains(&level)),
+            RecordFilter::KeywordsAny(mask) => raw.keywords().map(|keywords| keywords & mask != 0),
+            RecordFilter::KeywordsAll(mask) => {
+                raw.keywords().map(|keywords| keywords & mask == *mask)
+            }
+            RecordFilter::Compare {
+                field: Field::Element(element),
+                op,
+                value: Literal::Number(n),
+            } => raw
+                .element_as_u64(element, false)
+                .map(|value| op.matches(i128::from(value).cmp(n))),
+            RecordFilter::And(filters) => {
+                let mut result = Some(true);
+
+                for filter in filters {
+                    match filter.matches_substitutions(raw) {
+                        Some(false) => return Some(false),
+                        Some(true) => {}
+                        None => result = None,
+                    }
+                }
+
+                result
+            }
+            RecordFilter::Or(filters) => {
+                let mut result = Some(false);
+
+                for filter in filters {
+                    match filter.matches_substitutions(raw) {
+                        Some(true) => return Some(true),
+                        Some(false) => {}
+                        None => result = None,
+                    }
+                }
+
+                result
+            }
+            RecordFilter::Not(filter) => filter.matches_substitutions(raw).map(|m| !m),
+            _ => None,
+        }
+    }
+}
+
+/// A value of a record which can be compared, see `RecordFilter::parse`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Field {
+    /// The timestamp from the record header (the same as `TimeCreated.SystemTime`).
+    TimeCreated,
+    /// The record ID from the record header.
+    EventRecordId,
+    /// The content of the first element with this name (e.g. `EventID` or `Computer`).
+    Element(String),
+    /// An attribute of the first element with this name (e.g. `Execution.ProcessID`).
+    Attribute { element: String, attribute: String },
+    /// The content of the `Data` element with this `Name` (e.g. `EventData.LogonType`).
+    EventData(String),
+    /// The content of any `Data` element, compared until one matches (as with XPath `Data='x'`).
+    AnyEventData,
+}
+
+impl Field {
+    /// Parses a field name, as used by `RecordFilter::parse` (e.g. `EventID`, `EventData.LogonType` or `Execution.ProcessID`).
+    pub fn parse(name: &str) -> Option<Field> {
+        let parts: Vec<&str> = name.split('.').collect();
+
+        if parts.iter().any(|part| part.is_empty()) {
+            return None;
+        }
+
+        let field = match parts.as_slice() {
+            ["TimeCreated"] | ["System", "TimeCreated"] => Field::TimeCreated,
+            ["EventRecordID"] | ["System", "EventRecordID"] => Field::EventRecordId,
+            ["EventData", name] => Field::EventData(name.to_string()),
+            ["System", element] | ["UserData", element] | [element] => {
+                Field::Element(element.to_string())
+            }
+            ["System", element, attribute] | [element, attribute] => Field::Attribute {
+                element: element.to_string(),
+                attribute: attribute.to_string(),
+            },
+            _ => return None,
+        };
+
+        Some(field)
+    }
+
+    /// Returns true if string values compared with this field should be parsed as times.
+    pub fn is_time(&self) -> bool {
+        match self {
+            Field::TimeCreated => true,
+            Field::Attribute { attribute, .. } => attribute == "SystemTime",
+            _ => false,
+        }
+    }
+
+    fn compare(&self, record: &EvtxRecord, op: CompareOp, literal: &Literal) -> bool {
+        let ordering = match self {
+            Field::TimeCreated => match literal {
+                Literal::Time(time) => Some(record.timestamp.cmp(time)),
+                _ => None,
+            },
+            Field::EventRecordId => match literal {
+                Literal::Number(n) => Some(i128::from(record.event_record_id).cmp(n)),
+                _ => None,
+            },
+            Field::Element(element) => {
+                find_value(record.tokens_or_empty(), element, None).and_then(|v| literal.compare(v))
+            }
+            Field::Attribute { element, attribute } => {
+                find_value(record.tokens_or_empty(), element, Some(attribute))
+                    .and_then(|v| literal.compare(v))
+            }
+            Field::EventData(name) => {
+                find_data_value(record.tokens_or_empty(), name).and_then(|v| literal.compare(v))
+            }
+            Field::AnyEventData => {
+                return any_data_value(record.tokens_or_empty(), &mut |v| {
+                    literal
+                        .compare(v)
+                        .map_or(false, |ordering| op.matches(ordering))
+                })
+            }
+        };
+
+        // Missing values, or values of the wrong type, never match.
+        ordering.map_or(false, |ordering| op.matches(ordering))
+    }
+
+    /// Returns true if `predicate` holds for the value of the field in `record` as a string
+    /// (for any of the values of `AnyEventData`).
+    fn matches_string(&self, record: &EvtxRecord, predicate: impl Fn(&str) -> bool) -> bool {
+        let value = match self {
+            Field::TimeCreated => return predicate(&record.timestamp.to_rfc3339()),
+            Field::EventRecordId => return predicate(&record.event_record_id.to_string()),
+            Field::Element(element) => find_value(record.tokens_or_empty(), element, None),
+            Field::Attribute { element, attribute } => {
+                find_value(record.tokens_or_empty(), element, Some(attribute))
+            }
+            Field::EventData(name) => find_data_value(record.tokens_or_empty(), name),
+            Field::AnyEventData => {
+                return any_data_value(record.tokens_or_empty(), &mut |value| {
+                    predicate(&value.as_cow_str())
+                })
+            }
+        };
+
+        // Missing values never match.
+        value.map_or(false, |value| predicate(&value.as_cow_str()))
+    }
+}
+
+/// Matches `text` against `pattern`, where `*` matches any sequence of characters and `?` any single character.
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // The position of the last `*` in the pattern, and the position in the text it was matched at.
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` match one more character.
+                Some((star, star_t)) => {
+                    backtrack = Some((star, star_t + 1));
+                    p = star + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    /// Returns true if `field.cmp(value) == ordering` satisfies the operator.
+    pub fn matches(self, ordering: Ordering) -> bool {
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Ne => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Le => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// A compiled regex, which compares equal to regexes with the same source.
+#[derive(Debug, Clone)]
+pub struct FilterRegex(Regex);
+
+impl FilterRegex {
+    pub fn new(pattern: &str) -> Result<Self> {
+        Regex::new(pattern)
+            .map(FilterRegex)
+            .context(err::InvalidRegex { pattern })
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl PartialEq for FilterRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+/// The right hand side of a comparison.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Number(i128),
+    /// Compared case insensitively (stored in lowercase).
+    String(String),
+    Time(DateTime<Utc>),
+}
+
+impl Literal {
+    /// Compares `value` to the literal, returns `None` if they cannot be compared.
+    fn compare(&self, value: &BinXmlValue) -> Option<Ordering> {
+        match self {
+            Literal::Number(n) => value_as_number(value).map(|v| v.cmp(n)),
+            Literal::String(s) => Some(value.as_cow_str().to_lowercase().as_str().cmp(s)),
+            Literal::Time(time) => match value {
+                BinXmlValue::FileTimeType(v) | BinXmlValue::SysTimeType(v) => Some(v.cmp(time)),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Integer values, and strings holding decimal or hex (`0x`) integers.
+fn value_as_number(value: &BinXmlValue) -> Option<i128> {
+    match value {
+        BinXmlValue::Int8Type(n) => Some(i128::from(*n)),
+        BinXmlValue::Int16Type(n) => Some(i128::from(*n)),
+        BinXmlValue::Int32Type(n) => Some(i128::from(*n)),
+        BinXmlValue::Int64Type(n) => Some(i128::from(*n)),
+        BinXmlValue::StringType(s)
+        | BinXmlValue::AnsiStringType(s)
+        | BinXmlValue::HexInt32Type(s)
+        | BinXmlValue::HexInt64Type(s) => parse_number(s),
+        _ => value.as_u64().map(i128::from),
+    }
+}
+
+/// Parses a decimal or a hex (`0x` prefixed) integer.
+pub(crate) fn parse_number(s: &str) -> Option<i128> {
+    let s = s.trim();
+
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => i128::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// The `EventData` fields holding account names, and the fields holding their domains.
+const ACCOUNT_FIELDS: &[(&str, &str)] = &[
+    ("TargetUserName", "TargetDomainName"),
+    ("SubjectUserName", "SubjectDomainName"),
+];
+
+/// Matches an (uppercase) SID against a SID, or a prefix ending with `-*`.
+fn sid_matches(pattern: &str, sid: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => sid.starts_with(prefix),
+        None => pattern == sid,
+    }
+}
+
+/// Provider GUIDs appear both with and without braces.
+pub(crate) fn normalize_provider(provider: &str) -> String {
+    provider
+        .trim_start_matches('{')
+        .trim_end_matches('}')
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("admin*", "administrator"));
+        assert!(wildcard_match("*admin*", "domain admins"));
+        assert!(wildcard_match("a?c", "abc"));
+        assert!(wildcard_match("*", ""));
+        assert!(wildcard_match("*a*b", "xaxxab"));
+        assert!(!wildcard_match("admin*", "sysadmin"));
+        assert!(!wildcard_match("a?c", "ac"));
+        assert!(!wildcard_match("*a*b", "xaxxabc"));
+    }
+
+    #[test]
+    fn test_sid_matches() {
+        assert!(sid_matches("S-1-5-18", "S-1-5-18"));
+        assert!(!sid_matches("S-1-5-18", "S-1-5-180"));
+        assert!(sid_matches("S-1-5-21-1-2-3-*", "S-1-5-21-1-2-3-1000"));
+        assert!(!sid_matches("S-1-5-21-1-2-3-*", "S-1-5-21-1-2-30-1000"));
+        assert!(!sid_matches("S-1-5-21-1-2-3-*", "S-1-5-21-1-2-3"));
+    }
+}
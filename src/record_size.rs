@@ -0,0 +1,105 @@
+//! Per-record size accounting, for finding the records which dominate the size (and time) of an export.
+use crate::err::Result;
+use crate::EvtxRecord;
+
+use chrono::{DateTime, Utc};
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// The format records are rendered in, when measuring their size or selecting where `ParserSettings::symbolic_names` applies.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderFormat {
+    Xml,
+    Json,
+}
+
+/// The raw and rendered sizes of a single record.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RecordSize {
+    /// The size of the rendered record, in bytes.
+    pub rendered_size: usize,
+    /// The size of the record in the chunk (including it's header), in bytes.
+    pub raw_size: u32,
+    pub event_record_id: u64,
+    pub timestamp: DateTime<Utc>,
+    pub event_id: Option<u16>,
+    pub provider_name: Option<String>,
+}
+
+impl RecordSize {
+    /// Renders `record` using `format` (with the settings of the record), and measures it.
+    pub(crate) fn measure(record: EvtxRecord<'_>, format: RenderFormat) -> Result<Self> {
+        let event_id = record.event_id();
+        let provider_name = record.provider_name().map(|name| name.into_owned());
+        let raw_size = record.data_size;
+
+        let rendered = match format {
+            RenderFormat::Xml => record.into_xml()?,
+            RenderFormat::Json => record.into_json()?,
+        };
+
+        Ok(RecordSize {
+            rendered_size: rendered.data.len(),
+            raw_size,
+            event_record_id: rendered.event_record_id,
+            timestamp: rendered.timestamp,
+            event_id,
+            provider_name,
+        })
+    }
+}
+
+/// Keeps the `n` largest (by rendered size) of `sizes`, largest first.
+pub(crate) fn largest(sizes: impl Iterator<Item = RecordSize>, n: usize) -> Vec<RecordSize> {
+    let mut heap = BinaryHeap::with_capacity(n + 1);
+
+    for size in sizes {
+        heap.push(Reverse(size));
+
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(size)| size)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn size(event_record_id: u64, rendered_size: usize) -> RecordSize {
+        RecordSize {
+            rendered_size,
+            raw_size: 0,
+            event_record_id,
+            timestamp: Utc.ymd(2020, 1, 1).and_hms(0, 0, 0),
+            event_id: None,
+            provider_name: None,
+        }
+    }
+
+    #[test]
+    fn test_keeps_largest() {
+        let sizes = vec![
+            size(1, 10),
+            size(2, 500),
+            size(3, 20),
+            size(4, 500),
+            size(5, 1),
+        ];
+
+        let largest: Vec<u64> = largest(sizes.into_iter(), 3)
+            .iter()
+            .map(|s| s.event_record_id)
+            .collect();
+
+        assert_eq!(largest, vec![4, 2, 3]);
+        assert!(super::largest(vec![size(1, 1)].into_iter(), 0).is_empty());
+    }
+}
@@ -0,0 +1,471 @@
+//! Pseudonymization of records, for sharing them with third parties, see `ParserSettings::redactor`.
+//!
+//! The values of the configured fields (such as `TargetUserName` or `IpAddress`) are replaced by
+//! pseudonyms derived from a secret key (with HMAC-SHA256), so the same value is replaced by the same
+//! pseudonym in every record (and every file redacted with the same key), and the records can still
+//! be correlated:
+//!
+//! | Kind        | Value                                            | Pseudonym                                   |
+//! |-------------|--------------------------------------------------|---------------------------------------------|
+//! | `User`      | `alice`, `CORP\alice`, `WS042$`                  | `user-…`, `domain-…\user-…`, `computer-…$`  |
+//! | `Domain`    | `CORP`                                           | `domain-…`                                  |
+//! | `Sid`       | `S-1-5-21-3623811015-3361044348-30300820-1013`   | `S-1-5-21-…-…-…-…` (RIDs below 1000 are kept) |
+//! | `IpAddress` | `192.168.1.20`, `fe80::1`                        | `10.…`, `fd00:…`                            |
+//! | `Host`      | `WS042.corp.example.com`                         | `host-…`                                    |
+//! | `Other`     | anything                                         | `…` (hexadecimal)                           |
+//!
+//! Values which do not identify anyone are kept: empty values and `-`, well-known accounts and domains
+//! (`SYSTEM`, `NT AUTHORITY`, ...), well-known SIDs, and loopback or unspecified addresses.
+//!
+//! Values are matched by field name: the `Name` of `EventData` fields, the names of the elements of `System`
+//! and `UserData`, and the names of attributes (such as `UserID`). Free text (such as command lines) is not redacted.
+use crate::binxml::value_variant::BinXmlValue;
+use crate::evtx_writer::{EventContent, EventElement};
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// How the values of a field are pseudonymized.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PseudonymKind {
+    User,
+    Domain,
+    Sid,
+    IpAddress,
+    Host,
+    Other,
+}
+
+impl PseudonymKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PseudonymKind::User => "user",
+            PseudonymKind::Domain => "domain",
+            PseudonymKind::Sid => "sid",
+            PseudonymKind::IpAddress => "ip",
+            PseudonymKind::Host => "host",
+            PseudonymKind::Other => "other",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        [
+            PseudonymKind::User,
+            PseudonymKind::Domain,
+            PseudonymKind::Sid,
+            PseudonymKind::IpAddress,
+            PseudonymKind::Host,
+            PseudonymKind::Other,
+        ]
+        .iter()
+        .copied()
+        .find(|kind| kind.as_str().eq_ignore_ascii_case(s))
+    }
+}
+
+/// Fields redacted by `Redactor::with_default_fields`.
+const DEFAULT_FIELDS: &[(&str, PseudonymKind)] = &[
+    ("SubjectUserName", PseudonymKind::User),
+    ("TargetUserName", PseudonymKind::User),
+    ("TargetOutboundUserName", PseudonymKind::User),
+    ("OldTargetUserName", PseudonymKind::User),
+    ("NewTargetUserName", PseudonymKind::User),
+    ("SamAccountName", PseudonymKind::User),
+    ("AccountName", PseudonymKind::User),
+    ("User", PseudonymKind::User),
+    ("SubjectDomainName", PseudonymKind::Domain),
+    ("TargetDomainName", PseudonymKind::Domain),
+    ("TargetOutboundDomainName", PseudonymKind::Domain),
+    ("SubjectUserDomainName", PseudonymKind::Domain),
+    ("DomainName", PseudonymKind::Domain),
+    ("AccountDomain", PseudonymKind::Domain),
+    ("SubjectUserSid", PseudonymKind::Sid),
+    ("TargetUserSid", PseudonymKind::Sid),
+    ("TargetSid", PseudonymKind::Sid),
+    ("MemberSid", PseudonymKind::Sid),
+    ("UserID", PseudonymKind::Sid),
+    ("IpAddress", PseudonymKind::IpAddress),
+    ("ClientAddress", PseudonymKind::IpAddress),
+    ("SourceIp", PseudonymKind::IpAddress),
+    ("DestinationIp", PseudonymKind::IpAddress),
+    ("Computer", PseudonymKind::Host),
+    ("WorkstationName", PseudonymKind::Host),
+    ("TargetServerName", PseudonymKind::Host),
+    ("SourceHostname", PseudonymKind::Host),
+    ("DestinationHostname", PseudonymKind::Host),
+];
+
+/// Account and domain names which are kept as is (compared case insensitively).
+const WELL_KNOWN_NAMES: &[&str] = &[
+    "SYSTEM",
+    "LOCAL SERVICE",
+    "NETWORK SERVICE",
+    "ANONYMOUS LOGON",
+    "NT AUTHORITY",
+    "NT SERVICE",
+    "BUILTIN",
+    "Window Manager",
+    "Font Driver Host",
+];
+
+/// Replaces the values of configured fields by pseudonyms, see the module documentation.
+#[derive(Clone, PartialEq)]
+pub struct Redactor {
+    key: Vec<u8>,
+    fields: HashMap<String, PseudonymKind>,
+}
+
+impl fmt::Debug for Redactor {
+    // The key is not printed.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Redactor")
+            .field("fields", &self.fields)
+            .finish()
+    }
+}
+
+impl Redactor {
+    /// Creates a redactor without any field, using `key` to derive the pseudonyms.
+    pub fn new(key: impl AsRef<[u8]>) -> Self {
+        Redactor {
+            key: key.as_ref().to_vec(),
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Adds the usual user, domain, SID, IP address and host name fields of Security and Sysmon events
+    /// (`TargetUserName`, `IpAddress`, `Computer`, ...).
+    pub fn with_default_fields(mut self) -> Self {
+        for (name, kind) in DEFAULT_FIELDS {
+            self.fields.insert(name.to_string(), *kind);
+        }
+        self
+    }
+
+    /// Adds a field (or replaces the kind of a field).
+    pub fn field(mut self, name: impl Into<String>, kind: PseudonymKind) -> Self {
+        self.fields.insert(name.into(), kind);
+        self
+    }
+
+    /// Returns the kind of the field `name`, if it is redacted.
+    pub fn kind_of(&self, name: &str) -> Option<PseudonymKind> {
+        self.fields.get(name).copied()
+    }
+
+    /// Returns the pseudonym of `value`.
+    pub fn pseudonym(&self, kind: PseudonymKind, value: &str) -> String {
+        let trimmed = value.trim();
+        if trimmed.is_empty() || trimmed == "-" {
+            return value.to_string();
+        }
+
+        match kind {
+            PseudonymKind::User => match trimmed.split_once('\\') {
+                Some((domain, user)) => format!(
+                    "{}\\{}",
+                    self.pseudonym(PseudonymKind::Domain, domain),
+                    self.pseudonym(PseudonymKind::User, user)
+                ),
+                None if is_well_known_name(trimmed) => value.to_string(),
+                None => match trimmed.strip_suffix('$') {
+                    Some(computer) => format!("computer-{}$", self.hex(kind, computer)),
+                    None => format!("user-{}", self.hex(kind, trimmed)),
+                },
+            },
+            PseudonymKind::Domain if is_well_known_name(trimmed) => value.to_string(),
+            PseudonymKind::Domain => format!("domain-{}", self.hex(kind, trimmed)),
+            PseudonymKind::Sid => self.sid_pseudonym(trimmed),
+            PseudonymKind::IpAddress => self.ip_pseudonym(trimmed),
+            PseudonymKind::Host if trimmed.eq_ignore_ascii_case("localhost") => value.to_string(),
+            PseudonymKind::Host => format!("host-{}", self.hex(kind, trimmed)),
+            PseudonymKind::Other => self.hex(kind, trimmed),
+        }
+    }
+
+    /// Redacts the JSON output of a record: every string value of an object, under the name of a configured field.
+    pub fn redact_json(&self, value: &mut Value) {
+        match value {
+            Value::Object(object) => {
+                for (name, value) in object.iter_mut() {
+                    match (self.kind_of(name), value) {
+                        (Some(kind), Value::String(s)) => *s = self.pseudonym(kind, s),
+                        (_, value) => self.redact_json(value),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact_json(value)),
+            _ => {}
+        }
+    }
+
+    /// Redacts an element tree: the values of the configured attributes, and of the elements named after
+    /// a configured field (or `Data` elements with the `Name` of a configured field).
+    /// Redacted values are replaced by strings.
+    pub fn redact_element(&self, element: &mut EventElement) {
+        for (name, value) in element.attributes.iter_mut() {
+            if let Some(kind) = self.kind_of(name) {
+                self.redact_value(kind, value);
+            }
+        }
+
+        let field_name = match element.name.as_str() {
+            "Data" => element
+                .attributes
+                .iter()
+                .find(|(name, _)| name == "Name")
+                .map(|(_, value)| value.as_cow_str().into_owned()),
+            name => Some(name.to_string()),
+        };
+        let kind = field_name.and_then(|name| self.kind_of(&name));
+
+        for content in element.children.iter_mut() {
+            match content {
+                EventContent::Element(child) => self.redact_element(child),
+                EventContent::Value(value) => {
+                    if let Some(kind) = kind {
+                        self.redact_value(kind, value)
+                    }
+                }
+            }
+        }
+    }
+
+    fn redact_value(&self, kind: PseudonymKind, value: &mut BinXmlValue<'static>) {
+        if let BinXmlValue::NullType = value {
+            return;
+        }
+
+        let pseudonym = self.pseudonym(kind, &value.as_cow_str());
+        *value = BinXmlValue::StringType(Cow::Owned(pseudonym));
+    }
+
+    /// The HMAC of a value (case insensitive), for the given kind.
+    fn digest(&self, kind: PseudonymKind, value: &str) -> [u8; 32] {
+        let mut message = kind.as_str().as_bytes().to_vec();
+        message.push(0);
+        message.extend_from_slice(value.to_lowercase().as_bytes());
+
+        hmac_sha256(&self.key, &message)
+    }
+
+    fn hex(&self, kind: PseudonymKind, value: &str) -> String {
+        self.digest(kind, value)[..6]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Domain SIDs (`S-1-5-21-A-B-C-RID`) get a pseudonymous domain (the same for all the SIDs of a domain),
+    /// and RID (unless it is below 1000, the RIDs of built-in accounts and groups).
+    /// Other SIDs are well-known, and kept.
+    fn sid_pseudonym(&self, sid: &str) -> String {
+        let sid = sid.to_uppercase();
+        let parts: Vec<&str> = sid.split('-').collect();
+
+        if !sid.starts_with("S-1-5-21-") || parts.len() < 7 {
+            return sid;
+        }
+
+        let domain = self.digest(PseudonymKind::Sid, &parts[..7].join("-"));
+        let word =
+            |i: usize| u32::from_le_bytes([domain[i], domain[i + 1], domain[i + 2], domain[i + 3]]);
+        let mut pseudonym = format!("S-1-5-21-{}-{}-{}", word(0), word(4), word(8));
+
+        if let Some(rid) = parts.get(7) {
+            let rid = match rid.parse::<u32>() {
+                Ok(rid) if rid < 1000 => rid,
+                _ => {
+                    let digest = self.digest(PseudonymKind::Sid, &sid);
+                    1000 + u32::from_le_bytes([digest[0], digest[1], digest[2], digest[3]])
+                        % 1_000_000
+                }
+            };
+            pseudonym.push_str(&format!("-{}", rid));
+        }
+
+        pseudonym
+    }
+
+    /// IPv4 addresses are mapped to `10.0.0.0/8`, IPv6 addresses to `fd00::/8`.
+    /// Loopback and unspecified addresses are kept, values which are not addresses are pseudonymized as hosts.
+    fn ip_pseudonym(&self, value: &str) -> String {
+        let address = match value.parse::<IpAddr>() {
+            Ok(IpAddr::V6(v6)) => match v6.to_ipv4_mapped() {
+                Some(v4) => IpAddr::V4(v4),
+                None => IpAddr::V6(v6),
+            },
+            Ok(address) => address,
+            Err(_) => return self.pseudonym(PseudonymKind::Host, value),
+        };
+
+        if address.is_loopback() || address.is_unspecified() {
+            return value.to_string();
+        }
+
+        let digest = self.digest(PseudonymKind::IpAddress, &address.to_string());
+        match address {
+            IpAddr::V4(_) => Ipv4Addr::new(10, digest[0], digest[1], digest[2]).to_string(),
+            IpAddr::V6(_) => {
+                let mut octets = [0; 16];
+                octets[0] = 0xfd;
+                octets[1..].copy_from_slice(&digest[..15]);
+                Ipv6Addr::from(octets).to_string()
+            }
+        }
+    }
+}
+
+fn is_well_known_name(name: &str) -> bool {
+    WELL_KNOWN_NAMES
+        .iter()
+        .any(|known| known.eq_ignore_ascii_case(name))
+}
+
+/// HMAC-SHA256 (RFC 2104).
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+
+    let mut block = [0; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+
+    let pad = |byte: u8| block.iter().map(|b| b ^ byte).collect::<Vec<u8>>();
+
+    let inner = Sha256::new()
+        .chain_update(pad(0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(pad(0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231, test case 2.
+        let digest = hmac_sha256(b"Jefe", b"what do ya want for nothing?");
+        let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(
+            hex,
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_pseudonyms() {
+        let redactor = Redactor::new("secret");
+        let other = Redactor::new("other secret");
+        let user = |value| redactor.pseudonym(PseudonymKind::User, value);
+
+        assert_eq!(user("alice"), user("ALICE"));
+        assert_ne!(user("alice"), other.pseudonym(PseudonymKind::User, "alice"));
+        assert_ne!(user("alice"), user("bob"));
+        assert!(user("alice").starts_with("user-"));
+        assert_eq!(
+            user("CORP\\alice"),
+            format!(
+                "{}\\{}",
+                redactor.pseudonym(PseudonymKind::Domain, "corp"),
+                user("alice")
+            )
+        );
+        assert!(user("WS042$").starts_with("computer-") && user("WS042$").ends_with('$'));
+        assert_eq!(user("SYSTEM"), "SYSTEM");
+        assert_eq!(user("-"), "-");
+
+        let sid = |value| redactor.pseudonym(PseudonymKind::Sid, value);
+        assert_eq!(sid("S-1-5-18"), "S-1-5-18");
+        let admin = sid("S-1-5-21-3623811015-3361044348-30300820-500");
+        let alice = sid("S-1-5-21-3623811015-3361044348-30300820-1013");
+        assert!(admin.ends_with("-500"));
+        assert_eq!(admin[..admin.len() - 4], alice[..admin.len() - 4]);
+        assert_ne!(alice, "S-1-5-21-3623811015-3361044348-30300820-1013");
+
+        let ip = |value| redactor.pseudonym(PseudonymKind::IpAddress, value);
+        assert!(ip("192.168.1.20").starts_with("10."));
+        assert_eq!(ip("192.168.1.20"), ip("::ffff:192.168.1.20"));
+        assert!(ip("fe80::1").starts_with("fd"));
+        assert_eq!(ip("127.0.0.1"), "127.0.0.1");
+        assert_eq!(ip("::1"), "::1");
+    }
+
+    #[test]
+    fn test_redact_json_and_elements() {
+        let redactor = Redactor::new("secret").with_default_fields();
+
+        let mut record = json!({
+            "Event": {
+                "System": {"Computer": "WS042", "Security": {"#attributes": {"UserID": "S-1-5-18"}}},
+                "EventData": {"TargetUserName": "alice", "IpAddress": "10.1.2.3", "LogonType": 3}
+            }
+        });
+        redactor.redact_json(&mut record);
+
+        assert_eq!(
+            record["Event"]["System"]["Computer"],
+            redactor.pseudonym(PseudonymKind::Host, "WS042")
+        );
+        assert_eq!(
+            record["Event"]["System"]["Security"]["#attributes"]["UserID"],
+            "S-1-5-18"
+        );
+        assert_eq!(
+            record["Event"]["EventData"]["TargetUserName"],
+            redactor.pseudonym(PseudonymKind::User, "alice")
+        );
+        assert_eq!(record["Event"]["EventData"]["LogonType"], 3);
+
+        let mut event = EventElement::new("Event")
+            .child(EventElement::new("System").child(EventElement::new("Computer").text("WS042")))
+            .child(
+                EventElement::new("EventData")
+                    .child(
+                        EventElement::new("Data")
+                            .attribute("Name", BinXmlValue::StringType("TargetUserName".into()))
+                            .text("alice"),
+                    )
+                    .child(
+                        EventElement::new("Data")
+                            .attribute("Name", BinXmlValue::StringType("LogonType".into()))
+                            .value(BinXmlValue::UInt32Type(3)),
+                    ),
+            );
+        redactor.redact_element(&mut event);
+
+        let values: Vec<String> = event
+            .find("EventData")
+            .unwrap()
+            .elements()
+            .chain(event.find("System").unwrap().elements())
+            .flat_map(|element| &element.children)
+            .filter_map(|content| match content {
+                EventContent::Value(value) => Some(value.as_cow_str().into_owned()),
+                EventContent::Element(_) => None,
+            })
+            .collect();
+        assert_eq!(
+            values,
+            [
+                redactor.pseudonym(PseudonymKind::User, "alice"),
+                "3".to_string(),
+                redactor.pseudonym(PseudonymKind::Host, "WS042"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,379 @@
+//! Repair of files which were not closed properly (such as after an abrupt shutdown), see `EvtxParser::repair`.
+//!
+//! When a log is not closed properly, the headers may not reflect the last records written:
+//! the free space offset and the last record of a chunk lag behind it's records,
+//! the checksums do not match, the dirty flags are set and the chunk count of the file header is too small.
+use crate::err::{self, Result};
+use crate::evtx_chunk::{
+    read_plausible_record, EvtxChunkData, EvtxChunkHeader, EVTX_CHUNK_HEADER_SIZE,
+};
+use crate::evtx_parser::EVTX_FILE_HEADER_SIZE;
+use crate::evtx_record::EvtxRecordHeader;
+use crate::file_report::ChunkFailure;
+
+use log::warn;
+
+use std::convert::TryInto;
+use std::fmt;
+use std::io::Cursor;
+
+/// The dirty flag, of both the file header and the chunk headers.
+const DIRTY_FLAG: u32 = 0x1;
+
+/// The outcome of `EvtxParser::repair`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RepairReport {
+    /// The number of chunks according to the file header of the damaged file.
+    pub header_chunk_count: u16,
+    /// The number of chunks written to the repaired file.
+    pub chunk_count: u16,
+    /// `true` if the file header had an invalid checksum (or there was no file header).
+    pub fixed_header_checksum: bool,
+    /// `true` if the dirty flag of the file header was set.
+    pub cleared_dirty_flag: bool,
+    /// The chunks written to the repaired file, in order.
+    pub chunks: Vec<ChunkRepair>,
+    /// The chunks which could not be repaired, and were left out of the repaired file.
+    pub dropped_chunks: Vec<ChunkFailure>,
+}
+
+/// What was fixed in a chunk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkRepair {
+    /// Number of the chunk in the damaged file.
+    pub chunk_number: u16,
+    /// The number of records in the repaired chunk.
+    pub records: u64,
+    /// `true` if the header could not be parsed, and was reconstructed (see `EvtxChunkData::reconstruct`).
+    pub header_reconstructed: bool,
+    /// `true` if the bounds of the records (the record numbers and IDs, the offset of the last record
+    /// and the free space offset) did not match the records found in the chunk.
+    pub fixed_record_bounds: bool,
+    pub fixed_header_checksum: bool,
+    pub fixed_data_checksum: bool,
+    pub cleared_dirty_flag: bool,
+}
+
+impl ChunkRepair {
+    /// Returns `true` if anything was fixed.
+    pub fn is_repaired(&self) -> bool {
+        self.header_reconstructed
+            || self.fixed_record_bounds
+            || self.fixed_header_checksum
+            || self.fixed_data_checksum
+            || self.cleared_dirty_flag
+    }
+}
+
+impl RepairReport {
+    /// Returns `true` if anything was fixed (or dropped).
+    pub fn is_repaired(&self) -> bool {
+        self.chunk_count != self.header_chunk_count
+            || self.fixed_header_checksum
+            || self.cleared_dirty_flag
+            || self.chunks.iter().any(ChunkRepair::is_repaired)
+            || !self.dropped_chunks.is_empty()
+    }
+
+    /// The number of records in the repaired file.
+    pub fn record_count(&self) -> u64 {
+        self.chunks.iter().map(|chunk| chunk.records).sum()
+    }
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut fixes = vec![];
+        if self.chunk_count != self.header_chunk_count {
+            fixes.push(format!(
+                "chunk count {} (was {})",
+                self.chunk_count, self.header_chunk_count
+            ));
+        }
+        if self.fixed_header_checksum {
+            fixes.push("checksum".to_string());
+        }
+        if self.cleared_dirty_flag {
+            fixes.push("dirty flag cleared".to_string());
+        }
+        if !fixes.is_empty() {
+            writeln!(f, "File header: {}", fixes.join(", "))?;
+        }
+
+        for chunk in self.chunks.iter().filter(|chunk| chunk.is_repaired()) {
+            let fixes = [
+                (chunk.header_reconstructed, "header reconstructed"),
+                (chunk.fixed_record_bounds, "record bounds"),
+                (chunk.fixed_header_checksum, "header checksum"),
+                (chunk.fixed_data_checksum, "data checksum"),
+                (chunk.cleared_dirty_flag, "dirty flag cleared"),
+            ];
+            let fixes: Vec<&str> = fixes
+                .iter()
+                .filter(|(fixed, _)| *fixed)
+                .map(|(_, fix)| *fix)
+                .collect();
+
+            writeln!(
+                f,
+                "Chunk {} ({} records): {}",
+                chunk.chunk_number,
+                chunk.records,
+                fixes.join(", ")
+            )?;
+        }
+
+        for chunk in &self.dropped_chunks {
+            writeln!(
+                f,
+                "Chunk {}: dropped, {}",
+                chunk.chunk_number, chunk.message
+            )?;
+        }
+
+        write!(
+            f,
+            "{}: {} chunks, {} records",
+            if self.is_repaired() {
+                "REPAIRED"
+            } else {
+                "NOTHING TO REPAIR"
+            },
+            self.chunk_count,
+            self.record_count()
+        )
+    }
+}
+
+/// Repairs the (full, `EVTX_CHUNK_SIZE` sized) `data` of a chunk in place.
+///
+/// The records are found by following the chain of record headers from the start of the chunk,
+/// past the free space offset of the header (which might lag behind the records written),
+/// for as long as the record IDs are consecutive.
+pub(crate) fn repair_chunk(chunk_number: u16, data: &mut Vec<u8>) -> Result<ChunkRepair> {
+    let mut repair = ChunkRepair {
+        chunk_number,
+        records: 0,
+        header_reconstructed: false,
+        fixed_record_bounds: false,
+        fixed_header_checksum: false,
+        fixed_data_checksum: false,
+        cleared_dirty_flag: false,
+    };
+
+    let header = match EvtxChunkHeader::from_reader(&mut Cursor::new(data.as_slice())) {
+        Ok(header) => header,
+        Err(e) => {
+            let chunk = EvtxChunkData::reconstruct(std::mem::take(data)).ok_or(e)?;
+            *data = chunk.data;
+            repair.header_reconstructed = true;
+            EvtxChunkHeader::from_reader(&mut Cursor::new(data.as_slice()))?
+        }
+    };
+
+    let chunk = EvtxChunkData::new(std::mem::take(data), false)?;
+    repair.fixed_header_checksum = !chunk.validate_header_checksum();
+    repair.fixed_data_checksum = !chunk.validate_data_checksum();
+    *data = chunk.data;
+
+    let records = record_chain(data);
+    let (_, first) = match records.first() {
+        Some(record) => record,
+        None => {
+            return err::UnrepairableChunk {
+                chunk_number,
+                message: "no records were found",
+            }
+            .fail()
+        }
+    };
+    let (last_offset, last) = records.last().expect("not empty");
+
+    repair.records = records.len() as u64;
+    let free_space_offset = last_offset + last.data_size as usize;
+    let last_event_record_number = header.first_event_record_number + repair.records - 1;
+
+    repair.fixed_record_bounds = header.first_event_record_id != first.event_record_id
+        || header.last_event_record_id != last.event_record_id
+        || header.last_event_record_number != last_event_record_number
+        || header.last_event_record_data_offset as usize != *last_offset
+        || header.free_space_offset as usize != free_space_offset;
+    repair.cleared_dirty_flag = header.flags & DIRTY_FLAG != 0;
+
+    let events_checksum = crc32fast::hash(&data[EVTX_CHUNK_HEADER_SIZE..free_space_offset]);
+
+    let header_data = &mut data[..EVTX_CHUNK_HEADER_SIZE];
+    header_data[16..24].copy_from_slice(&last_event_record_number.to_le_bytes());
+    header_data[24..32].copy_from_slice(&first.event_record_id.to_le_bytes());
+    header_data[32..40].copy_from_slice(&last.event_record_id.to_le_bytes());
+    header_data[44..48].copy_from_slice(&(*last_offset as u32).to_le_bytes());
+    header_data[48..52].copy_from_slice(&(free_space_offset as u32).to_le_bytes());
+    header_data[52..56].copy_from_slice(&events_checksum.to_le_bytes());
+    header_data[120..124].copy_from_slice(&(header.flags & !DIRTY_FLAG).to_le_bytes());
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&header_data[..120]);
+    hasher.update(&header_data[128..]);
+    header_data[124..128].copy_from_slice(&hasher.finalize().to_le_bytes());
+
+    if repair.is_repaired() {
+        warn!(
+            "Repaired chunk {} with records {} to {}",
+            chunk_number, first.event_record_id, last.event_record_id
+        );
+    }
+
+    Ok(repair)
+}
+
+/// Follows the chain of records from the start of the chunk, while their IDs are consecutive.
+pub(crate) fn record_chain(data: &[u8]) -> Vec<(usize, EvtxRecordHeader)> {
+    let mut offset = EVTX_CHUNK_HEADER_SIZE;
+    let mut records: Vec<(usize, EvtxRecordHeader)> = vec![];
+
+    while let Ok(record) = read_plausible_record(data, offset) {
+        if let Some((_, previous)) = records.last() {
+            if record.event_record_id != previous.event_record_id + 1 {
+                break;
+            }
+        }
+
+        let size = record.data_size as usize;
+        records.push((offset, record));
+        offset += size;
+    }
+
+    records
+}
+
+/// Updates the file header (the whole header block) of the repaired file, whose chunks are `chunks`
+/// (as `(first_event_record_id, last_event_record_id)`), returning whether the dirty flag was set.
+pub(crate) fn repair_file_header(header: &mut [u8], chunks: &[(u64, u64)]) -> bool {
+    debug_assert_eq!(header.len(), EVTX_FILE_HEADER_SIZE);
+
+    // The oldest chunk, and the chunk holding the latest records.
+    let first_chunk_number = position_of(chunks, |a, b| a.0 < b.0);
+    let last_chunk_number = position_of(chunks, |a, b| a.1 > b.1);
+    let next_record_id = chunks.iter().map(|(_, last)| last + 1).max().unwrap_or(1);
+    let flags = u32::from_le_bytes(header[120..124].try_into().expect("4 bytes"));
+
+    header[8..16].copy_from_slice(&(first_chunk_number as u64).to_le_bytes());
+    header[16..24].copy_from_slice(&(last_chunk_number as u64).to_le_bytes());
+    header[24..32].copy_from_slice(&next_record_id.to_le_bytes());
+    header[42..44].copy_from_slice(&(chunks.len() as u16).to_le_bytes());
+    header[120..124].copy_from_slice(&(flags & !DIRTY_FLAG).to_le_bytes());
+    let checksum = crc32fast::hash(&header[..120]);
+    header[124..128].copy_from_slice(&checksum.to_le_bytes());
+
+    flags & DIRTY_FLAG != 0
+}
+
+/// The index of the chunk which is `before` all others.
+fn position_of(chunks: &[(u64, u64)], before: impl Fn(&(u64, u64), &(u64, u64)) -> bool) -> usize {
+    let mut position = 0;
+    for (i, chunk) in chunks.iter().enumerate() {
+        if before(chunk, &chunks[position]) {
+            position = i;
+        }
+    }
+    position
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evtx_parser::EVTX_CHUNK_SIZE;
+    use crate::EvtxParser;
+
+    fn sample() -> Vec<u8> {
+        include_bytes!("../samples/security.evtx").to_vec()
+    }
+
+    fn repair(data: Vec<u8>) -> (RepairReport, Vec<u8>) {
+        let mut repaired = Cursor::new(vec![]);
+        let report = EvtxParser::from_buffer(data)
+            .unwrap()
+            .repair(&mut repaired)
+            .unwrap();
+
+        (report, repaired.into_inner())
+    }
+
+    /// Makes the second chunk look like it was not closed properly: it's header lags behind it's last record,
+    /// and the file header lacks the last chunk.
+    fn damage(data: &mut [u8]) -> (u16, u64) {
+        let chunk = &mut data[EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE..][..EVTX_CHUNK_SIZE];
+        let header = EvtxChunkHeader::from_reader(&mut Cursor::new(&chunk[..])).unwrap();
+        let records = record_chain(chunk);
+        let (previous_offset, _) = records[records.len() - 2];
+        let (last_offset, last) = records.last().unwrap().clone();
+
+        chunk[16..24].copy_from_slice(&(header.last_event_record_number - 1).to_le_bytes());
+        chunk[32..40].copy_from_slice(&(last.event_record_id - 1).to_le_bytes());
+        chunk[44..48].copy_from_slice(&(previous_offset as u32).to_le_bytes());
+        chunk[48..52].copy_from_slice(&(last_offset as u32).to_le_bytes());
+        chunk[120] |= DIRTY_FLAG as u8;
+
+        let chunk_count = u16::from_le_bytes([data[42], data[43]]);
+        data[42..44].copy_from_slice(&(chunk_count - 1).to_le_bytes());
+        data[120] |= DIRTY_FLAG as u8;
+
+        (chunk_count, last.event_record_id)
+    }
+
+    fn record_ids(data: Vec<u8>) -> Vec<u64> {
+        EvtxParser::from_buffer(data)
+            .unwrap()
+            .records()
+            .map(|record| record.unwrap().event_record_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_repairs_a_file_which_was_not_closed() {
+        // The sample was copied from a live system, so it's flags are dirty.
+        let (_, original) = repair(sample());
+        let mut damaged = original.clone();
+        let (chunk_count, lost_record_id) = damage(&mut damaged);
+
+        assert!(!record_ids(damaged.clone()).contains(&lost_record_id));
+
+        let (report, repaired) = repair(damaged);
+
+        assert_eq!(report.chunk_count, chunk_count);
+        assert_eq!(report.header_chunk_count, chunk_count - 1);
+        assert!(report.cleared_dirty_flag);
+        assert!(report.fixed_header_checksum);
+        assert!(report.dropped_chunks.is_empty());
+
+        let repaired_chunks: Vec<_> = report
+            .chunks
+            .iter()
+            .filter(|chunk| chunk.is_repaired())
+            .collect();
+        assert_eq!(repaired_chunks.len(), 1);
+        assert_eq!(repaired_chunks[0].chunk_number, 1);
+        assert!(repaired_chunks[0].fixed_record_bounds);
+        assert!(repaired_chunks[0].fixed_header_checksum);
+        assert!(repaired_chunks[0].fixed_data_checksum);
+        assert!(repaired_chunks[0].cleared_dirty_flag);
+
+        assert_eq!(record_ids(repaired.clone()), record_ids(original));
+        assert!(EvtxParser::from_buffer(repaired)
+            .unwrap()
+            .validate()
+            .unwrap()
+            .is_valid());
+    }
+
+    #[test]
+    fn test_repaired_files_are_not_repaired_again() {
+        let (report, repaired) = repair(sample());
+        assert!(report.cleared_dirty_flag);
+        assert!(report.chunks.iter().all(|chunk| chunk.cleared_dirty_flag));
+
+        let (report, repaired_again) = repair(repaired.clone());
+        assert!(!report.is_repaired(), "{}", report);
+        assert_eq!(repaired_again, repaired);
+    }
+}
@@ -0,0 +1,251 @@
+//! Security identifiers, as found in `SidType` values and the `UserID` of records.
+//! https://github.com/libyal/libfwnt/wiki/Security-Descriptor#security-identifier
+use crate::err::{self, Error};
+use crate::sid_names::well_known_sid_name;
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
+use serde::{de, ser};
+
+use std::fmt;
+use std::io::{self, Cursor, Read};
+use std::str::FromStr;
+
+/// The maximum number of sub-authorities of a SID.
+const MAX_SUB_AUTHORITIES: usize = 15;
+
+/// A SID, displayed (and serialized) as `S-1-5-21-3623811015-3361044348-30300820-1013`.
+///
+/// ```
+/// # use evtx::Sid;
+/// let raw_sid = [0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x12, 0x00, 0x00, 0x00];
+///
+/// let sid = Sid::from_buffer(&raw_sid).unwrap();
+///
+/// assert_eq!(sid.to_string(), "S-1-5-18");
+/// assert_eq!("S-1-5-18".parse::<Sid>().unwrap(), sid);
+/// assert_eq!(sid.to_bytes(), raw_sid);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sid {
+    revision: u8,
+    /// The 48 bits identifier authority.
+    authority: u64,
+    sub_authorities: Vec<u32>,
+}
+
+impl Sid {
+    /// Creates a new SID from it's components, `authority` is truncated to 48 bits.
+    pub fn new(revision: u8, authority: u64, sub_authorities: Vec<u32>) -> Sid {
+        Sid {
+            revision,
+            authority: authority & 0xFFFF_FFFF_FFFF,
+            sub_authorities,
+        }
+    }
+
+    /// Reads a SID from the start of a buffer.
+    pub fn from_buffer(buffer: &[u8]) -> io::Result<Sid> {
+        Self::from_reader(&mut Cursor::new(buffer))
+    }
+
+    /// Reads a SID from a `Read` instance.
+    pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<Sid> {
+        let revision = reader.read_u8()?;
+        let sub_authority_count = reader.read_u8()?;
+        let authority = reader.read_u48::<BigEndian>()?;
+
+        let sub_authorities = (0..sub_authority_count)
+            .map(|_| reader.read_u32::<LittleEndian>())
+            .collect::<io::Result<Vec<u32>>>()?;
+
+        Ok(Sid {
+            revision,
+            authority,
+            sub_authorities,
+        })
+    }
+
+    /// Returns the binary representation of the SID (as stored on disk).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + 4 * self.sub_authorities.len());
+        bytes.push(self.revision);
+        bytes.push(self.sub_authorities.len() as u8);
+        bytes.extend_from_slice(&self.authority.to_be_bytes()[2..]);
+        for sub_authority in &self.sub_authorities {
+            bytes.extend_from_slice(&sub_authority.to_le_bytes());
+        }
+        bytes
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    pub fn authority(&self) -> u64 {
+        self.authority
+    }
+
+    pub fn sub_authorities(&self) -> &[u32] {
+        &self.sub_authorities
+    }
+
+    /// Returns the last sub-authority (the relative identifier of accounts), if any.
+    pub fn rid(&self) -> Option<u32> {
+        self.sub_authorities.last().copied()
+    }
+
+    /// Returns the name of the SID (such as `NT AUTHORITY\SYSTEM`), if it is a well-known SID.
+    pub fn well_known_name(&self) -> Option<&'static str> {
+        well_known_sid_name(&self.to_string())
+    }
+}
+
+impl fmt::Display for Sid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "S-{}-", self.revision)?;
+
+        // Like `ConvertSidToStringSid`, authorities which do not fit 32 bits are written in hex.
+        if self.authority >> 32 == 0 {
+            write!(f, "{}", self.authority)?;
+        } else {
+            write!(f, "0x{:012X}", self.authority)?;
+        }
+
+        for sub_authority in &self.sub_authorities {
+            write!(f, "-{}", sub_authority)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Parses `S-<revision>-<authority>(-<sub-authority>)*` (the `S` is case insensitive).
+impl FromStr for Sid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || err::InvalidIdentifier {
+            value: s,
+            kind: "SID",
+        };
+
+        let mut parts = s.split('-');
+        if !parts.next().map_or(false, |p| p.eq_ignore_ascii_case("S")) {
+            return invalid().fail();
+        }
+
+        let revision = match parts.next().and_then(decimal::<u8>) {
+            Some(revision) => revision,
+            None => return invalid().fail(),
+        };
+
+        let authority = match parts.next() {
+            Some(p) if p.starts_with("0x") || p.starts_with("0X") => {
+                u64::from_str_radix(&p[2..], 16).ok()
+            }
+            Some(p) => decimal::<u64>(p),
+            None => None,
+        };
+        let authority = match authority {
+            Some(authority) if authority >> 48 == 0 => authority,
+            _ => return invalid().fail(),
+        };
+
+        let sub_authorities = match parts.map(decimal::<u32>).collect::<Option<Vec<u32>>>() {
+            Some(sub_authorities) if sub_authorities.len() <= MAX_SUB_AUTHORITIES => {
+                sub_authorities
+            }
+            _ => return invalid().fail(),
+        };
+
+        Ok(Sid::new(revision, authority, sub_authorities))
+    }
+}
+
+/// Parses a number made only of decimal digits (no sign).
+fn decimal<N: FromStr>(s: &str) -> Option<N> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    s.parse().ok()
+}
+
+impl ser::Serialize for Sid {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Sid {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_sids() {
+        let sid: Sid = "S-1-5-21-3623811015-3361044348-30300820-1013"
+            .parse()
+            .unwrap();
+        assert_eq!(sid.revision(), 1);
+        assert_eq!(sid.authority(), 5);
+        assert_eq!(
+            sid.sub_authorities(),
+            &[21, 3_623_811_015, 3_361_044_348, 30_300_820, 1013]
+        );
+        assert_eq!(sid.rid(), Some(1013));
+        assert_eq!(Sid::from_buffer(&sid.to_bytes()).unwrap(), sid);
+
+        let sid: Sid = "s-1-0x123456789ABC-1".parse().unwrap();
+        assert_eq!(sid.authority(), 0x1234_5678_9ABC);
+        assert_eq!(sid.to_string(), "S-1-0x123456789ABC-1");
+        assert_eq!(Sid::from_buffer(&sid.to_bytes()).unwrap(), sid);
+
+        assert!("S-1-1".parse::<Sid>().unwrap().sub_authorities().is_empty());
+
+        for invalid in &[
+            "",
+            "S",
+            "S-1",
+            "X-1-5-18",
+            "S-1-5-",
+            "S-1-5--18",
+            "S-1-5-+18",
+            "S-1-5-4294967296",
+            "S-1-0x1000000000000-1",
+            "S-1-5-1-2-3-4-5-6-7-8-9-10-11-12-13-14-15-16",
+        ] {
+            assert!(invalid.parse::<Sid>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_serde_roundtrip() {
+        let sid: Sid = "S-1-5-32-544".parse().unwrap();
+
+        let json = serde_json::to_string(&sid).unwrap();
+        assert_eq!(json, "\"S-1-5-32-544\"");
+        assert_eq!(serde_json::from_str::<Sid>(&json).unwrap(), sid);
+    }
+
+    #[test]
+    fn test_sids_can_be_used_as_keys() {
+        let mut names = std::collections::HashMap::new();
+        names.insert("S-1-5-18".parse::<Sid>().unwrap(), "SYSTEM");
+
+        let sid = Sid::from_buffer(&[1, 1, 0, 0, 0, 0, 0, 5, 0x12, 0, 0, 0]).unwrap();
+        assert_eq!(names.get(&sid), Some(&"SYSTEM"));
+    }
+}
@@ -0,0 +1,165 @@
+//! Names of well-known SIDs (`NT AUTHORITY\SYSTEM`, `BUILTIN` groups, capability SIDs, ...).
+//!
+//! With `ParserSettings::resolve_sids`, the names of the SIDs of a record are added to it's JSON output,
+//! in an `Event.ResolvedSids` object mapping every SID to it's name.
+//! SIDs of the `sids` table of the `Enrichment` of the settings take precedence over the well-known names.
+/// Returns the name of the well-known SID `sid` (in it's `S-1-...` string form).
+///
+/// Accounts and groups relative to a domain (`S-1-5-21-<domain>-<RID>`) are resolved by their RID,
+/// without the name of the domain.
+pub fn well_known_sid_name(sid: &str) -> Option<&'static str> {
+    if let Ok(index) = WELL_KNOWN_SIDS.binary_search_by_key(&sid, |(sid, _)| *sid) {
+        return Some(WELL_KNOWN_SIDS[index].1);
+    }
+
+    let domain_relative = sid.strip_prefix("S-1-5-21-")?;
+    let mut parts = domain_relative.split('-');
+    let rid = parts.next_back()?;
+    // The domain identifier is made of 3 sub-authorities.
+    if parts.count() != 3 {
+        return None;
+    }
+
+    let name = match rid {
+        "500" => "Administrator",
+        "501" => "Guest",
+        "502" => "krbtgt",
+        "512" => "Domain Admins",
+        "513" => "Domain Users",
+        "514" => "Domain Guests",
+        "515" => "Domain Computers",
+        "516" => "Domain Controllers",
+        "517" => "Cert Publishers",
+        "518" => "Schema Admins",
+        "519" => "Enterprise Admins",
+        "520" => "Group Policy Creator Owners",
+        "521" => "Read-only Domain Controllers",
+        "525" => "Protected Users",
+        "553" => "RAS and IAS Servers",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Sorted by SID (as strings).
+const WELL_KNOWN_SIDS: &[(&str, &str)] = &[
+    ("S-1-0-0", "NULL SID"),
+    ("S-1-1-0", "Everyone"),
+    ("S-1-15-2-1", "APPLICATION PACKAGE AUTHORITY\\ALL APPLICATION PACKAGES"),
+    (
+        "S-1-15-2-2",
+        "APPLICATION PACKAGE AUTHORITY\\ALL RESTRICTED APPLICATION PACKAGES",
+    ),
+    ("S-1-15-3-1", "APPLICATION PACKAGE AUTHORITY\\Your Internet connection"),
+    ("S-1-15-3-10", "APPLICATION PACKAGE AUTHORITY\\Removable storage"),
+    ("S-1-15-3-11", "APPLICATION PACKAGE AUTHORITY\\Your Appointments"),
+    ("S-1-15-3-12", "APPLICATION PACKAGE AUTHORITY\\Your contacts"),
+    (
+        "S-1-15-3-2",
+        "APPLICATION PACKAGE AUTHORITY\\Your Internet connection, including incoming connections from the Internet",
+    ),
+    (
+        "S-1-15-3-3",
+        "APPLICATION PACKAGE AUTHORITY\\Your home or work networks",
+    ),
+    ("S-1-15-3-4", "APPLICATION PACKAGE AUTHORITY\\Your pictures library"),
+    ("S-1-15-3-5", "APPLICATION PACKAGE AUTHORITY\\Your videos library"),
+    ("S-1-15-3-6", "APPLICATION PACKAGE AUTHORITY\\Your music library"),
+    ("S-1-15-3-7", "APPLICATION PACKAGE AUTHORITY\\Your documents library"),
+    ("S-1-15-3-8", "APPLICATION PACKAGE AUTHORITY\\Your Windows credentials"),
+    (
+        "S-1-15-3-9",
+        "APPLICATION PACKAGE AUTHORITY\\Software and hardware certificates or a smart card",
+    ),
+    ("S-1-16-0", "Mandatory Label\\Untrusted Mandatory Level"),
+    ("S-1-16-12288", "Mandatory Label\\High Mandatory Level"),
+    ("S-1-16-16384", "Mandatory Label\\System Mandatory Level"),
+    ("S-1-16-20480", "Mandatory Label\\Protected Process Mandatory Level"),
+    ("S-1-16-4096", "Mandatory Label\\Low Mandatory Level"),
+    ("S-1-16-8192", "Mandatory Label\\Medium Mandatory Level"),
+    ("S-1-16-8448", "Mandatory Label\\Medium Plus Mandatory Level"),
+    ("S-1-2-0", "LOCAL"),
+    ("S-1-2-1", "CONSOLE LOGON"),
+    ("S-1-3-0", "CREATOR OWNER"),
+    ("S-1-3-1", "CREATOR GROUP"),
+    ("S-1-3-4", "OWNER RIGHTS"),
+    ("S-1-5-1", "NT AUTHORITY\\DIALUP"),
+    ("S-1-5-10", "NT AUTHORITY\\SELF"),
+    ("S-1-5-11", "NT AUTHORITY\\Authenticated Users"),
+    ("S-1-5-12", "NT AUTHORITY\\RESTRICTED"),
+    ("S-1-5-13", "NT AUTHORITY\\TERMINAL SERVER USER"),
+    ("S-1-5-14", "NT AUTHORITY\\REMOTE INTERACTIVE LOGON"),
+    ("S-1-5-15", "NT AUTHORITY\\This Organization"),
+    ("S-1-5-17", "NT AUTHORITY\\IUSR"),
+    ("S-1-5-18", "NT AUTHORITY\\SYSTEM"),
+    ("S-1-5-19", "NT AUTHORITY\\LOCAL SERVICE"),
+    ("S-1-5-2", "NT AUTHORITY\\NETWORK"),
+    ("S-1-5-20", "NT AUTHORITY\\NETWORK SERVICE"),
+    ("S-1-5-3", "NT AUTHORITY\\BATCH"),
+    ("S-1-5-32-544", "BUILTIN\\Administrators"),
+    ("S-1-5-32-545", "BUILTIN\\Users"),
+    ("S-1-5-32-546", "BUILTIN\\Guests"),
+    ("S-1-5-32-547", "BUILTIN\\Power Users"),
+    ("S-1-5-32-548", "BUILTIN\\Account Operators"),
+    ("S-1-5-32-549", "BUILTIN\\Server Operators"),
+    ("S-1-5-32-550", "BUILTIN\\Print Operators"),
+    ("S-1-5-32-551", "BUILTIN\\Backup Operators"),
+    ("S-1-5-32-552", "BUILTIN\\Replicator"),
+    ("S-1-5-32-554", "BUILTIN\\Pre-Windows 2000 Compatible Access"),
+    ("S-1-5-32-555", "BUILTIN\\Remote Desktop Users"),
+    ("S-1-5-32-556", "BUILTIN\\Network Configuration Operators"),
+    ("S-1-5-32-558", "BUILTIN\\Performance Monitor Users"),
+    ("S-1-5-32-559", "BUILTIN\\Performance Log Users"),
+    ("S-1-5-32-562", "BUILTIN\\Distributed COM Users"),
+    ("S-1-5-32-568", "BUILTIN\\IIS_IUSRS"),
+    ("S-1-5-32-569", "BUILTIN\\Cryptographic Operators"),
+    ("S-1-5-32-573", "BUILTIN\\Event Log Readers"),
+    ("S-1-5-32-578", "BUILTIN\\Hyper-V Administrators"),
+    ("S-1-5-32-580", "BUILTIN\\Remote Management Users"),
+    ("S-1-5-4", "NT AUTHORITY\\INTERACTIVE"),
+    ("S-1-5-6", "NT AUTHORITY\\SERVICE"),
+    ("S-1-5-7", "NT AUTHORITY\\ANONYMOUS LOGON"),
+    ("S-1-5-80-0", "NT SERVICE\\ALL SERVICES"),
+    ("S-1-5-9", "NT AUTHORITY\\ENTERPRISE DOMAIN CONTROLLERS"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sid::Sid;
+
+    #[test]
+    fn test_well_known_sids_are_sorted() {
+        assert!(WELL_KNOWN_SIDS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_well_known_sid_name() {
+        assert_eq!(
+            well_known_sid_name("S-1-5-18"),
+            Some("NT AUTHORITY\\SYSTEM")
+        );
+        assert_eq!(
+            well_known_sid_name("S-1-5-32-544"),
+            Some("BUILTIN\\Administrators")
+        );
+        assert_eq!(
+            well_known_sid_name("S-1-15-3-1"),
+            Some("APPLICATION PACKAGE AUTHORITY\\Your Internet connection")
+        );
+        assert_eq!(
+            well_known_sid_name("S-1-5-21-3623811015-3361044348-30300820-500"),
+            Some("Administrator")
+        );
+        assert_eq!(
+            well_known_sid_name("S-1-5-21-3623811015-3361044348-30300820-1013"),
+            None
+        );
+        assert_eq!(well_known_sid_name("S-1-5-21-500"), None);
+        assert_eq!(well_known_sid_name("S-1-5-99"), None);
+
+        let sid = Sid::from_buffer(&[1, 1, 0, 0, 0, 0, 0, 5, 0x12, 0, 0, 0]).unwrap();
+        assert_eq!(sid.well_known_name(), Some("NT AUTHORITY\\SYSTEM"));
+    }
+}
@@ -0,0 +1,862 @@
+//! Evaluation of [Sigma](https://github.com/SigmaHQ/sigma) rules against records, for triage.
+//!
+//! Rules are evaluated against the JSON rendering of a record (`EvtxParser::records_json_value`),
+//! where fields are named like most Sigma backends do:
+//! - The elements of `System` by their name (`EventID`, `Channel`, `Computer`..),
+//!   and their attributes as `<Element>_<Attribute>` (`Provider_Name`, `Execution_ProcessID`..).
+//! - The values of `EventData` by their `Name` (`CommandLine`, `TargetUserName`..).
+//! - The leaf elements of `UserData` by their name.
+//!
+//! Supported are:
+//! - Selections as maps (AND of fields), lists of maps (OR) and lists of keywords.
+//! - Wildcards (`*` and `?`), `null`, and the `contains`, `startswith`, `endswith`, `re` and `all` modifiers.
+//! - Conditions with `and`, `or`, `not`, parentheses, `1 of <pattern>` and `all of <pattern>` (or `them`).
+//!
+//! Aggregations (`| count() > 5`) and the `logsource` of rules are not evaluated.
+//!
+//! ```rust
+//! use evtx::sigma::SigmaRule;
+//!
+//! let rule = SigmaRule::from_yaml(r#"
+//! title: Network logon
+//! detection:
+//!     selection:
+//!         EventID: 4624
+//!         LogonType: 3
+//!     condition: selection
+//! "#).unwrap();
+//!
+//! assert_eq!(rule.title, "Network logon");
+//! ```
+use crate::err::{self, Result};
+use crate::filter_expr::MAX_DEPTH;
+use crate::SerializedEvtxRecord;
+
+use log::debug;
+use regex::{Regex, RegexBuilder};
+use serde_json::{json, Value};
+use serde_yaml::Value as YamlValue;
+use snafu::ResultExt;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// The fields of a record, as seen by Sigma rules.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RecordFields {
+    /// Field name (lowercase) -> values.
+    fields: HashMap<String, Vec<String>>,
+}
+
+impl RecordFields {
+    /// Collects the fields of a record rendered as JSON.
+    pub fn from_json(data: &Value) -> Self {
+        let mut fields = RecordFields::default();
+        let event = &data["Event"];
+
+        if let Some(system) = event["System"].as_object() {
+            for (name, value) in system {
+                fields.add_element(name, value);
+            }
+        }
+
+        match &event["EventData"] {
+            Value::Object(data) => {
+                for (name, value) in data {
+                    fields.add_element(name, value);
+                }
+            }
+            other => fields.add_leaves("Data", other),
+        }
+
+        if let Some(user_data) = event["UserData"].as_object() {
+            for (name, value) in user_data {
+                fields.add_leaves(name, value);
+            }
+        }
+
+        fields
+    }
+
+    /// Returns the values of a field (case insensitive).
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.fields
+            .get(&name.to_lowercase())
+            .map(|values| values.as_slice())
+    }
+
+    /// Returns the values of all the fields.
+    pub fn values(&self) -> impl Iterator<Item = &str> {
+        self.fields.values().flatten().map(String::as_str)
+    }
+
+    fn insert(&mut self, name: &str, value: &Value) {
+        let value = match value {
+            Value::Null => return,
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        };
+
+        self.fields
+            .entry(name.to_lowercase())
+            .or_default()
+            .push(value);
+    }
+
+    /// Adds an element, which might have attributes (with either `#attributes` or `<name>_attributes`).
+    fn add_element(&mut self, name: &str, value: &Value) {
+        if name == "#attributes" {
+            return;
+        }
+
+        if let Some(element) = name.strip_suffix("_attributes") {
+            self.add_attributes(element, value);
+            return;
+        }
+
+        match value {
+            Value::Object(children) => {
+                for (child, value) in children {
+                    match child.as_str() {
+                        "#attributes" => self.add_attributes(name, value),
+                        "#text" => self.add_leaves(name, value),
+                        _ => self.add_leaves(child, value),
+                    }
+                }
+            }
+            other => self.add_leaves(name, other),
+        }
+    }
+
+    fn add_attributes(&mut self, element: &str, attributes: &Value) {
+        if let Some(attributes) = attributes.as_object() {
+            for (attribute, value) in attributes {
+                self.insert(&format!("{}_{}", element, attribute), value);
+            }
+        }
+    }
+
+    fn add_leaves(&mut self, name: &str, value: &Value) {
+        match value {
+            Value::Array(values) => {
+                for value in values {
+                    self.add_leaves(name, value);
+                }
+            }
+            Value::Object(_) => self.add_element(name, value),
+            other => self.insert(name, other),
+        }
+    }
+}
+
+/// A compiled Sigma rule.
+#[derive(Debug, Clone)]
+pub struct SigmaRule {
+    pub title: String,
+    pub id: Option<String>,
+    pub level: Option<String>,
+    pub tags: Vec<String>,
+    selections: HashMap<String, Selection>,
+    condition: Condition,
+}
+
+#[derive(Debug, Clone)]
+enum Selection {
+    /// All the fields must match.
+    Fields(Vec<FieldMatch>),
+    /// Any of the selections must match.
+    Any(Vec<Selection>),
+    /// Any of the values of the record must match any of the patterns.
+    Keywords(Vec<Pattern>),
+}
+
+#[derive(Debug, Clone)]
+struct FieldMatch {
+    field: String,
+    patterns: Vec<Pattern>,
+    /// All of the patterns must match (instead of any of them).
+    all: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    /// The field must be missing or empty.
+    Null,
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Selection(String),
+    Not(Box<Condition>),
+    And(Vec<Condition>),
+    Or(Vec<Condition>),
+}
+
+fn fail<T>(message: impl Into<String>) -> Result<T> {
+    err::InvalidSigmaRule {
+        message: message.into(),
+    }
+    .fail()
+}
+
+impl SigmaRule {
+    /// Parses and compiles a rule from it's YAML source.
+    pub fn from_yaml(source: &str) -> Result<Self> {
+        let rule: YamlValue = match serde_yaml::from_str(source) {
+            Ok(rule) => rule,
+            Err(e) => return fail(e.to_string()),
+        };
+
+        let string = |key: &str| {
+            rule.get(key)
+                .and_then(YamlValue::as_str)
+                .map(str::to_string)
+        };
+
+        let title = match string("title") {
+            Some(title) => title,
+            None => return fail("Missing `title`"),
+        };
+
+        let tags = rule
+            .get("tags")
+            .and_then(YamlValue::as_sequence)
+            .map(|tags| {
+                tags.iter()
+                    .filter_map(YamlValue::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let detection = match rule.get("detection").and_then(YamlValue::as_mapping) {
+            Some(detection) => detection,
+            None => return fail("Missing `detection`"),
+        };
+
+        let mut selections = HashMap::new();
+        let mut conditions = vec![];
+
+        for (name, value) in detection {
+            match name.as_str() {
+                Some("condition") => match value {
+                    YamlValue::String(condition) => conditions.push(condition.as_str()),
+                    YamlValue::Sequence(values) => {
+                        for value in values {
+                            match value.as_str() {
+                                Some(condition) => conditions.push(condition),
+                                None => return fail("Expected `condition` to be a string"),
+                            }
+                        }
+                    }
+                    _ => return fail("Expected `condition` to be a string"),
+                },
+                // Correlation windows are irrelevant without aggregations.
+                Some("timeframe") => {}
+                Some(name) => {
+                    selections.insert(name.to_string(), Selection::compile(name, value)?);
+                }
+                None => return fail("Expected selection names to be strings"),
+            }
+        }
+
+        if conditions.is_empty() {
+            return fail("Missing `condition`");
+        }
+
+        let mut names: Vec<&str> = selections.keys().map(String::as_str).collect();
+        names.sort_unstable();
+
+        let condition = conditions
+            .into_iter()
+            .map(|condition| parse_condition(condition, &names))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(SigmaRule {
+            title,
+            id: string("id"),
+            level: string("level"),
+            tags,
+            selections,
+            condition: Condition::Or(condition),
+        })
+    }
+
+    /// Returns true if the record (rendered as JSON) matches the rule.
+    pub fn matches(&self, data: &Value) -> bool {
+        self.matches_fields(&RecordFields::from_json(data))
+    }
+
+    /// Same as `matches`, for fields that were already collected.
+    pub fn matches_fields(&self, fields: &RecordFields) -> bool {
+        self.condition.matches(&self.selections, fields)
+    }
+}
+
+impl Selection {
+    fn compile(name: &str, value: &YamlValue) -> Result<Self> {
+        match value {
+            YamlValue::Mapping(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, value)| match field.as_str() {
+                        Some(field) => FieldMatch::compile(field, value),
+                        None => fail(format!("Expected the fields of `{}` to be strings", name)),
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(Selection::Fields(fields))
+            }
+            YamlValue::Sequence(values) if values.iter().all(YamlValue::is_mapping) => {
+                let selections = values
+                    .iter()
+                    .map(|value| Selection::compile(name, value))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(Selection::Any(selections))
+            }
+            YamlValue::Sequence(values) => {
+                let patterns = values
+                    .iter()
+                    .map(|value| Pattern::compile(value, &["contains"]))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(Selection::Keywords(patterns))
+            }
+            _ => fail(format!("Expected `{}` to be a map or a list", name)),
+        }
+    }
+
+    fn matches(&self, fields: &RecordFields) -> bool {
+        match self {
+            Selection::Fields(matches) => matches.iter().all(|m| m.matches(fields)),
+            Selection::Any(selections) => selections.iter().any(|s| s.matches(fields)),
+            Selection::Keywords(patterns) => fields
+                .values()
+                .any(|value| patterns.iter().any(|p| p.matches(Some(value)))),
+        }
+    }
+}
+
+impl FieldMatch {
+    /// Compiles `Field|modifier|..: value(s)`.
+    fn compile(spec: &str, value: &YamlValue) -> Result<Self> {
+        let mut parts = spec.split('|');
+        let field = parts.next().unwrap_or_default().to_string();
+        let mut modifiers: Vec<&str> = parts.collect();
+
+        let all = modifiers.contains(&"all");
+        modifiers.retain(|m| *m != "all");
+
+        let patterns = match value {
+            YamlValue::Sequence(values) => values
+                .iter()
+                .map(|value| Pattern::compile(value, &modifiers))
+                .collect::<Result<Vec<_>>>()?,
+            value => vec![Pattern::compile(value, &modifiers)?],
+        };
+
+        Ok(FieldMatch {
+            field,
+            patterns,
+            all,
+        })
+    }
+
+    fn matches(&self, fields: &RecordFields) -> bool {
+        let values = fields.get(&self.field).unwrap_or(&[]);
+
+        let pattern_matches = |pattern: &Pattern| {
+            if values.is_empty() {
+                pattern.matches(None)
+            } else {
+                values.iter().any(|value| pattern.matches(Some(value)))
+            }
+        };
+
+        if self.all {
+            self.patterns.iter().all(pattern_matches)
+        } else {
+            self.patterns.iter().any(pattern_matches)
+        }
+    }
+}
+
+impl Pattern {
+    fn compile(value: &YamlValue, modifiers: &[&str]) -> Result<Self> {
+        let value = match value {
+            YamlValue::Null => return Ok(Pattern::Null),
+            YamlValue::String(s) => s.clone(),
+            YamlValue::Number(n) => n.to_string(),
+            YamlValue::Bool(b) => b.to_string(),
+            _ => return fail("Expected values to be strings, numbers or booleans"),
+        };
+
+        let regex = match modifiers {
+            ["re"] => value,
+            [] => format!("^{}$", wildcard_to_regex(&value)),
+            ["contains"] => wildcard_to_regex(&value),
+            ["startswith"] => format!("^{}", wildcard_to_regex(&value)),
+            ["endswith"] => format!("{}$", wildcard_to_regex(&value)),
+            _ => return fail(format!("Unsupported modifiers `{}`", modifiers.join("|"))),
+        };
+
+        RegexBuilder::new(&regex)
+            .case_insensitive(modifiers != ["re"])
+            .build()
+            .map(Pattern::Regex)
+            .or_else(|e| fail(e.to_string()))
+    }
+
+    fn matches(&self, value: Option<&str>) -> bool {
+        match (self, value) {
+            (Pattern::Null, value) => value.map_or(true, str::is_empty),
+            (Pattern::Regex(regex), Some(value)) => regex.is_match(value),
+            (Pattern::Regex(_), None) => false,
+        }
+    }
+}
+
+/// Converts a Sigma value with wildcards (`*`, `?`, escaped with `\`) to a regex.
+fn wildcard_to_regex(value: &str) -> String {
+    let mut pattern = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => pattern.push_str(".*"),
+            '?' => pattern.push('.'),
+            '\\' => match chars.next() {
+                Some(escaped @ ('*' | '?' | '\\')) => {
+                    pattern.push_str(&regex::escape(&escaped.to_string()))
+                }
+                Some(other) => {
+                    pattern.push_str(r"\\");
+                    pattern.push_str(&regex::escape(&other.to_string()));
+                }
+                None => pattern.push_str(r"\\"),
+            },
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    pattern
+}
+
+impl Condition {
+    fn matches(&self, selections: &HashMap<String, Selection>, fields: &RecordFields) -> bool {
+        match self {
+            Condition::Selection(name) => selections[name].matches(fields),
+            Condition::Not(condition) => !condition.matches(selections, fields),
+            Condition::And(conditions) => conditions.iter().all(|c| c.matches(selections, fields)),
+            Condition::Or(conditions) => conditions.iter().any(|c| c.matches(selections, fields)),
+        }
+    }
+}
+
+/// Parses a condition, `names` are the names of the selections of the rule.
+///
+/// ```text
+/// or    := and ( "or" and )*
+/// and   := unary ( "and" unary )*
+/// unary := "not" unary | "(" or ")" | ( "1" | "all" ) "of" ( pattern | "them" ) | name
+/// ```
+fn parse_condition(condition: &str, names: &[&str]) -> Result<Condition> {
+    if condition.contains('|') {
+        return fail(format!("Aggregations are not supported: `{}`", condition));
+    }
+
+    let spaced = condition.replace('(', " ( ").replace(')', " ) ");
+    let tokens: Vec<&str> = spaced.split_whitespace().collect();
+
+    let mut parser = ConditionParser {
+        tokens: &tokens,
+        position: 0,
+        names,
+        depth: 0,
+    };
+
+    let parsed = parser.parse_or()?;
+
+    match tokens.get(parser.position) {
+        None => Ok(parsed),
+        Some(token) => fail(format!(
+            "Unexpected `{}` in condition `{}`",
+            token, condition
+        )),
+    }
+}
+
+struct ConditionParser<'a> {
+    tokens: &'a [&'a str],
+    position: usize,
+    names: &'a [&'a str],
+    /// The number of enclosing `not` and `(`, limited to `MAX_DEPTH`.
+    depth: usize,
+}
+
+impl<'a> ConditionParser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).cloned()
+    }
+
+    fn next(&mut self) -> Result<&'a str> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(*token)
+            }
+            None => fail("Unexpected end of condition"),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Condition> {
+        let mut conditions = vec![self.parse_and()?];
+
+        while self.peek() == Some("or") {
+            self.position += 1;
+            conditions.push(self.parse_and()?);
+        }
+
+        Ok(single_or(conditions, Condition::Or))
+    }
+
+    fn parse_and(&mut self) -> Result<Condition> {
+        let mut conditions = vec![self.parse_unary()?];
+
+        while self.peek() == Some("and") {
+            self.position += 1;
+            conditions.push(self.parse_unary()?);
+        }
+
+        Ok(single_or(conditions, Condition::And))
+    }
+
+    /// Runs `parse` one nesting level deeper, failing past `MAX_DEPTH`.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_DEPTH {
+            return fail(format!(
+                "Condition is nested deeper than {} levels",
+                MAX_DEPTH
+            ));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn parse_unary(&mut self) -> Result<Condition> {
+        match self.next()? {
+            "not" => Ok(Condition::Not(Box::new(self.nested(Self::parse_unary)?))),
+            "(" => {
+                let condition = self.nested(Self::parse_or)?;
+
+                match self.next()? {
+                    ")" => Ok(condition),
+                    token => fail(format!("Expected `)`, found `{}`", token)),
+                }
+            }
+            quantifier @ ("1" | "all") => {
+                let quantifier = quantifier.to_string();
+
+                if self.next()? != "of" {
+                    return fail(format!("Expected `of` after `{}`", quantifier));
+                }
+
+                let pattern = self.next()?;
+                let selected: Vec<Condition> = self
+                    .names
+                    .iter()
+                    .filter(|name| match pattern.strip_suffix('*') {
+                        _ if pattern == "them" => !name.starts_with('_'),
+                        Some(prefix) => name.starts_with(prefix),
+                        None => **name == pattern,
+                    })
+                    .map(|name| Condition::Selection(name.to_string()))
+                    .collect();
+
+                if selected.is_empty() {
+                    return fail(format!("No selection matches `{}`", pattern));
+                }
+
+                Ok(if quantifier == "all" {
+                    Condition::And(selected)
+                } else {
+                    Condition::Or(selected)
+                })
+            }
+            name if self.names.contains(&name) => Ok(Condition::Selection(name.to_string())),
+            token => fail(format!("Unknown selection `{}`", token)),
+        }
+    }
+}
+
+fn single_or(mut conditions: Vec<Condition>, f: fn(Vec<Condition>) -> Condition) -> Condition {
+    if conditions.len() == 1 {
+        conditions.remove(0)
+    } else {
+        f(conditions)
+    }
+}
+
+/// A set of rules, evaluated together.
+#[derive(Debug, Default, Clone)]
+pub struct SigmaRuleSet {
+    rules: Vec<SigmaRule>,
+}
+
+impl SigmaRuleSet {
+    pub fn new(rules: Vec<SigmaRule>) -> Self {
+        SigmaRuleSet { rules }
+    }
+
+    /// Loads all the `.yml` and `.yaml` files in `path` and it's subdirectories.
+    /// Other files are ignored.
+    pub fn load_dir(path: impl AsRef<Path>) -> Result<Self> {
+        let mut rule_set = SigmaRuleSet::default();
+        rule_set.load_dir_into(path.as_ref())?;
+
+        Ok(rule_set)
+    }
+
+    fn load_dir_into(&mut self, path: &Path) -> Result<()> {
+        let mut entries = fs::read_dir(path)
+            .context(err::FailedToOpenFile { path })?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()
+            .context(err::FailedToOpenFile { path })?;
+
+        entries.sort();
+
+        for entry in entries {
+            if entry.is_dir() {
+                self.load_dir_into(&entry)?;
+                continue;
+            }
+
+            match entry.extension().and_then(|e| e.to_str()) {
+                Some("yml") | Some("yaml") => {
+                    let source = fs::read_to_string(&entry)
+                        .context(err::FailedToOpenFile { path: &entry })?;
+
+                    match SigmaRule::from_yaml(&source) {
+                        Ok(rule) => self.rules.push(rule),
+                        Err(e) => {
+                            return fail(format!("{}: {}", entry.display(), e));
+                        }
+                    }
+                }
+                _ => debug!("Ignoring `{}`", entry.display()),
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn rules(&self) -> &[SigmaRule] {
+        &self.rules
+    }
+
+    /// Returns the rules matched by the record (rendered as JSON).
+    pub fn matching(&self, data: &Value) -> Vec<&SigmaRule> {
+        let fields = RecordFields::from_json(data);
+
+        self.rules
+            .iter()
+            .filter(|rule| rule.matches_fields(&fields))
+            .collect()
+    }
+
+    /// Adds the rules matched by the record under a top-level `Sigma` key
+    /// (with their `title`, `id`, `level` and `tags`), and returns true if any rule matched.
+    pub fn tag(&self, record: &mut SerializedEvtxRecord<Value>) -> bool {
+        let matches: Vec<Value> = self
+            .matching(&record.data)
+            .into_iter()
+            .map(|rule| {
+                json!({
+                    "title": rule.title,
+                    "id": rule.id,
+                    "level": rule.level,
+                    "tags": rule.tags,
+                })
+            })
+            .collect();
+
+        if matches.is_empty() {
+            return false;
+        }
+
+        if let Value::Object(data) = &mut record.data {
+            data.insert("Sigma".to_string(), Value::Array(matches));
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvtxParser;
+
+    const LOGON_RULE: &str = r#"
+title: Interactive logon by an unexpected account
+id: 5a1ad7c0-0000-4000-8000-000000000001
+level: low
+tags:
+    - attack.t1078
+detection:
+    selection:
+        EventID: 4624
+        LogonType: 2
+        Provider_Name: 'microsoft-windows-security-*'
+    filter:
+        TargetUserName|startswith:
+            - 'admin'
+            - 'ARCH'
+    condition: selection and not filter
+"#;
+
+    #[test]
+    fn test_record_fields() {
+        let fields = RecordFields::from_json(&json!({
+            "Event": {
+                "System": {
+                    "EventID": {"#attributes": {"Qualifiers": 16384}, "#text": 7036},
+                    "Provider": {"#attributes": {"Name": "Service Control Manager"}},
+                    "Correlation": null,
+                },
+                "EventData": {"Data": ["WinHTTP", "running"]},
+            }
+        }));
+
+        assert_eq!(fields.get("eventid"), Some(&["7036".to_string()][..]));
+        assert_eq!(
+            fields.get("EventID_Qualifiers"),
+            Some(&["16384".to_string()][..])
+        );
+        assert_eq!(
+            fields.get("Provider_Name"),
+            Some(&["Service Control Manager".to_string()][..])
+        );
+        assert_eq!(fields.get("Correlation"), None);
+        assert_eq!(fields.get("Data").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_matches_values() {
+        let rule = SigmaRule::from_yaml(
+            r#"
+title: Test
+detection:
+    sel_encoded:
+        CommandLine|contains|all:
+            - ' -enc '
+            - 'powershell'
+    sel_hidden:
+        CommandLine|re: '-[Ww]indowStyle\s+[Hh]idden'
+    sel_keywords:
+        - 'mimikatz'
+    empty:
+        ParentImage: null
+    condition: 1 of sel_* and empty
+"#,
+        )
+        .unwrap();
+
+        let record = |command_line: &str| json!({"Event": {"EventData": {"CommandLine": command_line, "ParentImage": ""}}});
+
+        assert!(rule.matches(&record("PowerShell.exe -ENC abc")));
+        assert!(rule.matches(&record("powershell -WindowStyle Hidden")));
+        assert!(rule.matches(&record("C:\\tools\\Mimikatz.exe")));
+        assert!(!rule.matches(&record("powershell -windowstyle HIDDEN")));
+        assert!(!rule.matches(&record("cmd.exe -enc abc")));
+    }
+
+    #[test]
+    fn test_wildcards() {
+        assert_eq!(
+            wildcard_to_regex(r"C:\Windows\\*.exe?"),
+            r"C:\\Windows\\.*\.exe."
+        );
+        assert_eq!(wildcard_to_regex(r"a\*b\?"), r"a\*b\?");
+    }
+
+    #[test]
+    fn test_rejects_unsupported_rules() {
+        let rule = |detection: &str| {
+            SigmaRule::from_yaml(&format!("title: Test\ndetection:\n{}", detection))
+        };
+
+        assert!(
+            rule("    selection:\n        EventID: 1\n    condition: selection | count() > 5")
+                .is_err()
+        );
+        assert!(
+            rule("    selection:\n        EventID|base64: 1\n    condition: selection").is_err()
+        );
+        assert!(rule("    selection:\n        EventID: 1\n    condition: other").is_err());
+        assert!(rule("    selection:\n        EventID: 1").is_err());
+        assert!(SigmaRule::from_yaml("detection: {}").is_err());
+    }
+
+    #[test]
+    fn test_rejects_deeply_nested_conditions() {
+        let rule = |condition: String| {
+            SigmaRule::from_yaml(&format!(
+                "title: Test\ndetection:\n    selection:\n        EventID: 1\n    condition: {}",
+                condition
+            ))
+        };
+
+        let nested =
+            |depth: usize| format!("{}selection{}", "( not ".repeat(depth), " )".repeat(depth));
+
+        assert!(rule(nested(MAX_DEPTH / 2)).is_ok());
+        let too_deep = [
+            nested(MAX_DEPTH / 2 + 1),
+            "not ".repeat(100_000) + "selection",
+            "(".repeat(100_000) + "selection" + &")".repeat(100_000),
+        ];
+        for condition in too_deep {
+            let err = rule(condition).unwrap_err().to_string();
+            assert!(err.contains("nested deeper than"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_tags_records() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("windows")).unwrap();
+        fs::write(dir.path().join("windows").join("logon.yml"), LOGON_RULE).unwrap();
+        fs::write(dir.path().join("README.md"), "").unwrap();
+
+        let rules = SigmaRuleSet::load_dir(dir.path()).unwrap();
+        assert_eq!(rules.rules().len(), 1);
+
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+        let tagged: Vec<SerializedEvtxRecord<Value>> = parser
+            .records_json_value()
+            .map(|r| r.unwrap())
+            .filter_map(|mut r| if rules.tag(&mut r) { Some(r) } else { None })
+            .collect();
+
+        // The sample has 84 interactive logons, 4 of them by `archir`.
+        assert_eq!(tagged.len(), 80);
+
+        for record in tagged {
+            assert_eq!(record.data["Event"]["System"]["EventID"], 4624);
+            assert_eq!(record.data["Event"]["EventData"]["LogonType"], 2);
+            assert_eq!(record.data["Event"]["EventData"]["TargetUserName"], "fsir");
+            assert_eq!(record.data["Sigma"][0]["level"], "low");
+            assert_eq!(record.data["Sigma"][0]["tags"][0], "attack.t1078");
+        }
+    }
+}
@@ -0,0 +1,667 @@
+//! Records rendered by splicing their substitution values into the pre-rendered XML or JSON of their templates,
+//! see `ParserSettings::skeleton_cache`.
+use crate::binxml::assemble::parse_tokens;
+use crate::binxml::value_variant::{BinXmlValue, FloatFormat};
+use crate::guid::GuidFormat;
+use crate::json_output::JsonOutput;
+use crate::model::deserialized::{
+    BinXMLDeserializedTokens, BinXMLTemplateDefinition, BinXmlTemplate,
+};
+use crate::{ParserSettings, XmlOutput};
+
+use quick_xml::events::BytesText;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// Delimit the slot of a substitution in the rendered template (private use characters).
+const PLACEHOLDER_START: char = '\u{E000}';
+const PLACEHOLDER_END: char = '\u{E001}';
+
+/// Identifies a substitution of a record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Slot {
+    /// The index of the substitution in the template of the record.
+    index: usize,
+    /// If the substitution at `index` is a nested template (such as `EventData`),
+    /// the index of the substitution in that template.
+    nested: Option<usize>,
+}
+
+impl Slot {
+    fn placeholder(&self) -> String {
+        match self.nested {
+            Some(nested) => format!(
+                "{}{}.{}{}",
+                PLACEHOLDER_START, self.index, nested, PLACEHOLDER_END
+            ),
+            None => format!("{}{}{}", PLACEHOLDER_START, self.index, PLACEHOLDER_END),
+        }
+    }
+
+    /// Returns the slot of a string which is only a placeholder.
+    fn of_placeholder(s: &str) -> Option<Slot> {
+        Slot::parse(
+            s.strip_prefix(PLACEHOLDER_START)?
+                .strip_suffix(PLACEHOLDER_END)?,
+        )
+    }
+
+    /// Returns the value of the slot in a record made of `template`, `None` if it cannot be spliced in.
+    /// Missing substitutions are null, as when the tokens of the record are expanded.
+    fn value<'t, 'a>(&self, template: &'t BinXmlTemplate<'a>) -> Option<Cow<'t, BinXmlValue<'a>>> {
+        let substitutions = match self.nested {
+            None => &template.substitution_array,
+            Some(_) => match template.substitution_array.get(self.index)? {
+                BinXmlValue::BinXmlType(tokens) => &single_template(tokens)?.substitution_array,
+                _ => return None,
+            },
+        };
+
+        match substitutions.get(self.nested.unwrap_or(self.index)) {
+            Some(BinXmlValue::BinXmlType(_))
+            | Some(BinXmlValue::EvtXml)
+            | Some(BinXmlValue::ParseError(_)) => None,
+            Some(value) => Some(Cow::Borrowed(value)),
+            None => Some(Cow::Owned(BinXmlValue::NullType)),
+        }
+    }
+
+    fn parse(placeholder: &str) -> Option<Slot> {
+        let mut parts = placeholder.splitn(2, '.');
+
+        Some(Slot {
+            index: parts.next()?.parse().ok()?,
+            nested: match parts.next() {
+                Some(nested) => Some(nested.parse().ok()?),
+                None => None,
+            },
+        })
+    }
+}
+
+/// A part of the rendered XML of a record.
+#[derive(Debug)]
+enum Segment {
+    Static(String),
+    /// The text of an element, the value of the substitution in `Slot`.
+    Text(Slot),
+    /// An attribute with the value of the substitution in `Slot`, omitted when the value is empty.
+    /// `prefix` is ` Name="`.
+    Attribute {
+        prefix: String,
+        slot: Slot,
+    },
+}
+
+/// The substitutions of a template which are nested templates, with their definition.
+type NestedTemplates<'t, 'a> = Vec<(usize, &'t BinXMLTemplateDefinition<'a>)>;
+
+/// Returns the template instance which makes up `tokens`, if there is only one.
+fn single_template<'t, 'a>(
+    tokens: &'t [BinXMLDeserializedTokens<'a>],
+) -> Option<&'t BinXmlTemplate<'a>> {
+    let mut templates = tokens.iter().filter(|token| {
+        !matches!(
+            token,
+            BinXMLDeserializedTokens::FragmentHeader(_)
+                | BinXMLDeserializedTokens::StartOfStream
+                | BinXMLDeserializedTokens::EndOfStream
+        )
+    });
+
+    match (templates.next(), templates.next()) {
+        (Some(BinXMLDeserializedTokens::TemplateInstance(template)), None) => Some(template),
+        _ => None,
+    }
+}
+
+/// Returns the substitutions of `template` which are nested templates.
+/// `None` if a substitution is nested BinXml which is not a single template.
+fn nested_templates<'t, 'a>(template: &'t BinXmlTemplate<'a>) -> Option<NestedTemplates<'t, 'a>> {
+    template
+        .substitution_array
+        .iter()
+        .enumerate()
+        .filter_map(|(index, value)| match value {
+            BinXmlValue::BinXmlType(tokens) => {
+                Some(single_template(tokens).map(|nested| (index, nested.definition.as_ref())))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Tokens with placeholders, the names of the attributes holding a placeholder, and the slots of all the placeholders.
+type SkeletonTokens<'a> = (
+    Vec<BinXMLDeserializedTokens<'a>>,
+    HashMap<Slot, String>,
+    HashSet<Slot>,
+);
+
+/// The tokens of `definition` (with the `nested` templates in place of their substitutions),
+/// with placeholders instead of the substitutions.
+///
+/// Returns `None` if the output of the templates depends on more than the values of their substitutions
+/// (deeper nesting, or substitutions which are not an element text or an attribute value).
+fn skeleton_tokens<'a>(
+    definition: &BinXMLTemplateDefinition<'a>,
+    nested: &[(usize, &BinXMLTemplateDefinition<'a>)],
+) -> Option<SkeletonTokens<'a>> {
+    let mut tokens = vec![];
+    let mut attributes = HashMap::new();
+    let mut slots = HashSet::new();
+
+    push_template_tokens(
+        definition,
+        nested,
+        None,
+        &mut tokens,
+        &mut attributes,
+        &mut slots,
+    )?;
+
+    Some((tokens, attributes, slots))
+}
+
+/// The XML of a record, rendered once with placeholders instead of it's substitutions.
+/// Rendering a record of the same shape (the same templates) only requires splicing in the substitution values.
+#[derive(Debug)]
+pub(crate) struct XmlSkeleton {
+    segments: Vec<Segment>,
+    static_len: usize,
+}
+
+impl XmlSkeleton {
+    /// Renders `definition`, with the `nested` templates in place of their substitutions.
+    fn build<'a>(
+        definition: &BinXMLTemplateDefinition<'a>,
+        nested: &[(usize, &BinXMLTemplateDefinition<'a>)],
+        settings: &ParserSettings,
+    ) -> Option<Self> {
+        let (tokens, attributes, mut slots) = skeleton_tokens(definition, nested)?;
+
+        let mut output = XmlOutput::with_writer(Vec::new(), settings);
+        parse_tokens(tokens, &mut output).ok()?;
+        let xml = String::from_utf8(output.into_writer().ok()?).ok()?;
+
+        let mut segments = vec![];
+        let mut rest = xml.as_str();
+
+        while let Some(start) = rest.find(PLACEHOLDER_START) {
+            let (before, placeholder) = rest.split_at(start);
+            let placeholder = &placeholder[PLACEHOLDER_START.len_utf8()..];
+            let end = placeholder.find(PLACEHOLDER_END)?;
+
+            let slot = Slot::parse(&placeholder[..end])?;
+            if !slots.remove(&slot) {
+                return None;
+            }
+            rest = &placeholder[end + PLACEHOLDER_END.len_utf8()..];
+
+            match attributes.get(&slot) {
+                Some(name) => {
+                    let prefix = format!(" {}=\"", name);
+                    let before = before.strip_suffix(prefix.as_str())?;
+                    rest = rest.strip_prefix('"')?;
+
+                    segments.push(Segment::Static(before.to_string()));
+                    segments.push(Segment::Attribute { prefix, slot });
+                }
+                None => {
+                    segments.push(Segment::Static(before.to_string()));
+                    segments.push(Segment::Text(slot));
+                }
+            }
+        }
+
+        // Every placeholder should have been found exactly once.
+        if !slots.is_empty() {
+            return None;
+        }
+
+        segments.push(Segment::Static(rest.to_string()));
+        segments.retain(|segment| !matches!(segment, Segment::Static(s) if s.is_empty()));
+
+        let static_len = segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Static(s) => s.len(),
+                _ => 0,
+            })
+            .sum();
+
+        Some(XmlSkeleton {
+            segments,
+            static_len,
+        })
+    }
+
+    /// Renders a record made of `template`, which must have the shape the skeleton was built for.
+    fn render(
+        &self,
+        template: &BinXmlTemplate,
+        float_format: FloatFormat,
+        guid_format: GuidFormat,
+    ) -> Option<String> {
+        let value_at = |slot: &Slot| -> Option<Cow<str>> {
+            match slot.value(template)? {
+                Cow::Borrowed(value) => {
+                    Some(value.as_cow_str_with_formats(float_format, guid_format))
+                }
+                Cow::Owned(value) => Some(Cow::Owned(
+                    value
+                        .as_cow_str_with_formats(float_format, guid_format)
+                        .into_owned(),
+                )),
+            }
+        };
+
+        // Escaped the same way as `XmlOutput` does (quick-xml escapes text and attribute values alike).
+        let push_escaped = |xml: &mut String, value: &str| {
+            xml.push_str(&String::from_utf8_lossy(
+                BytesText::from_plain_str(value).escaped(),
+            ))
+        };
+
+        let mut xml = String::with_capacity(self.static_len * 2);
+
+        for segment in self.segments.iter() {
+            match segment {
+                Segment::Static(s) => xml.push_str(s),
+                Segment::Text(slot) => push_escaped(&mut xml, &value_at(slot)?),
+                Segment::Attribute { prefix, slot } => {
+                    let value = value_at(slot)?;
+
+                    if !value.is_empty() {
+                        xml.push_str(prefix);
+                        push_escaped(&mut xml, &value);
+                        xml.push('"');
+                    }
+                }
+            }
+        }
+
+        Some(xml)
+    }
+}
+
+/// The JSON of a record, rendered once with placeholders (strings) instead of it's substitutions.
+/// Rendering a record of the same shape only requires cloning the skeleton, and replacing the placeholders.
+///
+/// Attributes with a null value are omitted from JSON (which changes the shape of their element),
+/// so the null substitutions are part of the shape, and rendered in the skeleton.
+#[derive(Debug)]
+pub(crate) struct JsonSkeleton {
+    value: Value,
+    /// The slots which are attribute values.
+    attributes: HashSet<Slot>,
+}
+
+impl JsonSkeleton {
+    /// Renders `definition`, with the `nested` templates in place of their substitutions,
+    /// and null values in place of the `nulls` substitutions.
+    ///
+    /// Returns `None` if a placeholder ended up in a key (such as the `Name` of a `Data` element).
+    fn build<'a>(
+        definition: &BinXMLTemplateDefinition<'a>,
+        nested: &[(usize, &BinXMLTemplateDefinition<'a>)],
+        nulls: &[Slot],
+        settings: &ParserSettings,
+    ) -> Option<Self> {
+        let (mut tokens, attributes, _) = skeleton_tokens(definition, nested)?;
+
+        for token in tokens.iter_mut() {
+            if let BinXMLDeserializedTokens::Value(value) = token {
+                if let BinXmlValue::StringType(s) = value.as_ref() {
+                    if Slot::of_placeholder(s).map_or(false, |slot| nulls.contains(&slot)) {
+                        *value = Cow::Owned(BinXmlValue::NullType);
+                    }
+                }
+            }
+        }
+
+        let mut output = JsonOutput::new(settings);
+        parse_tokens(tokens, &mut output).ok()?;
+        let value = output.into_value().ok()?;
+
+        fn has_placeholder_key(value: &Value) -> bool {
+            match value {
+                Value::Object(map) => map.iter().any(|(key, value)| {
+                    key.contains(PLACEHOLDER_START) || has_placeholder_key(value)
+                }),
+                Value::Array(values) => values.iter().any(has_placeholder_key),
+                _ => false,
+            }
+        }
+
+        if has_placeholder_key(&value) {
+            return None;
+        }
+
+        Some(JsonSkeleton {
+            value,
+            attributes: attributes.into_keys().collect(),
+        })
+    }
+
+    /// Renders a record made of `template`, which must have the shape the skeleton was built for.
+    ///
+    /// Returns `None` if an attribute value is null (a substitution missing from the record).
+    fn render(
+        &self,
+        template: &BinXmlTemplate,
+        float_format: FloatFormat,
+        guid_format: GuidFormat,
+    ) -> Option<Value> {
+        fn fill(
+            value: &mut Value,
+            skeleton: &JsonSkeleton,
+            template: &BinXmlTemplate,
+            formats: (FloatFormat, GuidFormat),
+        ) -> Option<()> {
+            match value {
+                Value::String(s) => {
+                    if let Some(slot) = Slot::of_placeholder(s) {
+                        let json = slot
+                            .value(template)?
+                            .to_json_with_formats(formats.0, formats.1);
+
+                        if json.is_null() && skeleton.attributes.contains(&slot) {
+                            return None;
+                        }
+                        *value = json;
+                    }
+                }
+                Value::Object(map) => {
+                    for value in map.values_mut() {
+                        fill(value, skeleton, template, formats)?;
+                    }
+                }
+                Value::Array(values) => {
+                    for value in values.iter_mut() {
+                        fill(value, skeleton, template, formats)?;
+                    }
+                }
+                _ => {}
+            }
+
+            Some(())
+        }
+
+        let mut value = self.value.clone();
+        fill(&mut value, self, template, (float_format, guid_format))?;
+
+        Some(value)
+    }
+}
+
+/// Pushes the tokens of `definition` to `tokens`, with placeholders instead of it's substitutions
+/// (or the tokens of the `nested` templates).
+///
+/// `parent` is the index of the substitution `definition` is nested in.
+fn push_template_tokens<'a>(
+    definition: &BinXMLTemplateDefinition<'a>,
+    nested: &[(usize, &BinXMLTemplateDefinition<'a>)],
+    parent: Option<usize>,
+    tokens: &mut Vec<BinXMLDeserializedTokens<'a>>,
+    attributes: &mut HashMap<Slot, String>,
+    slots: &mut HashSet<Slot>,
+) -> Option<()> {
+    let mut in_start_element = false;
+    let mut attribute_name = None;
+    let mut indices = HashSet::new();
+
+    for token in definition.tokens.iter() {
+        let current_attribute = attribute_name.take();
+
+        match token {
+            BinXMLDeserializedTokens::TemplateInstance(_)
+            | BinXMLDeserializedTokens::EntityRef(_) => return None,
+            BinXMLDeserializedTokens::Value(value) => match value.as_ref() {
+                BinXmlValue::BinXmlType(_) | BinXmlValue::EvtXml => return None,
+                _ => tokens.push(token.clone()),
+            },
+            BinXMLDeserializedTokens::OpenStartElement(_) => {
+                in_start_element = true;
+                tokens.push(token.clone());
+            }
+            BinXMLDeserializedTokens::CloseStartElement
+            | BinXMLDeserializedTokens::CloseEmptyElement => {
+                in_start_element = false;
+                tokens.push(token.clone());
+            }
+            BinXMLDeserializedTokens::Attribute(attribute) => {
+                attribute_name = Some(attribute.name.as_str().to_string());
+                tokens.push(token.clone());
+            }
+            BinXMLDeserializedTokens::Substitution(descriptor) if descriptor.ignore => {}
+            BinXMLDeserializedTokens::Substitution(descriptor) => {
+                let index = descriptor.substitution_index as usize;
+
+                // A substitution used twice is only filled the first time (see `expand_owned_template`).
+                if !indices.insert(index) {
+                    return None;
+                }
+
+                let slot = match parent {
+                    Some(parent) => Slot {
+                        index: parent,
+                        nested: Some(index),
+                    },
+                    None => Slot {
+                        index,
+                        nested: None,
+                    },
+                };
+
+                let nested_definition = match parent {
+                    Some(_) => None,
+                    None => nested
+                        .iter()
+                        .find(|(nested_index, _)| *nested_index == index)
+                        .map(|(_, definition)| *definition),
+                };
+
+                match (current_attribute, nested_definition) {
+                    (Some(name), None) => {
+                        attributes.insert(slot, name);
+                    }
+                    (None, None) if !in_start_element => {}
+                    (None, Some(nested_definition)) if !in_start_element => {
+                        push_template_tokens(
+                            nested_definition,
+                            &[],
+                            Some(index),
+                            tokens,
+                            attributes,
+                            slots,
+                        )?;
+                        continue;
+                    }
+                    _ => return None,
+                }
+
+                slots.insert(slot);
+                tokens.push(BinXMLDeserializedTokens::Value(Cow::Owned(
+                    BinXmlValue::StringType(Cow::Owned(slot.placeholder())),
+                )));
+            }
+            _ => tokens.push(token.clone()),
+        }
+    }
+
+    // Nested templates are only expected where the record has them.
+    if parent.is_none() && nested.iter().any(|(index, _)| !indices.contains(index)) {
+        return None;
+    }
+
+    Some(())
+}
+
+/// The shape of a record: it's template, the templates nested in it's substitutions,
+/// the setting changing the layout of the output (indentation for XML, separate attributes for JSON)
+/// and it's null substitutions (for JSON).
+type SkeletonKey = (String, Vec<(usize, String)>, bool, Vec<Slot>);
+
+type SkeletonMap<S> = RwLock<HashMap<SkeletonKey, Option<Arc<S>>>>;
+
+/// The rendered XML and JSON of records (see `XmlSkeleton` and `JsonSkeleton`), shared by all the chunks of a file.
+///
+/// Most records of a file are made of a handful of templates, so rendering each of them once
+/// saves walking the tokens of every record, see `ParserSettings::skeleton_cache`.
+#[derive(Debug, Default)]
+pub struct SkeletonCache {
+    xml: SkeletonMap<XmlSkeleton>,
+    json: SkeletonMap<JsonSkeleton>,
+}
+
+impl SkeletonCache {
+    pub fn new() -> Self {
+        SkeletonCache::default()
+    }
+
+    /// Renders a record made of `tokens` (a single template instance) as XML, using the skeleton of it's shape.
+    /// The skeleton is rendered on first use.
+    ///
+    /// Returns `None` if the record has to be rendered token by token.
+    pub(crate) fn render_xml(
+        &self,
+        tokens: &[BinXMLDeserializedTokens],
+        settings: &ParserSettings,
+    ) -> Option<String> {
+        let template = single_template(tokens)?;
+
+        // Clones of the settings share the cache, but might not indent the same way.
+        let skeleton = get_or_build(
+            &self.xml,
+            template,
+            settings.should_indent(),
+            &[],
+            |definition, nested| XmlSkeleton::build(definition, nested, settings),
+        )?;
+
+        skeleton.render(
+            template,
+            settings.get_float_format(),
+            settings.get_guid_format(),
+        )
+    }
+
+    /// Renders a record made of `tokens` (a single template instance) as JSON, using the skeleton of it's shape.
+    /// The skeleton is rendered on first use.
+    ///
+    /// Returns `None` if the record has to be rendered token by token.
+    pub(crate) fn render_json(
+        &self,
+        tokens: &[BinXMLDeserializedTokens],
+        settings: &ParserSettings,
+    ) -> Option<Value> {
+        let template = single_template(tokens)?;
+        let nulls = null_slots(template);
+
+        let skeleton = get_or_build(
+            &self.json,
+            template,
+            settings.should_separate_json_attributes(),
+            &nulls,
+            |definition, nested| JsonSkeleton::build(definition, nested, &nulls, settings),
+        )?;
+
+        skeleton.render(
+            template,
+            settings.get_float_format(),
+            settings.get_guid_format(),
+        )
+    }
+
+    /// Returns the number of cached record shapes (including those which cannot be rendered as skeletons).
+    pub fn len(&self) -> usize {
+        self.xml
+            .read()
+            .expect("skeleton cache lock is poisoned")
+            .len()
+            + self
+                .json
+                .read()
+                .expect("skeleton cache lock is poisoned")
+                .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Returns the slots of the null substitutions of `template`, and of the templates nested in it.
+fn null_slots(template: &BinXmlTemplate) -> Vec<Slot> {
+    let mut nulls = vec![];
+
+    for (index, value) in template.substitution_array.iter().enumerate() {
+        match value {
+            BinXmlValue::NullType => nulls.push(Slot {
+                index,
+                nested: None,
+            }),
+            BinXmlValue::BinXmlType(tokens) => {
+                if let Some(nested) = single_template(tokens) {
+                    for (nested_index, value) in nested.substitution_array.iter().enumerate() {
+                        if let BinXmlValue::NullType = value {
+                            nulls.push(Slot {
+                                index,
+                                nested: Some(nested_index),
+                            });
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    nulls
+}
+
+/// Returns the skeleton of the shape of `template` from `map`, building it on first use.
+fn get_or_build<'a, S>(
+    map: &SkeletonMap<S>,
+    template: &BinXmlTemplate<'a>,
+    layout: bool,
+    nulls: &[Slot],
+    build: impl FnOnce(
+        &BinXMLTemplateDefinition<'a>,
+        &[(usize, &BinXMLTemplateDefinition<'a>)],
+    ) -> Option<S>,
+) -> Option<Arc<S>> {
+    let nested = nested_templates(template)?;
+
+    let key = (
+        template.definition.template_guid.to_string(),
+        nested
+            .iter()
+            .map(|(index, definition)| (*index, definition.template_guid.to_string()))
+            .collect(),
+        layout,
+        nulls.to_vec(),
+    );
+
+    let cached = map
+        .read()
+        .expect("skeleton cache lock is poisoned")
+        .get(&key)
+        .cloned();
+
+    match cached {
+        Some(skeleton) => skeleton,
+        None => {
+            let skeleton = build(&template.definition, &nested).map(Arc::new);
+
+            map.write()
+                .expect("skeleton cache lock is poisoned")
+                .insert(key, skeleton.clone());
+
+            skeleton
+        }
+    }
+}
@@ -0,0 +1,312 @@
+//! Sorting of serialized records by timestamp, see `TimestampSorter`.
+//!
+//! Records are read in the physical order of their chunks, which is not their timestamp order
+//! when a log has wrapped around, or when records were written late.
+//! The sorter keeps a bounded number of records in memory, writing sorted runs of them
+//! to temporary files when it is full, and merges the runs when the records are read back.
+use crate::err::Result;
+use crate::evtx_record::SerializedEvtxRecord;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{DateTime, TimeZone, Utc};
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Used to name the temporary files of the sorters of this process.
+static NEXT_RUN: AtomicUsize = AtomicUsize::new(0);
+
+/// Sorts records by timestamp (then by record ID), keeping the order of records which are equal on both.
+///
+/// ```rust
+/// use evtx::{EvtxParser, TimestampSorter};
+///
+/// let mut parser = EvtxParser::from_path("samples/security.evtx").unwrap();
+/// let mut sorter = TimestampSorter::new().max_records_in_memory(1000);
+/// for record in parser.records() {
+///     sorter.push(record.unwrap()).unwrap();
+/// }
+///
+/// let records: Vec<_> = sorter.into_sorted().unwrap().map(|r| r.unwrap()).collect();
+/// assert!(records.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+/// ```
+#[derive(Debug)]
+pub struct TimestampSorter {
+    max_records_in_memory: usize,
+    spill_dir: PathBuf,
+    buffer: Vec<SerializedEvtxRecord<String>>,
+    runs: Vec<Run>,
+}
+
+impl Default for TimestampSorter {
+    fn default() -> Self {
+        TimestampSorter {
+            max_records_in_memory: 100_000,
+            spill_dir: std::env::temp_dir(),
+            buffer: vec![],
+            runs: vec![],
+        }
+    }
+}
+
+impl TimestampSorter {
+    /// Keeps up to 100,000 records in memory, and writes runs to the temporary directory of the system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of records kept in memory, before they are written to a temporary file (at least 1).
+    pub fn max_records_in_memory(mut self, max_records_in_memory: usize) -> Self {
+        self.max_records_in_memory = max_records_in_memory.max(1);
+        self
+    }
+
+    /// The directory of the temporary files, which are deleted when the sorter (or it's iterator) is dropped.
+    pub fn spill_dir(mut self, spill_dir: impl Into<PathBuf>) -> Self {
+        self.spill_dir = spill_dir.into();
+        self
+    }
+
+    pub fn push(&mut self, record: SerializedEvtxRecord<String>) -> Result<()> {
+        self.buffer.push(record);
+
+        if self.buffer.len() >= self.max_records_in_memory {
+            self.spill()?;
+        }
+
+        Ok(())
+    }
+
+    /// The number of temporary files written so far.
+    pub fn spilled_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Returns the records pushed so far, sorted.
+    pub fn into_sorted(mut self) -> Result<SortedRecords> {
+        self.buffer.sort_by_key(sort_key);
+
+        let mut sources: Vec<Source> = std::mem::take(&mut self.runs)
+            .into_iter()
+            .map(Source::Run)
+            .collect();
+        sources.push(Source::Memory(std::mem::take(&mut self.buffer).into_iter()));
+
+        let heads = sources
+            .iter_mut()
+            .map(Source::next)
+            .collect::<io::Result<_>>()?;
+
+        Ok(SortedRecords { sources, heads })
+    }
+
+    /// Writes the records in memory to a temporary file, sorted.
+    fn spill(&mut self) -> Result<()> {
+        self.buffer.sort_by_key(sort_key);
+
+        let path = self.spill_dir.join(format!(
+            "evtx-sort-{}-{}.tmp",
+            std::process::id(),
+            NEXT_RUN.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        // The run deletes the file if writing it fails.
+        let mut run = Run { reader: None, path };
+
+        let mut writer = BufWriter::new(File::create(&run.path)?);
+        for record in self.buffer.drain(..) {
+            write_record(&mut writer, &record)?;
+        }
+        writer.flush()?;
+        drop(writer);
+
+        run.reader = Some(BufReader::new(File::open(&run.path)?));
+        self.runs.push(run);
+
+        Ok(())
+    }
+}
+
+fn sort_key(record: &SerializedEvtxRecord<String>) -> (DateTime<Utc>, u64) {
+    (record.timestamp, record.event_record_id)
+}
+
+/// A temporary file with sorted records.
+#[derive(Debug)]
+struct Run {
+    reader: Option<BufReader<File>>,
+    path: PathBuf,
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        // The file must be closed before it can be removed on Windows.
+        self.reader.take();
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[derive(Debug)]
+enum Source {
+    Run(Run),
+    Memory(std::vec::IntoIter<SerializedEvtxRecord<String>>),
+}
+
+impl Source {
+    fn next(&mut self) -> io::Result<Option<SerializedEvtxRecord<String>>> {
+        match self {
+            Source::Run(run) => read_record(run.reader.as_mut().expect("runs are read back")),
+            Source::Memory(records) => Ok(records.next()),
+        }
+    }
+}
+
+/// An iterator over sorted records, see `TimestampSorter::into_sorted`.
+#[derive(Debug)]
+pub struct SortedRecords {
+    sources: Vec<Source>,
+    /// The next record of every source.
+    heads: Vec<Option<SerializedEvtxRecord<String>>>,
+}
+
+impl Iterator for SortedRecords {
+    type Item = Result<SerializedEvtxRecord<String>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Sources are in the order their records were pushed, so the first of equal records is taken.
+        let (i, _) = self
+            .heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, head)| head.as_ref().map(|record| (i, sort_key(record))))
+            .min_by_key(|(_, key)| *key)?;
+
+        let next = match self.sources[i].next() {
+            Ok(next) => next,
+            Err(e) => {
+                self.heads[i] = None;
+                return Some(Err(e.into()));
+            }
+        };
+
+        std::mem::replace(&mut self.heads[i], next).map(Ok)
+    }
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_u64::<LittleEndian>(bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let len = reader.read_u64::<LittleEndian>()?;
+    let mut bytes = vec![];
+    reader.take(len).read_to_end(&mut bytes)?;
+
+    String::from_utf8(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_record(writer: &mut impl Write, record: &SerializedEvtxRecord<String>) -> io::Result<()> {
+    writer.write_i64::<LittleEndian>(record.timestamp.timestamp())?;
+    writer.write_u32::<LittleEndian>(record.timestamp.timestamp_subsec_nanos())?;
+    writer.write_u64::<LittleEndian>(record.event_record_id)?;
+    writer.write_u8(u8::from(record.recovered))?;
+    write_bytes(writer, record.data.as_bytes())?;
+
+    writer.write_u32::<LittleEndian>(record.errors.len() as u32)?;
+    for error in &record.errors {
+        write_bytes(writer, error.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// Returns `None` at the end of the run.
+fn read_record(reader: &mut impl Read) -> io::Result<Option<SerializedEvtxRecord<String>>> {
+    let seconds = match reader.read_i64::<LittleEndian>() {
+        Ok(seconds) => seconds,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let nanoseconds = reader.read_u32::<LittleEndian>()?;
+    let event_record_id = reader.read_u64::<LittleEndian>()?;
+    let recovered = reader.read_u8()? != 0;
+    let data = read_string(reader)?;
+
+    let error_count = reader.read_u32::<LittleEndian>()?;
+    let errors = (0..error_count)
+        .map(|_| read_string(reader))
+        .collect::<io::Result<_>>()?;
+
+    Ok(Some(SerializedEvtxRecord {
+        event_record_id,
+        timestamp: Utc.timestamp(seconds, nanoseconds),
+        data,
+        recovered,
+        errors,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvtxParser;
+    use tempfile::tempdir;
+
+    fn records() -> Vec<SerializedEvtxRecord<String>> {
+        EvtxParser::from_path("samples/security.evtx")
+            .unwrap()
+            .records_json()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_sorts_records_spilling_to_disk() {
+        let dir = tempdir().unwrap();
+        let mut records = records();
+        // Out of order, with records of the same timestamp.
+        records.reverse();
+        records.rotate_left(100);
+
+        let mut sorter = TimestampSorter::new()
+            .max_records_in_memory(500)
+            .spill_dir(dir.path());
+        for record in records.iter().cloned() {
+            sorter.push(record).unwrap();
+        }
+        assert_eq!(sorter.spilled_runs(), records.len() / 500);
+
+        let mut sorted: Vec<_> = sorter.into_sorted().unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        records.sort_by_key(sort_key);
+        assert_eq!(sorted.len(), records.len());
+        assert_eq!(sorted, records);
+
+        sorted.dedup_by_key(|r| r.timestamp);
+        assert!(sorted.len() < records.len());
+    }
+
+    #[test]
+    fn test_keeps_the_order_of_equal_records() {
+        let timestamp = Utc.ymd(2020, 1, 1).and_hms(0, 0, 0);
+        let record = |data: &str| SerializedEvtxRecord {
+            event_record_id: 1,
+            timestamp,
+            data: data.to_string(),
+            recovered: false,
+            errors: vec!["error".to_string()],
+        };
+
+        let mut sorter = TimestampSorter::new().max_records_in_memory(2);
+        for data in &["a", "b", "c", "d", "e"] {
+            sorter.push(record(data)).unwrap();
+        }
+
+        let sorted: Vec<_> = sorter.into_sorted().unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(sorted, ["a", "b", "c", "d", "e"].map(record));
+    }
+}
@@ -0,0 +1,277 @@
+//! Splitting of EVTX files into smaller EVTX files, see `EvtxParser::split`.
+use crate::err::Result;
+use crate::evtx_parser::{EVTX_CHUNK_SIZE, EVTX_FILE_HEADER_SIZE};
+use crate::evtx_record::EvtxRecordOwned;
+use crate::evtx_writer::{EventElement, EvtxWriter};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use log::warn;
+
+use std::collections::btree_map::{BTreeMap, Entry};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+/// How the records are partitioned into files.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SplitBy {
+    /// One file per day (UTC) of the record timestamps, named `{prefix}-YYYY-MM-DD.evtx`.
+    Day,
+    /// Files of at most this many bytes (rounded down to whole chunks, but at least one chunk),
+    /// named `{prefix}-NNNN.evtx`.
+    Size(u64),
+    /// Files of at most this many records, named `{prefix}-NNNN.evtx`.
+    RecordCount(u64),
+}
+
+/// A file written by `EvtxParser::split`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SplitPart {
+    pub path: PathBuf,
+    pub records: u64,
+    pub first_record_id: u64,
+    pub last_record_id: u64,
+    pub first_timestamp: DateTime<Utc>,
+    pub last_timestamp: DateTime<Utc>,
+}
+
+impl SplitPart {
+    fn new(path: PathBuf) -> Self {
+        SplitPart {
+            path,
+            records: 0,
+            first_record_id: 0,
+            last_record_id: 0,
+            first_timestamp: Utc::now(),
+            last_timestamp: Utc::now(),
+        }
+    }
+
+    fn add(&mut self, record: &EvtxRecordOwned) {
+        if self.records == 0 {
+            self.first_record_id = record.event_record_id;
+            self.first_timestamp = record.timestamp;
+        }
+
+        self.records += 1;
+        self.last_record_id = record.event_record_id;
+        self.last_timestamp = record.timestamp;
+    }
+}
+
+/// A file being written.
+struct OpenPart {
+    part: SplitPart,
+    writer: EvtxWriter<File>,
+}
+
+impl OpenPart {
+    fn create(path: PathBuf) -> Result<Self> {
+        Ok(OpenPart {
+            writer: EvtxWriter::create(&path)?,
+            part: SplitPart::new(path),
+        })
+    }
+
+    fn finish(self) -> Result<SplitPart> {
+        self.writer.finish()?;
+        Ok(self.part)
+    }
+}
+
+/// Writes `records` to files in `dir`, keeping their record IDs and timestamps.
+/// Records which failed to parse are skipped (with a warning).
+pub(crate) fn split_records(
+    records: impl IntoIterator<Item = Result<EvtxRecordOwned>>,
+    split_by: SplitBy,
+    dir: &Path,
+    prefix: &str,
+) -> Result<Vec<SplitPart>> {
+    let records = records.into_iter().filter_map(|record| match record {
+        Ok(record) => Some(record),
+        Err(e) => {
+            warn!("Skipped a record which failed to parse: {}", e);
+            None
+        }
+    });
+
+    match split_by {
+        SplitBy::Day => split_by_day(records, dir, prefix),
+        SplitBy::Size(size) => {
+            let max_chunks =
+                (size.saturating_sub(EVTX_FILE_HEADER_SIZE as u64) / EVTX_CHUNK_SIZE as u64).max(1);
+            let max_size = EVTX_FILE_HEADER_SIZE as u64 + max_chunks * EVTX_CHUNK_SIZE as u64;
+
+            split_sequentially(records, dir, prefix, max_size, |_| false)
+        }
+        SplitBy::RecordCount(count) => {
+            split_sequentially(records, dir, prefix, u64::MAX, |part| part.records >= count)
+        }
+    }
+}
+
+/// Writes the records to a file per day, all of them being open at the same time
+/// (records are not expected to be sorted).
+fn split_by_day(
+    records: impl Iterator<Item = EvtxRecordOwned>,
+    dir: &Path,
+    prefix: &str,
+) -> Result<Vec<SplitPart>> {
+    let mut parts: BTreeMap<NaiveDate, OpenPart> = BTreeMap::new();
+
+    for record in records {
+        let day = record.timestamp.date().naive_utc();
+        let part = match parts.entry(day) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = dir.join(format!("{}-{}.evtx", prefix, day.format("%Y-%m-%d")));
+                entry.insert(OpenPart::create(path)?)
+            }
+        };
+
+        part.writer.copy_record(&record)?;
+        part.part.add(&record);
+    }
+
+    parts.into_values().map(OpenPart::finish).collect()
+}
+
+/// Writes the records to numbered files, starting a new file when the current one `is_full`,
+/// or when a record does not fit in it's last chunk and another chunk would make it larger than `max_size`.
+fn split_sequentially(
+    records: impl Iterator<Item = EvtxRecordOwned>,
+    dir: &Path,
+    prefix: &str,
+    max_size: u64,
+    is_full: impl Fn(&SplitPart) -> bool,
+) -> Result<Vec<SplitPart>> {
+    let path = |n: usize| dir.join(format!("{}-{:04}.evtx", prefix, n));
+    let mut parts = vec![];
+    let mut current: Option<OpenPart> = None;
+
+    for record in records {
+        let event = EventElement::from_record(&record)?;
+
+        let written = match current.as_mut() {
+            Some(part) if !is_full(&part.part) => {
+                let new_chunk = part.writer.file_size() < max_size;
+                part.writer.write_event(
+                    record.event_record_id,
+                    record.timestamp,
+                    &event,
+                    new_chunk,
+                )?
+            }
+            _ => false,
+        };
+
+        if !written {
+            if let Some(part) = current.take() {
+                parts.push(part.finish()?);
+            }
+
+            let mut part = OpenPart::create(path(parts.len() + 1))?;
+            part.writer
+                .write_event(record.event_record_id, record.timestamp, &event, true)?;
+            current = Some(part);
+        }
+
+        let part = current.as_mut().expect("a part is open");
+        part.part.add(&record);
+    }
+
+    if let Some(part) = current {
+        parts.push(part.finish()?);
+    }
+
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EvtxParser, ParserSettings};
+    use tempfile::{tempdir, TempDir};
+
+    const SAMPLE: &str = "samples/security.evtx";
+
+    /// Returns the XML records of the parts, checking their checksums.
+    fn records_of(parts: &[SplitPart]) -> Vec<(u64, String)> {
+        parts
+            .iter()
+            .flat_map(|part| {
+                let mut parser = EvtxParser::from_path(&part.path)
+                    .unwrap()
+                    .with_configuration(ParserSettings::new().validate_checksums(true));
+                let records: Vec<_> = parser
+                    .records()
+                    .map(|r| r.unwrap())
+                    .map(|r| (r.event_record_id, r.data))
+                    .collect();
+                assert_eq!(records.len() as u64, part.records);
+                records
+            })
+            .collect()
+    }
+
+    fn split(split_by: SplitBy) -> (TempDir, Vec<SplitPart>, Vec<(u64, String)>) {
+        let dir = tempdir().unwrap();
+        let parts = EvtxParser::from_path(SAMPLE)
+            .unwrap()
+            .split(split_by, dir.path(), "security")
+            .unwrap();
+
+        let records = records_of(&parts);
+        (dir, parts, records)
+    }
+
+    fn original() -> Vec<(u64, String)> {
+        EvtxParser::from_path(SAMPLE)
+            .unwrap()
+            .records()
+            .map(|r| r.unwrap())
+            .map(|r| (r.event_record_id, r.data))
+            .collect()
+    }
+
+    #[test]
+    fn test_split_by_record_count() {
+        let (_dir, parts, records) = split(SplitBy::RecordCount(1000));
+
+        assert_eq!(records, original());
+        assert_eq!(parts.len(), (records.len() + 999) / 1000);
+        assert!(parts[..parts.len() - 1].iter().all(|p| p.records == 1000));
+        assert_eq!(parts[1].first_record_id, records[1000].0);
+        assert!(parts[0].path.ends_with("security-0001.evtx"));
+    }
+
+    #[test]
+    fn test_split_by_size() {
+        let max_size = (EVTX_FILE_HEADER_SIZE + 2 * EVTX_CHUNK_SIZE) as u64;
+        let (_dir, parts, records) = split(SplitBy::Size(max_size + 100));
+
+        assert_eq!(records, original());
+        assert!(parts.len() > 1);
+        for part in &parts {
+            assert!(std::fs::metadata(&part.path).unwrap().len() <= max_size);
+        }
+    }
+
+    #[test]
+    fn test_split_by_day() {
+        let (_dir, parts, mut records) = split(SplitBy::Day);
+
+        for part in &parts {
+            assert_eq!(
+                part.first_timestamp.date(),
+                part.last_timestamp.date(),
+                "{}",
+                part.path.display()
+            );
+            let day = part.first_timestamp.format("%Y-%m-%d").to_string();
+            assert!(part.path.ends_with(format!("security-{}.evtx", day)));
+        }
+
+        records.sort();
+        assert_eq!(records, original());
+    }
+}
@@ -0,0 +1,243 @@
+//! Counters of the work done by the parser, see `ParserSettings::collect_stats`,
+//! and the progress of a parser through it's file, see `ParserSettings::on_progress`.
+use crate::evtx_parser::EVTX_CHUNK_SIZE;
+
+use once_cell::sync::OnceCell;
+
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// A snapshot of the counters of a parser.
+///
+/// Times are summed over all the threads, so with multiple threads they can be longer than `elapsed`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParserStats {
+    /// Chunks read from the file, including those which failed to parse (but not those which failed to be read).
+    pub chunks_read: u64,
+    pub chunks_parsed: u64,
+    /// Chunks which failed to be read or parsed.
+    pub chunks_failed: u64,
+    pub records_ok: u64,
+    pub records_failed: u64,
+    /// Records skipped by the filters of the settings.
+    pub records_filtered: u64,
+    pub bytes_read: u64,
+    /// Template instances whose definition was found in the template table of their chunk,
+    /// or was already read for an earlier record of the chunk.
+    pub template_cache_hits: u64,
+    /// Template instances whose definition had to be read from the record (or from another chunk).
+    pub template_cache_misses: u64,
+    /// XML records rendered from a skeleton, see `ParserSettings::skeleton_cache`.
+    pub xml_skeleton_hits: u64,
+    pub xml_skeleton_misses: u64,
+    /// JSON records rendered from a skeleton, see `ParserSettings::skeleton_cache`.
+    pub json_skeleton_hits: u64,
+    pub json_skeleton_misses: u64,
+    /// Reading (and validating the checksums of) chunks.
+    pub read_time: Duration,
+    /// Loading the string and template tables of chunks.
+    pub parse_time: Duration,
+    /// Deserializing the BinXML of records.
+    pub deserialize_time: Duration,
+    /// Rendering records to XML/JSON (or whatever the caller maps them to).
+    pub serialize_time: Duration,
+    /// Time since the first chunk was read.
+    pub elapsed: Duration,
+}
+
+impl ParserStats {
+    /// Records (successfully) parsed per second.
+    pub fn records_per_second(&self) -> f64 {
+        self.records_ok as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Megabytes read per second.
+    pub fn megabytes_per_second(&self) -> f64 {
+        self.bytes_read as f64 / (1024.0 * 1024.0) / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// The ratio of template instances using the template table of their chunk, `None` without template instances.
+    pub fn template_cache_hit_rate(&self) -> Option<f64> {
+        hit_rate(self.template_cache_hits, self.template_cache_misses)
+    }
+
+    /// The ratio of XML records rendered from a skeleton, `None` if no XML record used the skeleton cache.
+    pub fn xml_skeleton_hit_rate(&self) -> Option<f64> {
+        hit_rate(self.xml_skeleton_hits, self.xml_skeleton_misses)
+    }
+
+    /// The ratio of JSON records rendered from a skeleton, `None` if no JSON record used the skeleton cache.
+    pub fn json_skeleton_hit_rate(&self) -> Option<f64> {
+        hit_rate(self.json_skeleton_hits, self.json_skeleton_misses)
+    }
+}
+
+fn hit_rate(hits: u64, misses: u64) -> Option<f64> {
+    match hits + misses {
+        0 => None,
+        total => Some(hits as f64 / total as f64),
+    }
+}
+
+impl fmt::Display for ParserStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rate = |rate: Option<f64>| match rate {
+            Some(rate) => format!("{:.1}%", rate * 100.0),
+            None => "-".to_string(),
+        };
+
+        writeln!(
+            f,
+            "Chunks: {} read, {} parsed, {} failed",
+            self.chunks_read, self.chunks_parsed, self.chunks_failed
+        )?;
+        writeln!(
+            f,
+            "Records: {} ok, {} failed, {} filtered",
+            self.records_ok, self.records_failed, self.records_filtered
+        )?;
+        writeln!(
+            f,
+            "Throughput: {:.2} MB in {:.3}s ({:.0} records/s, {:.2} MB/s)",
+            self.bytes_read as f64 / (1024.0 * 1024.0),
+            self.elapsed.as_secs_f64(),
+            self.records_per_second(),
+            self.megabytes_per_second()
+        )?;
+        writeln!(
+            f,
+            "Template cache hit rate: {}, XML skeleton hit rate: {}, JSON skeleton hit rate: {}",
+            rate(self.template_cache_hit_rate()),
+            rate(self.xml_skeleton_hit_rate()),
+            rate(self.json_skeleton_hit_rate())
+        )?;
+        write!(
+            f,
+            "Time per stage: read {:.3}s, parse {:.3}s, deserialize {:.3}s, serialize {:.3}s",
+            self.read_time.as_secs_f64(),
+            self.parse_time.as_secs_f64(),
+            self.deserialize_time.as_secs_f64(),
+            self.serialize_time.as_secs_f64()
+        )
+    }
+}
+
+/// The progress of a parser through it's file, passed to the callback of `ParserSettings::on_progress`
+/// every time a chunk is read.
+///
+/// Chunks are read in the order of the file (unless `ParserSettings::order_by_record_id` is set),
+/// so the number of the chunk tells how much of the file was read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// The number of the chunk which was read.
+    pub chunk_number: u16,
+    /// The number of chunks of the file, see `EvtxParser::chunk_count` (dirty files can have more).
+    pub chunk_count: u16,
+    /// The number of records of the chunk (according to it's header), 0 if the chunk is empty or failed to be read.
+    pub records: u64,
+}
+
+impl Progress {
+    /// The bytes of the chunks read so far (when chunks are read in order).
+    pub fn bytes_read(&self) -> u64 {
+        (u64::from(self.chunk_number) + 1) * EVTX_CHUNK_SIZE as u64
+    }
+
+    /// The bytes of all the chunks of the file.
+    pub fn total_bytes(&self) -> u64 {
+        u64::from(self.chunk_count) * EVTX_CHUNK_SIZE as u64
+    }
+
+    /// The ratio of the chunks of the file read so far (from 0 to 1).
+    pub fn fraction(&self) -> f64 {
+        match self.chunk_count {
+            0 => 1.0,
+            count => (f64::from(self.chunk_number) + 1.0).min(f64::from(count)) / f64::from(count),
+        }
+    }
+}
+
+/// A counter of `StatsCollector`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Counter {
+    ChunksRead,
+    ChunksParsed,
+    ChunksFailed,
+    RecordsOk,
+    RecordsFailed,
+    RecordsFiltered,
+    BytesRead,
+    TemplateCacheHits,
+    TemplateCacheMisses,
+    XmlSkeletonHits,
+    XmlSkeletonMisses,
+    JsonSkeletonHits,
+    JsonSkeletonMisses,
+}
+
+/// A stage whose time is measured by `StatsCollector`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Stage {
+    Read,
+    Parse,
+    Deserialize,
+    Serialize,
+}
+
+/// The counters behind `ParserStats`, shared by all the threads of a parser.
+#[derive(Debug, Default)]
+pub(crate) struct StatsCollector {
+    counters: [AtomicU64; 13],
+    /// Nanoseconds spent in every stage.
+    stages: [AtomicU64; 4],
+    started: OnceCell<Instant>,
+}
+
+impl StatsCollector {
+    pub(crate) fn add(&self, counter: Counter, n: u64) {
+        self.counters[counter as usize].fetch_add(n, Ordering::Relaxed);
+    }
+
+    /// Runs `f`, adding the time it took to `stage`.
+    pub(crate) fn time<T>(&self, stage: Stage, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        self.started.get_or_init(|| start);
+
+        let result = f();
+
+        self.stages[stage as usize].fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+        result
+    }
+
+    pub(crate) fn snapshot(&self) -> ParserStats {
+        let counter = |counter: Counter| self.counters[counter as usize].load(Ordering::Relaxed);
+        let stage = |stage: Stage| {
+            Duration::from_nanos(self.stages[stage as usize].load(Ordering::Relaxed))
+        };
+
+        ParserStats {
+            chunks_read: counter(Counter::ChunksRead),
+            chunks_parsed: counter(Counter::ChunksParsed),
+            chunks_failed: counter(Counter::ChunksFailed),
+            records_ok: counter(Counter::RecordsOk),
+            records_failed: counter(Counter::RecordsFailed),
+            records_filtered: counter(Counter::RecordsFiltered),
+            bytes_read: counter(Counter::BytesRead),
+            template_cache_hits: counter(Counter::TemplateCacheHits),
+            template_cache_misses: counter(Counter::TemplateCacheMisses),
+            xml_skeleton_hits: counter(Counter::XmlSkeletonHits),
+            xml_skeleton_misses: counter(Counter::XmlSkeletonMisses),
+            json_skeleton_hits: counter(Counter::JsonSkeletonHits),
+            json_skeleton_misses: counter(Counter::JsonSkeletonMisses),
+            read_time: stage(Stage::Read),
+            parse_time: stage(Stage::Parse),
+            deserialize_time: stage(Stage::Deserialize),
+            serialize_time: stage(Stage::Serialize),
+            elapsed: self
+                .started
+                .get()
+                .map_or(Duration::default(), Instant::elapsed),
+        }
+    }
+}
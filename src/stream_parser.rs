@@ -0,0 +1,218 @@
+use crate::err::{self, Result};
+use crate::evtx_parser::{
+    chunk_from_data, serialize_chunk, Sampler, EVTX_CHUNK_SIZE, EVTX_FILE_HEADER_SIZE,
+};
+use crate::{EvtxChunkData, EvtxFileHeader, EvtxRecord, ParserSettings, SerializedEvtxRecord};
+
+use log::debug;
+use std::collections::VecDeque;
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+
+/// An evtx parser reading from a stream which cannot seek (for example stdin, or a pipe from
+/// an archive or a remote shell).
+///
+/// The file header and the chunks are read in order, one chunk at a time, so at most one chunk
+/// is held in memory. Features which need other parts of the file (such as `EvtxParser::validate`
+/// or `EvtxParser::tail_id_range`) are only available with `EvtxParser`.
+///
+/// ```rust
+/// use evtx::EvtxStreamParser;
+/// use std::fs::File;
+///
+/// let file = File::open("samples/security.evtx").unwrap();
+/// let mut parser = EvtxStreamParser::from_read(file).unwrap();
+/// assert_eq!(parser.records().count(), 2261);
+/// ```
+pub struct EvtxStreamParser<R: Read> {
+    data: R,
+    header: EvtxFileHeader,
+    config: Arc<ParserSettings>,
+}
+
+impl<R: Read> EvtxStreamParser<R> {
+    /// Reads the evtx file header from `data`, will fail if the header is invalid.
+    pub fn from_read(mut data: R) -> Result<Self> {
+        let mut header_data = vec![0; EVTX_FILE_HEADER_SIZE];
+        data.read_exact(&mut header_data)?;
+
+        let header = EvtxFileHeader::from_stream(&mut Cursor::new(header_data))?;
+
+        debug!("EVTX Header: {:#?}", header);
+        Ok(EvtxStreamParser {
+            data,
+            header,
+            config: Arc::new(ParserSettings::default()),
+        })
+    }
+
+    pub fn with_configuration(mut self, configuration: ParserSettings) -> Self {
+        self.config = Arc::new(configuration);
+        self
+    }
+
+    pub fn header(&self) -> &EvtxFileHeader {
+        &self.header
+    }
+
+    /// Reads the next chunk of the stream, see `EvtxParser::allocate_chunk`.
+    fn allocate_chunk(&mut self, chunk_number: u16) -> Result<Option<EvtxChunkData>> {
+        let mut chunk_data = Vec::with_capacity(EVTX_CHUNK_SIZE);
+
+        (&mut self.data)
+            .take(EVTX_CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk_data)?;
+
+        let chunk = chunk_from_data(
+            chunk_data,
+            chunk_number,
+            self.config.should_validate_checksums(),
+            self.config.should_repair_chunk_headers(),
+        );
+
+        self.config
+            .report_progress(chunk_number, self.header.chunk_count, &chunk);
+        chunk
+    }
+
+    /// Find the next chunk, staring at `chunk_number` (which must be the number of the next chunk of the stream).
+    /// Same as `EvtxParser::find_next_chunk`.
+    fn find_next_chunk(&mut self, mut chunk_number: u16) -> Option<(Result<EvtxChunkData>, u16)> {
+        loop {
+            match self.allocate_chunk(chunk_number) {
+                Err(err) => {
+                    // Dirty files can have more chunks than `chunk_count`,
+                    // but past it, a chunk which fails is taken as the end of the stream.
+                    if chunk_number >= self.header.chunk_count
+                        || matches!(err, err::Error::IncompleteChunk { .. })
+                    {
+                        return None;
+                    } else {
+                        return Some((Err(err), chunk_number));
+                    }
+                }
+                Ok(None) => {
+                    chunk_number = chunk_number.checked_add(1)?;
+                }
+                Ok(Some(chunk)) => {
+                    if let Some((earliest, latest)) = chunk.timestamp_bounds() {
+                        if !self.config.matches_time_bounds(&earliest, &latest) {
+                            debug!("Chunk {} is outside of the time range", chunk_number);
+                            chunk_number = chunk_number.checked_add(1)?;
+                            continue;
+                        }
+                    }
+
+                    return Some((Ok(chunk), chunk_number));
+                }
+            }
+        }
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will be mapped `f`, which must produce owned data from the records.
+    pub fn serialized_records<'a, U: 'a>(
+        &'a mut self,
+        mut f: impl FnMut(Result<EvtxRecord<'_>>) -> Result<U> + 'a,
+    ) -> impl Iterator<Item = Result<U>> + 'a {
+        let settings = Arc::clone(&self.config);
+        let mut sampler = Sampler::new(&settings);
+        let mut records = VecDeque::new();
+        let mut next_chunk_number = Some(0);
+
+        std::iter::from_fn(move || loop {
+            if let Some(record) = records.pop_front() {
+                return Some(record);
+            }
+
+            let (chunk, chunk_number) = self.find_next_chunk(next_chunk_number?)?;
+            next_chunk_number = chunk_number.checked_add(1);
+
+            serialize_chunk(chunk, Arc::clone(&settings), &mut f, |record| {
+                if sampler.keep(&record) {
+                    records.push_back(record);
+                }
+                true
+            });
+        })
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will be XML-formatted.
+    pub fn records(&mut self) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_records(|record| record.and_then(|record| record.into_xml()))
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will be JSON-formatted.
+    pub fn records_json(
+        &mut self,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<String>>> + '_ {
+        self.serialized_records(|record| record.and_then(|record| record.into_json()))
+    }
+
+    /// Return an iterator over all the records.
+    /// Records will have a `serde_json::Value` data attribute.
+    pub fn records_json_value(
+        &mut self,
+    ) -> impl Iterator<Item = Result<SerializedEvtxRecord<serde_json::Value>>> + '_ {
+        self.serialized_records(|record| record.and_then(|record| record.into_json_value()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EvtxParser;
+    use std::io;
+
+    /// Returns at most 1000 bytes per read, like a pipe.
+    struct Pipe<R: Read>(R);
+
+    impl<R: Read> Read for Pipe<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(1000);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    fn render(records: impl Iterator<Item = Result<SerializedEvtxRecord<String>>>) -> Vec<String> {
+        records
+            .map(|record| match record {
+                Ok(record) => record.data,
+                Err(e) => format!("error: {}", e),
+            })
+            .collect()
+    }
+
+    fn records(data: &[u8]) -> (Vec<String>, Vec<String>) {
+        let expected = render(EvtxParser::from_buffer(data.to_vec()).unwrap().records());
+        let streamed = render(EvtxStreamParser::from_read(Pipe(data)).unwrap().records());
+
+        (expected, streamed)
+    }
+
+    #[test]
+    fn test_streams_the_same_records() {
+        let (expected, streamed) = records(&std::fs::read("samples/security.evtx").unwrap());
+        assert_eq!(streamed.len(), 2261);
+        assert_eq!(streamed, expected);
+    }
+
+    #[test]
+    fn test_streams_files_with_bad_chunks() {
+        let data = std::fs::read("samples/sample_with_a_bad_chunk_magic.evtx").unwrap();
+        let (expected, streamed) = records(&data);
+        assert_eq!(streamed, expected);
+    }
+
+    #[test]
+    fn test_streams_truncated_files() {
+        let data = std::fs::read("samples/security.evtx").unwrap();
+        let (expected, streamed) =
+            records(&data[..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE + 10_000]);
+
+        assert!(streamed.len() > 1);
+        assert_eq!(streamed, expected);
+    }
+}
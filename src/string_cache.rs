@@ -1,35 +1,121 @@
 use crate::binxml::name::BinXmlName;
-use crate::err::Result;
 use crate::Offset;
 
+use once_cell::sync::OnceCell;
+
 use std::collections::HashMap;
+use std::convert::TryInto;
 use std::io::{Cursor, Seek, SeekFrom};
 
 pub type StringHash = u16;
 
-pub type CachedString = (String, StringHash, Offset);
+/// A name of the chunk's string table, only decoded the first time it is used.
+#[derive(Debug)]
+struct CachedString {
+    hash: StringHash,
+    /// The size of the name in the chunk, including it's header and null terminator.
+    size: Offset,
+    /// `None` if the name could not be decoded.
+    value: OnceCell<Option<String>>,
+}
 
 #[derive(Debug, Default)]
-pub struct StringCache(HashMap<Offset, CachedString>);
+pub struct StringCache<'chunk> {
+    data: &'chunk [u8],
+    strings: HashMap<Offset, CachedString>,
+}
+
+impl<'chunk> StringCache<'chunk> {
+    /// Reads the headers of the names at `offsets`.
+    /// Names which do not fit in `data` are not cached, they will fail when they are read.
+    pub fn populate(data: &'chunk [u8], offsets: &[Offset]) -> Self {
+        let mut strings = HashMap::new();
+
+        for &offset in offsets.iter().filter(|&&offset| offset > 0) {
+            // The next string offset (u32), the hash (u16) and the number of utf16 characters (u16).
+            let header = match data.get(offset as usize..offset as usize + 8) {
+                Some(header) => header,
+                None => continue,
+            };
 
-impl StringCache {
-    pub fn populate(data: &[u8], offsets: &[Offset]) -> Result<Self> {
-        let mut cache = HashMap::new();
-        let mut cursor = Cursor::new(data);
+            let hash = u16::from_le_bytes(header[4..6].try_into().expect("2 bytes"));
+            let n_characters = u16::from_le_bytes(header[6..8].try_into().expect("2 bytes"));
+            let size = 8 + (Offset::from(n_characters) + 1) * 2;
 
-        for offset in offsets.iter().filter(|&&offset| offset > 0) {
-            cursor.seek(SeekFrom::Start(u64::from(*offset)))?;
-            cache.insert(*offset, BinXmlName::from_stream(&mut cursor)?);
+            if offset as usize + size as usize > data.len() {
+                continue;
+            }
+
+            strings.insert(
+                offset,
+                CachedString {
+                    hash,
+                    size,
+                    value: OnceCell::new(),
+                },
+            );
         }
 
-        Ok(StringCache(cache))
+        StringCache { data, strings }
     }
 
-    pub fn get_string_and_hash(&self, offset: Offset) -> Option<&CachedString> {
-        self.0.get(&offset)
+    /// Returns the name at `offset`, it's hash and it's size in the chunk.
+    /// Returns `None` if the name is not cached (or invalid).
+    pub fn get_string_and_hash(&self, offset: Offset) -> Option<(&str, StringHash, Offset)> {
+        let cached = self.strings.get(&offset)?;
+
+        let value = cached.value.get_or_init(|| {
+            let mut cursor = Cursor::new(self.data);
+            cursor.seek(SeekFrom::Start(u64::from(offset))).ok()?;
+
+            BinXmlName::from_stream(&mut cursor)
+                .ok()
+                .map(|(name, _, _)| name)
+        });
+
+        value
+            .as_deref()
+            .map(|value| (value, cached.hash, cached.size))
     }
 
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.strings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evtx_parser::{EVTX_CHUNK_SIZE, EVTX_FILE_HEADER_SIZE};
+
+    #[test]
+    fn test_names_are_decoded_when_used() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+        let data = &evtx_file[EVTX_FILE_HEADER_SIZE..EVTX_FILE_HEADER_SIZE + EVTX_CHUNK_SIZE];
+        // The offsets of the string table are at 0x80 of the chunk header.
+        let offsets: Vec<Offset> = data[0x80..0x180]
+            .chunks(4)
+            .map(|offset| u32::from_le_bytes(offset.try_into().unwrap()))
+            .collect();
+
+        let cache = StringCache::populate(data, &offsets);
+        assert!(!cache.is_empty());
+        assert!(cache.strings.values().all(|s| s.value.get().is_none()));
+
+        for (&offset, cached) in &cache.strings {
+            let (name, hash, size) = cache.get_string_and_hash(offset).unwrap();
+
+            let mut cursor = Cursor::new(data);
+            cursor.seek(SeekFrom::Start(u64::from(offset))).unwrap();
+            assert_eq!(
+                BinXmlName::from_stream(&mut cursor).unwrap(),
+                (name.to_string(), hash, size)
+            );
+            assert!(cached.value.get().is_some());
+        }
     }
 }
@@ -0,0 +1,341 @@
+//! The standard symbolic names of `Level`, `Opcode`, `Task` and `Keywords` values (as defined in `winmeta.xml`),
+//! rendered alongside the numeric values with `ParserSettings::symbolic_names`.
+//!
+//! The names are added the way `wevtutil qe /f:RenderedXml` renders them, in a `RenderingInfo` element
+//! (a `RenderingInfo` object in JSON) at the end of the `Event`.
+//! Levels and keywords of the `Enrichment` of the settings take precedence over the standard names.
+//! The names defined in the manifest of the provider of a record (see `Enrichment::add_manifest`) come next,
+//! except those of the standard definitions (prefixed with `win:`), and the channel of the event is added.
+use crate::EvtxRecord;
+
+use serde_json::{Map, Value};
+
+/// Returns the name of a standard level.
+pub fn level_name(level: u8) -> Option<&'static str> {
+    match level {
+        // `win:LogAlways`, which the Event Viewer shows as `Information`.
+        0 => Some("Information"),
+        1 => Some("Critical"),
+        2 => Some("Error"),
+        3 => Some("Warning"),
+        4 => Some("Information"),
+        5 => Some("Verbose"),
+        _ => None,
+    }
+}
+
+/// Returns the standard levels with the given name (case insensitive),
+/// `Information` being both `win:LogAlways` (`0`) and `win:Informational` (`4`).
+pub fn levels_named(name: &str) -> Vec<u8> {
+    (0..=5)
+        .filter(|&level| level_name(level).map_or(false, |n| n.eq_ignore_ascii_case(name)))
+        .collect()
+}
+
+/// Returns the name of a standard opcode.
+pub fn opcode_name(opcode: u8) -> Option<&'static str> {
+    match opcode {
+        0 => Some("Info"),
+        1 => Some("Start"),
+        2 => Some("Stop"),
+        3 => Some("DCStart"),
+        4 => Some("DCStop"),
+        5 => Some("Extension"),
+        6 => Some("Reply"),
+        7 => Some("Resume"),
+        8 => Some("Suspend"),
+        9 => Some("Send"),
+        240 => Some("Receive"),
+        _ => None,
+    }
+}
+
+/// Returns the name of a task of `provider` (matched case insensitively).
+///
+/// Tasks are defined by every provider, only those of `Microsoft-Windows-Security-Auditing`
+/// (the audit subcategories) are known.
+pub fn task_name(provider: &str, task: u16) -> Option<&'static str> {
+    if task == 0 {
+        return Some("None");
+    }
+
+    if !provider.eq_ignore_ascii_case("Microsoft-Windows-Security-Auditing") {
+        return None;
+    }
+
+    SECURITY_TASKS
+        .binary_search_by_key(&task, |(task, _)| *task)
+        .ok()
+        .map(|index| SECURITY_TASKS[index].1)
+}
+
+/// Returns the names of the standard keywords set in `keywords`.
+pub fn keyword_names(keywords: u64) -> Vec<&'static str> {
+    KEYWORDS
+        .iter()
+        .filter(|(mask, _)| keywords & mask != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+const KEYWORDS: &[(u64, &str)] = &[
+    (0x0001_0000_0000_0000, "Response Time"),
+    (0x0002_0000_0000_0000, "WDI Context"),
+    (0x0004_0000_0000_0000, "WDI Diag"),
+    (0x0008_0000_0000_0000, "SQM"),
+    (0x0010_0000_0000_0000, "Audit Failure"),
+    (0x0020_0000_0000_0000, "Audit Success"),
+    (0x0040_0000_0000_0000, "Correlation Hint"),
+    (0x0080_0000_0000_0000, "Classic"),
+];
+
+/// Sorted by task.
+const SECURITY_TASKS: &[(u16, &str)] = &[
+    (12288, "Security State Change"),
+    (12289, "Security System Extension"),
+    (12290, "System Integrity"),
+    (12291, "IPsec Driver"),
+    (12292, "Other System Events"),
+    (12544, "Logon"),
+    (12545, "Logoff"),
+    (12546, "Account Lockout"),
+    (12547, "IPsec Main Mode"),
+    (12548, "Special Logon"),
+    (12549, "IPsec Quick Mode"),
+    (12550, "IPsec Extended Mode"),
+    (12551, "Other Logon/Logoff Events"),
+    (12552, "Network Policy Server"),
+    (12553, "User / Device Claims"),
+    (12554, "Group Membership"),
+    (12800, "File System"),
+    (12801, "Registry"),
+    (12802, "Kernel Object"),
+    (12803, "SAM"),
+    (12804, "Other Object Access Events"),
+    (12805, "Certification Services"),
+    (12806, "Application Generated"),
+    (12807, "Handle Manipulation"),
+    (12808, "File Share"),
+    (12809, "Filtering Platform Packet Drop"),
+    (12810, "Filtering Platform Connection"),
+    (12811, "Detailed File Share"),
+    (12812, "Removable Storage"),
+    (12813, "Central Policy Staging"),
+    (13056, "Sensitive Privilege Use"),
+    (13057, "Non Sensitive Privilege Use"),
+    (13058, "Other Privilege Use Events"),
+    (13312, "Process Creation"),
+    (13313, "Process Termination"),
+    (13314, "DPAPI Activity"),
+    (13315, "RPC Events"),
+    (13316, "Plug and Play Events"),
+    (13317, "Token Right Adjusted Events"),
+    (13568, "Audit Policy Change"),
+    (13569, "Authentication Policy Change"),
+    (13570, "Authorization Policy Change"),
+    (13571, "MPSSVC Rule-Level Policy Change"),
+    (13572, "Filtering Platform Policy Change"),
+    (13573, "Other Policy Change Events"),
+    (13824, "User Account Management"),
+    (13825, "Computer Account Management"),
+    (13826, "Security Group Management"),
+    (13827, "Distribution Group Management"),
+    (13828, "Application Group Management"),
+    (13829, "Other Account Management Events"),
+    (14080, "Directory Service Access"),
+    (14081, "Directory Service Changes"),
+    (14082, "Directory Service Replication"),
+    (14083, "Detailed Directory Service Replication"),
+    (14336, "Credential Validation"),
+    (14337, "Kerberos Service Ticket Operations"),
+    (14338, "Other Account Logon Events"),
+    (14339, "Kerberos Authentication Service"),
+];
+
+/// The symbolic names of the `System` values of a record.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(crate) struct RenderingInfo {
+    level: Option<String>,
+    opcode: Option<String>,
+    task: Option<String>,
+    channel: Option<String>,
+    keywords: Vec<String>,
+}
+
+impl RenderingInfo {
+    pub(crate) fn of_record(record: &EvtxRecord<'_>) -> Self {
+        let enrichment = record.settings.get_enrichment();
+        let provider = record.provider_name().unwrap_or_default();
+        let manifest = enrichment
+            .and_then(|enrichment| enrichment.manifest_provider(&record.provider_guid()?));
+        // Names of the standard definitions are left to the standard names.
+        let defined = |name: &str| !name.starts_with("win:");
+
+        let level = record.level().and_then(|level| {
+            enrichment
+                .and_then(|enrichment| enrichment.level_name(level))
+                .or_else(|| {
+                    manifest
+                        .and_then(|manifest| manifest.level_name(level))
+                        .filter(|name| defined(name))
+                })
+                .or_else(|| level_name(level))
+        });
+
+        let task = record.task().and_then(|task| {
+            manifest
+                .and_then(|manifest| manifest.task_name(task))
+                .filter(|name| defined(name))
+                .or_else(|| task_name(&provider, task))
+        });
+
+        let opcode = record.opcode().and_then(|opcode| {
+            manifest
+                .and_then(|manifest| manifest.opcode_name(record.task().unwrap_or(0), opcode))
+                .filter(|name| defined(name))
+                .or_else(|| opcode_name(opcode))
+        });
+
+        let channel = manifest.and_then(|manifest| {
+            let event = manifest.event(record.event_id()?, record.version().unwrap_or(0))?;
+            manifest.channel_name(event.channel)
+        });
+
+        let keywords = record
+            .keywords()
+            .map(|keywords| {
+                let mut names: Vec<&str> = enrichment
+                    .map(|enrichment| enrichment.keyword_names(keywords))
+                    .unwrap_or_default();
+                if names.is_empty() {
+                    names = manifest
+                        .map(|manifest| manifest.keyword_names(keywords))
+                        .unwrap_or_default();
+                    names.retain(|name| defined(name));
+                    names.extend(keyword_names(keywords));
+                }
+                names
+            })
+            .unwrap_or_default();
+
+        RenderingInfo {
+            level: level.map(str::to_string),
+            opcode: opcode.map(str::to_string),
+            task: task.map(str::to_string),
+            channel: channel.map(str::to_string),
+            keywords: keywords.into_iter().map(str::to_string).collect(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == RenderingInfo::default()
+    }
+
+    fn fields(&self) -> impl Iterator<Item = (&'static str, &String)> {
+        vec![
+            ("Level", self.level.as_ref()),
+            ("Task", self.task.as_ref()),
+            ("Opcode", self.opcode.as_ref()),
+            ("Channel", self.channel.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+    }
+
+    pub(crate) fn to_json(&self) -> Option<Value> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut object = Map::new();
+        for (name, value) in self.fields() {
+            object.insert(name.to_string(), Value::from(value.as_str()));
+        }
+        if !self.keywords.is_empty() {
+            object.insert("Keywords".to_string(), Value::from(self.keywords.clone()));
+        }
+
+        Some(Value::Object(object))
+    }
+
+    /// Inserts a `RenderingInfo` element as the last child of the `Event` element of `xml`.
+    pub(crate) fn insert_into_xml(&self, xml: &mut String, indent: bool) {
+        let position = match xml.rfind("</Event>") {
+            Some(position) if !self.is_empty() => position,
+            _ => return,
+        };
+
+        let (newline, indentation) = if indent { ("\n", "  ") } else { ("", "") };
+        let line = |depth: usize, content: &str| {
+            format!("{}{}{}", indentation.repeat(depth), content, newline)
+        };
+
+        let mut element = line(1, "<RenderingInfo>");
+        for (name, value) in self.fields() {
+            element += &line(2, &format!("<{0}>{1}</{0}>", name, escape(value)));
+        }
+        if !self.keywords.is_empty() {
+            element += &line(2, "<Keywords>");
+            for keyword in &self.keywords {
+                element += &line(3, &format!("<Keyword>{}</Keyword>", escape(keyword)));
+            }
+            element += &line(2, "</Keywords>");
+        }
+        element += &line(1, "</RenderingInfo>");
+
+        xml.insert_str(position, &element);
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symbolic_names() {
+        assert_eq!(level_name(3), Some("Warning"));
+        assert_eq!(levels_named("warning"), vec![3]);
+        assert_eq!(levels_named("Information"), vec![0, 4]);
+        assert!(levels_named("Debug").is_empty());
+        assert_eq!(opcode_name(240), Some("Receive"));
+        assert_eq!(opcode_name(100), None);
+        assert_eq!(
+            task_name("microsoft-windows-security-auditing", 12544),
+            Some("Logon")
+        );
+        assert_eq!(task_name("Microsoft-Windows-Sysmon", 12544), None);
+        assert_eq!(task_name("Microsoft-Windows-Sysmon", 0), Some("None"));
+        assert_eq!(
+            keyword_names(0x8090_0000_0000_0000),
+            vec!["Audit Failure", "Classic"]
+        );
+    }
+
+    #[test]
+    fn test_security_tasks_are_sorted() {
+        assert!(SECURITY_TASKS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_insert_into_xml() {
+        let info = RenderingInfo {
+            level: Some("Information".to_string()),
+            keywords: vec!["Audit Success".to_string()],
+            ..RenderingInfo::default()
+        };
+
+        let mut xml = "<Event><System></System></Event>".to_string();
+        info.insert_into_xml(&mut xml, false);
+        assert_eq!(
+            xml,
+            "<Event><System></System><RenderingInfo><Level>Information</Level>\
+             <Keywords><Keyword>Audit Success</Keyword></Keywords></RenderingInfo></Event>"
+        );
+    }
+}
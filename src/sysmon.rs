@@ -0,0 +1,396 @@
+//! Typed Sysmon events (`Microsoft-Windows-Sysmon` provider).
+//!
+//! `SysmonEvent::from_record` deserializes the `EventData` of the common Sysmon events into a structure
+//! per event type, with the `Hashes` split into individual fields and the `UtcTime` (and `CreationUtcTime`)
+//! parsed as UTC timestamps.
+//!
+//! With `ParserSettings::sysmon_events`, the typed event is added to the JSON output of Sysmon records,
+//! in an `Event.Sysmon` object keyed by event type:
+//!
+//! ```json
+//! "Sysmon": {
+//!   "ProcessCreate": {
+//!     "UtcTime": "2017-12-26T13:11:33.753Z",
+//!     "Image": "C:\\Windows\\SysWOW64\\PING.EXE",
+//!     "Hashes": {"MD5": "A27A24553B2C36E139647F88DF738C35", "SHA256": "...", "IMPHASH": "..."},
+//!     ...
+//!   }
+//! }
+//! ```
+use crate::err::Result;
+use crate::guid::Guid;
+use crate::EvtxRecord;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use std::str::FromStr;
+
+const SYSMON_PROVIDER: &str = "Microsoft-Windows-Sysmon";
+
+/// The hashes of a file, parsed from `SHA1=...,MD5=...,SHA256=...,IMPHASH=...`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct Hashes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub md5: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha1: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imphash: Option<String>,
+}
+
+/// Parses the `ALGORITHM=hash` pairs of Sysmon, unknown algorithms are ignored.
+impl FromStr for Hashes {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut hashes = Hashes::default();
+
+        for pair in s.split(',') {
+            let (algorithm, hash) = match pair.split_once('=') {
+                Some((algorithm, hash)) => (algorithm.trim(), hash.trim().to_string()),
+                None => continue,
+            };
+
+            if algorithm.eq_ignore_ascii_case("MD5") {
+                hashes.md5 = Some(hash);
+            } else if algorithm.eq_ignore_ascii_case("SHA1") {
+                hashes.sha1 = Some(hash);
+            } else if algorithm.eq_ignore_ascii_case("SHA256") {
+                hashes.sha256 = Some(hash);
+            } else if algorithm.eq_ignore_ascii_case("IMPHASH") {
+                hashes.imphash = Some(hash);
+            }
+        }
+
+        Ok(hashes)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hashes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.parse().unwrap_or_default())
+    }
+}
+
+/// Parses the timestamps of Sysmon (`2017-12-26 13:11:33.753`, in UTC).
+pub fn parse_utc_time(s: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(s.trim(), "%Y-%m-%d %H:%M:%S%.f")
+        .ok()
+        .map(|time| DateTime::from_utc(time, Utc))
+}
+
+fn utc_time<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<DateTime<Utc>>, D::Error> {
+    let s = match Option::<String>::deserialize(deserializer)? {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+
+    parse_utc_time(&s)
+        .map(Some)
+        .ok_or_else(|| de::Error::custom(format!("invalid Sysmon timestamp `{}`", s)))
+}
+
+/// Event 1.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct ProcessCreate {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub process_guid: Option<Guid>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub file_version: Option<String>,
+    pub description: Option<String>,
+    pub product: Option<String>,
+    pub company: Option<String>,
+    pub original_file_name: Option<String>,
+    pub command_line: Option<String>,
+    pub current_directory: Option<String>,
+    pub user: Option<String>,
+    pub logon_guid: Option<Guid>,
+    pub logon_id: Option<u64>,
+    pub terminal_session_id: Option<u32>,
+    pub integrity_level: Option<String>,
+    pub hashes: Option<Hashes>,
+    pub parent_process_guid: Option<Guid>,
+    pub parent_process_id: Option<u32>,
+    pub parent_image: Option<String>,
+    pub parent_command_line: Option<String>,
+    pub parent_user: Option<String>,
+}
+
+/// Event 3.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct NetworkConnect {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub process_guid: Option<Guid>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub user: Option<String>,
+    pub protocol: Option<String>,
+    pub initiated: Option<bool>,
+    pub source_is_ipv6: Option<bool>,
+    pub source_ip: Option<String>,
+    pub source_hostname: Option<String>,
+    pub source_port: Option<u16>,
+    pub source_port_name: Option<String>,
+    pub destination_is_ipv6: Option<bool>,
+    pub destination_ip: Option<String>,
+    pub destination_hostname: Option<String>,
+    pub destination_port: Option<u16>,
+    pub destination_port_name: Option<String>,
+}
+
+/// Event 5.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct ProcessTerminate {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub process_guid: Option<Guid>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub user: Option<String>,
+}
+
+/// Event 6.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct DriverLoad {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub image_loaded: Option<String>,
+    pub hashes: Option<Hashes>,
+    pub signed: Option<bool>,
+    pub signature: Option<String>,
+    pub signature_status: Option<String>,
+}
+
+/// Event 7.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct ImageLoad {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub process_guid: Option<Guid>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub image_loaded: Option<String>,
+    pub file_version: Option<String>,
+    pub description: Option<String>,
+    pub product: Option<String>,
+    pub company: Option<String>,
+    pub original_file_name: Option<String>,
+    pub hashes: Option<Hashes>,
+    pub signed: Option<bool>,
+    pub signature: Option<String>,
+    pub signature_status: Option<String>,
+    pub user: Option<String>,
+}
+
+/// Event 8.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct CreateRemoteThread {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub source_process_guid: Option<Guid>,
+    pub source_process_id: Option<u32>,
+    pub source_image: Option<String>,
+    pub target_process_guid: Option<Guid>,
+    pub target_process_id: Option<u32>,
+    pub target_image: Option<String>,
+    pub new_thread_id: Option<u32>,
+    pub start_address: Option<String>,
+    pub start_module: Option<String>,
+    pub start_function: Option<String>,
+}
+
+/// Event 10.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct ProcessAccess {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    #[serde(rename = "SourceProcessGUID")]
+    pub source_process_guid: Option<Guid>,
+    pub source_process_id: Option<u32>,
+    pub source_thread_id: Option<u32>,
+    pub source_image: Option<String>,
+    #[serde(rename = "TargetProcessGUID")]
+    pub target_process_guid: Option<Guid>,
+    pub target_process_id: Option<u32>,
+    pub target_image: Option<String>,
+    pub granted_access: Option<u32>,
+    pub call_trace: Option<String>,
+}
+
+/// Event 11.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct FileCreate {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub process_guid: Option<Guid>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub target_filename: Option<String>,
+    #[serde(deserialize_with = "utc_time")]
+    pub creation_utc_time: Option<DateTime<Utc>>,
+    pub user: Option<String>,
+}
+
+/// Events 12 (key and value create and delete), 13 (value set) and 14 (key and value rename).
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct RegistryEvent {
+    /// `CreateKey`, `DeleteKey`, `SetValue`, `RenameKey`, ...
+    pub event_type: Option<String>,
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub process_guid: Option<Guid>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub target_object: Option<String>,
+    pub details: Option<String>,
+    pub new_name: Option<String>,
+    pub user: Option<String>,
+}
+
+/// Event 15.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct FileCreateStreamHash {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub process_guid: Option<Guid>,
+    pub process_id: Option<u32>,
+    pub image: Option<String>,
+    pub target_filename: Option<String>,
+    #[serde(deserialize_with = "utc_time")]
+    pub creation_utc_time: Option<DateTime<Utc>>,
+    pub hash: Option<Hashes>,
+    pub contents: Option<String>,
+    pub user: Option<String>,
+}
+
+/// Event 22.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct DnsQuery {
+    #[serde(deserialize_with = "utc_time")]
+    pub utc_time: Option<DateTime<Utc>>,
+    pub process_guid: Option<Guid>,
+    pub process_id: Option<u32>,
+    pub query_name: Option<String>,
+    pub query_status: Option<String>,
+    pub query_results: Option<String>,
+    pub image: Option<String>,
+    pub user: Option<String>,
+}
+
+/// A Sysmon event, by event type.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum SysmonEvent {
+    ProcessCreate(ProcessCreate),
+    NetworkConnect(NetworkConnect),
+    ProcessTerminate(ProcessTerminate),
+    DriverLoad(DriverLoad),
+    ImageLoad(ImageLoad),
+    CreateRemoteThread(CreateRemoteThread),
+    ProcessAccess(ProcessAccess),
+    FileCreate(FileCreate),
+    RegistryEvent(RegistryEvent),
+    FileCreateStreamHash(FileCreateStreamHash),
+    DnsQuery(DnsQuery),
+}
+
+impl SysmonEvent {
+    /// Deserializes the typed event of `record`, `None` if it is not one of the supported Sysmon events.
+    pub fn from_record(record: &EvtxRecord) -> Result<Option<SysmonEvent>> {
+        let is_sysmon = record.provider_name().map_or(false, |provider| {
+            provider.eq_ignore_ascii_case(SYSMON_PROVIDER)
+        });
+        if !is_sysmon {
+            return Ok(None);
+        }
+
+        let event = match record.event_id() {
+            Some(1) => SysmonEvent::ProcessCreate(record.deserialize()?),
+            Some(3) => SysmonEvent::NetworkConnect(record.deserialize()?),
+            Some(5) => SysmonEvent::ProcessTerminate(record.deserialize()?),
+            Some(6) => SysmonEvent::DriverLoad(record.deserialize()?),
+            Some(7) => SysmonEvent::ImageLoad(record.deserialize()?),
+            Some(8) => SysmonEvent::CreateRemoteThread(record.deserialize()?),
+            Some(10) => SysmonEvent::ProcessAccess(record.deserialize()?),
+            Some(11) => SysmonEvent::FileCreate(record.deserialize()?),
+            Some(12..=14) => SysmonEvent::RegistryEvent(record.deserialize()?),
+            Some(15) => SysmonEvent::FileCreateStreamHash(record.deserialize()?),
+            Some(22) => SysmonEvent::DnsQuery(record.deserialize()?),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(event))
+    }
+
+    /// Returns the `Event.Sysmon` object of the JSON output, without the missing fields.
+    pub(crate) fn to_json(&self) -> Value {
+        let mut value = serde_json::to_value(self).expect("Sysmon events are serializable");
+
+        if let Value::Object(event) = &mut value {
+            for fields in event.values_mut() {
+                if let Value::Object(fields) = fields {
+                    fields.retain(|_, value| !value.is_null());
+                }
+            }
+        }
+
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parses_hashes() {
+        let hashes: Hashes = "SHA1=CE8E487CB76704FE5FBB821A78A1E969EFBE71F9,MD5=A27A24553B2C36E139647F88DF738C35,IMPHASH=0EB64EACA8C951D760EEA1A941A2A3F7"
+            .parse()
+            .unwrap();
+
+        assert_eq!(
+            hashes,
+            Hashes {
+                md5: Some("A27A24553B2C36E139647F88DF738C35".to_string()),
+                sha1: Some("CE8E487CB76704FE5FBB821A78A1E969EFBE71F9".to_string()),
+                sha256: None,
+                imphash: Some("0EB64EACA8C951D760EEA1A941A2A3F7".to_string()),
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&hashes).unwrap()["MD5"],
+            "A27A24553B2C36E139647F88DF738C35"
+        );
+        assert_eq!("-".parse::<Hashes>().unwrap(), Hashes::default());
+    }
+
+    #[test]
+    fn test_parses_utc_times() {
+        assert_eq!(
+            parse_utc_time("2017-12-26 13:11:33.753").map(|time| time.to_rfc3339()),
+            Some("2017-12-26T13:11:33.753+00:00".to_string())
+        );
+        assert_eq!(parse_utc_time("26/12/2017"), None);
+    }
+}
@@ -0,0 +1,285 @@
+//! An analysis of the record IDs and timestamps of a whole file, see `EvtxParser::tampering_report`.
+use crate::evtx_chunk::TimestampRegression;
+use crate::evtx_record::EvtxRecordHeader;
+
+use chrono::Duration;
+use serde_json::{json, Value};
+
+use std::fmt;
+
+/// Inconsistencies of the record IDs and timestamps across the file,
+/// which are typical of records deleted from (or injected into) a log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TamperingReport {
+    /// The number of record headers read.
+    pub records: u64,
+    /// The lowest record ID of the file.
+    pub first_record_id: Option<u64>,
+    /// The highest record ID of the file.
+    pub last_record_id: Option<u64>,
+    /// Ranges of record IDs missing between the first and the last record ID.
+    pub missing_record_ids: Vec<RecordIdRange>,
+    /// Record IDs found in more than one record.
+    pub duplicate_record_ids: Vec<DuplicateRecordId>,
+    /// Records with a timestamp earlier than the timestamp of the previous record ID (beyond a tolerance).
+    pub timestamp_regressions: Vec<TimestampRegression>,
+    /// Errors of the chunks which could not be read, whose records are missing from the analysis.
+    pub chunk_errors: Vec<String>,
+}
+
+/// An inclusive range of record IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordIdRange {
+    pub first: u64,
+    pub last: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateRecordId {
+    pub event_record_id: u64,
+    /// The number of records with this ID.
+    pub count: u64,
+}
+
+impl RecordIdRange {
+    /// The number of record IDs in the range.
+    pub fn count(&self) -> u64 {
+        self.last - self.first + 1
+    }
+}
+
+impl TamperingReport {
+    /// Analyzes the headers of the records of a file (in any order).
+    ///
+    /// Timestamps are compared in record ID order, so that the wraparound of a circular log is not reported.
+    pub(crate) fn from_record_headers(
+        mut headers: Vec<EvtxRecordHeader>,
+        chunk_errors: Vec<String>,
+        tolerance: Duration,
+    ) -> Self {
+        // Stable, so that duplicated IDs stay in file order.
+        headers.sort_by_key(|header| header.event_record_id);
+
+        let mut missing_record_ids = vec![];
+        let mut duplicate_record_ids: Vec<DuplicateRecordId> = vec![];
+        let mut timestamp_regressions = vec![];
+
+        for pair in headers.windows(2) {
+            let (previous, current) = (&pair[0], &pair[1]);
+
+            if current.event_record_id == previous.event_record_id {
+                match duplicate_record_ids.last_mut() {
+                    Some(duplicate) if duplicate.event_record_id == current.event_record_id => {
+                        duplicate.count += 1
+                    }
+                    _ => duplicate_record_ids.push(DuplicateRecordId {
+                        event_record_id: current.event_record_id,
+                        count: 2,
+                    }),
+                }
+                continue;
+            }
+
+            if current.event_record_id > previous.event_record_id + 1 {
+                missing_record_ids.push(RecordIdRange {
+                    first: previous.event_record_id + 1,
+                    last: current.event_record_id - 1,
+                });
+            }
+
+            if let Some(regression) = TimestampRegression::check(previous, current, tolerance) {
+                timestamp_regressions.push(regression);
+            }
+        }
+
+        TamperingReport {
+            records: headers.len() as u64,
+            first_record_id: headers.first().map(|header| header.event_record_id),
+            last_record_id: headers.last().map(|header| header.event_record_id),
+            missing_record_ids,
+            duplicate_record_ids,
+            timestamp_regressions,
+            chunk_errors,
+        }
+    }
+
+    /// Returns `true` if no inconsistency was found (and every chunk could be read).
+    pub fn is_clean(&self) -> bool {
+        self.missing_record_ids.is_empty()
+            && self.duplicate_record_ids.is_empty()
+            && self.timestamp_regressions.is_empty()
+            && self.chunk_errors.is_empty()
+    }
+
+    /// The total number of record IDs missing between the first and the last record ID.
+    pub fn missing_record_count(&self) -> u64 {
+        self.missing_record_ids
+            .iter()
+            .map(RecordIdRange::count)
+            .sum()
+    }
+
+    pub fn to_json_value(&self) -> Value {
+        let missing: Vec<Value> = self
+            .missing_record_ids
+            .iter()
+            .map(|range| json!({"first": range.first, "last": range.last, "count": range.count()}))
+            .collect();
+
+        let duplicates: Vec<Value> = self
+            .duplicate_record_ids
+            .iter()
+            .map(|duplicate| {
+                json!({"event_record_id": duplicate.event_record_id, "count": duplicate.count})
+            })
+            .collect();
+
+        let regressions: Vec<Value> = self
+            .timestamp_regressions
+            .iter()
+            .map(|regression| {
+                json!({
+                    "event_record_id": regression.event_record_id,
+                    "timestamp": regression.timestamp.to_rfc3339(),
+                    "previous_event_record_id": regression.previous_event_record_id,
+                    "previous_timestamp": regression.previous_timestamp.to_rfc3339(),
+                })
+            })
+            .collect();
+
+        json!({
+            "clean": self.is_clean(),
+            "records": self.records,
+            "first_record_id": self.first_record_id,
+            "last_record_id": self.last_record_id,
+            "missing_record_count": self.missing_record_count(),
+            "missing_record_ids": missing,
+            "duplicate_record_ids": duplicates,
+            "timestamp_regressions": regressions,
+            "chunk_errors": self.chunk_errors,
+        })
+    }
+}
+
+impl fmt::Display for TamperingReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for error in &self.chunk_errors {
+            writeln!(f, "Unreadable chunk: {}", error)?;
+        }
+        for range in &self.missing_record_ids {
+            if range.first == range.last {
+                writeln!(f, "Missing record ID {}", range.first)?;
+            } else {
+                writeln!(
+                    f,
+                    "Missing record IDs {}-{} ({} records)",
+                    range.first,
+                    range.last,
+                    range.count()
+                )?;
+            }
+        }
+        for duplicate in &self.duplicate_record_ids {
+            writeln!(
+                f,
+                "Duplicated record ID {} ({} records)",
+                duplicate.event_record_id, duplicate.count
+            )?;
+        }
+        for regression in &self.timestamp_regressions {
+            writeln!(
+                f,
+                "Record {} ({}) is earlier than record {} ({})",
+                regression.event_record_id,
+                regression.timestamp,
+                regression.previous_event_record_id,
+                regression.previous_timestamp
+            )?;
+        }
+
+        let range = match (self.first_record_id, self.last_record_id) {
+            (Some(first), Some(last)) => format!("record IDs {}-{}", first, last),
+            _ => "no records".to_string(),
+        };
+
+        write!(
+            f,
+            "{}: {} records ({}), {} missing, {} duplicated, {} timestamp regressions",
+            if self.is_clean() {
+                "CLEAN"
+            } else {
+                "SUSPICIOUS"
+            },
+            self.records,
+            range,
+            self.missing_record_count(),
+            self.duplicate_record_ids.len(),
+            self.timestamp_regressions.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn header(event_record_id: u64, seconds: i64) -> EvtxRecordHeader {
+        EvtxRecordHeader {
+            data_size: 0,
+            event_record_id,
+            timestamp: Utc.timestamp_opt(1_600_000_000 + seconds, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_reports_gaps_duplicates_and_regressions() {
+        // Record IDs 4-5 and 8 are missing, 3 is duplicated, and 7 goes back in time.
+        let headers = vec![
+            header(6, 60),
+            header(1, 10),
+            header(2, 20),
+            header(3, 30),
+            header(3, 30),
+            header(7, 0),
+            header(9, 90),
+        ];
+
+        let report = TamperingReport::from_record_headers(headers, vec![], Duration::zero());
+
+        assert!(!report.is_clean());
+        assert_eq!(report.records, 7);
+        assert_eq!(report.first_record_id, Some(1));
+        assert_eq!(report.last_record_id, Some(9));
+        assert_eq!(
+            report.missing_record_ids,
+            vec![
+                RecordIdRange { first: 4, last: 5 },
+                RecordIdRange { first: 8, last: 8 }
+            ]
+        );
+        assert_eq!(report.missing_record_count(), 3);
+        assert_eq!(
+            report.duplicate_record_ids,
+            vec![DuplicateRecordId {
+                event_record_id: 3,
+                count: 2
+            }]
+        );
+        let regressions: Vec<u64> = report
+            .timestamp_regressions
+            .iter()
+            .map(|regression| regression.event_record_id)
+            .collect();
+        assert_eq!(regressions, vec![7]);
+    }
+
+    #[test]
+    fn test_tolerates_small_regressions() {
+        let headers = vec![header(1, 10), header(2, 5)];
+
+        let report = TamperingReport::from_record_headers(headers, vec![], Duration::seconds(10));
+
+        assert!(report.is_clean(), "{}", report);
+    }
+}
@@ -6,42 +6,206 @@ use crate::Offset;
 pub use byteorder::{LittleEndian, ReadBytesExt};
 
 use encoding::EncodingRef;
+use log::{trace, warn};
 use std::collections::HashMap;
+use std::convert::TryInto;
 use std::io::{Cursor, Seek, SeekFrom};
+use std::sync::RwLock;
 
 pub type CachedTemplate<'chunk> = BinXMLTemplateDefinition<'chunk>;
 
 #[derive(Debug, Default)]
-pub struct TemplateCache<'chunk>(HashMap<Offset, CachedTemplate<'chunk>>);
+pub struct TemplateCache<'chunk> {
+    templates: HashMap<Offset, CachedTemplate<'chunk>>,
+    /// Templates missing from the chunk's `template_offsets` table (common in dirty logs),
+    /// read on demand at the offset referenced by a template instance.
+    resolved: RwLock<HashMap<Offset, CachedTemplate<'chunk>>>,
+}
 
 impl<'chunk> TemplateCache<'chunk> {
     pub fn new() -> Self {
-        TemplateCache(HashMap::new())
+        TemplateCache::default()
     }
 
     pub fn populate(
         data: &'chunk [u8],
         offsets: &[Offset],
         ansi_codec: EncodingRef,
+    ) -> Result<Self> {
+        TemplateCache::populate_with_file_cache(data, offsets, ansi_codec, None)
+    }
+
+    /// Like `populate`, but templates found in `file_cache` are copied from it instead of being parsed,
+    /// and the parsed ones are added to it.
+    ///
+    /// With a file cache, templates chained (by `next_template_offset`) to the ones in `offsets` are also read.
+    pub fn populate_with_file_cache(
+        data: &'chunk [u8],
+        offsets: &[Offset],
+        ansi_codec: EncodingRef,
+        file_cache: Option<&FileTemplateCache>,
     ) -> Result<Self> {
         let mut cache = HashMap::new();
         let mut cursor = Cursor::new(data);
 
-        for offset in offsets.iter().filter(|&&offset| offset > 0) {
-            cursor.seek(SeekFrom::Start(u64::from(*offset)))?;
+        for &head in offsets.iter().filter(|&&offset| offset > 0) {
+            let mut offset = head;
+
+            loop {
+                let guid = template_guid_at(data, offset);
+
+                let cached = match (file_cache, guid) {
+                    (Some(file_cache), Some(guid)) => file_cache.get(&guid),
+                    _ => None,
+                };
+
+                let definition = match cached {
+                    // The chain and the size (which depends on names being stored inline or not)
+                    // are specific to this chunk.
+                    Some(definition) => CachedTemplate {
+                        next_template_offset: read_u32_at(data, offset),
+                        data_size: read_u32_at(data, offset + 20),
+                        ..definition
+                    },
+                    None => {
+                        cursor.seek(SeekFrom::Start(u64::from(offset)))?;
+
+                        match read_template_definition(&mut cursor, None, ansi_codec) {
+                            Ok(definition) => {
+                                if let (Some(file_cache), Some(guid)) = (file_cache, guid) {
+                                    file_cache.insert(guid, &definition);
+                                }
+                                definition
+                            }
+                            Err(e) if offset == head => return Err(e),
+                            // A chain can lead to a corrupted definition, which is not an error
+                            // until a record uses it.
+                            Err(e) => {
+                                warn!("Failed to read chained template at {}: {}", offset, e);
+                                break;
+                            }
+                        }
+                    }
+                };
 
-            let definition = read_template_definition(&mut cursor, None, ansi_codec)?;
-            cache.insert(*offset, definition);
+                let next_offset = definition.next_template_offset;
+                cache.insert(offset, definition);
+
+                if file_cache.is_none() || next_offset == 0 || cache.contains_key(&next_offset) {
+                    break;
+                }
+
+                trace!(
+                    "Following template chain from {} to {}",
+                    offset,
+                    next_offset
+                );
+                offset = next_offset;
+            }
         }
 
-        Ok(TemplateCache(cache))
+        Ok(TemplateCache {
+            templates: cache,
+            resolved: RwLock::default(),
+        })
     }
 
     pub fn get_template(&self, offset: Offset) -> Option<&CachedTemplate<'chunk>> {
-        self.0.get(&offset)
+        self.templates.get(&offset)
+    }
+
+    /// Returns a copy of the template read on demand at `offset`, see `insert_resolved`.
+    pub(crate) fn get_resolved(&self, offset: Offset) -> Option<CachedTemplate<'chunk>> {
+        self.resolved
+            .read()
+            .expect("template cache lock is poisoned")
+            .get(&offset)
+            .cloned()
+    }
+
+    /// Caches a template which is not in the chunk's table, but was read at `offset` for a template instance.
+    pub(crate) fn insert_resolved(&self, offset: Offset, definition: &CachedTemplate<'chunk>) {
+        self.resolved
+            .write()
+            .expect("template cache lock is poisoned")
+            .insert(offset, definition.clone());
+    }
+
+    /// Returns the number of templates in the chunk's table (excluding the ones read on demand).
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+}
+
+/// The GUID of a template definition, as it's raw bytes.
+pub(crate) type TemplateGuid = [u8; 16];
+
+fn read_u32_at(data: &[u8], offset: Offset) -> u32 {
+    let start = offset as usize;
+    data.get(start..start + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map_or(0, u32::from_le_bytes)
+}
+
+/// Reads the GUID of the template definition at `offset` (after `next_template_offset`).
+pub(crate) fn template_guid_at(data: &[u8], offset: Offset) -> Option<TemplateGuid> {
+    let start = offset as usize + 4;
+    data.get(start..start + 16)?.try_into().ok()
+}
+
+/// Templates shared by all the chunks of a file, identified by their GUID.
+///
+/// Real logs use a handful of templates, which are otherwise parsed again in every chunk.
+/// Records whose template definition is missing from their chunk (for example in recovered chunks)
+/// can also be rendered using the template of another chunk, see `ParserSettings::file_template_cache`.
+#[derive(Debug, Default)]
+pub struct FileTemplateCache(RwLock<HashMap<TemplateGuid, CachedTemplate<'static>>>);
+
+impl FileTemplateCache {
+    pub fn new() -> Self {
+        FileTemplateCache::default()
     }
 
+    /// Returns a copy of the template with the given GUID.
+    pub(crate) fn get<'a>(&self, guid: &TemplateGuid) -> Option<CachedTemplate<'a>> {
+        let templates = self.0.read().expect("template cache lock is poisoned");
+        templates
+            .get(guid)
+            .map(|definition| definition.to_owned_definition())
+    }
+
+    /// Returns a copy of the template identified by `template_id` (the first 4 bytes of it's GUID),
+    /// as used by template instances. `None` if several cached templates share that identifier.
+    pub(crate) fn get_by_id<'a>(&self, template_id: u32) -> Option<CachedTemplate<'a>> {
+        let templates = self.0.read().expect("template cache lock is poisoned");
+
+        let mut matching = templates
+            .iter()
+            .filter(|(guid, _)| guid[..4] == template_id.to_le_bytes());
+
+        match (matching.next(), matching.next()) {
+            (Some((_, definition)), None) => Some(definition.to_owned_definition()),
+            _ => None,
+        }
+    }
+
+    /// Adds a copy of `definition`.
+    pub(crate) fn insert(&self, guid: TemplateGuid, definition: &CachedTemplate<'_>) {
+        self.0
+            .write()
+            .expect("template cache lock is poisoned")
+            .insert(guid, definition.to_owned_definition());
+    }
+
+    /// Returns the number of cached templates.
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.0
+            .read()
+            .expect("template cache lock is poisoned")
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 }
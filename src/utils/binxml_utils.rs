@@ -2,6 +2,7 @@ use crate::evtx_parser::ReadSeek;
 
 use crate::err::{self, Result};
 
+use bumpalo::Bump;
 use byteorder::{LittleEndian, ReadBytesExt};
 
 use encoding::{decode, DecoderTrap, EncodingRef};
@@ -14,7 +15,7 @@ pub fn read_len_prefixed_utf16_string<T: ReadSeek>(
     is_null_terminated: bool,
 ) -> io::Result<Option<String>> {
     let expected_number_of_characters = stream.read_u16::<LittleEndian>()?;
-    let needed_bytes = u64::from(expected_number_of_characters * 2);
+    let needed_bytes = u64::from(expected_number_of_characters) * 2;
 
     trace!(
         "Going to read a{}string of len {} from stream",
@@ -32,7 +33,8 @@ pub fn read_len_prefixed_utf16_string<T: ReadSeek>(
         stream.read_u16::<LittleEndian>()?;
     };
 
-    let s_len = s.as_ref().map(String::len).unwrap_or(0);
+    // The length prefix counts utf16 code units, not utf8 bytes.
+    let s_len = s.as_ref().map(|s| s.encode_utf16().count()).unwrap_or(0);
 
     if s_len == expected_number_of_characters as usize {
         Ok(s)
@@ -61,6 +63,45 @@ pub fn read_utf16_by_size<T: ReadSeek>(stream: &mut T, size: u64) -> io::Result<
     }
 }
 
+/// Same as `read_utf16_by_size`, but the string is decoded into `arena`
+/// (so reading it does not require it's own heap allocations).
+pub fn read_utf16_by_size_in<'b, T: ReadSeek>(
+    stream: &mut T,
+    size: u64,
+    arena: &'b Bump,
+) -> io::Result<Option<&'b str>> {
+    if size == 0 {
+        return Ok(None);
+    }
+
+    let len = size as usize / 2;
+    let mut s = bumpalo::collections::String::with_capacity_in(len, arena);
+
+    let mut read_error = None;
+    let units = (0..len).map_while(|_| match stream.read_u16::<LittleEndian>() {
+        Ok(unit) => Some(unit),
+        Err(e) => {
+            read_error = Some(e);
+            None
+        }
+    });
+
+    for c in decode_utf16(units) {
+        s.push(c.map_err(|_e| Error::from(ErrorKind::InvalidData))?);
+    }
+
+    if let Some(e) = read_error {
+        return Err(e);
+    }
+
+    // Strip nul terminator if needed
+    if s.ends_with('\0') {
+        s.pop();
+    }
+
+    Ok(Some(s.into_bump_str()))
+}
+
 /// Reads an ansi encoded string from the given stream using `ansi_codec`.
 pub fn read_ansi_encoded_string<T: ReadSeek>(
     stream: &mut T,
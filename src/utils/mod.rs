@@ -4,7 +4,7 @@ mod time;
 
 pub use self::binxml_utils::{
     read_ansi_encoded_string, read_len_prefixed_utf16_string, read_null_terminated_utf16_string,
-    read_utf16_by_size,
+    read_utf16_by_size, read_utf16_by_size_in,
 };
 pub use self::hexdump::{dump_cursor, print_hexdump};
-pub use self::time::read_systemtime;
+pub use self::time::{read_systemtime, to_filetime, to_systemtime};
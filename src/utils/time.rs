@@ -1,5 +1,5 @@
 use crate::err::{self, Result};
-use snafu::ResultExt;
+use snafu::{OptionExt, ResultExt};
 
 use crate::evtx_parser::ReadSeek;
 use byteorder::ReadBytesExt;
@@ -15,13 +15,55 @@ pub fn read_systemtime<R: ReadSeek>(r: &mut R) -> Result<DateTime<Utc>> {
     let second = try_read!(r, u16);
     let milliseconds = try_read!(r, u16);
 
-    Ok(DateTime::from_utc(
-        NaiveDate::from_ymd(i32::from(year), u32::from(month), u32::from(day)).and_hms_nano(
-            u32::from(hour),
-            u32::from(minute),
-            u32::from(second),
-            u32::from(milliseconds),
-        ),
-        Utc,
-    ))
+    let datetime = NaiveDate::from_ymd_opt(i32::from(year), u32::from(month), u32::from(day))
+        .and_then(|date| {
+            date.and_hms_milli_opt(
+                u32::from(hour),
+                u32::from(minute),
+                u32::from(second),
+                u32::from(milliseconds),
+            )
+        })
+        .context(err::InvalidSystemTime {
+            value: format!(
+                "{}-{}-{} {}:{}:{}.{}",
+                year, month, day, hour, minute, second, milliseconds
+            ),
+            offset: r.tell().unwrap_or(0),
+        })?;
+
+    Ok(DateTime::from_utc(datetime, Utc))
+}
+
+/// Seconds between the FILETIME epoch (1601-01-01) and the Unix epoch.
+const FILETIME_EPOCH_OFFSET: i64 = 11_644_473_600;
+
+/// Returns the FILETIME (100 nanosecond intervals since 1601-01-01) of `datetime`, 0 for earlier dates.
+pub fn to_filetime(datetime: &DateTime<Utc>) -> u64 {
+    let seconds = datetime.timestamp() + FILETIME_EPOCH_OFFSET;
+    if seconds < 0 {
+        return 0;
+    }
+
+    seconds as u64 * 10_000_000 + u64::from(datetime.timestamp_subsec_nanos() / 100)
+}
+
+/// Returns the SYSTEMTIME structure of `datetime` (to the millisecond), see `read_systemtime`.
+pub fn to_systemtime(datetime: &DateTime<Utc>) -> [u8; 16] {
+    let fields = [
+        datetime.year() as u16,
+        datetime.month() as u16,
+        datetime.weekday().num_days_from_sunday() as u16,
+        datetime.day() as u16,
+        datetime.hour() as u16,
+        datetime.minute() as u16,
+        datetime.second() as u16,
+        (datetime.timestamp_subsec_millis() % 1000) as u16,
+    ];
+
+    let mut bytes = [0; 16];
+    for (i, field) in fields.iter().enumerate() {
+        bytes[i * 2..i * 2 + 2].copy_from_slice(&field.to_le_bytes());
+    }
+    bytes
 }
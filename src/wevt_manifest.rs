@@ -0,0 +1,704 @@
+//! Parsing of the instrumentation manifests of providers, found in `WEVT_TEMPLATE` resources
+//! (see `wevt_templates`, with the `wevt_templates` feature).
+//!
+//! A manifest (`CRIM`) holds a `WEVT` element for every provider, which references the tables of the provider:
+//! event definitions (`EVNT`), templates (`TTBL`), channels (`CHAN`), levels (`LEVL`), tasks (`TASK`),
+//! opcodes (`OPCO`) and keywords (`KEYW`). All the offsets are relative to the start of the manifest.
+//! Messages are referenced by their ID in the message table of the provider binary.
+use crate::binxml::fragment::BinXmlFragmentDecoder;
+use crate::err::{self, Result};
+use crate::model::deserialized::BinXMLDeserializedTokens;
+
+use crate::guid::Guid;
+use log::{debug, warn};
+use snafu::{ensure, OptionExt};
+
+use std::convert::TryInto;
+
+const CRIM_HEADER_SIZE: u32 = 16;
+const WEVT_HEADER_SIZE: u32 = 20;
+const EVENT_DEFINITION_SIZE: u32 = 48;
+const TEMPLATE_HEADER_SIZE: u32 = 40;
+const TEMPLATE_ITEM_SIZE: u32 = 20;
+/// Marks a missing message.
+const NO_MESSAGE_ID: u32 = 0xffff_ffff;
+
+/// A parsed instrumentation manifest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WevtManifest {
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub providers: Vec<WevtProvider>,
+}
+
+/// The definitions of a single provider.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WevtProvider {
+    pub guid: Guid,
+    pub message_id: Option<u32>,
+    pub events: Vec<WevtEventDefinition>,
+    pub templates: Vec<WevtTemplate>,
+    pub channels: Vec<WevtNamedValue>,
+    pub levels: Vec<WevtNamedValue>,
+    pub tasks: Vec<WevtNamedValue>,
+    pub opcodes: Vec<WevtNamedValue>,
+    pub keywords: Vec<WevtNamedValue>,
+}
+
+/// The descriptor of an event (it's `System` values), with the message and the template of the event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WevtEventDefinition {
+    pub event_id: u16,
+    pub version: u8,
+    pub channel: u8,
+    pub level: u8,
+    pub opcode: u8,
+    pub task: u16,
+    pub keywords: u64,
+    pub message_id: Option<u32>,
+    /// The offset of the template of the event (see `WevtTemplate::offset`), `None` for events without data.
+    pub template_offset: Option<u32>,
+}
+
+/// A template, describing the `EventData` (or `UserData`) of events.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WevtTemplate {
+    /// The offset of the template in the manifest, referenced by event definitions.
+    pub offset: u32,
+    pub guid: Guid,
+    /// The BinXML of the template, with substitutions for the values of the fields.
+    pub tokens: Vec<BinXMLDeserializedTokens<'static>>,
+    pub fields: Vec<WevtTemplateField>,
+}
+
+/// A field of a template, the values of substitution `index` in it's BinXML.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WevtTemplateField {
+    pub name: String,
+    /// The type of the value in the record (see `BinXmlValueType`).
+    pub input_type: u8,
+    /// The type the value is rendered as (such as `xs:string` or `win:HexInt32`).
+    pub output_type: u8,
+    pub count: u16,
+    pub size: u16,
+}
+
+/// A named value of the provider, such as a level, a task, an opcode, a keyword (where `value` is the mask)
+/// or a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WevtNamedValue {
+    pub value: u64,
+    pub name: String,
+    pub message_id: Option<u32>,
+}
+
+impl WevtManifest {
+    /// Parses the manifest in `data` (the data of a `WEVT_TEMPLATE` resource, starting with `CRIM`).
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        expect_signature(data, 0, b"CRIM")?;
+
+        let major_version = read_u16(data, 8)?;
+        let minor_version = read_u16(data, 10)?;
+        let provider_count = read_u32(data, 12)?;
+
+        let mut providers = vec![];
+
+        for index in 0..provider_count {
+            let descriptor = CRIM_HEADER_SIZE + index * 20;
+            let guid = read_guid(data, descriptor)?;
+            let offset = read_u32(data, descriptor + 16)?;
+
+            debug!("Provider {} at offset {}", guid, offset);
+            providers.push(WevtProvider::from_bytes(data, guid, offset)?);
+        }
+
+        Ok(WevtManifest {
+            major_version,
+            minor_version,
+            providers,
+        })
+    }
+
+    /// Returns the provider with `guid`.
+    pub fn provider(&self, guid: &Guid) -> Option<&WevtProvider> {
+        self.providers
+            .iter()
+            .find(|provider| provider.guid == *guid)
+    }
+}
+
+impl WevtProvider {
+    fn from_bytes(data: &[u8], guid: Guid, offset: u32) -> Result<Self> {
+        expect_signature(data, offset, b"WEVT")?;
+
+        let mut provider = WevtProvider {
+            guid,
+            message_id: read_message_id(data, offset + 8)?,
+            events: vec![],
+            templates: vec![],
+            channels: vec![],
+            levels: vec![],
+            tasks: vec![],
+            opcodes: vec![],
+            keywords: vec![],
+        };
+
+        let descriptor_count = read_u32(data, offset + 12)?;
+
+        for index in 0..descriptor_count {
+            let element_offset = read_u32(data, offset + WEVT_HEADER_SIZE + index * 8)?;
+
+            match read_bytes(data, element_offset, 4)? {
+                b"EVNT" => provider.events = read_events(data, element_offset)?,
+                b"TTBL" => provider.templates = read_templates(data, element_offset)?,
+                b"CHAN" => provider.channels = read_channels(data, element_offset)?,
+                // Identifier (4 bytes), message ID and name offset.
+                b"LEVL" => provider.levels = read_named_values(data, element_offset, 12, 4, 8)?,
+                b"OPCO" => provider.opcodes = read_named_values(data, element_offset, 12, 4, 8)?,
+                // Identifier, message ID, MUI GUID and name offset.
+                b"TASK" => provider.tasks = read_named_values(data, element_offset, 28, 4, 24)?,
+                // Mask (8 bytes), message ID and name offset.
+                b"KEYW" => provider.keywords = read_named_values(data, element_offset, 16, 8, 12)?,
+                other => debug!(
+                    "Skipping element `{}` at offset {}",
+                    String::from_utf8_lossy(other),
+                    element_offset
+                ),
+            }
+        }
+
+        Ok(provider)
+    }
+
+    /// Returns the definition of the event with `event_id` and `version`.
+    pub fn event(&self, event_id: u16, version: u8) -> Option<&WevtEventDefinition> {
+        self.events
+            .iter()
+            .find(|event| event.event_id == event_id && event.version == version)
+    }
+
+    /// Returns the template of `event`, `None` if the event has no data.
+    pub fn template(&self, event: &WevtEventDefinition) -> Option<&WevtTemplate> {
+        let offset = event.template_offset?;
+        self.templates
+            .iter()
+            .find(|template| template.offset == offset)
+    }
+
+    /// Returns the names of the keywords set in `keywords`.
+    pub fn keyword_names(&self, keywords: u64) -> Vec<&str> {
+        self.keywords
+            .iter()
+            .filter(|keyword| keyword.value != 0 && keywords & keyword.value == keyword.value)
+            .map(|keyword| keyword.name.as_str())
+            .collect()
+    }
+
+    pub fn level_name(&self, level: u8) -> Option<&str> {
+        named_value(&self.levels, u64::from(level))
+    }
+
+    pub fn task_name(&self, task: u16) -> Option<&str> {
+        named_value(&self.tasks, u64::from(task))
+    }
+
+    /// Returns the name of `opcode`, which is either specific to `task` or defined for all tasks.
+    pub fn opcode_name(&self, task: u16, opcode: u8) -> Option<&str> {
+        // The task is in the high word of the identifier (`0` for opcodes of all tasks).
+        named_value(&self.opcodes, u64::from(task) << 16 | u64::from(opcode))
+            .or_else(|| named_value(&self.opcodes, u64::from(opcode)))
+    }
+
+    pub fn channel_name(&self, channel: u8) -> Option<&str> {
+        named_value(&self.channels, u64::from(channel))
+    }
+}
+
+fn named_value(values: &[WevtNamedValue], value: u64) -> Option<&str> {
+    values
+        .iter()
+        .find(|named| named.value == value)
+        .map(|named| named.name.as_str())
+}
+
+fn read_events(data: &[u8], offset: u32) -> Result<Vec<WevtEventDefinition>> {
+    let count = read_u32(data, offset + 8)?;
+    let mut events = Vec::with_capacity(count.min(1024) as usize);
+
+    for index in 0..count {
+        let event = offset + 16 + index * EVENT_DEFINITION_SIZE;
+        let template_offset = read_u32(data, event + 20)?;
+
+        events.push(WevtEventDefinition {
+            event_id: read_u16(data, event)?,
+            version: read_bytes(data, event + 2, 1)?[0],
+            channel: read_bytes(data, event + 3, 1)?[0],
+            level: read_bytes(data, event + 4, 1)?[0],
+            opcode: read_bytes(data, event + 5, 1)?[0],
+            task: read_u16(data, event + 6)?,
+            keywords: read_u64(data, event + 8)?,
+            message_id: read_message_id(data, event + 16)?,
+            template_offset: Some(template_offset).filter(|&offset| offset != 0),
+        });
+    }
+
+    Ok(events)
+}
+
+fn read_templates(data: &[u8], offset: u32) -> Result<Vec<WevtTemplate>> {
+    let count = read_u32(data, offset + 8)?;
+    let mut templates = vec![];
+    let mut template_offset = offset + 12;
+
+    for _ in 0..count {
+        expect_signature(data, template_offset, b"TEMP")?;
+
+        let size = read_u32(data, template_offset + 4)?;
+        ensure!(
+            size >= TEMPLATE_HEADER_SIZE,
+            err::InvalidWevtManifest {
+                offset: template_offset,
+                message: format!("template size {} is too small", size),
+            }
+        );
+
+        // The template must fit in the manifest.
+        read_bytes(data, template_offset, size)?;
+
+        templates.push(read_template(data, template_offset, size)?);
+        template_offset += size;
+    }
+
+    Ok(templates)
+}
+
+fn read_template(data: &[u8], offset: u32, size: u32) -> Result<WevtTemplate> {
+    let item_count = read_u32(data, offset + 8)?;
+    let items_offset = read_u32(data, offset + 16)?;
+    let guid = read_guid(data, offset + 24)?;
+
+    let binxml_offset = offset + TEMPLATE_HEADER_SIZE;
+    let binxml_end = if item_count > 0 {
+        items_offset
+    } else {
+        offset + size
+    };
+
+    // Names are stored inline in the BinXML of templates, referenced by their offset in the manifest.
+    let tokens = match BinXmlFragmentDecoder::new().decode(
+        data,
+        u64::from(binxml_offset),
+        Some(binxml_end.saturating_sub(binxml_offset)),
+    ) {
+        Ok(fragment) => fragment.tokens,
+        Err(e) => {
+            warn!("Failed to decode the BinXML of template {}: {}", guid, e);
+            vec![]
+        }
+    };
+
+    let mut fields = Vec::with_capacity(item_count.min(1024) as usize);
+
+    if item_count > 0 {
+        read_bytes(data, items_offset, TEMPLATE_ITEM_SIZE)?;
+    }
+
+    for index in 0..item_count {
+        let item = items_offset + index * TEMPLATE_ITEM_SIZE;
+
+        fields.push(WevtTemplateField {
+            input_type: read_bytes(data, item + 4, 1)?[0],
+            output_type: read_bytes(data, item + 5, 1)?[0],
+            count: read_u16(data, item + 12)?,
+            size: read_u16(data, item + 14)?,
+            name: read_name(data, read_u32(data, item + 16)?)?,
+        });
+    }
+
+    Ok(WevtTemplate {
+        offset,
+        guid,
+        tokens,
+        fields,
+    })
+}
+
+fn read_channels(data: &[u8], offset: u32) -> Result<Vec<WevtNamedValue>> {
+    let count = read_u32(data, offset + 8)?;
+    let mut channels = Vec::with_capacity(count.min(1024) as usize);
+
+    // Identifier, name offset, unknown and message ID.
+    for index in 0..count {
+        let entry = offset + 12 + index * 16;
+
+        channels.push(WevtNamedValue {
+            value: u64::from(read_u32(data, entry)?),
+            name: read_name(data, read_u32(data, entry + 4)?)?,
+            message_id: read_message_id(data, entry + 12)?,
+        });
+    }
+
+    Ok(channels)
+}
+
+/// Reads a table of `entry_size` entries, each starting with it's value (of `value_size` bytes),
+/// followed by it's message ID, and with the offset of it's name at `name_offset_position`.
+fn read_named_values(
+    data: &[u8],
+    offset: u32,
+    entry_size: u32,
+    value_size: u32,
+    name_offset_position: u32,
+) -> Result<Vec<WevtNamedValue>> {
+    let count = read_u32(data, offset + 8)?;
+    let mut values = Vec::with_capacity(count.min(1024) as usize);
+
+    for index in 0..count {
+        let entry = offset + 12 + index * entry_size;
+
+        let value = match value_size {
+            8 => read_u64(data, entry)?,
+            _ => u64::from(read_u32(data, entry)?),
+        };
+
+        values.push(WevtNamedValue {
+            value,
+            name: read_name(data, read_u32(data, entry + name_offset_position)?)?,
+            message_id: read_message_id(data, entry + value_size)?,
+        });
+    }
+
+    Ok(values)
+}
+
+/// Reads a name, stored as it's size (including the size itself) followed by a null terminated UTF-16 string.
+fn read_name(data: &[u8], offset: u32) -> Result<String> {
+    let size = read_u32(data, offset)?;
+    ensure!(
+        size >= 4,
+        err::InvalidWevtManifest {
+            offset,
+            message: format!("name size {} is too small", size),
+        }
+    );
+
+    let utf16: Vec<u16> = read_bytes(data, offset + 4, size - 4)?
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&c| c != 0)
+        .collect();
+
+    Ok(String::from_utf16_lossy(&utf16))
+}
+
+fn read_message_id(data: &[u8], offset: u32) -> Result<Option<u32>> {
+    Ok(Some(read_u32(data, offset)?).filter(|&id| id != NO_MESSAGE_ID))
+}
+
+fn expect_signature(data: &[u8], offset: u32, signature: &[u8; 4]) -> Result<()> {
+    let found = read_bytes(data, offset, 4)?;
+    ensure!(
+        found == signature,
+        err::InvalidWevtManifest {
+            offset,
+            message: format!(
+                "expected `{}`, found `{}`",
+                String::from_utf8_lossy(signature),
+                String::from_utf8_lossy(found)
+            ),
+        }
+    );
+    Ok(())
+}
+
+fn read_bytes(data: &[u8], offset: u32, size: u32) -> Result<&[u8]> {
+    data.get(offset as usize..offset as usize + size as usize)
+        .context(err::InvalidWevtManifest {
+            offset,
+            message: format!("{} bytes are out of bounds", size),
+        })
+}
+
+fn read_u16(data: &[u8], offset: u32) -> Result<u16> {
+    Ok(u16::from_le_bytes(
+        read_bytes(data, offset, 2)?.try_into().expect("2 bytes"),
+    ))
+}
+
+fn read_u32(data: &[u8], offset: u32) -> Result<u32> {
+    Ok(u32::from_le_bytes(
+        read_bytes(data, offset, 4)?.try_into().expect("4 bytes"),
+    ))
+}
+
+fn read_u64(data: &[u8], offset: u32) -> Result<u64> {
+    Ok(u64::from_le_bytes(
+        read_bytes(data, offset, 8)?.try_into().expect("8 bytes"),
+    ))
+}
+
+fn read_guid(data: &[u8], offset: u32) -> Result<Guid> {
+    Ok(Guid::from_bytes(
+        read_bytes(data, offset, 16)?.try_into().expect("16 bytes"),
+    ))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::binxml::value_variant::BinXmlValueType;
+    use crate::model::deserialized::BinXMLDeserializedTokens;
+
+    const PROVIDER_GUID: [u8; 16] = [
+        0xa8, 0x2d, 0x7c, 0x54, 0x4a, 0x5c, 0x49, 0x4c, 0x8a, 0x3b, 0x55, 0x8e, 0x0d, 0x13, 0x2d,
+        0x7b,
+    ];
+    const TEMPLATE_GUID: [u8; 16] = [0x11; 16];
+
+    fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    fn name(s: &str) -> Vec<u8> {
+        let mut name = utf16(s);
+        name.extend_from_slice(&[0, 0]);
+        let mut data = (name.len() as u32 + 4).to_le_bytes().to_vec();
+        data.extend(name);
+        data
+    }
+
+    fn patch_u32(data: &mut [u8], offset: usize, value: u32) {
+        data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Sets the offset of the element descriptor at `descriptor` to the end of `data`.
+    fn point_to_end(data: &mut [u8], descriptor: usize) {
+        let end = data.len() as u32;
+        patch_u32(data, descriptor, end);
+    }
+
+    /// `<Data>%1</Data>`, with the name of the element inline at `offset` in the manifest.
+    fn template_binxml(offset: u32) -> Vec<u8> {
+        let mut binxml = vec![0x0f, 0x01, 0x01, 0x00, 0x01, 0xff, 0xff];
+        binxml.extend(&0_u32.to_le_bytes());
+        let name_offset = offset + binxml.len() as u32 + 4;
+        binxml.extend(&name_offset.to_le_bytes());
+        binxml.extend(&0_u32.to_le_bytes());
+        binxml.extend(&0_u16.to_le_bytes());
+        binxml.extend(&4_u16.to_le_bytes());
+        binxml.extend(utf16("Data"));
+        binxml.extend(&[0, 0]);
+        binxml.extend(&[0x02, 0x0d, 0x00, 0x00, 0x01, 0x04, 0x00]);
+        binxml
+    }
+
+    /// A manifest with a single provider, with a level, a keyword, a task, a template and two events.
+    pub(crate) fn sample_manifest() -> Vec<u8> {
+        let mut data = b"CRIM".to_vec();
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&3_u16.to_le_bytes());
+        data.extend(&1_u16.to_le_bytes());
+        data.extend(&1_u32.to_le_bytes());
+        data.extend(&PROVIDER_GUID);
+        data.extend(&36_u32.to_le_bytes());
+
+        // The provider, with 5 elements.
+        data.extend(b"WEVT");
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&100_u32.to_le_bytes());
+        data.extend(&5_u32.to_le_bytes());
+        data.extend(&0_u32.to_le_bytes());
+        let descriptors = data.len();
+        data.extend(vec![0; 5 * 8]);
+
+        // Levels.
+        point_to_end(&mut data, descriptors);
+        let levels = data.len();
+        data.extend(b"LEVL");
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&1_u32.to_le_bytes());
+        data.extend(&4_u32.to_le_bytes());
+        data.extend(&0x5000_0004_u32.to_le_bytes());
+        data.extend(&(levels as u32 + 24).to_le_bytes());
+        data.extend(name("win:Informational"));
+
+        // Keywords.
+        point_to_end(&mut data, descriptors + 8);
+        let keywords = data.len();
+        data.extend(b"KEYW");
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&1_u32.to_le_bytes());
+        data.extend(&0x10_u64.to_le_bytes());
+        data.extend(&NO_MESSAGE_ID.to_le_bytes());
+        data.extend(&(keywords as u32 + 28).to_le_bytes());
+        data.extend(name("Logon"));
+
+        // Tasks.
+        point_to_end(&mut data, descriptors + 16);
+        let tasks = data.len();
+        data.extend(b"TASK");
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&1_u32.to_le_bytes());
+        data.extend(&12544_u32.to_le_bytes());
+        data.extend(&NO_MESSAGE_ID.to_le_bytes());
+        data.extend(&[0; 16]);
+        data.extend(&(tasks as u32 + 40).to_le_bytes());
+        data.extend(name("Logon Events"));
+
+        // Templates.
+        point_to_end(&mut data, descriptors + 24);
+        data.extend(b"TTBL");
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&1_u32.to_le_bytes());
+        let template = data.len() as u32;
+        let binxml = template_binxml(template + TEMPLATE_HEADER_SIZE);
+        let items = template + TEMPLATE_HEADER_SIZE + binxml.len() as u32;
+        let field_name = items + TEMPLATE_ITEM_SIZE;
+        let field_name_data = name("TargetUserName");
+        let template_size = field_name + field_name_data.len() as u32 - template;
+        data.extend(b"TEMP");
+        data.extend(&template_size.to_le_bytes());
+        data.extend(&1_u32.to_le_bytes());
+        data.extend(&1_u32.to_le_bytes());
+        data.extend(&items.to_le_bytes());
+        data.extend(&1_u32.to_le_bytes());
+        data.extend(&TEMPLATE_GUID);
+        data.extend(binxml);
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&[0x01, 0x01]);
+        data.extend(&0_u16.to_le_bytes());
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&1_u16.to_le_bytes());
+        data.extend(&0_u16.to_le_bytes());
+        data.extend(&field_name.to_le_bytes());
+        data.extend(field_name_data);
+
+        // Events, the second one without a template.
+        point_to_end(&mut data, descriptors + 32);
+        data.extend(b"EVNT");
+        data.extend(&0_u32.to_le_bytes());
+        data.extend(&2_u32.to_le_bytes());
+        data.extend(&0_u32.to_le_bytes());
+        for &(event_id, template_offset) in [(4624_u16, template), (4625, 0)].iter() {
+            data.extend(&event_id.to_le_bytes());
+            data.extend(&[2, 0x10, 4, 0]);
+            data.extend(&12544_u16.to_le_bytes());
+            data.extend(&0x8020_0000_0000_0010_u64.to_le_bytes());
+            data.extend(&(0xb000_0000_u32 + u32::from(event_id)).to_le_bytes());
+            data.extend(&template_offset.to_le_bytes());
+            data.extend(vec![0; 24]);
+        }
+
+        data
+    }
+
+    #[test]
+    fn test_parses_manifest() {
+        let data = sample_manifest();
+        let manifest = WevtManifest::from_bytes(&data).unwrap();
+
+        assert_eq!((manifest.major_version, manifest.minor_version), (3, 1));
+        assert_eq!(manifest.providers.len(), 1);
+
+        let guid = Guid::from_buffer(&PROVIDER_GUID).unwrap();
+        let provider = manifest.provider(&guid).unwrap();
+
+        assert_eq!(provider.message_id, Some(100));
+        assert_eq!(
+            provider.levels,
+            vec![WevtNamedValue {
+                value: 4,
+                name: "win:Informational".to_string(),
+                message_id: Some(0x5000_0004),
+            }]
+        );
+        assert_eq!(provider.keyword_names(0x8020_0000_0000_0010), vec!["Logon"]);
+        assert_eq!(provider.task_name(12544), Some("Logon Events"));
+        assert_eq!(provider.level_name(4), Some("win:Informational"));
+        assert_eq!(provider.opcode_name(12544, 0), None);
+
+        let event = provider.event(4624, 2).unwrap();
+        assert_eq!(event.level, 4);
+        assert_eq!(event.task, 12544);
+        assert_eq!(event.message_id, Some(0xb000_0000 + 4624));
+        assert!(provider.event(4624, 1).is_none());
+
+        let template = provider.template(event).unwrap();
+        assert_eq!(template.guid, Guid::from_buffer(&TEMPLATE_GUID).unwrap());
+        assert_eq!(template.fields.len(), 1);
+        assert_eq!(template.fields[0].name, "TargetUserName");
+        assert_eq!(template.fields[0].input_type, 0x01);
+
+        let element_names: Vec<_> = template
+            .tokens
+            .iter()
+            .filter_map(|token| match token {
+                BinXMLDeserializedTokens::OpenStartElement(element) => {
+                    Some(element.name.as_str().to_string())
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(element_names, vec!["Data"]);
+        assert!(template.tokens.iter().any(|token| matches!(
+            token,
+            BinXMLDeserializedTokens::Substitution(descriptor)
+                if descriptor.substitution_index == 0 && descriptor.value_type == BinXmlValueType::StringType
+        )));
+
+        let no_data = provider.event(4625, 2).unwrap();
+        assert!(provider.template(no_data).is_none());
+    }
+
+    #[test]
+    fn test_resolves_symbolic_names_from_manifest() {
+        let mut manifest = WevtManifest::from_bytes(&sample_manifest()).unwrap();
+        let provider = &mut manifest.providers[0];
+        provider.guid = "54849625-5478-4994-A5BA-3E3B0328C30D".parse().unwrap();
+        provider.events[0].version = 0;
+        provider.channels.push(WevtNamedValue {
+            value: 0x10,
+            name: "Security".to_string(),
+            message_id: None,
+        });
+
+        let mut enrichment = crate::Enrichment::default();
+        enrichment.add_manifest(manifest);
+        assert!(enrichment
+            .manifest_provider("{54849625-5478-4994-a5ba-3e3b0328c30d}")
+            .is_some());
+
+        let settings = crate::ParserSettings::new()
+            .enrichment(enrichment)
+            .symbolic_names(crate::RenderFormat::Json, true);
+        let mut parser = crate::EvtxParser::from_path("samples/security.evtx")
+            .unwrap()
+            .with_configuration(settings);
+
+        let record = parser
+            .records_json_value()
+            .map(|record| record.unwrap().data)
+            .find(|data| data["Event"]["System"]["EventID"] == 4624)
+            .unwrap();
+        let info = &record["Event"]["RenderingInfo"];
+
+        assert_eq!(info["Task"], "Logon Events");
+        assert_eq!(info["Channel"], "Security");
+        // `win:Informational` is left to the standard name.
+        assert_eq!(info["Level"], "Information");
+        assert_eq!(info["Keywords"], serde_json::json!(["Audit Success"]));
+    }
+
+    #[test]
+    fn test_rejects_invalid_manifests() {
+        assert!(WevtManifest::from_bytes(b"WEVT").is_err());
+
+        let mut data = sample_manifest();
+        // The provider points past the end of the manifest.
+        patch_u32(&mut data, 32, 0xffff_fff0);
+        assert!(WevtManifest::from_bytes(&data).is_err());
+
+        let data = sample_manifest();
+        assert!(WevtManifest::from_bytes(&data[..data.len() - 26]).is_err());
+    }
+}
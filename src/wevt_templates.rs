@@ -0,0 +1,323 @@
+//! Extraction of `WEVT_TEMPLATE` resources from provider binaries.
+//!
+//! Event providers embed their instrumentation manifest (templates, keywords, message IDs..)
+//! as a `WEVT_TEMPLATE` resource in the provider DLL/EXE.
+//! `ParserSettings::load_provider_binary` registers it's providers with the enrichment data, so the symbolic names
+//! of records resolve without the provider being installed.
+//! The messages themselves are in the message table of the binary, which is not read.
+use crate::err::{self, Result};
+use crate::wevt_manifest::WevtManifest;
+
+use log::debug;
+use object::pe::{ImageNtHeaders32, ImageNtHeaders64};
+use object::read::pe::{ImageNtHeaders, PeFile, ResourceDirectoryEntryData, ResourceNameOrId};
+use object::FileKind;
+use snafu::ResultExt;
+
+use std::fs;
+use std::path::Path;
+
+/// The name of the resource type holding the provider manifest.
+pub const WEVT_TEMPLATE_RESOURCE_NAME: &str = "WEVT_TEMPLATE";
+
+/// A single `WEVT_TEMPLATE` resource, as found in the resource directory of a PE file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WevtTemplateResource {
+    /// The ID of the resource (usually `1`), or `None` if the resource is named.
+    pub id: Option<u16>,
+    /// The language ID of the resource.
+    pub language: Option<u16>,
+    /// The raw resource data (starting with the `CRIM` manifest header).
+    pub data: Vec<u8>,
+}
+
+impl WevtTemplateResource {
+    /// Parses the manifest of the resource, see `WevtManifest`.
+    pub fn manifest(&self) -> Result<WevtManifest> {
+        WevtManifest::from_bytes(&self.data)
+    }
+}
+
+/// Reads a provider binary from `path`, and extracts all of it's `WEVT_TEMPLATE` resources.
+pub fn extract_wevt_template_resources(
+    path: impl AsRef<Path>,
+) -> Result<Vec<WevtTemplateResource>> {
+    let path = path.as_ref();
+    let data = fs::read(path).context(err::FailedToOpenFile { path })?;
+
+    extract_wevt_template_resources_from_buffer(&data)
+}
+
+/// Extracts all the `WEVT_TEMPLATE` resources from the data of a PE file (32 or 64 bit).
+/// Returns an empty vector if the file has no such resources.
+pub fn extract_wevt_template_resources_from_buffer(
+    data: &[u8],
+) -> Result<Vec<WevtTemplateResource>> {
+    match FileKind::parse(data) {
+        Ok(FileKind::Pe32) => extract_from_pe::<ImageNtHeaders32>(data),
+        Ok(FileKind::Pe64) => extract_from_pe::<ImageNtHeaders64>(data),
+        Ok(other) => err::FailedToReadPEResources {
+            message: format!("Unsupported file kind `{:?}`", other),
+        }
+        .fail(),
+        Err(e) => err::FailedToReadPEResources {
+            message: e.to_string(),
+        }
+        .fail(),
+    }
+}
+
+fn extract_from_pe<Pe: ImageNtHeaders>(data: &[u8]) -> Result<Vec<WevtTemplateResource>> {
+    let pe_error = |e: object::read::Error| err::Error::FailedToReadPEResources {
+        message: e.to_string(),
+    };
+
+    let file = PeFile::<Pe>::parse(data).map_err(pe_error)?;
+    let sections = file.section_table();
+
+    let directory = match file
+        .data_directories()
+        .resource_directory(data, &sections)
+        .map_err(pe_error)?
+    {
+        Some(directory) => directory,
+        None => {
+            debug!("PE file has no resource directory");
+            return Ok(vec![]);
+        }
+    };
+
+    let mut resources = vec![];
+
+    // The resource tree is always `type -> name/id -> language -> data`.
+    for type_entry in directory.root().map_err(pe_error)?.entries {
+        let is_wevt_template = match type_entry.name_or_id() {
+            ResourceNameOrId::Name(name) => {
+                name.to_string_lossy(directory).map_err(pe_error)? == WEVT_TEMPLATE_RESOURCE_NAME
+            }
+            ResourceNameOrId::Id(_) => false,
+        };
+
+        if !is_wevt_template {
+            continue;
+        }
+
+        let names = match type_entry.data(directory).map_err(pe_error)? {
+            ResourceDirectoryEntryData::Table(table) => table,
+            ResourceDirectoryEntryData::Data(_) => continue,
+        };
+
+        for name_entry in names.entries {
+            let id = name_entry.name_or_id().id();
+
+            let languages = match name_entry.data(directory).map_err(pe_error)? {
+                ResourceDirectoryEntryData::Table(table) => table,
+                ResourceDirectoryEntryData::Data(_) => continue,
+            };
+
+            for language_entry in languages.entries {
+                let language = language_entry.name_or_id().id();
+
+                let data_entry = match language_entry.data(directory).map_err(pe_error)? {
+                    ResourceDirectoryEntryData::Data(data_entry) => data_entry,
+                    ResourceDirectoryEntryData::Table(_) => continue,
+                };
+
+                let rva = data_entry.offset_to_data.get(object::LittleEndian);
+                let size = data_entry.size.get(object::LittleEndian) as usize;
+
+                let resource_data = sections
+                    .pe_data_at(data, rva)
+                    .filter(|d| d.len() >= size)
+                    .ok_or_else(|| err::Error::FailedToReadPEResources {
+                        message: format!("Resource data at RVA {:#x} is out of bounds", rva),
+                    })?;
+
+                debug!(
+                    "Found `WEVT_TEMPLATE` resource {:?} (language {:?}), {} bytes",
+                    id, language, size
+                );
+
+                resources.push(WevtTemplateResource {
+                    id,
+                    language,
+                    data: resource_data[..size].to_vec(),
+                });
+            }
+        }
+    }
+
+    Ok(resources)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::wevt_manifest::tests::sample_manifest;
+    use crate::{EvtxParser, ParserSettings, RenderFormat};
+    use std::io::Write;
+
+    /// A 64 bit PE file with a single `.rsrc` section, holding `data` as the `WEVT_TEMPLATE` resource `1`.
+    fn pe_with_wevt_template(data: &[u8]) -> Vec<u8> {
+        const SECTION_RVA: u32 = 0x1000;
+        const SECTION_OFFSET: usize = 0x200;
+
+        fn align(value: usize, alignment: usize) -> usize {
+            (value + alignment - 1) / alignment * alignment
+        }
+
+        // Resource directory: type (named) -> ID -> language -> data entry, then the name and the data.
+        let mut rsrc = vec![];
+        let directory = |rsrc: &mut Vec<u8>, named: u16, ids: u16| {
+            rsrc.extend(vec![0; 12]);
+            rsrc.extend(&named.to_le_bytes());
+            rsrc.extend(&ids.to_le_bytes());
+        };
+        directory(&mut rsrc, 1, 0);
+        rsrc.extend(&(0x8000_0000_u32 | 88).to_le_bytes());
+        rsrc.extend(&(0x8000_0000_u32 | 24).to_le_bytes());
+        directory(&mut rsrc, 0, 1);
+        rsrc.extend(&1_u32.to_le_bytes());
+        rsrc.extend(&(0x8000_0000_u32 | 48).to_le_bytes());
+        directory(&mut rsrc, 0, 1);
+        rsrc.extend(&0x409_u32.to_le_bytes());
+        rsrc.extend(&72_u32.to_le_bytes());
+        rsrc.extend(&(SECTION_RVA + 120).to_le_bytes());
+        rsrc.extend(&(data.len() as u32).to_le_bytes());
+        rsrc.extend(vec![0; 8]);
+        rsrc.extend(&(WEVT_TEMPLATE_RESOURCE_NAME.len() as u16).to_le_bytes());
+        rsrc.extend(
+            WEVT_TEMPLATE_RESOURCE_NAME
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        );
+        rsrc.resize(120, 0);
+        rsrc.extend(data);
+
+        let raw_size = align(rsrc.len(), 0x200);
+        let image_size = SECTION_RVA as usize + align(rsrc.len(), 0x1000);
+
+        let mut pe = b"MZ".to_vec();
+        pe.resize(0x3c, 0);
+        pe.extend(&0x40_u32.to_le_bytes());
+
+        // COFF header.
+        pe.extend(b"PE\0\0");
+        pe.extend(&0x8664_u16.to_le_bytes());
+        pe.extend(&1_u16.to_le_bytes());
+        pe.extend(vec![0; 12]);
+        pe.extend(&240_u16.to_le_bytes());
+        pe.extend(&0x2022_u16.to_le_bytes());
+
+        // Optional header, with 16 data directories.
+        pe.extend(&0x20b_u16.to_le_bytes());
+        pe.extend(vec![0; 22]);
+        pe.extend(&0x1_8000_0000_u64.to_le_bytes());
+        pe.extend(&0x1000_u32.to_le_bytes());
+        pe.extend(&0x200_u32.to_le_bytes());
+        pe.extend(vec![0; 16]);
+        pe.extend(&(image_size as u32).to_le_bytes());
+        pe.extend(&(SECTION_OFFSET as u32).to_le_bytes());
+        pe.extend(&0_u32.to_le_bytes());
+        pe.extend(&2_u16.to_le_bytes());
+        pe.extend(&0_u16.to_le_bytes());
+        pe.extend(vec![0; 36]);
+        pe.extend(&16_u32.to_le_bytes());
+        for index in 0..16 {
+            let (rva, size) = match index {
+                2 => (SECTION_RVA, rsrc.len() as u32),
+                _ => (0, 0),
+            };
+            pe.extend(&rva.to_le_bytes());
+            pe.extend(&size.to_le_bytes());
+        }
+
+        // Section header.
+        pe.extend(b".rsrc\0\0\0");
+        pe.extend(&(rsrc.len() as u32).to_le_bytes());
+        pe.extend(&SECTION_RVA.to_le_bytes());
+        pe.extend(&(raw_size as u32).to_le_bytes());
+        pe.extend(&(SECTION_OFFSET as u32).to_le_bytes());
+        pe.extend(vec![0; 12]);
+        pe.extend(&0x4000_0040_u32.to_le_bytes());
+
+        pe.resize(SECTION_OFFSET, 0);
+        pe.extend(rsrc);
+        pe.resize(SECTION_OFFSET + raw_size, 0);
+        pe
+    }
+
+    #[test]
+    fn test_extracts_resources() {
+        let manifest = sample_manifest();
+        let resources =
+            extract_wevt_template_resources_from_buffer(&pe_with_wevt_template(&manifest)).unwrap();
+
+        assert_eq!(
+            resources,
+            vec![WevtTemplateResource {
+                id: Some(1),
+                language: Some(0x409),
+                data: manifest,
+            }]
+        );
+        assert_eq!(resources[0].manifest().unwrap().providers.len(), 1);
+    }
+
+    #[test]
+    fn test_loads_provider_binary() {
+        // The sample manifest, for the provider of the records of `security.evtx`.
+        let mut manifest = sample_manifest();
+        manifest[16..32].copy_from_slice(&[
+            0x25, 0x96, 0x84, 0x54, 0x78, 0x54, 0x94, 0x49, 0xa5, 0xba, 0x3e, 0x3b, 0x03, 0x28,
+            0xc3, 0x0d,
+        ]);
+
+        let mut binary = tempfile::NamedTempFile::new().unwrap();
+        binary.write_all(&pe_with_wevt_template(&manifest)).unwrap();
+
+        let settings = ParserSettings::new()
+            .load_provider_binary(binary.path())
+            .unwrap()
+            .symbolic_names(RenderFormat::Json, true);
+
+        let provider = settings
+            .get_enrichment()
+            .and_then(|enrichment| {
+                enrichment.manifest_provider("54849625-5478-4994-a5ba-3e3b0328c30d")
+            })
+            .unwrap();
+        let event = provider.event(4624, 2).unwrap();
+        assert_eq!(
+            provider.template(event).unwrap().fields[0].name,
+            "TargetUserName"
+        );
+
+        let mut parser = EvtxParser::from_path("samples/security.evtx")
+            .unwrap()
+            .with_configuration(settings);
+        let record = parser
+            .records_json_value()
+            .map(|record| record.unwrap().data)
+            .find(|data| data["Event"]["System"]["Task"] == 12544)
+            .unwrap();
+
+        let info = &record["Event"]["RenderingInfo"];
+        assert_eq!(info["Task"], "Logon Events");
+        // The `Logon` keyword (`0x10`) of the manifest is not set, the standard ones are still resolved.
+        assert_eq!(info["Keywords"], serde_json::json!(["Audit Success"]));
+
+        let not_a_provider = tempfile::NamedTempFile::new().unwrap();
+        assert!(ParserSettings::new()
+            .load_provider_binary(not_a_provider.path())
+            .is_err());
+    }
+
+    #[test]
+    fn test_it_fails_on_non_pe_data() {
+        let evtx_file = include_bytes!("../samples/security.evtx");
+
+        assert!(extract_wevt_template_resources_from_buffer(&evtx_file[..]).is_err());
+    }
+}
@@ -1,5 +1,6 @@
-use crate::binxml::value_variant::BinXmlValue;
+use crate::binxml::value_variant::{BinXmlValue, FloatFormat};
 use crate::err::Result;
+use crate::guid::GuidFormat;
 use crate::model::xml::XmlElement;
 use crate::unimplemented_fn;
 use crate::ParserSettings;
@@ -46,6 +47,8 @@ pub trait BinXmlOutput {
 
 pub struct XmlOutput<W: Write> {
     writer: Writer<W>,
+    float_format: FloatFormat,
+    guid_format: GuidFormat,
 }
 
 impl<W: Write> XmlOutput<W> {
@@ -56,7 +59,11 @@ impl<W: Write> XmlOutput<W> {
             Writer::new(target)
         };
 
-        XmlOutput { writer }
+        XmlOutput {
+            writer,
+            float_format: settings.get_float_format(),
+            guid_format: settings.get_guid_format(),
+        }
     }
 
     pub fn into_writer(self) -> Result<W> {
@@ -80,7 +87,10 @@ impl<W: Write> BinXmlOutput for XmlOutput<W> {
             BytesStart::borrowed_name(element.name.as_ref().as_str().as_bytes());
 
         for attr in element.attributes.iter() {
-            let value_cow: Cow<'_, str> = attr.value.as_ref().as_cow_str();
+            let value_cow: Cow<'_, str> = attr
+                .value
+                .as_ref()
+                .as_cow_str_with_formats(self.float_format, self.guid_format);
 
             if value_cow.len() > 0 {
                 let name_as_str = attr.name.as_str();
@@ -105,7 +115,14 @@ impl<W: Write> BinXmlOutput for XmlOutput<W> {
 
     fn visit_characters(&mut self, value: &BinXmlValue) -> Result<()> {
         trace!("visit_chars");
-        let cow: Cow<str> = value.as_cow_str();
+        if let BinXmlValue::ParseError(_) = value {
+            let event = BytesText::from_plain_str(" parse error ");
+            self.writer.write_event(Event::Comment(event))?;
+
+            return Ok(());
+        }
+
+        let cow: Cow<str> = value.as_cow_str_with_formats(self.float_format, self.guid_format);
         let event = BytesText::from_plain_str(&cow);
         self.writer.write_event(Event::Text(event))?;
 
@@ -0,0 +1,905 @@
+//! Compiles the subset of XPath 1.0 used by Windows Event Log queries
+//! (as saved by EventViewer, or passed to `wevtutil qe /q:`) into a `RecordFilter`.
+//!
+//! Supported queries select `*` (or `Event`), and filter it with predicates such as:
+//!
+//! ```text
+//! *[System[(EventID=4624 or EventID=4625) and Level<=3]]
+//! *[System[Provider[@Name='Microsoft-Windows-Security-Auditing'] and band(Keywords,4503599627370496)]]
+//! *[System[TimeCreated[@SystemTime>='2023-01-01T00:00:00.000Z' and timediff(@SystemTime) <= 86400000]]]
+//! *[EventData[Data[@Name='LogonType']='10']]
+//! *[EventData[Data='mimikatz.exe']]
+//! Event/System[EventID=4688]
+//! ```
+//!
+//! Queries can also be given as the `<QueryList>` documents saved by EventViewer.
+use crate::err::{self, Result};
+use crate::filter_expr::{parse_time, MAX_DEPTH};
+use crate::record_filter::{CompareOp, Field, Literal, RecordFilter};
+
+use chrono::{Duration, Utc};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Number(i128),
+    String(String),
+    Op(CompareOp),
+    Star,
+    At,
+    Slash,
+    Comma,
+    OpenBracket,
+    CloseBracket,
+    OpenParen,
+    CloseParen,
+}
+
+/// The element a predicate is evaluated on.
+#[derive(Debug, Clone, PartialEq)]
+enum Context {
+    Event,
+    System,
+    EventData,
+    UserData,
+    /// An element of `System` (e.g. `Provider` or `TimeCreated`).
+    Element(String),
+    /// A `Data` element, identified by it's `Name` attribute if it has a `[@Name='...']` predicate.
+    Data(Option<String>),
+}
+
+pub(crate) fn parse(query: &str) -> Result<RecordFilter> {
+    if query.trim_start().starts_with('<') {
+        parse_query_list(query)
+    } else {
+        parse_xpath(query)
+    }
+}
+
+fn parse_xpath(query: &str) -> Result<RecordFilter> {
+    let mut parser = Parser {
+        tokens: tokenize(query)?,
+        position: 0,
+        end: query.chars().count(),
+        depth: 0,
+    };
+
+    let filter = parser.parse_query()?;
+
+    match parser.tokens.get(parser.position) {
+        None => Ok(filter),
+        Some((token, position)) => fail(format!("Unexpected `{:?}`", token), *position),
+    }
+}
+
+fn fail<T>(message: impl Into<String>, position: usize) -> Result<T> {
+    err::InvalidFilterExpression {
+        message: message.into(),
+        position,
+    }
+    .fail()
+}
+
+/// Fails at the current position of `reader` in `xml`.
+fn fail_at<T>(message: impl Into<String>, xml: &str, reader: &Reader<&[u8]>) -> Result<T> {
+    fail(message, char_position(xml, reader.buffer_position()))
+}
+
+/// Returns the position in characters (like the positions of XPath errors) of the byte at `byte_position` of `xml`.
+fn char_position(xml: &str, byte_position: usize) -> usize {
+    xml.char_indices()
+        .take_while(|&(i, _)| i < byte_position)
+        .count()
+}
+
+/// Returns the position in `escaped` (XML text) of it's `position`th character once unescaped,
+/// since an entity reference (e.g. `&lt;`) is unescaped into a single character.
+fn escaped_position(escaped: &str, position: usize) -> usize {
+    let mut chars = escaped.chars();
+    let mut escaped_position = 0;
+
+    for _ in 0..position {
+        match chars.next() {
+            Some('&') => {
+                escaped_position += 1;
+                for c in chars.by_ref() {
+                    escaped_position += 1;
+                    if c == ';' {
+                        break;
+                    }
+                }
+            }
+            Some(_) => escaped_position += 1,
+            None => break,
+        }
+    }
+
+    escaped_position
+}
+
+/// Parses a `<QueryList>` document. Each `Query` selects the records matching any of it's `Select` elements
+/// and none of it's `Suppress` elements, and records selected by any `Query` are kept.
+///
+/// The `Path` of a `Select` (or `Suppress`) restricts it to the records of that channel,
+/// unless it is the path of a saved log (`file://...`).
+fn parse_query_list(xml: &str) -> Result<RecordFilter> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+
+    let mut buf = vec![];
+    let mut queries = vec![];
+    let mut selects = vec![];
+    let mut suppresses = vec![];
+    // The `Path` of the current `Query`, and the name and `Path` of the current `Select` or `Suppress`.
+    let mut query_path = None;
+    let mut current: Option<(bool, Option<String>)> = None;
+
+    loop {
+        let event = reader
+            .read_event(&mut buf)
+            .or_else(|e| fail_at(e.to_string(), xml, &reader))?;
+
+        match event {
+            Event::Start(ref start) | Event::Empty(ref start) => {
+                let path = read_path(xml, &reader, start)?;
+
+                match start.name() {
+                    b"QueryList" => {}
+                    b"Query" => query_path = path,
+                    b"Select" | b"Suppress" if current.is_none() => {
+                        current = Some((
+                            start.name() == b"Select",
+                            path.or_else(|| query_path.clone()),
+                        ))
+                    }
+                    name => {
+                        return fail_at(
+                            format!("Unexpected element `{}`", String::from_utf8_lossy(name)),
+                            xml,
+                            &reader,
+                        )
+                    }
+                }
+
+                if let (Event::Empty(_), Some(_)) = (&event, &current) {
+                    return fail_at("Expected a query", xml, &reader);
+                }
+            }
+            Event::Text(ref text) => {
+                let (select, path) = match current.take() {
+                    Some(current) => current,
+                    None => return fail_at("Unexpected text", xml, &reader),
+                };
+
+                // Errors in the query are reported relative to the document.
+                let escaped = String::from_utf8_lossy(text.escaped());
+                let offset = char_position(xml, reader.buffer_position() - text.escaped().len());
+                let text = text
+                    .unescaped()
+                    .or_else(|e| fail_at(e.to_string(), xml, &reader))?;
+                let filter = parse_xpath(&String::from_utf8_lossy(&text)).map_err(|e| match e {
+                    err::Error::InvalidFilterExpression { message, position } => {
+                        err::Error::InvalidFilterExpression {
+                            message,
+                            position: offset + escaped_position(&escaped, position),
+                        }
+                    }
+                    e => e,
+                })?;
+
+                let filter = match path {
+                    Some(path) if !path.starts_with("file://") => RecordFilter::Compare {
+                        field: Field::Element("Channel".to_string()),
+                        op: CompareOp::Eq,
+                        value: Literal::String(path.to_lowercase()),
+                    }
+                    .and(filter),
+                    _ => filter,
+                };
+
+                if select {
+                    selects.push(filter);
+                } else {
+                    suppresses.push(filter);
+                }
+            }
+            Event::End(ref end) => match end.name() {
+                b"Select" | b"Suppress" if current.is_some() => {
+                    return fail_at("Expected a query", xml, &reader)
+                }
+                b"Query" => {
+                    let mut query = any(std::mem::take(&mut selects));
+                    if !suppresses.is_empty() {
+                        query = query.and(any(std::mem::take(&mut suppresses)).negate());
+                    }
+                    queries.push(query);
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+
+        buf.clear();
+    }
+
+    if queries.is_empty() {
+        return fail("Expected a `Query` with a `Select`", xml.chars().count());
+    }
+
+    Ok(any(queries))
+}
+
+/// Reads the `Path` attribute of a `QueryList` element.
+fn read_path(xml: &str, reader: &Reader<&[u8]>, start: &BytesStart) -> Result<Option<String>> {
+    for attribute in start.attributes() {
+        let attribute = attribute.or_else(|e| fail_at(e.to_string(), xml, reader))?;
+
+        if attribute.key == b"Path" {
+            let value = attribute
+                .unescaped_value()
+                .or_else(|e| fail_at(e.to_string(), xml, reader))?;
+            return Ok(Some(String::from_utf8_lossy(&value).into_owned()));
+        }
+    }
+
+    Ok(None)
+}
+
+fn tokenize(query: &str) -> Result<Vec<(Token, usize)>> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let next = chars.get(i + 1).cloned();
+        i += 1;
+
+        let token = match (c, next) {
+            (c, _) if c.is_whitespace() => continue,
+            ('*', _) => Token::Star,
+            ('@', _) => Token::At,
+            ('/', _) => Token::Slash,
+            (',', _) => Token::Comma,
+            ('[', _) => Token::OpenBracket,
+            (']', _) => Token::CloseBracket,
+            ('(', _) => Token::OpenParen,
+            (')', _) => Token::CloseParen,
+            ('=', _) => Token::Op(CompareOp::Eq),
+            ('!', Some('=')) => {
+                i += 1;
+                Token::Op(CompareOp::Ne)
+            }
+            ('<', Some('=')) => {
+                i += 1;
+                Token::Op(CompareOp::Le)
+            }
+            ('>', Some('=')) => {
+                i += 1;
+                Token::Op(CompareOp::Ge)
+            }
+            ('<', _) => Token::Op(CompareOp::Lt),
+            ('>', _) => Token::Op(CompareOp::Gt),
+            ('\'', _) | ('"', _) => {
+                while i < chars.len() && chars[i] != c {
+                    i += 1;
+                }
+
+                if i == chars.len() {
+                    return fail("Unterminated string", start);
+                }
+
+                i += 1;
+                Token::String(chars[start + 1..i - 1].iter().collect())
+            }
+            (c, _) if c.is_ascii_digit() => {
+                while i < chars.len() && chars[i].is_ascii_digit() {
+                    i += 1;
+                }
+
+                let literal: String = chars[start..i].iter().collect();
+                match literal.parse() {
+                    Ok(n) => Token::Number(n),
+                    Err(_) => return fail(format!("Invalid number `{}`", literal), start),
+                }
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '-')
+                {
+                    i += 1;
+                }
+
+                Token::Name(chars[start..i].iter().collect())
+            }
+            (c, _) => return fail(format!("Unexpected character `{}`", c), start),
+        };
+
+        tokens.push((token, start));
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    /// Length of the query, reported when it ends unexpectedly.
+    end: usize,
+    /// The number of enclosing predicates, parentheses and steps, limited to `MAX_DEPTH`.
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn peek_name(&self, name: &str) -> bool {
+        match self.peek() {
+            Some(Token::Name(n)) => n == name,
+            _ => false,
+        }
+    }
+
+    fn next(&mut self) -> Result<(Token, usize)> {
+        match self.tokens.get(self.position) {
+            Some(token) => {
+                self.position += 1;
+                Ok(token.clone())
+            }
+            None => fail("Unexpected end of query", self.end),
+        }
+    }
+
+    /// Runs `parse` one nesting level deeper (after a `[`, `(` or `/`), failing at that token past `MAX_DEPTH`.
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_DEPTH {
+            let position = self.tokens[self.position - 1].1;
+            return fail(
+                format!("Query is nested deeper than {} levels", MAX_DEPTH),
+                position,
+            );
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next()? {
+            (ref token, _) if *token == expected => Ok(()),
+            (token, position) => fail(
+                format!("Expected `{:?}`, found `{:?}`", expected, token),
+                position,
+            ),
+        }
+    }
+
+    /// `query := ( "*" | "Event" ) predicate* ( "/" step )?`
+    fn parse_query(&mut self) -> Result<RecordFilter> {
+        match self.next()? {
+            (Token::Star, _) => {}
+            (Token::Name(ref name), _) if name == "Event" => {}
+            (token, position) => {
+                return fail(
+                    format!("Expected `*` or `Event`, found `{:?}`", token),
+                    position,
+                )
+            }
+        }
+
+        let mut filters = self.parse_predicates(&Context::Event)?;
+
+        if self.peek() == Some(&Token::Slash) {
+            self.position += 1;
+            filters.push(self.parse_step(&Context::Event)?);
+        }
+
+        Ok(all(filters))
+    }
+
+    /// Parses any number of `[expr]`.
+    fn parse_predicates(&mut self, context: &Context) -> Result<Vec<RecordFilter>> {
+        let mut filters = vec![];
+
+        while self.peek() == Some(&Token::OpenBracket) {
+            self.position += 1;
+            filters.push(self.parse_or(context)?);
+            self.expect(Token::CloseBracket)?;
+        }
+
+        Ok(filters)
+    }
+
+    fn parse_or(&mut self, context: &Context) -> Result<RecordFilter> {
+        self.nested(|parser| {
+            let mut filter = parser.parse_and(context)?;
+
+            while parser.peek_name("or") {
+                parser.position += 1;
+                filter = filter.or(parser.parse_and(context)?);
+            }
+
+            Ok(filter)
+        })
+    }
+
+    fn parse_and(&mut self, context: &Context) -> Result<RecordFilter> {
+        let mut filter = self.parse_unary(context)?;
+
+        while self.peek_name("and") {
+            self.position += 1;
+            filter = filter.and(self.parse_unary(context)?);
+        }
+
+        Ok(filter)
+    }
+
+    fn parse_unary(&mut self, context: &Context) -> Result<RecordFilter> {
+        match self.peek() {
+            Some(Token::OpenParen) => {
+                self.position += 1;
+                let filter = self.parse_or(context)?;
+                self.expect(Token::CloseParen)?;
+                Ok(filter)
+            }
+            Some(Token::At) => {
+                self.position += 1;
+                self.parse_attribute(context)
+            }
+            _ => self.parse_step(context),
+        }
+    }
+
+    /// Parses a step relative to `context`, either a function call (`not(..)`, `band(..)`, `timediff(..)`),
+    /// or an element followed by predicates, a comparison or a path (`System/EventID=1`).
+    fn parse_step(&mut self, context: &Context) -> Result<RecordFilter> {
+        let (name, position) = match self.next()? {
+            (Token::Name(name), position) => (name, position),
+            (token, position) => {
+                return fail(
+                    format!("Expected an element, found `{:?}`", token),
+                    position,
+                )
+            }
+        };
+
+        if self.peek() == Some(&Token::OpenParen) {
+            self.position += 1;
+            return self.parse_function(&name, position, context);
+        }
+
+        let child = match (context, name.as_str()) {
+            (Context::Event, "System") => Context::System,
+            (Context::Event, "EventData") => Context::EventData,
+            (Context::Event, "UserData") => Context::UserData,
+            (Context::System, _) => Context::Element(name.clone()),
+            (Context::EventData, "Data") => Context::Data(None),
+            (Context::UserData, _) => Context::UserData,
+            _ => return fail(format!("Unsupported element `{}` here", name), position),
+        };
+
+        // The name of a `Data` element is given by it's first predicate (`Data[@Name='LogonType']`).
+        let (child, mut filters) = if child == Context::Data(None) {
+            self.parse_data_name()?
+        } else {
+            (child.clone(), self.parse_predicates(&child)?)
+        };
+
+        match self.peek() {
+            Some(Token::Slash) => {
+                self.position += 1;
+                filters.push(self.nested(|parser| parser.parse_step(&child))?);
+            }
+            Some(Token::Op(_)) => {
+                let field = match (&child, context) {
+                    (Context::Data(Some(data_name)), _) => Field::EventData(data_name.clone()),
+                    // Without a name, `Data='x'` is true if any `Data` element is `x`.
+                    (Context::Data(None), _) => Field::AnyEventData,
+                    (Context::Element(element), _) => Field::Element(element.clone()),
+                    (Context::UserData, Context::UserData) => Field::Element(name.clone()),
+                    _ => return fail(format!("Cannot compare the value of `{}`", name), position),
+                };
+
+                filters.push(self.parse_comparison(field)?);
+            }
+            _ => {}
+        }
+
+        Ok(all(filters))
+    }
+
+    /// Parses `[@Name='...']` following `Data` (if present), and the rest of it's predicates.
+    fn parse_data_name(&mut self) -> Result<(Context, Vec<RecordFilter>)> {
+        let name = match self.tokens.get(self.position..self.position + 5) {
+            Some(
+                [(Token::OpenBracket, _), (Token::At, _), (Token::Name(attribute), _), (Token::Op(CompareOp::Eq), _), (Token::String(name), _)],
+            ) if attribute == "Name" => name.clone(),
+            _ => {
+                let context = Context::Data(None);
+                let filters = self.parse_predicates(&context)?;
+                return Ok((context, filters));
+            }
+        };
+
+        self.position += 5;
+        self.expect(Token::CloseBracket)?;
+
+        let context = Context::Data(Some(name));
+        let filters = self.parse_predicates(&context)?;
+
+        Ok((context, filters))
+    }
+
+    /// Parses `@Attribute op value` (after the `@`).
+    fn parse_attribute(&mut self, context: &Context) -> Result<RecordFilter> {
+        let (attribute, position) = match self.next()? {
+            (Token::Name(name), position) => (name, position),
+            (token, position) => {
+                return fail(
+                    format!("Expected an attribute, found `{:?}`", token),
+                    position,
+                )
+            }
+        };
+
+        let field = match context {
+            Context::Element(element) if element == "TimeCreated" && attribute == "SystemTime" => {
+                Field::TimeCreated
+            }
+            Context::Element(element) => Field::Attribute {
+                element: element.clone(),
+                attribute,
+            },
+            _ => {
+                return fail(
+                    format!("Unsupported attribute `@{}` here", attribute),
+                    position,
+                )
+            }
+        };
+
+        self.parse_comparison(field)
+    }
+
+    fn parse_function(
+        &mut self,
+        name: &str,
+        position: usize,
+        context: &Context,
+    ) -> Result<RecordFilter> {
+        match name {
+            "not" => {
+                let filter = self.parse_or(context)?;
+                self.expect(Token::CloseParen)?;
+                Ok(filter.negate())
+            }
+            // `band(Keywords, mask)` is true if any of the bits of the mask are set.
+            "band" if *context == Context::System => {
+                match self.next()? {
+                    (Token::Name(ref field), _) if field == "Keywords" => {}
+                    (token, position) => {
+                        return fail(
+                            format!("Expected `Keywords`, found `{:?}`", token),
+                            position,
+                        )
+                    }
+                }
+                self.expect(Token::Comma)?;
+
+                let mask = match self.next()? {
+                    (Token::Number(n), _) if n >= 0 && n <= i128::from(u64::MAX) => n as u64,
+                    (token, position) => {
+                        return fail(format!("Expected a mask, found `{:?}`", token), position)
+                    }
+                };
+                self.expect(Token::CloseParen)?;
+
+                Ok(RecordFilter::keywords_any(mask))
+            }
+            // `timediff(@SystemTime) <= ms` is true if the record is at most `ms` old.
+            "timediff" if *context == Context::Element("TimeCreated".to_string()) => {
+                self.expect(Token::At)?;
+                match self.next()? {
+                    (Token::Name(ref attribute), _) if attribute == "SystemTime" => {}
+                    (token, position) => {
+                        return fail(
+                            format!("Expected `SystemTime`, found `{:?}`", token),
+                            position,
+                        )
+                    }
+                }
+                self.expect(Token::CloseParen)?;
+
+                let (op, op_position) = match self.next()? {
+                    (Token::Op(op), position) => (op, position),
+                    (token, position) => {
+                        return fail(
+                            format!("Expected an operator, found `{:?}`", token),
+                            position,
+                        )
+                    }
+                };
+
+                let milliseconds = match self.next()? {
+                    (Token::Number(n), _) if n <= i128::from(i64::MAX) => n as i64,
+                    (token, position) => {
+                        return fail(format!("Expected a number, found `{:?}`", token), position)
+                    }
+                };
+
+                // A larger difference is an earlier time.
+                let op = match op {
+                    CompareOp::Lt => CompareOp::Gt,
+                    CompareOp::Le => CompareOp::Ge,
+                    CompareOp::Gt => CompareOp::Lt,
+                    CompareOp::Ge => CompareOp::Le,
+                    _ => {
+                        return fail(
+                            "Expected `timediff` to be compared with `<`, `<=`, `>` or `>=`",
+                            op_position,
+                        )
+                    }
+                };
+
+                Ok(RecordFilter::Compare {
+                    field: Field::TimeCreated,
+                    op,
+                    value: Literal::Time(Utc::now() - Duration::milliseconds(milliseconds)),
+                })
+            }
+            _ => fail(format!("Unsupported function `{}` here", name), position),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: Field) -> Result<RecordFilter> {
+        let op = match self.next()? {
+            (Token::Op(op), _) => op,
+            (token, position) => {
+                return fail(
+                    format!("Expected an operator, found `{:?}`", token),
+                    position,
+                )
+            }
+        };
+
+        let value = match self.next()? {
+            (Token::Number(n), _) => Literal::Number(n),
+            (Token::String(s), position) if field.is_time() => match parse_time(&s) {
+                Some(time) => Literal::Time(time),
+                None => return fail(format!("Invalid time `{}`", s), position),
+            },
+            (Token::String(s), _) => Literal::String(s.to_lowercase()),
+            (token, position) => {
+                return fail(format!("Expected a value, found `{:?}`", token), position)
+            }
+        };
+
+        Ok(RecordFilter::Compare { field, op, value })
+    }
+}
+
+/// Combines filters with AND, avoiding a needless `And` for a single filter.
+fn all(mut filters: Vec<RecordFilter>) -> RecordFilter {
+    if filters.len() == 1 {
+        filters.remove(0)
+    } else {
+        RecordFilter::And(filters)
+    }
+}
+
+/// Combines filters with OR, avoiding a needless `Or` for a single filter.
+fn any(mut filters: Vec<RecordFilter>) -> RecordFilter {
+    if filters.len() == 1 {
+        filters.remove(0)
+    } else {
+        RecordFilter::Or(filters)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compare(field: Field, op: CompareOp, value: Literal) -> RecordFilter {
+        RecordFilter::Compare { field, op, value }
+    }
+
+    fn element(name: &str) -> Field {
+        Field::Element(name.to_string())
+    }
+
+    #[test]
+    fn test_parses_event_id_query() {
+        let expected = compare(element("EventID"), CompareOp::Eq, Literal::Number(4688));
+
+        assert_eq!(parse("*[System[(EventID=4688)]]").unwrap(), expected);
+        assert_eq!(parse("*[System/EventID=4688]").unwrap(), expected);
+        assert_eq!(parse("Event/System[EventID=4688]").unwrap(), expected);
+    }
+
+    #[test]
+    fn test_parses_event_viewer_query() {
+        let query = "*[System[Provider[@Name='Microsoft-Windows-Security-Auditing'] \
+                     and (Level=1  or Level=2) and band(Keywords,4503599627370496) \
+                     and TimeCreated[@SystemTime>='2016-07-08T00:00:00.000Z']]]";
+
+        assert_eq!(
+            parse(query).unwrap(),
+            RecordFilter::And(vec![
+                compare(
+                    Field::Attribute {
+                        element: "Provider".to_string(),
+                        attribute: "Name".to_string()
+                    },
+                    CompareOp::Eq,
+                    Literal::String("microsoft-windows-security-auditing".to_string())
+                ),
+                compare(element("Level"), CompareOp::Eq, Literal::Number(1)).or(compare(
+                    element("Level"),
+                    CompareOp::Eq,
+                    Literal::Number(2)
+                )),
+                RecordFilter::keywords_any(0x0010_0000_0000_0000),
+                compare(
+                    Field::TimeCreated,
+                    CompareOp::Ge,
+                    Literal::Time(parse_time("2016-07-08T00:00:00.000Z").unwrap())
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parses_event_data_query() {
+        assert_eq!(
+            parse("*[EventData[Data[@Name='LogonType']='10' and Data[@Name='TargetUserName']!='SYSTEM']]")
+                .unwrap(),
+            compare(
+                Field::EventData("LogonType".to_string()),
+                CompareOp::Eq,
+                Literal::String("10".to_string())
+            )
+            .and(compare(
+                Field::EventData("TargetUserName".to_string()),
+                CompareOp::Ne,
+                Literal::String("system".to_string())
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parses_timediff() {
+        let filter = parse("*[System[TimeCreated[timediff(@SystemTime) <= 86400000]]]").unwrap();
+
+        match filter {
+            RecordFilter::Compare {
+                field: Field::TimeCreated,
+                op: CompareOp::Ge,
+                value: Literal::Time(time),
+            } => assert!((Utc::now() - time) >= Duration::days(1)),
+            other => panic!("Unexpected filter {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parses_any_data_query() {
+        assert_eq!(
+            parse("*[EventData[Data='mimikatz.exe' or Data[@Name='LogonType']=10]]").unwrap(),
+            compare(
+                Field::AnyEventData,
+                CompareOp::Eq,
+                Literal::String("mimikatz.exe".to_string())
+            )
+            .or(compare(
+                Field::EventData("LogonType".to_string()),
+                CompareOp::Eq,
+                Literal::Number(10)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parses_query_list() {
+        let query = r#"<QueryList>
+  <Query Id="0" Path="Security">
+    <Select Path="Security">*[System[(EventID=4624)]]</Select>
+    <Select Path="file://C:\logs\exported.evtx">*[System[(EventID=4625)]]</Select>
+    <Suppress Path="Security">*[EventData[Data[@Name='LogonType']='5']]</Suppress>
+  </Query>
+  <Query Id="1">
+    <Select>*[System[Level&lt;=2]]</Select>
+  </Query>
+</QueryList>"#;
+
+        let event_id = |id| compare(element("EventID"), CompareOp::Eq, Literal::Number(id));
+        let security = || {
+            compare(
+                element("Channel"),
+                CompareOp::Eq,
+                Literal::String("security".to_string()),
+            )
+        };
+
+        assert_eq!(
+            parse(query).unwrap(),
+            RecordFilter::Or(vec![
+                security().and(event_id(4624)).or(event_id(4625)).and(
+                    security()
+                        .and(compare(
+                            Field::EventData("LogonType".to_string()),
+                            CompareOp::Eq,
+                            Literal::String("5".to_string())
+                        ))
+                        .negate()
+                ),
+                compare(element("Level"), CompareOp::Le, Literal::Number(2)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_query_list_error_positions_are_in_characters() {
+        let position = |query: &str| match parse(query) {
+            Err(err::Error::InvalidFilterExpression { position, .. }) => position,
+            other => panic!("Expected an error, got {:?}", other),
+        };
+        // The character position of the `]` right after `pattern`.
+        let after = |query: &str, pattern: &str| {
+            query[..query.find(pattern).unwrap() + pattern.len()]
+                .chars()
+                .count()
+        };
+
+        let query = "<QueryList><Query Path='Sécurité'><Select>*[System[Level&lt;=2 and EventID=]]</Select></Query></QueryList>";
+        assert_eq!(position(query), after(query, "EventID="));
+
+        let query = "<QueryList><Query Path='Sécurité'><Where/></Query></QueryList>";
+        assert_eq!(position(query), after(query, "<Where/>"));
+
+        let query = "<QueryList><!-- Sécurité --></QueryList>";
+        assert_eq!(position(query), query.chars().count());
+    }
+
+    #[test]
+    fn test_limits_nesting() {
+        let position = |query: &str| match parse(query) {
+            Err(err::Error::InvalidFilterExpression { position, .. }) => position,
+            other => panic!("Expected an error, got {:?}", other),
+        };
+
+        let nested = format!("*[{}EventID=1{}]", "(".repeat(60000), ")".repeat(60000));
+        assert_eq!(position(&nested), MAX_DEPTH + 1);
+        let nested = format!("*[UserData{}=1]", "/Data".repeat(60000));
+        assert!(position(&nested) < nested.len());
+        assert!(parse(&format!(
+            "*[System[{}EventID=1{}]]",
+            "(".repeat(100),
+            ")".repeat(100)
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_rejects_unsupported_queries() {
+        assert!(parse("*[System[EventID=]]").is_err());
+        assert!(parse("*[EventData[Data/Foo='x']]").is_err());
+        assert!(parse("*[System[band(Level, 1)]]").is_err());
+        assert!(parse("*[System[EventID=1]").is_err());
+        assert!(parse("Foo[System[EventID=1]]").is_err());
+        assert!(parse("<QueryList></QueryList>").is_err());
+        assert!(parse("<QueryList><Query><Select Path='Security'/></Query></QueryList>").is_err());
+        assert!(parse("<QueryList><Query><Where>*</Where></Query></QueryList>").is_err());
+        assert!(parse(
+            "<QueryList><Query><Select>*[System[EventID=]]</Select></Query></QueryList>"
+        )
+        .is_err());
+    }
+}
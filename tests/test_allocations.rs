@@ -0,0 +1,71 @@
+mod fixtures;
+use fixtures::*;
+
+use evtx::{EvtxChunk, EvtxChunkData, ParserSettings};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Counts the heap allocations of the test binary, so it must only hold tests which measure them.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Returns the number of records of the chunk, and the allocations made while rendering them.
+fn render_counting_allocations<'a>(chunk: &'a mut EvtxChunk<'a>) -> (usize, usize) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+
+    let mut records = 0;
+    for record in chunk.iter() {
+        record.unwrap().into_xml().unwrap();
+        records += 1;
+    }
+
+    (records, ALLOCATIONS.load(Ordering::Relaxed) - before)
+}
+
+#[test]
+fn test_string_substitutions_are_allocated_in_the_chunk_arena() {
+    ensure_env_logger_initialized();
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let settings = Arc::new(ParserSettings::new().num_threads(1));
+    let mut chunk_data = EvtxChunkData::new(evtx_file[4096..4096 + 65536].to_vec(), true).unwrap();
+
+    // Without an arena, every string substitution is it's own `String`.
+    let (records, heap_allocations) = render_counting_allocations(
+        &mut EvtxChunk::new(&chunk_data.data, &chunk_data.header, Arc::clone(&settings)).unwrap(),
+    );
+
+    // The first parse grows the arena, later ones reuse it's memory.
+    render_counting_allocations(&mut chunk_data.parse(Arc::clone(&settings)).unwrap());
+    let (_, arena_allocations) =
+        render_counting_allocations(&mut chunk_data.parse(Arc::clone(&settings)).unwrap());
+
+    println!(
+        "allocations per record: {} without an arena, {} with an arena",
+        heap_allocations / records,
+        arena_allocations / records
+    );
+
+    assert_eq!(records, 91);
+    assert!(arena_allocations < heap_allocations);
+}
@@ -4,8 +4,9 @@ use fixtures::*;
 
 use assert_cmd::prelude::*;
 use std::fs::File;
-use std::io::{Read, Write};
-use std::process::Command;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
 use tempfile::tempdir;
 
 #[test]
@@ -70,3 +71,1352 @@ fn test_it_overwrites_file_anyways_if_passed_flag() {
         "Expected output to be printed to file"
     )
 }
+
+#[test]
+fn test_it_filters_by_provider_and_channel() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "--provider",
+        "Microsoft-Windows-Security-Auditing",
+        "--channel",
+        "security",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    assert!(
+        !output.stdout.is_empty(),
+        "Expected records from the provider to be printed"
+    );
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--channel", "System", sample.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    assert!(
+        output.stdout.is_empty(),
+        "Expected no records to be printed for a different channel"
+    );
+}
+
+#[test]
+fn test_it_filters_by_expression() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "--where",
+        "EventID == 4624 && EventData.LogonType == 5",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!stdout.is_empty(), "Expected logon records to be printed");
+    assert!(stdout.lines().all(|line| line.contains("\"LogonType\":5")));
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--where", "EventID ==", sample.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("Invalid filter expression"));
+}
+
+#[test]
+fn test_it_rewrites_filtered_records_to_evtx() {
+    let sample = regular_sample();
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("logons.evtx");
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "evtx",
+        "-f",
+        output.to_str().unwrap(),
+        "--where",
+        "EventID == 4624",
+        sample.to_str().unwrap(),
+    ]);
+    assert!(cmd.output().unwrap().status.success());
+
+    let mut parser = evtx::EvtxParser::from_path(&output)
+        .unwrap()
+        .with_configuration(evtx::ParserSettings::new().validate_checksums(true));
+    let records: Vec<_> = parser.records_json_value().map(Result::unwrap).collect();
+
+    assert!(!records.is_empty());
+    assert!(records
+        .iter()
+        .all(|record| record.data["Event"]["System"]["EventID"] == 4624));
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["-o", "evtx", sample.to_str().unwrap()]);
+    assert!(!cmd.output().unwrap().status.success());
+}
+
+#[test]
+fn test_it_merges_files() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("merged.evtx");
+    let inputs = [regular_sample(), sample_with_irregular_values()];
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["merge", "-f", output.to_str().unwrap()])
+        .args(inputs.iter().map(|path| path.to_str().unwrap()));
+    assert!(cmd.output().unwrap().status.success());
+
+    let expected: usize = inputs
+        .iter()
+        .map(|path| evtx::EvtxParser::from_path(path).unwrap().records().count())
+        .sum();
+
+    let mut parser = evtx::EvtxParser::from_path(&output).unwrap();
+    let records: Vec<_> = parser.records().map(Result::unwrap).collect();
+    assert_eq!(records.len(), expected);
+    assert!(records
+        .iter()
+        .enumerate()
+        .all(|(i, record)| record.event_record_id == i as u64 + 1));
+}
+
+#[test]
+fn test_it_splits_files() {
+    let sample = regular_sample();
+    let dir = tempdir().unwrap();
+    let parts = dir.path().join("parts");
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "split",
+        "-d",
+        parts.to_str().unwrap(),
+        "--max-size",
+        "200K",
+        sample.to_str().unwrap(),
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let paths: Vec<&str> = stdout
+        .lines()
+        .map(|line| line.split('\t').next().unwrap())
+        .collect();
+    assert!(paths.len() > 1);
+    assert!(paths[0].ends_with("security-0001.evtx"));
+
+    let records: usize = paths
+        .iter()
+        .map(|path| {
+            assert!(std::fs::metadata(path).unwrap().len() <= 200 * 1024);
+            evtx::EvtxParser::from_path(path).unwrap().records().count()
+        })
+        .sum();
+    assert_eq!(
+        records,
+        evtx::EvtxParser::from_path(&sample)
+            .unwrap()
+            .records()
+            .count()
+    );
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["split", "-d", parts.to_str().unwrap(), "--max-size", "2X"])
+        .arg(sample.to_str().unwrap());
+    assert!(!cmd.output().unwrap().status.success());
+}
+
+#[test]
+fn test_it_redacts_records() {
+    let sample = regular_sample();
+    let redacted = |key: &str| {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        cmd.args(["-o", "jsonl", "--redact", "--redact-key", key])
+            .args(["--redact-field", "ProcessName=other"])
+            .arg(sample.to_str().unwrap());
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let output = redacted("secret");
+    assert!(!output.contains("WIN-"));
+    assert!(!output.contains(r#""ProcessName":"C:"#));
+    assert!(output.contains(r#""Computer":"host-"#));
+    assert_eq!(output, redacted("secret"));
+    assert_ne!(output, redacted("another secret"));
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["-o", "xml", "--redact", sample.to_str().unwrap()]);
+    assert!(!cmd.output().unwrap().status.success());
+}
+
+#[test]
+fn test_it_repairs_files() {
+    let sample = regular_sample();
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("repaired.evtx");
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "repair",
+        "-f",
+        output.to_str().unwrap(),
+        sample.to_str().unwrap(),
+    ]);
+    let result = cmd.output().unwrap();
+    assert!(result.status.success());
+
+    let stdout = String::from_utf8(result.stdout).unwrap();
+    assert!(stdout.contains("File header: dirty flag cleared"));
+    assert!(stdout
+        .trim_end()
+        .ends_with("REPAIRED: 26 chunks, 2261 records"));
+
+    let mut parser = evtx::EvtxParser::from_path(&output).unwrap();
+    assert!(parser.validate().unwrap().is_valid());
+    assert_eq!(parser.records().count(), 2261);
+}
+
+#[test]
+fn test_it_imports_rendered_records() {
+    let sample = regular_sample();
+    let dir = tempdir().unwrap();
+    let rendered = dir.path().join("records.jsonl");
+    let output = dir.path().join("imported.evtx");
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "-f",
+        rendered.to_str().unwrap(),
+        sample.to_str().unwrap(),
+    ]);
+    assert!(cmd.output().unwrap().status.success());
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "import",
+        "-f",
+        output.to_str().unwrap(),
+        rendered.to_str().unwrap(),
+    ]);
+    let result = cmd.output().unwrap();
+    assert!(result.status.success());
+    assert!(String::from_utf8(result.stderr)
+        .unwrap()
+        .contains("Wrote 2261 records"));
+
+    let mut parser = evtx::EvtxParser::from_path(&output).unwrap();
+    assert!(parser.validate().unwrap().is_valid());
+    assert_eq!(parser.records().count(), 2261);
+}
+
+#[test]
+fn test_it_generates_files() {
+    let dir = tempdir().unwrap();
+    let output = dir.path().join("generated.evtx");
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "generate",
+        "-f",
+        output.to_str().unwrap(),
+        "--records",
+        "100",
+        "--seed",
+        "3",
+        "--corrupt",
+        "data-checksum",
+    ]);
+    let result = cmd.output().unwrap();
+    assert!(result.status.success());
+    assert_eq!(
+        String::from_utf8(result.stdout).unwrap(),
+        "BadDataChecksum\tchunk 0\n"
+    );
+
+    let mut parser = evtx::EvtxParser::from_path(&output).unwrap();
+    let report = parser.validate().unwrap();
+    assert_eq!(report.chunks[0].data_checksum_valid, Some(false));
+    assert_eq!(report.record_count(), 100);
+}
+
+#[test]
+fn test_it_sorts_records_by_timestamp() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "--sort-by-timestamp",
+        "--sort-buffer",
+        "100",
+        sample.to_str().unwrap(),
+    ]);
+    let output = cmd.output().unwrap();
+    assert!(output.status.success());
+
+    let timestamps: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            record["Event"]["System"]["TimeCreated"]["#attributes"]["SystemTime"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+
+    assert_eq!(timestamps.len(), 2261);
+    assert!(timestamps.windows(2).all(|w| w[0] <= w[1]));
+}
+
+#[test]
+fn test_it_reads_directories_and_globs() {
+    let dir = tempdir().unwrap();
+    let nested = dir.path().join("host").join("logs");
+    std::fs::create_dir_all(&nested).unwrap();
+    std::fs::copy(regular_sample(), nested.join("Security.evtx")).unwrap();
+    std::fs::copy(
+        sample_with_irregular_values(),
+        dir.path().join("Other.EVTX"),
+    )
+    .unwrap();
+    std::fs::write(nested.join("notes.txt"), "not a log").unwrap();
+    std::fs::write(nested.join("Broken.evtx"), "not a log either").unwrap();
+
+    let source_files = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        cmd.args(["-o", "jsonl"]).args(args);
+        let output = cmd.output().unwrap();
+
+        let mut counts: Vec<(String, usize)> = vec![];
+        for line in String::from_utf8(output.stdout).unwrap().lines() {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            let source = record["SourceFile"].as_str().unwrap();
+            let file = Path::new(source).file_name().unwrap().to_str().unwrap();
+            match counts.iter_mut().find(|(f, _)| f == file) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((file.to_string(), 1)),
+            }
+        }
+        (output.status.success(), counts)
+    };
+
+    // The broken file is reported, but does not stop the other files.
+    let (success, counts) = source_files(&[dir.path().to_str().unwrap()]);
+    assert!(!success);
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[1], ("Security.evtx".to_string(), 2261));
+
+    let pattern = dir.path().join("**").join("Sec*.evtx");
+    let (success, counts) = source_files(&[pattern.to_str().unwrap()]);
+    assert!(success);
+    assert_eq!(counts, vec![("Security.evtx".to_string(), 2261)]);
+
+    // Indented JSON and XML records are annotated too.
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    let output = cmd
+        .args(["-o", "json", "--dont-show-record-number"])
+        .arg(&pattern)
+        .arg(sample_with_irregular_values())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let first = stdout.split("\n}\n").next().unwrap().to_string() + "\n}";
+    let record: serde_json::Value = serde_json::from_str(&first).unwrap();
+    assert!(record["SourceFile"]
+        .as_str()
+        .unwrap()
+        .ends_with("Security.evtx"));
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    let output = cmd
+        .args(["-o", "xml", "--dont-show-record-number"])
+        .arg(&pattern)
+        .arg(sample_with_irregular_values())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<!-- SourceFile: "));
+}
+
+#[test]
+fn test_it_reads_from_stdin() {
+    let data = std::fs::read(regular_sample()).unwrap();
+
+    let output = |args: &[&str]| {
+        let mut child = Command::cargo_bin("evtx_dump")
+            .expect("failed to find binary")
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let mut stdin = child.stdin.take().unwrap();
+        let data = data.clone();
+        let writer = std::thread::spawn(move || stdin.write_all(&data).unwrap());
+
+        let output = child.wait_with_output().unwrap();
+        writer.join().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // Records are streamed, reports read stdin to memory.
+    assert_eq!(output(&["-o", "jsonl", "-"]).lines().count(), 2261);
+    assert!(output(&["validate", "-"]).contains("2261 records"));
+}
+
+#[test]
+fn test_it_follows_a_growing_file() {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("live.evtx");
+    // The records of the smaller file are the first records of the larger one.
+    let generate = |records| {
+        let file = evtx::EvtxGenerator::new().seed(7).records(records);
+        file.generate().unwrap().data
+    };
+    std::fs::write(&path, generate(100)).unwrap();
+
+    let mut child = Command::cargo_bin("evtx_dump")
+        .expect("failed to find binary")
+        .args(["-o", "jsonl", "--follow", "--poll-interval", "0.05"])
+        .args(["--tail", "5", "--head", "20", path.to_str().unwrap()])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+    let mut record_id = || {
+        let line = lines.next().unwrap().unwrap();
+        let record: serde_json::Value = serde_json::from_str(&line).unwrap();
+        record["Event"]["System"]["EventRecordID"].as_u64().unwrap()
+    };
+
+    let tail: Vec<u64> = (0..5).map(|_| record_id()).collect();
+    assert_eq!(tail, (96..=100).collect::<Vec<_>>());
+
+    // The file is replaced at once, so it's never read half written.
+    let grown = dir.path().join("grown.evtx");
+    std::fs::write(&grown, generate(115)).unwrap();
+    std::fs::rename(&grown, &path).unwrap();
+
+    let appended: Vec<u64> = (0..15).map(|_| record_id()).collect();
+    assert_eq!(appended, (101..=115).collect::<Vec<_>>());
+
+    // `--head` stops following.
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn test_it_filters_by_event_id_level_time_and_exclude_file() {
+    let sample = regular_sample();
+    let dir = tempdir().unwrap();
+    let exclude = dir.path().join("exclude.txt");
+    std::fs::write(&exclude, "# Special privileges\nEventID == 4672\n").unwrap();
+
+    let records = |args: &[&str]| -> Vec<serde_json::Value> {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        cmd.args(["-o", "jsonl"]).args(args).arg(&sample);
+
+        let output = cmd.output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    };
+
+    let filtered = records(&[
+        "--event-id",
+        "4624,4670-4672",
+        "--level",
+        "information",
+        "--after",
+        "2016-07-08",
+        "--before",
+        "2016-07-09 00:00:00",
+        "--exclude-file",
+        exclude.to_str().unwrap(),
+    ]);
+
+    let expected: Vec<_> = records(&[])
+        .into_iter()
+        .filter(|record| {
+            let system = &record["Event"]["System"];
+            let time = system["TimeCreated"]["#attributes"]["SystemTime"]
+                .as_str()
+                .unwrap();
+            let event_id = system["EventID"].as_u64().unwrap();
+
+            [4624, 4670, 4671].contains(&event_id)
+                && [0, 4].contains(&system["Level"].as_u64().unwrap())
+                && time.starts_with("2016-07-08")
+        })
+        .collect();
+
+    assert!(!filtered.is_empty());
+    assert_eq!(filtered, expected);
+}
+
+#[test]
+fn test_it_validates_files_with_the_validate_subcommand() {
+    let dir = tempdir().unwrap();
+    let damaged = dir.path().join("damaged.evtx");
+    let generated = evtx::EvtxGenerator::new()
+        .records(300)
+        .corrupt(evtx::Corruption::TruncatedFile, 1)
+        .generate()
+        .unwrap();
+    std::fs::write(&damaged, generated.data).unwrap();
+    let unreadable = dir.path().join("unreadable.evtx");
+    std::fs::write(&unreadable, "not an evtx file").unwrap();
+
+    let sample = regular_sample();
+    let validate = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        let output = cmd.arg("validate").args(args).output().unwrap();
+        (
+            output.status.code().unwrap(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    };
+
+    let (code, stdout) = validate(&[sample.to_str().unwrap()]);
+    assert_eq!(code, 0);
+    assert!(stdout.contains("26 chunks (26 found), dirty"), "{}", stdout);
+
+    // The sample is dirty.
+    assert_eq!(validate(&["--strict", sample.to_str().unwrap()]).0, 2);
+
+    let (code, stdout) = validate(&["-o", "json", damaged.to_str().unwrap()]);
+    assert_eq!(code, 2);
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(report["status"], "DAMAGED");
+    assert_eq!(report["report"]["truncated"], true);
+
+    let (code, stdout) = validate(&[dir.path().to_str().unwrap(), sample.to_str().unwrap()]);
+    assert_eq!(code, 3);
+    assert!(stdout.ends_with("3 files: 1 valid, 1 damaged, 1 unreadable\n"));
+}
+
+#[test]
+fn test_it_splits_the_output_into_parts() {
+    let d = tempdir().unwrap();
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["-o", "jsonl", "--split-records", "1000", "-f"])
+        .arg(d.path().join("out.jsonl"))
+        .arg(&sample);
+    assert!(cmd.output().unwrap().status.success());
+
+    let mut names: Vec<String> = std::fs::read_dir(d.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(
+        names,
+        ["out-0001.jsonl", "out-0002.jsonl", "out-0003.jsonl"]
+    );
+
+    let lines: Vec<usize> = names
+        .iter()
+        .map(|name| {
+            let parts = std::fs::read_to_string(d.path().join(name)).unwrap();
+            parts.lines().count()
+        })
+        .collect();
+    assert_eq!(lines, [1000, 1000, 261]);
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_it_compresses_the_output() {
+    let d = tempdir().unwrap();
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "--compress",
+        "gzip",
+        "--split-size",
+        "1M",
+        "-f",
+    ])
+    .arg(d.path().join("out.jsonl"))
+    .arg(&sample);
+    assert!(cmd.output().unwrap().status.success());
+
+    let mut records = 0;
+    for part in &[
+        "out-0001.jsonl.gz",
+        "out-0002.jsonl.gz",
+        "out-0003.jsonl.gz",
+    ] {
+        let mut jsonl = String::new();
+        flate2::read::MultiGzDecoder::new(File::open(d.path().join(part)).unwrap())
+            .read_to_string(&mut jsonl)
+            .unwrap();
+        assert!(jsonl.len() >= 1 << 20 || *part == "out-0003.jsonl.gz");
+        records += jsonl.lines().count();
+    }
+    assert_eq!(records, 2261);
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["-o", "jsonl", "--compress", "zstd", "-f"])
+        .arg(d.path().join("out.jsonl"))
+        .arg(&sample);
+    assert!(cmd.output().unwrap().status.success());
+
+    let compressed = std::fs::read(d.path().join("out.jsonl.zst")).unwrap();
+    assert_eq!(
+        evtx::Compression::detect(&compressed),
+        Some(evtx::Compression::Zstd)
+    );
+}
+
+#[cfg(not(feature = "compression"))]
+#[test]
+fn test_it_needs_the_compression_feature_to_compress_the_output() {
+    let d = tempdir().unwrap();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["-o", "jsonl", "--compress", "gzip", "-f"])
+        .arg(d.path().join("out.jsonl"))
+        .arg(regular_sample());
+    let output = cmd.output().unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("`compression` feature"));
+    assert_eq!(std::fs::read_dir(d.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_it_only_browses_in_a_terminal() {
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    let output = cmd.arg("browse").arg(regular_sample()).output().unwrap();
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if cfg!(feature = "tui") {
+        assert!(stderr.contains("must be run in a terminal"));
+    } else {
+        assert!(stderr.contains("`tui` feature"));
+    }
+}
+
+#[test]
+fn test_it_shows_the_progress() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    let output = cmd
+        .args(["-o", "jsonl", "--progress"])
+        .arg(&sample)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout.iter().filter(|&&b| b == b'\n').count(), 2261);
+
+    // stderr is not a terminal, so the progress is printed on lines.
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let last = stderr.lines().last().unwrap();
+    assert!(last.starts_with("100% "), "{}", stderr);
+    assert!(last.contains(" 2261 records "), "{}", stderr);
+    assert!(last.ends_with("ETA 00:00"), "{}", stderr);
+}
+
+#[test]
+fn test_it_diffs_two_files() {
+    let d = tempdir().unwrap();
+    let sample = regular_sample();
+
+    // The records of the sample, except for the logon events.
+    let filtered = d.path().join("filtered.evtx");
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["-o", "evtx", "--event-id", "1-4623,4625-65535", "-f"])
+        .arg(&filtered)
+        .arg(&sample);
+    assert!(cmd.output().unwrap().status.success());
+
+    let diff = |before: &Path, after: &Path, format: &str| {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        let output = cmd
+            .args(["diff", "-o", format])
+            .arg(before)
+            .arg(after)
+            .output()
+            .unwrap();
+        (
+            output.status.code().unwrap(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    };
+
+    let (code, stdout) = diff(&sample, &sample, "text");
+    assert_eq!(code, 0);
+    assert_eq!(stdout, "0 added, 0 removed, 0 modified, 2261 unchanged\n");
+
+    let (code, stdout) = diff(&sample, &filtered, "json");
+    assert_eq!(code, 2);
+    let report: serde_json::Value = serde_json::from_str(&stdout).unwrap();
+    let removed = report["removed"].as_u64().unwrap();
+    assert!(removed > 0);
+    assert_eq!(report["added"], 0);
+    assert_eq!(report["modified"], 0);
+    assert_eq!(report["unchanged"].as_u64().unwrap() + removed, 2261);
+
+    let (code, stdout) = diff(&filtered, &sample, "text");
+    assert_eq!(code, 2);
+    assert!(
+        stdout.lines().next().unwrap().starts_with("+ "),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn test_it_lists_the_templates_and_strings_of_chunks() {
+    let sample = regular_sample();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        let output = cmd.args(args).arg(&sample).output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let templates = run(&["templates"]);
+    let mut lines = templates.lines();
+    assert_eq!(
+        lines.next(),
+        Some("Chunk\tOffset\tGUID\tSize\tSubstitutions\tSkeleton")
+    );
+    let rows: Vec<Vec<&str>> = lines.map(|line| line.split('\t').collect()).collect();
+    assert!(rows.iter().all(|row| row.len() == 6));
+    assert!(rows
+        .iter()
+        .any(|row| row[5].starts_with("<Event xmlns=") && row[5].contains("{0:")));
+
+    // Templates are defined again in every chunk using them.
+    let unique = run(&["templates", "--unique", "-o", "json"]);
+    let unique: Vec<serde_json::Value> = unique
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(unique.len() < rows.len());
+    assert_eq!(
+        unique
+            .iter()
+            .map(|t| t["chunks"].as_u64().unwrap())
+            .sum::<u64>(),
+        rows.len() as u64
+    );
+
+    let strings = run(&["strings", "-o", "json"]);
+    let strings: Vec<serde_json::Value> = strings
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(strings
+        .iter()
+        .any(|s| s["chunk"] == 0 && s["value"] == "EventID"));
+}
+
+#[test]
+fn test_it_dumps_a_single_record_or_chunk() {
+    let sample = regular_sample();
+    let run = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        let output = cmd
+            .args(["-o", "jsonl"])
+            .args(args)
+            .arg(&sample)
+            .output()
+            .unwrap();
+        (
+            output.status.code().unwrap(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    };
+
+    let (code, stdout) = run(&["--record-id", "170", "--hexdump"]);
+    assert_eq!(code, 0);
+    let lines: Vec<&str> = stdout.lines().collect();
+    let record: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(record["Event"]["System"]["EventRecordID"], 170);
+    // The record starts with it's magic.
+    assert!(
+        lines[1].starts_with("00000000  2a 2a 00 00"),
+        "{}",
+        lines[1]
+    );
+    assert!(lines[1..].iter().all(|line| line.ends_with('|')));
+
+    let (code, stdout) = run(&["--chunk", "1"]);
+    assert_eq!(code, 0);
+    let ids: Vec<u64> = stdout
+        .lines()
+        .map(|line| {
+            let record: serde_json::Value = serde_json::from_str(line).unwrap();
+            record["Event"]["System"]["EventRecordID"].as_u64().unwrap()
+        })
+        .collect();
+    let mut parser = evtx::EvtxParser::from_path(&sample).unwrap();
+    let chunk = parser.chunks().nth(1).unwrap().unwrap();
+    assert_eq!(ids.first(), Some(&chunk.header.first_event_record_id));
+    assert_eq!(ids.last(), Some(&chunk.header.last_event_record_id));
+
+    assert_eq!(run(&["--record-id", "100000"]), (1, String::new()));
+}
+
+#[test]
+fn test_it_counts_records_by_event_id() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--count-by", "eventid", sample.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    assert_eq!(lines[0], "EventID\tCount");
+    assert_eq!(lines[1], "4907\t620");
+
+    let total: u64 = lines[1..]
+        .iter()
+        .map(|line| line.rsplit('\t').next().unwrap().parse::<u64>().unwrap())
+        .sum();
+
+    assert_eq!(total, 2261);
+}
+
+#[test]
+fn test_it_prints_a_stacking_analysis() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "--analyze",
+        "--stack-field",
+        "TargetUserName",
+        "--top",
+        "2",
+        "-o",
+        "json",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(report["records"], 2261);
+    assert_eq!(
+        report["fields"]["TargetUserName"]["most_common"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--analyze", sample.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("category,key,count,first_seen,last_seen\n"));
+    assert!(stdout.contains("\nevent_id,4907,620,"));
+}
+
+#[test]
+fn test_it_joins_lookup_tables() {
+    let sample = regular_sample();
+    let dir = tempdir().unwrap();
+    let table = dir.path().join("sids.csv");
+    File::create(&table)
+        .unwrap()
+        .write_all(b"Sid,Employee,Department\nS-1-5-18,SYSTEM,IT\n")
+        .unwrap();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "--lookup",
+        &format!("SubjectUserSid,TargetUserSid={}", table.display()),
+        "-o",
+        "jsonl",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let records: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    let joined: Vec<&serde_json::Value> = records
+        .iter()
+        .filter(|record| record["Event"]["EventData"]["SubjectUserSid"] == "S-1-5-18")
+        .collect();
+    assert!(!joined.is_empty());
+    assert!(joined.iter().all(|record| {
+        record["Event"]["Lookup"]["SubjectUserSid"]
+            == serde_json::json!({"Employee": "SYSTEM", "Department": "IT"})
+    }));
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--lookup", "SubjectUserSid", sample.to_str().unwrap()]);
+    assert!(!cmd.output().unwrap().status.success());
+}
+
+#[test]
+fn test_it_suppresses_duplicate_records() {
+    let sample = regular_sample();
+
+    let count = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        cmd.args(args)
+            .args(["-o", "jsonl", sample.to_str().unwrap()]);
+
+        let output = cmd.output().unwrap();
+        String::from_utf8(output.stdout).unwrap().lines().count()
+    };
+
+    assert_eq!(count(&[]), 2261);
+    assert_eq!(count(&["--dedup"]), 2249);
+    assert_eq!(count(&["--dedup-on", "provider,eventid"]), 31);
+}
+
+#[test]
+fn test_it_validates_files() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["validate", "-o", "json", sample.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(report["status"], "VALID");
+    assert_eq!(report["report"]["valid"], true);
+    assert_eq!(report["report"]["records"], 2261);
+}
+
+#[test]
+fn test_it_reports_tampering() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "tampering",
+        "--timestamp-tolerance",
+        "60",
+        "-o",
+        "json",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let file: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+
+    assert_eq!(file["clean"], true);
+    assert_eq!(file["report"]["records"], 2261);
+    assert_eq!(file["report"]["missing_record_count"], 0);
+
+    // Without a tolerance, the records written out of order are reported, as text.
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "tampering",
+        "--timestamp-tolerance",
+        "0",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(output.status.code(), Some(2), "{}", stdout);
+    assert!(stdout.starts_with(&format!("{}:\n", sample.display())));
+    assert!(stdout.contains("is earlier than record"));
+}
+
+#[test]
+fn test_it_prints_findings() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "--findings",
+        "--timestamp-tolerance",
+        "60",
+        "-o",
+        "json",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let findings = report["findings"].as_array().unwrap();
+
+    assert_eq!(report["records"], 2261);
+    assert_eq!(report["max_severity"], "medium");
+    assert_eq!(findings.len(), 36);
+    assert!(findings
+        .iter()
+        .all(|finding| finding["kind"] == "event_logging_shutdown"));
+}
+
+#[test]
+fn test_it_recovers_slack_records() {
+    let sample = sample_with_a_bad_checksum_2();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--recover-slack", "-o", "jsonl", sample.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    let records = String::from_utf8(output.stdout).unwrap().lines().count();
+
+    assert!(records > 0);
+}
+
+#[test]
+fn test_it_carves_records() {
+    let dir = tempdir().unwrap();
+    let blob = dir.path().join("blob.bin");
+
+    // The chunks of the sample, not aligned to the chunk size.
+    let mut data = vec![0xaa_u8; 100];
+    data.extend_from_slice(&std::fs::read(regular_sample()).unwrap()[4096..]);
+    std::fs::write(&blob, data).unwrap();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--carve", "-o", "jsonl", blob.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    let records = String::from_utf8(output.stdout).unwrap().lines().count();
+
+    assert_eq!(records, 2261);
+}
+
+#[test]
+fn test_it_prints_stats() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--stats", "-o", "jsonl", sample.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(stderr.contains("Records: 2261 ok, 0 failed"), "{}", stderr);
+}
+
+#[test]
+fn test_it_applies_rules_files() {
+    let sample = regular_sample();
+    let dir = tempdir().unwrap();
+    let rules = dir.path().join("rules.toml");
+    std::fs::write(&rules, "[[exclude]]\nevent_ids = [\"4600-4699\"]\n").unwrap();
+
+    let count = |args: &[&str]| {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        cmd.args(args)
+            .args(["-o", "jsonl", sample.to_str().unwrap()]);
+
+        let output = cmd.output().unwrap();
+        String::from_utf8(output.stdout).unwrap().lines().count()
+    };
+
+    let all = count(&[]);
+    let without_noise = count(&["--exclude-noise"]);
+    let with_rules = count(&["--rules", rules.to_str().unwrap()]);
+
+    assert!(without_noise < all);
+    assert!(with_rules < without_noise);
+    assert!(count(&["--rules", rules.to_str().unwrap(), "--exclude-noise"]) <= with_rules);
+}
+
+#[test]
+fn test_it_reports_largest_records() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "json",
+        "--largest-records",
+        "3",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+
+    assert_eq!(lines.len(), 4, "Expected a header and 3 records");
+    assert!(lines[0].starts_with("EventRecordID\t"));
+
+    let rendered_sizes: Vec<usize> = lines[1..]
+        .iter()
+        .map(|line| line.rsplit('\t').next().unwrap().parse().unwrap())
+        .collect();
+
+    assert!(rendered_sizes.windows(2).all(|w| w[0] >= w[1]));
+}
+
+#[test]
+fn test_it_combines_filters() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "--query",
+        "EventID=4624 AND TargetUserName~'*service'",
+        "--where",
+        "EventData.LogonType == 5",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!stdout.is_empty(), "Expected service logons to be printed");
+    assert!(stdout
+        .lines()
+        .all(|line| line.contains("\"LogonType\":5") && line.contains(" SERVICE\"")));
+}
+
+#[test]
+fn test_it_filters_by_regex_lists() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "--allow-regex",
+        "EventData.TargetUserName=(?i)^local",
+        "--allow-regex",
+        "EventData.TargetUserName=^NETWORK",
+        "--deny-regex",
+        "EventData.TargetUserName=LOCAL",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!stdout.is_empty(), "Expected records to be printed");
+    assert!(stdout
+        .lines()
+        .all(|line| line.contains("\"TargetUserName\":\"NETWORK SERVICE\"")));
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "--deny-regex",
+        "EventData.Image=(",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_it_filters_by_sid_and_account() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "--sid",
+        "S-1-5-21-2603537626-3982775912-406486804-*",
+        "--account",
+        "WIN-03DLIIOFRRA\\archir",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!stdout.is_empty(), "Expected records to be printed");
+    assert!(stdout.lines().all(
+        |line| line.contains("S-1-5-21-2603537626-3982775912-406486804-")
+            && line.contains("\"archir\"")
+    ));
+}
+
+#[test]
+fn test_it_selects_head_tail_and_sample() {
+    let sample = regular_sample();
+
+    let record_ids = |args: &[&str]| -> Vec<String> {
+        let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+        cmd.args(["-o", "jsonl"])
+            .args(args)
+            .arg(sample.to_str().unwrap());
+
+        let output = cmd.output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        stdout
+            .lines()
+            .map(|line| {
+                let record: serde_json::Value = serde_json::from_str(line).unwrap();
+                record["Event"]["System"]["EventRecordID"].to_string()
+            })
+            .collect()
+    };
+
+    assert_eq!(record_ids(&["--head", "2"]), vec!["1", "2"]);
+    assert_eq!(record_ids(&["--tail", "2"]), vec!["2260", "2261"]);
+    // The 1000th of the last 1500 records (762..=2261).
+    assert_eq!(
+        record_ids(&["--sample", "1000", "--tail", "1500"]),
+        vec!["1761"]
+    );
+
+    // Sampling and tails count the records matching the filters.
+    let logons = record_ids(&["--where", "EventID == 4624"]);
+    assert!(logons.len() > 30);
+    assert_eq!(
+        record_ids(&["--sample", "10", "--where", "EventID == 4624"]),
+        logons
+            .iter()
+            .skip(9)
+            .step_by(10)
+            .cloned()
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        record_ids(&["--tail", "3", "--where", "EventID == 4624"]),
+        logons[logons.len() - 3..]
+    );
+}
+
+#[test]
+fn test_it_filters_by_xpath() {
+    let sample = regular_sample();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "--xpath",
+        "*[System[(EventID=4624)] and EventData[Data[@Name='LogonType']=5]]",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!stdout.is_empty(), "Expected logon records to be printed");
+    assert!(stdout.lines().all(|line| line.contains("\"LogonType\":5")));
+
+    // A query saved by EventViewer, matching any `Data` value.
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args([
+        "-o",
+        "jsonl",
+        "--xpath",
+        r#"<QueryList>
+             <Query Id="0" Path="Security">
+               <Select Path="Security">*[EventData[Data='NT AUTHORITY']]</Select>
+               <Suppress Path="Security">*[System[(EventID=4624)]]</Suppress>
+             </Query>
+           </QueryList>"#,
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = cmd.output().unwrap();
+    let records: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    assert!(!records.is_empty(), "Expected records to be printed");
+    assert!(records.iter().all(|record| {
+        let event = &record["Event"];
+        event["System"]["EventID"] != 4624
+            && event["EventData"]
+                .as_object()
+                .unwrap()
+                .values()
+                .any(|value| value == "NT AUTHORITY")
+    }));
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--xpath", "*[System[EventID=]]", sample.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_it_renders_partial_records() {
+    let sample = sample_with_binxml_as_substitution_tokens_and_pi_target();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    cmd.args(["--partial-records", "-o", "jsonl", sample.to_str().unwrap()]);
+
+    let output = cmd.output().unwrap();
+    let records = String::from_utf8(output.stdout).unwrap().lines().count();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(records, 340);
+    assert_eq!(stderr.matches("substitution failed to decode").count(), 5);
+}
+
+#[test]
+fn test_it_repairs_chunk_headers() {
+    let sample = sample_with_a_bad_chunk_magic();
+
+    let mut cmd = Command::cargo_bin("evtx_dump").expect("failed to find binary");
+    // The first record of the repaired chunk is corrupted, so it's other records are only reached by resyncing.
+    cmd.args([
+        "--repair-chunks",
+        "--resync-records",
+        sample.to_str().unwrap(),
+    ]);
+
+    let output = String::from_utf8(cmd.output().unwrap().stdout).unwrap();
+
+    let records: Vec<&str> = output
+        .lines()
+        .filter(|line| line.starts_with("Record "))
+        .collect();
+
+    assert_eq!(records.len(), 383);
+    assert_eq!(
+        records
+            .iter()
+            .filter(|line| line.ends_with("(recovered)"))
+            .count(),
+        82
+    );
+}
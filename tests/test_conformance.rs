@@ -0,0 +1,37 @@
+//! Compares the XML output against `wevtutil` reference outputs (see `evtx::conformance`).
+//!
+//! Run with `EVTX_REFERENCE_DIR=<dir with <name>.xml files> cargo test --features conformance --test test_conformance`,
+//! optionally setting `EVTX_SAMPLES_DIR` (defaults to the samples of this crate).
+#![cfg(feature = "conformance")]
+mod fixtures;
+
+use evtx::conformance::compare_dir;
+use evtx::ParserSettings;
+use fixtures::*;
+use std::env;
+use std::path::PathBuf;
+
+#[test]
+fn test_conformance_with_reference_dir() {
+    ensure_env_logger_initialized();
+
+    let reference_dir = match env::var_os("EVTX_REFERENCE_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => return,
+    };
+
+    let samples = env::var_os("EVTX_SAMPLES_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(samples_dir);
+
+    let reports = compare_dir(samples, reference_dir, ParserSettings::default()).unwrap();
+
+    for report in &reports {
+        println!("{}", report);
+    }
+
+    assert!(
+        reports.iter().all(|report| report.is_conformant()),
+        "Output diverges from the references"
+    );
+}
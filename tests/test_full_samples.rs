@@ -1,9 +1,13 @@
 mod fixtures;
 
-use evtx::{EvtxParser, ParserSettings};
+use chrono::Duration;
+use evtx::err;
+use evtx::{DedupField, DedupKey, EvtxParser, ParserSettings, RecordFilter};
 use fixtures::*;
 use log::Level;
+use std::collections::BTreeMap;
 use std::path::Path;
+use std::sync::Arc;
 
 /// Tests an .evtx file, asserting the number of parsed records matches `count`.
 fn test_full_sample(path: impl AsRef<Path>, ok_count: usize, err_count: usize) {
@@ -110,6 +114,78 @@ fn test_dirty_sample_with_a_bad_chunk_magic() {
     test_full_sample(sample_with_a_bad_chunk_magic(), 270, 5)
 }
 
+/// Tests an .evtx file with `ParserSettings::resync_records`, asserting that the records parsed without it
+/// are unchanged, and that the `recovered_count` additional records are real records
+/// (their `EventRecordID` matches their header, and their timestamp is within a day of the other records).
+fn test_resynced_sample(path: impl AsRef<Path>, recovered_count: usize, err_count: usize) {
+    ensure_env_logger_initialized();
+
+    let records = |resync_records: bool| {
+        let mut parser = EvtxParser::from_path(path.as_ref())
+            .unwrap()
+            .with_configuration(ParserSettings::new().resync_records(resync_records));
+
+        let mut records = BTreeMap::new();
+        let mut errors = 0;
+        for r in parser.records_json_value() {
+            match r {
+                Ok(r) => {
+                    records.insert(r.event_record_id, r);
+                }
+                Err(_) => errors += 1,
+            }
+        }
+        (records, errors)
+    };
+
+    let (expected, _) = records(false);
+    let (actual, actual_err_count) = records(true);
+
+    for (id, record) in &expected {
+        assert_eq!(actual.get(id), Some(record), "Record {} changed", id);
+    }
+
+    let first = expected.values().map(|r| r.timestamp).min().unwrap();
+    let last = expected.values().map(|r| r.timestamp).max().unwrap();
+
+    let recovered: Vec<_> = actual
+        .values()
+        .filter(|r| !expected.contains_key(&r.event_record_id))
+        .collect();
+    for record in &recovered {
+        assert_eq!(
+            record.data["Event"]["System"]["EventRecordID"], record.event_record_id,
+            "Record {} is not a real record",
+            record.event_record_id
+        );
+        assert!(
+            record.timestamp > first - Duration::days(1)
+                && record.timestamp < last + Duration::days(1),
+            "Record {} has an implausible timestamp {}",
+            record.event_record_id,
+            record.timestamp
+        );
+    }
+
+    assert_eq!(recovered.len(), recovered_count);
+    assert_eq!(actual_err_count, err_count);
+}
+
+#[test]
+fn test_resyncs_dirty_sample_with_a_bad_checksum() {
+    test_resynced_sample(sample_with_a_bad_checksum(), 54, 13)
+}
+
+#[test]
+fn test_resyncs_dirty_sample_with_a_bad_checksum_2() {
+    test_resynced_sample(sample_with_a_bad_checksum_2(), 21, 2)
+}
+
+#[test]
+fn test_resyncs_dirty_sample_with_a_bad_chunk_magic() {
+    test_resynced_sample(sample_with_a_bad_chunk_magic(), 31, 5)
+}
+
 #[test]
 fn test_dirty_sample_binxml_with_incomplete_token() {
     // Contains an unparsable record
@@ -136,3 +212,82 @@ fn test_sample_with_binxml_as_substitution_tokens_and_pi_target() {
         5,
     )
 }
+
+#[test]
+fn test_sample_with_pi_target_renders_partial_records() {
+    ensure_env_logger_initialized();
+    let mut parser =
+        EvtxParser::from_path(sample_with_binxml_as_substitution_tokens_and_pi_target())
+            .unwrap()
+            .with_configuration(ParserSettings::new().partial_records(true));
+
+    let records: Vec<_> = parser.records().map(Result::unwrap).collect();
+    assert_eq!(records.len(), 340);
+
+    let partial: Vec<_> = records.iter().filter(|r| !r.errors.is_empty()).collect();
+    assert_eq!(partial.len(), 5);
+    assert!(partial
+        .iter()
+        .all(|r| r.data.contains("<!-- parse error -->")));
+
+    let partial_json: Vec<_> = parser
+        .records_json_value()
+        .map(Result::unwrap)
+        .filter(|r| !r.errors.is_empty())
+        .collect();
+    assert_eq!(partial_json.len(), 5);
+    assert!(partial_json
+        .iter()
+        .all(|r| r.data.to_string().contains("\"__error__\"")));
+}
+
+#[test]
+fn test_records_which_cannot_be_deserialized_are_not_filtered_out() {
+    ensure_env_logger_initialized();
+    let settings = ParserSettings::new().event_id_ranges_filter(&[0..=u16::MAX]);
+    let mut parser = EvtxParser::from_path(sample_with_a_bad_checksum_2())
+        .unwrap()
+        .with_configuration(settings);
+
+    // The same records as without the filter, see `test_dirty_sample_with_a_bad_checksum_2`.
+    let results: Vec<_> = parser.records().collect();
+    assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1774);
+    assert_eq!(results.iter().filter(|r| r.is_err()).count(), 2);
+}
+
+#[test]
+fn test_record_which_cannot_be_deserialized_fails_every_use() {
+    ensure_env_logger_initialized();
+    let mut parser = EvtxParser::from_path(sample_with_a_bad_checksum_2()).unwrap();
+    let settings = Arc::new(ParserSettings::default());
+
+    let mut failed = 0;
+    for chunk in parser.chunks() {
+        let mut chunk = chunk.unwrap();
+        let mut chunk = chunk.parse(Arc::clone(&settings)).unwrap();
+
+        // Records whose header is corrupted are errors of the iteration.
+        for record in chunk.iter().filter_map(Result::ok) {
+            let first = match record.tokens() {
+                Ok(_) => continue,
+                Err(err::Error::Shared { source }) => source,
+                Err(other) => panic!("Expected a shared error, found {}", other),
+            };
+            failed += 1;
+
+            // The error is cached, rather than deserializing the record again.
+            match record.tokens() {
+                Err(err::Error::Shared { source }) => assert!(Arc::ptr_eq(&source, &first)),
+                other => panic!("Expected the same error, found {:?}", other.err()),
+            }
+
+            assert!(RecordFilter::event_ids(&[4624]).matches(&record).is_err());
+            assert!(DedupKey::of(&record, &DedupField::ALL).is_err());
+            assert!(DedupKey::of(&record, &[DedupField::Timestamp]).is_ok());
+            assert!(record.clone().into_owned().tokens().is_err());
+            assert!(record.into_xml().is_err());
+        }
+    }
+
+    assert_eq!(failed, 1);
+}
@@ -1,8 +1,15 @@
 mod fixtures;
 use fixtures::*;
 
-use evtx::{EvtxParser, ParserSettings};
+use chrono::{DateTime, TimeZone, Utc};
+use evtx::binxml::value_variant::BinXmlValue;
+use evtx::{
+    EvtxParser, EvtxWriter, GuidFormat, LookupTable, ParserSettings, PseudonymKind, Redactor,
+    RenderFormat,
+};
 use pretty_assertions::assert_eq;
+use serde::Deserialize;
+use std::sync::Arc;
 
 #[test]
 fn test_event_xml_sample() {
@@ -27,6 +34,139 @@ fn test_event_xml_sample() {
     );
 }
 
+#[test]
+fn test_record_accessors() {
+    ensure_env_logger_initialized();
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+    let mut chunk = parser.chunks().next().unwrap().unwrap();
+    let mut chunk = chunk.parse(Default::default()).unwrap();
+    let record = chunk.iter().next().unwrap().unwrap();
+
+    // See `security_event_1.xml`.
+    assert_eq!(record.event_id(), Some(4608));
+    assert_eq!(record.version(), Some(0));
+    assert_eq!(record.level(), Some(0));
+    assert_eq!(record.task(), Some(12288));
+    assert_eq!(record.opcode(), Some(0));
+    assert_eq!(record.keywords(), Some(0x8020_0000_0000_0000));
+    assert_eq!(
+        record.provider_name().as_deref(),
+        Some("Microsoft-Windows-Security-Auditing")
+    );
+    assert_eq!(record.channel().as_deref(), Some("Security"));
+    assert_eq!(record.computer().as_deref(), Some("37L4247F27-25"));
+    assert_eq!(
+        record.time_created(),
+        Some(Utc.timestamp_opt(1_468_001_571, 681_640_000).unwrap())
+    );
+    assert_eq!(record.process_id(), Some(456));
+    assert_eq!(record.thread_id(), Some(460));
+    assert_eq!(record.user_id(), None);
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Logon {
+    target_user_name: String,
+    target_user_sid: String,
+    logon_type: u32,
+    // Hexadecimal strings are parsed as numbers.
+    process_id: u32,
+    target_logon_id: u64,
+    ip_address: Option<String>,
+    not_in_the_record: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Event {
+    system: System,
+    event_data: Logon,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct System {
+    #[serde(rename = "EventID")]
+    event_id: u16,
+    execution: Execution,
+    time_created: TimeCreated,
+    security: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Execution {
+    #[serde(rename = "ProcessID")]
+    process_id: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeCreated {
+    #[serde(rename = "SystemTime")]
+    system_time: DateTime<Utc>,
+}
+
+#[test]
+fn test_deserializes_records() {
+    ensure_env_logger_initialized();
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+    let mut chunk = parser.chunks().next().unwrap().unwrap();
+    let mut chunk = chunk.parse(Default::default()).unwrap();
+    let record = chunk.iter().nth(1).unwrap().unwrap();
+
+    let logon: Logon = record.deserialize().unwrap();
+    assert_eq!(
+        logon,
+        Logon {
+            target_user_name: "SYSTEM".to_string(),
+            target_user_sid: "S-1-5-18".to_string(),
+            logon_type: 0,
+            process_id: 4,
+            target_logon_id: 0x3e7,
+            ip_address: Some("-".to_string()),
+            not_in_the_record: None,
+        }
+    );
+
+    let event: Event = record.deserialize_event().unwrap();
+    assert_eq!(event.system.event_id, 4624);
+    assert_eq!(event.system.execution.process_id, 456);
+    assert_eq!(
+        event.system.time_created.system_time,
+        Utc.timestamp_opt(1_468_001_571, 681_640_000).unwrap()
+    );
+    assert_eq!(event.system.security, None);
+    assert_eq!(event.event_data, logon);
+
+    let data = record.event_data().unwrap();
+    assert_eq!(data.len(), 20);
+    assert_eq!(
+        data.keys().next().map(String::as_str),
+        Some("AuthenticationPackageName")
+    );
+    assert_eq!(
+        data["TargetUserName"],
+        BinXmlValue::StringType("SYSTEM".into())
+    );
+    assert_eq!(
+        data["TargetLogonId"],
+        BinXmlValue::HexInt64Type("0x3e7".into())
+    );
+
+    // Type mismatches are errors.
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Mismatch {
+        #[allow(dead_code)]
+        target_user_name: u32,
+    }
+    assert!(record.deserialize::<Mismatch>().is_err());
+}
+
 #[test]
 fn test_event_json_sample() {
     ensure_env_logger_initialized();
@@ -195,3 +335,339 @@ fn test_event_json_sample_with_separate_json_attributes() {
             .collect::<String>()
     );
 }
+
+#[test]
+fn test_owned_records_can_be_sent_to_other_threads() {
+    fn assert_send<T: Send>(_: &T) {}
+
+    ensure_env_logger_initialized();
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec()).unwrap();
+
+    let expected: Vec<_> = parser.records().map(Result::unwrap).collect();
+
+    assert_send(&parser.records());
+    assert_send(&parser.records_json_value());
+    assert_send(&parser.owned_records());
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let renderer = std::thread::spawn(move || {
+        receiver
+            .into_iter()
+            .map(|record: evtx::EvtxRecordOwned| record.into_xml().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    for record in parser.owned_records() {
+        sender.send(record.unwrap()).unwrap();
+    }
+    drop(sender);
+
+    let rendered = renderer.join().unwrap();
+
+    assert_eq!(rendered.len(), expected.len());
+    for (record, expected) in rendered.iter().zip(expected.iter()) {
+        assert_eq!(record.event_record_id, expected.event_record_id);
+        assert_eq!(record.data, expected.data);
+    }
+}
+
+#[test]
+fn test_event_symbolic_names() {
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let settings = ParserSettings::new()
+        .num_threads(1)
+        .symbolic_names(RenderFormat::Json, true)
+        .symbolic_names(RenderFormat::Xml, true);
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(settings);
+
+    let logon = parser.records_json_value().nth(1).unwrap().unwrap();
+    assert_eq!(
+        logon.data["Event"]["RenderingInfo"],
+        serde_json::json!({
+            "Level": "Information",
+            "Task": "Logon",
+            "Opcode": "Info",
+            "Keywords": ["Audit Success"]
+        })
+    );
+    assert_eq!(logon.data["Event"]["System"]["Task"], 12544);
+
+    let logon = parser.records().nth(1).unwrap().unwrap();
+    assert!(logon.data.ends_with(
+        "  <RenderingInfo>\n    <Level>Information</Level>\n    <Task>Logon</Task>\n    <Opcode>Info</Opcode>\n    \
+         <Keywords>\n      <Keyword>Audit Success</Keyword>\n    </Keywords>\n  </RenderingInfo>\n</Event>"
+    ));
+
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(ParserSettings::new().symbolic_names(RenderFormat::Json, true));
+    let logon = parser.records().nth(1).unwrap().unwrap();
+    assert!(!logon.data.contains("RenderingInfo"));
+}
+
+#[test]
+fn test_event_guid_format() {
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let settings = ParserSettings::new()
+        .num_threads(1)
+        .guid_format(GuidFormat::braced());
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(settings);
+
+    let record = parser.records_json_value().next().unwrap().unwrap();
+    assert_eq!(
+        record.data["Event"]["System"]["Provider"]["#attributes"]["Guid"],
+        "{54849625-5478-4994-A5BA-3E3B0328C30D}"
+    );
+
+    let record = parser.records().next().unwrap().unwrap();
+    assert!(record
+        .data
+        .contains(r#"Guid="{54849625-5478-4994-A5BA-3E3B0328C30D}""#));
+}
+
+#[test]
+fn test_event_json_with_resolved_sids() {
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(ParserSettings::new().num_threads(1).resolve_sids(true));
+
+    let logon = parser.records_json_value().nth(1).unwrap().unwrap();
+    assert_eq!(
+        logon.data["Event"]["ResolvedSids"],
+        serde_json::json!({
+            "S-1-0-0": "NULL SID",
+            "S-1-5-18": "NT AUTHORITY\\SYSTEM"
+        })
+    );
+    assert_eq!(
+        logon.data["Event"]["EventData"]["TargetUserSid"],
+        "S-1-5-18"
+    );
+}
+
+#[test]
+fn test_event_json_with_attack_techniques() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("attack.toml"),
+        r#"
+[[techniques]]
+id = "T1078"
+name = "Valid Accounts"
+provider = "Microsoft-Windows-Security-Auditing"
+event_id = 4624
+fields = { TargetUserSid = "S-1-5-18" }
+
+[[techniques]]
+id = "T1134"
+event_id = 4672
+"#,
+    )
+    .unwrap();
+
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(
+            ParserSettings::new()
+                .load_enrichment_dir(dir.path())
+                .unwrap(),
+        );
+
+    let records: Vec<_> = parser.records_json_value().map(Result::unwrap).collect();
+
+    let logon = &records[1].data;
+    assert_eq!(logon["Event"]["System"]["EventID"], 4624);
+    assert_eq!(
+        logon["threat"],
+        serde_json::json!({"technique": {"id": ["T1078"], "name": ["Valid Accounts"]}})
+    );
+
+    let privileges = records
+        .iter()
+        .find(|record| record.data["Event"]["System"]["EventID"] == 4672)
+        .unwrap();
+    assert_eq!(
+        privileges.data["threat"],
+        serde_json::json!({"technique": {"id": ["T1134"]}})
+    );
+
+    assert!(records
+        .iter()
+        .filter(|record| record.data["Event"]["System"]["EventID"] == 4634)
+        .all(|record| record.data.get("threat").is_none()));
+}
+
+#[test]
+fn test_event_json_with_decoded_logons() {
+    let evtx_file = include_bytes!("../samples/security_big_sample.evtx");
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(ParserSettings::new().decode_logon_events(true));
+
+    let records: Vec<_> = parser.records_json_value().map(Result::unwrap).collect();
+    let with_event_id = |event_id: u64| {
+        records
+            .iter()
+            .find(|record| record.data["Event"]["System"]["EventID"] == event_id)
+            .unwrap()
+    };
+
+    let logon = &with_event_id(4624).data["Event"];
+    assert_eq!(logon["Logon"]["Kind"], "Logon");
+    assert_eq!(logon["Logon"]["LogonType"], logon["EventData"]["LogonType"]);
+    assert!(logon["Logon"]["LogonTypeName"].is_string());
+
+    let privileges = &with_event_id(4672).data["Event"]["Logon"];
+    assert_eq!(privileges["Kind"], "SpecialPrivileges");
+    assert!(privileges["Privileges"]
+        .as_array()
+        .unwrap()
+        .contains(&"SeDebugPrivilege".into()));
+
+    assert!(records
+        .iter()
+        .filter(|record| record.data["Event"]["System"]["EventID"] == 4688)
+        .all(|record| record.data["Event"].get("Logon").is_none()));
+}
+
+#[test]
+fn test_event_json_with_lookup_tables() {
+    let table = LookupTable::from_json_value(&serde_json::json!({
+        "s-1-5-18": {"Owner": "Local System", "Tier": 0}
+    }))
+    .unwrap();
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(ParserSettings::new().lookup("SubjectUserSid", Arc::new(table)));
+
+    let records: Vec<_> = parser.records_json_value().map(Result::unwrap).collect();
+
+    for record in &records {
+        let event = &record.data["Event"];
+        if event["EventData"]["SubjectUserSid"] == "S-1-5-18" {
+            assert_eq!(event["Lookup"]["SubjectUserSid"]["Owner"], "Local System");
+            assert_eq!(event["Lookup"]["SubjectUserSid"]["Tier"], 0);
+        } else {
+            assert!(event.get("Lookup").is_none());
+        }
+    }
+}
+
+#[test]
+fn test_event_json_with_redaction() {
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let redactor = Redactor::new("secret").with_default_fields();
+
+    let original: Vec<_> = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .records_json_value()
+        .map(Result::unwrap)
+        .collect();
+
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(ParserSettings::new().redactor(redactor.clone()));
+    let redacted: Vec<_> = parser.records_json_value().map(Result::unwrap).collect();
+
+    let mut redacted_fields = 0;
+    for (original, redacted) in original.iter().zip(&redacted) {
+        let computer = original.data["Event"]["System"]["Computer"]
+            .as_str()
+            .unwrap();
+        assert_eq!(
+            redacted.data["Event"]["System"]["Computer"],
+            redactor.pseudonym(PseudonymKind::Host, computer)
+        );
+
+        let fields = original.data["Event"]["EventData"].as_object();
+        for (name, value) in fields.into_iter().flatten() {
+            let expected = match (redactor.kind_of(name), value.as_str()) {
+                (Some(kind), Some(value)) => {
+                    redacted_fields += 1;
+                    redactor.pseudonym(kind, value).into()
+                }
+                _ => value.clone(),
+            };
+            assert_eq!(redacted.data["Event"]["EventData"][name], expected);
+        }
+    }
+    assert!(redacted_fields > 0);
+
+    // The element trees of records are redacted too.
+    let mut writer = EvtxWriter::new(std::io::Cursor::new(vec![])).unwrap();
+    parser.rewrite(&mut writer).unwrap();
+    let data = writer.finish().unwrap().into_inner();
+
+    let rewritten: Vec<_> = EvtxParser::from_buffer(data)
+        .unwrap()
+        .records_json_value()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(
+        rewritten.iter().map(|r| &r.data).collect::<Vec<_>>(),
+        redacted.iter().map(|r| &r.data).collect::<Vec<_>>()
+    );
+}
+
+#[cfg(feature = "sysmon")]
+#[test]
+fn test_event_json_with_sysmon_events() {
+    let evtx_file = include_bytes!("../samples/sysmon.evtx");
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(ParserSettings::new().sysmon_events(true));
+
+    let records: Vec<_> = parser.records_json_value().map(Result::unwrap).collect();
+
+    let process_create = &records[0].data["Event"]["Sysmon"]["ProcessCreate"];
+    assert_eq!(process_create["Image"], "C:\\Windows\\SysWOW64\\PING.EXE");
+    assert_eq!(process_create["ProcessId"], 9112);
+    assert_eq!(process_create["LogonId"], 0x3e7);
+    assert_eq!(
+        process_create["Hashes"]["MD5"],
+        "A27A24553B2C36E139647F88DF738C35"
+    );
+    assert!(process_create["UtcTime"].as_str().unwrap().ends_with('Z'));
+    assert!(process_create.get("ParentUser").is_none());
+
+    let network_connect = records
+        .iter()
+        .find_map(|record| record.data["Event"]["Sysmon"].get("NetworkConnect"))
+        .unwrap();
+    assert_eq!(network_connect["DestinationPort"], 443);
+    assert_eq!(network_connect["Initiated"], true);
+
+    assert!(records
+        .iter()
+        .all(|record| record.data["Event"]["Sysmon"].is_object()
+            || record.data["Event"]["System"]["EventID"] == 16));
+}
+
+#[cfg(feature = "event_descriptions")]
+#[test]
+fn test_event_json_with_event_descriptions() {
+    let evtx_file = include_bytes!("../samples/security.evtx");
+    let mut parser = EvtxParser::from_buffer(evtx_file.to_vec())
+        .unwrap()
+        .with_configuration(ParserSettings::new().describe_events(true));
+
+    let records: Vec<_> = parser.records_json_value().map(Result::unwrap).collect();
+
+    let logon = records
+        .iter()
+        .find(|record| record.data["Event"]["System"]["EventID"] == 4624)
+        .expect("to have a logon event");
+    assert_eq!(
+        logon.data["event"]["description"],
+        "An account was successfully logged on."
+    );
+    assert!(logon.data["Event"]["EventData"].is_object());
+}
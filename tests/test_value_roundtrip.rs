@@ -0,0 +1,498 @@
+//! Property tests for `BinXmlValue` decoding.
+//!
+//! Every value type is encoded into it's binary representation using the small encoders below,
+//! and then decoded back, expecting the same value.
+//! In addition, decoding random bytes as any value type must never panic.
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Timelike, Utc};
+use encoding::all::WINDOWS_1252;
+use evtx::binxml::value_variant::{BinXmlValue, BinXmlValueType};
+use evtx::Guid;
+use evtx::Sid;
+use proptest::prelude::*;
+use std::borrow::Cow;
+use std::io::Cursor;
+
+/// Seconds between 1601-01-01 (FILETIME epoch) and 1970-01-01 (unix epoch).
+const SECONDS_TO_UNIX_EPOCH: i64 = 11_644_473_600;
+
+mod encode {
+    use super::*;
+
+    pub fn utf16(s: &str) -> Vec<u8> {
+        s.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    pub fn null_terminated_utf16(s: &str) -> Vec<u8> {
+        let mut bytes = utf16(s);
+        bytes.extend_from_slice(&[0, 0]);
+        bytes
+    }
+
+    pub fn bool(b: bool) -> Vec<u8> {
+        i32::from(b).to_le_bytes().to_vec()
+    }
+
+    pub fn guid(data1: u32, data2: u16, data3: u16, data4: [u8; 8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&data1.to_le_bytes());
+        bytes.extend_from_slice(&data2.to_le_bytes());
+        bytes.extend_from_slice(&data3.to_le_bytes());
+        bytes.extend_from_slice(&data4);
+        bytes
+    }
+
+    pub fn filetime(datetime: &DateTime<Utc>) -> Vec<u8> {
+        let ticks = (datetime.timestamp() + SECONDS_TO_UNIX_EPOCH) as u64 * 10_000_000
+            + u64::from(datetime.timestamp_subsec_nanos() / 100);
+
+        ticks.to_le_bytes().to_vec()
+    }
+
+    pub fn systime(datetime: &DateTime<Utc>) -> Vec<u8> {
+        let fields = [
+            datetime.year() as u16,
+            datetime.month() as u16,
+            datetime.weekday().num_days_from_sunday() as u16,
+            datetime.day() as u16,
+            datetime.hour() as u16,
+            datetime.minute() as u16,
+            datetime.second() as u16,
+            (datetime.timestamp_subsec_millis()) as u16,
+        ];
+
+        fields.iter().flat_map(|f| f.to_le_bytes()).collect()
+    }
+
+    pub fn sid(revision: u8, authority: [u8; 6], sub_authorities: &[u32]) -> Vec<u8> {
+        let mut bytes = vec![revision, sub_authorities.len() as u8];
+        bytes.extend_from_slice(&authority);
+        for sub_authority in sub_authorities {
+            bytes.extend_from_slice(&sub_authority.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// Decodes `bytes` as `value_type`, asserting all of the bytes were consumed.
+fn decode(value_type: BinXmlValueType, bytes: &[u8], size: Option<u16>) -> BinXmlValue<'_> {
+    let mut cursor = Cursor::new(bytes);
+    let value =
+        BinXmlValue::deserialize_value_type(&value_type, &mut cursor, None, size, WINDOWS_1252)
+            .expect("Failed to decode value");
+
+    assert_eq!(
+        cursor.position(),
+        bytes.len() as u64,
+        "Decoding {:?} did not consume all bytes",
+        value_type
+    );
+
+    value
+}
+
+fn sized(bytes: &[u8]) -> Option<u16> {
+    Some(bytes.len() as u16)
+}
+
+/// Any `f32`, including infinities and NaNs.
+fn any_f32() -> impl Strategy<Value = f32> {
+    use proptest::num::f32::*;
+    POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO | INFINITE | QUIET_NAN | SIGNALING_NAN
+}
+
+/// Any `f64`, including infinities and NaNs.
+fn any_f64() -> impl Strategy<Value = f64> {
+    use proptest::num::f64::*;
+    POSITIVE | NEGATIVE | NORMAL | SUBNORMAL | ZERO | INFINITE | QUIET_NAN | SIGNALING_NAN
+}
+
+/// Any time representable as a FILETIME (with microsecond precision) up to year 9999.
+fn filetime_datetime() -> impl Strategy<Value = DateTime<Utc>> {
+    (
+        -SECONDS_TO_UNIX_EPOCH..253_402_300_799_i64,
+        0..1_000_000_u32,
+    )
+        .prop_map(|(secs, micros)| Utc.timestamp(secs, micros * 1000))
+}
+
+/// Any time representable as a SYSTEMTIME.
+fn systime_datetime() -> impl Strategy<Value = DateTime<Utc>> {
+    (
+        1601..=9999_i32,
+        1..=12_u32,
+        1..=28_u32,
+        0..24_u32,
+        0..60_u32,
+        0..60_u32,
+        0..1000_u32,
+    )
+        .prop_map(|(year, month, day, hour, minute, second, milli)| {
+            DateTime::from_utc(
+                NaiveDate::from_ymd(year, month, day).and_hms_milli(hour, minute, second, milli),
+                Utc,
+            )
+        })
+}
+
+fn sid_parts() -> impl Strategy<Value = (u8, [u8; 6], Vec<u32>)> {
+    (
+        any::<u8>(),
+        any::<[u8; 6]>(),
+        prop::collection::vec(any::<u32>(), 0..16),
+    )
+}
+
+macro_rules! roundtrip_numbers {
+    ($name: ident, $array_name: ident, $t: ty, $strategy: expr, $value_type: ident, $array_value_type: ident) => {
+        proptest! {
+            #[test]
+            fn $name(n in $strategy) {
+                let bytes = n.to_le_bytes();
+                prop_assert_eq!(
+                    decode(BinXmlValueType::$value_type, &bytes, None),
+                    BinXmlValue::$value_type(n)
+                );
+            }
+
+            #[test]
+            fn $array_name(numbers in prop::collection::vec($strategy, 0..32)) {
+                let bytes: Vec<u8> = numbers.iter().flat_map(|n| n.to_le_bytes().to_vec()).collect();
+                prop_assert_eq!(
+                    decode(BinXmlValueType::$array_value_type, &bytes, sized(&bytes)),
+                    BinXmlValue::$array_value_type(numbers)
+                );
+            }
+        }
+    };
+}
+
+roundtrip_numbers!(
+    test_int8,
+    test_int8_array,
+    i8,
+    any::<i8>(),
+    Int8Type,
+    Int8ArrayType
+);
+roundtrip_numbers!(
+    test_uint8,
+    test_uint8_array,
+    u8,
+    any::<u8>(),
+    UInt8Type,
+    UInt8ArrayType
+);
+roundtrip_numbers!(
+    test_int16,
+    test_int16_array,
+    i16,
+    any::<i16>(),
+    Int16Type,
+    Int16ArrayType
+);
+roundtrip_numbers!(
+    test_uint16,
+    test_uint16_array,
+    u16,
+    any::<u16>(),
+    UInt16Type,
+    UInt16ArrayType
+);
+roundtrip_numbers!(
+    test_int32,
+    test_int32_array,
+    i32,
+    any::<i32>(),
+    Int32Type,
+    Int32ArrayType
+);
+roundtrip_numbers!(
+    test_uint32,
+    test_uint32_array,
+    u32,
+    any::<u32>(),
+    UInt32Type,
+    UInt32ArrayType
+);
+roundtrip_numbers!(
+    test_int64,
+    test_int64_array,
+    i64,
+    any::<i64>(),
+    Int64Type,
+    Int64ArrayType
+);
+roundtrip_numbers!(
+    test_uint64,
+    test_uint64_array,
+    u64,
+    any::<u64>(),
+    UInt64Type,
+    UInt64ArrayType
+);
+/// Same as `roundtrip_numbers`, but floats are compared bitwise, since NaNs are not equal to themselves.
+macro_rules! roundtrip_floats {
+    ($name: ident, $array_name: ident, $strategy: expr, $value_type: ident, $array_value_type: ident) => {
+        proptest! {
+            #[test]
+            fn $name(n in $strategy) {
+                let bytes = n.to_le_bytes();
+                match decode(BinXmlValueType::$value_type, &bytes, None) {
+                    BinXmlValue::$value_type(decoded) => prop_assert_eq!(decoded.to_bits(), n.to_bits()),
+                    other => prop_assert!(false, "Decoded {:?}", other),
+                }
+            }
+
+            #[test]
+            fn $array_name(numbers in prop::collection::vec($strategy, 0..32)) {
+                let bytes: Vec<u8> = numbers.iter().flat_map(|n| n.to_le_bytes().to_vec()).collect();
+                match decode(BinXmlValueType::$array_value_type, &bytes, sized(&bytes)) {
+                    BinXmlValue::$array_value_type(decoded) => prop_assert_eq!(
+                        decoded.iter().map(|n| n.to_bits()).collect::<Vec<_>>(),
+                        numbers.iter().map(|n| n.to_bits()).collect::<Vec<_>>()
+                    ),
+                    other => prop_assert!(false, "Decoded {:?}", other),
+                }
+            }
+        }
+    };
+}
+
+roundtrip_floats!(
+    test_real32,
+    test_real32_array,
+    any_f32(),
+    Real32Type,
+    Real32ArrayType
+);
+roundtrip_floats!(
+    test_real64,
+    test_real64_array,
+    any_f64(),
+    Real64Type,
+    Real64ArrayType
+);
+
+proptest! {
+    #[test]
+    fn test_null(size in prop::option::of(any::<u16>())) {
+        prop_assert_eq!(decode(BinXmlValueType::NullType, &[], size), BinXmlValue::NullType);
+    }
+
+    #[test]
+    fn test_string(s in "[^\u{0}]{0,64}") {
+        let bytes = encode::utf16(&s);
+        prop_assert_eq!(
+            decode(BinXmlValueType::StringType, &bytes, sized(&bytes)),
+            BinXmlValue::StringType(Cow::Owned(s))
+        );
+    }
+
+    #[test]
+    fn test_length_prefixed_string(s in "[^\u{0}]{0,64}") {
+        let mut bytes = (s.encode_utf16().count() as u16).to_le_bytes().to_vec();
+        bytes.extend(encode::utf16(&s));
+        prop_assert_eq!(
+            decode(BinXmlValueType::StringType, &bytes, None),
+            BinXmlValue::StringType(Cow::Owned(s))
+        );
+    }
+
+    #[test]
+    fn test_ansi_string(s in "[ -~]{0,64}") {
+        let bytes = s.as_bytes().to_vec();
+        prop_assert_eq!(
+            decode(BinXmlValueType::AnsiStringType, &bytes, sized(&bytes)),
+            BinXmlValue::AnsiStringType(Cow::Owned(s))
+        );
+    }
+
+    #[test]
+    fn test_bool(b in any::<bool>()) {
+        let bytes = encode::bool(b);
+        prop_assert_eq!(
+            decode(BinXmlValueType::BoolType, &bytes, None),
+            BinXmlValue::BoolType(b)
+        );
+    }
+
+    #[test]
+    fn test_bool_array(bools in prop::collection::vec(any::<bool>(), 0..32)) {
+        let bytes: Vec<u8> = bools.iter().flat_map(|&b| encode::bool(b)).collect();
+        prop_assert_eq!(
+            decode(BinXmlValueType::BoolArrayType, &bytes, sized(&bytes)),
+            BinXmlValue::BoolArrayType(bools)
+        );
+    }
+
+    #[test]
+    fn test_binary(data in prop::collection::vec(any::<u8>(), 0..256)) {
+        prop_assert_eq!(
+            decode(BinXmlValueType::BinaryType, &data, sized(&data)),
+            BinXmlValue::BinaryType(Cow::Borrowed(&data))
+        );
+    }
+
+    #[test]
+    fn test_guid(data1 in any::<u32>(), data2 in any::<u16>(), data3 in any::<u16>(), data4 in any::<[u8; 8]>()) {
+        let bytes = encode::guid(data1, data2, data3, data4);
+        prop_assert_eq!(
+            decode(BinXmlValueType::GuidType, &bytes, None),
+            BinXmlValue::GuidType(Guid::new(data1, data2, data3, data4))
+        );
+    }
+
+    #[test]
+    fn test_guid_array(parts in prop::collection::vec(any::<(u32, u16, u16, [u8; 8])>(), 0..16)) {
+        let bytes: Vec<u8> = parts.iter().flat_map(|&(a, b, c, d)| encode::guid(a, b, c, d)).collect();
+        let guids = parts.iter().map(|&(a, b, c, d)| Guid::new(a, b, c, d)).collect();
+        prop_assert_eq!(
+            decode(BinXmlValueType::GuidArrayType, &bytes, sized(&bytes)),
+            BinXmlValue::GuidArrayType(guids)
+        );
+    }
+
+    #[test]
+    fn test_filetime(datetime in filetime_datetime()) {
+        let bytes = encode::filetime(&datetime);
+        prop_assert_eq!(
+            decode(BinXmlValueType::FileTimeType, &bytes, None),
+            BinXmlValue::FileTimeType(datetime)
+        );
+    }
+
+    #[test]
+    fn test_filetime_array(datetimes in prop::collection::vec(filetime_datetime(), 0..16)) {
+        let bytes: Vec<u8> = datetimes.iter().flat_map(encode::filetime).collect();
+        prop_assert_eq!(
+            decode(BinXmlValueType::FileTimeArrayType, &bytes, sized(&bytes)),
+            BinXmlValue::FileTimeArrayType(datetimes)
+        );
+    }
+
+    #[test]
+    fn test_systime(datetime in systime_datetime()) {
+        let bytes = encode::systime(&datetime);
+        prop_assert_eq!(
+            decode(BinXmlValueType::SysTimeType, &bytes, None),
+            BinXmlValue::SysTimeType(datetime)
+        );
+    }
+
+    #[test]
+    fn test_systime_array(datetimes in prop::collection::vec(systime_datetime(), 0..16)) {
+        let bytes: Vec<u8> = datetimes.iter().flat_map(encode::systime).collect();
+        prop_assert_eq!(
+            decode(BinXmlValueType::SysTimeArrayType, &bytes, sized(&bytes)),
+            BinXmlValue::SysTimeArrayType(datetimes)
+        );
+    }
+
+    #[test]
+    fn test_sid((revision, authority, sub_authorities) in sid_parts()) {
+        let bytes = encode::sid(revision, authority, &sub_authorities);
+        prop_assert_eq!(
+            decode(BinXmlValueType::SidType, &bytes, None),
+            BinXmlValue::SidType(Sid::from_buffer(&bytes).unwrap())
+        );
+    }
+
+    #[test]
+    fn test_sid_array(parts in prop::collection::vec(sid_parts(), 0..8)) {
+        let encoded: Vec<Vec<u8>> = parts
+            .iter()
+            .map(|(revision, authority, sub_authorities)| encode::sid(*revision, *authority, sub_authorities))
+            .collect();
+        let sids = encoded.iter().map(|bytes| Sid::from_buffer(bytes).unwrap()).collect();
+        let bytes = encoded.concat();
+        prop_assert_eq!(
+            decode(BinXmlValueType::SidArrayType, &bytes, sized(&bytes)),
+            BinXmlValue::SidArrayType(sids)
+        );
+    }
+
+    #[test]
+    fn test_size_t_32(n in any::<u32>()) {
+        let bytes = n.to_le_bytes();
+        prop_assert_eq!(
+            decode(BinXmlValueType::SizeTType, &bytes, sized(&bytes)),
+            BinXmlValue::SizeTType(n as usize)
+        );
+    }
+
+    #[test]
+    fn test_size_t_64(n in any::<u64>()) {
+        let bytes = n.to_le_bytes();
+        prop_assert_eq!(
+            decode(BinXmlValueType::SizeTType, &bytes, None),
+            BinXmlValue::SizeTType(n as usize)
+        );
+        prop_assert_eq!(
+            decode(BinXmlValueType::SizeTType, &bytes, sized(&bytes)),
+            BinXmlValue::SizeTType(n as usize)
+        );
+    }
+
+    #[test]
+    fn test_hex_int32(n in any::<i32>()) {
+        let bytes = n.to_le_bytes();
+        prop_assert_eq!(
+            decode(BinXmlValueType::HexInt32Type, &bytes, None),
+            BinXmlValue::HexInt32Type(Cow::Owned(format!("0x{:x}", n)))
+        );
+    }
+
+    #[test]
+    fn test_hex_int64(n in any::<i64>()) {
+        let bytes = n.to_le_bytes();
+        prop_assert_eq!(
+            decode(BinXmlValueType::HexInt64Type, &bytes, None),
+            BinXmlValue::HexInt64Type(Cow::Owned(format!("0x{:x}", n)))
+        );
+    }
+
+    #[test]
+    fn test_hex_int32_array(numbers in prop::collection::vec(any::<i32>(), 0..32)) {
+        let bytes: Vec<u8> = numbers.iter().flat_map(|n| n.to_le_bytes()).collect();
+        let expected = numbers.iter().map(|n| Cow::Owned(format!("0x{:x}", n))).collect();
+        prop_assert_eq!(
+            decode(BinXmlValueType::HexInt32ArrayType, &bytes, sized(&bytes)),
+            BinXmlValue::HexInt32ArrayType(expected)
+        );
+    }
+
+    #[test]
+    fn test_hex_int64_array(numbers in prop::collection::vec(any::<i64>(), 0..32)) {
+        let bytes: Vec<u8> = numbers.iter().flat_map(|n| n.to_le_bytes()).collect();
+        let expected = numbers.iter().map(|n| Cow::Owned(format!("0x{:x}", n))).collect();
+        prop_assert_eq!(
+            decode(BinXmlValueType::HexInt64ArrayType, &bytes, sized(&bytes)),
+            BinXmlValue::HexInt64ArrayType(expected)
+        );
+    }
+
+    #[test]
+    fn test_string_array(strings in prop::collection::vec("[^\u{0}]{0,16}", 0..16)) {
+        let bytes: Vec<u8> = strings.iter().flat_map(|s| encode::null_terminated_utf16(s)).collect();
+        let expected = strings.into_iter().map(Cow::Owned).collect();
+        prop_assert_eq!(
+            decode(BinXmlValueType::StringArrayType, &bytes, sized(&bytes)),
+            BinXmlValue::StringArrayType(expected)
+        );
+    }
+
+    #[test]
+    fn test_decoding_arbitrary_bytes_does_not_panic(
+        value_type in any::<u8>(),
+        data in prop::collection::vec(any::<u8>(), 0..256),
+        size in prop::option::of(any::<u16>()),
+    ) {
+        if let Some(value_type) = BinXmlValueType::from_u8(value_type) {
+            let mut cursor = Cursor::new(data.as_slice());
+            let _ = BinXmlValue::deserialize_value_type(&value_type, &mut cursor, None, size, WINDOWS_1252);
+        }
+
+        let mut cursor = Cursor::new(data.as_slice());
+        let _ = BinXmlValue::from_binxml_stream(&mut cursor, None, size, WINDOWS_1252);
+    }
+}